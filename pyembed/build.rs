@@ -25,6 +25,7 @@ by this crate:
 
 use {
     std::env,
+    std::ffi::OsString,
     std::path::{Path, PathBuf},
 };
 
@@ -40,11 +41,11 @@ fn build_with_artifacts_in_dir(path: &Path) {
 }
 
 /// Build by calling a `pyoxidizer` executable to generate build artifacts.
-fn build_with_pyoxidizer_exe(exe: Option<String>, resolve_target: Option<&str>) {
+fn build_with_pyoxidizer_exe(exe: Option<OsString>, resolve_target: Option<&str>) {
     let pyoxidizer_exe = if let Some(path) = exe {
         path
     } else {
-        "pyoxidizer".to_string()
+        OsString::from("pyoxidizer")
     };
 
     let mut args = vec!["run-build-script", "build.rs"];
@@ -97,7 +98,7 @@ fn main() {
         };
 
         build_with_pyoxidizer_exe(
-            env::var("PYOXIDIZER_EXE").ok(),
+            env::var_os("PYOXIDIZER_EXE"),
             if let Some(target) = &target {
                 Some(target.as_ref())
             } else {
@@ -105,14 +106,12 @@ fn main() {
             },
         );
     } else if env::var("CARGO_FEATURE_BUILD_MODE_PREBUILT_ARTIFACTS").is_ok() {
-        let artifact_dir_env = env::var("PYOXIDIZER_ARTIFACT_DIR");
+        // Paths aren't necessarily valid Unicode.
+        let artifact_dir_env = env::var_os("PYOXIDIZER_ARTIFACT_DIR");
 
         let artifact_dir_path = match artifact_dir_env {
-            Ok(ref v) => PathBuf::from(v),
-            Err(_) => {
-                let out_dir = env::var("OUT_DIR").unwrap();
-                PathBuf::from(&out_dir)
-            }
+            Some(ref v) => PathBuf::from(v),
+            None => PathBuf::from(env::var_os("OUT_DIR").unwrap()),
         };
 
         println!("cargo:rerun-if-env-changed=PYOXIDIZER_ARTIFACT_DIR");
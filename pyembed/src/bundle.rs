@@ -0,0 +1,131 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Extension modules bundling the resources of an application.
+
+Libraries generated by PyOxidizer for bundle targets are extension modules
+loaded by an existing Python interpreter. They embed packed resources and
+their `PyInit_*` function calls [`init_bundle_module()`], which registers
+an `OxidizedFinder` importing these resources at the end of
+`sys.meta_path`. Once the extension is imported, modules of the application
+are importable. Modules of the interpreter, including its standard library,
+take precedence.
+
+The finder is created from an `oxidized_importer` module private to the
+extension, so bundles work regardless of any `oxidized_importer` installed
+in the interpreter.
+*/
+
+use {
+    super::importer::{OxidizedFinder, PyInit_oxidized_importer},
+    super::shared_library::library_directory,
+    cpython::exc::ImportError,
+    cpython::{ObjectProtocol, PyClone, PyErr, PyModule, PyObject, PyResult, Python, PythonObject},
+    python3_sys as pyffi,
+};
+
+static mut BUNDLE_MODULE_DEF: pyffi::PyModuleDef = pyffi::PyModuleDef {
+    m_base: pyffi::PyModuleDef_HEAD_INIT,
+    m_name: std::ptr::null(),
+    m_doc: std::ptr::null(),
+    m_size: 0,
+    m_methods: 0 as *mut _,
+    m_slots: 0 as *mut _,
+    m_traverse: None,
+    m_clear: None,
+    m_free: None,
+};
+
+/// Initialize an extension module bundling packed resources.
+///
+/// This is meant to be called by the `PyInit_*` function of the extension
+/// module. `name` is the NUL terminated name of the module. `resources` is
+/// packed resources data. `python_version` is the `X.Y` version of Python
+/// the resources were compiled for: importing the module from another
+/// version fails, as bytecode isn't portable across versions.
+///
+/// Resources with a filesystem-relative location are resolved relative to
+/// the directory containing the extension module.
+///
+/// Returns a new reference to the module or NULL with an exception set, as
+/// expected from `PyInit_*` functions.
+pub fn init_bundle_module(
+    name: &'static [u8],
+    resources: &'static [u8],
+    python_version: &str,
+) -> *mut pyffi::PyObject {
+    let py = unsafe { Python::assume_gil_acquired() };
+
+    match bundle_module_init(py, name, resources, python_version) {
+        Ok(module) => module.into_object().steal_ptr(),
+        Err(e) => {
+            e.restore(py);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+fn bundle_module_init(
+    py: Python,
+    name: &'static [u8],
+    resources: &'static [u8],
+    python_version: &str,
+) -> PyResult<PyModule> {
+    let module_name = String::from_utf8_lossy(&name[..name.len() - 1]).to_string();
+
+    let sys_module = py.import("sys")?;
+    let version_info = sys_module.get(py, "version_info")?;
+    let running_version = format!(
+        "{}.{}",
+        version_info.getattr(py, "major")?.extract::<i32>(py)?,
+        version_info.getattr(py, "minor")?.extract::<i32>(py)?
+    );
+
+    if running_version != python_version {
+        return Err(PyErr::new::<ImportError, _>(
+            py,
+            format!(
+                "{} was built for Python {}; cannot import from Python {}",
+                module_name, python_version, running_version
+            ),
+        ));
+    }
+
+    let importer_module = unsafe { PyObject::from_owned_ptr_opt(py, PyInit_oxidized_importer()) }
+        .ok_or_else(|| PyErr::fetch(py))?
+        .cast_into::<PyModule>(py)
+        .map_err(PyErr::from)?;
+
+    let origin = library_directory(init_bundle_module as *const libc::c_void);
+
+    let finder = OxidizedFinder::new_from_module_and_data(py, &importer_module, resources, origin)?;
+
+    // Modules of the interpreter, such as its standard library, are found
+    // before those of the application, so they aren't shadowed.
+    let meta_path = sys_module.get(py, "meta_path")?;
+    meta_path.call_method(py, "append", (finder.clone_ref(py),), None)?;
+
+    let path_hooks = sys_module.get(py, "path_hooks")?;
+    path_hooks.call_method(py, "append", (finder.getattr(py, "path_hook")?,), None)?;
+
+    unsafe {
+        if BUNDLE_MODULE_DEF.m_name.is_null() {
+            BUNDLE_MODULE_DEF.m_name = name.as_ptr() as *const _;
+            BUNDLE_MODULE_DEF.m_doc =
+                b"Resources of an application, importable once this module is imported\0".as_ptr()
+                    as *const _;
+        }
+    }
+
+    let module =
+        unsafe { PyObject::from_owned_ptr_opt(py, pyffi::PyModule_Create(&mut BUNDLE_MODULE_DEF)) }
+            .ok_or_else(|| PyErr::fetch(py))?
+            .cast_into::<PyModule>(py)
+            .map_err(PyErr::from)?;
+
+    module.add(py, "finder", finder)?;
+
+    Ok(module)
+}
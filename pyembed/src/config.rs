@@ -5,10 +5,13 @@
 //! Data structures for configuring a Python interpreter.
 
 use {
+    crate::remote_resources::RemoteChunkFetcher,
+    crate::startup_progress::StartupProgressCallback,
     libc::c_ulong,
     python3_sys as pyffi,
+    std::env,
     std::ffi::{CString, OsString},
-    std::path::PathBuf,
+    std::path::{Path, PathBuf},
 };
 
 /// Defines Python code to run.
@@ -41,6 +44,91 @@ pub enum TerminfoResolution {
     Static(String),
 }
 
+/// Customized messages for errors initializing the interpreter.
+///
+/// Templates can contain the following placeholders:
+///
+/// * `{error}` - The original error message.
+/// * `{exe}` - Path of the current executable.
+/// * `{exe_name}` - File name of the current executable.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StartupErrorMessages {
+    /// Template used if no template in `catalog` matches the current locale.
+    pub template: String,
+
+    /// Templates for specific locales, as `(locale, template)` pairs.
+    ///
+    /// Locales are POSIX locale names like `de` or `pt_BR`. The locale of
+    /// the process is resolved from the `LC_ALL`, `LC_MESSAGES` and `LANG`
+    /// environment variables. A template for the full locale (e.g. `pt_BR`)
+    /// is preferred over a template for its language (e.g. `pt`).
+    pub catalog: Vec<(String, String)>,
+}
+
+/// Normalize a locale name to `language[_territory]`.
+///
+/// Returns `None` for locales not denoting a language.
+fn normalize_locale(locale: &str) -> Option<String> {
+    let locale = locale
+        .split(|c| c == '.' || c == '@')
+        .next()
+        .unwrap_or("")
+        .replace('-', "_");
+
+    match locale.as_str() {
+        "" | "C" | "POSIX" => None,
+        _ => Some(locale),
+    }
+}
+
+impl StartupErrorMessages {
+    /// Obtain the template to use for a locale.
+    pub fn resolve_template(&self, locale: Option<&str>) -> &str {
+        let locale = match locale.and_then(normalize_locale) {
+            Some(locale) => locale,
+            None => return &self.template,
+        };
+        let language = locale.split('_').next().unwrap_or("").to_string();
+
+        for candidate in &[locale, language] {
+            if let Some((_, template)) = self
+                .catalog
+                .iter()
+                .find(|(l, _)| normalize_locale(l).as_ref() == Some(candidate))
+            {
+                return template;
+            }
+        }
+
+        &self.template
+    }
+
+    /// Render the message for an error.
+    pub fn render(&self, error: &str, exe: &Path, locale: Option<&str>) -> String {
+        let exe_name = exe
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // The error is substituted last so placeholders in it aren't expanded.
+        self.resolve_template(locale)
+            .replace("{exe_name}", &exe_name)
+            .replace("{exe}", &exe.display().to_string())
+            .replace("{error}", error)
+    }
+
+    /// Render the message for an error in the current process.
+    pub fn render_current(&self, error: &str) -> String {
+        let exe = env::current_exe().unwrap_or_default();
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| env::var(key).ok())
+            .find(|value| !value.is_empty());
+
+        self.render(error, &exe, locale.as_ref().map(|s| s.as_str()))
+    }
+}
+
 /// Defines an extra extension module to load.
 #[derive(Clone, Debug)]
 pub struct ExtensionModule {
@@ -169,6 +257,111 @@ pub struct PythonConfig<'a> {
     /// Which memory allocator to use for the raw domain.
     pub raw_allocator: PythonRawAllocator,
 
+    /// Bundled shared libraries to resolve from `ctypes.util.find_library()`.
+    ///
+    /// Each entry maps a library name (as passed to `find_library()`) to a
+    /// path relative to the directory of the current executable. If set,
+    /// `ctypes.util.find_library()` is patched when imported so it returns
+    /// these paths before falling back to searching the system.
+    pub ctypes_library_map: Option<Vec<(String, PathBuf)>>,
+
+    /// Module imported at the end of interpreter initialization to populate caches.
+    ///
+    /// This module is generated by PyOxidizer and seeds interpreter-level
+    /// caches, like the cache of compiled regular expressions, with data
+    /// computed at build time.
+    pub startup_cache_module: Option<String>,
+
+    /// Environment variable making the binary behave like a `python` executable.
+    ///
+    /// If this value is set and the environment variable it refers to is not
+    /// present, the variable is set in the environment of processes spawned
+    /// via `subprocess` to run `sys.executable`.
+    /// If the variable is present, the interpreter parses process arguments
+    /// like `python` does (e.g. `-m module`, `-c code` or a script path)
+    /// and runs what they specify instead of `run`. This allows applications
+    /// to spawn themselves via `sys.executable` as if it were `python`.
+    ///
+    /// In either case, `sys.executable` and `sys._base_executable` are set
+    /// to the current executable.
+    pub python_executable_env: Option<String>,
+
+    /// Environment variable holding the address of a debugpy listener to start.
+    ///
+    /// If this value is set and the environment variable it refers to is
+    /// present, the `debugpy` module is imported and listens for debugger
+    /// connections on the `[host:]port` address the variable holds, once the
+    /// interpreter is initialized and before `run` executes. The host
+    /// defaults to `127.0.0.1`.
+    pub debugpy_listen_env: Option<String>,
+
+    /// Whether the debugpy listener may listen on non-loopback addresses.
+    ///
+    /// Anyone able to connect to the listener can execute code in the
+    /// process, so addresses other than loopback addresses are refused
+    /// unless this is set.
+    pub debugpy_allow_remote_hosts: bool,
+
+    /// Customized messages for errors initializing the interpreter.
+    ///
+    /// If set, errors returned by `MainPythonInterpreter::new()` are rendered
+    /// using these templates instead of describing the failure in English.
+    pub startup_error_messages: Option<StartupErrorMessages>,
+
+    /// Function to call as interpreter startup progresses.
+    ///
+    /// This can be used to display a splash screen while the interpreter
+    /// initializes and the application starts. The application signals it
+    /// is ready via `oxidized_runtime.signal_ready()` (the `oxidized_runtime`
+    /// module is made available when this is set) or
+    /// `signal_startup_ready()`.
+    pub startup_progress_callback: Option<StartupProgressCallback>,
+
+    /// URL of the archive holding resources data stored remotely.
+    ///
+    /// Resources data can reference module bytecode stored in chunks of a
+    /// remote archive. Chunks are fetched from this URL when first needed,
+    /// verified against their SHA-256 digest and cached in a per-user cache
+    /// directory. If not set, modules whose bytecode is stored remotely
+    /// cannot be imported.
+    pub remote_resources_url: Option<String>,
+
+    /// Function used to fetch chunks of the remote resources archive.
+    ///
+    /// If not set, chunks are fetched via HTTP range requests.
+    pub remote_chunk_fetcher: Option<RemoteChunkFetcher>,
+
+    /// Ed25519 public key verifying resource files read from relative paths.
+    ///
+    /// If set, module sources, bytecode, extension modules and package
+    /// resources installed next to the binary are only used if the file
+    /// with the same path plus a `.sig` suffix holds a valid signature of
+    /// their content.
+    pub resources_verification_key: Option<[u8; 32]>,
+
+    /// Zip archive to import modules from via `zipimport`.
+    ///
+    /// The path is relative to the directory of the current executable. If
+    /// set, the archive is added to the front of `sys.path`, so modules not
+    /// found by other meta path finders are imported from it by the
+    /// filesystem importer, which must be enabled.
+    pub zipimport_archive: Option<PathBuf>,
+
+    /// Directory paths of resources installed next to the binary are relative to.
+    ///
+    /// Relative paths are relative to the directory of the current
+    /// executable, which is the default. macOS application bundles install
+    /// resources in `../Resources`.
+    pub origin: Option<PathBuf>,
+
+    /// Whether extension modules failing to load from memory are extracted to a cache.
+    ///
+    /// On Windows, extension modules embedded in the binary are loaded from
+    /// memory. If this is set, modules failing to load from memory are
+    /// extracted to a per-user cache directory and loaded from there instead
+    /// of failing to import.
+    pub extract_shared_libraries_to_cache: bool,
+
     /// How to resolve the `terminfo` database.
     pub terminfo_resolution: TerminfoResolution,
 
@@ -180,6 +373,13 @@ pub struct PythonConfig<'a> {
     /// loaded in ``sys.modules``.
     pub write_modules_directory_env: Option<String>,
 
+    /// Environment variable holding the path to write allocator statistics to.
+    ///
+    /// If this value is set and the environment variable it refers to is set,
+    /// on interpreter shutdown, we will write statistics about the raw
+    /// allocator and process memory usage to the path specified.
+    pub write_memory_stats_env: Option<String>,
+
     /// Defines what code to run by default.
     ///
     pub run: PythonRunMode,
@@ -217,8 +417,22 @@ impl<'a> Default for PythonConfig<'a> {
             sys_frozen: false,
             sys_meipass: false,
             raw_allocator: PythonRawAllocator::default(),
+            ctypes_library_map: None,
+            startup_cache_module: None,
+            python_executable_env: None,
+            debugpy_listen_env: None,
+            debugpy_allow_remote_hosts: false,
+            startup_error_messages: None,
+            startup_progress_callback: None,
+            remote_resources_url: None,
+            remote_chunk_fetcher: None,
+            resources_verification_key: None,
+            zipimport_archive: None,
+            origin: None,
+            extract_shared_libraries_to_cache: false,
             terminfo_resolution: TerminfoResolution::Dynamic,
             write_modules_directory_env: None,
+            write_memory_stats_env: None,
             run: PythonRunMode::None,
         }
     }
@@ -498,6 +712,10 @@ pub struct PythonRawAllocator {
     pub backend: MemoryAllocatorBackend,
     /// Whether memory debugging should be enabled.
     pub debug: bool,
+    /// Whether to collect allocator statistics.
+    ///
+    /// Statistics are exposed via `oxidized_runtime.memory_stats()`.
+    pub stats: bool,
 }
 
 impl PythonRawAllocator {
@@ -532,6 +750,7 @@ impl Default for PythonRawAllocator {
                 MemoryAllocatorBackend::Jemalloc
             },
             debug: false,
+            stats: false,
         }
     }
 }
@@ -612,6 +831,118 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// of setting this attribute.
     pub sys_meipass: bool,
 
+    /// Bundled shared libraries to resolve from `ctypes.util.find_library()`.
+    ///
+    /// Each entry maps a library name (as passed to `find_library()`) to a
+    /// path relative to the directory of the current executable. If set,
+    /// `ctypes.util.find_library()` is patched when imported so it returns
+    /// these paths before falling back to searching the system.
+    pub ctypes_library_map: Option<Vec<(String, PathBuf)>>,
+
+    /// Module imported at the end of interpreter initialization to populate caches.
+    ///
+    /// This module is generated by PyOxidizer and seeds interpreter-level
+    /// caches, like the cache of compiled regular expressions, with data
+    /// computed at build time.
+    pub startup_cache_module: Option<String>,
+
+    /// Environment variable making the binary behave like a `python` executable.
+    ///
+    /// If this value is set and the environment variable it refers to is not
+    /// present, the variable is set in the environment of processes spawned
+    /// via `subprocess` to run `sys.executable`.
+    /// If the variable is present, the interpreter parses process arguments
+    /// like `python` does (e.g. `-m module`, `-c code` or a script path)
+    /// and runs what they specify instead of `run`. This allows applications
+    /// to spawn themselves via `sys.executable` as if it were `python`.
+    ///
+    /// In either case, `sys.executable` and `sys._base_executable` are set
+    /// to the current executable.
+    ///
+    /// If the variable's value is the path of a virtual environment (a
+    /// directory containing a `pyvenv.cfg` file), the interpreter runs
+    /// within that environment: `sys.prefix` is the environment, its
+    /// `site-packages` directory is added to `sys.path`, the filesystem
+    /// importer is enabled and `sys.executable` is the environment's
+    /// `python` launcher.
+    pub python_executable_env: Option<String>,
+
+    /// Environment variable holding the address of a debugpy listener to start.
+    ///
+    /// If this value is set and the environment variable it refers to is
+    /// present, the `debugpy` module is imported and listens for debugger
+    /// connections on the `[host:]port` address the variable holds, once the
+    /// interpreter is initialized and before `run` executes. The host
+    /// defaults to `127.0.0.1`.
+    pub debugpy_listen_env: Option<String>,
+
+    /// Whether the debugpy listener may listen on non-loopback addresses.
+    ///
+    /// Anyone able to connect to the listener can execute code in the
+    /// process, so addresses other than loopback addresses are refused
+    /// unless this is set.
+    pub debugpy_allow_remote_hosts: bool,
+
+    /// Customized messages for errors initializing the interpreter.
+    ///
+    /// If set, errors returned by `MainPythonInterpreter::new()` are rendered
+    /// using these templates instead of describing the failure in English.
+    pub startup_error_messages: Option<StartupErrorMessages>,
+
+    /// Function to call as interpreter startup progresses.
+    ///
+    /// This can be used to display a splash screen while the interpreter
+    /// initializes and the application starts. The application signals it
+    /// is ready via `oxidized_runtime.signal_ready()` (the `oxidized_runtime`
+    /// module is made available when this is set) or
+    /// `signal_startup_ready()`.
+    pub startup_progress_callback: Option<StartupProgressCallback>,
+
+    /// URL of the archive holding resources data stored remotely.
+    ///
+    /// Resources data can reference module bytecode stored in chunks of a
+    /// remote archive. Chunks are fetched from this URL when first needed,
+    /// verified against their SHA-256 digest and cached in a per-user cache
+    /// directory. If not set, modules whose bytecode is stored remotely
+    /// cannot be imported.
+    pub remote_resources_url: Option<String>,
+
+    /// Function used to fetch chunks of the remote resources archive.
+    ///
+    /// If not set, chunks are fetched via HTTP range requests.
+    pub remote_chunk_fetcher: Option<RemoteChunkFetcher>,
+
+    /// Ed25519 public key verifying resource files read from relative paths.
+    ///
+    /// If set, module sources, bytecode, extension modules and package
+    /// resources installed next to the binary are only used if the file
+    /// with the same path plus a `.sig` suffix holds a valid signature of
+    /// their content.
+    pub resources_verification_key: Option<[u8; 32]>,
+
+    /// Zip archive to import modules from via `zipimport`.
+    ///
+    /// The path is relative to the directory of the current executable. If
+    /// set, the archive is added to the front of `sys.path`, so modules not
+    /// found by other meta path finders are imported from it by the
+    /// filesystem importer, which must be enabled.
+    pub zipimport_archive: Option<PathBuf>,
+
+    /// Directory paths of resources installed next to the binary are relative to.
+    ///
+    /// Defaults to the directory of the current executable, which relative
+    /// paths are relative to. Libraries loaded by other programs, such as
+    /// plugins, set it to their own directory.
+    pub origin: Option<PathBuf>,
+
+    /// Whether extension modules failing to load from memory are extracted to a cache.
+    ///
+    /// On Windows, extension modules embedded in the binary are loaded from
+    /// memory. If this is set, modules failing to load from memory are
+    /// extracted to a per-user cache directory and loaded from there instead
+    /// of failing to import.
+    pub extract_shared_libraries_to_cache: bool,
+
     /// How to resolve the `terminfo` database.
     pub terminfo_resolution: TerminfoResolution,
 
@@ -623,6 +954,13 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// loaded in ``sys.modules``.
     pub write_modules_directory_env: Option<String>,
 
+    /// Environment variable holding the path to write allocator statistics to.
+    ///
+    /// If this value is set and the environment variable it refers to is set,
+    /// on interpreter shutdown, we will write statistics about the raw
+    /// allocator and process memory usage to the path specified.
+    pub write_memory_stats_env: Option<String>,
+
     /// Defines what code to run by default.
     ///
     pub run: PythonRunMode,
@@ -643,13 +981,169 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             argvb: false,
             sys_frozen: false,
             sys_meipass: false,
+            ctypes_library_map: None,
+            startup_cache_module: None,
+            python_executable_env: None,
+            debugpy_listen_env: None,
+            debugpy_allow_remote_hosts: false,
+            startup_error_messages: None,
+            startup_progress_callback: None,
+            remote_resources_url: None,
+            remote_chunk_fetcher: None,
+            resources_verification_key: None,
+            zipimport_archive: None,
+            origin: None,
+            extract_shared_libraries_to_cache: false,
             terminfo_resolution: TerminfoResolution::Dynamic,
             write_modules_directory_env: None,
+            write_memory_stats_env: None,
             run: PythonRunMode::Repl,
         }
     }
 }
 
+/// Find the `site-packages` directory of the virtual environment at `venv`.
+fn find_venv_site_packages(venv: &Path) -> Option<PathBuf> {
+    if cfg!(windows) {
+        let path = venv.join("Lib").join("site-packages");
+        return if path.is_dir() { Some(path) } else { None };
+    }
+
+    let mut entries = std::fs::read_dir(venv.join("lib"))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+        .map(|entry| entry.path().join("site-packages"))
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    entries.pop()
+}
+
+impl<'a> OxidizedPythonInterpreterConfig<'a> {
+    /// Apply `python_executable_env` to this configuration.
+    ///
+    /// `exe` is the path of the current executable.
+    pub(crate) fn apply_python_executable_env(&mut self, exe: &Path) {
+        let key = match &self.python_executable_env {
+            Some(key) => key.clone(),
+            None => return,
+        };
+
+        if let Some(value) = env::var_os(&key) {
+            // Let Python parse arguments and run what they request via
+            // Py_RunMain(), like `python` would.
+            self.interpreter_config.parse_argv = Some(true);
+            self.interpreter_config.argv = Some(env::args_os().collect());
+            self.interpreter_config.run_command = None;
+            self.interpreter_config.run_filename = None;
+            self.interpreter_config.run_module = None;
+            self.run = PythonRunMode::Repl;
+
+            let venv = PathBuf::from(value);
+            if venv.join("pyvenv.cfg").is_file() {
+                self.apply_virtualenv(&venv);
+            }
+        }
+
+        if self.interpreter_config.executable.is_none() {
+            self.interpreter_config.executable = Some(exe.to_path_buf());
+        }
+        if self.interpreter_config.base_executable.is_none() {
+            self.interpreter_config.base_executable = Some(exe.to_path_buf());
+        }
+    }
+
+    /// Configure the interpreter to run within the virtual environment at `venv`.
+    fn apply_virtualenv(&mut self, venv: &Path) {
+        self.interpreter_config.prefix = Some(venv.to_path_buf());
+        self.interpreter_config.exec_prefix = Some(venv.to_path_buf());
+        self.interpreter_config.executable = Some(if cfg!(windows) {
+            venv.join("Scripts").join("python.exe")
+        } else {
+            venv.join("bin").join("python")
+        });
+
+        if let Some(site_packages) = find_venv_site_packages(venv) {
+            let mut paths = vec![site_packages];
+            if let Some(existing) = &self.interpreter_config.python_path_env {
+                paths.extend(env::split_paths(existing));
+            }
+
+            if let Ok(value) = env::join_paths(paths) {
+                self.interpreter_config.python_path_env = Some(value.to_string_lossy().to_string());
+            }
+        }
+
+        // Packages installed into the environment are imported from the
+        // filesystem.
+        self.filesystem_importer = true;
+    }
+
+    /// Create an instance whose interpreter initialization is fully deterministic.
+    ///
+    /// This is meant for fuzz harnesses and other contexts that need
+    /// reproducible interpreter behavior across runs and machines:
+    ///
+    /// * The hash seed is fixed to `0`, disabling hash randomization.
+    /// * Environment variables are ignored and the locale isn't configured
+    ///   from the environment. UTF-8 mode is enabled.
+    /// * `sys.path` is empty and the filesystem importer is disabled. All
+    ///   modules are imported from `packed_resources`.
+    /// * `sys.argv` is `['']` regardless of process arguments.
+    /// * `site` isn't imported, bytecode isn't written and signal handlers
+    ///   aren't installed.
+    /// * Nothing is run by `MainPythonInterpreter.run_as_main()`.
+    pub fn new_deterministic(packed_resources: &'a [u8]) -> Self {
+        Self {
+            interpreter_config: PythonInterpreterConfig {
+                profile: PythonInterpreterProfile::Isolated,
+                configure_locale: Some(false),
+                parse_argv: Some(false),
+                use_environment: Some(false),
+                utf8_mode: Some(true),
+                argv: Some(vec![OsString::new()]),
+                fault_handler: Some(false),
+                hash_seed: Some(0),
+                import_time: Some(false),
+                install_signal_handlers: Some(false),
+                module_search_paths: Some(vec![]),
+                pathconfig_warnings: Some(false),
+                site_import: Some(false),
+                user_site_directory: Some(false),
+                write_bytecode: Some(false),
+                ..PythonInterpreterConfig::default()
+            },
+            raw_allocator: None,
+            oxidized_importer: true,
+            filesystem_importer: false,
+            packed_resources: Some(packed_resources),
+            extra_extension_modules: None,
+            argvb: false,
+            sys_frozen: false,
+            sys_meipass: false,
+            ctypes_library_map: None,
+            startup_cache_module: None,
+            python_executable_env: None,
+            debugpy_listen_env: None,
+            debugpy_allow_remote_hosts: false,
+            startup_error_messages: None,
+            startup_progress_callback: None,
+            remote_resources_url: None,
+            remote_chunk_fetcher: None,
+            resources_verification_key: None,
+            zipimport_archive: None,
+            origin: None,
+            extract_shared_libraries_to_cache: false,
+            terminfo_resolution: TerminfoResolution::None,
+            write_modules_directory_env: None,
+            write_memory_stats_env: None,
+            run: PythonRunMode::None,
+        }
+    }
+}
+
 impl<'a> From<PythonConfig<'a>> for OxidizedPythonInterpreterConfig<'a> {
     fn from(config: PythonConfig<'a>) -> Self {
         Self {
@@ -700,8 +1194,22 @@ impl<'a> From<PythonConfig<'a>> for OxidizedPythonInterpreterConfig<'a> {
             argvb: config.argvb,
             sys_frozen: config.sys_frozen,
             sys_meipass: config.sys_meipass,
+            ctypes_library_map: config.ctypes_library_map,
+            startup_cache_module: config.startup_cache_module,
+            python_executable_env: config.python_executable_env,
+            debugpy_listen_env: config.debugpy_listen_env,
+            debugpy_allow_remote_hosts: config.debugpy_allow_remote_hosts,
+            startup_error_messages: config.startup_error_messages,
+            startup_progress_callback: config.startup_progress_callback,
+            remote_resources_url: config.remote_resources_url,
+            remote_chunk_fetcher: config.remote_chunk_fetcher,
+            resources_verification_key: config.resources_verification_key,
+            zipimport_archive: config.zipimport_archive,
+            origin: config.origin,
+            extract_shared_libraries_to_cache: config.extract_shared_libraries_to_cache,
             terminfo_resolution: config.terminfo_resolution,
             write_modules_directory_env: config.write_modules_directory_env,
+            write_memory_stats_env: config.write_memory_stats_env,
             run: config.run,
         }
     }
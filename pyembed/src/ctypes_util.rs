@@ -0,0 +1,59 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Redirection of `ctypes.util.find_library()` to bundled shared libraries.
+
+`ctypes.util.find_library()` searches system locations, so it won't find
+shared libraries installed next to a built binary. When a map of bundled
+libraries is configured, we wrap `find_library()` when `ctypes.util` is
+imported so the map is consulted before falling back to the system search.
+*/
+
+use {
+    crate::import_hook::run_with_post_import_hook,
+    cpython::{PyDict, PyResult, Python},
+    std::path::{Path, PathBuf},
+};
+
+/// Python code installing the redirection.
+///
+/// The `LIBRARIES` variable must be defined before this code runs.
+const REDIRECT_CODE: &str = r#"
+def _redirect_find_library(module):
+    find_library = module.find_library
+
+    def wrapped(name):
+        path = LIBRARIES.get(name)
+        if path is not None:
+            return path
+
+        return find_library(name)
+
+    wrapped.__doc__ = find_library.__doc__
+    module.find_library = wrapped
+
+
+install_post_import_hook("ctypes.util", _redirect_find_library)
+"#;
+
+/// Redirect `ctypes.util.find_library()` to bundled shared libraries.
+///
+/// `libraries` maps library names (as passed to `find_library()`) to paths
+/// relative to `origin`.
+pub fn install_find_library_redirect(
+    py: Python,
+    origin: &Path,
+    libraries: &[(String, PathBuf)],
+) -> PyResult<()> {
+    let mapping = PyDict::new(py);
+    for (name, path) in libraries {
+        mapping.set_item(py, name, origin.join(path).display().to_string())?;
+    }
+
+    let globals = PyDict::new(py);
+    globals.set_item(py, "LIBRARIES", mapping)?;
+
+    run_with_post_import_hook(py, REDIRECT_CODE, &globals)
+}
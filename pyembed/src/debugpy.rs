@@ -0,0 +1,113 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Listening for debugger connections with `debugpy`.
+
+Binaries built with a debugpy listener bundle the `debugpy` package. When
+the configured environment variable is present, we start listening for
+debugger connections once the interpreter is initialized, before any
+application code runs. IDE debuggers (e.g. VS Code) can then attach to the
+process with a remote attach configuration.
+
+Anyone able to connect to the listener can run code in the process, so
+only loopback addresses are listened on unless the binary was built
+allowing other hosts.
+
+debugpy imports its vendored `pydevd` from a directory it adds to
+`sys.path`. Rather than requiring the filesystem importer for all of
+`sys.path`, we append a meta path finder importing modules from that
+directory only.
+*/
+
+use cpython::{PyDict, PyResult, Python};
+
+/// Host listened on when the listen address doesn't define one.
+pub const DEFAULT_LISTEN_HOST: &str = "127.0.0.1";
+
+/// Python code installing the meta path finder of `pydevd` modules.
+const PYDEVD_FINDER_CODE: &str = r#"
+import importlib.machinery
+import os
+import sys
+
+import debugpy
+
+_PYDEVD_ROOT = os.path.join(os.path.dirname(debugpy.__file__), "_vendored", "pydevd")
+
+
+class _PydevdFinder:
+    @classmethod
+    def find_spec(cls, fullname, path=None, target=None):
+        if path is None:
+            path = [_PYDEVD_ROOT]
+        else:
+            path = [
+                entry
+                for entry in path
+                if entry == _PYDEVD_ROOT or entry.startswith(_PYDEVD_ROOT + os.sep)
+            ]
+
+        if not path:
+            return None
+
+        return importlib.machinery.PathFinder.find_spec(fullname, path, target)
+
+
+sys.meta_path.append(_PydevdFinder)
+"#;
+
+/// Whether a host only accepts connections from the local machine.
+pub fn is_loopback_host(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+/// Parse a `[host:]port` listen address.
+///
+/// Hosts other than loopback addresses are refused unless
+/// `allow_remote_hosts` is set.
+pub fn parse_listen_address(
+    value: &str,
+    allow_remote_hosts: bool,
+) -> Result<(String, u16), String> {
+    let (host, port) = match value.rfind(':') {
+        Some(index) => (&value[0..index], &value[index + 1..]),
+        None => (DEFAULT_LISTEN_HOST, value),
+    };
+
+    if host.is_empty() {
+        return Err(format!("missing host in debugpy listen address: {}", value));
+    }
+
+    let port = port
+        .parse::<u16>()
+        .or_else(|_| Err(format!("invalid port in debugpy listen address: {}", value)))?;
+
+    if !allow_remote_hosts && !is_loopback_host(host) {
+        return Err(format!(
+            "listening on non-loopback debugpy address {} isn't allowed",
+            value
+        ));
+    }
+
+    Ok((host.to_string(), port))
+}
+
+/// Start listening for debugger connections on `host` and `port`.
+///
+/// Returns immediately: debuggers can attach while the application runs.
+pub fn start_listener(py: Python, host: &str, port: u16) -> PyResult<()> {
+    let globals = PyDict::new(py);
+    globals.set_item(py, "__builtins__", py.import("builtins")?)?;
+    py.run(PYDEVD_FINDER_CODE, Some(&globals), None)?;
+
+    let debugpy = py.import("debugpy")?;
+    debugpy.call(py, "listen", ((host, port),), None)?;
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Extraction of embedded extension modules to a filesystem cache.
+
+Not every platform can load shared libraries from memory. When an extension
+module's shared library is embedded in the resources data and cannot be
+loaded from memory, we write it to a per-user cache directory and have the
+operating system load it from there.
+
+Files in the cache are content-addressed: the directory an extension module
+is written to is derived from the SHA-256 of the library. Existing files are
+compared against the embedded data before use and are rewritten if they
+differ, so a stale or tampered file is never loaded.
+
+On UNIX, the cache directory is only used if it is owned by the current user
+and not accessible by other users, and directories and files are created
+with `0700` permissions. New files are created exclusively, so an existing
+file or symlink planted in their place is never written through.
+*/
+
+use {
+    sha2::{Digest, Sha256},
+    std::fs,
+    std::path::{Path, PathBuf},
+};
+
+/// Environment variable that can be used to override the cache directory.
+pub const EXTENSION_CACHE_DIR_ENV: &str = "PYOXIDIZER_EXTENSION_CACHE_DIR";
+
+#[cfg(windows)]
+const EXTENSION_MODULE_SUFFIX: &str = ".pyd";
+
+#[cfg(unix)]
+const EXTENSION_MODULE_SUFFIX: &str = ".so";
+
+/// Resolve the per-user directory holding extracted extension modules.
+pub fn extension_cache_dir() -> PathBuf {
+    if let Some(path) = std::env::var_os(EXTENSION_CACHE_DIR_ENV) {
+        return PathBuf::from(path);
+    }
+
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+
+    match base {
+        Some(base) => base.join("pyoxidizer").join("extensions"),
+        None => std::env::temp_dir().join(temp_cache_dir_name()),
+    }
+}
+
+/// Name of the cache directory in the temporary directory shared by all users.
+#[cfg(unix)]
+fn temp_cache_dir_name() -> String {
+    format!("pyoxidizer-extensions-{}", unsafe { libc::getuid() })
+}
+
+#[cfg(windows)]
+fn temp_cache_dir_name() -> String {
+    "pyoxidizer-extensions".to_string()
+}
+
+/// Compute the content address of library data.
+fn content_key(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Create a directory only accessible by the current user, if missing.
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+        .or_else(|e| Err(format!("unable to create {}: {}", path.display(), e)))?;
+
+    // The directory may have existed before. Don't use it if other users
+    // could have written to it.
+    let metadata = fs::symlink_metadata(path)
+        .or_else(|e| Err(format!("unable to stat {}: {}", path.display(), e)))?;
+
+    if !metadata.is_dir()
+        || metadata.uid() != unsafe { libc::getuid() }
+        || metadata.mode() & 0o077 != 0
+    {
+        return Err(format!(
+            "{} is not a directory private to the current user",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn create_private_dir(path: &Path) -> Result<(), String> {
+    fs::create_dir_all(path).or_else(|e| Err(format!("unable to create {}: {}", path.display(), e)))
+}
+
+/// Write a new file, failing if a file already exists at its path.
+fn write_new_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o700);
+    }
+
+    options.open(path)?.write_all(data)
+}
+
+/// Whether a file exists and has exactly the given content.
+fn file_has_content(path: &Path, data: &[u8]) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() == data.len() as u64 => match fs::read(path) {
+            Ok(existing) => existing == data,
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// Ensure an extension module's shared library is present in a cache directory.
+///
+/// Returns the path of the verified library file.
+pub fn extract_extension_module_to(
+    cache_dir: &Path,
+    name: &str,
+    data: &[u8],
+) -> Result<PathBuf, String> {
+    create_private_dir(cache_dir)?;
+
+    let dir = cache_dir.join(content_key(data));
+    create_private_dir(&dir)?;
+
+    let path = dir.join(format!("{}{}", name, EXTENSION_MODULE_SUFFIX));
+
+    if file_has_content(&path, data) {
+        return Ok(path);
+    }
+
+    // Write to a temporary file and rename so concurrent processes never
+    // observe a partially written library.
+    let temp_path = dir.join(format!("{}.{}.tmp", name, uuid::Uuid::new_v4()));
+    write_new_file(&temp_path, data)
+        .or_else(|e| Err(format!("unable to write {}: {}", temp_path.display(), e)))?;
+
+    if let Err(e) = fs::rename(&temp_path, &path) {
+        let _ = fs::remove_file(&temp_path);
+
+        // Another process may have won the race. Accept its file if it is valid.
+        if !file_has_content(&path, data) {
+            return Err(format!("unable to write {}: {}", path.display(), e));
+        }
+    }
+
+    if file_has_content(&path, data) {
+        Ok(path)
+    } else {
+        Err(format!("content of {} does not verify", path.display()))
+    }
+}
+
+/// Ensure an extension module's shared library is present in the per-user cache.
+pub fn extract_extension_module(name: &str, data: &[u8]) -> Result<PathBuf, String> {
+    extract_extension_module_to(&extension_cache_dir(), name, data)
+}
@@ -0,0 +1,77 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Support for fuzzing Python code in an embedded interpreter.
+
+Fuzz harnesses (e.g. `cargo fuzz` / libFuzzer) invoke a function with
+arbitrary input many times within a single process. [`PythonFuzzTarget`]
+initializes an embedded interpreter once and then feeds each input to a
+Python callable.
+
+For reproducible behavior, construct the interpreter configuration via
+`OxidizedPythonInterpreterConfig::new_deterministic()`.
+*/
+
+use {
+    super::config::OxidizedPythonInterpreterConfig,
+    super::interpreter::{MainPythonInterpreter, NewInterpreterError},
+    cpython::{ObjectProtocol, PyBytes, PyObject, PyResult},
+};
+
+/// A Python callable receiving fuzz inputs.
+pub struct PythonFuzzTarget<'python, 'interpreter, 'resources> {
+    interpreter: MainPythonInterpreter<'python, 'interpreter, 'resources>,
+    callable: PyObject,
+}
+
+impl<'python, 'interpreter, 'resources> PythonFuzzTarget<'python, 'interpreter, 'resources> {
+    /// Initialize an interpreter and resolve the callable to feed inputs to.
+    ///
+    /// `module` and `function` name a Python callable accepting a single
+    /// `bytes` argument.
+    pub fn new(
+        config: OxidizedPythonInterpreterConfig<'resources>,
+        module: &str,
+        function: &str,
+    ) -> Result<Self, NewInterpreterError> {
+        let mut interpreter = MainPythonInterpreter::new(config)?;
+
+        let py = interpreter.acquire_gil()?;
+
+        let callable = py
+            .import(module)
+            .and_then(|m| m.get(py, function))
+            .or_else(|err| {
+                Err(NewInterpreterError::new_from_pyerr(
+                    py,
+                    err,
+                    "resolving fuzz target",
+                ))
+            })?;
+
+        Ok(Self {
+            interpreter,
+            callable,
+        })
+    }
+
+    /// Obtain the interpreter inputs are evaluated in.
+    pub fn interpreter(&mut self) -> &mut MainPythonInterpreter<'python, 'interpreter, 'resources> {
+        &mut self.interpreter
+    }
+
+    /// Call the Python callable with an input.
+    ///
+    /// Returns the value returned by the callable or the exception it raised.
+    /// Whether an exception constitutes a crash is up to the caller.
+    pub fn run_input(&mut self, data: &[u8]) -> PyResult<PyObject> {
+        let py = self
+            .interpreter
+            .acquire_gil()
+            .expect("interpreter should be initialized");
+
+        self.callable.call(py, (PyBytes::new(py, data),), None)
+    }
+}
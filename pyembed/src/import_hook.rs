@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Hooks modifying standard library modules when they are imported.
+
+Some features patch a standard library module, e.g. to wrap one of its
+functions. Importing the module during interpreter initialization would
+slow down startup of interpreters not using it. Instead we install a meta
+path finder that wraps the loader of the module and calls a hook with the
+module once it is executed. The finder removes itself on first use.
+*/
+
+use cpython::{PyDict, PyResult, Python};
+
+/// Python code defining `install_post_import_hook(fullname, hook)`.
+const POST_IMPORT_HOOK_CODE: &str = r#"
+import sys
+
+
+class _PostImportLoader:
+    def __init__(self, loader, hook):
+        self._loader = loader
+        self._hook = hook
+
+    def __getattr__(self, name):
+        return getattr(self._loader, name)
+
+    def create_module(self, spec):
+        return self._loader.create_module(spec)
+
+    def exec_module(self, module):
+        self._loader.exec_module(module)
+        self._hook(module)
+
+
+class _PostImportFinder:
+    def __init__(self, fullname, hook):
+        self._fullname = fullname
+        self._hook = hook
+
+    def find_spec(self, fullname, path=None, target=None):
+        if fullname != self._fullname:
+            return None
+
+        sys.meta_path.remove(self)
+
+        for finder in sys.meta_path:
+            find_spec = getattr(finder, "find_spec", None)
+            if find_spec is None:
+                continue
+
+            spec = find_spec(fullname, path, target)
+            if spec is not None:
+                if spec.loader is not None:
+                    spec.loader = _PostImportLoader(spec.loader, self._hook)
+
+                return spec
+
+        return None
+
+
+def install_post_import_hook(fullname, hook):
+    sys.meta_path.insert(0, _PostImportFinder(fullname, hook))
+"#;
+
+/// Run Python code that can call `install_post_import_hook(fullname, hook)`.
+///
+/// `hook` is called with module `fullname` after it is first imported.
+pub fn run_with_post_import_hook(py: Python, code: &str, globals: &PyDict) -> PyResult<()> {
+    globals.set_item(py, "__builtins__", py.import("builtins")?)?;
+
+    py.run(POST_IMPORT_HOOK_CODE, Some(globals), None)?;
+    py.run(code, Some(globals), None)
+}
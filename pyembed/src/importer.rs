@@ -12,20 +12,23 @@ for importing Python modules from memory.
 #[cfg(not(library_mode = "extension"))]
 use cpython::NoArgs;
 use {
-    super::conversion::pyobject_to_pathbuf,
+    super::conversion::{path_to_pyobject, pyobject_to_pathbuf},
+    super::extension_cache::extract_extension_module,
     super::python_resources::{
-        pyobject_to_resource, resource_to_pyobject, OptimizeLevel, OxidizedResource,
-        PythonResourcesState,
+        pyobject_to_resource, resource_to_pyobject, ImportablePythonModule, OptimizeLevel,
+        OxidizedResource, PythonResourcesState,
     },
     super::resource_scanning::find_resources_in_path,
     cpython::buffer::PyBuffer,
     cpython::exc::{FileNotFoundError, IOError, ImportError, ValueError},
     cpython::{
-        py_class, py_fn, ObjectProtocol, PyBytes, PyCapsule, PyClone, PyDict, PyErr, PyList,
-        PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
+        py_class, py_class_prop_getter, py_fn, ObjectProtocol, PyBytes, PyCapsule, PyClone, PyDict,
+        PyErr, PyList, PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject,
+        ToPyObject,
     },
     python3_sys as pyffi,
     python_packed_resources::data::ResourceFlavor,
+    std::path::PathBuf,
     std::sync::Arc,
 };
 #[cfg(windows)]
@@ -92,7 +95,13 @@ fn extension_module_shared_library_create_module(
 
     let module = unsafe { load_library_memory(resources_state, library_data) };
 
+    // Loading from memory doesn't work for every library. Fall back to
+    // loading the library from the filesystem cache if enabled.
     if module.is_null() {
+        if resources_state.extract_shared_libraries_to_cache {
+            return extension_module_extracted_create_module(py, spec, name, library_data);
+        }
+
         return Err(PyErr::new::<ImportError, _>(
             py,
             ("unable to load extension module library from memory", name),
@@ -110,17 +119,48 @@ fn extension_module_shared_library_create_module(
     })
 }
 
+/// Non-Windows platforms can't load shared libraries from memory.
+///
+/// Library data is extracted to a filesystem cache and loaded from there.
 #[cfg(unix)]
 fn extension_module_shared_library_create_module(
     _resources_state: &PythonResourcesState<u8>,
-    _py: Python,
+    py: Python,
     _sys_modules: PyObject,
-    _spec: &PyObject,
+    spec: &PyObject,
     _name_py: PyObject,
-    _name: &str,
-    _library_data: &[u8],
+    name: &str,
+    library_data: &[u8],
 ) -> PyResult<PyObject> {
-    panic!("should only be called on Windows");
+    extension_module_extracted_create_module(py, spec, name, library_data)
+}
+
+/// Create an extension module by extracting its shared library to a filesystem cache.
+///
+/// The library is written to a content-addressed location in a per-user
+/// cache directory (if not already present and valid) and the module is
+/// created via `imp.create_dynamic()` with the spec's origin pointing at the
+/// extracted file.
+fn extension_module_extracted_create_module(
+    py: Python,
+    spec: &PyObject,
+    name: &str,
+    library_data: &[u8],
+) -> PyResult<PyObject> {
+    let path = extract_extension_module(name, library_data).or_else(|msg| {
+        Err(PyErr::new::<ImportError, _>(
+            py,
+            (
+                format!("unable to extract extension module library: {}", msg),
+                name,
+            ),
+        ))
+    })?;
+
+    spec.setattr(py, "origin", path_to_pyobject(py, &path)?)?;
+
+    let imp_module = py.import("_imp")?;
+    imp_module.call(py, "create_dynamic", (spec,), None)
 }
 
 /// Reimplementation of `_PyImport_LoadDynamicModuleWithSpec()`.
@@ -274,7 +314,7 @@ pub(crate) struct ImporterState {
     /// `builtins.exec` function.
     exec_fn: PyObject,
     /// Bytecode optimization level currently in effect.
-    optimize_level: OptimizeLevel,
+    pub optimize_level: OptimizeLevel,
     /// Holds state about importable resources.
     ///
     /// This field is a PyCapsule and is a glorified wrapper around
@@ -477,7 +517,7 @@ impl Drop for ImporterState {
 // Because macro expansion confuses IDE type hinting and rustfmt, most
 // methods call into non-macro implemented methods named <method>_impl which
 // are defined below in separate `impl {}` blocks.
-py_class!(class OxidizedFinder |py| {
+py_class!(pub class OxidizedFinder |py| {
     data state: Arc<Box<ImporterState>>;
 
     // Start of importlib.abc.MetaPathFinder interface.
@@ -566,6 +606,15 @@ py_class!(class OxidizedFinder |py| {
     def serialize_indexed_resources(&self, ignore_builtin: bool = true, ignore_frozen: bool = true) -> PyResult<PyObject> {
         self.serialize_indexed_resources_impl(py, ignore_builtin, ignore_frozen)
     }
+
+    @property def index(&self) -> PyResult<PyObject> {
+        self.index_impl(py)
+    }
+
+    // Callable for sys.path_hooks.
+    def path_hook(&self, path: PyObject) -> PyResult<PyObject> {
+        self.path_hook_impl(py, path)
+    }
 });
 
 // importlib.abc.MetaPathFinder interface.
@@ -669,6 +718,39 @@ impl OxidizedFinder {
                         library_data,
                     )
                 } else {
+                    let resources_state = state.get_resources_state();
+
+                    // The shared library is loaded by the operating system from a
+                    // path, so the file could be swapped after we verify it. Instead,
+                    // load the verified bytes from a private copy in the extension
+                    // module cache.
+                    if let (Some(verifier), Some(path)) = (
+                        &resources_state.resource_verifier,
+                        &entry.relative_path_extension_module_shared_library,
+                    ) {
+                        let library_data =
+                            verifier.read(&resources_state.origin, path).or_else(|e| {
+                                Err(PyErr::new::<ImportError, _>(
+                                    py,
+                                    (
+                                        format!(
+                                            "error verifying extension module {}: {}",
+                                            resources_state.origin.join(path).display(),
+                                            e
+                                        ),
+                                        key.clone(),
+                                    ),
+                                ))
+                            })?;
+
+                        return extension_module_extracted_create_module(
+                            py,
+                            spec,
+                            &key,
+                            &library_data,
+                        );
+                    }
+
                     // Call `imp.create_dynamic()` for dynamic extension modules.
                     let create_dynamic =
                         state.imp_module.as_object().getattr(py, "create_dynamic")?;
@@ -706,6 +788,7 @@ impl OxidizedFinder {
             &state.io_module,
         )? {
             let code = state.marshal_loads.call(py, (bytecode,), None)?;
+            fix_code_filename(py, state, &entry, &code)?;
             let dict = module.getattr(py, "__dict__")?;
 
             state
@@ -768,7 +851,10 @@ impl OxidizedFinder {
             &state.decode_source,
             &state.io_module,
         )? {
-            state.marshal_loads.call(py, (bytecode,), None)
+            let code = state.marshal_loads.call(py, (bytecode,), None)?;
+            fix_code_filename(py, state, &module, &code)?;
+
+            Ok(code)
         } else if module.flavor == &ResourceFlavor::FrozenModule {
             state
                 .imp_module
@@ -881,7 +967,7 @@ impl OxidizedFinder {
             (None, None)
         };
 
-        super::package_metadata::find_distributions(py, state.clone(), path, name)
+        super::package_metadata::find_distributions(py, state.clone(), name, path)
     }
 }
 
@@ -912,6 +998,53 @@ impl OxidizedFinder {
     }
 }
 
+impl OxidizedFinder {
+    /// Construct an instance from a module and packed resources data.
+    ///
+    /// Unlike `OxidizedFinder()`, the finder isn't bound to the
+    /// `oxidized_importer` module importable by the interpreter, which may
+    /// be provided by another library.
+    pub(crate) fn new_from_module_and_data(
+        py: Python,
+        m: &PyModule,
+        resources_data: &'static [u8],
+        origin: Option<PathBuf>,
+    ) -> PyResult<OxidizedFinder> {
+        let bootstrap_module = py.import("_frozen_importlib")?;
+
+        let mut resources_state = Box::new(
+            PythonResourcesState::new_from_env()
+                .or_else(|err| Err(PyErr::new::<ValueError, _>(py, err)))?,
+        );
+
+        if let Some(origin) = origin {
+            resources_state.origin = origin;
+        }
+
+        resources_state
+            .load(Some(resources_data))
+            .or_else(|err| Err(PyErr::new::<ValueError, _>(py, err)))?;
+
+        let importer = OxidizedFinder::create_instance(
+            py,
+            Arc::new(Box::new(ImporterState::new(
+                py,
+                &m,
+                &bootstrap_module,
+                &resources_state,
+                true,
+                None,
+                None,
+            )?)),
+        )?;
+
+        // Like in `OxidizedFinder()`, the importer state owns resources_state.
+        Box::leak(resources_state);
+
+        Ok(importer)
+    }
+}
+
 /// OxidizedFinder.__new__(resources_data=None)
 fn oxidized_finder_new(
     py: Python,
@@ -1090,6 +1223,19 @@ impl OxidizedFinder {
 
         Ok(PyBytes::new(py, &data).into_object())
     }
+
+    fn index_impl(&self, py: Python) -> PyResult<PyObject> {
+        super::resource_index::resource_index(py, self.state(py).clone())
+    }
+
+    fn path_hook_impl(&self, py: Python, path: PyObject) -> PyResult<PyObject> {
+        super::path_entry_finder::path_entry_finder(
+            py,
+            self.as_object().clone_ref(py),
+            self.state(py).clone(),
+            path,
+        )
+    }
 }
 
 // Implements in-memory reading of resource data.
@@ -1315,6 +1461,26 @@ fn get_module_state<'a>(py: Python, m: &'a PyModule) -> Result<&'a mut ModuleSta
     Ok(unsafe { &mut *state })
 }
 
+/// Point code objects of a module at the file of its source.
+///
+/// This is what `SourceFileLoader` does. Bytecode compiled elsewhere then
+/// refers to the installed source, which tools like debuggers and coverage.py
+/// read. Modules without a source file are left alone.
+fn fix_code_filename(
+    py: Python,
+    state: &ImporterState,
+    module: &ImportablePythonModule<u8>,
+    code: &PyObject,
+) -> PyResult<()> {
+    if let Some(origin) = module.resolve_origin(py)? {
+        state
+            .imp_module
+            .call(py, "_fix_co_filename", (code, origin), None)?;
+    }
+
+    Ok(())
+}
+
 /// Decodes source bytes into a str.
 ///
 /// This is effectively a reimplementation of
@@ -1480,7 +1646,8 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
 ///
 /// This is called after PyInit_* to finish the initialization of the
 /// module. Its state struct is updated. A new instance of the meta path
-/// importer is constructed and registered on sys.meta_path.
+/// importer is constructed and registered on sys.meta_path. Its path hook is
+/// registered on sys.path_hooks.
 #[cfg(not(library_mode = "extension"))]
 pub(crate) fn initialize_importer<'a>(
     py: Python,
@@ -1502,6 +1669,17 @@ pub(crate) fn initialize_importer<'a>(
     meta_path_object.call_method(py, "clear", NoArgs, None)?;
     meta_path_object.call_method(py, "append", (unified_importer.clone_ref(py),), None)?;
 
+    // Paths of indexed packages are then resolved by the index instead of by
+    // scanning the filesystem. Paths we don't know about fall through to the
+    // next path hook.
+    let path_hooks_object = sys_module.get(py, "path_hooks")?;
+    path_hooks_object.call_method(
+        py,
+        "insert",
+        (0, unified_importer.getattr(py, "path_hook")?),
+        None,
+    )?;
+
     state.initialized = true;
 
     Ok(())
@@ -7,13 +7,27 @@
 use {
     super::config::{MemoryAllocatorBackend, OxidizedPythonInterpreterConfig, TerminfoResolution},
     super::conversion::{osstr_to_pyobject, osstring_to_bytes},
+    super::ctypes_util::install_find_library_redirect,
+    super::debugpy::{parse_listen_address, start_listener},
     super::importer::{
         initialize_importer, PyInit_oxidized_importer, OXIDIZED_IMPORTER_NAME,
         OXIDIZED_IMPORTER_NAME_STR,
     },
+    super::memory_stats::{
+        current_raw_allocator, make_tracking_allocator, MemoryStats, TrackingAllocator,
+    },
     super::osutils::resolve_terminfo_dirs,
+    super::oxidized_runtime::{PyInit_oxidized_runtime, OXIDIZED_RUNTIME_NAME},
     super::pyalloc::{make_raw_rust_memory_allocator, RawAllocator},
+    super::python_executable::install_python_executable_marker,
     super::python_resources::PythonResourcesState,
+    super::remote_resources::{fetch_http_range, remote_resources_cache_dir, RemoteChunkSource},
+    super::resource_verification::ResourceVerifier,
+    super::startup_progress::{
+        report_startup_progress, set_startup_progress_callback, signal_startup_ready,
+        StartupProgress,
+    },
+    super::zipimport::install_zipimport_finder,
     cpython::{
         GILGuard, NoArgs, ObjectProtocol, PyDict, PyErr, PyList, PyObject, PyString, Python,
         ToPyObject,
@@ -131,6 +145,7 @@ impl NewInterpreterError {
 enum InterpreterRawAllocator {
     Python(pyffi::PyMemAllocatorEx),
     Raw(RawAllocator),
+    Tracking(TrackingAllocator, Option<Box<InterpreterRawAllocator>>),
 }
 
 impl InterpreterRawAllocator {
@@ -138,6 +153,7 @@ impl InterpreterRawAllocator {
         match self {
             InterpreterRawAllocator::Python(alloc) => alloc as *const _,
             InterpreterRawAllocator::Raw(alloc) => &alloc.allocator as *const _,
+            InterpreterRawAllocator::Tracking(alloc, _) => &alloc.allocator as *const _,
         }
     }
 }
@@ -233,7 +249,14 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
             resources_state: None,
         };
 
-        res.init()?;
+        if let Err(err) = res.init() {
+            return Err(match &res.config.startup_error_messages {
+                Some(messages) => {
+                    NewInterpreterError::Dynamic(messages.render_current(&err.to_string()))
+                }
+                None => err,
+            });
+        }
 
         Ok(res)
     }
@@ -273,14 +296,22 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
 
         self.interpreter_state = InterpreterState::Initializing;
 
+        set_startup_progress_callback(self.config.startup_progress_callback);
+        report_startup_progress(StartupProgress::InitializingInterpreter);
+
         let exe = env::current_exe()
             .or_else(|_| Err(NewInterpreterError::Simple("could not obtain current exe")))?;
-        let origin = exe
+        let exe_dir = exe
             .parent()
-            .ok_or_else(|| NewInterpreterError::Simple("unable to get exe parent"))?
-            .to_path_buf();
+            .ok_or_else(|| NewInterpreterError::Simple("unable to get exe parent"))?;
+        let origin = match &self.config.origin {
+            Some(origin) => exe_dir.join(origin),
+            None => exe_dir.to_path_buf(),
+        };
         let origin_string = origin.display().to_string();
 
+        self.config.apply_python_executable_env(&exe);
+
         set_pyimport_inittab(&self.config);
 
         // Pre-configure Python.
@@ -313,6 +344,21 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
                 }
             }
 
+            // Statistics collection wraps whatever allocator is in effect.
+            // The wrapped allocator is retained so its state outlives the
+            // wrapper.
+            if raw_allocator.stats {
+                let inner = match &self.raw_allocator {
+                    Some(allocator) => unsafe { *allocator.as_ptr() },
+                    None => current_raw_allocator(),
+                };
+
+                self.raw_allocator = Some(InterpreterRawAllocator::Tracking(
+                    make_tracking_allocator(inner),
+                    self.raw_allocator.take().map(Box::new),
+                ));
+            }
+
             if let Some(allocator) = &self.raw_allocator {
                 unsafe {
                     pyffi::PyMem_SetAllocator(
@@ -359,6 +405,25 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
             ));
 
             if let Some(ref mut resources_state) = self.resources_state {
+                resources_state.origin = origin.clone();
+                resources_state.extract_shared_libraries_to_cache =
+                    self.config.extract_shared_libraries_to_cache;
+
+                if let Some(url) = &self.config.remote_resources_url {
+                    resources_state.remote_chunks = Some(RemoteChunkSource {
+                        url: url.clone(),
+                        fetcher: self.config.remote_chunk_fetcher.unwrap_or(fetch_http_range),
+                        cache_dir: remote_resources_cache_dir(),
+                    });
+                }
+
+                if let Some(key) = &self.config.resources_verification_key {
+                    resources_state.resource_verifier = Some(
+                        ResourceVerifier::new(key)
+                            .or_else(|e| Err(NewInterpreterError::Dynamic(e)))?,
+                    );
+                }
+
                 resources_state
                     .load(self.config.packed_resources)
                     .or_else(|err| Err(NewInterpreterError::Simple(err)))?;
@@ -445,21 +510,37 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
         // will be derived from wchar_t on Windows and char* on POSIX. We can
         // convert these to Python str instances using a platform-specific
         // mechanism.
-        let args_objs = env::args_os()
-            .map(|os_arg| osstr_to_pyobject(py, &os_arg, None))
-            .collect::<Result<Vec<PyObject>, &'static str>>()?;
+        //
+        // An explicitly configured argv takes precedence over the process
+        // arguments. If Python parsed arguments itself, it has already set
+        // sys.argv from them.
+        let args_objs = match &self.config.interpreter_config.argv {
+            Some(_) if self.config.interpreter_config.parse_argv == Some(true) => None,
+            Some(argv) => Some(
+                argv.iter()
+                    .map(|os_arg| osstr_to_pyobject(py, os_arg, None))
+                    .collect::<Result<Vec<PyObject>, &'static str>>()?,
+            ),
+            None => Some(
+                env::args_os()
+                    .map(|os_arg| osstr_to_pyobject(py, &os_arg, None))
+                    .collect::<Result<Vec<PyObject>, &'static str>>()?,
+            ),
+        };
 
-        // This will steal the pointer to the elements and mem::forget them.
-        let args = PyList::new(py, &args_objs);
-        let argv = b"argv\0";
+        if let Some(args_objs) = args_objs {
+            // This will steal the pointer to the elements and mem::forget them.
+            let args = PyList::new(py, &args_objs);
+            let argv = b"argv\0";
 
-        let res = args.with_borrowed_ptr(py, |args_ptr| unsafe {
-            pyffi::PySys_SetObject(argv.as_ptr() as *const i8, args_ptr)
-        });
+            let res = args.with_borrowed_ptr(py, |args_ptr| unsafe {
+                pyffi::PySys_SetObject(argv.as_ptr() as *const i8, args_ptr)
+            });
 
-        match res {
-            0 => (),
-            _ => return Err(NewInterpreterError::Simple("unable to set sys.argv")),
+            match res {
+                0 => (),
+                _ => return Err(NewInterpreterError::Simple("unable to set sys.argv")),
+            }
         }
 
         if self.config.argvb {
@@ -516,6 +597,71 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
             }
         }
 
+        if let Some(libraries) = &self.config.ctypes_library_map {
+            install_find_library_redirect(py, &origin, libraries).or_else(|err| {
+                Err(NewInterpreterError::new_from_pyerr(
+                    py,
+                    err,
+                    "installing ctypes.util.find_library() redirect",
+                ))
+            })?;
+        }
+
+        if let Some(key) = &self.config.python_executable_env {
+            // Processes spawned by this one run what they are told to.
+            if env::var_os(key).is_none() {
+                install_python_executable_marker(py, key).or_else(|err| {
+                    Err(NewInterpreterError::new_from_pyerr(
+                        py,
+                        err,
+                        "installing python_executable_env subprocess marker",
+                    ))
+                })?;
+            }
+        }
+
+        if let Some(archive) = &self.config.zipimport_archive {
+            let archive = origin.join(archive).display().to_string();
+
+            install_zipimport_finder(py, &archive).or_else(|err| {
+                Err(NewInterpreterError::new_from_pyerr(
+                    py,
+                    err,
+                    "installing zipimport finder",
+                ))
+            })?;
+        }
+
+        if let Some(module) = &self.config.startup_cache_module {
+            py.import(module).or_else(|err| {
+                Err(NewInterpreterError::new_from_pyerr(
+                    py,
+                    err,
+                    "importing startup cache module",
+                ))
+            })?;
+        }
+
+        if let Some(key) = &self.config.debugpy_listen_env {
+            if let Some(value) = env::var_os(key) {
+                let (host, port) = parse_listen_address(
+                    &value.to_string_lossy(),
+                    self.config.debugpy_allow_remote_hosts,
+                )
+                .or_else(|e| Err(NewInterpreterError::Dynamic(e)))?;
+
+                start_listener(py, &host, port).or_else(|err| {
+                    Err(NewInterpreterError::new_from_pyerr(
+                        py,
+                        err,
+                        "starting debugpy listener",
+                    ))
+                })?;
+            }
+        }
+
+        report_startup_progress(StartupProgress::InterpreterInitialized);
+
         Ok(())
     }
 
@@ -642,6 +788,19 @@ fn set_pyimport_inittab(config: &OxidizedPythonInterpreterConfig) {
         });
     }
 
+    let memory_stats = match &config.raw_allocator {
+        Some(raw_allocator) => raw_allocator.stats,
+        None => false,
+    };
+
+    if memory_stats || config.startup_progress_callback.is_some() {
+        let ptr = PyInit_oxidized_runtime as *const ();
+        extensions.push(pyffi::_inittab {
+            name: OXIDIZED_RUNTIME_NAME.as_ptr() as *mut _,
+            initfunc: Some(unsafe { std::mem::transmute::<*const (), extern "C" fn()>(ptr) }),
+        });
+    }
+
     // Add additional extension modules from the config.
     if let Some(extra_extension_modules) = &config.extra_extension_modules {
         for extension in extra_extension_modules {
@@ -713,6 +872,9 @@ impl<'python, 'interpreter, 'resources> Drop
     for MainPythonInterpreter<'python, 'interpreter, 'resources>
 {
     fn drop(&mut self) {
+        // Ensure a splash screen doesn't outlive the application.
+        signal_startup_ready();
+
         if let Some(key) = &self.config.write_modules_directory_env {
             if let Ok(path) = env::var(key) {
                 let path = PathBuf::from(path);
@@ -725,5 +887,13 @@ impl<'python, 'interpreter, 'resources> Drop
         }
 
         let _ = unsafe { pyffi::Py_FinalizeEx() };
+
+        if let Some(key) = &self.config.write_memory_stats_env {
+            if let Ok(path) = env::var(key) {
+                if let Err(e) = fs::write(&path, MemoryStats::current().to_text()) {
+                    eprintln!("error writing memory stats file: {}", e);
+                }
+            }
+        }
     }
 }
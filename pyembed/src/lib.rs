@@ -39,8 +39,8 @@ interfacing.
 **It is an explicit goal of this crate to rely on as few external dependencies
 as possible.** This is because we want to minimize bloat in produced binaries.
 At this time, we have required direct dependencies on published versions of the
-`anyhow`, `lazy_static`, `libc`, `memmap`, `python-packed-resources`, and `uuid`
-crates. On Windows, this list is extended by `memory-module-sys` and `winapi`,
+`anyhow`, `ed25519-dalek`, `lazy_static`, `libc`, `memmap`, `python-packed-resources`,
+`sha2`, `uuid`, and `zstd` crates. On Windows, this list is extended by `memory-module-sys` and `winapi`,
 which are required to support loading DLLs from memory. We also have an optional
 direct dependency on the `jemalloc-sys` crate.
 
@@ -70,6 +70,12 @@ to build required artifacts.
 environment variable can refer to the directory containing build artifacts
 that this crate needs. If not set, `OUT_DIR` will be used.
 
+`build-mode-extension-module` builds the crate for extension modules loaded
+by an existing Python interpreter, such as `oxidized_importer` and bundles
+built by `PyOxidizer`. In this mode, no interpreter is embedded and
+`init_bundle_module()` initializes extension modules containing the
+resources of an application.
+
 The exist mutually exclusive `cpython-link-*` features to control how
 the `cpython`/`python3-sys` crates are built.
 
@@ -83,9 +89,19 @@ That crate's build script will attempt to find a `libpython` from the
 
 */
 
+mod bundle;
 #[cfg(not(library_mode = "extension"))]
 mod config;
 mod conversion;
+#[cfg(not(library_mode = "extension"))]
+mod ctypes_util;
+#[cfg(not(library_mode = "extension"))]
+mod debugpy;
+mod extension_cache;
+#[cfg(not(library_mode = "extension"))]
+mod fuzzing;
+#[cfg(not(library_mode = "extension"))]
+mod import_hook;
 mod importer;
 #[cfg(not(library_mode = "extension"))]
 mod interpreter;
@@ -94,39 +110,80 @@ mod interpreter_config;
 #[cfg(windows)]
 mod memory_dll;
 #[cfg(not(library_mode = "extension"))]
+mod memory_stats;
+#[cfg(not(library_mode = "extension"))]
 mod osutils;
+#[cfg(not(library_mode = "extension"))]
+mod oxidized_runtime;
 mod package_metadata;
+mod path_entry_finder;
+#[cfg(not(library_mode = "extension"))]
+mod plugin;
 #[cfg(not(library_mode = "extension"))]
 mod pyalloc;
 #[cfg(not(library_mode = "extension"))]
 mod python_eval;
+#[cfg(not(library_mode = "extension"))]
+mod python_executable;
 #[allow(unused_variables)]
 mod python_resource_collector;
 mod python_resource_types;
 mod python_resources;
+mod remote_resources;
+mod resource_index;
 mod resource_scanning;
+mod resource_verification;
+mod shared_library;
+#[cfg(not(library_mode = "extension"))]
+mod startup_progress;
 #[cfg(not(library_mode = "extension"))]
 pub mod technotes;
 #[cfg(test)]
 mod test;
+#[cfg(not(library_mode = "extension"))]
+mod zipimport;
 
 #[cfg(not(library_mode = "extension"))]
 #[allow(unused_imports)]
 pub use crate::config::{
     Allocator, CheckHashPYCsMode, CoerceCLocale, ExtensionModule, OptimizationLevel,
     OxidizedPythonInterpreterConfig, PythonConfig, PythonInterpreterConfig,
-    PythonInterpreterProfile, PythonRawAllocator, PythonRunMode, TerminfoResolution,
+    PythonInterpreterProfile, PythonRawAllocator, PythonRunMode, StartupErrorMessages,
+    TerminfoResolution,
 };
 
+#[cfg(not(library_mode = "extension"))]
+#[allow(unused_imports)]
+pub use crate::fuzzing::PythonFuzzTarget;
+
 #[cfg(not(library_mode = "extension"))]
 #[allow(unused_imports)]
 pub use crate::interpreter::{MainPythonInterpreter, NewInterpreterError};
 
+#[cfg(not(library_mode = "extension"))]
+#[allow(unused_imports)]
+pub use crate::memory_stats::MemoryStats;
+
+#[cfg(not(library_mode = "extension"))]
+#[allow(unused_imports)]
+pub use crate::plugin::{PluginType, PluginValue, PythonPlugin};
+
 #[cfg(not(library_mode = "extension"))]
 #[allow(unused_imports)]
 pub use crate::python_eval::{
     run, run_and_handle_error, run_code, run_file, run_module_as_main, run_repl,
 };
 
+#[cfg(not(library_mode = "extension"))]
+#[allow(unused_imports)]
+pub use crate::remote_resources::{fetch_http_range, RemoteChunkFetcher};
+
+#[cfg(not(library_mode = "extension"))]
+#[allow(unused_imports)]
+pub use crate::startup_progress::{signal_startup_ready, StartupProgress, StartupProgressCallback};
+
 #[cfg(library_mode = "extension")]
 pub use crate::importer::PyInit_oxidized_importer;
+
+#[allow(unused_imports)]
+pub use crate::{bundle::init_bundle_module, shared_library::library_directory};
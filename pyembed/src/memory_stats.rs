@@ -0,0 +1,200 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Allocator statistics for the Python raw memory domain.
+
+When enabled, the configured raw allocator is wrapped by an allocator that
+counts calls and requested bytes before delegating to the real allocator.
+Statistics are exposed to Python via the built-in `oxidized_runtime` module
+and can be written to a file when the interpreter shuts down.
+*/
+
+use {
+    libc::{c_void, size_t},
+    python3_sys as pyffi,
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+static MALLOC_COUNT: AtomicU64 = AtomicU64::new(0);
+static CALLOC_COUNT: AtomicU64 = AtomicU64::new(0);
+static REALLOC_COUNT: AtomicU64 = AtomicU64::new(0);
+static FREE_COUNT: AtomicU64 = AtomicU64::new(0);
+static BYTES_REQUESTED: AtomicU64 = AtomicU64::new(0);
+
+/// A snapshot of allocator statistics.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    /// Number of `malloc()` calls to the raw allocator.
+    pub malloc_count: u64,
+    /// Number of `calloc()` calls to the raw allocator.
+    pub calloc_count: u64,
+    /// Number of `realloc()` calls to the raw allocator.
+    pub realloc_count: u64,
+    /// Number of `free()` calls to the raw allocator.
+    pub free_count: u64,
+    /// Total bytes requested from the raw allocator.
+    pub bytes_requested: u64,
+    /// Peak resident set size of the process, in bytes.
+    ///
+    /// `None` if the platform doesn't expose this information.
+    pub peak_rss: Option<u64>,
+}
+
+impl MemoryStats {
+    /// Obtain statistics for the current process.
+    ///
+    /// Counters are only incremented when the raw allocator is configured
+    /// to collect statistics.
+    pub fn current() -> Self {
+        Self {
+            malloc_count: MALLOC_COUNT.load(Ordering::Relaxed),
+            calloc_count: CALLOC_COUNT.load(Ordering::Relaxed),
+            realloc_count: REALLOC_COUNT.load(Ordering::Relaxed),
+            free_count: FREE_COUNT.load(Ordering::Relaxed),
+            bytes_requested: BYTES_REQUESTED.load(Ordering::Relaxed),
+            peak_rss: peak_rss(),
+        }
+    }
+
+    /// Obtain `(name, value)` pairs for each statistic.
+    pub fn entries(&self) -> Vec<(&'static str, Option<u64>)> {
+        vec![
+            ("malloc_count", Some(self.malloc_count)),
+            ("calloc_count", Some(self.calloc_count)),
+            ("realloc_count", Some(self.realloc_count)),
+            ("free_count", Some(self.free_count)),
+            ("bytes_requested", Some(self.bytes_requested)),
+            ("peak_rss", self.peak_rss),
+        ]
+    }
+
+    /// Render statistics as `name: value` lines.
+    pub fn to_text(&self) -> String {
+        self.entries()
+            .into_iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{}: {}\n", name, value),
+                None => format!("{}: unknown\n", name),
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+const MAXRSS_MULTIPLIER: u64 = 1;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const MAXRSS_MULTIPLIER: u64 = 1024;
+
+/// Obtain the peak resident set size of the process, in bytes.
+#[cfg(unix)]
+fn peak_rss() -> Option<u64> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } == 0 {
+        Some(usage.ru_maxrss as u64 * MAXRSS_MULTIPLIER)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn peak_rss() -> Option<u64> {
+    None
+}
+
+/// A raw allocator that records statistics before calling another allocator.
+pub struct TrackingAllocator {
+    pub allocator: pyffi::PyMemAllocatorEx,
+    _inner: Box<pyffi::PyMemAllocatorEx>,
+}
+
+extern "C" fn tracking_malloc(ctx: *mut c_void, size: size_t) -> *mut c_void {
+    MALLOC_COUNT.fetch_add(1, Ordering::Relaxed);
+    BYTES_REQUESTED.fetch_add(size as u64, Ordering::Relaxed);
+
+    unsafe {
+        let inner = &*(ctx as *const pyffi::PyMemAllocatorEx);
+        (inner.malloc.unwrap())(inner.ctx, size)
+    }
+}
+
+extern "C" fn tracking_calloc(ctx: *mut c_void, nelem: size_t, elsize: size_t) -> *mut c_void {
+    CALLOC_COUNT.fetch_add(1, Ordering::Relaxed);
+    BYTES_REQUESTED.fetch_add(nelem.saturating_mul(elsize) as u64, Ordering::Relaxed);
+
+    unsafe {
+        let inner = &*(ctx as *const pyffi::PyMemAllocatorEx);
+        (inner.calloc.unwrap())(inner.ctx, nelem, elsize)
+    }
+}
+
+extern "C" fn tracking_realloc(
+    ctx: *mut c_void,
+    ptr: *mut c_void,
+    new_size: size_t,
+) -> *mut c_void {
+    REALLOC_COUNT.fetch_add(1, Ordering::Relaxed);
+    BYTES_REQUESTED.fetch_add(new_size as u64, Ordering::Relaxed);
+
+    unsafe {
+        let inner = &*(ctx as *const pyffi::PyMemAllocatorEx);
+        (inner.realloc.unwrap())(inner.ctx, ptr, new_size)
+    }
+}
+
+extern "C" fn tracking_free(ctx: *mut c_void, ptr: *mut c_void) {
+    if !ptr.is_null() {
+        FREE_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    unsafe {
+        let inner = &*(ctx as *const pyffi::PyMemAllocatorEx);
+        (inner.free.unwrap())(inner.ctx, ptr)
+    }
+}
+
+/// Wrap a raw allocator with one recording statistics.
+///
+/// The wrapped allocator's `ctx` must remain valid for the lifetime of the
+/// returned instance.
+pub fn make_tracking_allocator(inner: pyffi::PyMemAllocatorEx) -> TrackingAllocator {
+    // The inner allocator lives on the heap so the ctx pointer remains valid
+    // when the returned struct is moved.
+    let inner = Box::new(inner);
+
+    let allocator = pyffi::PyMemAllocatorEx {
+        ctx: inner.as_ref() as *const _ as *mut c_void,
+        malloc: Some(tracking_malloc),
+        calloc: Some(tracking_calloc),
+        realloc: Some(tracking_realloc),
+        free: Some(tracking_free),
+    };
+
+    TrackingAllocator {
+        allocator,
+        _inner: inner,
+    }
+}
+
+/// Obtain the raw allocator currently registered with Python.
+pub fn current_raw_allocator() -> pyffi::PyMemAllocatorEx {
+    let mut allocator = pyffi::PyMemAllocatorEx {
+        ctx: std::ptr::null_mut(),
+        malloc: None,
+        calloc: None,
+        realloc: None,
+        free: None,
+    };
+
+    unsafe {
+        pyffi::PyMem_GetAllocator(
+            pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
+            &mut allocator,
+        );
+    }
+
+    allocator
+}
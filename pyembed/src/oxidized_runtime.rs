@@ -0,0 +1,99 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+The built-in `oxidized_runtime` module.
+
+This module exposes run-time state of the binary to Python code. It is
+registered when a feature using it is enabled.
+*/
+
+use {
+    super::memory_stats::MemoryStats,
+    super::startup_progress::signal_startup_ready,
+    cpython::{py_fn, PyDict, PyErr, PyModule, PyObject, PyResult, Python, PythonObject},
+    python3_sys as pyffi,
+};
+
+pub const OXIDIZED_RUNTIME_NAME: &[u8] = b"oxidized_runtime\0";
+
+const DOC: &[u8] = b"Runtime introspection of PyOxidizer binaries\0";
+
+/// oxidized_runtime.memory_stats()
+fn memory_stats(py: Python) -> PyResult<PyDict> {
+    let stats = PyDict::new(py);
+
+    for (name, value) in MemoryStats::current().entries() {
+        match value {
+            Some(value) => stats.set_item(py, name, value)?,
+            None => stats.set_item(py, name, py.None())?,
+        }
+    }
+
+    Ok(stats)
+}
+
+/// oxidized_runtime.signal_ready()
+fn signal_ready(py: Python) -> PyResult<PyObject> {
+    signal_startup_ready();
+
+    Ok(py.None())
+}
+
+fn module_init(py: Python, module: &PyModule) -> PyResult<()> {
+    module.add(py, "memory_stats", py_fn!(py, memory_stats()))?;
+    module.add(py, "signal_ready", py_fn!(py, signal_ready()))?;
+
+    Ok(())
+}
+
+static mut MODULE_DEF: pyffi::PyModuleDef = pyffi::PyModuleDef {
+    m_base: pyffi::PyModuleDef_HEAD_INIT,
+    m_name: std::ptr::null(),
+    m_doc: std::ptr::null(),
+    m_size: 0,
+    m_methods: 0 as *mut _,
+    m_slots: 0 as *mut _,
+    m_traverse: None,
+    m_clear: None,
+    m_free: None,
+};
+
+/// Module initialization function for `oxidized_runtime`.
+///
+/// See `PyInit_oxidized_importer()` for why the cpython crate's macros
+/// aren't used.
+#[allow(non_snake_case)]
+pub extern "C" fn PyInit_oxidized_runtime() -> *mut pyffi::PyObject {
+    let py = unsafe { cpython::Python::assume_gil_acquired() };
+
+    unsafe {
+        if MODULE_DEF.m_name.is_null() {
+            MODULE_DEF.m_name = OXIDIZED_RUNTIME_NAME.as_ptr() as *const _;
+            MODULE_DEF.m_doc = DOC.as_ptr() as *const _;
+        }
+    }
+
+    let module = unsafe { pyffi::PyModule_Create(&mut MODULE_DEF) };
+
+    if module.is_null() {
+        return module;
+    }
+
+    let module = match unsafe { PyObject::from_owned_ptr(py, module).cast_into::<PyModule>(py) } {
+        Ok(m) => m,
+        Err(e) => {
+            PyErr::from(e).restore(py);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match module_init(py, &module) {
+        Ok(()) => module.into_object().steal_ptr(),
+        Err(e) => {
+            e.restore(py);
+            std::ptr::null_mut()
+        }
+    }
+}
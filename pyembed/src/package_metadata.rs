@@ -4,7 +4,7 @@
 
 use {
     super::importer::ImporterState,
-    cpython::exc::{IOError, NotImplementedError},
+    cpython::exc::IOError,
     cpython::{
         py_class, py_class_prop_getter, NoArgs, ObjectProtocol, PyBytes, PyErr, PyList, PyObject,
         PyResult, PyString, Python, PythonObject, ToPyObject,
@@ -148,8 +148,65 @@ impl OxidizedDistribution {
         entry_point.call_method(py, "_from_text", (text,), None)
     }
 
+    /// Return the files of this Distribution, as listed by its `RECORD` file.
+    ///
+    /// Like the standard library, returns None if there is no `RECORD` file.
     fn files_impl(&self, py: Python) -> PyResult<PyObject> {
-        Err(PyErr::new::<NotImplementedError, _>(py, NoArgs))
+        let text = self.read_text_impl(py, &"RECORD".to_py_object(py))?;
+        if text == py.None() {
+            return Ok(py.None());
+        }
+
+        let importlib_metadata = py.import("importlib.metadata")?;
+        let package_path = importlib_metadata.get(py, "PackagePath")?;
+        let file_hash = importlib_metadata.get(py, "FileHash")?;
+
+        let lines = text.call_method(py, "splitlines", NoArgs, None)?;
+        let rows = py.import("csv")?.call(py, "reader", (lines,), None)?;
+
+        let builtins = py.import("builtins")?;
+        let mut files = Vec::new();
+
+        for row in rows.iter(py)? {
+            let row = row?;
+            let len = row.len(py)?;
+            if len == 0 {
+                continue;
+            }
+
+            // Rows have the path, then optional hash and size fields.
+            let field = |index: usize| -> PyResult<Option<PyObject>> {
+                if index >= len {
+                    return Ok(None);
+                }
+
+                let value = row.get_item(py, index)?;
+                if value.is_true(py)? {
+                    Ok(Some(value))
+                } else {
+                    Ok(None)
+                }
+            };
+
+            let path = package_path.call(py, (row.get_item(py, 0)?,), None)?;
+
+            let hash = match field(1)? {
+                Some(value) => file_hash.call(py, (value,), None)?,
+                None => py.None(),
+            };
+            let size = match field(2)? {
+                Some(value) => builtins.call(py, "int", (value,), None)?,
+                None => py.None(),
+            };
+
+            path.setattr(py, "hash", hash)?;
+            path.setattr(py, "size", size)?;
+            path.setattr(py, "dist", self.as_object())?;
+
+            files.push(path);
+        }
+
+        Ok(PyList::new(py, &files).into_object())
     }
 
     fn requires_impl(&self, py: Python) -> PyResult<PyObject> {
@@ -184,8 +241,33 @@ impl OxidizedDistribution {
     }
 }
 
+/// Normalize a distribution name as described by PEP 503.
+///
+/// Names are compared case-insensitively and runs of `-`, `_` and `.` are
+/// equivalent.
+fn normalize_distribution_name(name: &str) -> String {
+    let mut res = String::with_capacity(name.len());
+    let mut in_separator = false;
+
+    for c in name.chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !in_separator {
+                res.push('-');
+            }
+            in_separator = true;
+        } else {
+            res.extend(c.to_lowercase());
+            in_separator = false;
+        }
+    }
+
+    res
+}
+
 /// Find package metadata distributions given search criteria.
-pub(crate) fn find_distributions<'a>(
+///
+/// A `name` of None finds all distributions.
+pub(crate) fn find_distributions(
     py: Python,
     state: Arc<Box<ImporterState>>,
     name: Option<PyObject>,
@@ -193,42 +275,33 @@ pub(crate) fn find_distributions<'a>(
 ) -> PyResult<PyObject> {
     let resources = &state.get_resources_state().resources;
 
-    let distributions = if let Some(name) = name {
-        // Python normalizes the name. We do the same.
-        let name = name.str(py)?.to_string(py)?.to_string();
-        let name = name.to_lowercase().replace('-', "_");
-        let name_cow = Cow::Borrowed::<str>(&name);
-
-        if let Some(resource) = resources.get(&name_cow) {
-            if resource.is_package
-                && (resource.in_memory_distribution_resources.is_some()
-                    || resource.relative_path_distribution_resources.is_some())
-            {
-                vec![OxidizedDistribution::create_instance(py, state.clone(), name)?.into_object()]
-            } else {
-                vec![]
-            }
-        } else {
-            vec![]
+    let name = match name {
+        Some(name) if name != py.None() => {
+            Some(normalize_distribution_name(&name.str(py)?.to_string(py)?))
         }
-    } else {
-        // Return all distributions.
-        let mut distributions = Vec::new();
-
-        for (k, v) in resources.iter() {
-            if v.is_package
-                && (v.in_memory_distribution_resources.is_some()
-                    || v.relative_path_distribution_resources.is_some())
-            {
-                distributions.push(
-                    OxidizedDistribution::create_instance(py, state.clone(), k.to_string())?
-                        .into_object(),
-                );
+        _ => None,
+    };
+
+    let mut distributions = Vec::new();
+
+    for (k, v) in resources.iter() {
+        if !v.is_package
+            || (v.in_memory_distribution_resources.is_none()
+                && v.relative_path_distribution_resources.is_none())
+        {
+            continue;
+        }
+
+        if let Some(name) = &name {
+            if normalize_distribution_name(k) != *name {
+                continue;
             }
         }
 
-        distributions
-    };
+        distributions.push(
+            OxidizedDistribution::create_instance(py, state.clone(), k.to_string())?.into_object(),
+        );
+    }
 
     Ok(PyList::new(py, &distributions).into_object())
 }
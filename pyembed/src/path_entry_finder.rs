@@ -0,0 +1,125 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Path entry finder backed by the resources indexed by an `OxidizedFinder`.
+
+`OxidizedFinder.path_hook` is registered on `sys.path_hooks`. When the
+stock path machinery is asked about the `__path__` of an indexed package
+(e.g. by `pkgutil.iter_modules()` or `importlib.machinery.PathFinder`), an
+`OxidizedPathEntryFinder` answers from the index instead of scanning the
+filesystem. So modules found this way have the same metadata and integrity
+guarantees as modules found through `sys.meta_path`.
+*/
+
+use {
+    super::conversion::pyobject_to_pathbuf,
+    super::importer::ImporterState,
+    cpython::exc::ImportError,
+    cpython::{
+        py_class, ObjectProtocol, PyErr, PyList, PyObject, PyResult, PyString, Python,
+        PythonObject, ToPyObject,
+    },
+    python_packed_resources::data::ResourceFlavor,
+    std::sync::Arc,
+};
+
+// Implements importlib.abc.PathEntryFinder for the path of an indexed package.
+py_class!(class OxidizedPathEntryFinder |py| {
+    data finder: PyObject;
+    data state: Arc<Box<ImporterState>>;
+    data package: String;
+
+    def find_spec(&self, fullname: &PyString, target: Option<PyObject> = None) -> PyResult<PyObject> {
+        self.find_spec_impl(py, fullname, target)
+    }
+
+    def invalidate_caches(&self) -> PyResult<PyObject> {
+        Ok(py.None())
+    }
+
+    // Used by pkgutil.iter_modules().
+    def iter_modules(&self, prefix: &str = "") -> PyResult<PyObject> {
+        self.iter_modules_impl(py, prefix)
+    }
+});
+
+impl OxidizedPathEntryFinder {
+    /// Find the spec of a module directly within our package.
+    ///
+    /// Resolving the spec is delegated to the `OxidizedFinder`, which is the
+    /// loader of found modules.
+    fn find_spec_impl(
+        &self,
+        py: Python,
+        fullname: &PyString,
+        target: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let name = fullname.to_string(py)?;
+
+        match name.rfind('.') {
+            Some(idx) if name[0..idx] == *self.package(py) => {}
+            _ => return Ok(py.None()),
+        }
+
+        match state
+            .get_resources_state()
+            .resolve_importable_module(&name, state.optimize_level)
+        {
+            Some(module)
+                if *module.flavor == ResourceFlavor::Module
+                    || *module.flavor == ResourceFlavor::Extension => {}
+            _ => return Ok(py.None()),
+        }
+
+        self.finder(py)
+            .call_method(py, "find_spec", (fullname, py.None(), target), None)
+    }
+
+    /// Obtain (name, is_package) tuples for modules directly within our package.
+    fn iter_modules_impl(&self, py: Python, prefix: &str) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let package = self.package(py);
+
+        let modules = state
+            .get_resources_state()
+            .package_modules(package, state.optimize_level)
+            .iter()
+            .map(|(name, is_package)| {
+                let name = format!("{}{}", prefix, &name[package.len() + 1..]);
+
+                (name, *is_package).to_py_object(py).into_object()
+            })
+            .collect::<Vec<PyObject>>();
+
+        Ok(PyList::new(py, &modules).into_object())
+    }
+}
+
+/// Obtain an `OxidizedPathEntryFinder` for a `sys.path` entry.
+///
+/// Raises `ImportError` if the path isn't the path of an indexed package,
+/// which tells the path machinery to try the next path hook.
+pub(crate) fn path_entry_finder(
+    py: Python,
+    finder: PyObject,
+    state: Arc<Box<ImporterState>>,
+    path: PyObject,
+) -> PyResult<PyObject> {
+    let path = pyobject_to_pathbuf(py, path)?;
+
+    let package = state
+        .get_resources_state()
+        .package_for_path(&path)
+        .ok_or_else(|| {
+            PyErr::new::<ImportError, _>(
+                py,
+                format!("{} is not the path of an indexed package", path.display()),
+            )
+        })?
+        .to_string();
+
+    Ok(OxidizedPathEntryFinder::create_instance(py, finder, state, package)?.into_object())
+}
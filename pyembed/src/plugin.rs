@@ -0,0 +1,243 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Support for plugin libraries whose exported functions are implemented in Python.
+
+Plugin hosts (audio workstations, DCC tools, databases, ...) load shared
+libraries and call C functions they export. Libraries generated by
+PyOxidizer for plugin targets define these functions and forward calls to
+Python callables via [`PythonPlugin`], converting arguments and return
+values between C and Python types.
+
+The plugin isn't the executable of the process, so files installed next
+to it are located relative to the library itself, obtained via
+[`crate::library_directory()`], instead of the current executable.
+
+Hosts may call exported functions from any thread. Once initialized, the
+interpreter releases the GIL and each call acquires it, via
+`PyGILState_Ensure()`, for its duration.
+*/
+
+use {
+    super::config::OxidizedPythonInterpreterConfig,
+    super::interpreter::{MainPythonInterpreter, NewInterpreterError},
+    cpython::{GILGuard, ObjectProtocol, PyObject, Python, PythonObject, ToPyObject},
+    python3_sys as pyffi,
+    std::collections::BTreeMap,
+};
+
+/// C types of values returned by exported functions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PluginType {
+    /// Nothing is returned.
+    Void,
+    /// A signed integer.
+    Int,
+    /// An unsigned integer.
+    UInt,
+    /// A floating point number.
+    Float,
+    /// A boolean.
+    Bool,
+    /// An opaque pointer, as a Python integer.
+    Pointer,
+}
+
+/// A value passed to or returned by an exported function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PluginValue {
+    None,
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    Pointer(usize),
+    /// A NUL terminated string, passed to Python as `str`.
+    ///
+    /// `None` represents a NULL pointer.
+    String(Option<String>),
+}
+
+impl PluginValue {
+    /// The value returned when a Python callable fails.
+    pub fn zero(kind: PluginType) -> Self {
+        match kind {
+            PluginType::Void => PluginValue::None,
+            PluginType::Int => PluginValue::Int(0),
+            PluginType::UInt => PluginValue::UInt(0),
+            PluginType::Float => PluginValue::Float(0.0),
+            PluginType::Bool => PluginValue::Bool(false),
+            PluginType::Pointer => PluginValue::Pointer(0),
+        }
+    }
+
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            PluginValue::Int(v) => *v,
+            PluginValue::UInt(v) => *v as i64,
+            _ => 0,
+        }
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            PluginValue::Int(v) => *v as u64,
+            PluginValue::UInt(v) => *v,
+            _ => 0,
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            PluginValue::Float(v) => *v,
+            _ => 0.0,
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        match self {
+            PluginValue::Bool(v) => *v,
+            _ => false,
+        }
+    }
+
+    pub fn as_pointer(&self) -> usize {
+        match self {
+            PluginValue::Pointer(v) => *v,
+            _ => 0,
+        }
+    }
+
+    fn to_py_object(&self, py: Python) -> PyObject {
+        match self {
+            PluginValue::None | PluginValue::String(None) => py.None(),
+            PluginValue::Int(v) => v.to_py_object(py).into_object(),
+            PluginValue::UInt(v) => v.to_py_object(py).into_object(),
+            PluginValue::Float(v) => v.to_py_object(py).into_object(),
+            PluginValue::Bool(v) => v.to_py_object(py).into_object(),
+            PluginValue::Pointer(v) => v.to_py_object(py).into_object(),
+            PluginValue::String(Some(v)) => v.to_py_object(py).into_object(),
+        }
+    }
+}
+
+/// An interpreter and the Python callables implementing exported functions.
+pub struct PythonPlugin<'python, 'interpreter, 'resources> {
+    interpreter: MainPythonInterpreter<'python, 'interpreter, 'resources>,
+    callables: BTreeMap<String, PyObject>,
+
+    /// State of the thread which initialized the interpreter, saved when releasing the GIL.
+    thread_state: *mut pyffi::PyThreadState,
+}
+
+impl<'python, 'interpreter, 'resources> PythonPlugin<'python, 'interpreter, 'resources> {
+    /// Initialize an interpreter and resolve callables of exported functions.
+    ///
+    /// `exports` pairs names of exported functions with the Python callable
+    /// implementing them, as `module:function`.
+    ///
+    /// The GIL is released once callables are resolved. The instance must be
+    /// dropped by the thread which created it.
+    pub fn new(
+        config: OxidizedPythonInterpreterConfig<'resources>,
+        exports: &[(&str, &str)],
+    ) -> Result<Self, NewInterpreterError> {
+        let mut interpreter = MainPythonInterpreter::new(config)?;
+
+        let py = interpreter.acquire_gil()?;
+
+        let mut callables = BTreeMap::new();
+
+        for (symbol, callable) in exports {
+            let mut parts = callable.splitn(2, ':');
+            let module = parts.next().unwrap_or_default();
+            let function = parts.next().unwrap_or_default();
+
+            let value = py
+                .import(module)
+                .and_then(|m| m.get(py, function))
+                .or_else(|err| {
+                    Err(NewInterpreterError::new_from_pyerr(
+                        py,
+                        err,
+                        &format!("resolving {} implementing {}", callable, symbol),
+                    ))
+                })?;
+
+            callables.insert(symbol.to_string(), value);
+        }
+
+        // The GIL is still held by the thread state created when initializing
+        // the interpreter, so releasing our guard isn't enough.
+        interpreter.release_gil();
+        let thread_state = unsafe { pyffi::PyEval_SaveThread() };
+
+        Ok(Self {
+            interpreter,
+            callables,
+            thread_state,
+        })
+    }
+
+    /// Call the Python callable implementing an exported function.
+    ///
+    /// The value returned by the callable is converted to `returns`. As C
+    /// functions can't raise exceptions, exceptions raised by the callable
+    /// or when converting its value are printed and a zero value is
+    /// returned.
+    ///
+    /// May be called from any thread. The GIL is acquired for the duration
+    /// of the call.
+    pub fn call(&self, symbol: &str, args: &[PluginValue], returns: PluginType) -> PluginValue {
+        let gil = GILGuard::acquire();
+        let py = gil.python();
+
+        let callable = match self.callables.get(symbol) {
+            Some(callable) => callable,
+            None => {
+                eprintln!("error calling {}: function isn't exported", symbol);
+                return PluginValue::zero(returns);
+            }
+        };
+
+        let args = args
+            .iter()
+            .map(|arg| arg.to_py_object(py))
+            .collect::<Vec<_>>();
+
+        let res = callable
+            .call(py, cpython::PyTuple::new(py, &args), None)
+            .and_then(|value| {
+                Ok(match returns {
+                    PluginType::Void => PluginValue::None,
+                    PluginType::Int => PluginValue::Int(value.extract(py)?),
+                    PluginType::UInt => PluginValue::UInt(value.extract(py)?),
+                    PluginType::Float => PluginValue::Float(value.extract(py)?),
+                    PluginType::Bool => PluginValue::Bool(value.is_true(py)?),
+                    PluginType::Pointer => PluginValue::Pointer(value.extract(py)?),
+                })
+            });
+
+        match res {
+            Ok(value) => value,
+            Err(err) => {
+                eprintln!("error calling {}:", symbol);
+                err.print(py);
+                PluginValue::zero(returns)
+            }
+        }
+    }
+}
+
+impl<'python, 'interpreter, 'resources> Drop for PythonPlugin<'python, 'interpreter, 'resources> {
+    fn drop(&mut self) {
+        // The interpreter is finalized with the GIL held by the thread which
+        // initialized it.
+        unsafe { pyffi::PyEval_RestoreThread(self.thread_state) };
+
+        // Callables must be released before the interpreter is finalized.
+        self.callables.clear();
+    }
+}
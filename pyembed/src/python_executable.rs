@@ -0,0 +1,71 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Marking of subprocesses running the current executable as `python`.
+
+When `python_executable_env` is configured, the binary behaves like `python`
+when the environment variable it names is present. Rather than setting the
+variable in the environment of the current process, where every child
+process would inherit it, we wrap `subprocess.Popen` when `subprocess` is
+imported, so the variable is only added to the environment of processes
+running `sys.executable`.
+*/
+
+use {
+    crate::import_hook::run_with_post_import_hook,
+    cpython::{PyDict, PyResult, Python},
+};
+
+/// Python code installing the marker.
+///
+/// The `ENV_KEY` variable must be defined before this code runs.
+const MARKER_CODE: &str = r#"
+import os
+import sys
+
+
+def _mark_python_executable(module):
+    execute_child = module.Popen._execute_child
+    code = execute_child.__code__
+    names = code.co_varnames[: code.co_argcount]
+
+    def wrapped(*args, **kwargs):
+        # Arguments are looked up by name, so this doesn't depend on the
+        # signature of a specific Python version.
+        args = list(args)
+        arguments = dict(zip(names, args), **kwargs)
+
+        program = arguments["executable"]
+        if program is None:
+            program = arguments["args"]
+            if not isinstance(program, (str, bytes, os.PathLike)):
+                program = program[0]
+
+        if sys.executable and os.fsdecode(program) == sys.executable:
+            env = dict(os.environ if arguments["env"] is None else arguments["env"])
+            env[ENV_KEY] = "1"
+
+            i = names.index("env")
+            if i < len(args):
+                args[i] = env
+            else:
+                kwargs["env"] = env
+
+        return execute_child(*args, **kwargs)
+
+    wrapped.__doc__ = execute_child.__doc__
+    module.Popen._execute_child = wrapped
+
+
+install_post_import_hook("subprocess", _mark_python_executable)
+"#;
+
+/// Set environment variable `key` in subprocesses running `sys.executable`.
+pub fn install_python_executable_marker(py: Python, key: &str) -> PyResult<()> {
+    let globals = PyDict::new(py);
+    globals.set_item(py, "ENV_KEY", key)?;
+
+    run_with_post_import_hook(py, MARKER_CODE, &globals)
+}
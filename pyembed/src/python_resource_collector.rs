@@ -16,6 +16,7 @@ use {
         py_class, py_class_prop_getter, ObjectProtocol, PyBytes, PyErr, PyObject, PyResult, Python,
         PythonObject, ToPyObject,
     },
+    python_packaging::bytecode::BytecodeCompilerBackend,
     python_packaging::resource_collection::{
         PreparedPythonResources, PythonResourceCollector, PythonResourcesPolicy,
     },
@@ -211,8 +212,13 @@ impl OxidizedResourceCollector {
 
         let collector = self.collector(py).borrow();
 
+        let backend = BytecodeCompilerBackend::Process {
+            python_exe,
+            jobs: 1,
+        };
+
         let prepared: PreparedPythonResources = collector
-            .to_prepared_python_resources(&python_exe)
+            .to_prepared_python_resources(&backend, None)
             .or_else(|e| {
                 Err(PyErr::new::<ValueError, _>(
                     py,
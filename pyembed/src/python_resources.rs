@@ -12,6 +12,8 @@ use {
         pyobject_optional_resources_map_to_pathbuf, pyobject_to_owned_bytes_optional,
         pyobject_to_pathbuf_optional,
     },
+    super::remote_resources::RemoteChunkSource,
+    super::resource_verification::{read_resource_file, ResourceVerifier},
     anyhow::Result,
     cpython::exc::{ImportError, OSError, TypeError, ValueError},
     cpython::{
@@ -26,7 +28,7 @@ use {
     std::collections::HashMap,
     std::ffi::CStr,
     std::iter::FromIterator,
-    std::path::{Path, PathBuf},
+    std::path::{Component, Path, PathBuf},
 };
 
 /// Python bytecode optimization level.
@@ -50,13 +52,17 @@ where
         || entry.relative_path_module_source.is_some()
         || match optimize_level {
             OptimizeLevel::Zero => {
-                entry.in_memory_bytecode.is_some() || entry.relative_path_module_bytecode.is_some()
+                entry.in_memory_bytecode.is_some()
+                    || entry.relative_path_module_bytecode.is_some()
+                    || entry.remote_module_bytecode_chunks.is_some()
             }
             OptimizeLevel::One => {
-                entry.in_memory_bytecode_opt1.is_some() || entry.in_memory_bytecode_opt1.is_some()
+                entry.in_memory_bytecode_opt1.is_some()
+                    || entry.relative_path_module_bytecode_opt1.is_some()
             }
             OptimizeLevel::Two => {
-                entry.in_memory_bytecode_opt2.is_some() || entry.in_memory_bytecode_opt2.is_some()
+                entry.in_memory_bytecode_opt2.is_some()
+                    || entry.relative_path_module_bytecode_opt2.is_some()
             }
         }
 }
@@ -78,6 +84,12 @@ where
     /// Path from which relative paths should be interpreted.
     origin: &'a Path,
 
+    /// Source of data stored in remote archives.
+    remote_chunks: Option<&'a RemoteChunkSource>,
+
+    /// Verifies signatures of files read from relative paths.
+    resource_verifier: Option<&'a ResourceVerifier>,
+
     /// The resource/module flavor.
     pub flavor: &'a ResourceFlavor,
     /// Whether this module is a package.
@@ -85,6 +97,23 @@ where
 }
 
 impl<'a> ImportablePythonModule<'a, u8> {
+    /// Resolve in-memory module data, decompressing it if necessary.
+    fn resolve_in_memory_data<'b>(&self, py: Python, data: &'b [u8]) -> PyResult<Cow<'b, [u8]>> {
+        if self.resource.in_memory_module_data_compressed {
+            Ok(Cow::Owned(zstd::decode_all(data).or_else(|e| {
+                Err(PyErr::new::<ImportError, _>(
+                    py,
+                    (
+                        format!("error decompressing module data: {}", e),
+                        self.resource.name.clone(),
+                    ),
+                ))
+            })?))
+        } else {
+            Ok(Cow::Borrowed(data))
+        }
+    }
+
     /// Attempt to resolve a Python `bytes` for the source code behind this module.
     ///
     /// Will return a PyErr if an error occurs resolving source. If there is no source,
@@ -97,19 +126,20 @@ impl<'a> ImportablePythonModule<'a, u8> {
         io_module: &PyModule,
     ) -> PyResult<Option<PyObject>> {
         let bytes = if let Some(data) = &self.resource.in_memory_source {
-            Some(PyBytes::new(py, data))
+            Some(PyBytes::new(py, &self.resolve_in_memory_data(py, data)?))
         } else if let Some(relative_path) = &self.resource.relative_path_module_source {
             let path = self.origin.join(relative_path);
 
-            let source = std::fs::read(&path).or_else(|e| {
-                Err(PyErr::new::<ImportError, _>(
-                    py,
-                    (
-                        format!("error reading module source from {}: {}", path.display(), e),
-                        self.resource.name.clone(),
-                    ),
-                ))
-            })?;
+            let source = read_resource_file(self.resource_verifier, self.origin, relative_path)
+                .or_else(|e| {
+                    Err(PyErr::new::<ImportError, _>(
+                        py,
+                        (
+                            format!("error reading module source from {}: {}", path.display(), e),
+                            self.resource.name.clone(),
+                        ),
+                    ))
+                })?;
 
             Some(PyBytes::new(py, &source))
         } else {
@@ -143,6 +173,14 @@ impl<'a> ImportablePythonModule<'a, u8> {
             OptimizeLevel::One => &self.resource.in_memory_bytecode_opt1,
             OptimizeLevel::Two => &self.resource.in_memory_bytecode_opt2,
         } {
+            // Compressed bytecode is decompressed into a new buffer when the
+            // module is imported. Otherwise it is referenced without copying.
+            if self.resource.in_memory_module_data_compressed {
+                return Ok(Some(
+                    PyBytes::new(py, &self.resolve_in_memory_data(py, data)?).into_object(),
+                ));
+            }
+
             let ptr = unsafe {
                 pyffi::PyMemoryView_FromMemory(
                     data.as_ptr() as _,
@@ -152,19 +190,48 @@ impl<'a> ImportablePythonModule<'a, u8> {
             };
 
             Ok(unsafe { PyObject::from_owned_ptr_opt(py, ptr) })
-        } else if let Some(path) = self.bytecode_path(optimize_level) {
-            // TODO we could potentially avoid the double allocation for bytecode
-            // by reading directly into a buffer transferred to Python.
-            let bytecode = std::fs::read(&path).or_else(|e| {
+        } else if let (OptimizeLevel::Zero, Some(chunks)) =
+            (optimize_level, &self.resource.remote_module_bytecode_chunks)
+        {
+            let remote_chunks = self.remote_chunks.ok_or_else(|| {
+                PyErr::new::<ImportError, _>(
+                    py,
+                    (
+                        "bytecode is stored remotely but remote resources are not configured",
+                        self.resource.name.clone(),
+                    ),
+                )
+            })?;
+
+            let bytecode = remote_chunks.resolve(chunks).or_else(|e| {
                 Err(PyErr::new::<ImportError, _>(
                     py,
                     (
-                        format!("error reading bytecode from {}: {}", path.display(), e),
+                        format!("error fetching remote bytecode: {}", e),
                         self.resource.name.clone(),
                     ),
                 ))
             })?;
 
+            Ok(Some(PyBytes::new(py, &bytecode).into_object()))
+        } else if let Some(path) = self.relative_bytecode_path(optimize_level) {
+            // TODO we could potentially avoid the double allocation for bytecode
+            // by reading directly into a buffer transferred to Python.
+            let bytecode =
+                read_resource_file(self.resource_verifier, self.origin, path).or_else(|e| {
+                    Err(PyErr::new::<ImportError, _>(
+                        py,
+                        (
+                            format!(
+                                "error reading bytecode from {}: {}",
+                                self.origin.join(path).display(),
+                                e
+                            ),
+                            self.resource.name.clone(),
+                        ),
+                    ))
+                })?;
+
             if bytecode.len() < 16 {
                 return Err(PyErr::new::<ImportError, _>(
                     py,
@@ -329,19 +396,21 @@ impl<'a> ImportablePythonModule<'a, u8> {
         }
     }
 
-    /// Obtain the filesystem path to bytecode for this module.
-    fn bytecode_path(&self, optimize_level: OptimizeLevel) -> Option<PathBuf> {
+    /// Obtain the path to bytecode for this module, relative to `origin`.
+    fn relative_bytecode_path(&self, optimize_level: OptimizeLevel) -> Option<&Path> {
         let bytecode_path = match optimize_level {
             OptimizeLevel::Zero => &self.resource.relative_path_module_bytecode,
             OptimizeLevel::One => &self.resource.relative_path_module_bytecode_opt1,
             OptimizeLevel::Two => &self.resource.relative_path_module_bytecode_opt2,
         };
 
-        if let Some(bytecode_path) = bytecode_path {
-            Some(self.origin.join(bytecode_path))
-        } else {
-            None
-        }
+        bytecode_path.as_ref().map(|path| path.as_ref())
+    }
+
+    /// Obtain the filesystem path to bytecode for this module.
+    fn bytecode_path(&self, optimize_level: OptimizeLevel) -> Option<PathBuf> {
+        self.relative_bytecode_path(optimize_level)
+            .map(|path| self.origin.join(path))
     }
 }
 
@@ -361,6 +430,54 @@ where
 
     /// Named resources available for loading.
     pub resources: HashMap<Cow<'a, str>, Resource<'a, X>>,
+
+    /// Source of data stored in remote archives.
+    pub remote_chunks: Option<RemoteChunkSource>,
+
+    /// Verifies signatures of files read from relative paths.
+    ///
+    /// If set, files whose signature doesn't verify are not used.
+    pub resource_verifier: Option<ResourceVerifier>,
+
+    /// Whether extension modules failing to load from memory are extracted
+    /// to a filesystem cache and loaded from there.
+    pub extract_shared_libraries_to_cache: bool,
+}
+
+/// Obtain the names of the components of a relative path.
+///
+/// Returns `None` if the path is empty or has components other than names.
+fn path_components(path: &Path) -> Option<Vec<&str>> {
+    let components = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(s) => s.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(components)
+    }
+}
+
+/// Obtain the directory of a filesystem-relative package, relative to `origin`.
+fn relative_path_package_directory(entry: &Resource<u8>) -> Option<PathBuf> {
+    if let Some(path) = &entry.relative_path_module_source {
+        return path.parent().map(|p| p.to_path_buf());
+    }
+
+    // Bytecode is in the `__pycache__` directory of the package.
+    entry
+        .relative_path_module_bytecode
+        .as_ref()
+        .or_else(|| entry.relative_path_module_bytecode_opt1.as_ref())
+        .or_else(|| entry.relative_path_module_bytecode_opt2.as_ref())
+        .and_then(|path| path.parent())
+        .and_then(|path| path.parent())
+        .map(|path| path.to_path_buf())
 }
 
 impl<'a> Default for PythonResourcesState<'a, u8> {
@@ -369,6 +486,9 @@ impl<'a> Default for PythonResourcesState<'a, u8> {
             current_exe: PathBuf::new(),
             origin: PathBuf::new(),
             resources: HashMap::new(),
+            remote_chunks: None,
+            resource_verifier: None,
+            extract_shared_libraries_to_cache: false,
         }
     }
 }
@@ -387,6 +507,9 @@ impl<'a> PythonResourcesState<'a, u8> {
             current_exe: exe,
             origin,
             resources: Default::default(),
+            remote_chunks: None,
+            resource_verifier: None,
+            extract_shared_libraries_to_cache: false,
         })
     }
 
@@ -434,6 +557,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                         resource,
                         current_exe: &self.current_exe,
                         origin: &self.origin,
+                        remote_chunks: self.remote_chunks.as_ref(),
+                        resource_verifier: self.resource_verifier.as_ref(),
                         flavor: &resource.flavor,
                         is_package: resource.is_package,
                     })
@@ -445,6 +570,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                remote_chunks: self.remote_chunks.as_ref(),
+                resource_verifier: self.resource_verifier.as_ref(),
                 flavor: &resource.flavor,
                 is_package: resource.is_package,
             }),
@@ -452,6 +579,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                remote_chunks: self.remote_chunks.as_ref(),
+                resource_verifier: self.resource_verifier.as_ref(),
                 flavor: &resource.flavor,
                 is_package: resource.is_package,
             }),
@@ -459,6 +588,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                remote_chunks: self.remote_chunks.as_ref(),
+                resource_verifier: self.resource_verifier.as_ref(),
                 flavor: &resource.flavor,
                 is_package: resource.is_package,
             }),
@@ -493,10 +624,22 @@ impl<'a> PythonResourcesState<'a, u8> {
         }
 
         if let Some(resources) = &entry.relative_path_package_resources {
-            if let Some(path) = resources.get(resource_name) {
-                let path = self.origin.join(path);
+            if let Some(relative_path) = resources.get(resource_name) {
+                let path = self.origin.join(relative_path);
                 let io_module = py.import("io")?;
 
+                if let Some(verifier) = &self.resource_verifier {
+                    let data = verifier.read(&self.origin, relative_path).or_else(|e| {
+                        Err(PyErr::new::<OSError, _>(
+                            py,
+                            format!("error reading resource {}: {}", path.display(), e),
+                        ))
+                    })?;
+
+                    let bytes_io = io_module.get(py, "BytesIO")?;
+                    return Ok(Some(bytes_io.call(py, (PyBytes::new(py, &data),), None)?));
+                }
+
                 return Ok(Some(io_module.call(
                     py,
                     "FileIO",
@@ -509,6 +652,128 @@ impl<'a> PythonResourcesState<'a, u8> {
         Ok(None)
     }
 
+    /// Obtain the data of a single named resource in a package.
+    ///
+    /// `Ok(None)` is returned if the resource does not exist. Data of
+    /// resources relative to `origin` is read from the filesystem.
+    pub fn get_package_resource_data(
+        &self,
+        package: &str,
+        resource_name: &str,
+    ) -> Result<Option<Cow<[u8]>>, String> {
+        let entry = match self.resources.get(package) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if let Some(resources) = &entry.in_memory_package_resources {
+            if let Some(data) = resources.get(resource_name) {
+                return Ok(Some(Cow::Borrowed(data.as_ref())));
+            }
+        }
+
+        if let Some(resources) = &entry.relative_path_package_resources {
+            if let Some(relative_path) = resources.get(resource_name) {
+                let path = self.origin.join(relative_path);
+
+                let data = read_resource_file(
+                    self.resource_verifier.as_ref(),
+                    &self.origin,
+                    relative_path,
+                )
+                .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+                return Ok(Some(Cow::Owned(data)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Obtain the names of indexed Python packages.
+    ///
+    /// The names are returned in sorted order.
+    pub fn package_names(&self) -> Vec<&str> {
+        let mut names = self
+            .resources
+            .values()
+            .filter(|r| r.is_package)
+            .map(|r| r.name.as_ref())
+            .collect::<Vec<_>>();
+
+        names.sort();
+
+        names
+    }
+
+    /// Resolve the package whose modules are found in a filesystem path.
+    ///
+    /// The path is either the directory of a filesystem-relative package
+    /// under `origin` or the virtual `__path__` of a package under
+    /// `current_exe`. Returns `None` if the path doesn't correspond to an
+    /// indexed package.
+    pub fn package_for_path(&self, path: &Path) -> Option<&str> {
+        let is_package =
+            |entry: &Resource<u8>| entry.is_package && entry.flavor == ResourceFlavor::Module;
+
+        if let Ok(relative_path) = path.strip_prefix(&self.current_exe) {
+            let components = path_components(relative_path)?;
+
+            return match self.resources.get(components.join(".").as_str()) {
+                Some(entry) if is_package(entry) => Some(entry.name.as_ref()),
+                _ => None,
+            };
+        }
+
+        let relative_path = path.strip_prefix(&self.origin).ok()?;
+        let components = path_components(relative_path)?;
+
+        // Packages are installed under a prefix directory, `lib` by default.
+        // So the package name is made of trailing components and the files
+        // of the package tell where the prefix ends.
+        (0..components.len()).find_map(|start| {
+            match self.resources.get(components[start..].join(".").as_str()) {
+                Some(entry)
+                    if is_package(entry)
+                        && relative_path_package_directory(entry)
+                            .map_or(false, |dir| dir == relative_path) =>
+                {
+                    Some(entry.name.as_ref())
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Obtain the importable modules directly within a package.
+    ///
+    /// Returns tuples of (name, is_package) in sorted order. Only modules and
+    /// extension modules are considered, as built-in and frozen modules
+    /// aren't associated with a package path.
+    pub fn package_modules(
+        &self,
+        package: &str,
+        optimize_level: OptimizeLevel,
+    ) -> Vec<(&str, bool)> {
+        let prefix = format!("{}.", package);
+
+        let mut modules = self
+            .resources
+            .values()
+            .filter(|r| r.flavor == ResourceFlavor::Module || r.flavor == ResourceFlavor::Extension)
+            .filter(|r| r.name.starts_with(&prefix) && !r.name[prefix.len()..].contains('.'))
+            .filter(|r| {
+                self.resolve_importable_module(&r.name, optimize_level)
+                    .is_some()
+            })
+            .map(|r| (r.name.as_ref(), r.is_package))
+            .collect::<Vec<_>>();
+
+        modules.sort();
+
+        modules
+    }
+
     /// Determines whether a specific package + name pair is a known Python package resource.
     pub fn is_package_resource(&self, package: &str, resource_name: &str) -> bool {
         if let Some(entry) = self.resources.get(package) {
@@ -528,26 +793,34 @@ impl<'a> PythonResourcesState<'a, u8> {
         false
     }
 
-    /// Obtain the resources available in a Python package, as a Python list.
+    /// Obtain the names of resources available in a Python package.
     ///
     /// The names are returned in sorted order.
-    pub fn package_resource_names(&self, py: Python, package: &str) -> PyResult<PyObject> {
+    pub fn package_resource_names_list(&self, package: &str) -> Vec<&str> {
         let entry = match self.resources.get(package) {
             Some(entry) => entry,
-            None => return Ok(PyList::new(py, &[]).into_object()),
+            None => return vec![],
         };
 
         let mut names = if let Some(resources) = &entry.in_memory_package_resources {
-            resources.keys().collect()
+            resources.keys().map(|x| x.as_ref()).collect()
         } else if let Some(resources) = &entry.relative_path_package_resources {
-            resources.keys().collect()
+            resources.keys().map(|x| x.as_ref()).collect()
         } else {
             vec![]
         };
 
         names.sort();
 
-        let names = names
+        names
+    }
+
+    /// Obtain the resources available in a Python package, as a Python list.
+    ///
+    /// The names are returned in sorted order.
+    pub fn package_resource_names(&self, py: Python, package: &str) -> PyResult<PyObject> {
+        let names = self
+            .package_resource_names_list(package)
             .iter()
             .map(|x| x.to_py_object(py).into_object())
             .collect::<Vec<PyObject>>();
@@ -673,6 +946,23 @@ impl<'a> PythonResourcesState<'a, u8> {
                         if let Some(resource_relative_path) = resources.get(resource_name_ref) {
                             let resource_path = self.origin.join(resource_relative_path);
 
+                            if let Some(verifier) = &self.resource_verifier {
+                                let data = verifier
+                                    .read(&self.origin, resource_relative_path)
+                                    .or_else(|e| {
+                                        Err(PyErr::new::<OSError, _>(
+                                            py,
+                                            format!(
+                                                "error reading resource {}: {}",
+                                                resource_path.display(),
+                                                e
+                                            ),
+                                        ))
+                                    })?;
+
+                                return Ok(PyBytes::new(py, &data).into_object());
+                            }
+
                             let io_module = py.import("io")?;
 
                             let fh = io_module.call(
@@ -904,6 +1194,19 @@ py_class!(pub class OxidizedResource |py| {
         }
     }
 
+    @property def in_memory_module_data_compressed(&self) -> PyResult<bool> {
+        Ok(self.resource(py).borrow().in_memory_module_data_compressed)
+    }
+
+    @in_memory_module_data_compressed.setter def set_in_memory_module_data_compressed(&self, value: Option<bool>) -> PyResult<()> {
+        if let Some(value) = value {
+            self.resource(py).borrow_mut().in_memory_module_data_compressed = value;
+            Ok(())
+        } else {
+            Err(PyErr::new::<TypeError, _>(py, "cannot delete in_memory_module_data_compressed"))
+        }
+    }
+
     @property def in_memory_source(&self) -> PyResult<Option<PyBytes>> {
         Ok(self.resource(py).borrow().in_memory_source.as_ref().map(|x| PyBytes::new(py, x)))
     }
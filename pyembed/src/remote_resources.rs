@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Fetching of resources data stored in remote archives.
+
+Large applications can move rarely used module bytecode out of the binary
+and into an archive hosted elsewhere. The resources data then references
+content-defined chunks of that archive by offset, length, and SHA-256
+digest. When such a module is imported, its chunks are fetched (by default
+via HTTP range requests), verified against their digest, and stored in a
+per-user cache so subsequent imports don't need the network.
+
+Verification is strict: data whose digest doesn't match is never used,
+whether it came from the network or the cache.
+*/
+
+use {
+    python_packed_resources::data::RemoteChunk,
+    sha2::{Digest, Sha256},
+    std::fs,
+    std::io::{Read, Write},
+    std::net::TcpStream,
+    std::path::{Path, PathBuf},
+    std::time::Duration,
+};
+
+/// Environment variable that can be used to override the cache directory.
+pub const REMOTE_RESOURCES_CACHE_DIR_ENV: &str = "PYOXIDIZER_REMOTE_RESOURCES_CACHE_DIR";
+
+/// A function fetching a byte range of a remote archive.
+///
+/// Receives the URL of the archive, the offset of the first byte to fetch
+/// and the number of bytes to fetch. Returns the fetched data or a
+/// description of the error.
+pub type RemoteChunkFetcher = fn(&str, u64, u64) -> Result<Vec<u8>, String>;
+
+/// Resolve the per-user directory holding fetched chunks.
+pub fn remote_resources_cache_dir() -> PathBuf {
+    if let Some(path) = std::env::var_os(REMOTE_RESOURCES_CACHE_DIR_ENV) {
+        return PathBuf::from(path);
+    }
+
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+
+    match base {
+        Some(base) => base.join("pyoxidizer").join("remote-resources"),
+        None => std::env::temp_dir().join("pyoxidizer-remote-resources"),
+    }
+}
+
+/// Fetch a byte range of a resource via an HTTP range request.
+///
+/// Only `http://` URLs are supported. Integrity of fetched data is ensured
+/// by digest verification, not by the transport. Applications needing
+/// other transports (e.g. HTTPS through a proxy) can provide their own
+/// `RemoteChunkFetcher`.
+pub fn fetch_http_range(url: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+    if length == 0 {
+        return Ok(vec![]);
+    }
+
+    let rest = if url.starts_with("http://") {
+        &url["http://".len()..]
+    } else {
+        return Err(format!("unsupported URL: {}", url));
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let mut stream = TcpStream::connect(&address)
+        .or_else(|e| Err(format!("connecting to {}: {}", address, e)))?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(60)))
+        .or_else(|e| Err(format!("configuring connection: {}", e)))?;
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\nConnection: close\r\n\r\n",
+        path,
+        authority,
+        offset,
+        offset + length - 1
+    );
+    stream
+        .write_all(request.as_bytes())
+        .or_else(|e| Err(format!("sending request to {}: {}", address, e)))?;
+
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .or_else(|e| Err(format!("reading response from {}: {}", address, e)))?;
+
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| "malformed HTTP response".to_string())?;
+    let headers = String::from_utf8_lossy(&response[..header_end]).to_string();
+    let body = &response[header_end + 4..];
+
+    let status = headers
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("");
+
+    if headers.lines().skip(1).any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && !line.ends_with("identity")
+    }) {
+        return Err("unsupported HTTP transfer encoding".to_string());
+    }
+
+    let (start, end) = match status {
+        "206" => (0, length as usize),
+        // The server ignored the range and sent the entire resource.
+        "200" => (offset as usize, (offset + length) as usize),
+        _ => return Err(format!("HTTP request for {} failed: {}", url, status)),
+    };
+
+    if body.len() < end {
+        return Err(format!("HTTP response for {} is truncated", url));
+    }
+
+    Ok(body[start..end].to_vec())
+}
+
+/// Render a digest as hex.
+fn hex_digest(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether data has the given SHA-256 digest.
+fn verify_digest(data: &[u8], digest: &[u8]) -> bool {
+    Sha256::digest(data).as_slice() == digest
+}
+
+/// Resolves chunks of a remote archive.
+#[derive(Clone, Debug)]
+pub struct RemoteChunkSource {
+    /// URL of the remote archive.
+    pub url: String,
+
+    /// Function used to fetch data from the archive.
+    pub fetcher: RemoteChunkFetcher,
+
+    /// Directory holding verified chunks.
+    pub cache_dir: PathBuf,
+}
+
+impl RemoteChunkSource {
+    /// Obtain the data of a single chunk.
+    fn resolve_chunk(&self, chunk: &RemoteChunk) -> Result<Vec<u8>, String> {
+        let path = self.cache_dir.join(hex_digest(&chunk.digest));
+
+        if let Ok(data) = fs::read(&path) {
+            if verify_digest(&data, &chunk.digest) {
+                return Ok(data);
+            }
+        }
+
+        let data = (self.fetcher)(&self.url, chunk.offset, chunk.length as u64)?;
+
+        if !verify_digest(&data, &chunk.digest) {
+            return Err(format!(
+                "chunk at offset {} of {} does not match digest {}",
+                chunk.offset,
+                self.url,
+                hex_digest(&chunk.digest)
+            ));
+        }
+
+        // Failing to cache is not fatal: the chunk will be fetched again.
+        let _ = cache_chunk(&self.cache_dir, &path, &data);
+
+        Ok(data)
+    }
+
+    /// Obtain the concatenated data of a series of chunks.
+    pub fn resolve(&self, chunks: &[RemoteChunk]) -> Result<Vec<u8>, String> {
+        let mut res = Vec::with_capacity(chunks.iter().map(|c| c.length as usize).sum());
+
+        for chunk in chunks {
+            res.extend(self.resolve_chunk(chunk)?);
+        }
+
+        Ok(res)
+    }
+}
+
+/// Write a verified chunk to the cache.
+fn cache_chunk(cache_dir: &Path, path: &Path, data: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(cache_dir)?;
+
+    // Write to a temporary file and rename so concurrent processes never
+    // observe a partially written chunk.
+    let temp_path = cache_dir.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+    fs::write(&temp_path, data)?;
+
+    fs::rename(&temp_path, path).or_else(|e| {
+        let _ = fs::remove_file(&temp_path);
+        Err(e)
+    })
+}
@@ -0,0 +1,150 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Query the resources indexed by an `OxidizedFinder`.
+
+This powers `OxidizedFinder.index`, which allows applications to discover
+embedded packages and their data files at run-time, e.g. to find plugins.
+*/
+
+use {
+    super::importer::ImporterState,
+    cpython::exc::{FileNotFoundError, OSError},
+    cpython::{
+        py_class, PyDict, PyErr, PyList, PyObject, PyResult, PyString, Python, PythonObject,
+        ToPyObject,
+    },
+    sha2::{Digest, Sha256},
+    std::sync::Arc,
+};
+
+// Read-only view of the resources indexed by an `OxidizedFinder`.
+//
+// Names are always returned in sorted order.
+py_class!(class OxidizedResourceIndex |py| {
+    data state: Arc<Box<ImporterState>>;
+
+    def packages(&self) -> PyResult<PyObject> {
+        self.packages_impl(py)
+    }
+
+    def resource_names(&self, package: &PyString, recursive: bool = false) -> PyResult<PyObject> {
+        self.resource_names_impl(py, package, recursive)
+    }
+
+    def resource_info(&self, package: &PyString, name: &PyString) -> PyResult<PyObject> {
+        self.resource_info_impl(py, package, name)
+    }
+});
+
+impl OxidizedResourceIndex {
+    /// Names of indexed packages.
+    fn packages_impl(&self, py: Python) -> PyResult<PyObject> {
+        let state = self.state(py);
+
+        let names = state
+            .get_resources_state()
+            .package_names()
+            .iter()
+            .map(|x| x.to_py_object(py).into_object())
+            .collect::<Vec<PyObject>>();
+
+        Ok(PyList::new(py, &names).into_object())
+    }
+
+    /// Names of data files in a package.
+    ///
+    /// With `recursive`, data files of subpackages are included. Their names
+    /// are prefixed by the path of the subpackage relative to `package`,
+    /// e.g. `sub/data.txt` for `data.txt` of `package.sub`.
+    fn resource_names_impl(
+        &self,
+        py: Python,
+        package: &PyString,
+        recursive: bool,
+    ) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let resources_state = state.get_resources_state();
+        let package = package.to_string(py)?;
+
+        let mut names = resources_state
+            .package_resource_names_list(&package)
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+
+        if recursive {
+            let prefix = format!("{}.", package);
+
+            for subpackage in resources_state.package_names() {
+                if !subpackage.starts_with(&prefix) {
+                    continue;
+                }
+
+                let directory = subpackage[prefix.len()..].replace('.', "/");
+
+                names.extend(
+                    resources_state
+                        .package_resource_names_list(subpackage)
+                        .iter()
+                        .map(|x| format!("{}/{}", directory, x)),
+                );
+            }
+
+            names.sort();
+        }
+
+        let names = names
+            .iter()
+            .map(|x| x.to_py_object(py).into_object())
+            .collect::<Vec<PyObject>>();
+
+        Ok(PyList::new(py, &names).into_object())
+    }
+
+    /// Size and SHA-256 of a data file in a package.
+    ///
+    /// Raises FileNotFoundError if the resource does not exist.
+    fn resource_info_impl(
+        &self,
+        py: Python,
+        package: &PyString,
+        name: &PyString,
+    ) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let package = package.to_string(py)?;
+        let name = name.to_string(py)?;
+
+        let data = state
+            .get_resources_state()
+            .get_package_resource_data(&package, &name)
+            .or_else(|e| Err(PyErr::new::<OSError, _>(py, e)))?
+            .ok_or_else(|| {
+                PyErr::new::<FileNotFoundError, _>(
+                    py,
+                    format!("resource not found: {} in {}", name, package),
+                )
+            })?;
+
+        let info = PyDict::new(py);
+        info.set_item(py, "size", data.len())?;
+        info.set_item(py, "sha256", hex_digest(&data))?;
+
+        Ok(info.into_object())
+    }
+}
+
+/// Obtain an `OxidizedResourceIndex` for an importer.
+pub(crate) fn resource_index(py: Python, state: Arc<Box<ImporterState>>) -> PyResult<PyObject> {
+    Ok(OxidizedResourceIndex::create_instance(py, state)?.into_object())
+}
+
+/// Obtain the hex encoded SHA-256 of data.
+fn hex_digest(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,96 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Verification of signed resource files.
+
+Resources installed next to the binary can be modified after installation,
+unlike resources embedded in it. Such files can be signed at build time with
+an Ed25519 key: each file has a companion file with the `.sig` suffix holding
+the 64 byte signature of its path relative to the resources origin and its
+content. Binding the path prevents a signed file from being substituted for
+another. When a public key is configured, file content is only used if its
+signature verifies, and the verified bytes are the ones used.
+*/
+
+use {
+    ed25519_dalek::{PublicKey, Signature},
+    python_packed_resources::signing::{signed_message, SIGNATURE_SUFFIX},
+    std::convert::TryFrom,
+    std::ffi::OsString,
+    std::path::{Path, PathBuf},
+};
+
+/// Obtain the path of the signature of a file.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut res = OsString::from(path.as_os_str());
+    res.push(SIGNATURE_SUFFIX);
+
+    PathBuf::from(res)
+}
+
+/// Verifies signatures of resource files.
+#[derive(Clone, Debug)]
+pub struct ResourceVerifier {
+    public_key: PublicKey,
+}
+
+impl ResourceVerifier {
+    /// Construct an instance from the bytes of an Ed25519 public key.
+    pub fn new(public_key: &[u8]) -> Result<Self, String> {
+        let public_key = PublicKey::from_bytes(public_key)
+            .or_else(|e| Err(format!("invalid resources verification key: {}", e)))?;
+
+        Ok(Self { public_key })
+    }
+
+    /// Verify the signature of the data of a file at a relative path.
+    pub fn verify(
+        &self,
+        relative_path: &Path,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        let signature =
+            Signature::try_from(signature).or_else(|e| Err(format!("invalid signature: {}", e)))?;
+
+        self.public_key
+            .verify_strict(&signed_message(relative_path, data), &signature)
+            .or_else(|e| Err(format!("signature verification failed: {}", e)))
+    }
+
+    /// Read a file relative to `origin` and verify its signature.
+    ///
+    /// The file is read once and the returned data is the data verified.
+    pub fn read(&self, origin: &Path, relative_path: &Path) -> Result<Vec<u8>, String> {
+        let path = origin.join(relative_path);
+        let data = std::fs::read(&path).or_else(|e| Err(e.to_string()))?;
+
+        let signature_path = signature_path(&path);
+        let signature = std::fs::read(&signature_path).or_else(|e| {
+            Err(format!(
+                "error reading signature from {}: {}",
+                signature_path.display(),
+                e
+            ))
+        })?;
+
+        self.verify(relative_path, &data, &signature)?;
+
+        Ok(data)
+    }
+}
+
+/// Read a resource file, verifying its signature if a verifier is defined.
+pub fn read_resource_file(
+    verifier: Option<&ResourceVerifier>,
+    origin: &Path,
+    relative_path: &Path,
+) -> Result<Vec<u8>, String> {
+    if let Some(verifier) = verifier {
+        verifier.read(origin, relative_path)
+    } else {
+        std::fs::read(origin.join(relative_path)).or_else(|e| Err(e.to_string()))
+    }
+}
@@ -0,0 +1,75 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Locating shared libraries loaded in the process.
+
+Code in plugins and extension modules runs in processes whose executable
+wasn't built by PyOxidizer. Files installed next to these libraries are
+located relative to the library itself.
+*/
+
+use std::path::PathBuf;
+
+/// Obtain the directory of the shared library containing an address.
+///
+/// Passing the address of a function of a plugin library obtains the
+/// directory the plugin was loaded from.
+#[cfg(unix)]
+pub fn library_directory(address: *const libc::c_void) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::dladdr(address, &mut info) } == 0 || info.dli_fname.is_null() {
+        return None;
+    }
+
+    let path = unsafe { std::ffi::CStr::from_ptr(info.dli_fname) };
+    let path = PathBuf::from(std::ffi::OsStr::from_bytes(path.to_bytes()));
+
+    // Paths of libraries loaded via relative paths are relative to the
+    // current directory.
+    let path = std::env::current_dir().ok()?.join(path);
+
+    path.parent().map(|p| p.to_path_buf())
+}
+
+/// Obtain the directory of the shared library containing an address.
+///
+/// Passing the address of a function of a plugin library obtains the
+/// directory the plugin was loaded from.
+#[cfg(windows)]
+pub fn library_directory(address: *const libc::c_void) -> Option<PathBuf> {
+    use {
+        std::os::windows::ffi::OsStringExt,
+        winapi::um::libloaderapi::{
+            GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+            GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+        },
+    };
+
+    let mut module = std::ptr::null_mut();
+
+    if unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            address as *const u16,
+            &mut module,
+        )
+    } == 0
+    {
+        return None;
+    }
+
+    let mut buffer = vec![0u16; 32768];
+    let len = unsafe { GetModuleFileNameW(module, buffer.as_mut_ptr(), buffer.len() as u32) };
+    if len == 0 {
+        return None;
+    }
+
+    let path = PathBuf::from(std::ffi::OsString::from_wide(&buffer[..len as usize]));
+
+    path.parent().map(|p| p.to_path_buf())
+}
@@ -0,0 +1,73 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Reporting of interpreter startup progress.
+
+GUI applications can take a noticeable amount of time between process start
+and displaying a window, as the interpreter initializes and the application
+imports its dependencies. A callback can be registered to be notified of
+startup progress, e.g. to display a splash screen until the application
+signals it is ready.
+*/
+
+use {
+    lazy_static::lazy_static,
+    std::sync::atomic::{AtomicBool, Ordering},
+    std::sync::Mutex,
+};
+
+/// A stage of interpreter startup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StartupProgress {
+    /// Interpreter initialization is starting.
+    InitializingInterpreter,
+
+    /// The interpreter is initialized and application code can run.
+    InterpreterInitialized,
+
+    /// Startup is complete and any splash screen should be dismissed.
+    ///
+    /// Reported once, when the application signals readiness via
+    /// `signal_startup_ready()` or `oxidized_runtime.signal_ready()`. If it
+    /// never does, this is reported when the interpreter is destroyed,
+    /// including when initialization fails.
+    Ready,
+}
+
+/// A function called when interpreter startup progresses.
+///
+/// The function may be called from any thread holding the GIL.
+pub type StartupProgressCallback = fn(StartupProgress);
+
+lazy_static! {
+    static ref CALLBACK: Mutex<Option<StartupProgressCallback>> = Mutex::new(None);
+}
+
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// Register the callback to report progress to.
+pub(crate) fn set_startup_progress_callback(callback: Option<StartupProgressCallback>) {
+    *CALLBACK.lock().unwrap() = callback;
+    READY.store(false, Ordering::SeqCst);
+}
+
+/// Report startup progress to the registered callback.
+pub(crate) fn report_startup_progress(progress: StartupProgress) {
+    // Copy the callback so the lock isn't held while it runs.
+    let callback = *CALLBACK.lock().unwrap();
+
+    if let Some(callback) = callback {
+        callback(progress);
+    }
+}
+
+/// Signal that the application has finished starting.
+///
+/// Reports `StartupProgress::Ready` if it hasn't been reported already.
+pub fn signal_startup_ready() {
+    if !READY.swap(true, Ordering::SeqCst) {
+        report_startup_progress(StartupProgress::Ready);
+    }
+}
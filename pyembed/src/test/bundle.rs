@@ -0,0 +1,81 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{init_bundle_module, MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    anyhow::Result,
+    cpython::exc::ImportError,
+    cpython::{ObjectProtocol, PyErr, PyObject},
+    python_packed_resources::data::{Resource, ResourceFlavor},
+    std::borrow::Cow,
+};
+
+/// Packed resources defining a `bundled_app` module.
+fn bundled_app_resources() -> Result<&'static [u8]> {
+    let resource = Resource {
+        flavor: ResourceFlavor::Module,
+        name: Cow::from("bundled_app"),
+        in_memory_source: Some(Cow::from(b"VALUE = 42\n".to_vec())),
+        ..Resource::default()
+    };
+
+    let mut data = Vec::new();
+    python_packed_resources::writer::write_packed_resources_v1(&[&resource], &mut data, None)?;
+
+    Ok(Box::leak(data.into_boxed_slice()))
+}
+
+#[test]
+fn test_bundle_module() -> Result<()> {
+    let resources = bundled_app_resources()?;
+
+    let config = OxidizedPythonInterpreterConfig::default();
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil()?;
+
+    let sys = py.import("sys").unwrap();
+    let version_info = sys.get(py, "version_info").unwrap();
+    let version = format!(
+        "{}.{}",
+        version_info.getattr(py, "major").unwrap(),
+        version_info.getattr(py, "minor").unwrap()
+    );
+
+    // Bundles built for another version of Python aren't importable.
+    assert!(init_bundle_module(b"bundled_app_bundle\0", resources, "2.7").is_null());
+    let mut err = PyErr::fetch(py);
+    assert!(err.matches(py, py.get_type::<ImportError>()));
+    assert!(err
+        .instance(py)
+        .to_string()
+        .contains("bundled_app_bundle was built for Python 2.7"));
+
+    assert!(py.import("bundled_app").is_err());
+
+    let module = init_bundle_module(b"bundled_app_bundle\0", resources, &version);
+    assert!(!module.is_null());
+    let module = unsafe { PyObject::from_owned_ptr(py, module) };
+    assert_eq!(
+        module.getattr(py, "__name__").unwrap().to_string(),
+        "bundled_app_bundle"
+    );
+
+    let finder = module.getattr(py, "finder").unwrap();
+    assert_eq!(finder.get_type(py).name(py), "OxidizedFinder");
+
+    // The finder doesn't shadow modules of the interpreter.
+    let meta_path = sys.get(py, "meta_path").unwrap();
+    assert_eq!(
+        meta_path.get_item(py, -1).unwrap().as_ptr(),
+        finder.as_ptr()
+    );
+
+    let app = py.import("bundled_app").unwrap();
+    assert_eq!(
+        app.get(py, "VALUE").unwrap().extract::<i64>(py).unwrap(),
+        42
+    );
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    anyhow::Result,
+    cpython::{ObjectProtocol, PyObject},
+    std::path::PathBuf,
+};
+
+#[test]
+fn test_find_library_redirect() -> Result<()> {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.ctypes_library_map = Some(vec![(
+        "pyoxidizer_bundled".to_string(),
+        PathBuf::from("lib").join("libpyoxidizer_bundled.so"),
+    )]);
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+
+    let util = py.import("ctypes.util").unwrap();
+
+    let expected = std::env::current_exe()?
+        .parent()
+        .unwrap()
+        .join("lib")
+        .join("libpyoxidizer_bundled.so");
+    let found = util
+        .call(py, "find_library", ("pyoxidizer_bundled",), None)
+        .unwrap();
+    assert_eq!(
+        found.extract::<String>(py).unwrap(),
+        expected.display().to_string()
+    );
+
+    // Unknown libraries fall back to searching the system.
+    let missing: PyObject = util
+        .call(py, "find_library", ("pyoxidizer_missing_library",), None)
+        .unwrap();
+    assert!(missing.is_none(py));
+
+    // The finder removes itself once ctypes.util is imported.
+    let meta_path = py.import("sys").unwrap().get(py, "meta_path").unwrap();
+    for finder in meta_path.iter(py).unwrap() {
+        assert_ne!(finder.unwrap().get_type(py).name(py), "_PostImportFinder");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::debugpy::parse_listen_address,
+    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    anyhow::Result,
+    cpython::ObjectProtocol,
+};
+
+#[test]
+fn test_parse_listen_address() {
+    assert_eq!(
+        parse_listen_address("5678", false),
+        Ok(("127.0.0.1".to_string(), 5678))
+    );
+    assert_eq!(
+        parse_listen_address("localhost:5678", false),
+        Ok(("localhost".to_string(), 5678))
+    );
+    assert_eq!(
+        parse_listen_address("::1:5678", false),
+        Ok(("::1".to_string(), 5678))
+    );
+    assert!(parse_listen_address(":5678", false).is_err());
+    assert!(parse_listen_address("localhost", false).is_err());
+    assert!(parse_listen_address("localhost:70000", false).is_err());
+}
+
+#[test]
+fn test_parse_listen_address_remote_hosts() {
+    assert!(parse_listen_address("0.0.0.0:5678", false).is_err());
+    assert!(parse_listen_address("192.168.1.2:5678", false).is_err());
+    assert!(parse_listen_address("example.com:5678", false).is_err());
+    assert_eq!(
+        parse_listen_address("0.0.0.0:5678", true),
+        Ok(("0.0.0.0".to_string(), 5678))
+    );
+}
+
+#[test]
+fn test_listener_not_started_without_env() -> Result<()> {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.debugpy_listen_env = Some("PYEMBED_TEST_DEBUGPY_UNSET".to_string());
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+
+    let modules = py.import("sys").unwrap().get(py, "modules").unwrap();
+    assert!(!modules
+        .call_method(py, "__contains__", ("debugpy",), None)
+        .unwrap()
+        .extract::<bool>(py)
+        .unwrap());
+
+    Ok(())
+}
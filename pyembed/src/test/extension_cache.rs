@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::extension_cache::extract_extension_module_to, anyhow::Result, std::fs,
+    std::path::PathBuf,
+};
+
+fn temp_cache_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("pyembed-test-{}", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn extract_and_reuse() -> Result<()> {
+    let cache_dir = temp_cache_dir();
+
+    let path = extract_extension_module_to(&cache_dir, "foo", b"library data").unwrap();
+    assert!(path.starts_with(&cache_dir));
+    assert_eq!(fs::read(&path)?, b"library data");
+
+    // Extracting the same content again yields the same path.
+    let path2 = extract_extension_module_to(&cache_dir, "foo", b"library data").unwrap();
+    assert_eq!(path, path2);
+
+    // Different content is addressed differently.
+    let path3 = extract_extension_module_to(&cache_dir, "foo", b"other data").unwrap();
+    assert_ne!(path, path3);
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn extract_replaces_modified_file() -> Result<()> {
+    let cache_dir = temp_cache_dir();
+
+    let path = extract_extension_module_to(&cache_dir, "foo", b"library data").unwrap();
+    fs::write(&path, b"library dat!")?;
+
+    let path2 = extract_extension_module_to(&cache_dir, "foo", b"library data").unwrap();
+    assert_eq!(path, path2);
+    assert_eq!(fs::read(&path)?, b"library data");
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn extract_uses_sha256_key() -> Result<()> {
+    let cache_dir = temp_cache_dir();
+
+    let path = extract_extension_module_to(&cache_dir, "foo", b"library data").unwrap();
+    assert_eq!(
+        path.parent().unwrap().file_name().unwrap(),
+        "bf77489503bc816f6b601b2ced97c9383087cf78343dc609b7bf3dd4d3ddcd29"
+    );
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_creates_private_files() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cache_dir = temp_cache_dir();
+
+    let path = extract_extension_module_to(&cache_dir, "foo", b"library data").unwrap();
+    assert_eq!(
+        fs::metadata(&cache_dir)?.permissions().mode() & 0o777,
+        0o700
+    );
+    assert_eq!(
+        fs::metadata(path.parent().unwrap())?.permissions().mode() & 0o777,
+        0o700
+    );
+    assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o077, 0);
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_rejects_shared_cache_dir() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cache_dir = temp_cache_dir();
+    fs::create_dir_all(&cache_dir)?;
+    fs::set_permissions(&cache_dir, fs::Permissions::from_mode(0o777))?;
+
+    assert!(extract_extension_module_to(&cache_dir, "foo", b"library data").is_err());
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
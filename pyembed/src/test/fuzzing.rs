@@ -0,0 +1,72 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{OxidizedPythonInterpreterConfig, PythonFuzzTarget},
+    anyhow::Result,
+    cpython::ObjectProtocol,
+};
+
+/// Obtain a deterministic config usable without packed resources.
+///
+/// Test binaries don't embed the standard library, so we have to fall back
+/// to the filesystem importer and default module search paths.
+fn deterministic_config<'a>() -> OxidizedPythonInterpreterConfig<'a> {
+    let mut config = OxidizedPythonInterpreterConfig::new_deterministic(&[]);
+    config.interpreter_config.module_search_paths = None;
+    config.oxidized_importer = false;
+    config.filesystem_importer = true;
+
+    config
+}
+
+#[test]
+fn test_deterministic_interpreter() -> Result<()> {
+    let mut target = PythonFuzzTarget::new(deterministic_config(), "json", "loads")?;
+
+    let py = target.interpreter().acquire_gil().unwrap();
+    let sys = py.import("sys").unwrap();
+    let flags = sys.get(py, "flags").unwrap();
+
+    for (flag, value) in &[
+        ("hash_randomization", 0),
+        ("ignore_environment", 1),
+        ("isolated", 1),
+        ("no_site", 1),
+        ("utf8_mode", 1),
+    ] {
+        assert_eq!(
+            flags
+                .getattr(py, *flag)
+                .unwrap()
+                .extract::<i32>(py)
+                .unwrap(),
+            *value,
+            "sys.flags.{}",
+            flag
+        );
+    }
+
+    let argv = sys.get(py, "argv").unwrap();
+    assert_eq!(argv.len(py).unwrap(), 1);
+    assert_eq!(
+        argv.get_item(py, 0).unwrap().extract::<String>(py).unwrap(),
+        ""
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fuzz_target_run_input() -> Result<()> {
+    let mut target = PythonFuzzTarget::new(deterministic_config(), "json", "loads")?;
+
+    let value = target.run_input(b"[1, 2]").unwrap();
+    let py = target.interpreter().acquire_gil().unwrap();
+    assert_eq!(value.len(py).unwrap(), 2);
+
+    assert!(target.run_input(b"[").is_err());
+
+    Ok(())
+}
@@ -145,6 +145,18 @@ fn importer_module_loading_py() -> Result<()> {
     run_py_test("test_importer_module_loading.py")
 }
 
+/// Run test_importer_path_entry_finder.py.
+#[test]
+fn importer_path_entry_finder_py() -> Result<()> {
+    run_py_test("test_importer_path_entry_finder.py")
+}
+
+/// Run test_importer_resource_index.py.
+#[test]
+fn importer_resource_index_py() -> Result<()> {
+    run_py_test("test_importer_resource_index.py")
+}
+
 /// Run test_importer_resource_reading.py.
 #[test]
 fn importer_resource_reading_py() -> Result<()> {
@@ -3,7 +3,10 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig, PythonInterpreterProfile},
+    crate::{
+        MainPythonInterpreter, OxidizedPythonInterpreterConfig, PythonInterpreterProfile,
+        PythonRunMode, StartupErrorMessages,
+    },
     anyhow::Result,
     cpython::ObjectProtocol,
 };
@@ -56,3 +59,158 @@ fn test_isolated_interpreter() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_python_executable_env() {
+    let key = "PYEMBED_TEST_PYTHON_EXECUTABLE_ENV";
+    let exe = std::env::current_exe().unwrap();
+    std::env::remove_var(key);
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.python_executable_env = Some(key.to_string());
+    config.run = PythonRunMode::None;
+
+    // The first process doesn't set the variable in its own environment.
+    let mut parent = config.clone();
+    parent.apply_python_executable_env(&exe);
+    assert!(std::env::var_os(key).is_none());
+    assert_eq!(parent.interpreter_config.executable, Some(exe.clone()));
+    assert_eq!(parent.interpreter_config.base_executable, Some(exe.clone()));
+    assert_eq!(parent.interpreter_config.parse_argv, None);
+    assert_eq!(parent.run, PythonRunMode::None);
+
+    // A process seeing the variable behaves like `python`.
+    std::env::set_var(key, "1");
+    let mut child = config;
+    child.apply_python_executable_env(&exe);
+    assert_eq!(child.interpreter_config.parse_argv, Some(true));
+    assert_eq!(
+        child.interpreter_config.argv,
+        Some(std::env::args_os().collect())
+    );
+    assert_eq!(child.run, PythonRunMode::Repl);
+
+    std::env::remove_var(key);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_python_executable_env_subprocess() -> Result<()> {
+    let key = "PYEMBED_TEST_PYTHON_EXECUTABLE_ENV_SUBPROCESS";
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.python_executable_env = Some(key.to_string());
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+    assert!(std::env::var_os(key).is_none());
+
+    // Pretend `env` is the current executable so children print their environment.
+    let sys = py.import("sys").unwrap();
+    sys.add(py, "executable", "/usr/bin/env").unwrap();
+
+    let subprocess = py.import("subprocess").unwrap();
+    let run = |args: Vec<&str>| -> String {
+        let kwargs = cpython::PyDict::new(py);
+        kwargs.set_item(py, "capture_output", true).unwrap();
+        kwargs.set_item(py, "text", true).unwrap();
+        subprocess
+            .call(py, "run", (args,), Some(&kwargs))
+            .unwrap()
+            .getattr(py, "stdout")
+            .unwrap()
+            .extract::<String>(py)
+            .unwrap()
+    };
+
+    assert!(run(vec!["/usr/bin/env"]).contains(&format!("{}=1", key)));
+    assert!(!run(vec!["/bin/sh", "-c", "env"]).contains(key));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_python_executable_env_virtualenv() {
+    let key = "PYEMBED_TEST_PYTHON_EXECUTABLE_ENV_VIRTUALENV";
+    let exe = std::env::current_exe().unwrap();
+
+    let venv = std::env::temp_dir().join(format!("pyembed-venv-{}", uuid::Uuid::new_v4()));
+    let site_packages = venv.join("lib").join("python3.8").join("site-packages");
+    std::fs::create_dir_all(&site_packages).unwrap();
+    std::fs::write(venv.join("pyvenv.cfg"), b"").unwrap();
+    std::env::set_var(key, &venv);
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.python_executable_env = Some(key.to_string());
+    config.filesystem_importer = false;
+    config.apply_python_executable_env(&exe);
+
+    assert_eq!(config.interpreter_config.prefix, Some(venv.clone()));
+    assert_eq!(config.interpreter_config.exec_prefix, Some(venv.clone()));
+    assert_eq!(
+        config.interpreter_config.executable,
+        Some(venv.join("bin").join("python"))
+    );
+    assert_eq!(config.interpreter_config.base_executable, Some(exe));
+    assert_eq!(
+        config.interpreter_config.python_path_env,
+        Some(site_packages.display().to_string())
+    );
+    assert!(config.filesystem_importer);
+
+    std::env::remove_var(key);
+    std::fs::remove_dir_all(&venv).unwrap();
+}
+
+#[test]
+fn test_startup_error_messages() {
+    let messages = StartupErrorMessages {
+        template: "{exe_name} failed to start: {error}".to_string(),
+        catalog: vec![
+            (
+                "de".to_string(),
+                "{exe_name} konnte nicht starten: {error}".to_string(),
+            ),
+            (
+                "pt-BR".to_string(),
+                "{exe_name} falhou ao iniciar: {error}".to_string(),
+            ),
+            ("pt".to_string(), "{exe_name} falhou: {error}".to_string()),
+        ],
+    };
+
+    assert_eq!(messages.resolve_template(None), messages.template);
+    assert_eq!(messages.resolve_template(Some("C")), messages.template);
+    assert_eq!(
+        messages.resolve_template(Some("fr_FR.UTF-8")),
+        messages.template
+    );
+    assert_eq!(
+        messages.resolve_template(Some("de_AT.UTF-8@euro")),
+        messages.catalog[0].1
+    );
+    assert_eq!(
+        messages.resolve_template(Some("pt_BR")),
+        messages.catalog[1].1
+    );
+    assert_eq!(
+        messages.resolve_template(Some("pt_PT")),
+        messages.catalog[2].1
+    );
+
+    let exe = std::path::Path::new("/opt/myapp/myapp");
+    assert_eq!(
+        messages.render("missing {exe}", exe, Some("de_DE")),
+        "myapp konnte nicht starten: missing {exe}"
+    );
+
+    let messages = StartupErrorMessages {
+        template: "see {exe}".to_string(),
+        catalog: vec![],
+    };
+    assert_eq!(
+        messages.render("error", exe, None),
+        format!("see {}", exe.display())
+    );
+}
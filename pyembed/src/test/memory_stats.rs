@@ -0,0 +1,55 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        MainPythonInterpreter, MemoryStats, OxidizedPythonInterpreterConfig, PythonRawAllocator,
+    },
+    anyhow::Result,
+    cpython::{ObjectProtocol, PyDict},
+};
+
+#[test]
+fn test_memory_stats_module() -> Result<()> {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.raw_allocator = Some(PythonRawAllocator {
+        stats: true,
+        ..PythonRawAllocator::system()
+    });
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+
+    let module = py.import("oxidized_runtime").unwrap();
+    let stats = module
+        .call(py, "memory_stats", cpython::NoArgs, None)
+        .unwrap();
+    let stats = stats.cast_into::<PyDict>(py).unwrap();
+
+    let malloc_count = stats
+        .get_item(py, "malloc_count")
+        .unwrap()
+        .extract::<u64>(py)
+        .unwrap();
+    assert!(malloc_count > 0);
+    assert!(stats.get_item(py, "bytes_requested").is_some());
+    assert!(stats.get_item(py, "peak_rss").is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_memory_stats_text() {
+    let stats = MemoryStats {
+        malloc_count: 2,
+        bytes_requested: 64,
+        ..MemoryStats::default()
+    };
+
+    assert_eq!(
+        stats.to_text(),
+        "malloc_count: 2\ncalloc_count: 0\nrealloc_count: 0\nfree_count: 0\n\
+         bytes_requested: 64\npeak_rss: unknown\n"
+    );
+}
@@ -2,5 +2,17 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod bundle;
+mod ctypes_util;
+mod debugpy;
+mod extension_cache;
+mod fuzzing;
 mod importer;
 mod interpreter_config;
+mod memory_stats;
+mod plugin;
+mod remote_resources;
+mod resource_verification;
+mod startup_cache;
+mod startup_progress;
+mod zipimport;
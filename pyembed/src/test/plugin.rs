@@ -0,0 +1,134 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        library_directory, OxidizedPythonInterpreterConfig, PluginType, PluginValue, PythonPlugin,
+    },
+    anyhow::Result,
+    std::sync::Arc,
+};
+
+/// Obtain a config usable without packed resources.
+fn plugin_config<'a>() -> OxidizedPythonInterpreterConfig<'a> {
+    let mut config = OxidizedPythonInterpreterConfig::new_deterministic(&[]);
+    config.interpreter_config.module_search_paths = None;
+    config.oxidized_importer = false;
+    config.filesystem_importer = true;
+
+    config
+}
+
+#[test]
+fn test_plugin_value_conversions() {
+    assert_eq!(PluginValue::Int(-2).as_i64(), -2);
+    assert_eq!(PluginValue::UInt(2).as_i64(), 2);
+    assert_eq!(PluginValue::Float(1.5).as_f64(), 1.5);
+    assert_eq!(PluginValue::Float(1.5).as_i64(), 0);
+    assert!(PluginValue::Bool(true).as_bool());
+    assert_eq!(PluginValue::Pointer(42).as_pointer(), 42);
+    assert_eq!(
+        PluginValue::zero(PluginType::Float),
+        PluginValue::Float(0.0)
+    );
+    assert_eq!(PluginValue::zero(PluginType::Void), PluginValue::None);
+}
+
+#[test]
+fn test_plugin_call() -> Result<()> {
+    let plugin = PythonPlugin::new(
+        plugin_config(),
+        &[
+            ("plugin_max", "builtins:max"),
+            ("plugin_sqrt", "math:sqrt"),
+            ("plugin_truth", "operator:truth"),
+        ],
+    )?;
+
+    assert_eq!(
+        plugin.call(
+            "plugin_max",
+            &[PluginValue::Int(1), PluginValue::Int(3)],
+            PluginType::Int
+        ),
+        PluginValue::Int(3)
+    );
+    assert_eq!(
+        plugin.call("plugin_sqrt", &[PluginValue::Float(4.0)], PluginType::Float),
+        PluginValue::Float(2.0)
+    );
+    assert_eq!(
+        plugin.call(
+            "plugin_truth",
+            &[PluginValue::String(Some("A".to_string()))],
+            PluginType::Bool
+        ),
+        PluginValue::Bool(true)
+    );
+
+    // Exceptions result in zero values.
+    assert_eq!(
+        plugin.call(
+            "plugin_sqrt",
+            &[PluginValue::Float(-1.0)],
+            PluginType::Float
+        ),
+        PluginValue::Float(0.0)
+    );
+    assert_eq!(
+        plugin.call("unknown", &[], PluginType::Int),
+        PluginValue::Int(0)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_plugin_call_threads() -> Result<()> {
+    struct SharedPlugin(PythonPlugin<'static, 'static, 'static>);
+
+    // Calls acquire the GIL, so hosts may call functions from any thread.
+    unsafe impl Send for SharedPlugin {}
+    unsafe impl Sync for SharedPlugin {}
+
+    let plugin = Arc::new(SharedPlugin(PythonPlugin::new(
+        plugin_config(),
+        &[("plugin_sqrt", "math:sqrt")],
+    )?));
+
+    let threads = (0..4)
+        .map(|i| {
+            let plugin = plugin.clone();
+
+            std::thread::spawn(move || {
+                plugin.0.call(
+                    "plugin_sqrt",
+                    &[PluginValue::Float((i * i) as f64)],
+                    PluginType::Float,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (i, thread) in threads.into_iter().enumerate() {
+        assert_eq!(thread.join().unwrap(), PluginValue::Float(i as f64));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_plugin_unknown_callable() {
+    assert!(PythonPlugin::new(plugin_config(), &[("plugin_missing", "math:missing")]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_library_directory() {
+    let address = test_library_directory as *const libc::c_void;
+    let exe = std::env::current_exe().unwrap().canonicalize().unwrap();
+
+    let directory = library_directory(address).unwrap().canonicalize().unwrap();
+    assert_eq!(Some(directory.as_path()), exe.parent());
+}
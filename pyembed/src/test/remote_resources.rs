@@ -0,0 +1,135 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::remote_resources::{fetch_http_range, RemoteChunkSource},
+    anyhow::Result,
+    python_packed_resources::data::RemoteChunk,
+    sha2::{Digest, Sha256},
+    std::borrow::Cow,
+    std::fs,
+    std::io::{Read, Write},
+    std::net::TcpListener,
+    std::path::PathBuf,
+};
+
+const ARCHIVE: &[u8] = b"first chunk|second chunk";
+
+fn temp_cache_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("pyembed-test-{}", uuid::Uuid::new_v4()))
+}
+
+fn chunk(offset: u64, data: &[u8]) -> RemoteChunk<'static> {
+    RemoteChunk {
+        offset,
+        length: data.len() as u32,
+        digest: Cow::Owned(Sha256::digest(data).to_vec()),
+    }
+}
+
+fn fetch_archive(url: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+    assert_eq!(url, "test://archive");
+    Ok(ARCHIVE[offset as usize..(offset + length) as usize].to_vec())
+}
+
+fn fetch_unavailable(_url: &str, _offset: u64, _length: u64) -> Result<Vec<u8>, String> {
+    Err("unavailable".to_string())
+}
+
+fn fetch_corrupted(url: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+    let mut data = fetch_archive(url, offset, length)?;
+    data[0] ^= 0xff;
+    Ok(data)
+}
+
+#[test]
+fn resolve_and_cache() -> Result<()> {
+    let cache_dir = temp_cache_dir();
+    let chunks = vec![chunk(0, b"first chunk|"), chunk(12, b"second chunk")];
+
+    let source = RemoteChunkSource {
+        url: "test://archive".to_string(),
+        fetcher: fetch_archive,
+        cache_dir: cache_dir.clone(),
+    };
+    assert_eq!(source.resolve(&chunks).unwrap(), ARCHIVE);
+
+    // Chunks are served from the cache once fetched.
+    let source = RemoteChunkSource {
+        fetcher: fetch_unavailable,
+        ..source
+    };
+    assert_eq!(source.resolve(&chunks).unwrap(), ARCHIVE);
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn digest_mismatch() -> Result<()> {
+    let cache_dir = temp_cache_dir();
+    let chunks = vec![chunk(0, b"first chunk|")];
+
+    let source = RemoteChunkSource {
+        url: "test://archive".to_string(),
+        fetcher: fetch_corrupted,
+        cache_dir: cache_dir.clone(),
+    };
+    assert!(source
+        .resolve(&chunks)
+        .unwrap_err()
+        .contains("does not match digest"));
+
+    // Chunks failing verification are not cached.
+    assert!(!cache_dir.exists() || fs::read_dir(&cache_dir)?.next().is_none());
+
+    // Corrupted cache entries are not used.
+    let source = RemoteChunkSource {
+        fetcher: fetch_archive,
+        ..source
+    };
+    source.resolve(&chunks).unwrap();
+    let cached = fs::read_dir(&cache_dir)?.next().unwrap()?.path();
+    fs::write(&cached, b"tampered")?;
+    assert_eq!(source.resolve(&chunks).unwrap(), b"first chunk|");
+
+    fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn http_range() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let count = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..count]);
+        }
+
+        stream
+            .write_all(b"HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\n\r\nsecond")
+            .unwrap();
+
+        String::from_utf8(request).unwrap()
+    });
+
+    let data = fetch_http_range(&format!("http://{}/archive", address), 12, 6).unwrap();
+    assert_eq!(data, b"second");
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("GET /archive HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nRange: bytes=12-17\r\n"));
+
+    assert!(fetch_http_range("https://example.com/archive", 0, 1).is_err());
+
+    Ok(())
+}
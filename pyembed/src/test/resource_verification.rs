@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::resource_verification::{read_resource_file, signature_path, ResourceVerifier},
+    anyhow::Result,
+    ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey},
+    python_packed_resources::signing::signed_message,
+    std::fs,
+    std::path::{Path, PathBuf},
+};
+
+fn temp_dir() -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("pyembed-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&path)?;
+
+    Ok(path)
+}
+
+fn keys() -> (SecretKey, PublicKey) {
+    let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+
+    (secret, public)
+}
+
+fn write_signed(origin: &Path, relative_path: &str, data: &[u8]) -> Result<()> {
+    let (secret, public) = keys();
+    let signature = ExpandedSecretKey::from(&secret)
+        .sign(&signed_message(Path::new(relative_path), data), &public);
+
+    let path = origin.join(relative_path);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, data)?;
+    fs::write(signature_path(&path), &signature.to_bytes()[..])?;
+
+    Ok(())
+}
+
+#[test]
+fn signature_path_suffix() {
+    assert_eq!(
+        signature_path(Path::new("lib/foo.pyc")),
+        PathBuf::from("lib/foo.pyc.sig")
+    );
+}
+
+#[test]
+fn verify_files() -> Result<()> {
+    let dir = temp_dir()?;
+    let verifier = ResourceVerifier::new(keys().1.as_bytes()).unwrap();
+
+    let path = Path::new("lib/foo.py");
+    write_signed(&dir, "lib/foo.py", b"print('hello')")?;
+    assert_eq!(verifier.read(&dir, path).unwrap(), b"print('hello')");
+    assert_eq!(
+        read_resource_file(Some(&verifier), &dir, path).unwrap(),
+        b"print('hello')"
+    );
+
+    // Modified content is rejected.
+    fs::write(dir.join(path), b"print('tampered')")?;
+    assert!(verifier.read(&dir, path).is_err());
+    assert!(read_resource_file(Some(&verifier), &dir, path).is_err());
+    assert_eq!(
+        read_resource_file(None, &dir, path).unwrap(),
+        b"print('tampered')"
+    );
+
+    // Files without a signature are rejected.
+    fs::write(dir.join("unsigned.py"), b"print('hello')")?;
+    assert!(verifier
+        .read(&dir, Path::new("unsigned.py"))
+        .unwrap_err()
+        .contains("error reading signature"));
+
+    // Signatures from another key are rejected.
+    let other = ResourceVerifier::new(
+        PublicKey::from(&SecretKey::from_bytes(&[8; 32]).unwrap()).as_bytes(),
+    )
+    .unwrap();
+    write_signed(&dir, "lib/foo.py", b"print('hello')")?;
+    assert!(other.read(&dir, path).is_err());
+
+    // A signed file copied to another path along with its signature is rejected.
+    write_signed(&dir, "lib/bar.py", b"print('bar')")?;
+    fs::copy(dir.join("lib/bar.py"), dir.join(path))?;
+    fs::copy(
+        signature_path(&dir.join("lib/bar.py")),
+        signature_path(&dir.join(path)),
+    )?;
+    assert!(verifier.read(&dir, path).is_err());
+    assert_eq!(
+        verifier.read(&dir, Path::new("lib/bar.py")).unwrap(),
+        b"print('bar')"
+    );
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn invalid_key() {
+    assert!(ResourceVerifier::new(&[0; 31]).is_err());
+}
@@ -0,0 +1,27 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    anyhow::Result,
+    cpython::ObjectProtocol,
+};
+
+#[test]
+fn test_startup_cache_module_imported() -> Result<()> {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.startup_cache_module = Some("json".to_string());
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+
+    let modules = py.import("sys").unwrap().get(py, "modules").unwrap();
+    assert!(modules
+        .call_method(py, "__contains__", ("json",), None)
+        .unwrap()
+        .extract::<bool>(py)
+        .unwrap());
+
+    Ok(())
+}
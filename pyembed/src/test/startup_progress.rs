@@ -0,0 +1,47 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig, StartupProgress},
+    anyhow::Result,
+    cpython::NoArgs,
+    lazy_static::lazy_static,
+    std::sync::Mutex,
+};
+
+lazy_static! {
+    static ref EVENTS: Mutex<Vec<StartupProgress>> = Mutex::new(Vec::new());
+}
+
+fn record(progress: StartupProgress) {
+    EVENTS.lock().unwrap().push(progress);
+}
+
+#[test]
+fn test_startup_progress() -> Result<()> {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.startup_progress_callback = Some(record);
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        vec![
+            StartupProgress::InitializingInterpreter,
+            StartupProgress::InterpreterInitialized
+        ]
+    );
+
+    let py = interp.acquire_gil().unwrap();
+    let module = py.import("oxidized_runtime").unwrap();
+    module.call(py, "signal_ready", NoArgs, None).unwrap();
+    module.call(py, "signal_ready", NoArgs, None).unwrap();
+    assert_eq!(EVENTS.lock().unwrap().last(), Some(&StartupProgress::Ready));
+
+    std::mem::drop(interp);
+
+    // Readiness is only reported once.
+    assert_eq!(EVENTS.lock().unwrap().len(), 3);
+
+    Ok(())
+}
@@ -107,6 +107,63 @@ class TestImporterResourceScanning(unittest.TestCase):
         self.assertEqual(metadata["Name"], "my_package")
         self.assertEqual(metadata["Version"], "1.0")
 
+    def test_find_distributions_name(self):
+        self._write_metadata()
+        f = self._finder_from_td()
+
+        Context = importlib.metadata.DistributionFinder.Context
+
+        # Names are normalized as described by PEP 503.
+        for name in ("my_package", "My-Package", "my.package", "MY__package"):
+            dists = f.find_distributions(Context(name=name))
+            self.assertEqual(len(dists), 1, name)
+            self.assertEqual(dists[0].version, "1.0")
+
+        self.assertEqual(f.find_distributions(Context(name="other")), [])
+        self.assertEqual(len(f.find_distributions(Context())), 1)
+
+    def test_importlib_metadata_version(self):
+        self._write_metadata()
+        f = self._finder_from_td()
+
+        sys.meta_path.insert(0, f)
+        try:
+            self.assertEqual(importlib.metadata.version("My-Package"), "1.0")
+        finally:
+            sys.meta_path.remove(f)
+
+    def test_files_missing(self):
+        self._write_metadata()
+        f = self._finder_from_td()
+
+        self.assertIsNone(f.find_distributions()[0].files)
+
+    def test_files(self):
+        self._write_metadata()
+
+        record_path = self.td / "my_package-1.0.dist-info" / "RECORD"
+        with record_path.open("w", encoding="utf-8") as fh:
+            fh.write("my_package/__init__.py,sha256=abc,42\n")
+            fh.write("my_package-1.0.dist-info/RECORD,,\n")
+
+        f = self._finder_from_td()
+        d = f.find_distributions()[0]
+
+        files = d.files
+        self.assertIsInstance(files, list)
+        self.assertEqual(len(files), 2)
+
+        self.assertIsInstance(files[0], importlib.metadata.PackagePath)
+        self.assertEqual(str(files[0]), "my_package/__init__.py")
+        self.assertEqual(files[0].hash.mode, "sha256")
+        self.assertEqual(files[0].hash.value, "abc")
+        self.assertEqual(files[0].size, 42)
+        self.assertIs(files[0].dist, d)
+
+        self.assertEqual(str(files[1]), "my_package-1.0.dist-info/RECORD")
+        self.assertIsNone(files[1].hash)
+        self.assertIsNone(files[1].size)
+
     def test_version(self):
         self._write_metadata()
         f = self._finder_from_td()
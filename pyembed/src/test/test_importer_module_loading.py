@@ -13,6 +13,7 @@ import unittest
 
 from oxidized_importer import (
     OxidizedFinder,
+    OxidizedResource,
     OxidizedResourceCollector,
     find_resources_in_path,
 )
@@ -143,6 +144,81 @@ class TestImporterModuleLoading(unittest.TestCase):
         with self.assertRaises(ImportError):
             f.get_filename("my_package")
 
+    def test_filesystem_relative_bytecode_filename(self):
+        p = self._make_package("my_package")
+
+        with (p / "a.py").open("wb") as fh:
+            fh.write(b"def f():\n    pass\n")
+
+        (p / "__pycache__").mkdir()
+        with (
+            p / "__pycache__" / ("a.%s.pyc" % sys.implementation.cache_tag)
+        ).open("wb") as fh:
+            fh.write(b"0123456789abcdef")
+
+            code = compile("def f():\n    pass\n", "my_package.a", "exec")
+            fh.write(marshal.dumps(code))
+
+        resource = OxidizedResource()
+        resource.name = "my_package.a"
+        resource.flavor = "module"
+        resource.relative_path_module_source = pathlib.Path("my_package", "a.py")
+        resource.relative_path_module_bytecode = pathlib.Path(
+            "my_package",
+            "__pycache__",
+            "a.%s.pyc" % sys.implementation.cache_tag,
+        )
+
+        f = OxidizedFinder(relative_path_origin=str(self.td))
+        f.add_resource(resource)
+
+        # Code objects refer to the source file, like with SourceFileLoader.
+        self.assertEqual(f.get_code("my_package.a").co_filename, str(p / "a.py"))
+
+        spec = f.find_spec("my_package.a", None)
+        m = importlib.util.module_from_spec(spec)
+        self.assertIsNone(f.exec_module(m))
+        self.assertEqual(m.__file__, str(p / "a.py"))
+        self.assertEqual(m.f.__code__.co_filename, str(p / "a.py"))
+
+    def test_compressed_module_data(self):
+        def zstd_frame(data):
+            # A zstd frame holding a single uncompressed block.
+            assert len(data) < 256
+            block_header = (1 | len(data) << 3).to_bytes(3, "little")
+            return b"\x28\xb5\x2f\xfd\x20" + bytes([len(data)]) + block_header + data
+
+        source = b"import io\nvalue = 42\n"
+
+        resource = OxidizedResource()
+        resource.name = "my_module"
+        resource.flavor = "module"
+        resource.in_memory_source = zstd_frame(source)
+        resource.in_memory_bytecode = zstd_frame(
+            marshal.dumps(compile(source, "my_module", "exec"))
+        )
+        resource.in_memory_module_data_compressed = True
+        self.assertTrue(resource.in_memory_module_data_compressed)
+
+        f = OxidizedFinder()
+        f.add_resource(resource)
+
+        self.assertEqual(f.get_source("my_module"), source.decode("utf-8"))
+
+        spec = f.find_spec("my_module", None)
+        m = importlib.util.module_from_spec(spec)
+        self.assertIsNone(f.exec_module(m))
+        self.assertEqual(m.value, 42)
+
+        # Data which isn't a zstd frame fails to import.
+        resource.name = "my_module_bad"
+        resource.in_memory_source = source
+        resource.in_memory_bytecode = None
+        f.add_resource(resource)
+
+        with self.assertRaises(ImportError):
+            f.get_source("my_module_bad")
+
 
 if __name__ == "__main__":
     # Reset command arguments so test runner isn't confused.
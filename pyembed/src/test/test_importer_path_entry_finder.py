@@ -0,0 +1,155 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+import os
+import pathlib
+import pkgutil
+import sys
+import tempfile
+import unittest
+
+from oxidized_importer import (
+    OxidizedFinder,
+    OxidizedResource,
+    OxidizedResourceCollector,
+    find_resources_in_path,
+)
+
+
+class TestImporterPathEntryFinder(unittest.TestCase):
+    def setUp(self):
+        self.raw_temp_dir = tempfile.TemporaryDirectory(
+            prefix="oxidized_importer-test-"
+        )
+        self.td = pathlib.Path(self.raw_temp_dir.name)
+
+    def tearDown(self):
+        self.raw_temp_dir.cleanup()
+        del self.raw_temp_dir
+        del self.td
+
+    def _make_package(self, name, prefix=""):
+        package_path = self.td / prefix
+
+        for part in name.split("."):
+            package_path = package_path / part
+            package_path.mkdir(exist_ok=True)
+
+            with (package_path / "__init__.py").open("wb"):
+                pass
+
+        return package_path
+
+    def _finder_from_td(self):
+        collector = OxidizedResourceCollector(policy="in-memory-only")
+        for r in find_resources_in_path(self.td):
+            collector.add_in_memory(r)
+
+        f = OxidizedFinder()
+        f.add_resources(collector.oxidize()[0])
+
+        return f
+
+    def _filesystem_resource(self, name, is_package=False, prefix=""):
+        resource = OxidizedResource()
+        resource.name = name
+        resource.flavor = "module"
+        resource.is_package = is_package
+
+        path = pathlib.Path(prefix, *name.split("."))
+        if is_package:
+            path = path / "__init__.py"
+        else:
+            path = path.with_suffix(".py")
+
+        resource.relative_path_module_source = path
+
+        return resource
+
+    def test_unknown_path(self):
+        f = OxidizedFinder()
+
+        with self.assertRaises(ImportError):
+            f.path_hook(str(self.td))
+
+        with self.assertRaises(ImportError):
+            f.path_hook(sys.argv[0])
+
+    def test_in_memory_package(self):
+        p = self._make_package("my_package.sub")
+        with (p.parent / "a.py").open("wb"):
+            pass
+
+        f = self._finder_from_td()
+
+        spec = f.find_spec("my_package", None)
+        finder = f.path_hook(spec.submodule_search_locations[0])
+
+        spec = finder.find_spec("my_package.a")
+        self.assertEqual(spec.name, "my_package.a")
+        self.assertEqual(spec.loader, f)
+
+        self.assertIsNone(finder.find_spec("my_package.missing"))
+        self.assertIsNone(finder.find_spec("my_package.sub.missing"))
+        self.assertIsNone(finder.find_spec("other.a"))
+
+        self.assertEqual(finder.iter_modules(), [("a", False), ("sub", True)])
+        self.assertEqual(
+            finder.iter_modules("my_package."),
+            [("my_package.a", False), ("my_package.sub", True)],
+        )
+
+    def test_filesystem_relative_package(self):
+        # Packages are installed in the lib directory by default.
+        p = self._make_package("my_package.sub", prefix="lib")
+        with (p.parent / "a.py").open("wb") as fh:
+            fh.write(b"value = 42\n")
+
+        f = OxidizedFinder(relative_path_origin=str(self.td))
+        f.add_resources(
+            [
+                self._filesystem_resource("my_package", is_package=True, prefix="lib"),
+                self._filesystem_resource("my_package.a", prefix="lib"),
+                self._filesystem_resource(
+                    "my_package.sub", is_package=True, prefix="lib"
+                ),
+            ]
+        )
+
+        finder = f.path_hook(str(p.parent))
+
+        spec = finder.find_spec("my_package.a")
+        self.assertEqual(spec.loader, f)
+        self.assertEqual(spec.origin, str(p.parent / "a.py"))
+
+        finder = f.path_hook(str(p))
+        self.assertIsNone(finder.find_spec("my_package.a"))
+
+        # Paths not matching where the package is installed are unknown.
+        with self.assertRaises(ImportError):
+            f.path_hook(str(self.td / "my_package"))
+
+        with self.assertRaises(ImportError):
+            f.path_hook(str(self.td / "lib"))
+
+    def test_pkgutil_iter_modules(self):
+        p = self._make_package("my_package")
+        with (p / "a.py").open("wb"):
+            pass
+
+        f = self._finder_from_td()
+        path = os.path.join(sys.argv[0], "my_package")
+
+        sys.path_hooks.insert(0, f.path_hook)
+        try:
+            modules = [(m.name, m.ispkg) for m in pkgutil.iter_modules([path])]
+        finally:
+            sys.path_hooks.remove(f.path_hook)
+            sys.path_importer_cache.pop(path, None)
+
+        self.assertEqual(modules, [("a", False)])
+
+
+if __name__ == "__main__":
+    unittest.main()
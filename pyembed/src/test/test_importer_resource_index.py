@@ -0,0 +1,100 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+import hashlib
+import pathlib
+import tempfile
+import unittest
+
+from oxidized_importer import (
+    OxidizedFinder,
+    OxidizedResourceCollector,
+    find_resources_in_path,
+)
+
+
+class TestImporterResourceIndex(unittest.TestCase):
+    def setUp(self):
+        self.raw_temp_dir = tempfile.TemporaryDirectory(
+            prefix="oxidized_importer-test-"
+        )
+        self.td = pathlib.Path(self.raw_temp_dir.name)
+
+    def tearDown(self):
+        self.raw_temp_dir.cleanup()
+        del self.raw_temp_dir
+        del self.td
+
+    def _make_package(self, name):
+        package_path = self.td
+
+        for part in name.split("."):
+            package_path = package_path / part
+
+            package_path.mkdir(exist_ok=True)
+
+            with (package_path / "__init__.py").open("wb"):
+                pass
+
+        return package_path
+
+    def _finder_from_td(self):
+        collector = OxidizedResourceCollector(policy="in-memory-only")
+        for r in find_resources_in_path(self.td):
+            collector.add_in_memory(r)
+
+        f = OxidizedFinder()
+        f.add_resources(collector.oxidize()[0])
+
+        return f
+
+    def test_packages(self):
+        self._make_package("plugins.b")
+        self._make_package("plugins.a")
+
+        with (self.td / "module.py").open("wb"):
+            pass
+
+        f = self._finder_from_td()
+
+        self.assertEqual(f.index.packages(), ["plugins", "plugins.a", "plugins.b"])
+
+    def test_resource_names(self):
+        package_path = self._make_package("plugins.a")
+
+        (package_path / "b.txt").write_bytes(b"b")
+        (package_path / "a.txt").write_bytes(b"a")
+        (package_path.parent / "top.txt").write_bytes(b"top")
+
+        f = self._finder_from_td()
+
+        self.assertEqual(f.index.resource_names("plugins.a"), ["a.txt", "b.txt"])
+        self.assertEqual(f.index.resource_names("plugins"), ["top.txt"])
+        self.assertEqual(
+            f.index.resource_names("plugins", recursive=True),
+            ["a/a.txt", "a/b.txt", "top.txt"],
+        )
+        self.assertEqual(f.index.resource_names("missing"), [])
+
+    def test_resource_info(self):
+        package_path = self._make_package("plugins")
+
+        (package_path / "data.txt").write_bytes(b"hello, world")
+
+        f = self._finder_from_td()
+
+        self.assertEqual(
+            f.index.resource_info("plugins", "data.txt"),
+            {
+                "size": 12,
+                "sha256": hashlib.sha256(b"hello, world").hexdigest(),
+            },
+        )
+
+        with self.assertRaises(FileNotFoundError):
+            f.index.resource_info("plugins", "missing.txt")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -36,6 +36,7 @@ class TestImporterResources(unittest.TestCase):
 
         self.assertFalse(resource.is_package)
         self.assertFalse(resource.is_namespace_package)
+        self.assertFalse(resource.in_memory_module_data_compressed)
         self.assertIsNone(resource.in_memory_source)
         self.assertIsNone(resource.in_memory_bytecode)
         self.assertIsNone(resource.in_memory_bytecode_opt1)
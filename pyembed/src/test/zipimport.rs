@@ -0,0 +1,86 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    anyhow::Result,
+    cpython::{NoArgs, ObjectProtocol},
+    std::path::PathBuf,
+};
+
+#[test]
+fn test_zipimport_archive_in_sys_path() -> Result<()> {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.zipimport_archive = Some(PathBuf::from("modules.zip"));
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+
+    let sys_path = py.import("sys").unwrap().get(py, "path").unwrap();
+    let first = sys_path
+        .get_item(py, 0)
+        .unwrap()
+        .extract::<String>(py)
+        .unwrap();
+
+    let expected = std::env::current_exe()?
+        .parent()
+        .unwrap()
+        .join("modules.zip");
+    assert_eq!(first, expected.display().to_string());
+
+    Ok(())
+}
+
+#[test]
+fn test_zipimport_without_filesystem_importer() -> Result<()> {
+    let origin = std::env::temp_dir().join(format!("pyembed-zipimport-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&origin)?;
+
+    // The archive is written by an interpreter having the stdlib available.
+    {
+        let mut interp = MainPythonInterpreter::new(OxidizedPythonInterpreterConfig::default())?;
+        let py = interp.acquire_gil().unwrap();
+
+        let zipfile = py.import("zipfile").unwrap();
+        let archive = zipfile
+            .call(
+                py,
+                "ZipFile",
+                (origin.join("modules.zip").display().to_string(), "w"),
+                None,
+            )
+            .unwrap();
+        archive
+            .call_method(py, "writestr", ("zipped/__init__.py", ""), None)
+            .unwrap();
+        archive
+            .call_method(py, "writestr", ("zipped/sub.py", "VALUE = 42\n"), None)
+            .unwrap();
+        archive.call_method(py, "close", NoArgs, None).unwrap();
+    }
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.filesystem_importer = false;
+    config.origin = Some(origin.clone());
+    config.zipimport_archive = Some(PathBuf::from("modules.zip"));
+
+    let mut interp = MainPythonInterpreter::new(config)?;
+    let py = interp.acquire_gil().unwrap();
+
+    let sub = py.import("zipped.sub").unwrap();
+    assert_eq!(
+        sub.get(py, "VALUE").unwrap().extract::<i64>(py).unwrap(),
+        42
+    );
+
+    // Only modules of the archive are importable from the filesystem.
+    assert!(py.import("zipfile").is_err());
+
+    drop(py);
+    drop(interp);
+    std::fs::remove_dir_all(&origin)?;
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Importing modules from a zip archive next to the binary.
+
+Modules in the archive are imported via `zipimport`, which is reached through
+the `sys.path` entry of the archive. That entry is only consulted by
+`PathFinder`, which is registered by the filesystem importer. So the filesystem
+importer remains optional, we install a meta path finder using `PathFinder`
+for paths inside the archive only.
+*/
+
+use cpython::{PyDict, PyResult, Python};
+
+/// Python code installing the meta path finder.
+///
+/// The `ARCHIVE` variable must be defined before this code runs. Only frozen
+/// modules are imported, as other importers may not be available.
+const ZIPIMPORT_FINDER_CODE: &str = r#"
+import _frozen_importlib_external
+import sys
+
+
+class _ZipimportFinder:
+    @classmethod
+    def find_spec(cls, fullname, path=None, target=None):
+        if path is None:
+            path = [ARCHIVE]
+        else:
+            path = [
+                entry
+                for entry in path
+                if entry == ARCHIVE
+                or entry.startswith(ARCHIVE + _frozen_importlib_external.path_sep)
+            ]
+
+        if not path:
+            return None
+
+        return _frozen_importlib_external.PathFinder.find_spec(fullname, path, target)
+
+
+sys.path.insert(0, ARCHIVE)
+sys.meta_path.append(_ZipimportFinder)
+"#;
+
+/// Make modules in the zip archive at `archive` importable.
+///
+/// The archive is added to `sys.path` and a meta path finder importing from
+/// it is installed.
+pub fn install_zipimport_finder(py: Python, archive: &str) -> PyResult<()> {
+    let globals = PyDict::new(py);
+    globals.set_item(py, "__builtins__", py.import("builtins")?)?;
+    globals.set_item(py, "ARCHIVE", archive)?;
+
+    py.run(ZIPIMPORT_FINDER_CODE, Some(&globals), None)
+}
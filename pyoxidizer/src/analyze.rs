@@ -5,7 +5,7 @@
 //! Analyze binaries for distribution compatibility.
 
 use {
-    anyhow::Result,
+    anyhow::{anyhow, Result},
     byteorder::ReadBytesExt,
     lazy_static::lazy_static,
     std::collections::BTreeMap,
@@ -469,3 +469,26 @@ pub fn find_pe_dependencies_path(path: &Path) -> Result<Vec<String>> {
     let data = std::fs::read(path)?;
     find_pe_dependencies(&data)
 }
+
+/// Find the file names of shared libraries an ELF, PE or Mach-O binary loads.
+///
+/// Mach-O install names are reduced to their file name.
+pub fn find_shared_library_dependencies(data: &[u8]) -> Result<Vec<String>> {
+    let libraries = match goblin::Object::parse(data)? {
+        goblin::Object::Elf(elf) => elf.libraries,
+        goblin::Object::PE(pe) => pe.libraries,
+        goblin::Object::Mach(goblin::mach::Mach::Binary(macho)) => macho.libs,
+        goblin::Object::Mach(goblin::mach::Mach::Fat(fat)) => fat.get(0)?.libs,
+        _ => return Err(anyhow!("not an ELF, PE or Mach-O binary")),
+    };
+
+    Ok(libraries
+        .iter()
+        // goblin lists the Mach-O binary itself as `self`.
+        .filter(|l| **l != "self")
+        .map(|l| match l.rfind('/') {
+            Some(idx) => l[idx + 1..].to_string(),
+            None => (*l).to_string(),
+        })
+        .collect())
+}
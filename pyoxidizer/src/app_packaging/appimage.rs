@@ -0,0 +1,319 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Linux applications packaged as AppImages.
+
+An AppImage is a single executable file holding a filesystem image of an
+application directory (AppDir). The AppDir contains an `AppRun` entry point,
+a desktop entry and an icon of the application at its root. The executable
+and its files are installed in `usr/bin`. `appimagetool` turns an AppDir
+into an AppImage.
+*/
+
+use {
+    super::desktop_entry::DesktopEntry,
+    super::resource::{FileContent, FileManifest},
+    crate::atomic_output::produce_path_atomic,
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    std::path::{Path, PathBuf},
+};
+
+/// Settings of an AppImage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppImageOptions {
+    /// Identifier of the application, used to name files.
+    pub app_id: String,
+
+    /// Desktop entry of the application.
+    ///
+    /// `Exec` and `Icon` are derived from the executable and `app_id`.
+    pub desktop_entry: DesktopEntry,
+
+    /// Extension of the icon file, e.g. `png` or `svg`.
+    pub icon_extension: String,
+
+    /// Content of the icon file.
+    pub icon_data: Vec<u8>,
+}
+
+impl AppImageOptions {
+    /// Validate an application identifier.
+    pub fn validate_app_id(app_id: &str) -> Result<()> {
+        if app_id.is_empty()
+            || !app_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            return Err(anyhow!(
+                "app_id must only contain letters, digits, -, _ and .; got {}",
+                app_id
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the file name of the AppImage for a target triple.
+    pub fn appimage_filename(&self, target_triple: &str) -> Result<String> {
+        Ok(format!(
+            "{}-{}.AppImage",
+            self.app_id,
+            appimage_arch(target_triple)?
+        ))
+    }
+
+    /// Obtain the files of the AppDir of an executable.
+    ///
+    /// `files` are installed next to the executable. Paths are relative to
+    /// the AppDir.
+    pub fn to_app_dir_manifest(
+        &self,
+        exe_name: &str,
+        exe_data: &[u8],
+        files: &FileManifest,
+    ) -> Result<FileManifest> {
+        let bin = PathBuf::from("usr").join("bin");
+        let icon_filename = format!("{}.{}", self.app_id, self.icon_extension);
+
+        let desktop_entry = DesktopEntry {
+            exec: exe_name.to_string(),
+            icon: Some(self.app_id.clone()),
+            ..self.desktop_entry.clone()
+        };
+
+        let mut manifest = FileManifest::default();
+
+        manifest.add_file(
+            Path::new("AppRun"),
+            &FileContent {
+                data: app_run_script(exe_name).into_bytes(),
+                executable: true,
+            },
+        )?;
+        manifest.add_file(
+            Path::new(&format!("{}.desktop", self.app_id)),
+            &FileContent {
+                data: desktop_entry.to_desktop_file().into_bytes(),
+                executable: false,
+            },
+        )?;
+
+        let icon = FileContent {
+            data: self.icon_data.clone(),
+            executable: false,
+        };
+        manifest.add_file(Path::new(&icon_filename), &icon)?;
+        // Thumbnailers and file managers look for the icon here.
+        manifest.add_file(Path::new(".DirIcon"), &icon)?;
+
+        manifest.add_file(
+            &bin.join(exe_name),
+            &FileContent {
+                data: exe_data.to_vec(),
+                executable: true,
+            },
+        )?;
+
+        for (path, content) in files.entries() {
+            manifest.add_file(&bin.join(path), content)?;
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// Obtain the `AppRun` script launching an executable of an AppDir.
+pub fn app_run_script(exe_name: &str) -> String {
+    format!(
+        "#!/bin/sh\n\
+         HERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\n\
+         exec \"$HERE/usr/bin/{}\" \"$@\"\n",
+        exe_name
+    )
+}
+
+/// Obtain the AppImage architecture name of a target triple.
+fn appimage_arch(target_triple: &str) -> Result<&'static str> {
+    if !target_triple.contains("-linux-") {
+        Err(anyhow!(
+            "AppImages can only be built for Linux targets; got {}",
+            target_triple
+        ))
+    } else if target_triple.starts_with("x86_64-") {
+        Ok("x86_64")
+    } else if target_triple.starts_with("i686-") || target_triple.starts_with("i586-") {
+        Ok("i686")
+    } else if target_triple.starts_with("aarch64-") {
+        Ok("aarch64")
+    } else if target_triple.starts_with("armv7-") {
+        Ok("armhf")
+    } else {
+        Err(anyhow!(
+            "unsupported AppImage architecture: {}",
+            target_triple
+        ))
+    }
+}
+
+/// Find `appimagetool`.
+///
+/// The `APPIMAGETOOL` environment variable defines its path. Otherwise it
+/// is searched in `PATH`.
+fn find_appimagetool() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("APPIMAGETOOL") {
+        return Some(PathBuf::from(path));
+    }
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join("appimagetool"))
+        .find(|path| path.is_file())
+}
+
+/// Build an AppImage of an AppDir.
+///
+/// Returns the path of the AppImage, which is written to `output_path`.
+pub fn build_appimage(
+    logger: &slog::Logger,
+    options: &AppImageOptions,
+    app_dir: &Path,
+    target_triple: &str,
+    output_path: &Path,
+) -> Result<PathBuf> {
+    let arch = appimage_arch(target_triple)?;
+
+    if !cfg!(target_os = "linux") {
+        return Err(anyhow!("AppImages can only be built on Linux"));
+    }
+
+    let tool = find_appimagetool().ok_or_else(|| {
+        anyhow!(
+            "appimagetool not found; install it in PATH or set APPIMAGETOOL to its path \
+             (the AppDir was written to {})",
+            app_dir.display()
+        )
+    })?;
+
+    let appimage_path = output_path.join(options.appimage_filename(target_triple)?);
+    warn!(logger, "running {}", tool.display());
+
+    produce_path_atomic(&appimage_path, |staged_path| {
+        let output = std::process::Command::new(&tool)
+            .arg(app_dir)
+            .arg(staged_path)
+            .env("ARCH", arch)
+            .output()
+            .context(format!("running {}", tool.display()))?;
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            warn!(logger, "{}", line);
+        }
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "error running {}: {}",
+                tool.display(),
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    })?;
+
+    Ok(appimage_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> AppImageOptions {
+        AppImageOptions {
+            app_id: "myapp".to_string(),
+            desktop_entry: DesktopEntry {
+                name: "My App".to_string(),
+                exec: String::new(),
+                icon: None,
+                comment: None,
+                categories: vec![],
+                terminal: false,
+            },
+            icon_extension: "png".to_string(),
+            icon_data: vec![42],
+        }
+    }
+
+    #[test]
+    fn test_validate_app_id() {
+        assert!(AppImageOptions::validate_app_id("org.example.my-app").is_ok());
+        assert!(AppImageOptions::validate_app_id("").is_err());
+        assert!(AppImageOptions::validate_app_id("my app").is_err());
+    }
+
+    #[test]
+    fn test_appimage_filename() -> Result<()> {
+        let options = options();
+
+        assert_eq!(
+            options.appimage_filename("x86_64-unknown-linux-gnu")?,
+            "myapp-x86_64.AppImage"
+        );
+        assert_eq!(
+            options.appimage_filename("aarch64-unknown-linux-gnu")?,
+            "myapp-aarch64.AppImage"
+        );
+        assert!(options.appimage_filename("x86_64-apple-darwin").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_app_dir_manifest() -> Result<()> {
+        let mut files = FileManifest::default();
+        files.add_file(
+            Path::new("lib/foo.py"),
+            &FileContent {
+                data: vec![42],
+                executable: false,
+            },
+        )?;
+
+        let manifest = options().to_app_dir_manifest("myapp-bin", &[1, 2], &files)?;
+
+        let paths = manifest
+            .entries()
+            .map(|(path, content)| (path.clone(), content.executable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                (PathBuf::from(".DirIcon"), false),
+                (PathBuf::from("AppRun"), true),
+                (PathBuf::from("myapp.desktop"), false),
+                (PathBuf::from("myapp.png"), false),
+                (PathBuf::from("usr/bin/lib/foo.py"), false),
+                (PathBuf::from("usr/bin/myapp-bin"), true),
+            ]
+        );
+
+        let desktop = manifest
+            .entries()
+            .find(|(path, _)| path.as_path() == Path::new("myapp.desktop"))
+            .map(|(_, content)| String::from_utf8_lossy(&content.data).to_string())
+            .unwrap();
+        assert!(desktop.contains("\nExec=myapp-bin\n"));
+        assert!(desktop.contains("\nIcon=myapp\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_app_run_script() {
+        assert_eq!(
+            app_run_script("myapp"),
+            "#!/bin/sh\nHERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\nexec \"$HERE/usr/bin/myapp\" \"$@\"\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+freedesktop.org desktop entries.
+
+Desktop environments on Linux discover applications, their icon and how to
+launch them from `.desktop` files. Linux application packages like AppImages
+and snaps ship one for each graphical application they contain.
+*/
+
+use anyhow::{anyhow, Result};
+
+/// A desktop entry launching an application.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DesktopEntry {
+    /// Name of the application displayed to users.
+    pub name: String,
+
+    /// Command launching the application.
+    pub exec: String,
+
+    /// Name of the icon of the application, without extension, or its path.
+    pub icon: Option<String>,
+
+    /// Description of the application displayed as a tooltip.
+    pub comment: Option<String>,
+
+    /// Menu categories of the application, e.g. `Utility`.
+    pub categories: Vec<String>,
+
+    /// Whether the application runs in a terminal.
+    pub terminal: bool,
+}
+
+impl DesktopEntry {
+    /// Validate a menu category.
+    pub fn validate_category(category: &str) -> Result<()> {
+        if category.is_empty() || !category.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(anyhow!(
+                "desktop entry category must only contain letters and digits; got {}",
+                category
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the content of the `.desktop` file of this entry.
+    pub fn to_desktop_file(&self) -> String {
+        let mut lines = vec![
+            "[Desktop Entry]".to_string(),
+            "Type=Application".to_string(),
+            format!("Name={}", escape_value(&self.name)),
+            format!("Exec={}", self.exec),
+        ];
+
+        if let Some(icon) = &self.icon {
+            lines.push(format!("Icon={}", escape_value(icon)));
+        }
+        if let Some(comment) = &self.comment {
+            lines.push(format!("Comment={}", escape_value(comment)));
+        }
+
+        // The list of categories is terminated by a semicolon.
+        let categories = if self.categories.is_empty() {
+            vec!["Utility".to_string()]
+        } else {
+            self.categories.clone()
+        };
+        lines.push(format!("Categories={};", categories.join(";")));
+        lines.push(format!("Terminal={}", self.terminal));
+
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Escape a string value of a desktop entry.
+fn escape_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_category() {
+        assert!(DesktopEntry::validate_category("Development").is_ok());
+        assert!(DesktopEntry::validate_category("").is_err());
+        assert!(DesktopEntry::validate_category("A;B").is_err());
+    }
+
+    #[test]
+    fn test_to_desktop_file() {
+        let mut entry = DesktopEntry {
+            name: "My App".to_string(),
+            exec: "myapp".to_string(),
+            icon: None,
+            comment: None,
+            categories: vec![],
+            terminal: false,
+        };
+
+        assert_eq!(
+            entry.to_desktop_file(),
+            "[Desktop Entry]\nType=Application\nName=My App\nExec=myapp\n\
+             Categories=Utility;\nTerminal=false\n"
+        );
+
+        entry.icon = Some("myapp".to_string());
+        entry.comment = Some("Does\nthings".to_string());
+        entry.categories = vec!["Development".to_string(), "IDE".to_string()];
+        entry.terminal = true;
+
+        assert_eq!(
+            entry.to_desktop_file(),
+            "[Desktop Entry]\nType=Application\nName=My App\nExec=myapp\nIcon=myapp\n\
+             Comment=Does\\nthings\nCategories=Development;IDE;\nTerminal=true\n"
+        );
+    }
+}
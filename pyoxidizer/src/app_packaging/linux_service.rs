@@ -0,0 +1,603 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Sandboxing configuration for binaries deployed as Linux services.
+
+Services are commonly confined with systemd hardening directives and
+seccomp system call filters. Writing these by hand is error prone and they
+drift from what the application actually needs. This module derives a
+systemd unit and an OCI seccomp profile (as used by Docker, Podman and
+Kubernetes) from a declaration of what the application needs.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    std::collections::BTreeSet,
+};
+
+/// Linux capabilities that can be granted to services.
+pub const KNOWN_CAPABILITIES: &[&str] = &[
+    "CAP_AUDIT_CONTROL",
+    "CAP_AUDIT_READ",
+    "CAP_AUDIT_WRITE",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_KILL",
+    "CAP_LEASE",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_MAC_ADMIN",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MKNOD",
+    "CAP_NET_ADMIN",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_RAW",
+    "CAP_SETFCAP",
+    "CAP_SETGID",
+    "CAP_SETPCAP",
+    "CAP_SETUID",
+    "CAP_SYSLOG",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_NICE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_WAKE_ALARM",
+];
+
+/// System calls needed by the Python interpreter and its standard library.
+const BASE_SYSCALLS: &[&str] = &[
+    "access",
+    "arch_prctl",
+    "brk",
+    "capget",
+    "chdir",
+    "clock_getres",
+    "clock_gettime",
+    "clock_nanosleep",
+    "clone",
+    "clone3",
+    "close",
+    "dup",
+    "dup2",
+    "dup3",
+    "epoll_create",
+    "epoll_create1",
+    "epoll_ctl",
+    "epoll_pwait",
+    "epoll_wait",
+    "eventfd2",
+    "execve",
+    "exit",
+    "exit_group",
+    "faccessat",
+    "faccessat2",
+    "fadvise64",
+    "fchdir",
+    "fchmod",
+    "fcntl",
+    "fdatasync",
+    "flock",
+    "fork",
+    "fstat",
+    "fstatfs",
+    "fsync",
+    "ftruncate",
+    "futex",
+    "getcwd",
+    "getdents",
+    "getdents64",
+    "getegid",
+    "geteuid",
+    "getgid",
+    "getgroups",
+    "getpgrp",
+    "getpid",
+    "getppid",
+    "getpriority",
+    "getrandom",
+    "getresgid",
+    "getresuid",
+    "getrlimit",
+    "getrusage",
+    "gettid",
+    "gettimeofday",
+    "getuid",
+    "ioctl",
+    "kill",
+    "lseek",
+    "lstat",
+    "madvise",
+    "mkdir",
+    "mkdirat",
+    "mmap",
+    "mprotect",
+    "mremap",
+    "munmap",
+    "nanosleep",
+    "newfstatat",
+    "open",
+    "openat",
+    "pipe",
+    "pipe2",
+    "poll",
+    "ppoll",
+    "prctl",
+    "pread64",
+    "prlimit64",
+    "pselect6",
+    "pwrite64",
+    "read",
+    "readlink",
+    "readlinkat",
+    "readv",
+    "rename",
+    "renameat",
+    "renameat2",
+    "restart_syscall",
+    "rmdir",
+    "rseq",
+    "rt_sigaction",
+    "rt_sigprocmask",
+    "rt_sigreturn",
+    "sched_getaffinity",
+    "sched_yield",
+    "select",
+    "set_robust_list",
+    "set_tid_address",
+    "sigaltstack",
+    "socketpair",
+    "stat",
+    "statfs",
+    "statx",
+    "sysinfo",
+    "tgkill",
+    "time",
+    "umask",
+    "uname",
+    "unlink",
+    "unlinkat",
+    "utimensat",
+    "vfork",
+    "wait4",
+    "waitid",
+    "write",
+    "writev",
+];
+
+/// System calls operating on sockets.
+///
+/// Creating sockets is controlled separately: services without network
+/// access can only create `AF_UNIX` sockets.
+const SOCKET_SYSCALLS: &[&str] = &[
+    "accept",
+    "accept4",
+    "bind",
+    "connect",
+    "getpeername",
+    "getsockname",
+    "getsockopt",
+    "listen",
+    "recvfrom",
+    "recvmmsg",
+    "recvmsg",
+    "sendmmsg",
+    "sendmsg",
+    "sendto",
+    "setsockopt",
+    "shutdown",
+];
+
+/// Value of the `AF_UNIX` address family.
+const AF_UNIX: u32 = 1;
+
+/// Ensure a value written to a systemd unit doesn't contain control characters.
+///
+/// A newline would otherwise let the value inject arbitrary directives.
+fn validate_unit_value(what: &str, value: &str) -> Result<()> {
+    if value.chars().any(|c| c.is_control()) {
+        Err(anyhow!("{} contains control characters: {:?}", what, value))
+    } else {
+        Ok(())
+    }
+}
+
+/// Escape systemd specifiers in a value.
+fn escape_specifiers(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+/// Quote a value so systemd parses it as a single word.
+fn quote_word(value: &str) -> String {
+    let mut res = String::from("\"");
+
+    for c in escape_specifiers(value).chars() {
+        if c == '\\' || c == '"' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+
+    res.push('"');
+
+    res
+}
+
+/// Describes what a service needs from the system.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinuxServiceOptions {
+    /// Description of the service.
+    pub description: String,
+
+    /// Directory the binary is installed in.
+    pub install_dir: String,
+
+    /// Whether the service uses the network.
+    pub network: bool,
+
+    /// Capabilities the service needs.
+    pub capabilities: BTreeSet<String>,
+
+    /// Paths the service writes to.
+    pub writable_paths: Vec<String>,
+
+    /// Whether the service needs memory that is both writable and executable.
+    ///
+    /// `ctypes` and `cffi` callbacks need this.
+    pub executable_memory: bool,
+
+    /// System calls to allow in addition to the ones derived from other settings.
+    pub extra_syscalls: BTreeSet<String>,
+}
+
+impl LinuxServiceOptions {
+    /// Validate settings.
+    pub fn validate(&self) -> Result<()> {
+        validate_unit_value("description", &self.description)?;
+        validate_unit_value("install directory", &self.install_dir)?;
+
+        for capability in &self.capabilities {
+            if !KNOWN_CAPABILITIES.contains(&capability.as_str()) {
+                return Err(anyhow!("unknown capability: {}", capability));
+            }
+        }
+
+        for path in &self.writable_paths {
+            validate_unit_value("writable path", path)?;
+
+            if !path.starts_with('/') {
+                return Err(anyhow!("writable path must be absolute: {}", path));
+            }
+        }
+
+        if !self.install_dir.starts_with('/') {
+            return Err(anyhow!(
+                "install directory must be absolute: {}",
+                self.install_dir
+            ));
+        }
+
+        for syscall in &self.extra_syscalls {
+            if syscall.is_empty()
+                || !syscall
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(anyhow!("invalid system call name: {}", syscall));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Derive a systemd unit running a binary.
+    ///
+    /// Values are quoted and escaped so paths containing spaces, quotes or
+    /// `%` and `$` characters are passed through verbatim.
+    pub fn to_systemd_unit(&self, exe_name: &str) -> Result<String> {
+        self.validate()?;
+        validate_unit_value("executable name", exe_name)?;
+
+        let exe_path = format!("{}/{}", self.install_dir.trim_end_matches('/'), exe_name);
+
+        let mut lines = vec![
+            "[Unit]".to_string(),
+            format!("Description={}", escape_specifiers(&self.description)),
+        ];
+
+        if self.network {
+            lines.push("After=network-online.target".to_string());
+            lines.push("Wants=network-online.target".to_string());
+        }
+
+        lines.extend(
+            vec![
+                "",
+                "[Service]",
+                "Type=simple",
+                // `$` is only expanded in command lines.
+                &format!("ExecStart={}", quote_word(&exe_path).replace('$', "$$")),
+                "DynamicUser=yes",
+                "NoNewPrivileges=yes",
+                "ProtectSystem=strict",
+                "ProtectHome=yes",
+                "PrivateTmp=yes",
+                "PrivateDevices=yes",
+                "ProtectKernelTunables=yes",
+                "ProtectKernelModules=yes",
+                "ProtectKernelLogs=yes",
+                "ProtectControlGroups=yes",
+                "ProtectClock=yes",
+                "ProtectHostname=yes",
+                "RestrictNamespaces=yes",
+                "RestrictRealtime=yes",
+                "RestrictSUIDSGID=yes",
+                "LockPersonality=yes",
+                "SystemCallArchitectures=native",
+                "SystemCallFilter=@system-service",
+                "SystemCallErrorNumber=EPERM",
+            ]
+            .into_iter()
+            .map(|s| s.to_string()),
+        );
+
+        if self.network {
+            lines.push("RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6".to_string());
+        } else {
+            lines.push("PrivateNetwork=yes".to_string());
+            lines.push("RestrictAddressFamilies=AF_UNIX".to_string());
+        }
+
+        let capabilities = self
+            .capabilities
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        lines.push(format!("CapabilityBoundingSet={}", capabilities));
+        if !capabilities.is_empty() {
+            lines.push(format!("AmbientCapabilities={}", capabilities));
+        }
+
+        if !self.executable_memory {
+            lines.push("MemoryDenyWriteExecute=yes".to_string());
+        }
+
+        if !self.writable_paths.is_empty() {
+            lines.push(format!(
+                "ReadWritePaths={}",
+                self.writable_paths
+                    .iter()
+                    .map(|path| quote_word(path))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+
+        if !self.extra_syscalls.is_empty() {
+            lines.push(format!(
+                "SystemCallFilter={}",
+                self.extra_syscalls
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+
+        lines.push("".to_string());
+        lines.push("[Install]".to_string());
+        lines.push("WantedBy=multi-user.target".to_string());
+        lines.push("".to_string());
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Obtain the system calls the service is allowed to make.
+    ///
+    /// Without network access, `socket` is allowed for `AF_UNIX` only, which
+    /// the seccomp profile enforces with a separate rule.
+    pub fn syscalls(&self) -> BTreeSet<String> {
+        let mut res = BASE_SYSCALLS
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<BTreeSet<_>>();
+
+        res.extend(SOCKET_SYSCALLS.iter().map(|s| s.to_string()));
+        if self.network {
+            res.insert("socket".to_string());
+        }
+
+        res.extend(self.extra_syscalls.iter().cloned());
+
+        res
+    }
+
+    /// Derive an OCI seccomp profile for a target triple.
+    pub fn to_seccomp_profile(&self, target_triple: &str) -> Result<String> {
+        let architectures: &[&str] = if target_triple.starts_with("x86_64-") {
+            &["SCMP_ARCH_X86_64", "SCMP_ARCH_X86", "SCMP_ARCH_X32"]
+        } else if target_triple.starts_with("aarch64-") {
+            &["SCMP_ARCH_AARCH64", "SCMP_ARCH_ARM"]
+        } else if target_triple.starts_with("i686-") {
+            &["SCMP_ARCH_X86"]
+        } else {
+            &[]
+        };
+
+        let names = self.syscalls();
+        let allow_unix_sockets = !names.contains("socket");
+
+        let mut syscalls = vec![serde_json::json!({
+            "names": names,
+            "action": "SCMP_ACT_ALLOW",
+        })];
+
+        // Matches `RestrictAddressFamilies=AF_UNIX` of the systemd unit.
+        if allow_unix_sockets {
+            syscalls.push(serde_json::json!({
+                "names": ["socket"],
+                "action": "SCMP_ACT_ALLOW",
+                "args": [
+                    {
+                        "index": 0,
+                        "value": AF_UNIX,
+                        "op": "SCMP_CMP_EQ",
+                    },
+                ],
+            }));
+        }
+
+        let profile = serde_json::json!({
+            "defaultAction": "SCMP_ACT_ERRNO",
+            "architectures": architectures,
+            "syscalls": syscalls,
+        });
+
+        Ok(serde_json::to_string_pretty(&profile)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> LinuxServiceOptions {
+        LinuxServiceOptions {
+            description: "myapp".to_string(),
+            install_dir: "/opt/myapp".to_string(),
+            network: false,
+            capabilities: BTreeSet::new(),
+            writable_paths: vec![],
+            executable_memory: false,
+            extra_syscalls: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(options().validate().is_ok());
+
+        let mut o = options();
+        o.capabilities.insert("CAP_NET_BIND_SERVICE".to_string());
+        assert!(o.validate().is_ok());
+        o.capabilities.insert("CAP_EVERYTHING".to_string());
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.writable_paths.push("var/lib/myapp".to_string());
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.install_dir = "opt".to_string();
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.extra_syscalls.insert("ptrace\n".to_string());
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.description = "myapp\nExecStartPre=/bin/sh".to_string();
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.install_dir = "/opt/myapp\r".to_string();
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.writable_paths.push("/var/lib/myapp\n".to_string());
+        assert!(o.validate().is_err());
+
+        assert!(options().to_systemd_unit("myapp\n").is_err());
+    }
+
+    #[test]
+    fn test_systemd_unit() -> Result<()> {
+        let unit = options().to_systemd_unit("myapp")?;
+        assert!(unit.contains("\nDescription=myapp\n"));
+        assert!(unit.contains("\nExecStart=\"/opt/myapp/myapp\"\n"));
+        assert!(unit.contains("\nPrivateNetwork=yes\n"));
+        assert!(unit.contains("\nCapabilityBoundingSet=\n"));
+        assert!(!unit.contains("AmbientCapabilities"));
+        assert!(unit.contains("\nMemoryDenyWriteExecute=yes\n"));
+
+        let mut o = options();
+        o.network = true;
+        o.capabilities.insert("CAP_NET_BIND_SERVICE".to_string());
+        o.writable_paths.push("/var/lib/myapp".to_string());
+        o.executable_memory = true;
+        let unit = o.to_systemd_unit("myapp")?;
+        assert!(unit.contains("\nRestrictAddressFamilies=AF_UNIX AF_INET AF_INET6\n"));
+        assert!(!unit.contains("PrivateNetwork"));
+        assert!(unit.contains("\nAmbientCapabilities=CAP_NET_BIND_SERVICE\n"));
+        assert!(!unit.contains("MemoryDenyWriteExecute"));
+        assert!(unit.contains("\nReadWritePaths=\"/var/lib/myapp\"\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_systemd_unit_quoting() -> Result<()> {
+        let mut o = options();
+        o.description = "100% my app".to_string();
+        o.install_dir = "/opt/my \"app\" $HOME\\".to_string();
+        o.writable_paths.push("/var/lib/my app".to_string());
+
+        let unit = o.to_systemd_unit("my app%n")?;
+        assert!(unit.contains("\nDescription=100%% my app\n"));
+        assert!(unit.contains("\nExecStart=\"/opt/my \\\"app\\\" $$HOME\\\\/my app%%n\"\n"));
+        assert!(unit.contains("\nReadWritePaths=\"/var/lib/my app\"\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seccomp_profile() -> Result<()> {
+        let mut o = options();
+        o.extra_syscalls.insert("ptrace".to_string());
+
+        let profile: serde_json::Value =
+            serde_json::from_str(&o.to_seccomp_profile("x86_64-unknown-linux-gnu")?)?;
+        assert_eq!(profile["defaultAction"], "SCMP_ACT_ERRNO");
+        assert_eq!(profile["architectures"][0], "SCMP_ARCH_X86_64");
+
+        let names = profile["syscalls"][0]["names"].as_array().unwrap();
+        assert!(names.contains(&serde_json::Value::from("read")));
+        assert!(names.contains(&serde_json::Value::from("ptrace")));
+        assert!(names.contains(&serde_json::Value::from("connect")));
+        assert!(!names.contains(&serde_json::Value::from("socket")));
+        // Only AF_UNIX sockets can be created, as with the systemd unit.
+        assert_eq!(
+            profile["syscalls"][1],
+            serde_json::json!({
+                "names": ["socket"],
+                "action": "SCMP_ACT_ALLOW",
+                "args": [{"index": 0, "value": 1, "op": "SCMP_CMP_EQ"}],
+            })
+        );
+
+        o.network = true;
+        let profile: serde_json::Value =
+            serde_json::from_str(&o.to_seccomp_profile("aarch64-unknown-linux-gnu")?)?;
+        assert_eq!(profile["architectures"][0], "SCMP_ARCH_AARCH64");
+        let names = profile["syscalls"][0]["names"].as_array().unwrap();
+        assert!(names.contains(&serde_json::Value::from("socket")));
+        assert_eq!(profile["syscalls"].as_array().unwrap().len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,543 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+macOS application bundles.
+
+An application bundle is a directory with a `.app` extension holding an
+executable, the `Info.plist` property list describing it and the files it
+uses:
+
+* `Contents/MacOS/` holds the executable.
+* `Contents/Frameworks/` holds shared libraries the executable links against.
+* `Contents/Resources/` holds icons and the files otherwise installed next to
+  the executable, which finds them through its resources origin.
+
+`codesign` only accepts code in `Contents/MacOS`, so other files must be in
+`Contents/Resources`. Code among them, such as extension modules, is signed
+individually before the bundle.
+
+Shared libraries are found through the install names recorded in the
+executable, which are rewritten with `install_name_tool` to point inside the
+bundle. This tool only runs on macOS.
+*/
+
+use {
+    super::resource::{FileContent, FileManifest},
+    anyhow::{anyhow, Result},
+    slog::warn,
+    std::collections::BTreeMap,
+    std::path::{Path, PathBuf},
+};
+
+/// Directory of files installed next to the executable, relative to the executable.
+pub const RESOURCES_ORIGIN: &str = "../Resources";
+
+/// A value in a property list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlistValue {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<PlistValue>),
+    Dict(BTreeMap<String, PlistValue>),
+}
+
+impl PlistValue {
+    /// Append XML lines representing this value.
+    fn write_xml(&self, lines: &mut Vec<String>, indent: usize) {
+        let pad = "    ".repeat(indent);
+
+        match self {
+            PlistValue::Bool(true) => lines.push(format!("{}<true/>", pad)),
+            PlistValue::Bool(false) => lines.push(format!("{}<false/>", pad)),
+            PlistValue::Integer(v) => lines.push(format!("{}<integer>{}</integer>", pad, v)),
+            PlistValue::String(v) => {
+                lines.push(format!("{}<string>{}</string>", pad, escape_xml(v)))
+            }
+            PlistValue::Array(values) => {
+                lines.push(format!("{}<array>", pad));
+                for value in values {
+                    value.write_xml(lines, indent + 1);
+                }
+                lines.push(format!("{}</array>", pad));
+            }
+            PlistValue::Dict(values) => {
+                lines.push(format!("{}<dict>", pad));
+                for (key, value) in values {
+                    lines.push(format!("{}    <key>{}</key>", pad, escape_xml(key)));
+                    value.write_xml(lines, indent + 1);
+                }
+                lines.push(format!("{}</dict>", pad));
+            }
+        }
+    }
+}
+
+/// Render a dictionary as a property list document.
+pub fn to_plist_xml(values: &BTreeMap<String, PlistValue>) -> String {
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        r#"<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">"#.to_string(),
+        r#"<plist version="1.0">"#.to_string(),
+    ];
+
+    PlistValue::Dict(values.clone()).write_xml(&mut lines, 0);
+
+    lines.push("</plist>".to_string());
+    lines.push("".to_string());
+
+    lines.join("\n")
+}
+
+/// Escape a value for use in XML text.
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Settings and resources of a macOS application bundle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacOsApplicationBundle {
+    /// Name of the bundle, without the `.app` extension.
+    pub bundle_name: String,
+
+    /// Keys of `Info.plist`.
+    ///
+    /// Keys describing the executable are derived when not defined.
+    pub info_plist: BTreeMap<String, PlistValue>,
+
+    /// Files installed in `Contents/Resources`.
+    pub resources: FileManifest,
+}
+
+impl MacOsApplicationBundle {
+    /// Construct an instance, validating settings.
+    ///
+    /// `info_plist` must define `CFBundleIdentifier`.
+    pub fn new(bundle_name: &str, info_plist: BTreeMap<String, PlistValue>) -> Result<Self> {
+        if bundle_name.is_empty() || bundle_name.contains('/') {
+            return Err(anyhow!("invalid bundle name: {}", bundle_name));
+        }
+
+        match info_plist.get("CFBundleIdentifier") {
+            Some(PlistValue::String(identifier)) if !identifier.is_empty() => {}
+            Some(_) => return Err(anyhow!("CFBundleIdentifier must be a non-empty string")),
+            None => return Err(anyhow!("Info.plist must define CFBundleIdentifier")),
+        }
+
+        Ok(Self {
+            bundle_name: bundle_name.to_string(),
+            info_plist,
+            resources: FileManifest::default(),
+        })
+    }
+
+    /// Add an icon file to the bundle resources.
+    ///
+    /// The first icon added becomes the icon of the bundle unless
+    /// `CFBundleIconFile` is defined.
+    pub fn add_icon(&mut self, file_name: &str, data: Vec<u8>) -> Result<()> {
+        let path = Path::new(file_name);
+        if path.components().count() != 1 {
+            return Err(anyhow!("icon must be a file name; got {}", file_name));
+        }
+
+        self.resources.add_file(
+            path,
+            &FileContent {
+                data,
+                executable: false,
+            },
+        )?;
+
+        self.info_plist
+            .entry("CFBundleIconFile".to_string())
+            .or_insert_with(|| PlistValue::String(file_name.to_string()));
+
+        Ok(())
+    }
+
+    /// Obtain the `Info.plist` keys of the bundle of an executable.
+    pub fn resolved_info_plist(&self, exe_name: &str) -> BTreeMap<String, PlistValue> {
+        let mut values = self.info_plist.clone();
+
+        for (key, value) in &[
+            ("CFBundleExecutable", exe_name),
+            ("CFBundleName", self.bundle_name.as_str()),
+            ("CFBundlePackageType", "APPL"),
+            ("CFBundleInfoDictionaryVersion", "6.0"),
+        ] {
+            values
+                .entry(key.to_string())
+                .or_insert_with(|| PlistValue::String(value.to_string()));
+        }
+
+        values
+    }
+
+    /// Obtain the files of the bundle of an executable.
+    ///
+    /// `files` are installed in `Contents/Resources`, the resources origin of
+    /// the executable, except those named in `frameworks`, which are installed
+    /// in `Contents/Frameworks`. Paths are relative to the directory
+    /// containing the bundle.
+    pub fn to_manifest(
+        &self,
+        exe_name: &str,
+        exe_data: &[u8],
+        files: &FileManifest,
+        frameworks: &[PathBuf],
+    ) -> Result<FileManifest> {
+        let contents = PathBuf::from(format!("{}.app", self.bundle_name)).join("Contents");
+
+        let mut manifest = FileManifest::default();
+
+        manifest.add_file(
+            &contents.join("Info.plist"),
+            &FileContent {
+                data: to_plist_xml(&self.resolved_info_plist(exe_name)).into_bytes(),
+                executable: false,
+            },
+        )?;
+        manifest.add_file(
+            &contents.join("PkgInfo"),
+            &FileContent {
+                data: b"APPL????".to_vec(),
+                executable: false,
+            },
+        )?;
+        manifest.add_file(
+            &contents.join("MacOS").join(exe_name),
+            &FileContent {
+                data: exe_data.to_vec(),
+                executable: true,
+            },
+        )?;
+
+        for (path, content) in files.entries() {
+            let dest_dir = if frameworks.contains(path) {
+                "Frameworks"
+            } else {
+                "Resources"
+            };
+
+            manifest.add_file(&contents.join(dest_dir).join(path), content)?;
+        }
+
+        for (path, content) in self.resources.entries() {
+            manifest.add_file(&contents.join("Resources").join(path), content)?;
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// Whether data is a Mach-O binary.
+fn is_mach_o(data: &[u8]) -> bool {
+    // 32 and 64-bit binaries of either endianness and universal binaries.
+    match data.get(0..4) {
+        Some([0xfe, 0xed, 0xfa, 0xce])
+        | Some([0xfe, 0xed, 0xfa, 0xcf])
+        | Some([0xce, 0xfa, 0xed, 0xfe])
+        | Some([0xcf, 0xfa, 0xed, 0xfe])
+        | Some([0xca, 0xfe, 0xba, 0xbe]) => true,
+        _ => false,
+    }
+}
+
+/// Obtain the paths of code nested in a bundle, which is signed before the bundle.
+///
+/// `manifest` is the manifest returned by `MacOsApplicationBundle::to_manifest()`.
+/// The executable in `Contents/MacOS` is signed with the bundle, so it is
+/// excluded.
+pub fn nested_code_paths(manifest: &FileManifest) -> Vec<PathBuf> {
+    manifest
+        .entries()
+        .filter(|(path, content)| {
+            let in_macos = path
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .map(|name| name == "MacOS")
+                .unwrap_or(false);
+
+            !in_macos && is_mach_o(&content.data)
+        })
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+/// Derive install name changes pointing an executable at bundled frameworks.
+///
+/// `otool_output` is the output of `otool -L` for the executable. Returns
+/// `(old, new)` install names of the libraries named in `frameworks`.
+pub fn framework_install_name_changes(
+    otool_output: &str,
+    frameworks: &[PathBuf],
+) -> Vec<(String, String)> {
+    // The first line names the inspected file. Others are
+    // `<install name> (compatibility version ...)`.
+    otool_output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let install_name = line.trim().split(" (").next()?;
+            let file_name = Path::new(install_name).file_name()?;
+
+            if frameworks.iter().any(|p| p.as_os_str() == file_name) {
+                let new_name = format!(
+                    "@executable_path/../Frameworks/{}",
+                    file_name.to_string_lossy()
+                );
+
+                if new_name != install_name {
+                    return Some((install_name.to_string(), new_name));
+                }
+            }
+
+            None
+        })
+        .collect()
+}
+
+/// Run a command, failing if it fails.
+fn run_tool(program: &str, args: &[String]) -> Result<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .or_else(|e| Err(anyhow!("running {}: {}", program, e)))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Point an executable in a written bundle at its bundled frameworks.
+///
+/// Install names of frameworks and references to them in the executable are
+/// rewritten relative to the executable.
+pub fn relink_frameworks(
+    logger: &slog::Logger,
+    bundle_path: &Path,
+    exe_name: &str,
+    frameworks: &[PathBuf],
+) -> Result<()> {
+    if frameworks.is_empty() {
+        return Ok(());
+    }
+
+    if !cfg!(target_os = "macos") {
+        return Err(anyhow!(
+            "application bundles with frameworks can only be built on macOS"
+        ));
+    }
+
+    let contents = bundle_path.join("Contents");
+    let exe_path = contents.join("MacOS").join(exe_name);
+
+    for framework in frameworks {
+        let framework_path = contents.join("Frameworks").join(framework);
+
+        run_tool(
+            "install_name_tool",
+            &[
+                "-id".to_string(),
+                format!("@executable_path/../Frameworks/{}", framework.display()),
+                framework_path.display().to_string(),
+            ],
+        )?;
+    }
+
+    let otool_output = run_tool("otool", &["-L".to_string(), exe_path.display().to_string()])?;
+
+    for (old, new) in framework_install_name_changes(&otool_output, frameworks) {
+        warn!(logger, "relinking {} to {}", old, new);
+
+        run_tool(
+            "install_name_tool",
+            &[
+                "-change".to_string(),
+                old,
+                new,
+                exe_path.display().to_string(),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle() -> Result<MacOsApplicationBundle> {
+        let mut info_plist = BTreeMap::new();
+        info_plist.insert(
+            "CFBundleIdentifier".to_string(),
+            PlistValue::String("com.example.myapp".to_string()),
+        );
+
+        MacOsApplicationBundle::new("My App", info_plist)
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(bundle().is_ok());
+        assert!(MacOsApplicationBundle::new("My App", BTreeMap::new()).is_err());
+
+        let mut info_plist = BTreeMap::new();
+        info_plist.insert(
+            "CFBundleIdentifier".to_string(),
+            PlistValue::String("com.example.myapp".to_string()),
+        );
+        assert!(MacOsApplicationBundle::new("", info_plist.clone()).is_err());
+        assert!(MacOsApplicationBundle::new("a/b", info_plist).is_err());
+    }
+
+    #[test]
+    fn test_plist_xml() {
+        let mut values = BTreeMap::new();
+        values.insert("A".to_string(), PlistValue::String("<&>".to_string()));
+        values.insert(
+            "B".to_string(),
+            PlistValue::Array(vec![PlistValue::Bool(true), PlistValue::Integer(42)]),
+        );
+
+        assert!(to_plist_xml(&values).contains(
+            "<dict>\n    <key>A</key>\n    <string>&lt;&amp;&gt;</string>\n    <key>B</key>\n    <array>\n        <true/>\n        <integer>42</integer>\n    </array>\n</dict>\n</plist>"
+        ));
+    }
+
+    #[test]
+    fn test_resolved_info_plist() -> Result<()> {
+        let mut bundle = bundle()?;
+        bundle.add_icon("myapp.icns", vec![42])?;
+        bundle.add_icon("document.icns", vec![42])?;
+        assert!(bundle.add_icon("icons/myapp.icns", vec![42]).is_err());
+
+        let values = bundle.resolved_info_plist("myapp");
+        assert_eq!(
+            values.get("CFBundleExecutable"),
+            Some(&PlistValue::String("myapp".to_string()))
+        );
+        assert_eq!(
+            values.get("CFBundleName"),
+            Some(&PlistValue::String("My App".to_string()))
+        );
+        assert_eq!(
+            values.get("CFBundleIconFile"),
+            Some(&PlistValue::String("myapp.icns".to_string()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_manifest() -> Result<()> {
+        let mut bundle = bundle()?;
+        bundle.add_icon("myapp.icns", vec![42])?;
+
+        let content = FileContent {
+            data: vec![42],
+            executable: false,
+        };
+        let mut files = FileManifest::default();
+        files.add_file(Path::new("lib/foo.py"), &content)?;
+        files.add_file(Path::new("lib/_foo.so"), &content)?;
+        files.add_file(Path::new("libpython3.8.dylib"), &content)?;
+
+        let manifest = bundle.to_manifest(
+            "myapp",
+            &[42],
+            &files,
+            &[PathBuf::from("libpython3.8.dylib")],
+        )?;
+
+        let mut paths = manifest
+            .entries()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("My App.app/Contents/Frameworks/libpython3.8.dylib"),
+                PathBuf::from("My App.app/Contents/Info.plist"),
+                PathBuf::from("My App.app/Contents/MacOS/myapp"),
+                PathBuf::from("My App.app/Contents/PkgInfo"),
+                PathBuf::from("My App.app/Contents/Resources/lib/_foo.so"),
+                PathBuf::from("My App.app/Contents/Resources/lib/foo.py"),
+                PathBuf::from("My App.app/Contents/Resources/myapp.icns"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_code_paths() -> Result<()> {
+        let bundle = bundle()?;
+
+        let code = FileContent {
+            data: vec![0xcf, 0xfa, 0xed, 0xfe, 0x07],
+            executable: false,
+        };
+        let mut files = FileManifest::default();
+        files.add_file(
+            Path::new("lib/foo.py"),
+            &FileContent {
+                data: b"import os".to_vec(),
+                executable: false,
+            },
+        )?;
+        files.add_file(Path::new("lib/_foo.so"), &code)?;
+        files.add_file(Path::new("libpython3.8.dylib"), &code)?;
+
+        let manifest = bundle.to_manifest(
+            "myapp",
+            &code.data,
+            &files,
+            &[PathBuf::from("libpython3.8.dylib")],
+        )?;
+
+        let mut paths = nested_code_paths(&manifest);
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("My App.app/Contents/Frameworks/libpython3.8.dylib"),
+                PathBuf::from("My App.app/Contents/Resources/lib/_foo.so"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_framework_install_name_changes() {
+        let output = "/tmp/myapp:\n\
+                      \t/install/lib/libpython3.8.dylib (compatibility version 3.8.0, current version 3.8.0)\n\
+                      \t/usr/lib/libSystem.B.dylib (compatibility version 1.0.0, current version 1281.0.0)\n";
+
+        assert_eq!(
+            framework_install_name_changes(output, &[PathBuf::from("libpython3.8.dylib")]),
+            vec![(
+                "/install/lib/libpython3.8.dylib".to_string(),
+                "@executable_path/../Frameworks/libpython3.8.dylib".to_string()
+            )]
+        );
+        assert!(framework_install_name_changes(output, &[]).is_empty());
+    }
+}
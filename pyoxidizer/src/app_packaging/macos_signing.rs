@@ -0,0 +1,231 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Code signing of macOS binaries.
+
+Gatekeeper requires distributed binaries to be signed with the hardened
+runtime enabled. The hardened runtime denies capabilities such as creating
+executable memory (used by `ctypes` and `cffi` callbacks) or accessing the
+microphone unless the binary is signed with entitlements granting them.
+This module derives the entitlements property list from a set of
+entitlement keys and signs binaries with `codesign`.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    slog::warn,
+    std::collections::BTreeSet,
+    std::path::Path,
+};
+
+/// Entitlements that can be granted to signed binaries.
+pub const KNOWN_ENTITLEMENTS: &[&str] = &[
+    "com.apple.security.app-sandbox",
+    "com.apple.security.automation.apple-events",
+    "com.apple.security.cs.allow-dyld-environment-variables",
+    "com.apple.security.cs.allow-jit",
+    "com.apple.security.cs.allow-unsigned-executable-memory",
+    "com.apple.security.cs.debugger",
+    "com.apple.security.cs.disable-executable-page-protection",
+    "com.apple.security.cs.disable-library-validation",
+    "com.apple.security.device.audio-input",
+    "com.apple.security.device.bluetooth",
+    "com.apple.security.device.camera",
+    "com.apple.security.device.usb",
+    "com.apple.security.files.downloads.read-only",
+    "com.apple.security.files.downloads.read-write",
+    "com.apple.security.files.user-selected.read-only",
+    "com.apple.security.files.user-selected.read-write",
+    "com.apple.security.network.client",
+    "com.apple.security.network.server",
+    "com.apple.security.personal-information.addressbook",
+    "com.apple.security.personal-information.calendars",
+    "com.apple.security.personal-information.location",
+    "com.apple.security.personal-information.photos-library",
+    "com.apple.security.print",
+];
+
+/// How to sign a macOS binary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacOsSigningOptions {
+    /// Identity to sign with, as accepted by `codesign --sign`.
+    ///
+    /// `-` signs ad-hoc.
+    pub identity: String,
+
+    /// Whether to enable the hardened runtime.
+    pub hardened_runtime: bool,
+
+    /// Entitlements to grant.
+    pub entitlements: BTreeSet<String>,
+}
+
+impl MacOsSigningOptions {
+    /// Construct an instance, validating entitlement keys.
+    pub fn new(identity: &str, hardened_runtime: bool, entitlements: &[String]) -> Result<Self> {
+        if identity.is_empty() {
+            return Err(anyhow!("signing identity cannot be empty"));
+        }
+
+        for entitlement in entitlements {
+            if !KNOWN_ENTITLEMENTS.contains(&entitlement.as_str()) {
+                return Err(anyhow!("unknown entitlement: {}", entitlement));
+            }
+        }
+
+        Ok(Self {
+            identity: identity.to_string(),
+            hardened_runtime,
+            entitlements: entitlements.iter().cloned().collect(),
+        })
+    }
+
+    /// Derive the entitlements property list.
+    pub fn to_entitlements_plist(&self) -> String {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+            r#"<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">"#.to_string(),
+            r#"<plist version="1.0">"#.to_string(),
+            "<dict>".to_string(),
+        ];
+
+        for entitlement in &self.entitlements {
+            lines.push(format!("    <key>{}</key>", entitlement));
+            lines.push("    <true/>".to_string());
+        }
+
+        lines.push("</dict>".to_string());
+        lines.push("</plist>".to_string());
+        lines.push("".to_string());
+
+        lines.join("\n")
+    }
+
+    /// Obtain `codesign` arguments signing a binary.
+    pub fn codesign_args(&self, exe_path: &Path, entitlements_path: &Path) -> Vec<String> {
+        let mut args = vec![
+            "--force".to_string(),
+            "--sign".to_string(),
+            self.identity.clone(),
+        ];
+
+        if self.hardened_runtime {
+            args.push("--options".to_string());
+            args.push("runtime".to_string());
+        }
+
+        // Ad-hoc signatures can't be timestamped.
+        if self.identity != "-" {
+            args.push("--timestamp".to_string());
+        }
+
+        args.push("--entitlements".to_string());
+        args.push(entitlements_path.display().to_string());
+        args.push(exe_path.display().to_string());
+
+        args
+    }
+
+    /// Sign a binary in place.
+    pub fn sign(
+        &self,
+        logger: &slog::Logger,
+        exe_path: &Path,
+        entitlements_path: &Path,
+    ) -> Result<()> {
+        warn!(
+            logger,
+            "signing {} with {}",
+            exe_path.display(),
+            self.identity
+        );
+
+        let status = std::process::Command::new("codesign")
+            .args(self.codesign_args(exe_path, entitlements_path))
+            .status()
+            .or_else(|e| Err(anyhow!("running codesign: {}", e)))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("codesign failed"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(MacOsSigningOptions::new(
+            "Developer ID Application: Example",
+            true,
+            &["com.apple.security.device.audio-input".to_string()]
+        )
+        .is_ok());
+        assert!(MacOsSigningOptions::new(
+            "-",
+            true,
+            &["com.apple.security.device.microphone".to_string()]
+        )
+        .is_err());
+        assert!(MacOsSigningOptions::new("", true, &[]).is_err());
+    }
+
+    #[test]
+    fn test_entitlements_plist() -> Result<()> {
+        let options = MacOsSigningOptions::new(
+            "-",
+            true,
+            &[
+                "com.apple.security.network.client".to_string(),
+                "com.apple.security.cs.allow-unsigned-executable-memory".to_string(),
+            ],
+        )?;
+
+        let plist = options.to_entitlements_plist();
+        assert!(plist.contains(
+            "<dict>\n    <key>com.apple.security.cs.allow-unsigned-executable-memory</key>\n    <true/>\n    <key>com.apple.security.network.client</key>\n    <true/>\n</dict>"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_codesign_args() -> Result<()> {
+        let options = MacOsSigningOptions::new("Developer ID Application: Example", true, &[])?;
+        assert_eq!(
+            options.codesign_args(Path::new("app"), Path::new("app.entitlements")),
+            vec![
+                "--force",
+                "--sign",
+                "Developer ID Application: Example",
+                "--options",
+                "runtime",
+                "--timestamp",
+                "--entitlements",
+                "app.entitlements",
+                "app",
+            ]
+        );
+
+        let options = MacOsSigningOptions::new("-", false, &[])?;
+        assert_eq!(
+            options.codesign_args(Path::new("app"), Path::new("app.entitlements")),
+            vec![
+                "--force",
+                "--sign",
+                "-",
+                "--entitlements",
+                "app.entitlements",
+                "app",
+            ]
+        );
+
+        Ok(())
+    }
+}
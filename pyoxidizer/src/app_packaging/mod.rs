@@ -2,5 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod appimage;
+pub mod desktop_entry;
 pub mod glob;
+pub mod linux_service;
+pub mod macos_application_bundle;
+pub mod macos_signing;
 pub mod resource;
+pub mod snap;
+pub mod windows_manifest;
+pub mod wix;
@@ -3,11 +3,13 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    crate::atomic_output::{replace_dir_atomic, write_file_atomic},
     anyhow::{anyhow, Context, Result},
+    python_packaging::case_collision::CaseCollisionDetector,
+    python_packaging::long_path::extended_length_path,
     std::collections::btree_map::Iter,
     std::collections::{BTreeMap, BTreeSet},
     std::convert::TryFrom,
-    std::io::Write,
     std::path::{Path, PathBuf},
 };
 
@@ -52,8 +54,9 @@ impl TryFrom<&Path> for FileContent {
     type Error = std::io::Error;
 
     fn try_from(value: &Path) -> Result<Self, Self::Error> {
-        let data = std::fs::read(value)?;
-        let metadata = std::fs::metadata(value)?;
+        let path = extended_length_path(value);
+        let data = std::fs::read(&path)?;
+        let metadata = std::fs::metadata(&path)?;
         let executable = is_executable(&metadata);
 
         Ok(FileContent { data, executable })
@@ -64,10 +67,16 @@ impl TryFrom<&Path> for FileContent {
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct FileManifest {
     files: BTreeMap<PathBuf, FileContent>,
+
+    /// Detects files which would overwrite each other on case-insensitive filesystems.
+    case_collisions: CaseCollisionDetector,
 }
 
 impl FileManifest {
     /// Add a file to the manifest.
+    ///
+    /// Adding a file at an existing path replaces it. Adding a file whose
+    /// path only differs from an existing path by case is an error.
     pub fn add_file(&mut self, path: &Path, content: &FileContent) -> Result<()> {
         let path_s = path.display().to_string();
 
@@ -80,6 +89,7 @@ impl FileManifest {
             return Err(anyhow!("path cannot be absolute: {}", path.display()));
         }
 
+        self.case_collisions.add(path)?;
         self.files.insert(path.to_path_buf(), content.clone());
 
         Ok(())
@@ -138,21 +148,14 @@ impl FileManifest {
     }
 
     /// Write the contents of the install manifest to a filesystem path.
+    ///
+    /// Each file is written atomically.
     pub fn write_to_path(&self, path: &Path) -> Result<()> {
         for (p, c) in &self.files {
             let dest_path = path.join(p);
-            let parent = dest_path
-                .parent()
-                .ok_or_else(|| anyhow!("unable to resolve parent directory"))?;
 
-            std::fs::create_dir_all(parent)
-                .context("creating parent directory for FileManifest")?;
-
-            let mut fh = std::fs::File::create(&dest_path)?;
-            fh.write_all(&c.data)?;
-            if c.executable {
-                set_executable(&mut fh)?;
-            }
+            write_file_atomic(&dest_path, &c.data, c.executable)
+                .context("writing file of FileManifest")?;
         }
 
         Ok(())
@@ -160,12 +163,10 @@ impl FileManifest {
 
     /// Write the contents of the install manifest to a filesystem path,
     /// replacing any existing content at the specified path.
+    ///
+    /// Existing content is only replaced once all files are written.
     pub fn replace_path(&self, path: &Path) -> Result<()> {
-        if path.exists() {
-            std::fs::remove_dir_all(path)?;
-        }
-
-        self.write_to_path(path)
+        replace_dir_atomic(path, |staging_path| self.write_to_path(staging_path))
     }
 }
 
@@ -205,6 +206,27 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_add_case_collision() {
+        let mut v = FileManifest::default();
+        let f = FileContent {
+            data: vec![],
+            executable: false,
+        };
+
+        v.add_file(&PathBuf::from("lib/foo/bar.py"), &f).unwrap();
+        v.add_file(&PathBuf::from("lib/foo/bar.py"), &f).unwrap();
+
+        let err = v
+            .add_file(&PathBuf::from("lib/Foo/baz.py"), &f)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "lib/foo/bar.py and lib/Foo/baz.py would collide on case-insensitive filesystems, as lib/foo and lib/Foo differ only by case"
+        );
+        assert!(!v.has_path(&PathBuf::from("lib/Foo/baz.py")));
+    }
+
     #[test]
     fn test_relative_directories() {
         let mut v = FileManifest::default();
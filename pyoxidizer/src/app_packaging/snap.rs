@@ -0,0 +1,389 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Linux applications packaged as snaps.
+
+`snapcraft` builds snaps from a project directory described by a
+`snap/snapcraft.yaml` file. We derive a project installing the executable
+and its files with the `dump` plugin, from an `app` directory next to
+`snap`. A desktop entry and an icon are installed in `snap/gui`, where
+`snapcraft` looks for them. Running `snapcraft` in the project directory
+produces the `.snap`.
+*/
+
+use {
+    super::desktop_entry::DesktopEntry,
+    super::resource::{FileContent, FileManifest},
+    anyhow::{anyhow, Result},
+    std::path::{Path, PathBuf},
+};
+
+/// Bases snaps can be built on, named after the Ubuntu LTS they derive from.
+pub const SNAP_BASES: &[&str] = &["core18", "core20", "core22", "core24"];
+
+/// Settings of a snap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapOptions {
+    /// Name of the snap, which is also the name of its command.
+    pub name: String,
+
+    /// Version of the snap.
+    pub version: String,
+
+    /// Single line summary of the snap.
+    pub summary: String,
+
+    /// Description of the snap. The summary is used if empty.
+    pub description: String,
+
+    /// Base snap providing the run-time environment, e.g. `core18`.
+    pub base: String,
+
+    /// `stable` or `devel`.
+    pub grade: String,
+
+    /// `strict`, `classic` or `devmode`.
+    pub confinement: String,
+
+    /// Interfaces the application connects to, e.g. `network`.
+    pub plugs: Vec<String>,
+
+    /// Desktop entry of graphical applications.
+    ///
+    /// `Exec` and `Icon` are derived from the snap.
+    pub desktop_entry: Option<DesktopEntry>,
+
+    /// Extension and content of the icon file.
+    pub icon: Option<(String, Vec<u8>)>,
+}
+
+impl SnapOptions {
+    /// Validate these settings against the rules of the snap store.
+    pub fn validate(&self) -> Result<()> {
+        let name = &self.name;
+        if name.len() > 40
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            || !name.chars().any(|c| c.is_ascii_lowercase())
+            || name.starts_with('-')
+            || name.ends_with('-')
+            || name.contains("--")
+        {
+            return Err(anyhow!(
+                "snap name must be up to 40 lowercase letters, digits and single inner hyphens; got {}",
+                name
+            ));
+        }
+
+        let version = &self.version;
+        if version.is_empty()
+            || version.len() > 32
+            || !version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".+~-".contains(c))
+            || !version.starts_with(|c: char| c.is_ascii_alphanumeric())
+            || !version.ends_with(|c: char| c.is_ascii_alphanumeric())
+        {
+            return Err(anyhow!(
+                "snap version must be up to 32 letters, digits and .+~- characters; got {}",
+                version
+            ));
+        }
+
+        if self.summary.is_empty() || self.summary.len() > 78 || self.summary.contains('\n') {
+            return Err(anyhow!(
+                "snap summary must be a line of up to 78 characters"
+            ));
+        }
+
+        if !SNAP_BASES.contains(&self.base.as_str()) {
+            return Err(anyhow!(
+                "snap base must be one of {}; got {}",
+                SNAP_BASES.join(", "),
+                self.base
+            ));
+        }
+
+        if !["stable", "devel"].contains(&self.grade.as_str()) {
+            return Err(anyhow!(
+                "snap grade must be stable or devel; got {}",
+                self.grade
+            ));
+        }
+
+        if !["strict", "classic", "devmode"].contains(&self.confinement.as_str()) {
+            return Err(anyhow!(
+                "snap confinement must be strict, classic or devmode; got {}",
+                self.confinement
+            ));
+        }
+
+        for plug in &self.plugs {
+            if plug.is_empty()
+                || !plug
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                return Err(anyhow!("invalid snap plug: {}", plug));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the content of `snapcraft.yaml` running an executable.
+    pub fn to_snapcraft_yaml(&self, exe_name: &str) -> String {
+        let description = if self.description.is_empty() {
+            &self.summary
+        } else {
+            &self.description
+        };
+
+        let mut lines = vec![
+            format!("name: {}", self.name),
+            format!("base: {}", self.base),
+            format!("version: {}", quote_yaml(&self.version)),
+            format!("summary: {}", quote_yaml(&self.summary)),
+            "description: |".to_string(),
+        ];
+        lines.extend(description.lines().map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", line)
+            }
+        }));
+        lines.extend(vec![
+            format!("grade: {}", self.grade),
+            format!("confinement: {}", self.confinement),
+            String::new(),
+            "apps:".to_string(),
+            format!("  {}:", self.name),
+            format!("    command: {}", quote_yaml(exe_name)),
+        ]);
+
+        if !self.plugs.is_empty() {
+            lines.push("    plugs:".to_string());
+            lines.extend(self.plugs.iter().map(|plug| format!("      - {}", plug)));
+        }
+
+        lines.extend(vec![
+            String::new(),
+            "parts:".to_string(),
+            format!("  {}:", self.name),
+            "    plugin: dump".to_string(),
+            "    source: app".to_string(),
+        ]);
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Obtain the files of the snapcraft project of an executable.
+    ///
+    /// `files` are installed next to the executable. Paths are relative to
+    /// the project directory.
+    pub fn to_manifest(
+        &self,
+        exe_name: &str,
+        exe_data: &[u8],
+        files: &FileManifest,
+    ) -> Result<FileManifest> {
+        let snap = PathBuf::from("snap");
+        let app = PathBuf::from("app");
+
+        let mut manifest = FileManifest::default();
+
+        manifest.add_file(
+            &snap.join("snapcraft.yaml"),
+            &FileContent {
+                data: self.to_snapcraft_yaml(exe_name).into_bytes(),
+                executable: false,
+            },
+        )?;
+
+        if let Some((extension, data)) = &self.icon {
+            manifest.add_file(
+                &snap.join("gui").join(format!("icon.{}", extension)),
+                &FileContent {
+                    data: data.clone(),
+                    executable: false,
+                },
+            )?;
+        }
+
+        if let Some(entry) = &self.desktop_entry {
+            let entry = DesktopEntry {
+                exec: self.name.clone(),
+                icon: self
+                    .icon
+                    .as_ref()
+                    .map(|(extension, _)| format!("${{SNAP}}/meta/gui/icon.{}", extension)),
+                ..entry.clone()
+            };
+
+            manifest.add_file(
+                &snap.join("gui").join(format!("{}.desktop", self.name)),
+                &FileContent {
+                    data: entry.to_desktop_file().into_bytes(),
+                    executable: false,
+                },
+            )?;
+        }
+
+        manifest.add_file(
+            &app.join(exe_name),
+            &FileContent {
+                data: exe_data.to_vec(),
+                executable: true,
+            },
+        )?;
+
+        for (path, content) in files.entries() {
+            manifest.add_file(&app.join(path), content)?;
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// Quote a YAML string scalar.
+fn quote_yaml(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Obtain the path of the snapcraft project of a snap in a directory.
+pub fn snapcraft_project_path(output_path: &Path, name: &str) -> PathBuf {
+    output_path.join(format!("{}-snap", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> SnapOptions {
+        SnapOptions {
+            name: "myapp".to_string(),
+            version: "1.0".to_string(),
+            summary: "My app".to_string(),
+            description: String::new(),
+            base: "core18".to_string(),
+            grade: "stable".to_string(),
+            confinement: "strict".to_string(),
+            plugs: vec![],
+            desktop_entry: None,
+            icon: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(options().validate().is_ok());
+
+        for name in &["", "MyApp", "-myapp", "my--app", "123", &"a".repeat(41)] {
+            let mut o = options();
+            o.name = name.to_string();
+            assert!(o.validate().is_err(), "{}", name);
+        }
+
+        let mut o = options();
+        o.version = "1.0 beta".to_string();
+        assert!(o.validate().is_err());
+
+        for base in &["", "core", "core19", "core18\nconfinement: classic", "bare"] {
+            let mut o = options();
+            o.base = base.to_string();
+            assert!(o.validate().is_err(), "{}", base);
+        }
+
+        let mut o = options();
+        o.base = "core22".to_string();
+        assert!(o.validate().is_ok());
+
+        let mut o = options();
+        o.grade = "beta".to_string();
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.confinement = "none".to_string();
+        assert!(o.validate().is_err());
+
+        let mut o = options();
+        o.plugs = vec!["Network".to_string()];
+        assert!(o.validate().is_err());
+    }
+
+    #[test]
+    fn test_to_snapcraft_yaml() {
+        let mut o = options();
+        o.description = "It's an app.\n\nReally.".to_string();
+        o.plugs = vec!["network".to_string(), "home".to_string()];
+
+        assert_eq!(
+            o.to_snapcraft_yaml("myapp-bin"),
+            "name: myapp\n\
+             base: core18\n\
+             version: '1.0'\n\
+             summary: 'My app'\n\
+             description: |\n  It's an app.\n\n  Really.\n\
+             grade: stable\n\
+             confinement: strict\n\
+             \n\
+             apps:\n  myapp:\n    command: 'myapp-bin'\n    plugs:\n      - network\n      - home\n\
+             \n\
+             parts:\n  myapp:\n    plugin: dump\n    source: app\n"
+        );
+    }
+
+    #[test]
+    fn test_to_manifest() -> Result<()> {
+        let mut o = options();
+        o.icon = Some(("png".to_string(), vec![42]));
+        o.desktop_entry = Some(DesktopEntry {
+            name: "My App".to_string(),
+            exec: String::new(),
+            icon: None,
+            comment: None,
+            categories: vec![],
+            terminal: false,
+        });
+
+        let mut files = FileManifest::default();
+        files.add_file(
+            Path::new("lib/foo.py"),
+            &FileContent {
+                data: vec![42],
+                executable: false,
+            },
+        )?;
+
+        let manifest = o.to_manifest("myapp-bin", &[1, 2], &files)?;
+
+        let paths = manifest
+            .entries()
+            .map(|(path, content)| (path.clone(), content.executable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                (PathBuf::from("app/lib/foo.py"), false),
+                (PathBuf::from("app/myapp-bin"), true),
+                (PathBuf::from("snap/gui/icon.png"), false),
+                (PathBuf::from("snap/gui/myapp.desktop"), false),
+                (PathBuf::from("snap/snapcraft.yaml"), false),
+            ]
+        );
+
+        let desktop = manifest
+            .entries()
+            .find(|(path, _)| path.as_path() == Path::new("snap/gui/myapp.desktop"))
+            .map(|(_, content)| String::from_utf8_lossy(&content.data).to_string())
+            .unwrap();
+        assert!(desktop.contains("\nExec=myapp\n"));
+        assert!(desktop.contains("\nIcon=${SNAP}/meta/gui/icon.png\n"));
+
+        Ok(())
+    }
+}
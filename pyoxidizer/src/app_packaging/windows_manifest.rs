@@ -0,0 +1,270 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Windows application manifests.
+
+Windows reads settings such as DPI awareness, long path support and the
+privileges to request on launch from an XML manifest embedded in the
+executable. This module derives such a manifest from structured options and
+the MSVC linker arguments embedding it.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    std::path::Path,
+};
+
+/// How an application handles display scaling.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DpiAwareness {
+    /// Windows scales the application's windows.
+    Unaware,
+
+    /// The application scales to the DPI of the primary display.
+    System,
+
+    /// The application scales to the DPI of each display.
+    PerMonitor,
+
+    /// Like `PerMonitor`, with Windows scaling non-client areas and dialogs.
+    ///
+    /// Falls back to `PerMonitor` on Windows versions not supporting it.
+    PerMonitorV2,
+}
+
+impl DpiAwareness {
+    /// Resolve an instance from its Starlark name.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "unaware" => Ok(DpiAwareness::Unaware),
+            "system" => Ok(DpiAwareness::System),
+            "per-monitor" => Ok(DpiAwareness::PerMonitor),
+            "per-monitor-v2" => Ok(DpiAwareness::PerMonitorV2),
+            _ => Err(anyhow!(
+                "DPI awareness must be one of unaware, system, per-monitor or per-monitor-v2; got {}",
+                name
+            )),
+        }
+    }
+
+    /// Value of the `dpiAware` setting, honored by Windows before 10 1607.
+    fn dpi_aware(self) -> &'static str {
+        match self {
+            DpiAwareness::Unaware => "false",
+            DpiAwareness::System => "true",
+            DpiAwareness::PerMonitor | DpiAwareness::PerMonitorV2 => "true/pm",
+        }
+    }
+
+    /// Value of the `dpiAwareness` setting, which overrides `dpiAware`.
+    fn dpi_awareness(self) -> &'static str {
+        match self {
+            DpiAwareness::Unaware => "unaware",
+            DpiAwareness::System => "system",
+            DpiAwareness::PerMonitor => "PerMonitor",
+            DpiAwareness::PerMonitorV2 => "PerMonitorV2, PerMonitor",
+        }
+    }
+}
+
+/// Privileges an application requests when launched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UacExecutionLevel {
+    /// Run with the privileges of the parent process.
+    AsInvoker,
+
+    /// Run with the highest privileges available to the user.
+    HighestAvailable,
+
+    /// Run as an administrator, prompting for elevation if needed.
+    RequireAdministrator,
+}
+
+impl UacExecutionLevel {
+    /// Resolve an instance from its Starlark name.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "asInvoker" => Ok(UacExecutionLevel::AsInvoker),
+            "highestAvailable" => Ok(UacExecutionLevel::HighestAvailable),
+            "requireAdministrator" => Ok(UacExecutionLevel::RequireAdministrator),
+            _ => Err(anyhow!(
+                "UAC execution level must be one of asInvoker, highestAvailable or requireAdministrator; got {}",
+                name
+            )),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            UacExecutionLevel::AsInvoker => "asInvoker",
+            UacExecutionLevel::HighestAvailable => "highestAvailable",
+            UacExecutionLevel::RequireAdministrator => "requireAdministrator",
+        }
+    }
+}
+
+/// Settings of a Windows application manifest.
+///
+/// The default value only enables long paths, which deeply nested package
+/// data typically needs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowsManifestOptions {
+    /// How the application handles display scaling.
+    pub dpi_awareness: Option<DpiAwareness>,
+
+    /// Whether the application supports paths longer than `MAX_PATH`.
+    ///
+    /// Long paths also need to be enabled system-wide.
+    pub long_path_aware: bool,
+
+    /// Privileges the application requests when launched.
+    pub uac_execution_level: Option<UacExecutionLevel>,
+}
+
+impl Default for WindowsManifestOptions {
+    fn default() -> Self {
+        Self {
+            dpi_awareness: None,
+            long_path_aware: true,
+            uac_execution_level: None,
+        }
+    }
+}
+
+impl WindowsManifestOptions {
+    /// Whether these options require a custom manifest.
+    ///
+    /// Otherwise, the manifest the linker produces is equivalent.
+    pub fn requires_custom_manifest(&self) -> bool {
+        self.dpi_awareness.is_some() || self.long_path_aware || self.uac_execution_level.is_some()
+    }
+
+    /// Derive the XML of the manifest.
+    pub fn to_xml(&self) -> String {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#.to_string(),
+            r#"<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0">"#
+                .to_string(),
+            r#"  <trustInfo xmlns="urn:schemas-microsoft-com:asm.v3">"#.to_string(),
+            "    <security>".to_string(),
+            "      <requestedPrivileges>".to_string(),
+            format!(
+                r#"        <requestedExecutionLevel level="{}" uiAccess="false"/>"#,
+                self.uac_execution_level
+                    .unwrap_or(UacExecutionLevel::AsInvoker)
+                    .as_str()
+            ),
+            "      </requestedPrivileges>".to_string(),
+            "    </security>".to_string(),
+            "  </trustInfo>".to_string(),
+        ];
+
+        if self.dpi_awareness.is_some() || self.long_path_aware {
+            lines.push(r#"  <application xmlns="urn:schemas-microsoft-com:asm.v3">"#.to_string());
+            lines.push("    <windowsSettings>".to_string());
+
+            if let Some(dpi_awareness) = self.dpi_awareness {
+                lines.push(format!(
+                    r#"      <dpiAware xmlns="http://schemas.microsoft.com/SMI/2005/WindowsSettings">{}</dpiAware>"#,
+                    dpi_awareness.dpi_aware()
+                ));
+                lines.push(format!(
+                    r#"      <dpiAwareness xmlns="http://schemas.microsoft.com/SMI/2016/WindowsSettings">{}</dpiAwareness>"#,
+                    dpi_awareness.dpi_awareness()
+                ));
+            }
+
+            if self.long_path_aware {
+                lines.push(
+                    r#"      <longPathAware xmlns="http://schemas.microsoft.com/SMI/2016/WindowsSettings">true</longPathAware>"#
+                        .to_string(),
+                );
+            }
+
+            lines.push("    </windowsSettings>".to_string());
+            lines.push("  </application>".to_string());
+        }
+
+        lines.push("</assembly>".to_string());
+        lines.push("".to_string());
+
+        lines.join("\r\n")
+    }
+
+    /// Obtain MSVC linker arguments embedding the manifest written to a path.
+    pub fn linker_args(&self, manifest_path: &Path) -> Vec<String> {
+        vec![
+            "/MANIFEST:EMBED".to_string(),
+            format!("/MANIFESTINPUT:{}", manifest_path.display()),
+            // The execution level is defined by our manifest.
+            "/MANIFESTUAC:NO".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() -> Result<()> {
+        assert_eq!(
+            DpiAwareness::from_name("per-monitor-v2")?,
+            DpiAwareness::PerMonitorV2
+        );
+        assert!(DpiAwareness::from_name("PerMonitorV2").is_err());
+
+        assert_eq!(
+            UacExecutionLevel::from_name("requireAdministrator")?,
+            UacExecutionLevel::RequireAdministrator
+        );
+        assert!(UacExecutionLevel::from_name("admin").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default() {
+        let options = WindowsManifestOptions::default();
+        assert!(options.requires_custom_manifest());
+
+        let xml = options.to_xml();
+        assert!(xml.contains(r#"<requestedExecutionLevel level="asInvoker" uiAccess="false"/>"#));
+        assert!(xml.contains(">true</longPathAware>"));
+        assert!(!xml.contains("dpiAware"));
+
+        let options = WindowsManifestOptions {
+            long_path_aware: false,
+            ..WindowsManifestOptions::default()
+        };
+        assert!(!options.requires_custom_manifest());
+        assert!(!options.to_xml().contains("windowsSettings"));
+    }
+
+    #[test]
+    fn test_settings() {
+        let options = WindowsManifestOptions {
+            dpi_awareness: Some(DpiAwareness::PerMonitorV2),
+            long_path_aware: true,
+            uac_execution_level: Some(UacExecutionLevel::HighestAvailable),
+        };
+        assert!(options.requires_custom_manifest());
+
+        let xml = options.to_xml();
+        assert!(xml.contains(r#"level="highestAvailable""#));
+        assert!(xml.contains(">true/pm</dpiAware>"));
+        assert!(xml.contains(">PerMonitorV2, PerMonitor</dpiAwareness>"));
+        assert!(xml.contains(">true</longPathAware>"));
+
+        assert_eq!(
+            options.linker_args(Path::new("app.manifest")),
+            vec![
+                "/MANIFEST:EMBED".to_string(),
+                "/MANIFESTINPUT:app.manifest".to_string(),
+                "/MANIFESTUAC:NO".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,683 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Windows MSI installers built with the WiX Toolset.
+
+A WiX source file (`.wxs`) installing every file of a `FileManifest` into
+`Program Files` is derived from `WixMsiOptions`. The WiX Toolset then compiles
+it (`candle.exe`) and links it into an `.msi` (`light.exe`). The toolset only
+runs on Windows.
+*/
+
+use {
+    super::resource::FileManifest,
+    crate::atomic_output::produce_path_atomic,
+    crate::py_packaging::download::{download_to_path, get_http_client},
+    anyhow::{anyhow, Context, Result},
+    fs2::FileExt,
+    sha2::{Digest, Sha256},
+    slog::warn,
+    std::collections::{BTreeMap, BTreeSet},
+    std::io::Read,
+    std::path::{Path, PathBuf},
+};
+
+const WIX_TOOLSET_URL: &str =
+    "https://github.com/wixtoolset/wix3/releases/download/wix3111rtm/wix311-binaries.zip";
+const WIX_TOOLSET_SHA256: &str = "37f0a533b0978a454efb5dc3bd3598becf9660aaf4287e55bf68ca6b527d051d";
+
+/// Oldest (major, minor) version of installed WiX Toolsets that is used.
+///
+/// The WiX source we generate is WiX 3 source. WiX 4 changed the format and
+/// the tools, so only WiX 3 toolsets are used.
+const WIX_TOOLSET_MINIMUM_VERSION: (u32, u32) = (3, 10);
+
+/// A Start menu shortcut created by an installer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WixShortcut {
+    /// Name of the shortcut.
+    pub name: String,
+
+    /// Path of the file the shortcut opens, relative to the install directory.
+    pub target: PathBuf,
+}
+
+/// Settings of an MSI installer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WixMsiOptions {
+    /// Prefix of identifiers in the WiX source file.
+    pub id_prefix: String,
+
+    /// Name of the product, which is also the install directory name.
+    pub product_name: String,
+
+    /// Version of the product, e.g. `1.2.3`.
+    pub product_version: String,
+
+    /// Manufacturer of the product.
+    pub product_manufacturer: String,
+
+    /// GUID identifying the product across versions.
+    ///
+    /// Installing a version upgrades previously installed versions having
+    /// the same upgrade code. Derived from `id_prefix` if not defined.
+    pub upgrade_code: Option<String>,
+
+    /// Start menu shortcuts to create.
+    pub shortcuts: Vec<WixShortcut>,
+}
+
+impl WixMsiOptions {
+    /// Validate a product version.
+    ///
+    /// MSI versions consist of up to 4 numeric components. The first two
+    /// components are at most 255 and the others at most 65535.
+    pub fn validate_version(version: &str) -> Result<()> {
+        let components = version.split('.').collect::<Vec<_>>();
+
+        if components.len() > 4
+            || components
+                .iter()
+                .any(|c| c.is_empty() || c.parse::<u32>().is_err())
+        {
+            return Err(anyhow!(
+                "product version must consist of up to 4 numeric components; got {}",
+                version
+            ));
+        }
+
+        for (i, component) in components.iter().enumerate() {
+            let maximum = if i < 2 { 255 } else { 65535 };
+
+            if component.parse::<u32>()? > maximum {
+                return Err(anyhow!(
+                    "component {} of product version must be at most {}; got {}",
+                    i + 1,
+                    maximum,
+                    version
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the upgrade code of the installer.
+    pub fn resolved_upgrade_code(&self) -> String {
+        match &self.upgrade_code {
+            Some(code) => code.clone(),
+            None => uuid::Uuid::new_v5(
+                &uuid::Uuid::NAMESPACE_DNS,
+                format!("pyoxidizer.{}.msi", self.id_prefix).as_bytes(),
+            )
+            .to_string(),
+        }
+    }
+
+    /// Obtain the file name of the installer for a target triple.
+    pub fn msi_filename(&self, target_triple: &str) -> Result<String> {
+        let (platform, _) = wix_platform(target_triple)?;
+
+        Ok(format!(
+            "{}-{}-{}.msi",
+            self.product_name, self.product_version, platform
+        ))
+    }
+
+    /// Derive the WiX source installing the files of a manifest.
+    ///
+    /// File sources are relative to the `SourceDir` preprocessor variable.
+    pub fn to_wxs(&self, manifest: &FileManifest, target_triple: &str) -> Result<String> {
+        let (platform, program_files) = wix_platform(target_triple)?;
+
+        for shortcut in &self.shortcuts {
+            if !manifest.has_path(&shortcut.target) {
+                return Err(anyhow!(
+                    "target of shortcut {} is not installed: {}",
+                    shortcut.name,
+                    shortcut.target.display()
+                ));
+            }
+        }
+
+        let mut files: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        for (path, _) in manifest.entries() {
+            files
+                .entry(path.parent().unwrap_or_else(|| Path::new("")).to_path_buf())
+                .or_default()
+                .push(path.clone());
+        }
+
+        let mut directories = BTreeSet::new();
+        directories.insert(PathBuf::new());
+        directories.extend(manifest.relative_directories());
+
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="utf-8"?>"#.to_string(),
+            r#"<Wix xmlns="http://schemas.microsoft.com/wix/2006/wi">"#.to_string(),
+            format!(
+                r#"  <Product Id="*" Name="{}" Language="1033" Version="{}" Manufacturer="{}" UpgradeCode="{}">"#,
+                escape_xml(&self.product_name),
+                escape_xml(&self.product_version),
+                escape_xml(&self.product_manufacturer),
+                escape_xml(&self.resolved_upgrade_code()),
+            ),
+            format!(
+                r#"    <Package InstallerVersion="500" Compressed="yes" InstallScope="perMachine" Platform="{}" />"#,
+                platform
+            ),
+            r#"    <MajorUpgrade DowngradeErrorMessage="A newer version of [ProductName] is already installed." />"#
+                .to_string(),
+            r#"    <MediaTemplate EmbedCab="yes" />"#.to_string(),
+            r#"    <Directory Id="TARGETDIR" Name="SourceDir">"#.to_string(),
+            format!(r#"      <Directory Id="{}">"#, program_files),
+            format!(
+                r#"        <Directory Id="INSTALLDIR" Name="{}">"#,
+                escape_xml(&self.product_name)
+            ),
+        ];
+
+        let mut components = Vec::new();
+        self.write_directory(
+            &mut lines,
+            &mut components,
+            Path::new(""),
+            &directories,
+            &files,
+            5,
+        );
+
+        lines.push("        </Directory>".to_string());
+        lines.push("      </Directory>".to_string());
+
+        if !self.shortcuts.is_empty() {
+            let component_id = format!("{}.shortcuts", self.id_prefix);
+
+            lines.push(r#"      <Directory Id="ProgramMenuFolder">"#.to_string());
+            lines.push(format!(
+                r#"        <Directory Id="ProgramMenuDir" Name="{}">"#,
+                escape_xml(&self.product_name)
+            ));
+            lines.push(format!(
+                r#"          <Component Id="{}" Guid="*">"#,
+                component_id
+            ));
+
+            for (i, shortcut) in self.shortcuts.iter().enumerate() {
+                lines.push(format!(
+                    r#"            <Shortcut Id="{}.shortcut.{}" Name="{}" Target="[INSTALLDIR]{}" WorkingDirectory="INSTALLDIR" />"#,
+                    self.id_prefix,
+                    i,
+                    escape_xml(&shortcut.name),
+                    escape_xml(&windows_path(&shortcut.target)),
+                ));
+            }
+
+            lines.push(
+                r#"            <RemoveFolder Id="ProgramMenuDir" On="uninstall" />"#.to_string(),
+            );
+            lines.push(format!(
+                r#"            <RegistryValue Root="HKCU" Key="Software\{}\{}" Name="shortcuts" Type="integer" Value="1" KeyPath="yes" />"#,
+                escape_xml(&self.product_manufacturer),
+                escape_xml(&self.product_name),
+            ));
+            lines.push("          </Component>".to_string());
+            lines.push("        </Directory>".to_string());
+            lines.push("      </Directory>".to_string());
+
+            components.push(component_id);
+        }
+
+        lines.push("    </Directory>".to_string());
+        lines.push(format!(
+            r#"    <Feature Id="{}.feature" Title="{}" Level="1">"#,
+            self.id_prefix,
+            escape_xml(&self.product_name)
+        ));
+        for component in components {
+            lines.push(format!(r#"      <ComponentRef Id="{}" />"#, component));
+        }
+        lines.push("    </Feature>".to_string());
+        lines.push("  </Product>".to_string());
+        lines.push("</Wix>".to_string());
+        lines.push("".to_string());
+
+        Ok(lines.join("\r\n"))
+    }
+
+    /// Write the components of files in a directory and its subdirectories.
+    fn write_directory(
+        &self,
+        lines: &mut Vec<String>,
+        components: &mut Vec<String>,
+        directory: &Path,
+        directories: &BTreeSet<PathBuf>,
+        files: &BTreeMap<PathBuf, Vec<PathBuf>>,
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth);
+
+        for path in files.get(directory).map(|x| x.as_slice()).unwrap_or(&[]) {
+            let id = format!("{}.{}", self.id_prefix, path_id(path));
+
+            lines.push(format!(r#"{}<Component Id="{}" Guid="*">"#, indent, id));
+            lines.push(format!(
+                r#"{}  <File Id="{}" Source="$(var.SourceDir)\{}" KeyPath="yes" />"#,
+                indent,
+                id,
+                escape_xml(&windows_path(path))
+            ));
+            lines.push(format!("{}</Component>", indent));
+
+            components.push(id);
+        }
+
+        for child in directories
+            .iter()
+            .filter(|d| d.parent() == Some(directory) && d.as_os_str() != "")
+        {
+            lines.push(format!(
+                r#"{}<Directory Id="{}.{}" Name="{}">"#,
+                indent,
+                self.id_prefix,
+                path_id(child),
+                escape_xml(&child.file_name().unwrap().to_string_lossy())
+            ));
+            self.write_directory(lines, components, child, directories, files, depth + 1);
+            lines.push(format!("{}</Directory>", indent));
+        }
+    }
+}
+
+/// Resolve the WiX platform and Program Files directory of a target triple.
+fn wix_platform(target_triple: &str) -> Result<(&'static str, &'static str)> {
+    match target_triple {
+        "x86_64-pc-windows-msvc" => Ok(("x64", "ProgramFiles64Folder")),
+        "i686-pc-windows-msvc" => Ok(("x86", "ProgramFilesFolder")),
+        _ => Err(anyhow!(
+            "MSI installers can't be built for target {}",
+            target_triple
+        )),
+    }
+}
+
+/// Derive a WiX identifier for a path.
+///
+/// Identifiers are limited to 72 characters, so paths are hashed.
+fn path_id(path: &Path) -> String {
+    let digest = Sha256::digest(windows_path(path).as_bytes());
+
+    format!("p{}", &hex::encode(digest)[0..32])
+}
+
+/// Format a relative path with Windows separators.
+fn windows_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("\\")
+}
+
+/// Escape a value for use in an XML attribute.
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Parse the (major, minor) version of the WiX Toolset from `candle.exe` output.
+///
+/// The first line of the output reads e.g.
+/// `Windows Installer XML Toolset Compiler version 3.11.2.4516`.
+fn parse_wix_toolset_version(output: &str) -> Option<(u32, u32)> {
+    let line = output.lines().find(|line| line.contains(" version "))?;
+    let version = line.rsplit(' ').next()?;
+
+    let mut components = version.split('.');
+    let major = components.next()?.parse::<u32>().ok()?;
+    let minor = components.next()?.parse::<u32>().ok()?;
+
+    Some((major, minor))
+}
+
+/// Whether an installed WiX Toolset can be used.
+fn is_wix_toolset_supported(logger: &slog::Logger, bin_dir: &Path) -> Result<bool> {
+    let candle = bin_dir.join("candle.exe");
+    let output = std::process::Command::new(&candle)
+        .arg("-?")
+        .output()
+        .context(format!("running {}", candle.display()))?;
+
+    match parse_wix_toolset_version(&String::from_utf8_lossy(&output.stdout)) {
+        Some((major, minor))
+            if major == WIX_TOOLSET_MINIMUM_VERSION.0 && minor >= WIX_TOOLSET_MINIMUM_VERSION.1 =>
+        {
+            Ok(true)
+        }
+        version => {
+            warn!(
+                logger,
+                "ignoring WiX Toolset {} of unsupported version {:?}; version 3.{} or newer 3.x is required",
+                bin_dir.display(),
+                version,
+                WIX_TOOLSET_MINIMUM_VERSION.1
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// Find the directory containing the WiX Toolset binaries.
+///
+/// An installed toolset is found through the `WIX` environment variable,
+/// which the WiX installer defines, if it has a supported version.
+/// Otherwise the toolset is downloaded to `cache_dir`, which can be shared by
+/// concurrent builds, unless `offline` is set.
+fn resolve_wix_toolset(logger: &slog::Logger, cache_dir: &Path, offline: bool) -> Result<PathBuf> {
+    if let Ok(path) = std::env::var("WIX") {
+        let path = PathBuf::from(path).join("bin");
+
+        if path.join("candle.exe").exists() && is_wix_toolset_supported(logger, &path)? {
+            return Ok(path);
+        }
+    }
+
+    std::fs::create_dir_all(cache_dir).context(format!("creating {}", cache_dir.display()))?;
+
+    let lock_path = cache_dir.join("wix311.lock");
+    let lock =
+        std::fs::File::create(&lock_path).context(format!("creating {}", lock_path.display()))?;
+    lock.lock_exclusive()
+        .context(format!("failed to obtain lock for {}", lock_path.display()))?;
+
+    let toolset_path = cache_dir.join("wix311");
+    if toolset_path.join("candle.exe").exists() {
+        return Ok(toolset_path);
+    }
+
+    if offline {
+        return Err(anyhow!(
+            "WiX Toolset isn't installed and downloading it requires network access, which is disabled in offline mode"
+        ));
+    }
+
+    let archive_path = cache_dir.join("wix311-binaries.zip");
+    download_to_path(
+        logger,
+        &get_http_client()?,
+        WIX_TOOLSET_URL,
+        WIX_TOOLSET_SHA256,
+        &archive_path,
+    )?;
+
+    warn!(
+        logger,
+        "extracting WiX Toolset to {}",
+        toolset_path.display()
+    );
+    // Extract next to the final location, so an interrupted extraction
+    // isn't mistaken for the toolset.
+    let partial_path = cache_dir.join("wix311.partial");
+    if partial_path.exists() {
+        std::fs::remove_dir_all(&partial_path)
+            .context(format!("removing {}", partial_path.display()))?;
+    }
+
+    let mut zf = zip::ZipArchive::new(std::fs::File::open(&archive_path)?)?;
+    for i in 0..zf.len() {
+        let mut f = zf.by_index(i)?;
+        if !f.is_file() {
+            continue;
+        }
+
+        let dest_path = partial_path.join(f.sanitized_name());
+        std::fs::create_dir_all(dest_path.parent().unwrap())?;
+
+        let mut data = Vec::new();
+        f.read_to_end(&mut data)?;
+        std::fs::write(&dest_path, data).context(format!("writing {}", dest_path.display()))?;
+    }
+
+    std::fs::rename(&partial_path, &toolset_path)
+        .context(format!("renaming {}", partial_path.display()))?;
+
+    Ok(toolset_path)
+}
+
+/// Run a WiX Toolset program.
+fn run_wix_tool(logger: &slog::Logger, exe: &Path, args: &[String], cwd: &Path) -> Result<()> {
+    warn!(logger, "running {}", exe.display());
+
+    let output = std::process::Command::new(exe)
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .context(format!("running {}", exe.display()))?;
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        warn!(logger, "{}", line);
+    }
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("error running {}", exe.display()))
+    }
+}
+
+/// Build an MSI installer installing the files of a manifest.
+///
+/// Intermediate files are written to `build_path`. The WiX Toolset is
+/// downloaded to `toolset_cache_dir` if it isn't installed, unless `offline`
+/// is set. Returns the path of the installer, which is written to
+/// `output_path`.
+pub fn build_msi(
+    logger: &slog::Logger,
+    options: &WixMsiOptions,
+    manifest: &FileManifest,
+    target_triple: &str,
+    build_path: &Path,
+    toolset_cache_dir: &Path,
+    offline: bool,
+    output_path: &Path,
+) -> Result<PathBuf> {
+    let wxs = options.to_wxs(manifest, target_triple)?;
+    let (platform, _) = wix_platform(target_triple)?;
+
+    if !cfg!(windows) {
+        return Err(anyhow!("MSI installers can only be built on Windows"));
+    }
+
+    let files_path = build_path.join("files");
+    manifest.replace_path(&files_path)?;
+    std::fs::write(build_path.join("main.wxs"), wxs)?;
+
+    let toolset_path = resolve_wix_toolset(logger, toolset_cache_dir, offline)?;
+
+    run_wix_tool(
+        logger,
+        &toolset_path.join("candle.exe"),
+        &[
+            "-nologo".to_string(),
+            "-arch".to_string(),
+            platform.to_string(),
+            format!("-dSourceDir={}", files_path.display()),
+            "-out".to_string(),
+            "main.wixobj".to_string(),
+            "main.wxs".to_string(),
+        ],
+        build_path,
+    )?;
+
+    let msi_path = output_path.join(options.msi_filename(target_triple)?);
+    produce_path_atomic(&msi_path, |staged_path| {
+        run_wix_tool(
+            logger,
+            &toolset_path.join("light.exe"),
+            &[
+                "-nologo".to_string(),
+                "-o".to_string(),
+                staged_path.display().to_string(),
+                "main.wixobj".to_string(),
+            ],
+            build_path,
+        )
+    })?;
+
+    Ok(msi_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::app_packaging::resource::FileContent};
+
+    fn options() -> WixMsiOptions {
+        WixMsiOptions {
+            id_prefix: "myapp".to_string(),
+            product_name: "My App".to_string(),
+            product_version: "1.0.0".to_string(),
+            product_manufacturer: "Me & You".to_string(),
+            upgrade_code: None,
+            shortcuts: vec![],
+        }
+    }
+
+    fn manifest() -> Result<FileManifest> {
+        let mut manifest = FileManifest::default();
+        let content = FileContent {
+            data: vec![42],
+            executable: false,
+        };
+
+        manifest.add_file(Path::new("myapp.exe"), &content)?;
+        manifest.add_file(Path::new("lib/foo/bar.py"), &content)?;
+
+        Ok(manifest)
+    }
+
+    #[test]
+    fn test_validate_version() {
+        assert!(WixMsiOptions::validate_version("1").is_ok());
+        assert!(WixMsiOptions::validate_version("1.2.3.4").is_ok());
+        assert!(WixMsiOptions::validate_version("1.2.3.4.5").is_err());
+        assert!(WixMsiOptions::validate_version("1.0-beta").is_err());
+        assert!(WixMsiOptions::validate_version("1..2").is_err());
+        assert!(WixMsiOptions::validate_version("255.255.65535.65535").is_ok());
+        assert!(WixMsiOptions::validate_version("256.0").is_err());
+        assert!(WixMsiOptions::validate_version("1.256").is_err());
+        assert!(WixMsiOptions::validate_version("1.0.65536").is_err());
+    }
+
+    #[test]
+    fn test_parse_wix_toolset_version() {
+        assert_eq!(
+            parse_wix_toolset_version(
+                "Windows Installer XML Toolset Compiler version 3.11.2.4516\r\nCopyright (c) .NET Foundation and contributors. All rights reserved.\r\n"
+            ),
+            Some((3, 11))
+        );
+        assert_eq!(parse_wix_toolset_version("usage: candle.exe"), None);
+    }
+
+    #[test]
+    fn test_resolve_wix_toolset_offline() -> Result<()> {
+        let logger = crate::testutil::get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        if std::env::var("WIX").is_err() {
+            assert!(resolve_wix_toolset(&logger, temp_dir.path(), true).is_err());
+        }
+
+        // A previously downloaded toolset is used.
+        let toolset_path = temp_dir.path().join("wix311");
+        std::fs::create_dir_all(&toolset_path)?;
+        std::fs::write(toolset_path.join("candle.exe"), b"")?;
+
+        if std::env::var("WIX").is_err() {
+            assert_eq!(
+                resolve_wix_toolset(&logger, temp_dir.path(), true)?,
+                toolset_path
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_code() {
+        let mut options = options();
+        let code = options.resolved_upgrade_code();
+        assert_eq!(code, options.resolved_upgrade_code());
+
+        options.id_prefix = "other".to_string();
+        assert_ne!(code, options.resolved_upgrade_code());
+
+        options.upgrade_code = Some("41e3ec6f-3bd0-4c1b-a3ca-0fcd85aaff92".to_string());
+        assert_eq!(
+            options.resolved_upgrade_code(),
+            "41e3ec6f-3bd0-4c1b-a3ca-0fcd85aaff92"
+        );
+    }
+
+    #[test]
+    fn test_to_wxs() -> Result<()> {
+        let options = options();
+        let wxs = options.to_wxs(&manifest()?, "x86_64-pc-windows-msvc")?;
+
+        assert!(wxs.contains(r#"Name="My App""#));
+        assert!(wxs.contains(r#"Manufacturer="Me &amp; You""#));
+        assert!(wxs.contains(r#"Platform="x64""#));
+        assert!(wxs.contains(r#"<Directory Id="ProgramFiles64Folder">"#));
+        assert!(wxs.contains(r#"Source="$(var.SourceDir)\myapp.exe""#));
+        assert!(wxs.contains(r#"Source="$(var.SourceDir)\lib\foo\bar.py""#));
+        assert!(wxs.contains(r#"Name="lib">"#));
+        assert!(wxs.contains(r#"Name="foo">"#));
+        assert_eq!(wxs.matches("<ComponentRef ").count(), 2);
+        assert!(!wxs.contains("ProgramMenuFolder"));
+
+        assert!(options
+            .to_wxs(&manifest()?, "x86_64-unknown-linux-gnu")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_wxs_shortcuts() -> Result<()> {
+        let mut options = options();
+        options.shortcuts.push(WixShortcut {
+            name: "My App".to_string(),
+            target: PathBuf::from("myapp.exe"),
+        });
+
+        let wxs = options.to_wxs(&manifest()?, "i686-pc-windows-msvc")?;
+        assert!(wxs.contains(r#"Platform="x86""#));
+        assert!(wxs.contains(r#"Target="[INSTALLDIR]myapp.exe""#));
+        assert_eq!(wxs.matches("<ComponentRef ").count(), 3);
+
+        options.shortcuts.push(WixShortcut {
+            name: "Missing".to_string(),
+            target: PathBuf::from("missing.exe"),
+        });
+        assert!(options
+            .to_wxs(&manifest()?, "i686-pc-windows-msvc")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_msi_filename() -> Result<()> {
+        assert_eq!(
+            options().msi_filename("x86_64-pc-windows-msvc")?,
+            "My App-1.0.0-x64.msi"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,464 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Atomic writing of build outputs and rollback of failed builds.
+
+Artifacts are written to temporary paths next to their destination and
+renamed into place once complete, so an interrupted write never leaves a
+truncated artifact behind. Directories are replaced by populating a staging
+directory and swapping it with the existing directory.
+
+Before a target is built, the files in its output directory are recorded.
+If the build fails, files created by the failed build and leftover temporary
+paths are deleted, so later builds don't mistake them for valid artifacts.
+*/
+
+use {
+    crate::app_packaging::resource::set_executable,
+    anyhow::{anyhow, Context, Result},
+    python_packaging::long_path::extended_length_path,
+    slog::warn,
+    std::collections::BTreeSet,
+    std::io::Write,
+    std::path::{Path, PathBuf},
+};
+
+/// Marker in the names of temporary paths written by this module.
+pub const TEMP_MARKER: &str = ".pyoxidizer-tmp-";
+
+#[cfg(test)]
+thread_local! {
+    /// Number of atomic operations to perform before failing, if any.
+    static FAIL_AFTER: std::cell::Cell<Option<usize>> = std::cell::Cell::new(None);
+}
+
+/// Make the atomic operation after `count` more of them fail.
+#[cfg(test)]
+pub fn inject_failure(count: Option<usize>) {
+    FAIL_AFTER.with(|f| f.set(count));
+}
+
+/// Fail if a failure was injected for the current atomic operation.
+///
+/// Called after temporary content is written and before it is moved into
+/// place, simulating an interrupted build.
+fn check_injected_failure() -> Result<()> {
+    #[cfg(test)]
+    {
+        if let Some(count) = FAIL_AFTER.with(|f| f.get()) {
+            if count == 0 {
+                FAIL_AFTER.with(|f| f.set(None));
+                return Err(anyhow!("injected failure"));
+            }
+
+            FAIL_AFTER.with(|f| f.set(Some(count - 1)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Obtain a temporary path next to `path`.
+fn temp_path(path: &Path) -> Result<PathBuf> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("unable to resolve parent directory of {}", path.display()))?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("unable to resolve file name of {}", path.display()))?;
+
+    Ok(parent.join(format!(
+        ".{}{}{}",
+        name.to_string_lossy(),
+        TEMP_MARKER,
+        uuid::Uuid::new_v4()
+    )))
+}
+
+/// Write a file atomically.
+///
+/// The data is written to a temporary file in the same directory, which is
+/// then renamed to `path`. Parent directories are created as needed.
+pub fn write_file_atomic(path: &Path, data: &[u8], executable: bool) -> Result<()> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("unable to resolve parent directory of {}", path.display()))?;
+    std::fs::create_dir_all(extended_length_path(parent))
+        .with_context(|| format!("creating {}", parent.display()))?;
+
+    let temp_path = temp_path(path)?;
+
+    let res = (|| -> Result<()> {
+        let mut fh = std::fs::File::create(extended_length_path(&temp_path))
+            .with_context(|| format!("creating {}", temp_path.display()))?;
+        fh.write_all(data)
+            .with_context(|| format!("writing {}", temp_path.display()))?;
+        if executable {
+            set_executable(&mut fh)?;
+        }
+        fh.sync_all()?;
+        drop(fh);
+
+        check_injected_failure()?;
+
+        std::fs::rename(extended_length_path(&temp_path), extended_length_path(path))
+            .with_context(|| format!("renaming {} to {}", temp_path.display(), path.display()))
+    })();
+
+    if res.is_err() {
+        let _ = std::fs::remove_file(extended_length_path(&temp_path));
+    }
+
+    res
+}
+
+/// Replace a directory atomically.
+///
+/// `populate` is called with an empty staging directory next to `path`. If
+/// it succeeds, the staging directory replaces `path`. Otherwise, the staging
+/// directory is deleted and `path` is left untouched.
+pub fn replace_dir_atomic<F>(path: &Path, populate: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let staging_path = temp_path(path)?;
+    std::fs::create_dir_all(&staging_path)
+        .with_context(|| format!("creating {}", staging_path.display()))?;
+
+    let res = populate(&staging_path).and_then(|_| check_injected_failure());
+    if let Err(e) = res {
+        let _ = std::fs::remove_dir_all(extended_length_path(&staging_path));
+        return Err(e);
+    }
+
+    let res = move_into_place(&staging_path, path);
+    if res.is_err() {
+        let _ = std::fs::remove_dir_all(extended_length_path(&staging_path));
+    }
+
+    res
+}
+
+/// Produce a file or directory atomically.
+///
+/// `produce` is called with a path having the file name of `path` inside a
+/// staging directory next to `path`, and must create a file or a directory
+/// there, e.g. by running an external tool writing its output to it. If it
+/// succeeds, the produced path replaces `path`. Otherwise, the staging
+/// directory is deleted and `path` is left untouched.
+///
+/// Unlike with `replace_dir_atomic()`, the produced path keeps the name of
+/// `path`, for tools caring about file extensions.
+pub fn produce_path_atomic<F>(path: &Path, produce: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("unable to resolve file name of {}", path.display()))?;
+
+    let staging_path = temp_path(path)?;
+    std::fs::create_dir_all(&staging_path)
+        .with_context(|| format!("creating {}", staging_path.display()))?;
+    let produced_path = staging_path.join(name);
+
+    let res = produce(&produced_path)
+        .and_then(|_| {
+            if produced_path.exists() {
+                Ok(())
+            } else {
+                Err(anyhow!("{} was not produced", path.display()))
+            }
+        })
+        .and_then(|_| check_injected_failure())
+        .and_then(|_| move_into_place(&produced_path, path));
+
+    let _ = std::fs::remove_dir_all(extended_length_path(&staging_path));
+
+    res
+}
+
+/// Move a complete file or directory to `path`, replacing what's there.
+fn move_into_place(source: &Path, path: &Path) -> Result<()> {
+    // Renaming over a directory isn't possible, so the existing path is
+    // moved aside first and restored if the swap fails.
+    let old_path = if path.is_dir() || (source.is_dir() && path.exists()) {
+        let old_path = temp_path(path)?;
+        std::fs::rename(path, &old_path)
+            .with_context(|| format!("moving {} aside", path.display()))?;
+        Some(old_path)
+    } else {
+        None
+    };
+
+    if let Err(e) = std::fs::rename(extended_length_path(source), extended_length_path(path)) {
+        if let Some(old_path) = &old_path {
+            let _ = std::fs::rename(old_path, path);
+        }
+
+        return Err(e).with_context(|| format!("replacing {}", path.display()));
+    }
+
+    if let Some(old_path) = old_path {
+        if old_path.is_dir() {
+            std::fs::remove_dir_all(extended_length_path(&old_path))
+        } else {
+            std::fs::remove_file(extended_length_path(&old_path))
+        }
+        .with_context(|| format!("removing {}", old_path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Records the files of a directory so a failed build can be rolled back.
+#[derive(Clone, Debug)]
+pub struct OutputSnapshot {
+    root: PathBuf,
+    existed: bool,
+    paths: BTreeSet<PathBuf>,
+}
+
+impl OutputSnapshot {
+    /// Record the paths in a directory, which may not exist.
+    pub fn capture(root: &Path) -> Result<Self> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            existed: root.exists(),
+            paths: walk(root)?.into_iter().collect(),
+        })
+    }
+
+    /// Delete paths created since the snapshot was captured.
+    ///
+    /// Files that existed before are left in place: they are only ever
+    /// replaced atomically, so they hold either their previous or their new
+    /// complete content. Temporary paths are always deleted.
+    ///
+    /// Returns the deleted paths.
+    pub fn rollback(&self, logger: &slog::Logger) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+
+        // Children are visited before their parents, so directories are
+        // empty by the time they are considered.
+        for path in walk(&self.root)?.into_iter().rev() {
+            let is_temp = path
+                .file_name()
+                .map(|name| name.to_string_lossy().contains(TEMP_MARKER))
+                .unwrap_or(false);
+
+            if !is_temp && self.paths.contains(&path) {
+                continue;
+            }
+
+            warn!(logger, "rolling back {}", path.display());
+            if path.is_dir() {
+                std::fs::remove_dir_all(extended_length_path(&path))
+            } else {
+                std::fs::remove_file(extended_length_path(&path))
+            }
+            .with_context(|| format!("removing {}", path.display()))?;
+
+            removed.push(path);
+        }
+
+        if !self.existed && self.root.exists() {
+            warn!(logger, "rolling back {}", self.root.display());
+            std::fs::remove_dir_all(extended_length_path(&self.root))
+                .with_context(|| format!("removing {}", self.root.display()))?;
+            removed.push(self.root.clone());
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Obtain the paths in a directory tree, parents first.
+fn walk(root: &Path) -> Result<Vec<PathBuf>> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = Vec::new();
+
+    // Paths are returned in their usual form, relative to `root`.
+    let walk_root = extended_length_path(root);
+
+    for entry in walkdir::WalkDir::new(&walk_root)
+        .min_depth(1)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    {
+        let entry = entry.with_context(|| format!("walking {}", root.display()))?;
+        paths.push(root.join(entry.path().strip_prefix(&walk_root)?));
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*};
+
+    fn read_tree(root: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+        let mut res = Vec::new();
+
+        for path in walk(root)? {
+            if path.is_file() {
+                res.push((
+                    path.strip_prefix(root)?.to_path_buf(),
+                    std::fs::read(&path)?,
+                ));
+            }
+        }
+
+        Ok(res)
+    }
+
+    #[test]
+    fn test_write_file_atomic() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let path = temp_dir.path().join("dir").join("file");
+
+        write_file_atomic(&path, b"old", false)?;
+        assert_eq!(std::fs::read(&path)?, b"old");
+
+        inject_failure(Some(0));
+        assert!(write_file_atomic(&path, b"new", true).is_err());
+        assert_eq!(std::fs::read(&path)?, b"old");
+        assert_eq!(read_tree(temp_dir.path())?.len(), 1);
+
+        write_file_atomic(&path, b"new", true)?;
+        assert_eq!(std::fs::read(&path)?, b"new");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_dir_atomic_interrupted() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let path = temp_dir.path().join("out");
+
+        replace_dir_atomic(&path, |staging| {
+            write_file_atomic(&staging.join("old"), b"old", false)
+        })?;
+        let original = read_tree(&path)?;
+
+        // Interrupt each phase: each of the 3 file writes, then the swap.
+        for count in 0..4 {
+            inject_failure(Some(count));
+
+            let res = replace_dir_atomic(&path, |staging| {
+                for name in &["a", "b/c", "d"] {
+                    write_file_atomic(&staging.join(name), name.as_bytes(), false)?;
+                }
+
+                Ok(())
+            });
+
+            assert!(res.is_err(), "phase {} should fail", count);
+            assert_eq!(read_tree(&path)?, original);
+            assert_eq!(walk(temp_dir.path())?.len(), 2);
+        }
+
+        inject_failure(None);
+        replace_dir_atomic(&path, |staging| {
+            write_file_atomic(&staging.join("new"), b"new", false)
+        })?;
+        assert_eq!(
+            read_tree(&path)?,
+            vec![(PathBuf::from("new"), b"new".to_vec())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_produce_path_atomic_interrupted() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let path = temp_dir.path().join("app.msi");
+
+        produce_path_atomic(&path, |produced| {
+            assert_eq!(produced.file_name(), path.file_name());
+            std::fs::write(produced, b"old")?;
+            Ok(())
+        })?;
+        assert_eq!(std::fs::read(&path)?, b"old");
+
+        // A tool failing after writing part of its output.
+        let res = produce_path_atomic(&path, |produced| {
+            std::fs::write(produced, b"ne")?;
+            Err(anyhow!("tool killed"))
+        });
+        assert!(res.is_err());
+        assert_eq!(std::fs::read(&path)?, b"old");
+        assert_eq!(walk(temp_dir.path())?.len(), 1);
+
+        // A tool succeeding without writing its output.
+        assert!(produce_path_atomic(&path, |_| Ok(())).is_err());
+        assert_eq!(std::fs::read(&path)?, b"old");
+
+        // An interruption once the output is complete.
+        inject_failure(Some(0));
+        let res = produce_path_atomic(&path, |produced| {
+            std::fs::write(produced, b"new")?;
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert_eq!(std::fs::read(&path)?, b"old");
+        assert_eq!(walk(temp_dir.path())?.len(), 1);
+
+        // Directories replace files, and the other way around.
+        produce_path_atomic(&path, |produced| {
+            write_file_atomic(&produced.join("file"), b"new", false)
+        })?;
+        assert_eq!(
+            read_tree(temp_dir.path())?,
+            vec![(PathBuf::from("app.msi/file"), b"new".to_vec())]
+        );
+        produce_path_atomic(&path, |produced| write_file_atomic(produced, b"new", false))?;
+        assert_eq!(std::fs::read(&path)?, b"new");
+        assert_eq!(walk(temp_dir.path())?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path().join("out");
+
+        write_file_atomic(&root.join("cache").join("tool"), b"tool", false)?;
+        write_file_atomic(&root.join("app"), b"old", false)?;
+        let snapshot = OutputSnapshot::capture(&root)?;
+
+        // A build replacing a file then failing while writing others.
+        write_file_atomic(&root.join("app"), b"new", false)?;
+        write_file_atomic(&root.join("lib").join("module.py"), b"", false)?;
+        inject_failure(Some(0));
+        assert!(write_file_atomic(&root.join("cache").join("data"), b"data", false).is_err());
+        std::fs::write(
+            root.join(format!(".partial{}interrupted", TEMP_MARKER)),
+            b"",
+        )?;
+
+        let removed = snapshot.rollback(&logger)?;
+        assert_eq!(removed.len(), 3);
+        assert_eq!(
+            read_tree(&root)?,
+            vec![
+                (PathBuf::from("app"), b"new".to_vec()),
+                (PathBuf::from("cache/tool"), b"tool".to_vec()),
+            ]
+        );
+
+        // Rolling back a directory that didn't exist deletes it entirely.
+        let snapshot = OutputSnapshot::capture(&temp_dir.path().join("missing"))?;
+        write_file_atomic(&temp_dir.path().join("missing").join("file"), b"", false)?;
+        assert_eq!(snapshot.rollback(&logger)?.len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Benchmarking built binaries against a reference Python interpreter.
+
+Startup time is a common reason for packaging choices (e.g. importing from
+memory versus the filesystem). This module measures the wall time of
+processes running a built binary and a reference interpreter running the
+same entry point, so the two can be compared.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::ffi::OsString,
+    std::path::PathBuf,
+    std::process::{Command, Stdio},
+    std::time::{Duration, Instant},
+};
+
+/// A process invocation to measure.
+#[derive(Clone, Debug)]
+pub struct BenchmarkCommand {
+    /// Program to execute.
+    pub program: PathBuf,
+
+    /// Arguments to pass to the program.
+    pub args: Vec<OsString>,
+
+    /// Extra environment variables to define.
+    pub envs: Vec<(OsString, OsString)>,
+}
+
+impl BenchmarkCommand {
+    /// Obtain a command with additional arguments.
+    pub fn with_args(&self, args: &[&str]) -> Self {
+        let mut res = self.clone();
+        res.args.extend(args.iter().map(OsString::from));
+        res
+    }
+
+    /// Execute the command and return how long it took.
+    ///
+    /// Output of the process is discarded. The process must exit successfully.
+    pub fn time(&self) -> Result<Duration> {
+        let start = Instant::now();
+
+        let status = Command::new(&self.program)
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("running {}", self.program.display()))?;
+
+        let elapsed = start.elapsed();
+
+        if status.success() {
+            Ok(elapsed)
+        } else {
+            Err(anyhow!(
+                "{} {:?} exited with {}",
+                self.program.display(),
+                self.args,
+                status
+            ))
+        }
+    }
+}
+
+/// Timings of repeated executions of a command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Samples {
+    /// Duration of the first execution.
+    ///
+    /// This approximates a cold start. Operating system caches may still
+    /// hold files used by the process.
+    pub first: Duration,
+
+    /// Durations of subsequent executions, in order of execution.
+    pub warm: Vec<Duration>,
+}
+
+impl Samples {
+    /// Execute a command `iterations + 1` times.
+    pub fn collect(command: &BenchmarkCommand, iterations: usize) -> Result<Self> {
+        let first = command.time()?;
+
+        let warm = (0..iterations)
+            .map(|_| command.time())
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { first, warm })
+    }
+
+    /// The fastest warm execution.
+    pub fn min(&self) -> Duration {
+        self.warm.iter().min().cloned().unwrap_or(self.first)
+    }
+
+    /// The median warm execution.
+    pub fn median(&self) -> Duration {
+        if self.warm.is_empty() {
+            return self.first;
+        }
+
+        let mut sorted = self.warm.clone();
+        sorted.sort();
+
+        let middle = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            (sorted[middle - 1] + sorted[middle]) / 2
+        } else {
+            sorted[middle]
+        }
+    }
+}
+
+/// A measurement compared between the built binary and the reference interpreter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkRow {
+    /// What was measured.
+    pub name: String,
+
+    /// Value for the built binary.
+    pub oxidized: Duration,
+
+    /// Value for the reference interpreter.
+    pub reference: Duration,
+}
+
+/// Options controlling what is measured.
+#[derive(Clone, Debug)]
+pub struct BenchmarkOptions {
+    /// Number of warm executions of each command.
+    pub iterations: usize,
+
+    /// Modules to measure the import time of.
+    pub imports: Vec<String>,
+}
+
+/// Time importing a module, relative to executing a no-op.
+///
+/// `python` must accept `python` command line arguments.
+fn import_time(python: &BenchmarkCommand, module: &str, iterations: usize) -> Result<Duration> {
+    let baseline = Samples::collect(&python.with_args(&["-c", "pass"]), iterations)?;
+    let import = Samples::collect(
+        &python.with_args(&["-c", &format!("import {}", module)]),
+        iterations,
+    )?;
+
+    Ok(import
+        .median()
+        .checked_sub(baseline.median())
+        .unwrap_or_default())
+}
+
+/// Compare a built binary against a reference interpreter.
+///
+/// `oxidized` and `reference` run the application's entry point.
+/// `oxidized_python` runs the built binary as a `python` executable and is
+/// required to measure import times.
+pub fn compare(
+    oxidized: &BenchmarkCommand,
+    oxidized_python: Option<&BenchmarkCommand>,
+    reference: &BenchmarkCommand,
+    options: &BenchmarkOptions,
+) -> Result<Vec<BenchmarkRow>> {
+    let oxidized_samples = Samples::collect(oxidized, options.iterations)?;
+    let reference_samples = Samples::collect(reference, options.iterations)?;
+
+    let mut rows = vec![
+        BenchmarkRow {
+            name: "cold startup".to_string(),
+            oxidized: oxidized_samples.first,
+            reference: reference_samples.first,
+        },
+        BenchmarkRow {
+            name: "warm startup (median)".to_string(),
+            oxidized: oxidized_samples.median(),
+            reference: reference_samples.median(),
+        },
+        BenchmarkRow {
+            name: "warm startup (min)".to_string(),
+            oxidized: oxidized_samples.min(),
+            reference: reference_samples.min(),
+        },
+    ];
+
+    if !options.imports.is_empty() {
+        let oxidized_python = oxidized_python.ok_or_else(|| {
+            anyhow!("measuring import times requires the binary to act as a python executable")
+        })?;
+
+        // The reference interpreter is invoked without the entry point arguments.
+        let reference_python = BenchmarkCommand {
+            program: reference.program.clone(),
+            args: vec![],
+            envs: reference.envs.clone(),
+        };
+
+        for module in &options.imports {
+            rows.push(BenchmarkRow {
+                name: format!("import {} (median)", module),
+                oxidized: import_time(oxidized_python, module, options.iterations)?,
+                reference: import_time(&reference_python, module, options.iterations)?,
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+fn format_duration(value: Duration) -> String {
+    format!("{:.1} ms", value.as_secs_f64() * 1000.0)
+}
+
+/// Render benchmark results as a text table.
+pub fn format_table(rows: &[BenchmarkRow]) -> String {
+    let mut lines = vec![(
+        "measurement".to_string(),
+        "oxidized".to_string(),
+        "reference".to_string(),
+        "ratio".to_string(),
+    )];
+
+    for row in rows {
+        let ratio = if row.reference.as_nanos() == 0 {
+            "-".to_string()
+        } else {
+            format!(
+                "{:.2}x",
+                row.oxidized.as_secs_f64() / row.reference.as_secs_f64()
+            )
+        };
+
+        lines.push((
+            row.name.clone(),
+            format_duration(row.oxidized),
+            format_duration(row.reference),
+            ratio,
+        ));
+    }
+
+    let width0 = lines.iter().map(|l| l.0.len()).max().unwrap_or(0);
+    let width1 = lines.iter().map(|l| l.1.len()).max().unwrap_or(0);
+    let width2 = lines.iter().map(|l| l.2.len()).max().unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|(name, oxidized, reference, ratio)| {
+            format!(
+                "{:<w0$}  {:>w1$}  {:>w2$}  {}\n",
+                name,
+                oxidized,
+                reference,
+                ratio,
+                w0 = width0,
+                w1 = width1,
+                w2 = width2
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn test_samples() {
+        let samples = Samples {
+            first: ms(50),
+            warm: vec![ms(12), ms(10), ms(30), ms(11)],
+        };
+        assert_eq!(samples.min(), ms(10));
+        assert_eq!(samples.median(), Duration::from_micros(11500));
+
+        let samples = Samples {
+            first: ms(50),
+            warm: vec![],
+        };
+        assert_eq!(samples.min(), ms(50));
+        assert_eq!(samples.median(), ms(50));
+    }
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![
+            BenchmarkRow {
+                name: "cold startup".to_string(),
+                oxidized: ms(10),
+                reference: ms(40),
+            },
+            BenchmarkRow {
+                name: "import json (median)".to_string(),
+                oxidized: ms(1),
+                reference: Duration::default(),
+            },
+        ];
+
+        assert_eq!(
+            format_table(&rows),
+            "measurement           oxidized  reference  ratio\n\
+             cold startup           10.0 ms    40.0 ms  0.25x\n\
+             import json (median)    1.0 ms     0.0 ms  -\n"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Conversion of BeeWare briefcase configuration to PyOxidizer configuration.
+
+Briefcase is configured by the `[tool.briefcase]` section of a project's
+`pyproject.toml`. Each app of the project is defined by a
+`[tool.briefcase.app.<name>]` section listing its source packages and
+requirements, with additional requirements for each platform in sections
+like `[tool.briefcase.app.<name>.macOS]`.
+*/
+
+use {
+    crate::pyinstaller_spec::{script_module, starlark_list, starlark_str, SpecConversion},
+    anyhow::{anyhow, Context, Result},
+    std::path::Path,
+    toml::value::Table,
+};
+
+/// Keys describing a project or an app, which have no effect.
+const METADATA_KEYS: &[&str] = &[
+    "project_name",
+    "formal_name",
+    "bundle",
+    "version",
+    "description",
+    "long_description",
+    "url",
+    "license",
+    "author",
+    "author_email",
+    "template",
+    "template_branch",
+    "test_sources",
+    "test_requires",
+    "supported",
+];
+
+/// Briefcase platforms and the `os` of the equivalent Starlark `Platform`.
+const PLATFORMS: &[(&str, &str)] = &[
+    ("macOS", "macos"),
+    ("linux", "linux"),
+    ("windows", "windows"),
+];
+
+/// Briefcase platforms without an equivalent.
+const UNSUPPORTED_PLATFORMS: &[&str] = &["iOS", "android", "web"];
+
+/// Read a list of strings from a table.
+fn string_list(table: &Table, key: &str, section: &str) -> Result<Vec<String>> {
+    let error = || anyhow!("{} of [{}] must be a list of strings", key, section);
+
+    match table.get(key) {
+        Some(toml::Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_str().map(|s| s.to_string()).ok_or_else(error))
+            .collect(),
+        Some(_) => Err(error()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Convert the briefcase configuration of an app to Starlark.
+///
+/// `pyproject_name` is the name of the parsed `pyproject.toml`. `app_name`
+/// selects the app to convert and can be omitted if the project defines a
+/// single app.
+pub fn convert_pyproject(
+    pyproject_name: &str,
+    pyproject: &toml::Value,
+    app_name: Option<&str>,
+) -> Result<SpecConversion> {
+    let briefcase = pyproject
+        .get("tool")
+        .and_then(|tool| tool.get("briefcase"))
+        .and_then(|briefcase| briefcase.as_table())
+        .ok_or_else(|| anyhow!("no [tool.briefcase] section found in {}", pyproject_name))?;
+    let apps = briefcase
+        .get("app")
+        .and_then(|apps| apps.as_table())
+        .ok_or_else(|| anyhow!("no [tool.briefcase.app] section found"))?;
+
+    let (name, app) = match app_name {
+        Some(app_name) => apps
+            .iter()
+            .find(|(name, _)| name.as_str() == app_name)
+            .ok_or_else(|| anyhow!("app {} not found in [tool.briefcase.app]", app_name))?,
+        None if apps.len() == 1 => apps.iter().next().unwrap(),
+        None => {
+            return Err(anyhow!(
+                "several apps are defined; choose one of {}",
+                apps.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        }
+    };
+    let section = format!("tool.briefcase.app.{}", name);
+    let app = app
+        .as_table()
+        .ok_or_else(|| anyhow!("[{}] must be a table", section))?;
+
+    let mut res = SpecConversion::default();
+
+    for key in briefcase.keys() {
+        if key == "app" || key == "requires" || METADATA_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+
+        res.flag(format!("`[tool.briefcase] {}`: no equivalent", key));
+    }
+
+    for key in app.keys() {
+        if key == "sources"
+            || key == "requires"
+            || METADATA_KEYS.contains(&key.as_str())
+            || PLATFORMS.iter().any(|(platform, _)| platform == key)
+        {
+            continue;
+        }
+
+        let message = if UNSUPPORTED_PLATFORMS.contains(&key.as_str()) {
+            "mobile and web platforms aren't supported"
+        } else {
+            match key.as_str() {
+                "icon" | "splash" | "installer_icon" | "installer_background" => {
+                    "icons and splash screens aren't supported"
+                }
+                "document_type" => "document types are only registered by installers",
+                _ => "no equivalent",
+            }
+        };
+
+        res.flag(format!("`[{}] {}`: {}", section, key, message));
+    }
+
+    let sources = string_list(app, "sources", &section)?;
+    if sources.is_empty() {
+        return Err(anyhow!("[{}] has no sources", section));
+    }
+
+    let mut requires = string_list(briefcase, "requires", "tool.briefcase")?;
+    requires.extend(string_list(app, "requires", &section)?);
+
+    // Briefcase runs the app package, which is named after the app.
+    let module = name.replace('-', "_");
+
+    let mut lines = vec![
+        format!(
+            "# Generated from {} by `pyoxidizer import-briefcase-config`.",
+            pyproject_name
+        ),
+        "#".to_string(),
+        format!(
+            "# These fragments replicate the `{}` app in a configuration file",
+            name
+        ),
+        "# created by `pyoxidizer init-config-file`. Paths are relative to the".to_string(),
+        "# directory containing pyproject.toml, which should also contain the".to_string(),
+        "# configuration file.".to_string(),
+        String::new(),
+        "# In make_exe():".to_string(),
+        "python_config = PythonInterpreterConfig(".to_string(),
+        format!("    run_module={},", starlark_str(&module)),
+        ")".to_string(),
+        String::new(),
+        "exe = dist.to_python_executable(".to_string(),
+        format!("    name={},", starlark_str(name)),
+        "    config=python_config,".to_string(),
+        "    extension_module_filter=\"all\",".to_string(),
+        "    include_sources=True,".to_string(),
+        "    include_resources=True,".to_string(),
+        ")".to_string(),
+        String::new(),
+        format!(
+            "# `sources` of the app. The `{}` package is run through its",
+            module
+        ),
+        "# `__main__` module, as by briefcase.".to_string(),
+    ];
+
+    for source in &sources {
+        let (root, package) = script_module(source);
+
+        lines.push(format!(
+            "exe.add_in_memory_python_resources(dist.read_package_root({}, {}))",
+            root,
+            starlark_list(&[package])
+        ));
+    }
+
+    if !requires.is_empty() {
+        lines.extend(vec![
+            String::new(),
+            "# `requires` of the project and the app.".to_string(),
+            format!(
+                "exe.add_in_memory_python_resources(dist.pip_install({}))",
+                starlark_list(&requires)
+            ),
+        ]);
+    }
+
+    for (platform, os) in PLATFORMS {
+        let platform_section = format!("{}.{}", section, platform);
+        let table = match app.get(*platform) {
+            Some(table) => table
+                .as_table()
+                .ok_or_else(|| anyhow!("[{}] must be a table", platform_section))?,
+            None => continue,
+        };
+
+        for key in table.keys() {
+            if key != "requires" {
+                res.flag(format!("`[{}] {}`: no equivalent", platform_section, key));
+            }
+        }
+
+        let requires = string_list(table, "requires", &platform_section)?;
+        if !requires.is_empty() {
+            lines.extend(vec![
+                String::new(),
+                format!("# `requires` of the app on {}.", platform),
+                format!("if BUILD_TARGET.os == {}:", starlark_str(os)),
+                format!(
+                    "    exe.add_in_memory_python_resources(dist.pip_install({}))",
+                    starlark_list(&requires)
+                ),
+            ]);
+        }
+    }
+
+    lines.extend(vec![
+        String::new(),
+        "# In make_install():".to_string(),
+        "files = FileManifest()".to_string(),
+        "files.add_python_resource(\".\", exe)".to_string(),
+        String::new(),
+        "# Write files to a directory of the build path.".to_string(),
+        format!("files.install({})", starlark_str(name)),
+    ]);
+
+    res.finish(lines, "briefcase configuration");
+
+    Ok(res)
+}
+
+/// Read a `pyproject.toml` and convert the briefcase configuration of an app
+/// to Starlark.
+pub fn import_pyproject(path: &Path, app_name: Option<&str>) -> Result<SpecConversion> {
+    let source = std::fs::read_to_string(path).context(format!("reading {}", path.display()))?;
+    let pyproject = source
+        .parse::<toml::Value>()
+        .context(format!("parsing {}", path.display()))?;
+
+    let pyproject_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("unable to determine file name"))?
+        .to_string_lossy()
+        .to_string();
+
+    convert_pyproject(&pyproject_name, &pyproject, app_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PYPROJECT: &str = r#"[tool.briefcase]
+project_name = "Hello World"
+bundle = "com.example"
+version = "0.0.1"
+license = "BSD license"
+requires = ["requests"]
+
+[tool.briefcase.app.hello-world]
+formal_name = "Hello World"
+description = "My first application"
+icon = "src/hello_world/resources/hello_world"
+sources = ["src/hello_world", "lib/helpers.py"]
+requires = ["toga-core>=0.3.0"]
+
+[tool.briefcase.app.hello-world.macOS]
+requires = ["toga-cocoa>=0.3.0"]
+
+[tool.briefcase.app.hello-world.linux]
+requires = ["toga-gtk>=0.3.0"]
+system_requires = ["libgirepository1.0-dev"]
+
+[tool.briefcase.app.hello-world.android]
+requires = ["toga-android>=0.3.0"]
+"#;
+
+    #[test]
+    fn test_convert_pyproject() -> Result<()> {
+        let pyproject = PYPROJECT.parse::<toml::Value>()?;
+
+        let res = convert_pyproject("pyproject.toml", &pyproject, None)?;
+
+        assert!(res.starlark.contains("    run_module=\"hello_world\",\n"));
+        assert!(res.starlark.contains("    name=\"hello-world\",\n"));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(\
+             dist.read_package_root(CWD + \"/src\", [\"hello_world\"]))\n"
+        ));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(\
+             dist.read_package_root(CWD + \"/lib\", [\"helpers\"]))\n"
+        ));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(\
+             dist.pip_install([\"requests\", \"toga-core>=0.3.0\"]))\n"
+        ));
+        assert!(res.starlark.contains(
+            "if BUILD_TARGET.os == \"macos\":\n    \
+             exe.add_in_memory_python_resources(dist.pip_install([\"toga-cocoa>=0.3.0\"]))\n"
+        ));
+        assert!(res.starlark.contains(
+            "if BUILD_TARGET.os == \"linux\":\n    \
+             exe.add_in_memory_python_resources(dist.pip_install([\"toga-gtk>=0.3.0\"]))\n"
+        ));
+        assert!(!res.starlark.contains("toga-android"));
+        assert!(res.starlark.contains("files.install(\"hello-world\")\n"));
+
+        assert_eq!(
+            res.unsupported,
+            vec![
+                "`[tool.briefcase.app.hello-world] android`: \
+                 mobile and web platforms aren't supported",
+                "`[tool.briefcase.app.hello-world] icon`: \
+                 icons and splash screens aren't supported",
+                "`[tool.briefcase.app.hello-world.linux] system_requires`: no equivalent",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_pyproject_app_selection() -> Result<()> {
+        let pyproject = r#"[tool.briefcase.app.first]
+sources = ["src/first"]
+
+[tool.briefcase.app.second]
+sources = ["src/second"]
+"#
+        .parse::<toml::Value>()?;
+
+        assert!(convert_pyproject("pyproject.toml", &pyproject, None).is_err());
+        assert!(convert_pyproject("pyproject.toml", &pyproject, Some("third")).is_err());
+
+        let res = convert_pyproject("pyproject.toml", &pyproject, Some("second"))?;
+        assert!(res.starlark.contains("    run_module=\"second\",\n"));
+        assert!(res.unsupported.is_empty());
+
+        let pyproject = "[tool.poetry]\nname = \"x\"\n".parse::<toml::Value>()?;
+        assert!(convert_pyproject("pyproject.toml", &pyproject, None).is_err());
+
+        Ok(())
+    }
+}
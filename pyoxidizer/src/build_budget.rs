@@ -0,0 +1,489 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Budgets of disk space and memory used by builds.
+
+On constrained machines, such as CI runners, builds running out of disk
+space or memory die mid-way with opaque errors. When budgets are defined,
+each target is checked before being built, against an estimate of what
+building it needs, and after being built, against actual usage. Builds
+then fail early with a message naming the budget, and the output of a
+target exceeding a budget is removed.
+
+While a target builds, processes run with `run_within_budget()`, such as
+`cargo`, are polled against the budgets of the target and killed as soon
+as one is exceeded, so a runaway build doesn't run to completion.
+
+Disk usage is the size of the build directory. Memory usage is the peak
+resident memory of PyOxidizer and of processes it ran, such as `cargo`.
+Time is how long building a target takes.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    std::cell::RefCell,
+    std::path::{Path, PathBuf},
+    std::process::{Command, ExitStatus},
+    std::time::{Duration, Instant},
+};
+
+/// Interval between checks of the budget while a process runs.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between checks of whether a process exited.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Units of sizes, largest first.
+const UNITS: &[(&str, u64)] = &[
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+];
+
+/// Parse a size in bytes, e.g. `512M` or `1.5G`.
+///
+/// `K`, `M`, `G` and `T` suffixes, optionally followed by `B` or `iB`, are
+/// powers of 1024.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let trimmed = value.trim();
+    let upper = trimmed.to_uppercase();
+    let number = upper
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+        .trim_end();
+
+    let (number, multiplier) = match UNITS.iter().find(|(suffix, _)| number.ends_with(suffix)) {
+        Some((suffix, multiplier)) => (&number[..number.len() - suffix.len()], *multiplier),
+        None => (number, 1),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(number) if number >= 0.0 && number.is_finite() => {
+            Ok((number * multiplier as f64) as u64)
+        }
+        _ => Err(anyhow!("invalid size: {}", value)),
+    }
+}
+
+/// Format a size in bytes for humans.
+pub fn format_size(size: u64) -> String {
+    match UNITS.iter().find(|(_, multiplier)| size >= *multiplier) {
+        Some((suffix, multiplier)) => {
+            format!("{:.1} {}iB", size as f64 / *multiplier as f64, suffix)
+        }
+        None => format!("{} bytes", size),
+    }
+}
+
+/// Total size of the files in a directory.
+///
+/// Files which can't be read, e.g. because they are removed concurrently,
+/// are ignored.
+pub fn directory_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Peak resident memory of this process and of its terminated child processes.
+#[cfg(unix)]
+pub fn peak_memory_usage() -> Option<u64> {
+    let max_rss = |who| {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::getrusage(who, &mut usage) } == 0 {
+            Some(usage.ru_maxrss as u64)
+        } else {
+            None
+        }
+    };
+
+    let max_rss = max_rss(libc::RUSAGE_SELF)?.max(max_rss(libc::RUSAGE_CHILDREN)?);
+
+    // macOS reports bytes, other platforms kilobytes.
+    if cfg!(target_os = "macos") {
+        Some(max_rss)
+    } else {
+        Some(max_rss * 1024)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn peak_memory_usage() -> Option<u64> {
+    None
+}
+
+/// Limits of disk space and memory used by a build.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BuildBudget {
+    /// Maximum size of the build directory, in bytes.
+    pub disk_usage: Option<u64>,
+
+    /// Maximum peak memory usage, in bytes.
+    pub memory: Option<u64>,
+
+    /// Maximum time building a target takes.
+    pub time: Option<Duration>,
+}
+
+impl BuildBudget {
+    /// Whether no limit is defined.
+    pub fn is_empty(&self) -> bool {
+        self.disk_usage.is_none() && self.memory.is_none() && self.time.is_none()
+    }
+
+    /// Obtain a budget whose limits defined by `other` take precedence.
+    pub fn merge(&self, other: &BuildBudget) -> BuildBudget {
+        BuildBudget {
+            disk_usage: other.disk_usage.or(self.disk_usage),
+            memory: other.memory.or(self.memory),
+            time: other.time.or(self.time),
+        }
+    }
+
+    /// Enforce the budget while building `target`.
+    ///
+    /// Until the returned guard is dropped, processes run on this thread
+    /// with `run_within_budget()` are killed when the budget is exceeded.
+    /// Time is counted from this call.
+    pub fn enforce(&self, target: &str, build_path: &Path) -> BudgetGuard {
+        let active = ActiveBudget {
+            budget: self.clone(),
+            target: target.to_string(),
+            build_path: build_path.to_path_buf(),
+            start: Instant::now(),
+        };
+
+        BudgetGuard {
+            previous: ACTIVE_BUDGET.with(|current| current.borrow_mut().replace(active)),
+        }
+    }
+
+    /// Verify a target can be built within budget before building it.
+    ///
+    /// `disk_needed` and `memory_needed` estimate what building the target
+    /// needs, in bytes.
+    pub fn check_estimate(
+        &self,
+        target: &str,
+        build_path: &Path,
+        disk_needed: u64,
+        memory_needed: u64,
+    ) -> Result<()> {
+        if let Some(budget) = self.disk_usage {
+            let used = directory_size(build_path);
+
+            if used.saturating_add(disk_needed) > budget {
+                return Err(anyhow!(
+                    "building {} would exceed the disk budget of {}: the build directory uses {} and building {} needs about {}",
+                    target,
+                    format_size(budget),
+                    format_size(used),
+                    target,
+                    format_size(disk_needed)
+                ));
+            }
+
+            if let Ok(available) = fs2::available_space(build_path) {
+                if disk_needed > available {
+                    return Err(anyhow!(
+                        "building {} needs about {} of disk space but only {} is available in {}",
+                        target,
+                        format_size(disk_needed),
+                        format_size(available),
+                        build_path.display()
+                    ));
+                }
+            }
+        }
+
+        if let Some(budget) = self.memory {
+            if memory_needed > budget {
+                return Err(anyhow!(
+                    "building {} would exceed the memory budget of {}: building it needs about {}",
+                    target,
+                    format_size(budget),
+                    format_size(memory_needed)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify building a target stayed within budget.
+    pub fn check_usage(&self, target: &str, build_path: &Path) -> Result<()> {
+        if let Some(budget) = self.disk_usage {
+            let used = directory_size(build_path);
+
+            if used > budget {
+                return Err(anyhow!(
+                    "building {} exceeded the disk budget of {}: the build directory uses {}",
+                    target,
+                    format_size(budget),
+                    format_size(used)
+                ));
+            }
+        }
+
+        if let (Some(budget), Some(used)) = (self.memory, peak_memory_usage()) {
+            if used > budget {
+                return Err(anyhow!(
+                    "building {} exceeded the memory budget of {}: peak memory usage was {}",
+                    target,
+                    format_size(budget),
+                    format_size(used)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A budget enforced while a target builds.
+struct ActiveBudget {
+    budget: BuildBudget,
+    target: String,
+    build_path: PathBuf,
+    start: Instant,
+}
+
+impl ActiveBudget {
+    fn check(&self) -> Result<()> {
+        self.budget.check_usage(&self.target, &self.build_path)?;
+
+        if let Some(budget) = self.budget.time {
+            let elapsed = self.start.elapsed();
+
+            if elapsed > budget {
+                return Err(anyhow!(
+                    "building {} exceeded the time budget of {}s: it has been building for {}s",
+                    self.target,
+                    budget.as_secs(),
+                    elapsed.as_secs()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// The budget enforced on processes run on this thread, if any.
+    ///
+    /// Thread local so concurrent builds, e.g. in tests, don't share budgets.
+    static ACTIVE_BUDGET: RefCell<Option<ActiveBudget>> = RefCell::new(None);
+}
+
+/// Enforces a budget until dropped. See `BuildBudget::enforce()`.
+pub struct BudgetGuard {
+    /// Budget enforced before, e.g. while building a dependent target.
+    previous: Option<ActiveBudget>,
+}
+
+impl BudgetGuard {
+    /// Verify the budget wasn't exceeded so far.
+    pub fn check(&self) -> Result<()> {
+        check_active_budget()
+    }
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        ACTIVE_BUDGET.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Verify the budget enforced on this thread, if any, wasn't exceeded.
+fn check_active_budget() -> Result<()> {
+    ACTIVE_BUDGET.with(|current| match current.borrow().as_ref() {
+        Some(active) => active.check(),
+        None => Ok(()),
+    })
+}
+
+/// Run a command to completion, killing it if the enforced budget is exceeded.
+///
+/// Processes spawned by the command are not killed, but have their parent
+/// killed, e.g. `rustc` processes of a killed `cargo` exit once done.
+pub fn run_within_budget(command: &mut Command) -> Result<ExitStatus> {
+    let mut child = command.spawn()?;
+    let mut last_check = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if last_check.elapsed() >= POLL_INTERVAL {
+            if let Err(e) = check_active_budget() {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                return Err(e);
+            }
+
+            last_check = Instant::now();
+        }
+
+        std::thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() -> Result<()> {
+        assert_eq!(parse_size("1024")?, 1024);
+        assert_eq!(parse_size("512M")?, 512 << 20);
+        assert_eq!(parse_size("512mb")?, 512 << 20);
+        assert_eq!(parse_size("1.5G")?, 3 << 29);
+        assert_eq!(parse_size("2 GiB")?, 2 << 30);
+        assert_eq!(parse_size("1T")?, 1 << 40);
+        assert!(parse_size("").is_err());
+        assert!(parse_size("-1G").is_err());
+        assert!(parse_size("many").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(42), "42 bytes");
+        assert_eq!(format_size(3 << 29), "1.5 GiB");
+        assert_eq!(format_size(512 << 20), "512.0 MiB");
+    }
+
+    #[test]
+    fn test_check_budget() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::create_dir(temp_dir.path().join("exe"))?;
+        std::fs::write(temp_dir.path().join("exe").join("myapp"), vec![0; 1000])?;
+
+        assert_eq!(directory_size(temp_dir.path()), 1000);
+
+        let budget = BuildBudget::default();
+        assert!(budget.is_empty());
+        budget.check_estimate("exe", temp_dir.path(), u64::MAX / 2, u64::MAX / 2)?;
+        budget.check_usage("exe", temp_dir.path())?;
+
+        let budget = BuildBudget {
+            disk_usage: Some(1500),
+            memory: Some(1 << 40),
+            time: None,
+        };
+        budget.check_estimate("exe", temp_dir.path(), 500, 1 << 30)?;
+        budget.check_usage("exe", temp_dir.path())?;
+
+        let err = budget
+            .check_estimate("exe", temp_dir.path(), 501, 0)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "building exe would exceed the disk budget of 1.5 KiB: the build directory uses 1000 bytes and building exe needs about 501 bytes"
+        );
+
+        let err = budget
+            .check_estimate("exe", temp_dir.path(), 0, 2 << 40)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "building exe would exceed the memory budget of 1.0 TiB: building it needs about 2.0 TiB"
+        );
+
+        std::fs::write(temp_dir.path().join("exe").join("lib"), vec![0; 1000])?;
+        let err = budget.check_usage("exe", temp_dir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "building exe exceeded the disk budget of 1.5 KiB: the build directory uses 2.0 KiB"
+        );
+
+        let budget = BuildBudget {
+            disk_usage: None,
+            memory: Some(1),
+            time: None,
+        };
+        if peak_memory_usage().is_some() {
+            assert!(budget.check_usage("exe", temp_dir.path()).is_err());
+        }
+
+        assert_eq!(
+            BuildBudget {
+                disk_usage: Some(1),
+                memory: Some(2),
+                time: None,
+            }
+            .merge(&BuildBudget {
+                disk_usage: Some(3),
+                memory: None,
+                time: Some(Duration::from_secs(4)),
+            }),
+            BuildBudget {
+                disk_usage: Some(3),
+                memory: Some(2),
+                time: Some(Duration::from_secs(4)),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_within_budget() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        // Without an enforced budget, processes run to completion.
+        assert!(run_within_budget(Command::new("sh").args(&["-c", "exit 0"]))?.success());
+
+        let budget = BuildBudget {
+            time: Some(Duration::from_secs(1)),
+            ..BuildBudget::default()
+        };
+
+        {
+            let guard = budget.enforce("exe", temp_dir.path());
+            guard.check()?;
+
+            let start = Instant::now();
+            let err = run_within_budget(Command::new("sleep").arg("60")).unwrap_err();
+            assert!(start.elapsed() < Duration::from_secs(30));
+            assert!(err
+                .to_string()
+                .starts_with("building exe exceeded the time budget of 1s"));
+            assert!(guard.check().is_err());
+        }
+
+        // The budget is no longer enforced once the guard is dropped.
+        check_active_budget()?;
+
+        let budget = BuildBudget {
+            disk_usage: Some(1000),
+            ..BuildBudget::default()
+        };
+        let _guard = budget.enforce("exe", temp_dir.path());
+        let path = temp_dir.path().join("big");
+        let err = run_within_budget(Command::new("sh").args(&[
+            "-c",
+            &format!("head -c 2000 /dev/zero > {}; sleep 60", path.display()),
+        ]))
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("building exe exceeded the disk budget of 1000 bytes"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Describe the content of a build.
+
+A `BuildManifest` records what went into a built binary (the Python
+version and the Python package distributions it embeds) and what came out
+of it (artifact files with their sizes and SHA-256 digests).
+
+The manifest can be serialized to JSON or rendered as a Markdown or HTML
+fragment summarizing the build, suitable for pasting into release notes.
+
+For audits, the manifest can also carry the entries of the `RECORD` files
+of embedded package distributions, preserving the hashes of every file as
+originally installed. These entries can be written out as a single
+consolidated `RECORD` file.
+*/
+
+use {
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::{anyhow, Result},
+    python_packaging::package_metadata::{PythonPackageRecord, PythonPackageRecordEntry},
+    serde::Serialize,
+    sha2::{Digest, Sha256},
+    std::collections::BTreeMap,
+    std::convert::TryFrom,
+    std::path::Path,
+};
+
+/// Formats a build summary can be rendered in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SummaryFormat {
+    Markdown,
+    Html,
+}
+
+impl SummaryFormat {
+    /// Filename a summary in this format is written to.
+    pub fn filename(self) -> &'static str {
+        match self {
+            SummaryFormat::Markdown => "build-summary.md",
+            SummaryFormat::Html => "build-summary.html",
+        }
+    }
+}
+
+impl TryFrom<&str> for SummaryFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "markdown" => Ok(SummaryFormat::Markdown),
+            "html" => Ok(SummaryFormat::Html),
+            _ => Err(format!(
+                "{} is not a valid summary format; use \"markdown\" or \"html\"",
+                value
+            )),
+        }
+    }
+}
+
+/// A Python package distribution embedded in a build.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PackageEntry {
+    pub name: String,
+    pub version: String,
+}
+
+/// A file produced by a build.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArtifactEntry {
+    /// Path of the file relative to the build output directory.
+    pub path: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Hex encoded SHA-256 digest of the file content.
+    pub sha256: String,
+}
+
+/// An entry of a package distribution's `RECORD` file.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecordEntry {
+    /// Name of the package distribution the entry belongs to.
+    pub package: String,
+    /// Path of the installed file, relative to the installation root.
+    pub path: String,
+    /// Hash of the file content as recorded at install time.
+    pub hash: Option<String>,
+    /// Size of the file in bytes as recorded at install time.
+    pub size: Option<u64>,
+}
+
+/// Describes the inputs and outputs of a build.
+#[derive(Clone, Debug, Serialize)]
+pub struct BuildManifest {
+    pub name: String,
+    pub pyoxidizer_version: String,
+    pub target_triple: String,
+    pub python_version: String,
+    pub packages: Vec<PackageEntry>,
+    pub artifacts: Vec<ArtifactEntry>,
+    /// Entries of `RECORD` files of embedded package distributions, if collected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub records: Option<Vec<RecordEntry>>,
+}
+
+impl BuildManifest {
+    /// Construct an instance describing the inputs of a binary builder.
+    pub fn from_binary_builder(exe: &dyn PythonBinaryBuilder, target_triple: &str) -> Self {
+        Self {
+            name: exe.name(),
+            pyoxidizer_version: crate::environment::PYOXIDIZER_VERSION.to_string(),
+            target_triple: target_triple.to_string(),
+            python_version: exe.python_version(),
+            packages: exe
+                .package_distributions()
+                .into_iter()
+                .map(|(name, version)| PackageEntry { name, version })
+                .collect(),
+            artifacts: Vec::new(),
+            records: None,
+        }
+    }
+
+    /// Record entries of `RECORD` files of package distributions.
+    pub fn add_package_records(&mut self, records: &BTreeMap<String, PythonPackageRecord>) {
+        let entries = self.records.get_or_insert_with(Vec::new);
+
+        for (package, record) in records {
+            for entry in &record.entries {
+                entries.push(RecordEntry {
+                    package: package.clone(),
+                    path: entry.path.clone(),
+                    hash: entry.hash.clone(),
+                    size: entry.size,
+                });
+            }
+        }
+    }
+
+    /// Render collected `RECORD` entries as a single consolidated `RECORD` file.
+    pub fn to_record(&self) -> Vec<u8> {
+        PythonPackageRecord {
+            entries: self
+                .records
+                .iter()
+                .flatten()
+                .map(|entry| PythonPackageRecordEntry {
+                    path: entry.path.clone(),
+                    hash: entry.hash.clone(),
+                    size: entry.size,
+                })
+                .collect(),
+        }
+        .to_record()
+    }
+
+    /// Record a produced file given its content.
+    pub fn add_artifact(&mut self, path: &str, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.input(data);
+
+        self.artifacts.push(ArtifactEntry {
+            path: path.to_string(),
+            size: data.len() as u64,
+            sha256: hex::encode(hasher.result()),
+        });
+    }
+
+    /// Record all files in a directory as produced artifacts.
+    ///
+    /// Paths in `exclude` (relative to `root`) are ignored.
+    pub fn add_artifacts_from_directory(&mut self, root: &Path, exclude: &[&str]) -> Result<()> {
+        let mut entries = Vec::new();
+
+        for entry in walkdir::WalkDir::new(root) {
+            let entry = entry?;
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let rel_path = entry
+                .path()
+                .strip_prefix(root)
+                .or_else(|_| Err(anyhow!("unable to strip {} prefix", root.display())))?
+                .display()
+                .to_string()
+                .replace('\\', "/");
+
+            if exclude.contains(&rel_path.as_str()) {
+                continue;
+            }
+
+            entries.push((rel_path, entry.path().to_path_buf()));
+        }
+
+        entries.sort();
+
+        for (rel_path, path) in entries {
+            self.add_artifact(&rel_path, &std::fs::read(&path)?);
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the manifest to a JSON string.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Render a summary of the build in the requested format.
+    pub fn render_summary(&self, format: SummaryFormat) -> String {
+        match format {
+            SummaryFormat::Markdown => self.to_markdown(),
+            SummaryFormat::Html => self.to_html(),
+        }
+    }
+
+    /// Render a Markdown fragment summarizing the build.
+    pub fn to_markdown(&self) -> String {
+        let mut lines = vec![
+            format!("### {}", self.name),
+            "".to_string(),
+            format!("* Python version: {}", self.python_version),
+            format!("* Target: `{}`", self.target_triple),
+            format!("* PyOxidizer version: {}", self.pyoxidizer_version),
+            "".to_string(),
+            "#### Python Packages".to_string(),
+            "".to_string(),
+        ];
+
+        if self.packages.is_empty() {
+            lines.push("No Python packages beyond the standard library.".to_string());
+        } else {
+            lines.push("| Package | Version |".to_string());
+            lines.push("| --- | --- |".to_string());
+            for package in &self.packages {
+                lines.push(format!("| {} | {} |", package.name, package.version));
+            }
+        }
+
+        lines.push("".to_string());
+        lines.push("#### Artifacts".to_string());
+        lines.push("".to_string());
+        lines.push("| File | Size | SHA-256 |".to_string());
+        lines.push("| --- | ---: | --- |".to_string());
+        for artifact in &self.artifacts {
+            lines.push(format!(
+                "| `{}` | {} | `{}` |",
+                artifact.path, artifact.size, artifact.sha256
+            ));
+        }
+        lines.push("".to_string());
+
+        lines.join("\n")
+    }
+
+    /// Render an HTML fragment summarizing the build.
+    pub fn to_html(&self) -> String {
+        let mut lines = vec![
+            format!("<h3>{}</h3>", html_escape(&self.name)),
+            "<ul>".to_string(),
+            format!(
+                "<li>Python version: {}</li>",
+                html_escape(&self.python_version)
+            ),
+            format!(
+                "<li>Target: <code>{}</code></li>",
+                html_escape(&self.target_triple)
+            ),
+            format!(
+                "<li>PyOxidizer version: {}</li>",
+                html_escape(&self.pyoxidizer_version)
+            ),
+            "</ul>".to_string(),
+            "<h4>Python Packages</h4>".to_string(),
+        ];
+
+        if self.packages.is_empty() {
+            lines.push("<p>No Python packages beyond the standard library.</p>".to_string());
+        } else {
+            lines.push("<table>".to_string());
+            lines.push("<tr><th>Package</th><th>Version</th></tr>".to_string());
+            for package in &self.packages {
+                lines.push(format!(
+                    "<tr><td>{}</td><td>{}</td></tr>",
+                    html_escape(&package.name),
+                    html_escape(&package.version)
+                ));
+            }
+            lines.push("</table>".to_string());
+        }
+
+        lines.push("<h4>Artifacts</h4>".to_string());
+        lines.push("<table>".to_string());
+        lines.push("<tr><th>File</th><th>Size</th><th>SHA-256</th></tr>".to_string());
+        for artifact in &self.artifacts {
+            lines.push(format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td><code>{}</code></td></tr>",
+                html_escape(&artifact.path),
+                artifact.size,
+                artifact.sha256
+            ));
+        }
+        lines.push("</table>".to_string());
+        lines.push("".to_string());
+
+        lines.join("\n")
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> BuildManifest {
+        let mut manifest = BuildManifest {
+            name: "myapp".to_string(),
+            pyoxidizer_version: "0.8.0".to_string(),
+            target_triple: "x86_64-unknown-linux-gnu".to_string(),
+            python_version: "3.7.7".to_string(),
+            packages: vec![PackageEntry {
+                name: "<pkg>".to_string(),
+                version: "1.0".to_string(),
+            }],
+            artifacts: vec![],
+            records: None,
+        };
+        manifest.add_artifact("myapp", b"foo");
+
+        manifest
+    }
+
+    #[test]
+    fn test_summary_format() {
+        assert_eq!(
+            SummaryFormat::try_from("markdown"),
+            Ok(SummaryFormat::Markdown)
+        );
+        assert_eq!(SummaryFormat::try_from("html"), Ok(SummaryFormat::Html));
+        assert!(SummaryFormat::try_from("pdf").is_err());
+    }
+
+    #[test]
+    fn test_add_artifact() {
+        let manifest = manifest();
+
+        assert_eq!(
+            manifest.artifacts,
+            vec![ArtifactEntry {
+                path: "myapp".to_string(),
+                size: 3,
+                sha256: "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae"
+                    .to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let md = manifest().to_markdown();
+
+        assert!(md.starts_with("### myapp\n"));
+        assert!(md.contains("* Python version: 3.7.7\n"));
+        assert!(md.contains("| <pkg> | 1.0 |\n"));
+        assert!(md.contains(
+            "| `myapp` | 3 | `2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae` |\n"
+        ));
+    }
+
+    #[test]
+    fn test_to_html() {
+        let html = manifest().to_html();
+
+        assert!(html.starts_with("<h3>myapp</h3>\n"));
+        assert!(html.contains("<tr><td>&lt;pkg&gt;</td><td>1.0</td></tr>\n"));
+        assert!(html.contains("<td><code>myapp</code></td><td>3</td>"));
+    }
+
+    #[test]
+    fn test_package_records() -> Result<()> {
+        let mut manifest = manifest();
+        assert_eq!(manifest.to_record(), b"");
+        assert!(!manifest.to_json()?.contains("records"));
+
+        let mut records = BTreeMap::new();
+        records.insert(
+            "bar".to_string(),
+            PythonPackageRecord::from_record(b"bar.py,sha256=abc,3\nbar-1.0.dist-info/RECORD,,\n")?,
+        );
+        records.insert(
+            "foo".to_string(),
+            PythonPackageRecord::from_record(b"foo/__init__.py,sha256=def,0\n")?,
+        );
+        manifest.add_package_records(&records);
+
+        assert_eq!(
+            manifest.records.as_ref().unwrap()[2],
+            RecordEntry {
+                package: "foo".to_string(),
+                path: "foo/__init__.py".to_string(),
+                hash: Some("sha256=def".to_string()),
+                size: Some(0),
+            }
+        );
+        assert_eq!(
+            manifest.to_record(),
+            b"bar.py,sha256=abc,3\nbar-1.0.dist-info/RECORD,,\nfoo/__init__.py,sha256=def,0\n"
+                .to_vec()
+        );
+        assert!(manifest.to_json()?.contains("\"records\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_artifacts_from_directory() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::create_dir(temp_dir.path().join("lib"))?;
+        std::fs::write(temp_dir.path().join("myapp"), b"foo")?;
+        std::fs::write(temp_dir.path().join("lib").join("bar.py"), b"bar")?;
+        std::fs::write(temp_dir.path().join("build-summary.md"), b"ignored")?;
+
+        let mut manifest = manifest();
+        manifest.artifacts.clear();
+        manifest.add_artifacts_from_directory(temp_dir.path(), &["build-summary.md"])?;
+
+        assert_eq!(
+            manifest
+                .artifacts
+                .iter()
+                .map(|a| a.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["lib/bar.py", "myapp"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Opt-in collection of build metrics.
+
+When enabled, a JSON document describing a build (which targets were built,
+how long they took, how large their outputs are, how effective the
+distribution cache was, etc) is written to a local file or piped into a
+local command.
+
+Metrics are only sent over the network when OTLP export is configured
+(see the `otlp` module). Collection is disabled unless the
+`PYOXIDIZER_METRICS_PATH` or `PYOXIDIZER_METRICS_COMMAND` environment
+variable is set, or OTLP export is configured.
+*/
+
+use {
+    crate::atomic_output::write_file_atomic,
+    anyhow::{anyhow, Context, Result},
+    python_packaging::bytecode::bytecode_cache_lookups,
+    serde::Serialize,
+    slog::warn,
+    std::io::Write,
+    std::path::{Path, PathBuf},
+    std::sync::atomic::{AtomicUsize, Ordering},
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Environment variable defining a path to write metrics JSON to.
+pub const METRICS_PATH_ENV: &str = "PYOXIDIZER_METRICS_PATH";
+
+/// Environment variable defining a program to pipe metrics JSON into.
+pub const METRICS_COMMAND_ENV: &str = "PYOXIDIZER_METRICS_COMMAND";
+
+/// Version of the metrics document format.
+pub const METRICS_FORMAT_VERSION: u32 = 1;
+
+static DISTRIBUTION_CACHE_HITS: AtomicUsize = AtomicUsize::new(0);
+static DISTRIBUTION_CACHE_MISSES: AtomicUsize = AtomicUsize::new(0);
+
+/// Record the outcome of looking up a Python distribution in the local cache.
+pub fn record_distribution_cache_lookup(hit: bool) {
+    if hit {
+        DISTRIBUTION_CACHE_HITS.fetch_add(1, Ordering::SeqCst);
+    } else {
+        DISTRIBUTION_CACHE_MISSES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Where build metrics should be emitted to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricsDestination {
+    /// Write metrics to a file.
+    ///
+    /// If the path is an existing directory, a uniquely named file will be
+    /// created within it.
+    Path(PathBuf),
+
+    /// Execute a program and write metrics to its stdin.
+    Command(String),
+}
+
+impl MetricsDestination {
+    /// Resolve the metrics destination from the environment.
+    ///
+    /// Returns `None` if metrics collection is not enabled.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(path) = std::env::var(METRICS_PATH_ENV) {
+            if !path.is_empty() {
+                return Some(MetricsDestination::Path(PathBuf::from(path)));
+            }
+        }
+
+        if let Ok(command) = std::env::var(METRICS_COMMAND_ENV) {
+            if !command.is_empty() {
+                return Some(MetricsDestination::Command(command));
+            }
+        }
+
+        None
+    }
+}
+
+/// Describes a single built target.
+#[derive(Clone, Debug, Serialize)]
+pub struct TargetMetrics {
+    /// Name of the target.
+    pub name: String,
+    /// Wall time spent building the target, in milliseconds.
+    pub duration_ms: u64,
+    /// Total size in bytes of files in the target's output directory.
+    pub output_size: u64,
+    /// Number of files in the target's output directory.
+    pub output_files: u64,
+}
+
+/// Describes usage of a cache.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CacheMetrics {
+    pub hits: usize,
+    pub misses: usize,
+    /// Fraction of lookups served from the cache, or `None` if no lookups occurred.
+    pub hit_rate: Option<f64>,
+}
+
+impl CacheMetrics {
+    pub fn new(hits: usize, misses: usize) -> Self {
+        Self {
+            hits,
+            misses,
+            hit_rate: if hits + misses > 0 {
+                Some(hits as f64 / (hits + misses) as f64)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Describes a single invocation of a build.
+#[derive(Clone, Debug, Serialize)]
+pub struct BuildMetrics {
+    pub format_version: u32,
+    pub pyoxidizer_version: String,
+    pub config_path: String,
+    pub target_triple: String,
+    pub release: bool,
+    /// Seconds since the UNIX epoch when the build started.
+    pub start_time: u64,
+    /// Total wall time of the build, in milliseconds.
+    pub duration_ms: u64,
+    pub targets: Vec<TargetMetrics>,
+    pub distribution_cache: CacheMetrics,
+    pub bytecode_cache: CacheMetrics,
+}
+
+impl BuildMetrics {
+    pub fn new(config_path: &Path, target_triple: &str, release: bool) -> Self {
+        Self {
+            format_version: METRICS_FORMAT_VERSION,
+            pyoxidizer_version: crate::environment::PYOXIDIZER_VERSION.to_string(),
+            config_path: config_path.display().to_string(),
+            target_triple: target_triple.to_string(),
+            release,
+            start_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_else(|_| Duration::from_secs(0))
+                .as_secs(),
+            duration_ms: 0,
+            targets: Vec::new(),
+            distribution_cache: CacheMetrics::default(),
+            bytecode_cache: CacheMetrics::default(),
+        }
+    }
+
+    /// Record that a target was built.
+    pub fn add_target(&mut self, name: &str, duration: Duration, output_path: &Path) {
+        let (output_size, output_files) = directory_size(output_path);
+
+        self.targets.push(TargetMetrics {
+            name: name.to_string(),
+            duration_ms: duration.as_millis() as u64,
+            output_size,
+            output_files,
+        });
+    }
+
+    /// Finalize metrics at the end of a build.
+    pub fn finish(&mut self, duration: Duration) {
+        self.duration_ms = duration.as_millis() as u64;
+
+        self.distribution_cache = CacheMetrics::new(
+            DISTRIBUTION_CACHE_HITS.load(Ordering::SeqCst),
+            DISTRIBUTION_CACHE_MISSES.load(Ordering::SeqCst),
+        );
+
+        let (hits, misses) = bytecode_cache_lookups();
+        self.bytecode_cache = CacheMetrics::new(hits, misses);
+    }
+
+    /// Serialize metrics to a JSON string.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Emit metrics to a destination.
+    pub fn emit(&self, logger: &slog::Logger, destination: &MetricsDestination) -> Result<()> {
+        let data = self.to_json()?;
+
+        match destination {
+            MetricsDestination::Path(path) => {
+                let path = if path.is_dir() {
+                    path.join(format!("pyoxidizer-build-{}.json", uuid::Uuid::new_v4()))
+                } else {
+                    path.clone()
+                };
+
+                warn!(logger, "writing build metrics to {}", path.display());
+                write_file_atomic(&path, data.as_bytes(), false)
+                    .with_context(|| format!("writing build metrics to {}", path.display()))?;
+            }
+            MetricsDestination::Command(command) => {
+                warn!(logger, "sending build metrics to {}", command);
+                let mut child = std::process::Command::new(command)
+                    .stdin(std::process::Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("running metrics command {}", command))?;
+
+                child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| anyhow!("unable to obtain stdin of metrics command"))?
+                    .write_all(data.as_bytes())?;
+
+                let status = child.wait()?;
+                if !status.success() {
+                    return Err(anyhow!("metrics command {} failed", command));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute the total size and number of files in a directory tree.
+fn directory_size(path: &Path) -> (u64, u64) {
+    let mut size = 0;
+    let mut count = 0;
+
+    for entry in walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if entry.file_type().is_file() {
+            if let Ok(metadata) = entry.metadata() {
+                size += metadata.len();
+                count += 1;
+            }
+        }
+    }
+
+    (size, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*};
+
+    #[test]
+    fn test_write_metrics_to_directory() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let output_path = temp_dir.path().join("target");
+        std::fs::create_dir(&output_path)?;
+        std::fs::write(output_path.join("file"), b"foo")?;
+
+        let mut metrics = BuildMetrics::new(Path::new("pyoxidizer.bzl"), "x86_64", false);
+        metrics.add_target("exe", Duration::from_millis(42), &output_path);
+        metrics.finish(Duration::from_millis(50));
+
+        assert_eq!(metrics.targets.len(), 1);
+        assert_eq!(metrics.targets[0].output_size, 3);
+        assert_eq!(metrics.targets[0].output_files, 1);
+
+        let metrics_dir = temp_dir.path().join("metrics");
+        std::fs::create_dir(&metrics_dir)?;
+        metrics.emit(&logger, &MetricsDestination::Path(metrics_dir.clone()))?;
+
+        let entries = std::fs::read_dir(&metrics_dir)?.collect::<Vec<_>>();
+        assert_eq!(entries.len(), 1);
+
+        let data = std::fs::read_to_string(entries[0].as_ref().unwrap().path())?;
+        let value: serde_json::Value = serde_json::from_str(&data)?;
+        assert_eq!(value["targets"][0]["name"], "exe");
+        assert_eq!(value["target_triple"], "x86_64");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Detection of targets affected by changes in a Git repository.
+
+Targets declare the files and directories they are built from with the
+`inputs` argument of `register_target()`. Source directories read by
+resolved targets, such as package roots, are inputs as well. A target is
+affected by changes since a Git revision if the configuration file, one of
+its inputs or one of its dependencies changed.
+*/
+
+use {
+    crate::starlark::env::EnvironmentContext,
+    anyhow::{anyhow, Context, Result},
+    git2::{DiffOptions, Repository},
+    path_dedot::ParseDot,
+    std::collections::BTreeMap,
+    std::fmt,
+    std::path::{Path, PathBuf},
+};
+
+/// Why a target is or isn't affected by changes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TargetChange {
+    /// The configuration file changed.
+    ConfigChanged,
+
+    /// An input of the target changed.
+    InputChanged(PathBuf),
+
+    /// A target this one depends on is affected.
+    DependencyChanged(String),
+
+    /// Nothing the target is built from changed.
+    Unchanged,
+}
+
+impl TargetChange {
+    /// Whether the target must be built again.
+    pub fn is_affected(&self) -> bool {
+        *self != TargetChange::Unchanged
+    }
+}
+
+impl fmt::Display for TargetChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetChange::ConfigChanged => write!(f, "configuration file changed"),
+            TargetChange::InputChanged(path) => write!(f, "input {} changed", path.display()),
+            TargetChange::DependencyChanged(target) => {
+                write!(f, "dependency {} is affected", target)
+            }
+            TargetChange::Unchanged => write!(f, "no inputs changed"),
+        }
+    }
+}
+
+/// Normalize a path for comparison with paths in the repository.
+pub fn normalize_path(path: &Path) -> Result<PathBuf> {
+    if path.exists() {
+        Ok(path.canonicalize()?)
+    } else {
+        Ok(path.parse_dot()?.to_path_buf())
+    }
+}
+
+/// Obtain paths of files changed since a revision of the Git repository containing `path`.
+///
+/// Committed, staged and unstaged changes are considered, as well as untracked
+/// files which aren't ignored. Paths are absolute.
+pub fn changed_paths(path: &Path, revision: &str) -> Result<Vec<PathBuf>> {
+    let repo = Repository::discover(path)
+        .context(format!("finding Git repository of {}", path.display()))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("Git repository of {} is bare", path.display()))?
+        .canonicalize()?;
+
+    let tree = repo
+        .revparse_single(revision)
+        .and_then(|object| object.peel_to_tree())
+        .context(format!("resolving Git revision {}", revision))?;
+
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?;
+
+    let mut paths = Vec::new();
+
+    for delta in diff.deltas() {
+        for file in &[delta.old_file(), delta.new_file()] {
+            if let Some(path) = file.path() {
+                let path = workdir.join(path);
+
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn resolve_target_change(
+    context: &EnvironmentContext,
+    target: &str,
+    config_changed: bool,
+    changed: &[PathBuf],
+    changes: &mut BTreeMap<String, TargetChange>,
+) -> Result<TargetChange> {
+    if let Some(change) = changes.get(target) {
+        return Ok(change.clone());
+    }
+
+    let entry = context
+        .targets
+        .get(target)
+        .ok_or_else(|| anyhow!("target {} is not registered", target))?;
+
+    let mut change = if config_changed {
+        TargetChange::ConfigChanged
+    } else {
+        TargetChange::Unchanged
+    };
+
+    if !change.is_affected() {
+        for input in entry.inputs.iter().chain(&entry.source_paths) {
+            let input = normalize_path(input)?;
+
+            if let Some(path) = changed.iter().find(|path| path.starts_with(&input)) {
+                change = TargetChange::InputChanged(path.clone());
+                break;
+            }
+        }
+    }
+
+    if !change.is_affected() {
+        for depend in &entry.depends {
+            if resolve_target_change(context, depend, config_changed, changed, changes)?
+                .is_affected()
+            {
+                change = TargetChange::DependencyChanged(depend.clone());
+                break;
+            }
+        }
+    }
+
+    changes.insert(target.to_string(), change.clone());
+
+    Ok(change)
+}
+
+/// Determine how targets are affected by changed paths.
+///
+/// `changed` holds absolute paths, as returned by `changed_paths()`.
+pub fn target_changes(
+    context: &EnvironmentContext,
+    targets: &[String],
+    changed: &[PathBuf],
+) -> Result<Vec<(String, TargetChange)>> {
+    // Dependency cycles would otherwise recurse endlessly.
+    context.targets_with_dependencies(targets)?;
+
+    let config_path = normalize_path(&context.config_path)?;
+    let config_changed = changed.contains(&config_path);
+
+    let mut changes = BTreeMap::new();
+
+    targets
+        .iter()
+        .map(|target| {
+            Ok((
+                target.clone(),
+                resolve_target_change(context, target, config_changed, changed, &mut changes)?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*, starlark::values::Value, std::collections::HashMap};
+
+    fn commit_all(repo: &Repository) -> Result<()> {
+        let mut index = repo.index()?;
+        index.add_all(&["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit()?],
+            Err(_) => vec![],
+        };
+
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "commit",
+            &tree,
+            &parents.iter().collect::<Vec<_>>(),
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_changes() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path().canonicalize()?;
+
+        let repo = Repository::init(&root)?;
+        std::fs::create_dir_all(root.join("app/src"))?;
+        std::fs::create_dir_all(root.join("tool"))?;
+        std::fs::write(root.join("pyoxidizer.bzl"), "")?;
+        std::fs::write(root.join("app/src/main.py"), "")?;
+        std::fs::write(root.join("tool/tool.py"), "")?;
+        commit_all(&repo)?;
+
+        let mut context = EnvironmentContext::new(
+            &logger,
+            false,
+            &root.join("pyoxidizer.bzl"),
+            crate::project_building::HOST,
+            crate::project_building::HOST,
+            false,
+            "0",
+            None,
+            false,
+            false,
+            false,
+            false,
+        )?;
+        for (target, depends, inputs) in &[
+            ("dist", vec![], vec![]),
+            ("app", vec!["dist".to_string()], vec![root.join("app")]),
+            ("tool", vec!["dist".to_string()], vec![root.join("tool")]),
+            ("install", vec!["app".to_string()], vec![]),
+        ] {
+            context.register_target(
+                target.to_string(),
+                Value::new(None),
+                depends.clone(),
+                HashMap::new(),
+                inputs.clone(),
+                Vec::new(),
+                false,
+                false,
+            );
+        }
+        let targets = context.targets_order.clone();
+
+        assert!(changed_paths(&root, "HEAD")?.is_empty());
+
+        std::fs::write(root.join("app/src/main.py"), "print('hello')")?;
+        std::fs::write(root.join("app/src/new.py"), "")?;
+
+        let changed = changed_paths(&root, "HEAD")?;
+        assert_eq!(
+            changed,
+            vec![root.join("app/src/main.py"), root.join("app/src/new.py")]
+        );
+
+        assert_eq!(
+            target_changes(&context, &targets, &changed)?,
+            vec![
+                ("dist".to_string(), TargetChange::Unchanged),
+                (
+                    "app".to_string(),
+                    TargetChange::InputChanged(root.join("app/src/main.py"))
+                ),
+                ("tool".to_string(), TargetChange::Unchanged),
+                (
+                    "install".to_string(),
+                    TargetChange::DependencyChanged("app".to_string())
+                ),
+            ]
+        );
+
+        commit_all(&repo)?;
+        std::fs::write(root.join("pyoxidizer.bzl"), "# changed")?;
+
+        let changed = changed_paths(&root, "HEAD")?;
+        assert!(target_changes(&context, &targets, &changed)?
+            .iter()
+            .all(|(_, change)| *change == TargetChange::ConfigChanged));
+
+        assert!(changed_paths(&root, "HEAD~1")?.contains(&root.join("app/src/new.py")));
+        assert!(changed_paths(&root, "unknown").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Checksums and signatures of build artifacts.
+
+Once all targets of a build are built, a `SHA256SUMS` file listing the
+SHA-256 of every file in their output directories is written in the
+directory of the build profile, in the format of `sha256sum`, so releases
+can be verified with `sha256sum -c SHA256SUMS`.
+
+When a signing tool is defined by `set_artifact_signing()` in the
+configuration file, the checksums file is signed with it, which covers all
+artifacts with a single signature.
+
+The checksums file is written atomically. When a build fails, artifacts may
+no longer match checksums of a previous build, so the checksums file and its
+signatures are removed.
+*/
+
+use {
+    crate::atomic_output::write_file_atomic,
+    anyhow::{anyhow, Context, Result},
+    sha2::{Digest, Sha256},
+    slog::warn,
+    std::collections::BTreeMap,
+    std::convert::TryFrom,
+    std::io::Read,
+    std::path::{Path, PathBuf},
+};
+
+/// Name of the file listing checksums of artifacts.
+pub const CHECKSUMS_FILENAME: &str = "SHA256SUMS";
+
+/// A tool signing the checksums file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SigningTool {
+    /// `minisign`, writing `SHA256SUMS.minisig`.
+    Minisign,
+    /// `gpg`, writing an ASCII armored `SHA256SUMS.asc`.
+    Gpg,
+}
+
+impl TryFrom<&str> for SigningTool {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "minisign" => Ok(SigningTool::Minisign),
+            "gpg" => Ok(SigningTool::Gpg),
+            _ => Err(format!(
+                "signing tool must be minisign or gpg: got {}",
+                value
+            )),
+        }
+    }
+}
+
+impl SigningTool {
+    /// Name of the program of the tool.
+    pub fn program(self) -> &'static str {
+        match self {
+            SigningTool::Minisign => "minisign",
+            SigningTool::Gpg => "gpg",
+        }
+    }
+
+    /// Name of the signature of a file.
+    pub fn signature_filename(self, filename: &str) -> String {
+        match self {
+            SigningTool::Minisign => format!("{}.minisig", filename),
+            SigningTool::Gpg => format!("{}.asc", filename),
+        }
+    }
+}
+
+/// How the checksums file is signed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArtifactSigning {
+    pub tool: SigningTool,
+
+    /// Key signing the file.
+    ///
+    /// The path of a secret key for `minisign`, a key ID for `gpg`. If
+    /// `None`, the default key of the tool is used.
+    pub key: Option<String>,
+}
+
+impl ArtifactSigning {
+    /// Sign a file, writing a detached signature next to it.
+    ///
+    /// Returns the path of the signature.
+    pub fn sign(&self, logger: &slog::Logger, path: &Path) -> Result<PathBuf> {
+        let filename = path
+            .file_name()
+            .ok_or_else(|| anyhow!("{} has no filename", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let signature_path = path.with_file_name(self.tool.signature_filename(&filename));
+
+        // Don't leave the signature of previous checksums behind on failure.
+        if signature_path.exists() {
+            std::fs::remove_file(&signature_path)
+                .with_context(|| format!("removing {}", signature_path.display()))?;
+        }
+
+        let mut command = match self.tool {
+            SigningTool::Minisign => {
+                let mut command = std::process::Command::new(self.tool.program());
+                command.arg("-S").arg("-m").arg(path);
+                command.arg("-x").arg(&signature_path);
+                if let Some(key) = &self.key {
+                    command.arg("-s").arg(key);
+                }
+                command
+            }
+            SigningTool::Gpg => {
+                let mut command = std::process::Command::new(self.tool.program());
+                command.args(&["--batch", "--yes", "--armor", "--detach-sign"]);
+                if let Some(key) = &self.key {
+                    command.arg("--local-user").arg(key);
+                }
+                command.arg("--output").arg(&signature_path).arg(path);
+                command
+            }
+        };
+
+        warn!(
+            logger,
+            "signing {} with {}",
+            path.display(),
+            self.tool.program()
+        );
+
+        let status = command
+            .status()
+            .with_context(|| format!("running {}", self.tool.program()))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "error signing {}: {} exited with {}",
+                path.display(),
+                self.tool.program(),
+                status
+            ));
+        }
+
+        Ok(signature_path)
+    }
+}
+
+/// Compute the SHA-256 of a file.
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut fh =
+        std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 65536];
+
+    loop {
+        let count = fh.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+
+        hasher.input(&buffer[0..count]);
+    }
+
+    Ok(hex::encode(hasher.result()))
+}
+
+/// Compute checksums of all files in output directories.
+///
+/// Files are keyed by their path relative to `root`, with `/` separators.
+/// Paths of output directories outside of `root` are kept as is.
+pub fn artifact_checksums(
+    root: &Path,
+    output_paths: &[PathBuf],
+) -> Result<BTreeMap<String, String>> {
+    let mut checksums = BTreeMap::new();
+
+    for output_path in output_paths {
+        for entry in walkdir::WalkDir::new(output_path) {
+            let entry = entry?;
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let rel_path = entry
+                .path()
+                .strip_prefix(root)
+                .unwrap_or_else(|_| entry.path())
+                .display()
+                .to_string()
+                .replace('\\', "/");
+
+            checksums.insert(rel_path, file_sha256(entry.path())?);
+        }
+    }
+
+    Ok(checksums)
+}
+
+/// Render checksums in the format of `sha256sum`.
+pub fn render_checksums(checksums: &BTreeMap<String, String>) -> String {
+    checksums
+        .iter()
+        .map(|(path, sha256)| format!("{}  {}\n", sha256, path))
+        .collect()
+}
+
+/// Write the checksums file of the outputs of a build, signing it if requested.
+///
+/// Returns the path of the checksums file.
+pub fn write_checksums(
+    logger: &slog::Logger,
+    root: &Path,
+    output_paths: &[PathBuf],
+    signing: Option<&ArtifactSigning>,
+) -> Result<PathBuf> {
+    let checksums = artifact_checksums(root, output_paths)?;
+    let path = root.join(CHECKSUMS_FILENAME);
+
+    warn!(
+        logger,
+        "writing checksums of {} artifacts to {}",
+        checksums.len(),
+        path.display()
+    );
+    write_file_atomic(&path, render_checksums(&checksums).as_bytes(), false)?;
+
+    if let Some(signing) = signing {
+        signing.sign(logger, &path)?;
+    }
+
+    Ok(path)
+}
+
+/// Remove the checksums file of a previous build and its signatures, if any.
+pub fn remove_checksums(logger: &slog::Logger, root: &Path) -> Result<()> {
+    let mut filenames = vec![CHECKSUMS_FILENAME.to_string()];
+    for tool in &[SigningTool::Minisign, SigningTool::Gpg] {
+        filenames.push(tool.signature_filename(CHECKSUMS_FILENAME));
+    }
+
+    for filename in filenames {
+        let path = root.join(filename);
+
+        if path.exists() {
+            warn!(logger, "removing stale {}", path.display());
+            std::fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*};
+
+    #[test]
+    fn test_signing_tool() {
+        assert_eq!(SigningTool::try_from("gpg"), Ok(SigningTool::Gpg));
+        assert_eq!(
+            SigningTool::Minisign.signature_filename(CHECKSUMS_FILENAME),
+            "SHA256SUMS.minisig"
+        );
+        assert_eq!(
+            SigningTool::try_from("signify"),
+            Err("signing tool must be minisign or gpg: got signify".to_string())
+        );
+    }
+
+    #[test]
+    fn test_write_checksums() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+
+        std::fs::create_dir_all(root.join("exe").join("lib"))?;
+        std::fs::write(root.join("exe").join("app"), "app")?;
+        std::fs::write(root.join("exe").join("lib").join("data"), "")?;
+        std::fs::create_dir_all(root.join("ignored"))?;
+        std::fs::write(root.join("ignored").join("file"), "ignored")?;
+
+        let path = write_checksums(&logger, root, &[root.join("exe")], None)?;
+        assert_eq!(path, root.join(CHECKSUMS_FILENAME));
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            concat!(
+                "a172cedcae47474b615c54d510a5d84a8dea3032e958587430b413538be3f333  exe/app\n",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  exe/lib/data\n",
+            )
+        );
+
+        // Checksums are replaced without leaving temporary files behind.
+        std::fs::write(root.join("exe").join("app"), "app2")?;
+        write_checksums(&logger, root, &[root.join("exe")], None)?;
+        assert!(std::fs::read_to_string(&path)?.contains("  exe/app\n"));
+        assert_eq!(
+            std::fs::read_dir(root)?
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|name| !["exe", "ignored", CHECKSUMS_FILENAME].contains(&name.as_str()))
+                .collect::<Vec<_>>(),
+            Vec::<String>::new()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_checksums() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+
+        for filename in &["SHA256SUMS", "SHA256SUMS.minisig", "SHA256SUMS.asc", "app"] {
+            std::fs::write(root.join(filename), "")?;
+        }
+
+        remove_checksums(&logger, root)?;
+        assert!(!root.join("SHA256SUMS").exists());
+        assert!(!root.join("SHA256SUMS.minisig").exists());
+        assert!(!root.join("SHA256SUMS.asc").exists());
+        assert!(root.join("app").exists());
+
+        // Nothing to remove isn't an error.
+        remove_checksums(&logger, root)?;
+
+        Ok(())
+    }
+}
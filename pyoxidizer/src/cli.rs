@@ -4,6 +4,8 @@
 
 use {
     super::analyze,
+    super::benchmark::BenchmarkOptions,
+    super::build_budget::{parse_size, BuildBudget},
     super::environment::BUILD_SEMVER_LIGHTWEIGHT,
     super::logging,
     super::project_building,
@@ -12,6 +14,7 @@ use {
     anyhow::{anyhow, Result},
     clap::{App, AppSettings, Arg, SubCommand},
     std::path::{Path, PathBuf},
+    std::time::Duration,
 };
 
 const ADD_ABOUT: &str = "\
@@ -32,6 +35,27 @@ distribution URLs and dependency crate versions and locations from the
 PyOxidizer executable that runs this command.
 ";
 
+const BENCH_ABOUT: &str = "\
+Compare startup of a built binary against a reference Python interpreter.
+
+The target is built and then executed repeatedly, as is the reference
+interpreter. The first execution of each approximates a cold start and
+subsequent executions measure warm starts. Output of executed processes
+is discarded.
+
+Arguments after `--` are passed to both the built binary and the reference
+interpreter. Use --reference-arg to make the reference interpreter run the
+same entry point as the binary (e.g. `--reference-arg -m --reference-arg
+myapp`).
+
+--import measures the time to import a module, relative to running
+`-c pass`. This requires the binary to act as a `python` executable, so
+the name of its `python_executable_env` environment variable must be given
+via --python-executable-env.
+
+A table comparing the measurements is printed.
+";
+
 const BUILD_ABOUT: &str = "\
 Build a PyOxidizer project.
 
@@ -40,6 +64,140 @@ existing PyOxidizer enabled project.
 
 This command will invoke Rust's build system tool (Cargo) to build
 the project.
+
+With --changed-since, only targets affected by changes in the Git
+repository since the given revision are built. A target is affected if
+the configuration file, one of the inputs declared by its `inputs`
+argument or one of its dependencies changed. Uncommitted and untracked
+files count as changes.
+
+With --tag, targets having the given tag are built instead of the
+default target. When --tag is repeated, targets must have every given
+tag.
+
+With --otlp-endpoint, or when the OTEL_EXPORTER_OTLP_ENDPOINT environment
+variable is set, a trace of the build (with a span for evaluating the
+configuration file and one per built target, and child spans for the
+resolve, collect, compile and link phases) and hits and misses of the
+distribution and bytecode caches are exported to an OpenTelemetry
+collector using OTLP/HTTP with JSON encoding. OTEL_EXPORTER_OTLP_HEADERS
+and OTEL_SERVICE_NAME are honored.
+
+With --keep-outputs, the previous output of each built target is moved
+to build/<triple>/<profile>/.previous/<target> and only the given number
+of previous outputs is kept, overriding set_output_retention() in the
+configuration file. --keep-all moves previous outputs there without
+pruning any.
+
+With --max-disk-usage and --max-memory, the build fails as soon as
+building a target would exceed, or exceeded, the given size of the build
+directory or peak memory usage, overriding set_build_budget() in the
+configuration file. Sizes are in bytes or use a K, M, G or T suffix, e.g.
+10G. With --max-build-time, the build fails when building a target takes
+longer than the given number of seconds. Targets are checked against an
+estimate before being built, cargo is killed as soon as a budget is
+exceeded while it runs and the output of a target exceeding a budget is
+removed.
+
+With --config-dir, every pyoxidizer.bzl file found in the given directory
+and its subdirectories (except hidden directories and build directories of
+configuration files) is built, each in isolation. A build failure doesn't
+stop other configuration files from being built. Compiled bytecode is
+cached in the build directory of the given directory, so it is shared by
+all configuration files. A summary is printed at the end and
+--workspace-report writes a JSON report of the outcome of every
+configuration file and target.
+";
+
+const ANALYZE_ABOUT: &str = "\
+Analyze a built binary or the Python executables of a project.
+
+If PATH is a file, it is analyzed as a built binary: the shared libraries
+and symbols it depends on are printed, along with the Linux distributions
+it is compatible with.
+
+If PATH is a directory, the configuration file of the project in it is
+evaluated and TARGET (the default target if omitted) and its dependencies
+are resolved. For each resolved Python executable, modules loaded from
+memory whose sources or bytecode reference __file__ or __path__, use
+pkg_resources or load shared libraries with ctypes are printed, as these
+patterns are likely to break when importing from memory. Packages known
+to not work from memory are printed as well. Nothing is built. Findings in
+the Python standard library are only printed with --include-stdlib.
+
+Targets are resolved in offline mode unless --allow-network is given, so
+nothing is downloaded: Python distributions must be cached and packages
+are only installed from the pip cache and local paths.
+";
+
+const EXPLAIN_ABOUT: &str = "\
+Describe what building a target would do, without building it.
+
+The configuration file is evaluated and TARGET (the default target if
+omitted) and its dependencies are resolved. For each resolved target,
+its type and output directory are printed. For Python executables, the
+Python distribution, the resources policy, how many resources are loaded
+from memory and installed next to the executable and the Cargo profile
+and features the executable would be built with are printed as well.
+
+Packaging operations run by target functions, such as `pip install`, are
+listed. These operations are performed when resolving targets, but
+nothing is built and no artifacts are written.
+
+Targets are resolved in offline mode unless --allow-network is given, so
+nothing is downloaded: Python distributions must be cached and packages
+are only installed from the pip cache and local paths.
+";
+
+const LIST_TARGETS_ABOUT: &str = "\
+List targets available to resolve in a configuration file.
+
+Targets are printed in the order they are registered, with the targets
+they depend on and their tags. The default target is marked with `*`.
+
+Targets are listed from their registration only: target functions aren't
+called, so nothing is downloaded, installed or built.
+";
+
+const IMPORT_BRIEFCASE_CONFIG_ABOUT: &str = "\
+Generate PyOxidizer configuration from BeeWare briefcase configuration.
+
+The PATH argument is the path to a pyproject.toml file with a
+[tool.briefcase] section. The app to convert is selected with --app and
+can be omitted if the project defines a single app.
+
+Starlark fragments replicating the sources and requirements of the app,
+including requirements specific to macOS, Linux and Windows, are printed,
+to be merged into a configuration file created by
+`pyoxidizer init-config-file`. Settings without an equivalent are listed
+at the end of the output.
+";
+
+const IMPORT_CX_FREEZE_SETUP_ABOUT: &str = "\
+Generate PyOxidizer configuration from a cx_Freeze setup script.
+
+The SETUP argument is the path to a setup.py script calling cx_Freeze's
+setup(). The script is not executed: arguments of setup() and Executable()
+are read as Python literals.
+
+Starlark fragments replicating the first executable, the packages,
+includes and include_files options of build_exe and the install_requires
+of setup() are printed, to be merged into a configuration file created by
+`pyoxidizer init-config-file`. Options without an equivalent are listed at
+the end of the output.
+";
+
+const IMPORT_PYINSTALLER_SPEC_ABOUT: &str = "\
+Generate PyOxidizer configuration from a PyInstaller spec file.
+
+The SPEC argument is the path to a PyInstaller .spec file. The spec is not
+executed: arguments of its Analysis(), EXE() and COLLECT() calls are read
+as Python literals.
+
+Starlark fragments replicating the entry script, hidden imports, data files
+and binaries of the spec are printed, to be merged into a configuration file
+created by `pyoxidizer init-config-file`. Constructs of the spec without an
+equivalent are listed at the end of the output.
 ";
 
 const INIT_RUST_PROJECT_ABOUT: &str = "\
@@ -72,6 +230,43 @@ This command executes the functionality to derive various artifacts and
 emits special lines that tell the Rust build system how to consume them.
 ";
 
+const TEST_CONFIG_ABOUT: &str = "\
+Run unit tests of Starlark configuration logic.
+
+PATH is a test file or a directory searched for test files, which are
+files whose name ends with _test.bzl. If a file with the same name
+without _test exists next to a test file (e.g. pyoxidizer.bzl next to
+pyoxidizer_test.bzl), it is evaluated before the test file, so its
+functions can be tested.
+
+Every top-level function of a test file whose name starts with test_ is
+called without arguments. assert_eq(), assert_ne(), assert_true(),
+assert_false() and assert_fails() are available to test files.
+
+Targets are only resolved if a test calls resolve_target(), network
+access is disabled and build state is written to a temporary directory.
+
+The outcome of each test is printed. The command fails if any test
+failed.
+";
+
+const WATCH_ABOUT: &str = "\
+Build targets, then build them again when files they are built from change.
+
+The configuration file, files and directories declared as inputs of
+targets and source directories read by target functions, such as package
+roots and setup.py projects, are polled for changes. Only targets affected
+by changed files, or depending on affected targets, are built again.
+Changes to the configuration file affect all targets.
+
+Evaluation and build errors are printed and don't stop watching, so they
+can be fixed in place.
+
+With --run, the last built target which can be run, e.g. an executable, is
+launched after each build. The process launched by the previous build is
+stopped first.
+";
+
 pub fn run_cli() -> Result<()> {
     let matches = App::new("PyOxidizer")
         .setting(AppSettings::ArgRequiredElseHelp)
@@ -81,7 +276,18 @@ pub fn run_cli() -> Result<()> {
         .arg(
             Arg::with_name("verbose")
                 .long("verbose")
-                .help("Enable verbose output"),
+                .multiple(true)
+                .help("Enable verbose output (repeat to log subprocess invocations)"),
+        )
+        .arg(
+            Arg::with_name("offline")
+                .long("offline")
+                .help("Fail instead of accessing the network"),
+        )
+        .arg(
+            Arg::with_name("no_pip_cache")
+                .long("no-pip-cache")
+                .help("Don't cache packages downloaded by pip across builds"),
         )
         .subcommand(
             SubCommand::with_name("add")
@@ -97,9 +303,111 @@ pub fn run_cli() -> Result<()> {
         )
         .subcommand(
             SubCommand::with_name("analyze")
-                .about("Analyze a built binary")
+                .about("Analyze a built binary or the Python executables of a project")
+                .long_about(ANALYZE_ABOUT)
                 .setting(AppSettings::ArgRequiredElseHelp)
-                .arg(Arg::with_name("path").help("Path to executable to analyze")),
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to analyze a project for"),
+                )
+                .arg(
+                    Arg::with_name("release")
+                        .long("release")
+                        .help("Analyze a project for a release build"),
+                )
+                .arg(
+                    Arg::with_name("include_stdlib")
+                        .long("include-stdlib")
+                        .help("Report findings in the Python standard library"),
+                )
+                .arg(
+                    Arg::with_name("allow_network")
+                        .long("allow-network")
+                        .help("Allow downloads when resolving targets"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path to executable or project directory to analyze"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .value_name("TARGET")
+                        .help("Target of a project to analyze"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .setting(AppSettings::TrailingVarArg)
+                .about("Compare startup of a built target against a reference Python interpreter")
+                .long_about(BENCH_ABOUT)
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to build for"),
+                )
+                .arg(
+                    Arg::with_name("release")
+                        .long("release")
+                        .help("Benchmark a release binary"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .default_value(".")
+                        .value_name("PATH")
+                        .help("Directory containing project to build"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .takes_value(true)
+                        .help("Build target to benchmark"),
+                )
+                .arg(
+                    Arg::with_name("iterations")
+                        .long("iterations")
+                        .default_value("10")
+                        .value_name("COUNT")
+                        .help("Number of warm executions of each command"),
+                )
+                .arg(
+                    Arg::with_name("reference")
+                        .long("reference")
+                        .default_value("python3")
+                        .value_name("PYTHON")
+                        .help("Python interpreter to compare against"),
+                )
+                .arg(
+                    Arg::with_name("reference_arg")
+                        .long("reference-arg")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .allow_hyphen_values(true)
+                        .help("Argument to pass to the reference interpreter"),
+                )
+                .arg(
+                    Arg::with_name("import")
+                        .long("import")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("MODULE")
+                        .help("Module to measure the import time of"),
+                )
+                .arg(
+                    Arg::with_name("python_executable_env")
+                        .long("python-executable-env")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .help("Environment variable making the binary act as `python`"),
+                )
+                .arg(Arg::with_name("extra").multiple(true)),
         )
         .subcommand(
             SubCommand::with_name("run-build-script")
@@ -118,6 +426,49 @@ pub fn run_cli() -> Result<()> {
                         .help("The config file target to resolve"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("import-briefcase-config")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Generate PyOxidizer configuration from BeeWare briefcase configuration")
+                .long_about(IMPORT_BRIEFCASE_CONFIG_ABOUT)
+                .arg(
+                    Arg::with_name("app")
+                        .long("app")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .help("Name of the app to convert"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path to pyproject.toml file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import-cx-freeze-setup")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Generate PyOxidizer configuration from a cx_Freeze setup script")
+                .long_about(IMPORT_CX_FREEZE_SETUP_ABOUT)
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("SETUP")
+                        .help("Path to cx_Freeze setup script"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import-pyinstaller-spec")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Generate PyOxidizer configuration from a PyInstaller spec file")
+                .long_about(IMPORT_PYINSTALLER_SPEC_ABOUT)
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("SPEC")
+                        .help("Path to PyInstaller spec file"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("init-config-file")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -159,6 +510,7 @@ pub fn run_cli() -> Result<()> {
             SubCommand::with_name("list-targets")
                 .setting(AppSettings::ArgRequiredElseHelp)
                 .about("List targets available to resolve in a configuration file")
+                .long_about(LIST_TARGETS_ABOUT)
                 .arg(
                     Arg::with_name("path")
                         .default_value(".")
@@ -166,6 +518,45 @@ pub fn run_cli() -> Result<()> {
                         .help("Path to project to evaluate"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Describe what building a target would do, without building it")
+                .long_about(EXPLAIN_ABOUT)
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to build for"),
+                )
+                .arg(
+                    Arg::with_name("release")
+                        .long("release")
+                        .help("Describe a release build"),
+                )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Fail if a resource can't be added as requested"),
+                )
+                .arg(
+                    Arg::with_name("allow_network")
+                        .long("allow-network")
+                        .help("Allow downloads when resolving targets"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .takes_value(true)
+                        .default_value(".")
+                        .value_name("PATH")
+                        .help("Directory containing project to describe"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .value_name("TARGET")
+                        .help("Target to describe"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("build")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -182,6 +573,11 @@ pub fn run_cli() -> Result<()> {
                         .long("release")
                         .help("Build a release binary"),
                 )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Fail if a resource can't be added as requested"),
+                )
                 .arg(
                     Arg::with_name("path")
                         .long("path")
@@ -190,6 +586,79 @@ pub fn run_cli() -> Result<()> {
                         .value_name("PATH")
                         .help("Directory containing project to build"),
                 )
+                .arg(
+                    Arg::with_name("tags")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("TAG")
+                        .conflicts_with("targets")
+                        .help("Build targets having this tag"),
+                )
+                .arg(
+                    Arg::with_name("changed_since")
+                        .long("changed-since")
+                        .takes_value(true)
+                        .value_name("REVISION")
+                        .help("Only build targets affected by changes since a Git revision"),
+                )
+                .arg(
+                    Arg::with_name("otlp_endpoint")
+                        .long("otlp-endpoint")
+                        .takes_value(true)
+                        .value_name("URL")
+                        .help("Export build traces and cache metrics to an OTLP/HTTP endpoint"),
+                )
+                .arg(
+                    Arg::with_name("keep_outputs")
+                        .long("keep-outputs")
+                        .takes_value(true)
+                        .value_name("COUNT")
+                        .help("Number of previous outputs to keep for each target"),
+                )
+                .arg(
+                    Arg::with_name("keep_all")
+                        .long("keep-all")
+                        .help("Don't prune previous outputs of targets"),
+                )
+                .arg(
+                    Arg::with_name("max_disk_usage")
+                        .long("max-disk-usage")
+                        .takes_value(true)
+                        .value_name("SIZE")
+                        .help("Fail if the build directory would exceed this size"),
+                )
+                .arg(
+                    Arg::with_name("max_memory")
+                        .long("max-memory")
+                        .takes_value(true)
+                        .value_name("SIZE")
+                        .help("Fail if peak memory usage would exceed this size"),
+                )
+                .arg(
+                    Arg::with_name("max_build_time")
+                        .long("max-build-time")
+                        .takes_value(true)
+                        .value_name("SECONDS")
+                        .help("Fail if building a target takes longer than this"),
+                )
+                .arg(
+                    Arg::with_name("config_dir")
+                        .long("config-dir")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .conflicts_with("targets")
+                        .help("Build every configuration file found in this directory"),
+                )
+                .arg(
+                    Arg::with_name("workspace_report")
+                        .long("workspace-report")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .requires("config_dir")
+                        .help("Write a JSON report of the builds of --config-dir to this path"),
+                )
                 .arg(
                     Arg::with_name("targets")
                         .value_name("TARGET")
@@ -212,6 +681,11 @@ pub fn run_cli() -> Result<()> {
                         .long("release")
                         .help("Run a release binary"),
                 )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Fail if a resource can't be added as requested"),
+                )
                 .arg(
                     Arg::with_name("path")
                         .long("path")
@@ -263,14 +737,82 @@ pub fn run_cli() -> Result<()> {
                         .help("Path to Python distribution to analyze"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("test-config")
+                .about("Run unit tests of Starlark configuration logic")
+                .long_about(TEST_CONFIG_ABOUT)
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to evaluate tests for"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .default_value(".")
+                        .value_name("PATH")
+                        .help("Test file or directory containing test files"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Build targets again when files they are built from change")
+                .long_about(WATCH_ABOUT)
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to build for"),
+                )
+                .arg(
+                    Arg::with_name("release")
+                        .long("release")
+                        .help("Build a release binary"),
+                )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Fail if a resource can't be added as requested"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .takes_value(true)
+                        .default_value(".")
+                        .value_name("PATH")
+                        .help("Directory containing project to build"),
+                )
+                .arg(
+                    Arg::with_name("run")
+                        .long("run")
+                        .help("Launch the built executable after each build"),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .takes_value(true)
+                        .default_value("1")
+                        .value_name("SECONDS")
+                        .help("Interval between checks for changed files"),
+                )
+                .arg(
+                    Arg::with_name("targets")
+                        .value_name("TARGET")
+                        .multiple(true)
+                        .help("Target to build"),
+                ),
+        )
         .get_matches();
 
-    let verbose = matches.is_present("verbose");
+    let verbosity = matches.occurrences_of("verbose");
+    let verbose = verbosity > 0;
+    let offline = matches.is_present("offline");
+    let pip_cache = !matches.is_present("no_pip_cache");
 
-    let log_level = if verbose {
-        slog::Level::Info
-    } else {
-        slog::Level::Warning
+    let log_level = match verbosity {
+        0 => slog::Level::Warning,
+        1 => slog::Level::Info,
+        _ => slog::Level::Debug,
     };
 
     let logger_context = logging::logger_from_env(log_level);
@@ -285,9 +827,67 @@ pub fn run_cli() -> Result<()> {
         ("analyze", Some(args)) => {
             let path = args.value_of("path").unwrap();
             let path = PathBuf::from(path);
-            analyze::analyze_file(path);
 
-            Ok(())
+            if path.is_dir() {
+                projectmgmt::analyze_project(
+                    &logger_context.logger,
+                    &path,
+                    args.value_of("target_triple"),
+                    args.is_present("release"),
+                    args.value_of("target"),
+                    args.is_present("include_stdlib"),
+                    verbose,
+                    offline || !args.is_present("allow_network"),
+                    pip_cache,
+                )
+            } else {
+                analyze::analyze_file(path);
+
+                Ok(())
+            }
+        }
+
+        ("bench", Some(args)) => {
+            let target_triple = args.value_of("target_triple");
+            let release = args.is_present("release");
+            let path = args.value_of("path").unwrap();
+            let target = args.value_of("target");
+            let iterations = args
+                .value_of("iterations")
+                .unwrap()
+                .parse::<usize>()
+                .or_else(|e| Err(anyhow!("invalid --iterations value: {}", e)))?;
+            let reference = args.value_of("reference").unwrap();
+            let reference_args: Vec<&str> = args
+                .values_of("reference_arg")
+                .unwrap_or_default()
+                .collect();
+            let imports = args
+                .values_of("import")
+                .unwrap_or_default()
+                .map(|x| x.to_string())
+                .collect();
+            let python_executable_env = args.value_of("python_executable_env");
+            let extra: Vec<&str> = args.values_of("extra").unwrap_or_default().collect();
+
+            projectmgmt::bench(
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                release,
+                target,
+                reference,
+                &reference_args,
+                &extra,
+                python_executable_env,
+                &BenchmarkOptions {
+                    iterations,
+                    imports,
+                },
+                verbose,
+                offline,
+                pip_cache,
+            )
         }
 
         ("build", Some(args)) => {
@@ -299,6 +899,56 @@ pub fn run_cli() -> Result<()> {
             } else {
                 None
             };
+            let tags = if let Some(values) = args.values_of("tags") {
+                values.map(|x| x.to_string()).collect()
+            } else {
+                Vec::new()
+            };
+            let keep_outputs = match args.value_of("keep_outputs") {
+                Some(value) => Some(
+                    value
+                        .parse::<usize>()
+                        .or_else(|_| Err(anyhow!("invalid --keep-outputs value: {}", value)))?,
+                ),
+                None => None,
+            };
+            let budget = BuildBudget {
+                disk_usage: match args.value_of("max_disk_usage") {
+                    Some(value) => Some(parse_size(value)?),
+                    None => None,
+                },
+                memory: match args.value_of("max_memory") {
+                    Some(value) => Some(parse_size(value)?),
+                    None => None,
+                },
+                time: match args.value_of("max_build_time") {
+                    Some(value) => match value.parse::<u64>() {
+                        Ok(seconds) if seconds > 0 => Some(Duration::from_secs(seconds)),
+                        _ => return Err(anyhow!("invalid --max-build-time value: {}", value)),
+                    },
+                    None => None,
+                },
+            };
+
+            if let Some(config_dir) = args.value_of("config_dir") {
+                return projectmgmt::build_workspace(
+                    &logger_context.logger,
+                    Path::new(config_dir),
+                    target_triple,
+                    release,
+                    verbose,
+                    args.is_present("strict"),
+                    offline,
+                    pip_cache,
+                    &tags,
+                    args.value_of("changed_since"),
+                    args.value_of("otlp_endpoint"),
+                    keep_outputs,
+                    args.is_present("keep_all"),
+                    &budget,
+                    args.value_of("workspace_report").map(Path::new),
+                );
+            }
 
             projectmgmt::build(
                 &logger_context.logger,
@@ -307,9 +957,37 @@ pub fn run_cli() -> Result<()> {
                 resolve_targets,
                 release,
                 verbose,
+                args.is_present("strict"),
+                offline,
+                pip_cache,
+                &tags,
+                args.value_of("changed_since"),
+                args.value_of("otlp_endpoint"),
+                keep_outputs,
+                args.is_present("keep_all"),
+                &budget,
             )
         }
 
+        ("import-briefcase-config", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+            let app = args.value_of("app");
+
+            projectmgmt::import_briefcase_config(&logger_context.logger, Path::new(path), app)
+        }
+
+        ("import-cx-freeze-setup", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::import_cx_freeze_setup(&logger_context.logger, Path::new(path))
+        }
+
+        ("import-pyinstaller-spec", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::import_pyinstaller_spec(&logger_context.logger, Path::new(path))
+        }
+
         ("init-config-file", Some(args)) => {
             let code = args.value_of("python-code");
             let pip_install = if args.is_present("pip-install") {
@@ -329,6 +1007,25 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::list_targets(&logger_context.logger, Path::new(path))
         }
 
+        ("explain", Some(args)) => {
+            let target_triple = args.value_of("target_triple");
+            let release = args.is_present("release");
+            let path = args.value_of("path").unwrap();
+            let target = args.value_of("target");
+
+            projectmgmt::explain(
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                release,
+                target,
+                verbose,
+                args.is_present("strict"),
+                offline || !args.is_present("allow_network"),
+                pip_cache,
+            )
+        }
+
         ("init-rust-project", Some(args)) => {
             let path = args.value_of("path").unwrap();
             let project_path = Path::new(path);
@@ -377,6 +1074,48 @@ pub fn run_cli() -> Result<()> {
                 target,
                 &extra,
                 verbose,
+                args.is_present("strict"),
+                offline,
+                pip_cache,
+            )
+        }
+
+        ("test-config", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::test_config(
+                &logger_context.logger,
+                Path::new(path),
+                args.value_of("target_triple"),
+                verbose,
+            )
+        }
+
+        ("watch", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+            let resolve_targets = if let Some(values) = args.values_of("targets") {
+                Some(values.map(|x| x.to_string()).collect())
+            } else {
+                None
+            };
+            let interval = args.value_of("interval").unwrap();
+            let interval = match interval.parse::<f64>() {
+                Ok(value) if value > 0.0 && value.is_finite() => Duration::from_secs_f64(value),
+                _ => return Err(anyhow!("invalid --interval value: {}", interval)),
+            };
+
+            projectmgmt::watch(
+                &logger_context.logger,
+                Path::new(path),
+                args.value_of("target_triple"),
+                resolve_targets,
+                args.is_present("release"),
+                verbose,
+                args.is_present("strict"),
+                offline,
+                pip_cache,
+                args.is_present("run"),
+                interval,
             )
         }
 
@@ -0,0 +1,565 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Conversion of cx_Freeze setup scripts to PyOxidizer configuration.
+
+cx_Freeze is configured by the `executables` and `options` arguments of a
+`setup()` call in a `setup.py` script. Like PyInstaller spec files, setup
+scripts are not executed: arguments are read as Python literals by
+`crate::pyinstaller_spec::parse_calls()`.
+*/
+
+use {
+    crate::pyinstaller_spec::{
+        describe, normalize_path, parse_calls, placement_glob, script_module, starlark_list,
+        starlark_str, SpecCall, SpecConversion, SpecValue,
+    },
+    anyhow::{anyhow, Context, Result},
+    std::path::Path,
+};
+
+/// Arguments of `setup()` describing the project, which have no effect.
+const METADATA_ARGUMENTS: &[&str] = &[
+    "name",
+    "version",
+    "description",
+    "long_description",
+    "long_description_content_type",
+    "author",
+    "author_email",
+    "maintainer",
+    "maintainer_email",
+    "url",
+    "license",
+    "classifiers",
+    "keywords",
+    "platforms",
+    "python_requires",
+];
+
+/// Read the entries of a dict literal or a `dict()` call with string keys.
+///
+/// Dashes in keys are replaced by underscores, as distutils does for
+/// command options.
+fn dict_entries(value: &SpecValue) -> Option<Vec<(String, SpecValue)>> {
+    let entries = match value {
+        SpecValue::Dict(entries) => entries
+            .iter()
+            .map(|(key, value)| Some((key.as_str()?.to_string(), value.clone())))
+            .collect::<Option<Vec<_>>>()?,
+        SpecValue::Call(call) if call.function == "dict" && call.args.is_empty() => {
+            call.kwargs.clone()
+        }
+        _ => return None,
+    };
+
+    Some(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.replace('-', "_"), value))
+            .collect(),
+    )
+}
+
+/// Read a list of strings, also accepting a single string.
+fn string_list(value: &SpecValue) -> Option<Vec<String>> {
+    match value {
+        SpecValue::Str(s) => Some(vec![s.clone()]),
+        SpecValue::List(values) => values
+            .iter()
+            .map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Derive the `glob()` call placing an `include_files` entry.
+///
+/// cx_Freeze copies `source` to the `target` path, which defaults to the
+/// file name of `source`. Directories are copied recursively. Files can't be
+/// renamed by `glob()`, so `None` is returned if `target` changes the file
+/// name of a file.
+fn include_file_glob(setup_dir: &Path, source: &str, target: Option<&str>) -> Option<String> {
+    let source = normalize_path(source);
+    let file_name = source.rsplit('/').next().unwrap_or(&source).to_string();
+    let target = target
+        .map(normalize_path)
+        .unwrap_or_else(|| file_name.clone());
+
+    if setup_dir.join(&source).is_dir() {
+        return placement_glob(setup_dir, &source, &target);
+    }
+
+    let (dir, name) = match target.rfind('/') {
+        Some(index) => (&target[..index], &target[index + 1..]),
+        None => ("", target.as_str()),
+    };
+
+    if name == file_name {
+        placement_glob(setup_dir, &source, dir)
+    } else {
+        None
+    }
+}
+
+/// Convert the `setup()` call of a setup script to Starlark.
+///
+/// `setup_name` is the name of the setup script and `setup_dir` the
+/// directory containing it, which relative paths are resolved against.
+pub fn convert_setup(
+    setup_name: &str,
+    setup_dir: &Path,
+    calls: &[SpecCall],
+) -> Result<SpecConversion> {
+    let setup = calls
+        .iter()
+        .find(|call| call.function == "setup")
+        .ok_or_else(|| anyhow!("no setup() call found in {}", setup_name))?;
+
+    let mut res = SpecConversion::default();
+
+    for (key, value) in &setup.kwargs {
+        if ["executables", "options", "install_requires"].contains(&key.as_str())
+            || METADATA_ARGUMENTS.contains(&key.as_str())
+            || value.is_default()
+        {
+            continue;
+        }
+
+        let message = match key.as_str() {
+            "packages" | "py_modules" | "package_dir" => {
+                "add the project's packages with `dist.read_package_root()`"
+            }
+            "data_files" | "package_data" => "add data files with `files.add_manifest()`",
+            _ => "no equivalent",
+        };
+
+        res.flag(format!("`setup({}=...)`: {}", key, message));
+    }
+
+    let options = match setup.kwarg("options") {
+        Some(value) => dict_entries(value)
+            .ok_or_else(|| anyhow!("setup() options must be a dict; got {}", describe(value)))?,
+        None => Vec::new(),
+    };
+
+    let mut build_exe = Vec::new();
+    for (command, value) in &options {
+        match command.as_str() {
+            "build_exe" => {
+                build_exe = dict_entries(value).ok_or_else(|| {
+                    anyhow!(
+                        "setup() options of build_exe must be a dict; got {}",
+                        describe(value)
+                    )
+                })?;
+            }
+            "bdist_msi" | "bdist_mac" | "bdist_dmg" | "bdist_rpm" | "bdist_appimage" => {
+                res.flag(format!(
+                    "`setup(options={{\"{}\": ...}})`: installers aren't produced; \
+                     executables are installed to a directory",
+                    command
+                ));
+            }
+            _ => res.flag(format!(
+                "`setup(options={{\"{}\": ...}})`: no equivalent",
+                command
+            )),
+        }
+    }
+
+    let build_option = |name: &str| {
+        build_exe
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    };
+
+    for (key, value) in &build_exe {
+        if ["packages", "includes", "include_files", "build_exe"].contains(&key.as_str())
+            || value.is_default()
+        {
+            continue;
+        }
+
+        let message = match key.as_str() {
+            "excludes" => {
+                "modules can't be excluded from packages; filter resources with \
+                 `exe.filter_resources_from_files()`"
+            }
+            "path" => "add packages from these directories with `dist.read_package_root()`",
+            "zip_include_packages" | "zip_exclude_packages" | "zip_includes" => {
+                "resources are embedded in the executable or installed next to it"
+            }
+            "bin_includes" | "bin_excludes" | "bin_path_includes" | "bin_path_excludes" => {
+                "shared libraries of packages are installed whole"
+            }
+            "silent" | "silent_level" => continue,
+            _ => "no equivalent",
+        };
+
+        res.flag(format!(
+            "`setup(options={{\"build_exe\": {{\"{}\": ...}}}})`: {}",
+            key, message
+        ));
+    }
+
+    let executables = match setup.kwarg("executables") {
+        Some(SpecValue::List(executables)) => executables
+            .iter()
+            .map(|executable| match executable {
+                SpecValue::Call(call) if call.function == "Executable" => Some(call.clone()),
+                // A string is the script of an executable.
+                SpecValue::Str(_) => Some(SpecCall {
+                    function: "Executable".to_string(),
+                    args: vec![executable.clone()],
+                    kwargs: Vec::new(),
+                }),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("setup() executables must be a list of Executable() calls"))?;
+
+    let executable = executables
+        .first()
+        .ok_or_else(|| anyhow!("setup() has no executables"))?;
+    if executables.len() > 1 {
+        res.flag(
+            "`setup(executables=...)`: only the first executable is converted; \
+             define other executables with `dist.to_python_executable()`"
+                .to_string(),
+        );
+    }
+
+    let script = executable
+        .kwarg("script")
+        .or_else(|| executable.args.first())
+        .and_then(|script| script.as_str())
+        .ok_or_else(|| anyhow!("Executable() script must be a string"))?;
+    let (script_root, module) = script_module(script);
+    let module = module.as_str();
+
+    for (key, value) in &executable.kwargs {
+        if ["script", "target_name", "targetName", "uac_admin"].contains(&key.as_str())
+            || value.is_default()
+        {
+            continue;
+        }
+
+        let message = match (key.as_str(), value) {
+            ("base", SpecValue::Str(base)) if base == "Console" || base == "console" => continue,
+            ("base", _) => "windowed (non-console) and service executables aren't supported",
+            ("init_script", _) | ("initScript", _) => {
+                "initialization scripts aren't run; run their code from the entry module"
+            }
+            ("icon", _) => "executable icons aren't supported",
+            ("shortcut_name", _)
+            | ("shortcutName", _)
+            | ("shortcut_dir", _)
+            | ("shortcutDir", _) => "shortcuts are only created by installers",
+            _ => "no equivalent",
+        };
+
+        res.flag(format!("`Executable({}=...)`: {}", key, message));
+    }
+
+    let name = executable
+        .kwarg("target_name")
+        .or_else(|| executable.kwarg("targetName"))
+        .and_then(|name| name.as_str())
+        .map(|name| name.trim_end_matches(".exe").to_string())
+        .unwrap_or_else(|| module.to_string());
+
+    let mut lines = vec![
+        format!(
+            "# Generated from {} by `pyoxidizer import-cx-freeze-setup`.",
+            setup_name
+        ),
+        "#".to_string(),
+        "# These fragments replicate the setup script in a configuration file".to_string(),
+        "# created by `pyoxidizer init-config-file`. Paths are relative to the".to_string(),
+        "# directory containing the setup script, which should also contain the".to_string(),
+        "# configuration file.".to_string(),
+        String::new(),
+        "# In make_exe():".to_string(),
+        "python_config = PythonInterpreterConfig(".to_string(),
+        format!("    run_module={},", starlark_str(module)),
+        "    # Emulate cx_Freeze for code checking sys.frozen.".to_string(),
+        "    #sys_frozen=True,".to_string(),
+        ")".to_string(),
+        String::new(),
+        "exe = dist.to_python_executable(".to_string(),
+        format!("    name={},", starlark_str(&name)),
+        "    config=python_config,".to_string(),
+        "    extension_module_filter=\"all\",".to_string(),
+        "    include_sources=True,".to_string(),
+        "    include_resources=True,".to_string(),
+        ")".to_string(),
+        String::new(),
+        format!(
+            "# The entry script, run as the `{}` module. Add local packages it",
+            module
+        ),
+        "# imports the same way.".to_string(),
+        format!(
+            "exe.add_in_memory_python_resources(dist.read_package_root({}, {}))",
+            script_root,
+            starlark_list(&[module.to_string()])
+        ),
+    ];
+
+    let mut packages: Vec<String> = Vec::new();
+    for key in &["packages", "includes"] {
+        match build_option(key) {
+            Some(value) if !value.is_default() => match string_list(value) {
+                Some(names) => {
+                    for name in names {
+                        let package = name.split('.').next().unwrap_or(&name).to_string();
+                        if !packages.contains(&package) {
+                            packages.push(package);
+                        }
+                    }
+                }
+                None => res.flag(format!(
+                    "`setup(options={{\"build_exe\": {{\"{}\": {}}}}})`: \
+                     only lists of module names are converted",
+                    key,
+                    describe(value)
+                )),
+            },
+            _ => {}
+        }
+    }
+
+    if !packages.is_empty() {
+        lines.extend(vec![
+            String::new(),
+            "# `packages` and `includes` of build_exe. Packages are added whole, so".to_string(),
+            "# only the package of included modules needs to be installed. Package".to_string(),
+            "# names are used as requirements: adjust those differing from their".to_string(),
+            "# distribution name and remove standard library modules.".to_string(),
+            format!(
+                "exe.add_in_memory_python_resources(dist.pip_install({}))",
+                starlark_list(&packages)
+            ),
+        ]);
+    }
+
+    match setup.kwarg("install_requires") {
+        Some(value) if !value.is_default() => match string_list(value) {
+            Some(requirements) => lines.extend(vec![
+                String::new(),
+                "# `install_requires` of setup().".to_string(),
+                format!(
+                    "exe.add_in_memory_python_resources(dist.pip_install({}))",
+                    starlark_list(&requirements)
+                ),
+            ]),
+            None => res.flag(format!(
+                "`setup(install_requires={})`: only lists of requirements are converted",
+                describe(value)
+            )),
+        },
+        _ => {}
+    }
+
+    if executable.kwarg("uac_admin") == Some(&SpecValue::Bool(true)) {
+        lines.extend(vec![
+            String::new(),
+            "exe.set_windows_manifest(uac_execution_level=\"requireAdministrator\")".to_string(),
+        ]);
+    }
+
+    lines.extend(vec![
+        String::new(),
+        "# In make_install():".to_string(),
+        "files = FileManifest()".to_string(),
+        "files.add_python_resource(\".\", exe)".to_string(),
+    ]);
+
+    match build_option("include_files") {
+        Some(SpecValue::List(entries)) if !entries.is_empty() => {
+            lines.push(String::new());
+            lines.push("# `include_files` of build_exe.".to_string());
+
+            for entry in entries {
+                let placement = match entry {
+                    SpecValue::Str(source) => Some((source.as_str(), None)),
+                    SpecValue::List(pair) if pair.len() == 2 => {
+                        match (pair[0].as_str(), pair[1].as_str()) {
+                            (Some(source), Some(target)) => Some((source, Some(target))),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+
+                match placement {
+                    Some((source, target)) => match include_file_glob(setup_dir, source, target) {
+                        Some(glob) => lines.push(format!("files.add_manifest({})", glob)),
+                        None => res.flag(format!(
+                            "`include_files`: ({:?}, {:?}) can't be placed with glob(), \
+                             which can't rename files and whose strip_prefix must leave \
+                             the target directory",
+                            source,
+                            target.unwrap_or(source)
+                        )),
+                    },
+                    None => res.flag(format!(
+                        "`include_files`: {} isn't a path or a (source, target) tuple",
+                        describe(entry)
+                    )),
+                }
+            }
+        }
+        Some(value) if !value.is_default() => res.flag(format!(
+            "`include_files={}`: only lists of paths and (source, target) tuples are converted",
+            describe(value)
+        )),
+        _ => {}
+    }
+
+    let install_dir = build_option("build_exe")
+        .and_then(|dir| dir.as_str())
+        .map(normalize_path)
+        .unwrap_or_else(|| name.clone());
+    lines.extend(vec![
+        String::new(),
+        "# Write files to a directory of the build path, like build_exe.".to_string(),
+        format!("files.install({})", starlark_str(&install_dir)),
+    ]);
+
+    res.finish(lines, "setup script");
+
+    Ok(res)
+}
+
+/// Read a setup script and convert it to Starlark.
+pub fn import_setup(path: &Path) -> Result<SpecConversion> {
+    let source = std::fs::read_to_string(path).context(format!("reading {}", path.display()))?;
+
+    let setup_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("unable to determine file name"))?
+        .to_string_lossy()
+        .to_string();
+    let setup_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    convert_setup(&setup_name, setup_dir, &parse_calls(&source, &["setup"])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETUP: &str = r#"import sys
+from cx_Freeze import setup, Executable
+
+build_exe_options = {
+    "packages": ["os", "requests"],
+    "includes": ["yaml.loader"],
+    "excludes": ["tkinter"],
+    "include_files": ["data/", ("config/app.ini", "etc/app.ini"), ("logo.png", "icon.png")],
+    "include-msvcr": False,
+}
+
+base = None
+if sys.platform == "win32":
+    base = "Win32GUI"
+
+setup(
+    name="guifoo",
+    version="0.1",
+    description="My GUI application!",
+    install_requires=["click>=7"],
+    options={"build_exe": build_exe_options, "bdist_msi": dict(upgrade_code="{X}")},
+    executables=[Executable("src/guifoo.py", base=base, target_name="foo.exe")],
+)
+"#;
+
+    #[test]
+    fn test_include_file_glob() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::create_dir_all(temp_dir.path().join("data"))?;
+        let dir = temp_dir.path();
+
+        assert_eq!(
+            include_file_glob(dir, "data/", None).unwrap(),
+            "glob([\"data/**/*\"], strip_prefix=CWD)"
+        );
+        assert_eq!(
+            include_file_glob(dir, "config/app.ini", Some("etc/app.ini")),
+            None
+        );
+        assert_eq!(
+            include_file_glob(dir, "etc/app.ini", Some("etc/app.ini")).unwrap(),
+            "glob([\"etc/app.ini\"], strip_prefix=CWD)"
+        );
+        assert_eq!(
+            include_file_glob(dir, "lib/libfoo.so", None).unwrap(),
+            "glob([\"lib/libfoo.so\"], strip_prefix=CWD + \"/lib\")"
+        );
+        assert_eq!(include_file_glob(dir, "logo.png", Some("icon.png")), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_setup() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let setup_path = temp_dir.path().join("setup.py");
+        std::fs::create_dir_all(temp_dir.path().join("data"))?;
+        std::fs::write(&setup_path, SETUP)?;
+
+        let res = import_setup(&setup_path)?;
+
+        assert!(res.starlark.contains("    run_module=\"guifoo\",\n"));
+        assert!(res.starlark.contains("    name=\"foo\",\n"));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(dist.read_package_root(CWD + \"/src\", [\"guifoo\"]))\n"
+        ));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(dist.pip_install([\"os\", \"requests\", \"yaml\"]))\n"
+        ));
+        assert!(res
+            .starlark
+            .contains("exe.add_in_memory_python_resources(dist.pip_install([\"click>=7\"]))\n"));
+        assert!(res
+            .starlark
+            .contains("files.add_manifest(glob([\"data/**/*\"], strip_prefix=CWD))\n"));
+        assert!(res.starlark.contains("files.install(\"foo\")\n"));
+
+        assert_eq!(
+            res.unsupported,
+            vec![
+                "`setup(options={\"bdist_msi\": ...})`: installers aren't produced; \
+                 executables are installed to a directory",
+                "`setup(options={\"build_exe\": {\"excludes\": ...}})`: modules can't be \
+                 excluded from packages; filter resources with \
+                 `exe.filter_resources_from_files()`",
+                "`Executable(base=...)`: windowed (non-console) and service executables \
+                 aren't supported",
+                "`include_files`: (\"config/app.ini\", \"etc/app.ini\") can't be placed with \
+                 glob(), which can't rename files and whose strip_prefix must leave the \
+                 target directory",
+                "`include_files`: (\"logo.png\", \"icon.png\") can't be placed with \
+                 glob(), which can't rename files and whose strip_prefix must leave the \
+                 target directory",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_setup_without_executables() {
+        let calls = parse_calls("setup(name='x')", &["setup"]).unwrap();
+
+        assert!(convert_setup("setup.py", Path::new("."), &calls).is_err());
+        assert!(convert_setup("setup.py", Path::new("."), &[]).is_err());
+    }
+}
@@ -37,6 +37,42 @@ lazy_static! {
     pub static ref MINIMUM_RUST_VERSION: semver::Version = semver::Version::new(1, 36, 0);
 }
 
+/// Obtain the directory where Python distributions are cached.
+///
+/// The cache is shared by all projects of the current user, so distributions
+/// aren't downloaded and extracted again for every project. Falls back to a
+/// directory under `build_path` if the user has no cache directory.
+pub fn python_distributions_cache_dir(build_path: &Path) -> PathBuf {
+    match dirs::cache_dir() {
+        Some(path) => path.join("pyoxidizer").join("distributions"),
+        None => build_path.join("python_distributions"),
+    }
+}
+
+/// Obtain the directory where pip caches downloaded packages.
+///
+/// Like Python distributions, the cache is shared by all projects of the
+/// current user. Falls back to a directory under `build_path` if the user
+/// has no cache directory.
+pub fn pip_cache_dir(build_path: &Path) -> PathBuf {
+    match dirs::cache_dir() {
+        Some(path) => path.join("pyoxidizer").join("pip"),
+        None => build_path.join("pip_cache"),
+    }
+}
+
+/// Obtain the directory where tools used by builds are cached.
+///
+/// Tools like the WiX Toolset are downloaded once for all projects of the
+/// current user. Falls back to a directory under `build_path` if the user
+/// has no cache directory.
+pub fn tools_cache_dir(build_path: &Path) -> PathBuf {
+    match dirs::cache_dir() {
+        Some(path) => path.join("pyoxidizer").join("tools"),
+        None => build_path.join("tools"),
+    }
+}
+
 /// Find the root Git commit given a starting Git commit.
 ///
 /// This just walks parents until it gets to a commit without any.
@@ -54,13 +90,16 @@ pub fn canonicalize_path(path: &Path) -> Result<PathBuf, std::io::Error> {
     let mut p = path.canonicalize()?;
 
     // Strip \\?\ prefix on Windows and replace \ with /, which is valid.
+    // Paths that aren't valid Unicode are left as is rather than mangled.
     if cfg!(windows) {
-        let mut s = p.display().to_string().replace("\\", "/");
-        if s.starts_with("//?/") {
-            s = s[4..].to_string();
-        }
+        if let Some(s) = p.to_str() {
+            let mut s = s.replace("\\", "/");
+            if s.starts_with("//?/") {
+                s = s[4..].to_string();
+            }
 
-        p = PathBuf::from(s);
+            p = PathBuf::from(s);
+        }
     }
 
     Ok(p)
@@ -0,0 +1,258 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Description of what building targets would do.
+
+Plans are derived from targets resolved by evaluating a config file. Resolving
+a target calls its function, which may perform packaging operations such as
+`pip install`, but nothing is built and no artifacts are written. The `explain`
+command resolves targets offline by default, so nothing is downloaded.
+*/
+
+use {
+    crate::project_building::cargo_features,
+    crate::starlark::env::EnvironmentContext,
+    crate::starlark::file_resource::FileManifest,
+    crate::starlark::python_executable::PythonExecutable,
+    anyhow::{anyhow, Result},
+    std::fmt::Write,
+    std::path::PathBuf,
+};
+
+/// Describes how a Python executable would be built.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutablePlan {
+    /// Name of the executable.
+    pub name: String,
+
+    /// Python version of the distribution.
+    pub python_version: String,
+
+    /// Path to the distribution's `python` executable.
+    pub python_exe_path: PathBuf,
+
+    /// Policy for placing resources.
+    pub resources_policy: String,
+
+    /// Number of resources loaded from memory.
+    pub in_memory_resources: usize,
+
+    /// Number of resources installed relative to the executable.
+    pub filesystem_relative_resources: usize,
+
+    /// Cargo profile the executable is built with.
+    pub cargo_profile: String,
+
+    /// Cargo features the executable is built with.
+    pub cargo_features: Vec<String>,
+}
+
+impl ExecutablePlan {
+    fn new(exe: &PythonExecutable, release: bool) -> Self {
+        let (in_memory_resources, filesystem_relative_resources) =
+            exe.exe.resource_location_counts();
+
+        Self {
+            name: exe.exe.name(),
+            python_version: exe.exe.python_version(),
+            python_exe_path: exe.exe.python_exe_path().to_path_buf(),
+            resources_policy: exe.exe.python_resources_policy().into(),
+            in_memory_resources,
+            filesystem_relative_resources,
+            cargo_profile: if release { "release" } else { "debug" }.to_string(),
+            cargo_features: cargo_features(exe.exe.as_ref())
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Describes how a target would be built.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TargetPlan {
+    /// Name of the target.
+    pub name: String,
+
+    /// Type of the value the target resolved to.
+    pub target_type: String,
+
+    /// Directory the target would be written to.
+    pub output_path: PathBuf,
+
+    /// Targets this one depends on.
+    pub depends: Vec<String>,
+
+    /// Number of files installed, for file manifests.
+    pub files: Option<usize>,
+
+    /// How the executable would be built, for Python executables.
+    pub executable: Option<ExecutablePlan>,
+}
+
+/// Describe how resolved targets would be built.
+///
+/// Targets are described in the order they were registered in. Targets that
+/// weren't resolved are ignored.
+pub fn plan_targets(context: &EnvironmentContext) -> Result<Vec<TargetPlan>> {
+    let profile_path = context.profile_path();
+    let mut plans = Vec::new();
+
+    for name in &context.targets_order {
+        let target = context
+            .targets
+            .get(name)
+            .ok_or_else(|| anyhow!("target {} is not registered", name))?;
+
+        let value = match &target.resolved_value {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let target_type = value.get_type().to_string();
+
+        let files = if target_type == "FileManifest" {
+            Some(value.downcast_apply(|m: &FileManifest| m.manifest.entries().count()))
+        } else {
+            None
+        };
+
+        let executable = if target_type == "PythonExecutable" {
+            Some(value.downcast_apply(|exe: &PythonExecutable| {
+                ExecutablePlan::new(exe, context.build_release)
+            }))
+        } else {
+            None
+        };
+
+        plans.push(TargetPlan {
+            name: name.clone(),
+            target_type,
+            output_path: profile_path.join(name),
+            depends: target.depends.clone(),
+            files,
+            executable,
+        });
+    }
+
+    Ok(plans)
+}
+
+/// Render a human readable description of target plans.
+pub fn render_plans(context: &EnvironmentContext, plans: &[TargetPlan]) -> Result<String> {
+    let mut s = String::new();
+
+    writeln!(s, "build target: {}", context.build_target_triple)?;
+    writeln!(s, "build path: {}", context.build_path.display())?;
+
+    writeln!(s, "packaging operations:")?;
+    if context.packaging_operations.is_empty() {
+        writeln!(s, "  (none)")?;
+    }
+    for operation in &context.packaging_operations {
+        writeln!(s, "  {}", operation)?;
+    }
+
+    for plan in plans {
+        writeln!(s)?;
+        writeln!(s, "target {} ({})", plan.name, plan.target_type)?;
+        if !plan.depends.is_empty() {
+            writeln!(s, "  depends on: {}", plan.depends.join(", "))?;
+        }
+        writeln!(s, "  output: {}", plan.output_path.display())?;
+
+        if let Some(files) = plan.files {
+            writeln!(s, "  files: {}", files)?;
+        }
+
+        if let Some(exe) = &plan.executable {
+            writeln!(s, "  executable: {}", exe.name)?;
+            writeln!(
+                s,
+                "  python: {} ({})",
+                exe.python_version,
+                exe.python_exe_path.display()
+            )?;
+            writeln!(s, "  resources policy: {}", exe.resources_policy)?;
+            writeln!(s, "  in-memory resources: {}", exe.in_memory_resources)?;
+            writeln!(
+                s,
+                "  filesystem-relative resources: {}",
+                exe.filesystem_relative_resources
+            )?;
+            writeln!(s, "  cargo profile: {}", exe.cargo_profile)?;
+            writeln!(s, "  cargo features: {}", exe.cargo_features.join(" "))?;
+        }
+    }
+
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*};
+
+    fn plans_for(snippets: &[&str]) -> Result<(Vec<TargetPlan>, String)> {
+        let mut env = starlark_env();
+        for snippet in snippets {
+            starlark_eval_in_env(&mut env, snippet).unwrap();
+        }
+
+        let context = env.get("CONTEXT").unwrap();
+        context.downcast_apply(|x: &EnvironmentContext| {
+            let plans = plan_targets(x)?;
+            let rendered = render_plans(x, &plans)?;
+
+            Ok((plans, rendered))
+        })
+    }
+
+    #[test]
+    fn test_file_manifest() -> Result<()> {
+        let (plans, rendered) = plans_for(&[
+            "def make():\n    return FileManifest()\n",
+            "def other():\n    return FileManifest()\n",
+            "register_target('files', make)",
+            "register_target('other', other)",
+            "resolve_target('files')",
+        ])?;
+
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].name, "files");
+        assert_eq!(plans[0].target_type, "FileManifest");
+        assert_eq!(plans[0].files, Some(0));
+        assert!(plans[0].output_path.ends_with("debug/files"));
+        assert!(plans[0].executable.is_none());
+
+        assert!(rendered.contains("packaging operations:\n  (none)\n"));
+        assert!(rendered.contains("target files (FileManifest)\n"));
+        assert!(!rendered.contains("target other"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_python_executable() -> Result<()> {
+        let (plans, rendered) = plans_for(&[
+            "def make():\n    dist = default_python_distribution()\n    return dist.to_python_executable('testapp')\n",
+            "register_target('exe', make)",
+            "resolve_target('exe')",
+        ])?;
+
+        assert_eq!(plans.len(), 1);
+        let exe = plans[0].executable.as_ref().unwrap();
+        assert_eq!(exe.name, "testapp");
+        assert_eq!(exe.resources_policy, "in-memory-only");
+        assert!(exe.in_memory_resources > 0);
+        assert_eq!(exe.filesystem_relative_resources, 0);
+        assert_eq!(exe.cargo_profile, "debug");
+        assert_eq!(exe.cargo_features[0], "build-mode-prebuilt-artifacts");
+
+        assert!(rendered.contains("target exe (PythonExecutable)\n"));
+        assert!(rendered.contains("  cargo features: build-mode-prebuilt-artifacts "));
+
+        Ok(())
+    }
+}
@@ -13,16 +13,35 @@ This library exposes that functionality to other tools.
 
 pub mod analyze;
 pub mod app_packaging;
+pub mod atomic_output;
+pub mod benchmark;
+pub mod briefcase;
+pub mod build_budget;
+pub mod build_manifest;
+pub mod build_metrics;
+pub mod checksums;
+pub mod change_detection;
+pub mod cx_freeze;
 //pub mod distribution;
 pub mod environment;
+pub mod error_details;
+pub mod explain;
 mod licensing;
 pub mod logging;
+pub mod otlp;
+pub mod output_retention;
+pub mod path_util;
+pub mod plugin_exports;
 pub mod project_building;
 pub mod project_layout;
 pub mod projectmgmt;
 pub mod py_packaging;
+pub mod pyinstaller_spec;
 pub mod python_distributions;
 pub mod starlark;
+pub mod watch;
+pub mod webhooks;
+pub mod workspace;
 
 #[cfg(test)]
 mod testutil;
@@ -30,19 +30,38 @@ a rather effective and powerful tool.
 mod analyze;
 #[allow(unused)]
 pub mod app_packaging;
+mod atomic_output;
+mod benchmark;
+mod briefcase;
+mod build_budget;
+mod build_manifest;
+mod build_metrics;
+mod change_detection;
+mod checksums;
 mod cli;
+mod cx_freeze;
 //mod distribution;
 mod environment;
+mod error_details;
+mod explain;
 mod licensing;
 mod logging;
+mod otlp;
+mod output_retention;
+mod path_util;
+mod plugin_exports;
 mod project_building;
 mod project_layout;
 mod projectmgmt;
 mod py_packaging;
+mod pyinstaller_spec;
 mod python_distributions;
 pub mod starlark;
 #[cfg(test)]
 mod testutil;
+mod watch;
+mod webhooks;
+mod workspace;
 
 fn main() {
     std::process::exit(match cli::run_cli() {
@@ -0,0 +1,511 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Export of build traces and metrics to OpenTelemetry collectors.
+
+When an OTLP endpoint is configured, a trace of the build is sent to it at
+the end of `pyoxidizer build`. The trace has a root span covering the build,
+with a child span for evaluating the configuration file and one for each
+built target. These have child spans for the phases of the build: `resolve`
+(calling a target function), `collect` (gathering the resources of a binary),
+`compile` (compiling bytecode) and `link` (building the binary with cargo).
+Hits and misses of the Python distribution and bytecode caches are sent as
+metrics.
+
+Data is sent with the OTLP/HTTP protocol, JSON encoded, to the `/v1/traces`
+and `/v1/metrics` paths of the endpoint. The endpoint is defined by the
+standard `OTEL_EXPORTER_OTLP_ENDPOINT` environment variable or the
+`--otlp-endpoint` argument. `OTEL_EXPORTER_OTLP_HEADERS` defines extra
+request headers, e.g. for authentication, and `OTEL_SERVICE_NAME` the name
+of the reporting service.
+
+Export failures are logged and don't fail builds.
+*/
+
+use {
+    crate::build_metrics::{BuildMetrics, CacheMetrics},
+    anyhow::{anyhow, Context, Result},
+    lazy_static::lazy_static,
+    serde_json::{json, Value},
+    slog::warn,
+    std::sync::Mutex,
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Environment variable defining the base URL of the OTLP endpoint.
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Environment variable defining `key=value` headers, separated by commas.
+pub const OTLP_HEADERS_ENV: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+
+/// Environment variable defining the name of the reporting service.
+pub const SERVICE_NAME_ENV: &str = "OTEL_SERVICE_NAME";
+
+/// Service name used when `OTEL_SERVICE_NAME` isn't defined.
+pub const DEFAULT_SERVICE_NAME: &str = "pyoxidizer";
+
+/// Instrumentation scope of exported data.
+const INSTRUMENTATION_NAME: &str = "pyoxidizer.build";
+
+lazy_static! {
+    /// Spans of build phases not yet added to a trace.
+    static ref PHASE_SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+}
+
+/// Record a phase of the build which started at `start` and just ended.
+///
+/// Phases run deep in the code building targets, which has no access to the
+/// trace. Their spans become children of the next span added to the trace.
+pub fn record_build_phase(
+    name: &str,
+    start: SystemTime,
+    attributes: &[(&str, &str)],
+    error: Option<String>,
+) {
+    let span = Span::new(name, None, start, attributes, error);
+
+    PHASE_SPANS.lock().unwrap().push(span);
+}
+
+/// Run a phase of the build, recording its span.
+pub fn build_phase<T>(
+    name: &str,
+    attributes: &[(&str, &str)],
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let start = SystemTime::now();
+    let res = f();
+    record_build_phase(
+        name,
+        start,
+        attributes,
+        res.as_ref().err().map(|e| e.to_string()),
+    );
+
+    res
+}
+
+/// A span of a build trace.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub name: String,
+    pub span_id: String,
+    /// The root span of the build if not set.
+    pub parent_span_id: Option<String>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(String, String)>,
+    /// Message of the error which failed the span, if any.
+    pub error: Option<String>,
+}
+
+/// Trace of a build.
+///
+/// Spans are children of a root span covering the whole build.
+#[derive(Clone, Debug)]
+pub struct BuildTrace {
+    pub trace_id: String,
+    pub root_span_id: String,
+    pub start: SystemTime,
+    pub spans: Vec<Span>,
+}
+
+impl Span {
+    /// Construct a span which started at `start` and just ended.
+    fn new(
+        name: &str,
+        parent_span_id: Option<String>,
+        start: SystemTime,
+        attributes: &[(&str, &str)],
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            span_id: random_id(8),
+            parent_span_id,
+            start,
+            end: SystemTime::now(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            error,
+        }
+    }
+}
+
+impl Default for BuildTrace {
+    fn default() -> Self {
+        Self {
+            trace_id: random_id(16),
+            root_span_id: random_id(8),
+            start: SystemTime::now(),
+            spans: Vec::new(),
+        }
+    }
+}
+
+impl BuildTrace {
+    /// Record a span which started at `start` and just ended.
+    ///
+    /// Phases recorded by `record_build_phase()` since the previous span was
+    /// added become children of this span.
+    pub fn add_span(
+        &mut self,
+        name: &str,
+        start: SystemTime,
+        attributes: &[(&str, &str)],
+        error: Option<String>,
+    ) {
+        let span = Span::new(name, None, start, attributes, error);
+
+        for mut phase in PHASE_SPANS.lock().unwrap().drain(..) {
+            phase.parent_span_id = Some(span.span_id.clone());
+            self.spans.push(phase);
+        }
+
+        self.spans.push(span);
+    }
+
+    /// Obtain the OTLP JSON representation of this trace.
+    ///
+    /// `metrics` describe the build and define the attributes of the root
+    /// span, which ends at `end`.
+    pub fn to_otlp_json(
+        &self,
+        service_name: &str,
+        metrics: &BuildMetrics,
+        end: SystemTime,
+    ) -> Value {
+        let failed = self.spans.iter().any(|span| span.error.is_some());
+
+        let mut spans = vec![json!({
+            "traceId": self.trace_id,
+            "spanId": self.root_span_id,
+            "name": "build",
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(end),
+            "attributes": [
+                string_attribute("pyoxidizer.config_path", &metrics.config_path),
+                string_attribute("pyoxidizer.target_triple", &metrics.target_triple),
+                json!({"key": "pyoxidizer.release", "value": {"boolValue": metrics.release}}),
+            ],
+            "status": {"code": if failed { 2 } else { 1 }},
+        })];
+
+        spans.extend(self.spans.iter().map(|span| {
+            let mut status = json!({"code": 1});
+            if let Some(error) = &span.error {
+                status = json!({"code": 2, "message": error});
+            }
+
+            json!({
+                "traceId": self.trace_id,
+                "spanId": span.span_id,
+                "parentSpanId": span.parent_span_id.as_ref().unwrap_or(&self.root_span_id),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(span.end),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(k, v)| string_attribute(k, v))
+                    .collect::<Vec<_>>(),
+                "status": status,
+            })
+        }));
+
+        json!({
+            "resourceSpans": [{
+                "resource": resource(service_name, metrics),
+                "scopeSpans": [{
+                    "scope": {"name": INSTRUMENTATION_NAME},
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+}
+
+/// Obtain the OTLP JSON representation of the cache metrics of a build.
+pub fn metrics_to_otlp_json(service_name: &str, metrics: &BuildMetrics, time: SystemTime) -> Value {
+    let start = UNIX_EPOCH + Duration::from_secs(metrics.start_time);
+
+    let counter = |name: &str, description: &str, value: usize| {
+        json!({
+            "name": name,
+            "description": description,
+            "unit": "1",
+            "sum": {
+                "aggregationTemporality": 2,
+                "isMonotonic": true,
+                "dataPoints": [{
+                    "startTimeUnixNano": unix_nanos(start),
+                    "timeUnixNano": unix_nanos(time),
+                    "asInt": value.to_string(),
+                }],
+            },
+        })
+    };
+
+    let cache_counters = |cache: &str, stats: &CacheMetrics| {
+        vec![
+            counter(
+                &format!("pyoxidizer.{}_cache.hits", cache),
+                &format!("Lookups served by the {} cache", cache),
+                stats.hits,
+            ),
+            counter(
+                &format!("pyoxidizer.{}_cache.misses", cache),
+                &format!("Lookups missing from the {} cache", cache),
+                stats.misses,
+            ),
+        ]
+    };
+
+    let mut counters = cache_counters("distribution", &metrics.distribution_cache);
+    counters.extend(cache_counters("bytecode", &metrics.bytecode_cache));
+
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(service_name, metrics),
+            "scopeMetrics": [{
+                "scope": {"name": INSTRUMENTATION_NAME},
+                "metrics": counters,
+            }],
+        }],
+    })
+}
+
+/// Sends build traces and metrics to an OTLP/HTTP endpoint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OtlpExporter {
+    /// Base URL of the endpoint.
+    pub endpoint: String,
+
+    /// Extra headers of requests.
+    pub headers: Vec<(String, String)>,
+
+    /// Name of the reporting service.
+    pub service_name: String,
+}
+
+impl OtlpExporter {
+    /// Resolve the exporter from an endpoint argument and the environment.
+    ///
+    /// `endpoint` takes precedence over `OTEL_EXPORTER_OTLP_ENDPOINT`.
+    /// Returns `None` if no endpoint is defined.
+    pub fn resolve(endpoint: Option<&str>) -> Result<Option<Self>> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint.to_string(),
+            None => match std::env::var(OTLP_ENDPOINT_ENV) {
+                Ok(endpoint) if !endpoint.is_empty() => endpoint,
+                _ => return Ok(None),
+            },
+        };
+
+        let headers = match std::env::var(OTLP_HEADERS_ENV) {
+            Ok(headers) => parse_headers(&headers, OTLP_HEADERS_ENV)?,
+            Err(_) => Vec::new(),
+        };
+
+        let service_name = match std::env::var(SERVICE_NAME_ENV) {
+            Ok(name) if !name.is_empty() => name,
+            _ => DEFAULT_SERVICE_NAME.to_string(),
+        };
+
+        Ok(Some(Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            headers,
+            service_name,
+        }))
+    }
+
+    /// Export the trace and cache metrics of a build.
+    ///
+    /// Failures are logged.
+    pub fn export(&self, logger: &slog::Logger, trace: &BuildTrace, metrics: &BuildMetrics) {
+        let end = SystemTime::now();
+
+        warn!(logger, "exporting build telemetry to {}", self.endpoint);
+
+        for (path, data) in &[
+            (
+                "v1/traces",
+                trace.to_otlp_json(&self.service_name, metrics, end),
+            ),
+            (
+                "v1/metrics",
+                metrics_to_otlp_json(&self.service_name, metrics, end),
+            ),
+        ] {
+            if let Err(e) = self.post(path, data) {
+                warn!(logger, "error exporting build telemetry: {:#}", e);
+            }
+        }
+    }
+
+    fn post(&self, path: &str, data: &Value) -> Result<()> {
+        let url = format!("{}/{}", self.endpoint, path);
+
+        let mut request = reqwest::blocking::Client::new()
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(data)?);
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().with_context(|| format!("sending {}", url))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} responded {}", url, response.status()))
+        }
+    }
+}
+
+/// Parse `key=value` pairs separated by commas.
+///
+/// `source` names where the value comes from, e.g. an environment variable.
+pub fn parse_headers(value: &str, source: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(',')
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if !key.trim().is_empty() => {
+                    Ok((key.trim().to_string(), value.trim().to_string()))
+                }
+                _ => Err(anyhow!("invalid header in {}: {}", source, pair)),
+            }
+        })
+        .collect()
+}
+
+fn resource(service_name: &str, metrics: &BuildMetrics) -> Value {
+    json!({
+        "attributes": [
+            string_attribute("service.name", service_name),
+            string_attribute("service.version", &metrics.pyoxidizer_version),
+        ],
+    })
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({"key": key, "value": {"stringValue": value}})
+}
+
+/// OTLP JSON encodes 64-bit integers as strings.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0))
+        .as_nanos()
+        .to_string()
+}
+
+/// Obtain a random identifier of `size` bytes, hex encoded.
+fn random_id(size: usize) -> String {
+    hex::encode(&uuid::Uuid::new_v4().as_bytes()[0..size])
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::path::Path};
+
+    #[test]
+    fn test_parse_headers() -> Result<()> {
+        assert_eq!(parse_headers("", OTLP_HEADERS_ENV)?, vec![]);
+        assert_eq!(
+            parse_headers("api-key=secret, x-team = build=farm", OTLP_HEADERS_ENV)?,
+            vec![
+                ("api-key".to_string(), "secret".to_string()),
+                ("x-team".to_string(), "build=farm".to_string())
+            ]
+        );
+        assert!(parse_headers("api-key", OTLP_HEADERS_ENV).is_err());
+        assert!(parse_headers("=secret", OTLP_HEADERS_ENV).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_to_otlp_json() {
+        let metrics = BuildMetrics::new(Path::new("pyoxidizer.bzl"), "x86_64", true);
+
+        let mut trace = BuildTrace::default();
+        let start = SystemTime::now();
+        trace.add_span("evaluate", start, &[], None);
+        build_phase("test link", &[("pyoxidizer.target", "exe")], || Ok(())).unwrap();
+        trace.add_span(
+            "build target",
+            start,
+            &[("pyoxidizer.target", "exe")],
+            Some("oops".to_string()),
+        );
+
+        let value = trace.to_otlp_json("myservice", &metrics, SystemTime::now());
+        let resource_spans = &value["resourceSpans"][0];
+
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0]["value"]["stringValue"],
+            "myservice"
+        );
+
+        // Other tests may record build phases concurrently.
+        let spans = resource_spans["scopeSpans"][0]["spans"].as_array().unwrap();
+        let span = |name: &str| spans.iter().find(|span| span["name"] == name).unwrap();
+
+        assert_eq!(spans[0]["name"], "build");
+        assert_eq!(spans[0]["status"]["code"], 2);
+        assert_eq!(trace.trace_id.len(), 32);
+        assert_eq!(trace.root_span_id.len(), 16);
+
+        for span in &spans[1..] {
+            assert_eq!(span["traceId"], trace.trace_id.as_str());
+        }
+        assert_eq!(
+            span("evaluate")["parentSpanId"],
+            trace.root_span_id.as_str()
+        );
+        assert_eq!(span("evaluate")["status"]["code"], 1);
+        assert_eq!(
+            span("build target")["parentSpanId"],
+            trace.root_span_id.as_str()
+        );
+        assert_eq!(span("build target")["status"]["message"], "oops");
+        assert_eq!(
+            span("build target")["attributes"][0]["key"],
+            "pyoxidizer.target"
+        );
+        assert_eq!(
+            span("test link")["parentSpanId"],
+            span("build target")["spanId"]
+        );
+    }
+
+    #[test]
+    fn test_metrics_to_otlp_json() {
+        let mut metrics = BuildMetrics::new(Path::new("pyoxidizer.bzl"), "x86_64", false);
+        metrics.distribution_cache = CacheMetrics::new(3, 1);
+
+        let value = metrics_to_otlp_json("pyoxidizer", &metrics, SystemTime::now());
+        let counters = value["resourceMetrics"][0]["scopeMetrics"][0]["metrics"]
+            .as_array()
+            .unwrap();
+
+        assert_eq!(counters.len(), 4);
+        assert_eq!(counters[0]["name"], "pyoxidizer.distribution_cache.hits");
+        assert_eq!(counters[0]["sum"]["dataPoints"][0]["asInt"], "3");
+        assert_eq!(counters[1]["name"], "pyoxidizer.distribution_cache.misses");
+        assert_eq!(counters[1]["sum"]["dataPoints"][0]["asInt"], "1");
+        assert_eq!(counters[2]["name"], "pyoxidizer.bytecode_cache.hits");
+    }
+}
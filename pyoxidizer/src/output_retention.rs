@@ -0,0 +1,162 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Retention of previous build outputs.
+
+Targets are built into the same output directory on every build. When a
+retention policy is defined, the existing output directory of a target is
+moved to a directory of previous outputs of the target before building it,
+under a name ordering outputs by age. Previous outputs beyond the number to
+keep are then deleted, oldest first.
+*/
+
+use {
+    anyhow::{Context, Result},
+    slog::warn,
+    std::path::{Path, PathBuf},
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the directory holding previous outputs of targets.
+pub const PREVIOUS_OUTPUTS_DIR: &str = ".previous";
+
+/// Move the output directory of a target to its previous outputs and prune them.
+///
+/// `keep` is the number of previous outputs to keep, or `None` to keep all
+/// of them. When 0, the output directory is deleted.
+///
+/// Returns the paths of deleted previous outputs.
+pub fn rotate_output(
+    logger: &slog::Logger,
+    output_path: &Path,
+    previous_path: &Path,
+    keep: Option<usize>,
+) -> Result<Vec<PathBuf>> {
+    if output_path.exists() {
+        if keep == Some(0) {
+            std::fs::remove_dir_all(output_path)
+                .with_context(|| format!("removing {}", output_path.display()))?;
+        } else {
+            std::fs::create_dir_all(previous_path)
+                .with_context(|| format!("creating {}", previous_path.display()))?;
+
+            let archive_path = unique_archive_path(previous_path);
+            std::fs::rename(output_path, &archive_path).with_context(|| {
+                format!(
+                    "moving {} to {}",
+                    output_path.display(),
+                    archive_path.display()
+                )
+            })?;
+        }
+    }
+
+    match keep {
+        Some(keep) => prune_outputs(logger, previous_path, keep),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Delete previous outputs beyond the `keep` most recent ones.
+///
+/// Returns the paths of deleted previous outputs.
+pub fn prune_outputs(
+    logger: &slog::Logger,
+    previous_path: &Path,
+    keep: usize,
+) -> Result<Vec<PathBuf>> {
+    let mut outputs = previous_outputs(previous_path)?;
+    let count = outputs.len().saturating_sub(keep);
+    let pruned = outputs.drain(0..count).collect::<Vec<_>>();
+
+    for path in &pruned {
+        warn!(logger, "removing previous output {}", path.display());
+        std::fs::remove_dir_all(path).with_context(|| format!("removing {}", path.display()))?;
+    }
+
+    Ok(pruned)
+}
+
+/// Obtain the previous outputs in a directory, oldest first.
+pub fn previous_outputs(previous_path: &Path) -> Result<Vec<PathBuf>> {
+    if !previous_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut outputs = std::fs::read_dir(previous_path)
+        .with_context(|| format!("reading {}", previous_path.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+
+    // Names are fixed width, so they sort by age.
+    outputs.sort();
+
+    Ok(outputs)
+}
+
+/// Obtain an unused path for an output archived now.
+fn unique_archive_path(previous_path: &Path) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0))
+        .as_millis();
+
+    (0..)
+        .map(|n| previous_path.join(format!("{:016}-{:04}", millis, n)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*};
+
+    fn write_output(path: &Path, content: &str) -> Result<()> {
+        std::fs::create_dir_all(path)?;
+        std::fs::write(path.join("file"), content)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_output() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let output_path = temp_dir.path().join("exe");
+        let previous_path = temp_dir.path().join(PREVIOUS_OUTPUTS_DIR).join("exe");
+
+        // Nothing to rotate.
+        assert!(rotate_output(&logger, &output_path, &previous_path, Some(2))?.is_empty());
+        assert!(previous_outputs(&previous_path)?.is_empty());
+
+        for i in 0..3 {
+            write_output(&output_path, &format!("{}", i))?;
+            rotate_output(&logger, &output_path, &previous_path, Some(2))?;
+            assert!(!output_path.exists());
+        }
+
+        let outputs = previous_outputs(&previous_path)?;
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(std::fs::read_to_string(outputs[0].join("file"))?, "1");
+        assert_eq!(std::fs::read_to_string(outputs[1].join("file"))?, "2");
+
+        // Everything is kept without a limit.
+        write_output(&output_path, "3")?;
+        assert!(rotate_output(&logger, &output_path, &previous_path, None)?.is_empty());
+        assert_eq!(previous_outputs(&previous_path)?.len(), 3);
+
+        // Lowering the limit prunes outputs, oldest first.
+        write_output(&output_path, "4")?;
+        let pruned = rotate_output(&logger, &output_path, &previous_path, Some(0))?;
+        assert_eq!(pruned.len(), 3);
+        assert!(!output_path.exists());
+        assert!(previous_outputs(&previous_path)?.is_empty());
+
+        Ok(())
+    }
+}
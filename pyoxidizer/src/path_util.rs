@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Passing filesystem paths to other tools.
+
+Paths may contain spaces, quotes, backslashes or non-ASCII characters.
+Subprocesses receive them as separate arguments, never through a shell, and
+paths embedded in generated files are escaped for the syntax of the file.
+Paths that aren't valid Unicode can't be represented in most generated files
+and are rejected with an error rather than being silently mangled.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    std::path::Path,
+};
+
+/// Obtain the string value of a path, failing if it isn't valid Unicode.
+pub fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| {
+        anyhow!(
+            "path is not valid Unicode and can't be used: {}",
+            path.display()
+        )
+    })
+}
+
+/// Obtain a Rust string literal evaluating to a string.
+pub fn rust_str_literal(value: &str) -> String {
+    // The Debug representation of strings is a valid Rust string literal.
+    format!("{:?}", value)
+}
+
+/// Obtain a TOML basic string evaluating to a string.
+pub fn toml_str_literal(value: &str) -> String {
+    let mut res = String::with_capacity(value.len() + 2);
+    res.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04X}", c as u32)),
+            c => res.push(c),
+        }
+    }
+
+    res.push('"');
+    res
+}
+
+/// Join paths into the value of a search path environment variable like `PATH`.
+///
+/// Paths are separated by `:` on Unix and `;` on Windows.
+pub fn join_search_paths<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<String> {
+    let joined = std::env::join_paths(paths).map_err(|e| anyhow!("joining search paths: {}", e))?;
+
+    joined
+        .into_string()
+        .map_err(|value| anyhow!("search path is not valid Unicode: {:?}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::path::PathBuf};
+
+    #[test]
+    fn test_rust_str_literal() {
+        assert_eq!(
+            rust_str_literal(r"C:\Users\Jöhn Doe\packed-resources"),
+            r#""C:\\Users\\Jöhn Doe\\packed-resources""#
+        );
+        assert_eq!(rust_str_literal("my \"app\""), r#""my \"app\"""#);
+    }
+
+    #[test]
+    fn test_toml_str_literal() {
+        assert_eq!(
+            toml_str_literal(r"C:\Users\Jöhn Doe\pyembed"),
+            r#""C:\\Users\\Jöhn Doe\\pyembed""#
+        );
+        assert_eq!(
+            toml_str_literal("/home/jöhn doe/\"src\"\t"),
+            r#""/home/jöhn doe/\"src\"\t""#
+        );
+        assert_eq!(toml_str_literal("a\u{1}b"), r#""a\u0001b""#);
+    }
+
+    #[test]
+    fn test_path_str() -> Result<()> {
+        assert_eq!(
+            path_str(Path::new("/home/jöhn doe/my app"))?,
+            "/home/jöhn doe/my app"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_str_invalid() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/home/j\xf6hn"));
+        assert!(path_str(path).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_path_str_invalid() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let path = PathBuf::from(std::ffi::OsString::from_wide(&[0x43, 0x3a, 0xd800]));
+        assert!(path_str(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_join_search_paths() -> Result<()> {
+        assert_eq!(
+            join_search_paths(&[
+                PathBuf::from("/home/jöhn doe/lib"),
+                PathBuf::from("/usr/lib")
+            ])?,
+            "/home/jöhn doe/lib:/usr/lib"
+        );
+        assert!(join_search_paths(&[PathBuf::from("/home/a:b")]).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_join_search_paths() -> Result<()> {
+        assert_eq!(
+            join_search_paths(&[
+                PathBuf::from(r"C:\Users\Jöhn Doe\lib"),
+                PathBuf::from(r"C:\lib")
+            ])?,
+            r"C:\Users\Jöhn Doe\lib;C:\lib"
+        );
+        assert!(join_search_paths(&[PathBuf::from("C:\\a\"b")]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,556 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Functions exported by plugin libraries and implemented in Python.
+
+Plugin hosts call C functions exported by plugins with signatures they
+define. Exported functions are declared with C declarations, e.g.
+`double process(double sample, int channel)`, and are implemented by
+Python callables. The Rust functions of the library forward calls to
+these callables via `pyembed::PythonPlugin`.
+
+Only scalar types, strings and opaque pointers are supported. Strings are
+passed to Python as `str` and pointers as `int`.
+
+C names may be Rust keywords or clash with items of the generated library,
+so Rust functions and parameters are named after C names with a prefix.
+Functions are exported under their C name via `#[export_name]`.
+*/
+
+use anyhow::{anyhow, Result};
+
+/// A C type of an argument or return value of an exported function.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CType {
+    Void,
+    Bool,
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Size,
+    Float,
+    Double,
+    /// `const char *`, a NUL terminated string.
+    String,
+    /// `void *`, an opaque pointer.
+    Pointer,
+    /// `const void *`, an opaque pointer.
+    ConstPointer,
+}
+
+/// C types and their names.
+const C_TYPES: &[(&str, CType)] = &[
+    ("void", CType::Void),
+    ("bool", CType::Bool),
+    ("int", CType::Int),
+    ("unsigned int", CType::UnsignedInt),
+    ("long", CType::Long),
+    ("unsigned long", CType::UnsignedLong),
+    ("long long", CType::LongLong),
+    ("unsigned long long", CType::UnsignedLongLong),
+    ("int8_t", CType::Int8),
+    ("int16_t", CType::Int16),
+    ("int32_t", CType::Int32),
+    ("int64_t", CType::Int64),
+    ("uint8_t", CType::UInt8),
+    ("uint16_t", CType::UInt16),
+    ("uint32_t", CType::UInt32),
+    ("uint64_t", CType::UInt64),
+    ("size_t", CType::Size),
+    ("float", CType::Float),
+    ("double", CType::Double),
+    ("const char *", CType::String),
+    ("void *", CType::Pointer),
+    ("const void *", CType::ConstPointer),
+];
+
+impl CType {
+    /// Resolve a type from its C name, e.g. `unsigned int`.
+    pub fn from_c_name(name: &str) -> Option<CType> {
+        C_TYPES
+            .iter()
+            .find(|(c_name, _)| *c_name == name)
+            .map(|(_, t)| *t)
+    }
+
+    /// The C name of the type.
+    pub fn c_name(self) -> &'static str {
+        C_TYPES.iter().find(|(_, t)| *t == self).unwrap().0
+    }
+
+    /// The Rust type of the type in the generated library.
+    fn rust_type(self) -> &'static str {
+        match self {
+            CType::Void => "()",
+            CType::Bool => "bool",
+            CType::Int => "c_int",
+            CType::UnsignedInt => "c_uint",
+            CType::Long => "c_long",
+            CType::UnsignedLong => "c_ulong",
+            CType::LongLong => "c_longlong",
+            CType::UnsignedLongLong => "c_ulonglong",
+            CType::Int8 => "i8",
+            CType::Int16 => "i16",
+            CType::Int32 => "i32",
+            CType::Int64 => "i64",
+            CType::UInt8 => "u8",
+            CType::UInt16 => "u16",
+            CType::UInt32 => "u32",
+            CType::UInt64 => "u64",
+            CType::Size => "usize",
+            CType::Float => "f32",
+            CType::Double => "f64",
+            CType::String => "*const c_char",
+            CType::Pointer => "*mut c_void",
+            CType::ConstPointer => "*const c_void",
+        }
+    }
+
+    /// The `pyembed::PluginType` values of the type are converted to.
+    fn plugin_type(self) -> &'static str {
+        match self {
+            CType::Void => "Void",
+            CType::Bool => "Bool",
+            CType::Int | CType::Long | CType::LongLong => "Int",
+            CType::Int8 | CType::Int16 | CType::Int32 | CType::Int64 => "Int",
+            CType::UnsignedInt | CType::UnsignedLong | CType::UnsignedLongLong => "UInt",
+            CType::UInt8 | CType::UInt16 | CType::UInt32 | CType::UInt64 | CType::Size => "UInt",
+            CType::Float | CType::Double => "Float",
+            CType::String => "String",
+            CType::Pointer | CType::ConstPointer => "Pointer",
+        }
+    }
+
+    /// Rust expression converting an argument to a `pyembed::PluginValue`.
+    fn rust_argument(self, name: &str) -> String {
+        match self.plugin_type() {
+            "Bool" => format!("PluginValue::Bool({})", name),
+            "Int" => format!("PluginValue::Int({} as i64)", name),
+            "UInt" => format!("PluginValue::UInt({} as u64)", name),
+            "Float" => format!("PluginValue::Float({} as f64)", name),
+            "String" => format!("string_arg({})", name),
+            _ => format!("PluginValue::Pointer({} as usize)", name),
+        }
+    }
+
+    /// Rust expression converting a `pyembed::PluginValue` to the type.
+    fn rust_return(self, value: &str) -> String {
+        match self.plugin_type() {
+            "Void" => format!("{};", value),
+            "Bool" => format!("{}.as_bool()", value),
+            "Int" => format!("{}.as_i64() as {}", value, self.rust_type()),
+            "UInt" => format!("{}.as_u64() as {}", value, self.rust_type()),
+            "Float" => format!("{}.as_f64() as {}", value, self.rust_type()),
+            _ => format!("{}.as_pointer() as {}", value, self.rust_type()),
+        }
+    }
+}
+
+/// A function exported by a plugin library.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PluginExport {
+    /// Name of the exported symbol.
+    pub name: String,
+
+    /// Names and types of arguments.
+    pub arguments: Vec<(String, CType)>,
+
+    /// Type of the returned value.
+    pub returns: CType,
+
+    /// Python callable implementing the function, as `module:function`.
+    pub callable: String,
+}
+
+/// Keywords of C, which can't name functions or arguments.
+const C_KEYWORDS: &[&str] = &[
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_Bool",
+    "_Complex",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+];
+
+fn is_identifier(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with(|c: char| c.is_ascii_digit())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The name of the Rust parameter of an argument.
+fn rust_argument_name(name: &str) -> String {
+    format!("arg_{}", name)
+}
+
+/// Parse a C declaration of a name, e.g. `const char *path`, into the name and type.
+fn parse_named_type(declaration: &str) -> Result<(String, CType)> {
+    let declaration = declaration.replace('*', " * ");
+    let mut tokens = declaration.split_whitespace().collect::<Vec<_>>();
+
+    let name = match tokens.pop() {
+        Some(name) if is_identifier(name) && !tokens.is_empty() => name,
+        _ => return Err(anyhow!("expected a type followed by a name")),
+    };
+
+    if C_KEYWORDS.contains(&name) {
+        return Err(anyhow!(
+            "{} is a C keyword and can't be used as a name",
+            name
+        ));
+    }
+
+    let c_name = tokens.join(" ");
+    let c_type = CType::from_c_name(&c_name).ok_or_else(|| {
+        anyhow!(
+            "unsupported type {}; supported types are {}",
+            c_name,
+            C_TYPES
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+
+    Ok((name.to_string(), c_type))
+}
+
+impl PluginExport {
+    /// Parse the C declaration of an exported function.
+    ///
+    /// `callable` names the Python callable implementing it, as
+    /// `module:function`.
+    pub fn parse(declaration: &str, callable: &str) -> Result<PluginExport> {
+        let error = |e: anyhow::Error| anyhow!("invalid declaration {}: {}", declaration, e);
+
+        let trimmed = declaration.trim().trim_end_matches(';').trim_end();
+        let (head, arguments) = match (trimmed.find('('), trimmed.ends_with(')')) {
+            (Some(index), true) => (&trimmed[..index], &trimmed[index + 1..trimmed.len() - 1]),
+            _ => {
+                return Err(error(anyhow!(
+                    "expected a function declaration, e.g. double process(double sample)"
+                )))
+            }
+        };
+
+        let (name, returns) = parse_named_type(head).map_err(error)?;
+
+        // Names of the C API of the Python interpreter linked in the library,
+        // e.g. Py_Initialize or PyInit_module.
+        let unprefixed = name.trim_start_matches('_');
+        if unprefixed.starts_with("Py")
+            && unprefixed[2..].starts_with(|c: char| c.is_ascii_uppercase() || c == '_')
+        {
+            return Err(error(anyhow!(
+                "{} is reserved by the C API of the Python interpreter",
+                name
+            )));
+        }
+
+        let arguments = if arguments.trim().is_empty() || arguments.trim() == "void" {
+            Vec::new()
+        } else {
+            arguments
+                .split(',')
+                .map(|argument| {
+                    let (name, c_type) = parse_named_type(argument)?;
+
+                    if c_type == CType::Void {
+                        Err(anyhow!("argument {} can't be void", name))
+                    } else {
+                        Ok((name, c_type))
+                    }
+                })
+                .collect::<Result<Vec<_>>>()
+                .map_err(error)?
+        };
+
+        if returns == CType::String {
+            return Err(error(anyhow!(
+                "functions can't return strings, as their memory would have no owner"
+            )));
+        }
+
+        for (i, (argument, _)) in arguments.iter().enumerate() {
+            if *argument == name || arguments[..i].iter().any(|(a, _)| a == argument) {
+                return Err(error(anyhow!("{} is declared more than once", argument)));
+            }
+        }
+
+        let mut parts = callable.splitn(2, ':');
+        let module = parts.next().unwrap_or_default();
+        let function = parts.next().unwrap_or_default();
+
+        if !module.split('.').all(is_identifier) || !is_identifier(function) {
+            return Err(anyhow!(
+                "invalid callable {} implementing {}: expected module:function",
+                callable,
+                name
+            ));
+        }
+
+        Ok(PluginExport {
+            name,
+            arguments,
+            returns,
+            callable: callable.to_string(),
+        })
+    }
+
+    /// The C declaration of the function, normalized.
+    pub fn c_declaration(&self) -> String {
+        let c_type = |t: CType, name: &str| {
+            if t.c_name().ends_with('*') {
+                format!("{}{}", t.c_name(), name)
+            } else {
+                format!("{} {}", t.c_name(), name)
+            }
+        };
+
+        format!(
+            "{}({})",
+            c_type(self.returns, &self.name),
+            if self.arguments.is_empty() {
+                "void".to_string()
+            } else {
+                self.arguments
+                    .iter()
+                    .map(|(name, t)| c_type(*t, name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        )
+    }
+
+    /// The name of the Rust function, e.g. `export_process`.
+    ///
+    /// The function is exported under the C name of the function.
+    pub fn rust_name(&self) -> String {
+        format!("export_{}", self.name)
+    }
+
+    /// The parameters of the Rust function, e.g. `arg_sample: f64, arg_channel: c_int`.
+    pub fn rust_parameters(&self) -> String {
+        self.arguments
+            .iter()
+            .map(|(name, t)| format!("{}: {}", rust_argument_name(name), t.rust_type()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The return type of the Rust function, e.g. ` -> f64`.
+    pub fn rust_returns(&self) -> String {
+        match self.returns {
+            CType::Void => "".to_string(),
+            t => format!(" -> {}", t.rust_type()),
+        }
+    }
+
+    /// The body of the Rust function, forwarding calls to Python.
+    pub fn rust_body(&self) -> String {
+        self.returns.rust_return(&format!(
+            "call(\"{}\", &[{}], PluginType::{})",
+            self.name,
+            self.arguments
+                .iter()
+                .map(|(name, t)| t.rust_argument(&rust_argument_name(name)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.returns.plugin_type()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_export() -> Result<()> {
+        let export = PluginExport::parse(
+            "double process(double sample, int channel)",
+            "plugin.audio:process",
+        )?;
+        assert_eq!(export.name, "process");
+        assert_eq!(
+            export.arguments,
+            vec![
+                ("sample".to_string(), CType::Double),
+                ("channel".to_string(), CType::Int)
+            ]
+        );
+        assert_eq!(export.returns, CType::Double);
+        assert_eq!(
+            export.c_declaration(),
+            "double process(double sample, int channel)"
+        );
+        assert_eq!(export.rust_name(), "export_process");
+        assert_eq!(
+            export.rust_parameters(),
+            "arg_sample: f64, arg_channel: c_int"
+        );
+        assert_eq!(export.rust_returns(), " -> f64");
+        assert_eq!(
+            export.rust_body(),
+            "call(\"process\", &[PluginValue::Float(arg_sample as f64), PluginValue::Int(arg_channel as i64)], PluginType::Float).as_f64() as f64"
+        );
+
+        let export = PluginExport::parse(
+            "void *  open_node( const char* path,void*context , unsigned long long flags);",
+            "plugin:open_node",
+        )?;
+        assert_eq!(
+            export.c_declaration(),
+            "void *open_node(const char *path, void *context, unsigned long long flags)"
+        );
+        assert_eq!(
+            export.rust_parameters(),
+            "arg_path: *const c_char, arg_context: *mut c_void, arg_flags: c_ulonglong"
+        );
+        assert_eq!(
+            export.rust_body(),
+            "call(\"open_node\", &[string_arg(arg_path), PluginValue::Pointer(arg_context as usize), PluginValue::UInt(arg_flags as u64)], PluginType::Pointer).as_pointer() as *mut c_void"
+        );
+
+        let export = PluginExport::parse("void reset(void)", "plugin:reset")?;
+        assert_eq!(export.c_declaration(), "void reset(void)");
+        assert_eq!(export.rust_returns(), "");
+        assert_eq!(
+            export.rust_body(),
+            "call(\"reset\", &[], PluginType::Void);"
+        );
+
+        // Names of Rust keywords and of items of the generated library are
+        // prefixed.
+        let export = PluginExport::parse(
+            "int call(const char *string_arg, int type, int fn, int match)",
+            "plugin:call",
+        )?;
+        assert_eq!(export.rust_name(), "export_call");
+        assert_eq!(
+            export.rust_parameters(),
+            "arg_string_arg: *const c_char, arg_type: c_int, arg_fn: c_int, arg_match: c_int"
+        );
+        assert_eq!(
+            export.rust_body(),
+            "call(\"call\", &[string_arg(arg_string_arg), PluginValue::Int(arg_type as i64), PluginValue::Int(arg_fn as i64), PluginValue::Int(arg_match as i64)], PluginType::Int).as_i64() as c_int"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_export_errors() {
+        for (declaration, callable, message) in &[
+            (
+                "process",
+                "plugin:process",
+                "invalid declaration process: expected a function declaration, e.g. double process(double sample)",
+            ),
+            (
+                "const char *name(void)",
+                "plugin:name",
+                "invalid declaration const char *name(void): functions can't return strings, as their memory would have no owner",
+            ),
+            (
+                "int add(int a, int)",
+                "plugin:add",
+                "invalid declaration int add(int a, int): expected a type followed by a name",
+            ),
+            (
+                "int add(int a, int a)",
+                "plugin:add",
+                "invalid declaration int add(int a, int a): a is declared more than once",
+            ),
+            (
+                "void set(void value)",
+                "plugin:set",
+                "invalid declaration void set(void value): argument value can't be void",
+            ),
+            (
+                "void process(char c)",
+                "plugin:process",
+                "invalid declaration void process(char c): unsupported type char; supported types are void, bool, int, unsigned int, long, unsigned long, long long, unsigned long long, int8_t, int16_t, int32_t, int64_t, uint8_t, uint16_t, uint32_t, uint64_t, size_t, float, double, const char *, void *, const void *",
+            ),
+            (
+                "int int(int a)",
+                "plugin:int",
+                "invalid declaration int int(int a): int is a C keyword and can't be used as a name",
+            ),
+            (
+                "int add(int a, int default)",
+                "plugin:add",
+                "invalid declaration int add(int a, int default): default is a C keyword and can't be used as a name",
+            ),
+            (
+                "void Py_Initialize(void)",
+                "plugin:initialize",
+                "invalid declaration void Py_Initialize(void): Py_Initialize is reserved by the C API of the Python interpreter",
+            ),
+            (
+                "void process(void)",
+                "plugin.process",
+                "invalid callable plugin.process implementing process: expected module:function",
+            ),
+        ] {
+            assert_eq!(
+                PluginExport::parse(declaration, callable)
+                    .unwrap_err()
+                    .to_string(),
+                *message
+            );
+        }
+    }
+}
@@ -3,20 +3,65 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    crate::app_packaging::resource::is_executable,
+    crate::atomic_output::write_file_atomic,
+    crate::build_budget::run_within_budget,
     crate::environment::{canonicalize_path, MINIMUM_RUST_VERSION},
-    crate::project_layout::initialize_project,
-    crate::py_packaging::binary::{EmbeddedPythonBinaryData, PythonBinaryBuilder},
+    crate::otlp::build_phase,
+    crate::project_layout::{
+        bundle_lib_rs, initialize_bundle_project, initialize_library_project, initialize_project,
+    },
+    crate::py_packaging::binary::{
+        EmbeddedPythonBinaryData, EmbeddedPythonBinaryPaths, EmbeddedPythonResourcesData,
+        PythonBinaryBuilder,
+    },
     crate::starlark::eval::{eval_starlark_config_file, EvalResult},
     crate::starlark::target::ResolvedTarget,
     anyhow::{anyhow, Context, Result},
     slog::warn,
+    std::collections::BTreeMap,
+    std::convert::TryFrom,
     std::env,
+    std::ffi::OsString,
     std::fs::create_dir_all,
     std::path::{Path, PathBuf},
 };
 
 pub const HOST: &str = env!("HOST");
 
+/// Ensure the Rust standard library for a target is installed.
+///
+/// Building for another platform than the build machine requires installing
+/// the standard library for it, which cargo reports with a confusing error
+/// otherwise.
+fn ensure_rust_target(target: &str) -> Result<()> {
+    if target == HOST {
+        return Ok(());
+    }
+
+    let output = std::process::Command::new("rustc")
+        .args(&["--print", "sysroot"])
+        .output()
+        .context("running rustc")?;
+
+    // Let cargo report problems with the toolchain itself.
+    if !output.status.success() {
+        return Ok(());
+    }
+
+    let sysroot = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+
+    if !sysroot.join("lib").join("rustlib").join(target).is_dir() {
+        return Err(anyhow!(
+            "Rust standard library for {} is not installed; install it with `rustup target add {}`",
+            target,
+            target
+        ));
+    }
+
+    Ok(())
+}
+
 /// Find a pyoxidizer.toml configuration file by walking directory ancestry.
 pub fn find_pyoxidizer_config_file(start_dir: &Path) -> Option<PathBuf> {
     for test_dir in start_dir.ancestors() {
@@ -83,27 +128,122 @@ pub struct BuiltExecutable {
     pub binary_data: EmbeddedPythonBinaryData,
 }
 
-/// Build an executable embedding Python using an existing Rust project.
+/// Kinds of libraries embedding Python with a C API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CLibraryKind {
+    /// A static library, e.g. `libmyapp.a`.
+    Static,
+    /// A dynamic library, e.g. `libmyapp.so`.
+    Dynamic,
+}
+
+impl CLibraryKind {
+    /// Cargo crate type of libraries of this kind.
+    pub fn crate_type(self) -> &'static str {
+        match self {
+            CLibraryKind::Static => "staticlib",
+            CLibraryKind::Dynamic => "cdylib",
+        }
+    }
+
+    /// Name of the file of a library of this kind built for a target triple.
+    pub fn filename(self, name: &str, target: &str) -> String {
+        match self {
+            CLibraryKind::Static if target.contains("pc-windows-msvc") => format!("{}.lib", name),
+            CLibraryKind::Static => format!("lib{}.a", name),
+            CLibraryKind::Dynamic if target.contains("pc-windows") => format!("{}.dll", name),
+            CLibraryKind::Dynamic if target.contains("apple") => format!("lib{}.dylib", name),
+            CLibraryKind::Dynamic => format!("lib{}.so", name),
+        }
+    }
+}
+
+impl TryFrom<&str> for CLibraryKind {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "static" => Ok(CLibraryKind::Static),
+            "dynamic" => Ok(CLibraryKind::Dynamic),
+            _ => Err(format!(
+                "library kind must be 'static' or 'dynamic': got {}",
+                value
+            )),
+        }
+    }
+}
+
+/// Holds results from building a library.
+pub struct BuiltLibrary {
+    /// Files of the built library, keyed by file name.
+    ///
+    /// Besides the library, this includes the import library of DLLs.
+    pub files: BTreeMap<String, Vec<u8>>,
+
+    /// Holds state generated from building.
+    pub binary_data: EmbeddedPythonBinaryData,
+}
+
+/// Holds results from building an extension module bundling an application.
+pub struct BuiltBundle {
+    /// Content of the extension module.
+    pub data: Vec<u8>,
+
+    /// Resources embedded in the extension module and extra files to install.
+    pub resources: EmbeddedPythonResourcesData,
+}
+
+/// Obtain the cargo features to build an executable with.
+pub fn cargo_features(exe: &dyn PythonBinaryBuilder) -> Vec<&'static str> {
+    let mut features = vec!["build-mode-prebuilt-artifacts"];
+
+    // If we have a real libpython, let cpython crate link against it. Otherwise
+    // leave symbols unresolved, as we'll provide them.
+    features.push(if exe.links_shared_libpython() {
+        "cpython-link-default"
+    } else {
+        "cpython-link-unresolved-static"
+    });
+
+    if exe.requires_jemalloc() {
+        features.push("jemalloc");
+    }
+
+    features
+}
+
+/// Run `cargo build` in a Rust project embedding Python.
 ///
-/// The path to the produced executable is returned.
+/// `artifact_args` select what is built, e.g. `--bin <name>`. Returns the
+/// data embedded in the built artifact, the paths it was written to and the
+/// directory containing built artifacts.
 #[allow(clippy::too_many_arguments)]
-pub fn build_executable_with_rust_project(
+fn cargo_build_embedding_python(
     logger: &slog::Logger,
     project_path: &Path,
-    bin_name: &str,
+    artifact_args: &[&str],
     exe: &dyn PythonBinaryBuilder,
     build_path: &Path,
     artifacts_path: &Path,
     target: &str,
     opt_level: &str,
     release: bool,
-) -> Result<BuiltExecutable> {
+    bytecode_compiler_jobs: usize,
+    bytecode_compiler_in_process: bool,
+    bytecode_cache_path: Option<&Path>,
+) -> Result<(EmbeddedPythonBinaryData, EmbeddedPythonBinaryPaths, PathBuf)> {
     create_dir_all(&artifacts_path)
         .with_context(|| "creating directory for PyOxidizer build artifacts")?;
 
     // Derive and write the artifacts needed to build a binary embedding Python.
-    let embedded_data = exe.as_embedded_python_binary_data(logger, opt_level)?;
-    embedded_data.write_files(&artifacts_path)?;
+    let embedded_data = exe.as_embedded_python_binary_data(
+        logger,
+        opt_level,
+        bytecode_compiler_jobs,
+        bytecode_compiler_in_process,
+        bytecode_cache_path,
+    )?;
+    let embedded_paths = embedded_data.write_files(&artifacts_path)?;
 
     let rust_version = rustc_version::version()?;
     if rust_version.lt(&MINIMUM_RUST_VERSION) {
@@ -114,6 +254,7 @@ pub fn build_executable_with_rust_project(
         ));
     }
     warn!(logger, "building with Rust {}", rust_version);
+    ensure_rust_target(target)?;
 
     let target_base_path = build_path.join("target");
     let target_triple_base_path =
@@ -121,93 +262,148 @@ pub fn build_executable_with_rust_project(
             .join(target)
             .join(if release { "release" } else { "debug" });
 
-    let mut args = Vec::new();
-    args.push("build");
-    args.push("--target");
-    args.push(target);
+    let link_args = if let Some(manifest_path) = &embedded_paths.windows_manifest {
+        embedded_data.windows_manifest.linker_args(manifest_path)
+    } else {
+        vec![]
+    };
 
-    let target_dir = target_base_path.display().to_string();
-    args.push("--target-dir");
-    args.push(&target_dir);
+    // `cargo rustc` passes extra flags to the final binary only. Unlike
+    // RUSTFLAGS, these may contain paths with spaces.
+    let subcommand = if link_args.is_empty() {
+        "build"
+    } else {
+        "rustc"
+    };
 
-    args.push("--bin");
-    args.push(bin_name);
+    // Paths are passed as is, without going through a shell or a lossy
+    // conversion to strings.
+    let mut args: Vec<OsString> = Vec::new();
+    args.push(subcommand.into());
+    args.push("--target".into());
+    args.push(target.into());
 
-    if release {
-        args.push("--release");
+    args.push("--target-dir".into());
+    args.push(target_base_path.clone().into_os_string());
+
+    for arg in artifact_args {
+        args.push(arg.into());
     }
 
-    args.push("--no-default-features");
-    let mut features = vec!["build-mode-prebuilt-artifacts"];
+    if release {
+        args.push("--release".into());
+    }
 
-    // If we have a real libpython, let cpython crate link against it. Otherwise
-    // leave symbols unresolved, as we'll provide them.
-    features.push(if embedded_data.linking_info.libpython_filename.is_some() {
-        "cpython-link-default"
-    } else {
-        "cpython-link-unresolved-static"
-    });
+    args.push("--no-default-features".into());
+    let features = cargo_features(exe).join(" ");
 
-    if exe.requires_jemalloc() {
-        features.push("jemalloc");
+    if !features.is_empty() {
+        args.push("--features".into());
+        args.push(features.into());
     }
 
-    let features = features.join(" ");
+    if !link_args.is_empty() {
+        args.push("--".into());
 
-    if !features.is_empty() {
-        args.push("--features");
-        args.push(&features);
+        for arg in &link_args {
+            args.push("-C".into());
+            args.push(format!("link-arg={}", arg).into());
+        }
     }
 
-    let mut envs = Vec::new();
+    let mut envs: Vec<(&str, OsString)> = Vec::new();
     envs.push((
         "PYOXIDIZER_ARTIFACT_DIR",
-        artifacts_path.display().to_string(),
+        artifacts_path.as_os_str().to_os_string(),
     ));
-    envs.push(("PYOXIDIZER_REUSE_ARTIFACTS", "1".to_string()));
+    envs.push(("PYOXIDIZER_REUSE_ARTIFACTS", "1".into()));
 
     // Set PYTHON_SYS_EXECUTABLE so python3-sys uses our distribution's Python to configure
-    // itself.
+    // itself. When building for another platform, this is the Python of a distribution
+    // of the same version for the build machine.
     let python_exe_path = exe.python_exe_path();
     envs.push((
         "PYTHON_SYS_EXECUTABLE",
-        python_exe_path.display().to_string(),
+        python_exe_path.as_os_str().to_os_string(),
     ));
 
     // If linking against an existing dynamic library on Windows, add the path to that
     // library to an environment variable so link.exe can find it.
     if let Some(libpython_filename) = &embedded_data.linking_info.libpython_filename {
-        if cfg!(windows) {
+        if target.contains("pc-windows") {
             let libpython_dir = libpython_filename
                 .parent()
                 .ok_or_else(|| anyhow!("unable to find parent directory of python DLL"))?;
 
+            let mut lib_paths = match std::env::var_os("LIB") {
+                Some(lib) => std::env::split_paths(&lib).collect::<Vec<_>>(),
+                None => Vec::new(),
+            };
+            lib_paths.push(libpython_dir.to_path_buf());
+
             envs.push((
                 "LIB",
-                if let Ok(lib) = std::env::var("LIB") {
-                    format!("{};{}", lib, libpython_dir.display())
-                } else {
-                    format!("{}", libpython_dir.display())
-                },
+                std::env::join_paths(lib_paths).context("joining LIB paths")?,
             ));
         }
     }
 
     // static-nobundle link kind requires nightly Rust compiler until
     // https://github.com/rust-lang/rust/issues/37403 is resolved.
-    if cfg!(windows) {
-        envs.push(("RUSTC_BOOTSTRAP", "1".to_string()));
+    if target.contains("pc-windows") {
+        envs.push(("RUSTC_BOOTSTRAP", "1".into()));
     }
 
-    let status = std::process::Command::new("cargo")
-        .args(args)
-        .current_dir(&project_path)
-        .envs(envs)
-        .status()?;
+    build_phase("link", &[], || {
+        let status = run_within_budget(
+            std::process::Command::new("cargo")
+                .args(args)
+                .current_dir(&project_path)
+                .envs(envs),
+        )?;
 
-    if !status.success() {
-        return Err(anyhow!("cargo build failed"));
-    }
+        if !status.success() {
+            return Err(anyhow!("cargo build failed"));
+        }
+
+        Ok(())
+    })?;
+
+    Ok((embedded_data, embedded_paths, target_triple_base_path))
+}
+
+/// Build an executable embedding Python using an existing Rust project.
+///
+/// The path to the produced executable is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn build_executable_with_rust_project(
+    logger: &slog::Logger,
+    project_path: &Path,
+    bin_name: &str,
+    exe: &dyn PythonBinaryBuilder,
+    build_path: &Path,
+    artifacts_path: &Path,
+    target: &str,
+    opt_level: &str,
+    release: bool,
+    bytecode_compiler_jobs: usize,
+    bytecode_compiler_in_process: bool,
+    bytecode_cache_path: Option<&Path>,
+) -> Result<BuiltExecutable> {
+    let (embedded_data, embedded_paths, target_triple_base_path) = cargo_build_embedding_python(
+        logger,
+        project_path,
+        &["--bin", bin_name],
+        exe,
+        build_path,
+        artifacts_path,
+        target,
+        opt_level,
+        release,
+        bytecode_compiler_jobs,
+        bytecode_compiler_in_process,
+        bytecode_cache_path,
+    )?;
 
     let exe_name = if target.contains("pc-windows") {
         format!("{}.exe", bin_name)
@@ -221,6 +417,13 @@ pub fn build_executable_with_rust_project(
         return Err(anyhow!("{} does not exist", exe_path.display()));
     }
 
+    if let (Some(signing), Some(entitlements_path)) = (
+        &embedded_data.macos_signing,
+        &embedded_paths.macos_entitlements,
+    ) {
+        signing.sign(logger, &exe_path, entitlements_path)?;
+    }
+
     let exe_data = std::fs::read(&exe_path)?;
     let exe_name = exe_path.file_name().unwrap().to_string_lossy().to_string();
 
@@ -235,6 +438,7 @@ pub fn build_executable_with_rust_project(
 /// Build a Python executable using a temporary Rust project.
 ///
 /// Returns the binary data constituting the built executable.
+#[allow(clippy::too_many_arguments)]
 pub fn build_python_executable(
     logger: &slog::Logger,
     bin_name: &str,
@@ -242,6 +446,9 @@ pub fn build_python_executable(
     target: &str,
     opt_level: &str,
     release: bool,
+    bytecode_compiler_jobs: usize,
+    bytecode_compiler_in_process: bool,
+    bytecode_cache_path: Option<&Path>,
 ) -> Result<BuiltExecutable> {
     let env = crate::environment::resolve_environment()?;
     let pyembed_location = env.as_pyembed_location();
@@ -265,6 +472,9 @@ pub fn build_python_executable(
         target,
         opt_level,
         release,
+        bytecode_compiler_jobs,
+        bytecode_compiler_in_process,
+        bytecode_cache_path,
     )?;
 
     // Blank out the path since it is in the temporary directory.
@@ -273,6 +483,164 @@ pub fn build_python_executable(
     Ok(build)
 }
 
+/// Build a library embedding Python with a C API using a temporary Rust project.
+///
+/// `name` is the name of the library, without prefix or extension. `lib_rs`
+/// is the source of the library, defining its C API.
+#[allow(clippy::too_many_arguments)]
+pub fn build_python_library(
+    logger: &slog::Logger,
+    name: &str,
+    kind: CLibraryKind,
+    lib_rs: &str,
+    exe: &dyn PythonBinaryBuilder,
+    target: &str,
+    opt_level: &str,
+    release: bool,
+    bytecode_compiler_jobs: usize,
+    bytecode_compiler_in_process: bool,
+    bytecode_cache_path: Option<&Path>,
+) -> Result<BuiltLibrary> {
+    let env = crate::environment::resolve_environment()?;
+    let pyembed_location = env.as_pyembed_location();
+
+    let temp_dir = tempdir::TempDir::new("pyoxidizer")?;
+
+    // Directory needs to have name of project.
+    let project_path = temp_dir.path().join(name);
+    let build_path = temp_dir.path().join("build");
+    let artifacts_path = temp_dir.path().join("artifacts");
+
+    initialize_library_project(&project_path, &pyembed_location, kind.crate_type(), lib_rs)?;
+
+    let (binary_data, _, target_triple_base_path) = cargo_build_embedding_python(
+        logger,
+        &project_path,
+        &["--lib"],
+        exe,
+        &build_path,
+        &artifacts_path,
+        target,
+        opt_level,
+        release,
+        bytecode_compiler_jobs,
+        bytecode_compiler_in_process,
+        bytecode_cache_path,
+    )?;
+
+    let filename = kind.filename(name, target);
+    let mut filenames = vec![filename.clone()];
+    if kind == CLibraryKind::Dynamic && target.contains("pc-windows-msvc") {
+        filenames.push(format!("{}.lib", filename));
+    }
+
+    let mut files = BTreeMap::new();
+    for filename in filenames {
+        let path = target_triple_base_path.join(&filename);
+
+        if !path.exists() {
+            return Err(anyhow!("{} does not exist", path.display()));
+        }
+
+        files.insert(filename, std::fs::read(&path)?);
+    }
+
+    Ok(BuiltLibrary { files, binary_data })
+}
+
+/// Build an extension module bundling the resources of an executable.
+///
+/// The extension module is built with a temporary Rust project. Unlike
+/// libraries embedding Python, it doesn't link libpython: it is loaded by
+/// an existing interpreter of the same Python version as the executable.
+#[allow(clippy::too_many_arguments)]
+pub fn build_python_bundle(
+    logger: &slog::Logger,
+    name: &str,
+    exe: &dyn PythonBinaryBuilder,
+    target: &str,
+    release: bool,
+    bytecode_compiler_jobs: usize,
+    bytecode_compiler_in_process: bool,
+    bytecode_cache_path: Option<&Path>,
+) -> Result<BuiltBundle> {
+    let env = crate::environment::resolve_environment()?;
+    let pyembed_location = env.as_pyembed_location();
+
+    let temp_dir = tempdir::TempDir::new("pyoxidizer")?;
+
+    // Directory needs to have name of project.
+    let project_path = temp_dir.path().join(name);
+    let build_path = temp_dir.path().join("build");
+    let artifacts_path = temp_dir.path().join("artifacts");
+
+    create_dir_all(&artifacts_path)
+        .with_context(|| "creating directory for PyOxidizer build artifacts")?;
+
+    let resources = exe.as_embedded_python_resources_data(
+        logger,
+        bytecode_compiler_jobs,
+        bytecode_compiler_in_process,
+        bytecode_cache_path,
+    )?;
+    let resources_path = artifacts_path.join("packed-resources");
+    std::fs::write(&resources_path, &resources.resources.resources)?;
+
+    let python_version = exe
+        .python_version()
+        .split('.')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(".");
+
+    initialize_bundle_project(
+        &project_path,
+        &pyembed_location,
+        &bundle_lib_rs(name, &python_version)?,
+    )?;
+
+    let target_base_path = build_path.join("target");
+
+    let mut args: Vec<OsString> = vec![
+        "build".into(),
+        "--lib".into(),
+        "--target".into(),
+        target.into(),
+        "--target-dir".into(),
+        target_base_path.clone().into_os_string(),
+    ];
+
+    if release {
+        args.push("--release".into());
+    }
+
+    warn!(logger, "building {} extension module", name);
+    ensure_rust_target(target)?;
+
+    // python3-sys configures itself with the distribution's Python, e.g.
+    // to find the library defining Python symbols on Windows.
+    let status = run_within_budget(
+        std::process::Command::new("cargo")
+            .args(args)
+            .current_dir(&project_path)
+            .env("PYOXIDIZER_PACKED_RESOURCES", &resources_path)
+            .env("PYTHON_SYS_EXECUTABLE", exe.python_exe_path()),
+    )?;
+
+    if !status.success() {
+        return Err(anyhow!("cargo build failed"));
+    }
+
+    let path = target_base_path
+        .join(target)
+        .join(if release { "release" } else { "debug" })
+        .join(CLibraryKind::Dynamic.filename(name, target));
+
+    let data = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+    Ok(BuiltBundle { data, resources })
+}
+
 /// Build artifacts needed by the pyembed crate.
 ///
 /// This will resolve `resolve_target` or the default then build it. Built
@@ -306,6 +674,9 @@ pub fn build_pyembed_artifacts(
             None
         },
         true,
+        false,
+        false,
+        true,
     )?;
 
     // TODO should we honor only the specified target if one is given?
@@ -325,11 +696,11 @@ pub fn build_pyembed_artifacts(
             let p = p?;
 
             let dest_path = artifacts_path.join(p.file_name());
-            std::fs::copy(&p.path(), &dest_path).context(format!(
-                "copying {} to {}",
-                p.path().display(),
-                dest_path.display()
-            ))?;
+            let data = std::fs::read(&p.path())
+                .with_context(|| format!("reading {}", p.path().display()))?;
+            write_file_atomic(&dest_path, &data, is_executable(&p.metadata()?)).context(
+                format!("copying {} to {}", p.path().display(), dest_path.display()),
+            )?;
         }
 
         // TODO should we normalize paths to pyoxidizer build directory in cargo_metadata.txt
@@ -519,12 +890,84 @@ mod tests {
         crate::testutil::*,
     };
 
+    #[test]
+    fn test_c_library_filename() {
+        let cases = [
+            (
+                CLibraryKind::Static,
+                "x86_64-unknown-linux-gnu",
+                "libmyapp.a",
+            ),
+            (CLibraryKind::Static, "x86_64-pc-windows-msvc", "myapp.lib"),
+            (
+                CLibraryKind::Dynamic,
+                "x86_64-unknown-linux-gnu",
+                "libmyapp.so",
+            ),
+            (
+                CLibraryKind::Dynamic,
+                "x86_64-apple-darwin",
+                "libmyapp.dylib",
+            ),
+            (CLibraryKind::Dynamic, "x86_64-pc-windows-msvc", "myapp.dll"),
+        ];
+
+        for (kind, target, filename) in &cases {
+            assert_eq!(kind.filename("myapp", target), *filename);
+        }
+
+        assert_eq!(CLibraryKind::try_from("static"), Ok(CLibraryKind::Static));
+        assert_eq!(CLibraryKind::try_from("dynamic"), Ok(CLibraryKind::Dynamic));
+        assert!(CLibraryKind::try_from("shared").is_err());
+    }
+
     #[test]
     fn test_empty_project() -> Result<()> {
         let logger = get_logger()?;
         let pre_built = get_standalone_executable_builder(&logger)?;
 
-        build_python_executable(&logger, "myapp", &pre_built, env!("HOST"), "0", false)?;
+        build_python_executable(
+            &logger,
+            "myapp",
+            &pre_built,
+            env!("HOST"),
+            "0",
+            false,
+            2,
+            false,
+            None,
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_path_with_spaces_and_unicode() -> Result<()> {
+        let logger = get_logger()?;
+        let pre_built = get_standalone_executable_builder(&logger)?;
+
+        let env = crate::environment::resolve_environment()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let base_path = temp_dir.path().join("Jöhn Doe's projects");
+        create_dir_all(&base_path)?;
+
+        let project_path = base_path.join("myapp");
+        initialize_project(&project_path, &env.as_pyembed_location(), None, &[])?;
+
+        build_executable_with_rust_project(
+            &logger,
+            &project_path,
+            "myapp",
+            &pre_built,
+            &base_path.join("build"),
+            &base_path.join("artifacts"),
+            env!("HOST"),
+            "0",
+            false,
+            2,
+            false,
+            None,
+        )?;
 
         Ok(())
     }
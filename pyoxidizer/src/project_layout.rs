@@ -14,11 +14,28 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use crate::environment::{PyOxidizerSource, BUILD_GIT_COMMIT, PYOXIDIZER_VERSION};
+use crate::path_util::{path_str, toml_str_literal};
+use crate::plugin_exports::PluginExport;
 
 lazy_static! {
     static ref HANDLEBARS: Handlebars<'static> = {
         let mut handlebars = Handlebars::new();
 
+        handlebars
+            .register_template_string(
+                "bundle-cargo-config",
+                include_str!("templates/bundle-cargo-config"),
+            )
+            .unwrap();
+        handlebars
+            .register_template_string("bundle-lib.rs", include_str!("templates/bundle-lib.rs"))
+            .unwrap();
+        handlebars
+            .register_template_string("c-api.h", include_str!("templates/c-api.h"))
+            .unwrap();
+        handlebars
+            .register_template_string("c-api-lib.rs", include_str!("templates/c-api-lib.rs"))
+            .unwrap();
         handlebars
             .register_template_string("new-build.rs", include_str!("templates/new-build.rs"))
             .unwrap();
@@ -37,6 +54,12 @@ lazy_static! {
                 include_str!("templates/new-pyoxidizer.bzl"),
             )
             .unwrap();
+        handlebars
+            .register_template_string("plugin.h", include_str!("templates/plugin.h"))
+            .unwrap();
+        handlebars
+            .register_template_string("plugin-lib.rs", include_str!("templates/plugin-lib.rs"))
+            .unwrap();
 
         handlebars
     };
@@ -110,7 +133,7 @@ pub fn find_pyoxidizer_files(root: &Path) -> Vec<PathBuf> {
 
     for f in walk_tree_files(&root) {
         let path = f.path().strip_prefix(root).expect("unable to strip prefix");
-        let path_s = path.to_str().expect("unable to convert path to str");
+        let path_s = path.to_string_lossy();
 
         if path_s.contains("pyoxidizer") || path_s.contains("pyembed") {
             res.push(path.to_path_buf());
@@ -153,13 +176,92 @@ pub fn write_new_main_rs(path: &Path) -> Result<()> {
     let data: BTreeMap<String, String> = BTreeMap::new();
     let t = HANDLEBARS.render("new-main.rs", &data)?;
 
-    println!("writing {}", path.to_str().unwrap());
+    println!("writing {}", path.display());
     let mut fh = std::fs::File::create(path)?;
     fh.write_all(t.as_bytes())?;
 
     Ok(())
 }
 
+/// Obtain a lib.rs file exposing a C API to the embedded Python interpreter.
+pub fn c_api_lib_rs() -> Result<String> {
+    let data: BTreeMap<String, String> = BTreeMap::new();
+
+    Ok(HANDLEBARS.render("c-api-lib.rs", &data)?)
+}
+
+/// Obtain the C header declaring the API of a library embedding Python.
+pub fn c_api_header(library_name: &str) -> Result<String> {
+    let mut data: BTreeMap<String, String> = BTreeMap::new();
+    data.insert("library_name".to_string(), library_name.to_string());
+    data.insert(
+        "include_guard".to_string(),
+        format!("{}_H", library_name.to_uppercase()),
+    );
+
+    Ok(HANDLEBARS.render("c-api.h", &data)?)
+}
+
+#[derive(Serialize)]
+struct PluginExportData {
+    name: String,
+    callable: String,
+    declaration: String,
+    rust_name: String,
+    rust_parameters: String,
+    rust_returns: String,
+    rust_body: String,
+}
+
+#[derive(Serialize)]
+struct PluginData {
+    library_name: String,
+    include_guard: String,
+    exports: Vec<PluginExportData>,
+}
+
+impl PluginData {
+    fn new(library_name: &str, exports: &[PluginExport]) -> Self {
+        PluginData {
+            library_name: library_name.to_string(),
+            include_guard: format!("{}_H", library_name.to_uppercase()),
+            exports: exports
+                .iter()
+                .map(|export| PluginExportData {
+                    name: export.name.clone(),
+                    callable: export.callable.clone(),
+                    declaration: export.c_declaration(),
+                    rust_name: export.rust_name(),
+                    rust_parameters: export.rust_parameters(),
+                    rust_returns: export.rust_returns(),
+                    rust_body: export.rust_body(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Obtain a lib.rs file defining functions of a plugin library.
+pub fn plugin_lib_rs(exports: &[PluginExport]) -> Result<String> {
+    Ok(HANDLEBARS.render("plugin-lib.rs", &PluginData::new("", exports))?)
+}
+
+/// Obtain the C header declaring functions of a plugin library.
+pub fn plugin_header(library_name: &str, exports: &[PluginExport]) -> Result<String> {
+    Ok(HANDLEBARS.render("plugin.h", &PluginData::new(library_name, exports))?)
+}
+
+/// Obtain a lib.rs file defining an extension module bundling an application.
+///
+/// `python_version` is the `X.Y` version of Python resources are compiled for.
+pub fn bundle_lib_rs(name: &str, python_version: &str) -> Result<String> {
+    let mut data: BTreeMap<String, String> = BTreeMap::new();
+    data.insert("name".to_string(), name.to_string());
+    data.insert("python_version".to_string(), python_version.to_string());
+
+    Ok(HANDLEBARS.render("bundle-lib.rs", &data)?)
+}
+
 /// Writes default PyOxidizer config files into a project directory.
 pub fn write_new_pyoxidizer_config_file(
     project_dir: &Path,
@@ -183,7 +285,7 @@ pub fn write_new_pyoxidizer_config_file(
 
     let t = HANDLEBARS.render("new-pyoxidizer.bzl", &data)?;
 
-    println!("writing {}", path.to_str().unwrap());
+    println!("writing {}", path.display());
     let mut fh = std::fs::File::create(path)?;
     fh.write_all(t.as_bytes())?;
 
@@ -270,8 +372,8 @@ pub fn update_new_cargo_toml(path: &Path, pyembed_location: &PyembedLocation) ->
             version
         ),
         PyembedLocation::Path(path) => format!(
-            "pyembed = {{ path = \"{}\", default-features=false }}\n",
-            path.display()
+            "pyembed = {{ path = {}, default-features=false }}\n",
+            toml_str_literal(path_str(path)?)
         ),
     });
 
@@ -313,7 +415,10 @@ pub fn initialize_project(
     }
 
     let path = PathBuf::from(project_path);
-    let name = path.iter().last().unwrap().to_str().unwrap();
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("unable to resolve project name from {}", path.display()))?;
     add_pyoxidizer(&path, true)?;
     update_new_cargo_toml(&path.join("Cargo.toml"), pyembed_location)?;
     write_new_cargo_config(&path)?;
@@ -323,3 +428,168 @@ pub fn initialize_project(
 
     Ok(())
 }
+
+/// Initialize a new Rust project building a library embedding Python.
+///
+/// The library exposes a C API and is named after the final path component.
+/// `crate_type` is the Cargo crate type of the library, e.g. `staticlib`.
+pub fn initialize_library_project(
+    project_path: &Path,
+    pyembed_location: &PyembedLocation,
+    crate_type: &str,
+    lib_rs: &str,
+) -> Result<()> {
+    let status = std::process::Command::new("cargo")
+        .arg("init")
+        .arg("--lib")
+        .arg(project_path)
+        .status()?;
+
+    if !status.success() {
+        return Err(anyhow!("cargo init failed"));
+    }
+
+    let cargo_toml = project_path.join("Cargo.toml");
+    update_new_cargo_toml(&cargo_toml, pyembed_location)?;
+
+    let mut content = std::fs::read_to_string(&cargo_toml)?;
+    content.push_str("\n[lib]\n");
+    content.push_str(&format!("crate-type = [\"{}\"]\n", crate_type));
+    std::fs::write(&cargo_toml, content)?;
+
+    write_new_cargo_config(project_path)?;
+    write_new_build_rs(&project_path.join("build.rs"))?;
+
+    let lib_rs_path = project_path.join("src").join("lib.rs");
+    println!("writing {}", lib_rs_path.display());
+    std::fs::write(&lib_rs_path, lib_rs)?;
+
+    Ok(())
+}
+
+/// Initialize a new Rust project building an extension module bundling an application.
+///
+/// Unlike other projects, `pyembed` is built as for extension modules,
+/// without embedding an interpreter or linking libpython.
+pub fn initialize_bundle_project(
+    project_path: &Path,
+    pyembed_location: &PyembedLocation,
+    lib_rs: &str,
+) -> Result<()> {
+    let status = std::process::Command::new("cargo")
+        .arg("init")
+        .arg("--lib")
+        .arg(project_path)
+        .status()?;
+
+    if !status.success() {
+        return Err(anyhow!("cargo init failed"));
+    }
+
+    let cargo_toml = project_path.join("Cargo.toml");
+
+    let mut content = std::fs::read_to_string(&cargo_toml)?;
+    content.push_str(&match pyembed_location {
+        PyembedLocation::Version(version) => format!(
+            "pyembed = {{ version = \"{}\", default-features = false, features = [\"build-mode-extension-module\"] }}\n",
+            version
+        ),
+        PyembedLocation::Path(path) => format!(
+            "pyembed = {{ path = {}, default-features = false, features = [\"build-mode-extension-module\"] }}\n",
+            toml_str_literal(path_str(path)?)
+        ),
+    });
+    content.push_str("\n[lib]\n");
+    content.push_str("crate-type = [\"cdylib\"]\n");
+    std::fs::write(&cargo_toml, content)?;
+
+    let cargo_path = project_path.join(".cargo");
+    std::fs::create_dir_all(&cargo_path)?;
+    let data: BTreeMap<String, String> = BTreeMap::new();
+    std::fs::write(
+        cargo_path.join("config"),
+        HANDLEBARS.render("bundle-cargo-config", &data)?,
+    )?;
+
+    let lib_rs_path = project_path.join("src").join("lib.rs");
+    println!("writing {}", lib_rs_path.display());
+    std::fs::write(&lib_rs_path, lib_rs)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_api_header() -> Result<()> {
+        let header = c_api_header("myapp")?;
+
+        assert!(header.contains("C API of myapp, a library embedding a Python interpreter."));
+        assert!(header.contains("#ifndef MYAPP_H\n#define MYAPP_H\n"));
+        assert!(header.contains("int pyoxidizer_init(void);"));
+        assert!(header.contains("int pyoxidizer_run_main(void);"));
+        assert!(header.contains("void pyoxidizer_shutdown(void);"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundle_lib_rs() -> Result<()> {
+        let lib_rs = bundle_lib_rs("myapp_bundle", "3.8")?;
+
+        assert!(lib_rs.contains("pub extern \"C\" fn PyInit_myapp_bundle() -> *mut c_void {"));
+        assert!(lib_rs.contains("        b\"myapp_bundle\\0\",\n"));
+        assert!(lib_rs.contains("        \"3.8\",\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plugin_sources() -> Result<()> {
+        let exports = vec![
+            PluginExport::parse("double process(double sample, int channel)", "fx:process")?,
+            PluginExport::parse("void reset(void)", "fx:reset")?,
+        ];
+
+        let header = plugin_header("fx_plugin", &exports)?;
+        assert!(header.contains("#ifndef FX_PLUGIN_H\n#define FX_PLUGIN_H\n"));
+        assert!(header.contains(
+            "/* Implemented by fx:process. */\ndouble process(double sample, int channel);"
+        ));
+        assert!(header.contains("/* Implemented by fx:reset. */\nvoid reset(void);"));
+
+        let lib_rs = plugin_lib_rs(&exports)?;
+        assert!(lib_rs.contains("(\"process\", \"fx:process\"),"));
+        assert!(lib_rs.contains(
+            "#[export_name = \"process\"]\npub unsafe extern \"C\" fn export_process(arg_sample: f64, arg_channel: c_int) -> f64 {\n    call(\"process\", "
+        ));
+        assert!(lib_rs.contains(
+            "#[export_name = \"reset\"]\npub unsafe extern \"C\" fn export_reset() {\n    call(\"reset\", &[], PluginType::Void);\n}"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_new_cargo_toml_path() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let cargo_toml = temp_dir.path().join("Cargo.toml");
+        std::fs::write(
+            &cargo_toml,
+            "[package]\nname = \"myapp\"\nversion = \"0.1.0\"\n\n[dependencies]\n",
+        )?;
+
+        let pyembed_path = PathBuf::from(r"C:\Users\Jöhn Doe\PyOxidizer\pyembed");
+        update_new_cargo_toml(&cargo_toml, &PyembedLocation::Path(pyembed_path.clone()))?;
+
+        let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(&cargo_toml)?)?;
+        assert_eq!(
+            manifest["dependencies"]["pyembed"]["path"].as_str(),
+            pyembed_path.to_str()
+        );
+
+        Ok(())
+    }
+}
@@ -5,14 +5,41 @@
 //! Manage PyOxidizer projects.
 
 use {
+    crate::atomic_output::write_file_atomic,
+    crate::benchmark::{compare, format_table, BenchmarkCommand, BenchmarkOptions},
+    crate::briefcase::import_pyproject,
+    crate::build_budget::BuildBudget,
+    crate::build_metrics::{BuildMetrics, MetricsDestination},
+    crate::change_detection::{changed_paths, normalize_path, target_changes},
+    crate::checksums::{remove_checksums, write_checksums},
+    crate::cx_freeze::import_setup,
+    crate::explain::{plan_targets, render_plans},
+    crate::otlp::{BuildTrace, OtlpExporter},
     crate::project_building::find_pyoxidizer_config_file_env,
     crate::project_layout::{initialize_project, write_new_pyoxidizer_config_file},
     crate::py_packaging::standalone_distribution::StandaloneDistribution,
+    crate::pyinstaller_spec::{import_spec, SpecConversion},
+    crate::starlark::env::EnvironmentContext,
     crate::starlark::eval::{eval_starlark_config_file, EvalResult},
-    anyhow::{anyhow, Result},
+    crate::starlark::hooks::{run_hooks, TargetResult},
+    crate::starlark::python_executable::PythonExecutable,
+    crate::starlark::target::RunMode,
+    crate::starlark::testing::{find_test_files, run_test_file},
+    crate::watch::{watched_paths, FileSnapshot},
+    crate::webhooks::{
+        BuildNotifier, BuildWebhook, BUILD_ERROR_CODE, EVALUATION_ERROR_CODE, HOOK_ERROR_CODE,
+    },
+    crate::workspace::{find_config_files, workspace_bytecode_cache_path, WorkspaceReport},
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    starlark::values::Value,
+    std::collections::BTreeMap,
+    std::ffi::OsString,
     std::fs::create_dir_all,
     std::io::{Cursor, Read},
-    std::path::Path,
+    std::path::{Path, PathBuf},
+    std::process::Child,
+    std::time::{Duration, Instant, SystemTime},
 };
 
 /// Attempt to resolve the default Rust target for a build.
@@ -37,6 +64,10 @@ pub fn resolve_target(target: Option<&str>) -> Result<String> {
     }
 }
 
+/// List the targets of a configuration file.
+///
+/// Targets are described from their registration. No target is resolved, so
+/// target functions and hooks don't run and nothing is downloaded or built.
 pub fn list_targets(logger: &slog::Logger, project_path: &Path) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
         anyhow!(
@@ -54,21 +85,17 @@ pub fn list_targets(logger: &slog::Logger, project_path: &Path) -> Result<()> {
         false,
         Some(Vec::new()),
         false,
+        false,
+        true,
+        true,
     )?;
 
-    if res.context.default_target.is_none() {
+    if res.context.targets_order.is_empty() {
         println!("(no targets defined)");
         return Ok(());
     }
 
-    for target in res.context.targets.keys() {
-        let prefix = if Some(target.clone()) == res.context.default_target {
-            "*"
-        } else {
-            ""
-        };
-        println!("{}{}", prefix, target);
-    }
+    print!("{}", res.context.describe_targets());
 
     Ok(())
 }
@@ -77,6 +104,7 @@ pub fn list_targets(logger: &slog::Logger, project_path: &Path) -> Result<()> {
 ///
 /// This is a glorified wrapper around `cargo build`. Our goal is to get the
 /// output from repackaging to give the user something for debugging.
+#[allow(clippy::too_many_arguments)]
 pub fn build(
     logger: &slog::Logger,
     project_path: &Path,
@@ -84,6 +112,15 @@ pub fn build(
     resolve_targets: Option<Vec<String>>,
     release: bool,
     verbose: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
+    tags: &[String],
+    changed_since: Option<&str>,
+    otlp_endpoint: Option<&str>,
+    keep_outputs: Option<usize>,
+    keep_all: bool,
+    budget: &BuildBudget,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
         anyhow!(
@@ -93,7 +130,211 @@ pub fn build(
     })?;
     let target_triple = resolve_target(target_triple)?;
 
-    let mut res: EvalResult = eval_starlark_config_file(
+    build_config(
+        logger,
+        &config_path,
+        &target_triple,
+        resolve_targets,
+        release,
+        verbose,
+        strict,
+        offline,
+        pip_cache,
+        tags,
+        changed_since,
+        otlp_endpoint,
+        keep_outputs,
+        keep_all,
+        budget,
+        None,
+        &mut Vec::new(),
+    )
+}
+
+/// Build all configuration files in a workspace directory.
+///
+/// Each configuration file is built like by `build()`. Failing to build one
+/// doesn't prevent building the others. Compiled bytecode is cached in the
+/// workspace's build directory. A summary is printed and, if `report_path`
+/// is defined, a JSON report is written to it.
+#[allow(clippy::too_many_arguments)]
+pub fn build_workspace(
+    logger: &slog::Logger,
+    workspace_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    verbose: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
+    tags: &[String],
+    changed_since: Option<&str>,
+    otlp_endpoint: Option<&str>,
+    keep_outputs: Option<usize>,
+    keep_all: bool,
+    budget: &BuildBudget,
+    report_path: Option<&Path>,
+) -> Result<()> {
+    let config_paths = find_config_files(workspace_path)?;
+    if config_paths.is_empty() {
+        return Err(anyhow!(
+            "no PyOxidizer config files found in {}",
+            workspace_path.display()
+        ));
+    }
+
+    let target_triple = resolve_target(target_triple)?;
+    let mut report = WorkspaceReport::new(workspace_path, &target_triple, release);
+
+    for config_path in &config_paths {
+        warn!(logger, "building {}", config_path.display());
+
+        let mut results = Vec::new();
+        let res = build_config(
+            logger,
+            config_path,
+            &target_triple,
+            None,
+            release,
+            verbose,
+            strict,
+            offline,
+            pip_cache,
+            tags,
+            changed_since,
+            otlp_endpoint,
+            keep_outputs,
+            keep_all,
+            budget,
+            Some(workspace_path),
+            &mut results,
+        );
+
+        if let Err(e) = &res {
+            warn!(logger, "error building {}: {:?}", config_path.display(), e);
+        }
+
+        report.add_config(config_path, &results, &res);
+    }
+
+    println!("{}", report.summary());
+
+    if let Some(path) = report_path {
+        warn!(logger, "writing workspace report to {}", path.display());
+        write_file_atomic(path, report.to_json()?.as_bytes(), false)
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+
+    let failed = report.failed_configs();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("failed to build {}", failed.join(", ")))
+    }
+}
+
+/// Build the targets of a configuration file.
+///
+/// `workspace_path` is the workspace the configuration file is built as part
+/// of, if any. In a workspace, compiled bytecode is cached in the workspace's
+/// build directory and a configuration file without targets having `tags` is
+/// skipped rather than being an error.
+///
+/// Results of built targets are appended to `results`, even if building fails.
+#[allow(clippy::too_many_arguments)]
+fn build_config(
+    logger: &slog::Logger,
+    config_path: &Path,
+    target_triple: &str,
+    resolve_targets: Option<Vec<String>>,
+    release: bool,
+    verbose: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
+    tags: &[String],
+    changed_since: Option<&str>,
+    otlp_endpoint: Option<&str>,
+    keep_outputs: Option<usize>,
+    keep_all: bool,
+    budget: &BuildBudget,
+    workspace_path: Option<&Path>,
+    results: &mut Vec<TargetResult>,
+) -> Result<()> {
+    let resolve_targets = if !tags.is_empty() || changed_since.is_some() {
+        // Evaluate the config without resolving targets to obtain their tags
+        // and inputs.
+        let mut res: EvalResult = eval_starlark_config_file(
+            logger,
+            &config_path,
+            &target_triple,
+            release,
+            verbose,
+            Some(Vec::new()),
+            false,
+            strict,
+            offline,
+            pip_cache,
+        )?;
+
+        let targets = if tags.is_empty() {
+            res.context.resolve_targets = resolve_targets;
+            res.context.targets_to_resolve()
+        } else {
+            let targets = res.context.targets_with_tags(tags);
+
+            if targets.is_empty() {
+                if workspace_path.is_some() {
+                    warn!(
+                        logger,
+                        "skipping {}: no targets are tagged {}",
+                        config_path.display(),
+                        tags.join(" and ")
+                    );
+                    return Ok(());
+                }
+
+                return Err(anyhow!("no targets are tagged {}", tags.join(" and ")));
+            }
+
+            targets
+        };
+
+        if let Some(revision) = changed_since {
+            let changed = changed_paths(&res.context.cwd, revision)?;
+            let mut affected = Vec::new();
+
+            for (target, change) in target_changes(&res.context, &targets, &changed)? {
+                if change.is_affected() {
+                    warn!(logger, "building {}: {}", target, change);
+                    affected.push(target);
+                } else {
+                    warn!(logger, "skipping {}: {} since {}", target, change, revision);
+                }
+            }
+
+            if affected.is_empty() {
+                warn!(logger, "no targets affected by changes since {}", revision);
+                return Ok(());
+            }
+
+            Some(affected)
+        } else {
+            Some(targets)
+        }
+    } else {
+        resolve_targets
+    };
+
+    let metrics_destination = MetricsDestination::from_env();
+    let otlp_exporter = OtlpExporter::resolve(otlp_endpoint)?;
+    let env_webhook = BuildWebhook::from_env()?;
+    let mut metrics = BuildMetrics::new(&config_path, &target_triple, release);
+    let mut trace = BuildTrace::default();
+    let build_start = Instant::now();
+
+    let eval_start = SystemTime::now();
+    let res = eval_starlark_config_file(
         logger,
         &config_path,
         &target_triple,
@@ -101,15 +342,373 @@ pub fn build(
         verbose,
         resolve_targets,
         false,
-    )?;
+        strict,
+        offline,
+        pip_cache,
+    );
+    trace.add_span(
+        "evaluate configuration",
+        eval_start,
+        &[],
+        res.as_ref().err().map(|e| e.to_string()),
+    );
+
+    let mut res: EvalResult = match res {
+        Ok(res) => res,
+        Err(e) => {
+            if let Some(exporter) = &otlp_exporter {
+                metrics.finish(build_start.elapsed());
+                exporter.export(logger, &trace, &metrics);
+            }
+            if let Some(webhook) = env_webhook.filter(|_| !offline) {
+                BuildNotifier::new(webhook, &config_path, &target_triple, release).failed(
+                    logger,
+                    None,
+                    EVALUATION_ERROR_CODE,
+                    &e,
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    // The environment takes precedence over the configuration file.
+    let webhook = env_webhook.or_else(|| res.context.build_webhook.clone());
+    if offline && webhook.is_some() {
+        warn!(logger, "not notifying build webhook in offline mode");
+    }
+    let notifier = webhook
+        .filter(|_| !offline)
+        .map(|webhook| BuildNotifier::new(webhook, &config_path, &target_triple, release));
 
-    for target in res.context.targets_to_resolve() {
-        res.context.build_resolved_target(&target)?;
+    // Command line settings take precedence over the configuration file.
+    if keep_outputs.is_some() {
+        res.context.output_retention = keep_outputs;
+    }
+    res.context.keep_all_outputs = keep_all;
+    res.context.build_budget = res.context.build_budget.merge(budget);
+    if let Some(path) = workspace_path {
+        res.context.bytecode_cache_path = workspace_bytecode_cache_path(path);
+    }
+
+    let build_res = build_targets(
+        &mut res,
+        &mut metrics,
+        &mut trace,
+        notifier.as_ref(),
+        results,
+    );
+
+    if metrics_destination.is_some() || otlp_exporter.is_some() {
+        metrics.finish(build_start.elapsed());
+    }
+    if let Some(exporter) = &otlp_exporter {
+        exporter.export(logger, &trace, &metrics);
+    }
+    build_res?;
+
+    if let Some(destination) = metrics_destination {
+        metrics.emit(logger, &destination)?;
     }
 
     Ok(())
 }
 
+/// Build the targets of an evaluated configuration, running hooks.
+///
+/// Build events are sent to `notifier`, if defined. Results of built targets
+/// are appended to `target_results`.
+fn build_targets(
+    res: &mut EvalResult,
+    metrics: &mut BuildMetrics,
+    trace: &mut BuildTrace,
+    notifier: Option<&BuildNotifier>,
+    target_results: &mut Vec<TargetResult>,
+) -> Result<()> {
+    let logger = res.context.logger.clone();
+    let targets = res
+        .context
+        .order_targets(&res.context.targets_to_resolve())?;
+    let mut results = Vec::new();
+    let mut output_paths = Vec::new();
+
+    if let Some(notifier) = notifier {
+        notifier.started(&logger, &targets);
+    }
+
+    for target in targets.iter().cloned() {
+        let target_start = Instant::now();
+        let span_start = SystemTime::now();
+        let resolved = res.context.build_resolved_target(&target);
+
+        trace.add_span(
+            "build target",
+            span_start,
+            &[("pyoxidizer.target", &target)],
+            resolved.as_ref().err().map(|e| e.to_string()),
+        );
+
+        let result = TargetResult::built(
+            &target,
+            target_start.elapsed(),
+            resolved.as_ref().ok().map(|r| r.output_path.as_path()),
+        );
+        results.push(Value::new(result.clone()));
+        target_results.push(result.clone());
+
+        if let (Some(notifier), true) = (notifier, result.success) {
+            notifier.target_built(&logger, &result);
+        }
+
+        let hooks_res = run_hooks(&res.env, "post_build", vec![Value::new(result)]);
+
+        let resolved = match resolved {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                if let Some(notifier) = notifier {
+                    notifier.failed(&logger, Some(&target), BUILD_ERROR_CODE, &e);
+                }
+
+                // Artifacts of the failed build may not match checksums of
+                // a previous build.
+                let _ = remove_checksums(&logger, &res.context.profile_path());
+
+                // Let build_end hooks know about the failure. Errors of the
+                // build take precedence over errors of hooks.
+                let _ = run_hooks(&res.env, "build_end", vec![Value::from(results)]);
+                return Err(e);
+            }
+        };
+        if let Err(e) = hooks_res {
+            if let Some(notifier) = notifier {
+                notifier.failed(&logger, Some(&target), HOOK_ERROR_CODE, &e);
+            }
+
+            let _ = remove_checksums(&logger, &res.context.profile_path());
+            return Err(e);
+        }
+
+        metrics.add_target(&target, target_start.elapsed(), &resolved.output_path);
+        output_paths.push(resolved.output_path);
+    }
+
+    // Checksums are written once every target is built, as if by a final
+    // target depending on all others.
+    let span_start = SystemTime::now();
+    let checksums_res = write_checksums(
+        &logger,
+        &res.context.profile_path(),
+        &output_paths,
+        res.context.artifact_signing.as_ref(),
+    );
+    trace.add_span(
+        "write checksums",
+        span_start,
+        &[],
+        checksums_res.as_ref().err().map(|e| e.to_string()),
+    );
+    if let Err(e) = checksums_res {
+        if let Some(notifier) = notifier {
+            notifier.failed(&logger, None, BUILD_ERROR_CODE, &e);
+        }
+
+        // e.g. if signing failed, checksums would be left unsigned.
+        let _ = remove_checksums(&logger, &res.context.profile_path());
+
+        let _ = run_hooks(&res.env, "build_end", vec![Value::from(results)]);
+        return Err(e);
+    }
+
+    let hooks_res = run_hooks(&res.env, "build_end", vec![Value::from(results)]);
+
+    if let Some(notifier) = notifier {
+        match &hooks_res {
+            Ok(()) => notifier.completed(&logger, &targets),
+            Err(e) => notifier.failed(&logger, None, HOOK_ERROR_CODE, e),
+        }
+    }
+
+    hooks_res
+}
+
+/// Launch the last target built by a build which can be run.
+///
+/// The process previously launched, if any, is stopped first. Nothing
+/// happens if no target which can be run was built, e.g. if only targets
+/// it depends on were affected by changes.
+fn relaunch(
+    logger: &slog::Logger,
+    context: &EnvironmentContext,
+    process: &mut Option<Child>,
+) -> Result<()> {
+    let built = context
+        .order_targets(&context.targets_to_resolve())?
+        .iter()
+        .rev()
+        .filter_map(|target| context.targets[target].built_target.clone())
+        .find(|built| match built.run_mode {
+            RunMode::Path { .. } => true,
+            RunMode::None => false,
+        });
+
+    if let Some(built) = built {
+        if let Some(mut child) = process.take() {
+            // The process may have exited already.
+            let _ = child.kill();
+            child.wait()?;
+        }
+
+        if let RunMode::Path { path } = &built.run_mode {
+            warn!(logger, "launching {}", path.display());
+        }
+        *process = built.spawn()?;
+    }
+
+    Ok(())
+}
+
+/// Build targets of a project, then build them again when their files change.
+///
+/// The configuration file, inputs of targets and source directories read by
+/// target functions are polled every `interval`. Only targets affected by
+/// changed files are built again. Errors are reported and watching goes on,
+/// so broken code can be fixed. If `run` is true, the last built target
+/// which can be run is launched after each build, stopping the process
+/// launched by the previous build.
+#[allow(clippy::too_many_arguments)]
+pub fn watch(
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    resolve_targets: Option<Vec<String>>,
+    release: bool,
+    verbose: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
+    run: bool,
+    interval: Duration,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let mut to_build = resolve_targets.clone();
+    // Targets requested on the command line, or the default target.
+    let mut requested = resolve_targets.clone();
+    // Targets which aren't built again keep source paths of previous builds.
+    let mut source_paths: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut context: Option<EnvironmentContext> = None;
+    let mut watched = vec![normalize_path(&config_path)?];
+    let mut process = None;
+
+    loop {
+        match eval_starlark_config_file(
+            logger,
+            &config_path,
+            &target_triple,
+            release,
+            verbose,
+            to_build.clone(),
+            false,
+            strict,
+            offline,
+            pip_cache,
+        ) {
+            Ok(mut res) => {
+                for (target, entry) in res.context.targets.iter_mut() {
+                    if entry.resolved_value.is_some() {
+                        source_paths.insert(target.clone(), entry.source_paths.clone());
+                    } else if let Some(paths) = source_paths.get(target) {
+                        entry.source_paths = paths.clone();
+                    }
+                }
+
+                if requested.is_none() {
+                    requested = Some(res.context.targets_to_resolve());
+                }
+
+                match build_targets(
+                    &mut res,
+                    &mut BuildMetrics::new(&config_path, &target_triple, release),
+                    &mut BuildTrace::default(),
+                    None,
+                    &mut Vec::new(),
+                ) {
+                    Ok(()) => {
+                        if run {
+                            relaunch(logger, &res.context, &mut process)?;
+                        }
+                    }
+                    Err(e) => warn!(logger, "build failed: {:?}", e),
+                }
+
+                watched = watched_paths(&res.context)?;
+                context = Some(res.context);
+            }
+            Err(e) => warn!(
+                logger,
+                "evaluating {} failed: {:?}",
+                config_path.display(),
+                e
+            ),
+        }
+
+        let exclude = match &context {
+            Some(context) => normalize_path(&context.build_path)?,
+            None => normalize_path(&config_path)?.with_file_name("build"),
+        };
+        let mut snapshot = FileSnapshot::capture(&watched, &exclude);
+
+        warn!(
+            logger,
+            "watching {} paths for changes; press Ctrl+C to stop",
+            watched.len()
+        );
+
+        to_build = loop {
+            std::thread::sleep(interval);
+
+            let current = FileSnapshot::capture(&watched, &exclude);
+            let changed = snapshot.changed_paths(&current);
+            if changed.is_empty() {
+                continue;
+            }
+            snapshot = current;
+
+            let (context, requested) = match (&context, &requested) {
+                (Some(context), Some(requested)) => (context, requested),
+                // The configuration file couldn't be evaluated before.
+                _ => break resolve_targets.clone(),
+            };
+
+            let mut affected = Vec::new();
+
+            for (target, change) in target_changes(context, requested, &changed)? {
+                if change.is_affected() {
+                    warn!(logger, "building {}: {}", target, change);
+                    affected.push(target);
+                }
+            }
+
+            if !affected.is_empty() {
+                break Some(affected);
+            }
+
+            warn!(
+                logger,
+                "no targets affected by changes to {}",
+                changed[0].display()
+            );
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     logger: &slog::Logger,
     project_path: &Path,
@@ -118,6 +717,9 @@ pub fn run(
     target: Option<&str>,
     _extra_args: &[&str],
     verbose: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
         anyhow!(
@@ -141,11 +743,286 @@ pub fn run(
         verbose,
         resolve_targets,
         false,
+        strict,
+        offline,
+        pip_cache,
     )?;
 
     res.context.run_target(target)
 }
 
+/// Describe what building targets in a project would do, without building them.
+///
+/// The config file is evaluated and targets are resolved, which runs
+/// packaging operations like `pip install` defined by target functions.
+#[allow(clippy::too_many_arguments)]
+pub fn explain(
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    target: Option<&str>,
+    verbose: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let resolve_targets = if let Some(target) = target {
+        Some(vec![target.to_string()])
+    } else {
+        None
+    };
+
+    let res: EvalResult = eval_starlark_config_file(
+        logger,
+        &config_path,
+        &target_triple,
+        release,
+        verbose,
+        resolve_targets,
+        false,
+        strict,
+        offline,
+        pip_cache,
+    )
+    .with_context(|| {
+        if offline {
+            "unable to resolve targets offline (use --allow-network to allow downloads)"
+        } else {
+            "unable to resolve targets"
+        }
+    })?;
+
+    let plans = plan_targets(&res.context)?;
+    print!("{}", render_plans(&res.context, &plans)?);
+
+    Ok(())
+}
+
+/// Report modules of resolved Python executables likely to break when imported from memory.
+#[allow(clippy::too_many_arguments)]
+pub fn analyze_project(
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    target: Option<&str>,
+    include_stdlib: bool,
+    verbose: bool,
+    offline: bool,
+    pip_cache: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let resolve_targets = if let Some(target) = target {
+        Some(vec![target.to_string()])
+    } else {
+        None
+    };
+
+    let res: EvalResult = eval_starlark_config_file(
+        logger,
+        &config_path,
+        &target_triple,
+        release,
+        verbose,
+        resolve_targets,
+        false,
+        false,
+        offline,
+        pip_cache,
+    )
+    .with_context(|| {
+        if offline {
+            "unable to resolve targets offline (use --allow-network to allow downloads)"
+        } else {
+            "unable to resolve targets"
+        }
+    })?;
+
+    for name in &res.context.targets_order {
+        let value = match res
+            .context
+            .targets
+            .get(name)
+            .and_then(|target| target.resolved_value.as_ref())
+        {
+            Some(value) if value.get_type() == "PythonExecutable" => value,
+            _ => continue,
+        };
+
+        let issues = value.downcast_apply(|exe: &PythonExecutable| {
+            exe.exe.in_memory_import_issues(include_stdlib)
+        })?;
+
+        println!("target {}: {} potential issues", name, issues.len());
+        for issue in issues {
+            println!("  {}", issue.message);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the Starlark unit tests found in a path.
+///
+/// `path` is a test file or a directory searched for test files. The outcome
+/// of each test is printed and an error is returned if any test failed.
+pub fn test_config(
+    logger: &slog::Logger,
+    path: &Path,
+    target_triple: Option<&str>,
+    verbose: bool,
+) -> Result<()> {
+    let target_triple = resolve_target(target_triple)?;
+
+    let files = find_test_files(path)?;
+    if files.is_empty() {
+        return Err(anyhow!("no test files found in {}", path.display()));
+    }
+
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for file in files {
+        let res = run_test_file(logger, &file, &target_triple, verbose)?;
+
+        if let Some(error) = &res.error {
+            failed += 1;
+            println!("FAIL {}", file.display());
+            println!("     {}", error);
+            continue;
+        }
+
+        for case in &res.cases {
+            if let Some(error) = &case.error {
+                failed += 1;
+                println!("FAIL {}::{}", file.display(), case.name);
+                println!("     {}", error);
+            } else {
+                passed += 1;
+                println!("ok   {}::{}", file.display(), case.name);
+            }
+        }
+    }
+
+    println!();
+    println!("{} passed; {} failed", passed, failed);
+
+    if failed > 0 {
+        Err(anyhow!("{} Starlark tests failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+/// Compare startup of a built target against a reference Python interpreter.
+///
+/// The built binary is run with `extra_args` and the reference interpreter
+/// with `reference_args` followed by `extra_args`. `reference_args` should
+/// make the reference interpreter run the same entry point as the binary.
+#[allow(clippy::too_many_arguments)]
+pub fn bench(
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    target: Option<&str>,
+    reference: &str,
+    reference_args: &[&str],
+    extra_args: &[&str],
+    python_executable_env: Option<&str>,
+    options: &BenchmarkOptions,
+    verbose: bool,
+    offline: bool,
+    pip_cache: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let resolve_targets = if let Some(target) = target {
+        Some(vec![target.to_string()])
+    } else {
+        None
+    };
+
+    let mut res: EvalResult = eval_starlark_config_file(
+        logger,
+        &config_path,
+        &target_triple,
+        release,
+        verbose,
+        resolve_targets,
+        false,
+        false,
+        offline,
+        pip_cache,
+    )?;
+
+    let resolved = res.context.build_target(target)?;
+
+    let path = match resolved.run_mode {
+        RunMode::Path { path } => path,
+        RunMode::None => {
+            return Err(anyhow!("target does not produce a runnable binary"));
+        }
+    };
+
+    let oxidized = BenchmarkCommand {
+        program: path.clone(),
+        args: extra_args.iter().map(OsString::from).collect(),
+        envs: vec![],
+    };
+
+    let oxidized_python = python_executable_env.map(|env| BenchmarkCommand {
+        program: path.clone(),
+        args: vec![],
+        envs: vec![(OsString::from(env), OsString::from("1"))],
+    });
+
+    let reference = BenchmarkCommand {
+        program: PathBuf::from(reference),
+        args: reference_args
+            .iter()
+            .chain(extra_args.iter())
+            .map(OsString::from)
+            .collect(),
+        envs: vec![],
+    };
+
+    println!(
+        "comparing {} against {} ({} iterations)",
+        path.display(),
+        reference.program.display(),
+        options.iterations
+    );
+
+    let rows = compare(&oxidized, oxidized_python.as_ref(), &reference, options)?;
+
+    print!("{}", format_table(&rows));
+
+    Ok(())
+}
+
 /// Initialize a PyOxidizer configuration file in a given directory.
 pub fn init_config_file(
     project_dir: &Path,
@@ -183,6 +1060,49 @@ pub fn init_config_file(
     Ok(())
 }
 
+/// Print the Starlark of a conversion of the file at `path`.
+fn print_conversion(logger: &slog::Logger, conversion: &SpecConversion, path: &Path) {
+    print!("{}", conversion.starlark);
+
+    if !conversion.unsupported.is_empty() {
+        warn!(
+            logger,
+            "{} constructs of {} have no equivalent; see the end of the output",
+            conversion.unsupported.len(),
+            path.display()
+        );
+    }
+}
+
+/// Print Starlark configuration equivalent to a PyInstaller spec file.
+pub fn import_pyinstaller_spec(logger: &slog::Logger, spec_path: &Path) -> Result<()> {
+    print_conversion(logger, &import_spec(spec_path)?, spec_path);
+
+    Ok(())
+}
+
+/// Print Starlark configuration equivalent to a cx_Freeze setup script.
+pub fn import_cx_freeze_setup(logger: &slog::Logger, setup_path: &Path) -> Result<()> {
+    print_conversion(logger, &import_setup(setup_path)?, setup_path);
+
+    Ok(())
+}
+
+/// Print Starlark configuration equivalent to an app of a briefcase project.
+pub fn import_briefcase_config(
+    logger: &slog::Logger,
+    pyproject_path: &Path,
+    app_name: Option<&str>,
+) -> Result<()> {
+    print_conversion(
+        logger,
+        &import_pyproject(pyproject_path, app_name)?,
+        pyproject_path,
+    );
+
+    Ok(())
+}
+
 /// Initialize a new Rust project with PyOxidizer support.
 pub fn init_rust_project(project_path: &Path) -> Result<()> {
     let env = crate::environment::resolve_environment()?;
@@ -7,18 +7,30 @@ Defining and manipulating binaries embedding Python.
 */
 
 use {
+    super::compatibility::CompatibilityIssue,
     super::config::EmbeddedPythonConfig,
     super::embedded_resource::EmbeddedPythonResources,
-    super::pyembed::{derive_python_config, write_default_python_config_rs},
+    super::pyembed::{
+        derive_ctypes_library_map, derive_python_config, write_default_python_config_rs,
+    },
+    super::resource_signing::ResourceSigningKey,
     super::standalone_distribution::DistributionExtensionModule,
+    crate::app_packaging::linux_service::LinuxServiceOptions,
+    crate::app_packaging::macos_signing::MacOsSigningOptions,
     crate::app_packaging::resource::FileManifest,
+    crate::app_packaging::windows_manifest::WindowsManifestOptions,
+    crate::atomic_output::write_file_atomic,
+    crate::path_util::path_str,
     anyhow::Result,
+    python_packaging::package_metadata::PythonPackageRecord,
     python_packaging::resource::{
-        PythonExtensionModule, PythonModuleBytecodeFromSource, PythonModuleSource,
-        PythonPackageDistributionResource, PythonPackageResource,
+        PythonExtensionModule, PythonModuleBytecode, PythonModuleBytecodeFromSource,
+        PythonModuleSource, PythonPackageDistributionResource, PythonPackageResource,
+    },
+    python_packaging::resource_collection::{
+        FilesystemModuleLayout, PrePackagedResource, PythonResourcesPolicy,
     },
-    python_packaging::resource_collection::PythonResourcesPolicy,
-    std::collections::BTreeMap,
+    std::collections::{BTreeMap, BTreeSet, HashMap},
     std::convert::TryFrom,
     std::fs::File,
     std::io::Write,
@@ -49,12 +61,31 @@ pub trait PythonBinaryBuilder {
     /// returned executable.
     fn python_exe_path(&self) -> &Path;
 
+    /// Version string of the Python distribution being embedded.
+    fn python_version(&self) -> String;
+
+    /// Obtain names and versions of Python package distributions embedded in this instance.
+    fn package_distributions(&self) -> BTreeMap<String, String>;
+
+    /// Obtain parsed `RECORD` files of Python package distributions embedded in this instance.
+    fn package_records(&self) -> Result<BTreeMap<String, PythonPackageRecord>>;
+
     /// Obtain Python source modules imported from memory to be embedded in this instance.
     fn in_memory_module_sources(&self) -> BTreeMap<String, PythonModuleSource>;
 
     /// Obtain Python package resources data loaded from memory to be embedded in this instance.
     fn in_memory_package_resources(&self) -> BTreeMap<String, BTreeMap<String, Vec<u8>>>;
 
+    /// Obtain the state of a named resource in the resources collected for this instance.
+    ///
+    /// This reflects what was accepted by previous `add_*` calls.
+    fn resource(&self, name: &str) -> Option<&PrePackagedResource>;
+
+    /// Obtain names of extension modules in this instance.
+    ///
+    /// This includes extension modules linked into the binary.
+    fn extension_module_names(&self) -> BTreeSet<String>;
+
     /// Add Python module source code to be imported from memory to the embedded resources.
     fn add_in_memory_module_source(&mut self, module: &PythonModuleSource) -> Result<()>;
 
@@ -69,7 +100,8 @@ pub trait PythonBinaryBuilder {
     fn add_module_source(&mut self, module: &PythonModuleSource) -> Result<()> {
         match self.python_resources_policy().clone() {
             PythonResourcesPolicy::InMemoryOnly
-            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_) => {
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => {
                 self.add_in_memory_module_source(module)
             }
             PythonResourcesPolicy::FilesystemRelativeOnly(ref prefix) => {
@@ -95,7 +127,8 @@ pub trait PythonBinaryBuilder {
     fn add_module_bytecode(&mut self, module: &PythonModuleBytecodeFromSource) -> Result<()> {
         match self.python_resources_policy().clone() {
             PythonResourcesPolicy::InMemoryOnly
-            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_) => {
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => {
                 self.add_in_memory_module_bytecode(module)
             }
             PythonResourcesPolicy::FilesystemRelativeOnly(ref prefix) => {
@@ -104,6 +137,33 @@ pub trait PythonBinaryBuilder {
         }
     }
 
+    /// Add compiled Python module bytecode to be imported from memory to the embedded resources.
+    fn add_in_memory_compiled_module_bytecode(
+        &mut self,
+        module: &PythonModuleBytecode,
+    ) -> Result<()>;
+
+    /// Add compiled Python module bytecode to be imported from the filesystem relative to the produced binary.
+    fn add_relative_path_compiled_module_bytecode(
+        &mut self,
+        prefix: &str,
+        module: &PythonModuleBytecode,
+    ) -> Result<()>;
+
+    /// Add compiled Python module bytecode to a location as determined by the builder's resource policy.
+    fn add_compiled_module_bytecode(&mut self, module: &PythonModuleBytecode) -> Result<()> {
+        match self.python_resources_policy().clone() {
+            PythonResourcesPolicy::InMemoryOnly
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => {
+                self.add_in_memory_compiled_module_bytecode(module)
+            }
+            PythonResourcesPolicy::FilesystemRelativeOnly(ref prefix) => {
+                self.add_relative_path_compiled_module_bytecode(prefix, module)
+            }
+        }
+    }
+
     /// Add resource data to the collection of embedded resource data.
     fn add_in_memory_package_resource(&mut self, resource: &PythonPackageResource) -> Result<()>;
 
@@ -118,7 +178,8 @@ pub trait PythonBinaryBuilder {
     fn add_package_resource(&mut self, resource: &PythonPackageResource) -> Result<()> {
         match self.python_resources_policy().clone() {
             PythonResourcesPolicy::InMemoryOnly
-            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_) => {
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => {
                 self.add_in_memory_package_resource(resource)
             }
             PythonResourcesPolicy::FilesystemRelativeOnly(ref prefix) => {
@@ -147,7 +208,8 @@ pub trait PythonBinaryBuilder {
     ) -> Result<()> {
         match self.python_resources_policy().clone() {
             PythonResourcesPolicy::InMemoryOnly
-            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_) => {
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => {
                 self.add_in_memory_package_distribution_resource(resource)
             }
             PythonResourcesPolicy::FilesystemRelativeOnly(ref prefix) => {
@@ -226,14 +288,194 @@ pub trait PythonBinaryBuilder {
         glob_patterns: &[&str],
     ) -> Result<()>;
 
+    /// Install all resources as files next to the binary.
+    ///
+    /// Resources are moved to the `prefix` directory relative to the binary,
+    /// except package resources, which are moved to `resources_prefix`.
+    /// Resources added afterwards follow the `filesystem-relative-only`
+    /// policy with `prefix`.
+    fn relocate_resources_to_filesystem(
+        &mut self,
+        prefix: &str,
+        resources_prefix: &str,
+    ) -> Result<()>;
+
+    /// Add support for creating virtual environments at run-time.
+    ///
+    /// This adds the `oxidized_venv` module along with the standard library
+    /// packages it needs, including the `pip` and `setuptools` wheels bundled
+    /// with `ensurepip`. The interpreter must be configured with a
+    /// `python_executable_env`. Windows targets are not supported.
+    fn add_venv_support(&mut self) -> Result<()>;
+
+    /// Store bytecode of modules in a package in a remote archive.
+    ///
+    /// Bytecode of the package and its sub-packages is moved out of the
+    /// binary and into an archive installed next to it, which must be hosted
+    /// at the interpreter's `remote_resources_url`. Modules are fetched from
+    /// the archive when first imported.
+    fn add_remote_resources_package(&mut self, package: &str) -> Result<()>;
+
+    /// Import modules in a package from a zip archive via `zipimport`.
+    ///
+    /// Sources, bytecode and package resources of the package and its
+    /// sub-packages are moved out of the binary and into a zip archive
+    /// installed next to it. The archive is added to `sys.path` and modules
+    /// are imported by Python's `zipimport`, for compatibility with code
+    /// expecting zip or filesystem semantics.
+    fn add_zipimport_package(&mut self, package: &str) -> Result<()>;
+
+    /// Embed caches populated when the interpreter starts.
+    ///
+    /// A module looking up `codecs` and seeding the `re` module cache with
+    /// `regex_patterns`, `(pattern, flags)` tuples compiled at build time, is
+    /// added and imported at the end of interpreter initialization.
+    fn add_startup_cache(
+        &mut self,
+        codecs: &[String],
+        regex_patterns: &[(String, u32)],
+    ) -> Result<()>;
+
+    /// Start a debugpy listener when an environment variable is present.
+    ///
+    /// The packages installed by `pip install <requirement>`, run with
+    /// environment variables `pip_envs`, are added next to the binary, which
+    /// requires a resources policy allowing filesystem-relative resources.
+    /// At run-time, if the environment variable `env` is present, `debugpy`
+    /// listens for debugger connections on the `[host:]port` address it holds
+    /// before Python code runs. Only loopback addresses are accepted unless
+    /// `allow_remote_hosts` is set.
+    fn add_debugpy_listener(
+        &mut self,
+        logger: &slog::Logger,
+        env: &str,
+        requirement: &str,
+        allow_remote_hosts: bool,
+        pip_envs: &HashMap<String, String>,
+    ) -> Result<()>;
+
+    /// Sign resource files installed next to the binary.
+    ///
+    /// A signature of each resource file is installed next to it and the
+    /// public key of `key` is embedded in the binary. The importer refuses
+    /// resource files whose signature doesn't verify.
+    fn sign_external_resources(&mut self, key: &ResourceSigningKey) -> Result<()>;
+
+    /// Define settings of the Windows application manifest of the binary.
+    ///
+    /// Settings are ignored when not targeting Windows with the MSVC toolchain.
+    fn set_windows_manifest(&mut self, options: &WindowsManifestOptions) -> Result<()>;
+
+    /// Define how to sign the binary on macOS.
+    ///
+    /// Binaries are signed after they are built. Signing is skipped when
+    /// not targeting macOS.
+    fn set_macos_signing(&mut self, options: &MacOsSigningOptions) -> Result<()>;
+
+    /// Define the sandbox of the binary when deployed as a Linux service.
+    ///
+    /// A systemd unit and a seccomp profile derived from `options` are
+    /// installed next to the binary. They are skipped when not targeting Linux.
+    fn set_linux_service(&mut self, options: &LinuxServiceOptions) -> Result<()>;
+
+    /// Define the directory files installed next to the binary are found in.
+    ///
+    /// `origin` is relative to the directory of the binary. Whoever installs
+    /// the binary is responsible for installing its extra files there.
+    fn set_resources_origin(&mut self, origin: &str) -> Result<()>;
+
+    /// Define the layout of Python modules installed next to the binary.
+    ///
+    /// `FilesystemModuleLayout::Pycache` installs sources and PEP 3147
+    /// `__pycache__` files the way `compileall` does, for tools expecting them.
+    fn set_filesystem_module_layout(&mut self, layout: FilesystemModuleLayout) -> Result<()>;
+
+    /// Configure the binary for debugging and coverage tools.
+    ///
+    /// Resources are installed next to the binary with the pycache layout,
+    /// bytecode isn't optimized, `sys.frozen` isn't set and environment
+    /// variables are honored. A `<name>.sourcemap.json` file mapping
+    /// installed module sources to the files they were read from is
+    /// installed next to the binary.
+    fn set_debug_build(&mut self) -> Result<()>;
+
+    /// Compress in-memory Python module sources and bytecode with zstd.
+    ///
+    /// Data is decompressed by the importer when a module is imported, so
+    /// rarely imported modules only take the space of their compressed data.
+    fn set_compress_resources(&mut self) -> Result<()>;
+
     /// Whether the binary requires the jemalloc library.
     fn requires_jemalloc(&self) -> bool;
 
+    /// Whether the binary links against a shared libpython.
+    ///
+    /// Otherwise, libpython is linked statically.
+    fn links_shared_libpython(&self) -> bool;
+
+    /// Count resources by location.
+    ///
+    /// Returns the number of resources loaded from memory and the number of
+    /// resources installed relative to the binary.
+    fn resource_location_counts(&self) -> (usize, usize);
+
+    /// Obtain the total size of the data of added resources, in bytes.
+    fn resources_size(&self) -> Result<u64>;
+
+    /// Estimate the disk space needed to build this binary, in bytes.
+    ///
+    /// This is the size of added resources and of the Python distribution.
+    fn estimated_build_size(&self) -> Result<u64>;
+
+    /// Find modules likely to break when imported from memory.
+    ///
+    /// Sources and bytecode of modules loaded from memory are searched for
+    /// patterns such as `__file__` references, which suggest the modules
+    /// expect to exist on the filesystem. Issues of standard library packages
+    /// are only returned if `include_stdlib` is true.
+    fn in_memory_import_issues(&self, include_stdlib: bool) -> Result<Vec<CompatibilityIssue>>;
+
+    /// Remove resources provided by the Python distribution.
+    ///
+    /// Modules and resources of packages of the standard library are
+    /// removed, for binaries loaded by an existing interpreter, which provides
+    /// its own standard library.
+    fn remove_distribution_resources(&mut self) -> Result<()>;
+
+    /// Obtain the files of a zip application running this binary's Python code.
+    ///
+    /// Zip applications are run by an existing Python interpreter, so
+    /// resources provided by the Python distribution aren't included. A
+    /// `__main__.py` running code like the embedded interpreter would is
+    /// added, unless a `__main__` module was added. Names of resources which
+    /// can't be imported from a zip file are returned as well.
+    fn zipapp_files(&self) -> Result<(FileManifest, BTreeSet<String>)>;
+
+    /// Obtain an `EmbeddedPythonResourcesData` instance from this one.
+    ///
+    /// Resources are packaged like by `as_embedded_python_binary_data()`,
+    /// but libpython isn't linked, so no compiler is needed.
+    fn as_embedded_python_resources_data(
+        &self,
+        logger: &slog::Logger,
+        bytecode_compiler_jobs: usize,
+        bytecode_compiler_in_process: bool,
+        bytecode_cache_path: Option<&Path>,
+    ) -> Result<EmbeddedPythonResourcesData>;
+
     /// Obtain an `EmbeddedPythonBinaryData` instance from this one.
+    ///
+    /// Bytecode is compiled by up to `bytecode_compiler_jobs` Python processes
+    /// in parallel or, if `bytecode_compiler_in_process` is true, by the
+    /// distribution's libpython loaded in this process. Compiled bytecode is
+    /// cached in `bytecode_cache_path`, if defined.
     fn as_embedded_python_binary_data(
         &self,
         logger: &slog::Logger,
         opt_level: &str,
+        bytecode_compiler_jobs: usize,
+        bytecode_compiler_in_process: bool,
+        bytecode_cache_path: Option<&Path>,
     ) -> Result<EmbeddedPythonBinaryData>;
 }
 
@@ -301,6 +543,12 @@ pub struct EmbeddedPythonBinaryPaths {
 
     /// Path to a file containing lines needed to be emitted by a Cargo build script.
     pub cargo_metadata: PathBuf,
+
+    /// Path to the Windows application manifest to embed in the binary.
+    pub windows_manifest: Option<PathBuf>,
+
+    /// Path to the entitlements to sign the macOS binary with.
+    pub macos_entitlements: Option<PathBuf>,
 }
 
 /// Represents resources to embed Python in a binary.
@@ -322,6 +570,12 @@ pub struct EmbeddedPythonBinaryData {
 
     /// Rust target triple for the target we are building for.
     pub target: String,
+
+    /// Settings of the Windows application manifest.
+    pub windows_manifest: WindowsManifestOptions,
+
+    /// How to sign the binary on macOS.
+    pub macos_signing: Option<MacOsSigningOptions>,
 }
 
 impl EmbeddedPythonBinaryData {
@@ -353,7 +607,11 @@ impl EmbeddedPythonBinaryData {
             None
         };
 
-        let config_rs_data = derive_python_config(&self.config, &embedded_resources);
+        let config_rs_data = derive_python_config(
+            &self.config,
+            &embedded_resources,
+            &derive_ctypes_library_map(&self.extra_files),
+        )?;
         let config_rs = dest_dir.join("default_python_config.rs");
         write_default_python_config_rs(&config_rs, &config_rs_data)?;
 
@@ -363,19 +621,39 @@ impl EmbeddedPythonBinaryData {
         // Tell Cargo where libpythonXY is located.
         cargo_metadata_lines.push(format!(
             "cargo:rustc-link-search=native={}",
-            dest_dir.display()
+            path_str(dest_dir)?
         ));
 
         // Give dependent crates the path to the default config file.
         cargo_metadata_lines.push(format!(
             "cargo:default-python-config-rs={}",
-            config_rs.display()
+            path_str(&config_rs)?
         ));
 
         let cargo_metadata = dest_dir.join("cargo_metadata.txt");
         let mut fh = File::create(&cargo_metadata)?;
         fh.write_all(cargo_metadata_lines.join("\n").as_bytes())?;
 
+        // Manifests are embedded by the MSVC linker.
+        let windows_manifest = if self.target.contains("pc-windows-msvc")
+            && self.windows_manifest.requires_custom_manifest()
+        {
+            let path = dest_dir.join("app.manifest");
+            std::fs::write(&path, self.windows_manifest.to_xml())?;
+            Some(path)
+        } else {
+            None
+        };
+
+        let macos_entitlements = match &self.macos_signing {
+            Some(signing) if self.target.contains("apple-darwin") => {
+                let path = dest_dir.join("entitlements.plist");
+                std::fs::write(&path, signing.to_entitlements_plist())?;
+                Some(path)
+            }
+            _ => None,
+        };
+
         Ok(EmbeddedPythonBinaryPaths {
             module_names,
             embedded_resources,
@@ -383,6 +661,42 @@ impl EmbeddedPythonBinaryData {
             libpyembeddedconfig,
             config_rs,
             cargo_metadata,
+            windows_manifest,
+            macos_entitlements,
         })
     }
 }
+
+/// Represents resources to embed Python in a binary built by another build system.
+///
+/// Unlike `EmbeddedPythonBinaryData`, no libpython is linked.
+pub struct EmbeddedPythonResourcesData {
+    /// The configuration for the embedded interpreter.
+    pub config: EmbeddedPythonConfig,
+
+    /// Python resources to embed in the binary.
+    pub resources: EmbeddedResourcesBlobs,
+
+    /// Extra files to install next to the binary.
+    pub extra_files: FileManifest,
+}
+
+impl EmbeddedPythonResourcesData {
+    /// Write out packed resources, the `pyembed` config and extra files.
+    pub fn write_files(&self, dest_dir: &Path) -> Result<()> {
+        let embedded_resources = dest_dir.join("packed-resources");
+        write_file_atomic(&embedded_resources, &self.resources.resources, false)?;
+
+        let config_rs_data = derive_python_config(
+            &self.config,
+            &embedded_resources,
+            &derive_ctypes_library_map(&self.extra_files),
+        )?;
+        write_default_python_config_rs(
+            &dest_dir.join("default_python_config.rs"),
+            &config_rs_data,
+        )?;
+
+        self.extra_files.write_to_path(dest_dir)
+    }
+}
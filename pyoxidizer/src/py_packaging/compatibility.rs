@@ -0,0 +1,445 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Detection of packages known to be incompatible with some build configurations.
+
+Some packages make assumptions about how they are installed that don't hold
+in embedded interpreters. e.g. `greenlet` switches C stacks from a compiled
+extension module and `gevent` monkey-patches standard library modules backed
+by extension modules. If those extension modules aren't packaged, the
+failure only manifests at run-time.
+
+This module defines the requirements of such packages and checks packaged
+resources against them.
+*/
+
+use {
+    python_packaging::python_source::FilesystemReference,
+    std::collections::{BTreeMap, BTreeSet},
+};
+
+/// Describes what a package needs in order to work in a built binary.
+pub struct KnownPackageRequirements {
+    /// Name of the top-level package or module.
+    pub package: &'static str,
+
+    /// Compiled extension modules providing the package's functionality.
+    ///
+    /// At least one must be packaged for the package to work.
+    pub extension_modules: &'static [&'static str],
+
+    /// Other modules (Python or extension) which must be packaged.
+    pub required_modules: &'static [&'static str],
+
+    /// `(extension module, dependency)` pairs of modules which are only
+    /// importable if the dependency is also packaged.
+    pub extension_module_dependencies: &'static [(&'static str, &'static str)],
+
+    /// Additional guidance for users of the package.
+    pub notes: &'static str,
+}
+
+/// Requirements of packages known to be sensitive to build configuration.
+pub const KNOWN_PACKAGE_REQUIREMENTS: &[KnownPackageRequirements] = &[
+    KnownPackageRequirements {
+        package: "greenlet",
+        extension_modules: &["greenlet", "greenlet._greenlet"],
+        required_modules: &[],
+        extension_module_dependencies: &[],
+        notes: "Stack switching is implemented in the compiled extension module. \
+                Other extension modules (such as gevent's) access it through the \
+                ``greenlet._C_API`` capsule, so greenlet must be importable before them.",
+    },
+    KnownPackageRequirements {
+        package: "gevent",
+        extension_modules: &[
+            "gevent.libev.corecext",
+            "gevent.libev._corecffi",
+            "gevent.libuv._corecffi",
+        ],
+        required_modules: &["greenlet", "_socket", "select", "_ssl"],
+        extension_module_dependencies: &[
+            ("gevent.libev._corecffi", "_cffi_backend"),
+            ("gevent.libuv._corecffi", "_cffi_backend"),
+        ],
+        notes: "Monkey-patching replaces ``socket``, ``select`` and ``ssl`` \
+                functionality, so the standard library extension modules backing \
+                them must not be excluded by ``extension_module_filter``.",
+    },
+];
+
+/// Describes a problem with a packaged package.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompatibilityIssue {
+    /// Name of the package the issue applies to.
+    pub package: String,
+
+    /// Human readable description of the issue.
+    pub message: String,
+}
+
+/// Check packaged modules against requirements of known packages.
+///
+/// `modules` holds names of Python modules and `extension_modules` holds
+/// names of extension modules available to the binary. `static_linking`
+/// denotes whether the Python distribution is statically linked, in which
+/// case extension modules can only be loaded if they are compiled into the
+/// binary.
+pub fn find_compatibility_issues(
+    modules: &BTreeSet<String>,
+    extension_modules: &BTreeSet<String>,
+    static_linking: bool,
+) -> Vec<CompatibilityIssue> {
+    let mut res = Vec::new();
+
+    for requirements in KNOWN_PACKAGE_REQUIREMENTS {
+        let package = requirements.package;
+
+        if !modules.contains(package) && !extension_modules.contains(package) {
+            continue;
+        }
+
+        let issue = |message: String| CompatibilityIssue {
+            package: package.to_string(),
+            message,
+        };
+
+        if !requirements
+            .extension_modules
+            .iter()
+            .any(|name| extension_modules.contains(*name))
+        {
+            res.push(issue(format!(
+                "{} is packaged without any of its compiled extension modules ({}); {}",
+                package,
+                requirements.extension_modules.join(", "),
+                if static_linking {
+                    format!(
+                        "the Python distribution is statically linked, so extension modules \
+                         must be compiled into the binary from source (e.g. `pip install \
+                         --no-binary {}`) instead of installed from binary wheels",
+                        package
+                    )
+                } else {
+                    "ensure the resources policy allows extension modules to be loaded".to_string()
+                }
+            )));
+        }
+
+        for name in requirements.required_modules {
+            if !modules.contains(*name) && !extension_modules.contains(*name) {
+                res.push(issue(format!(
+                    "{} requires the {} module, which is not packaged",
+                    package, name
+                )));
+            }
+        }
+
+        for (name, dependency) in requirements.extension_module_dependencies {
+            if extension_modules.contains(*name) && !extension_modules.contains(*dependency) {
+                res.push(issue(format!(
+                    "{} requires the {} extension module, which is not packaged",
+                    name, dependency
+                )));
+            }
+        }
+    }
+
+    res
+}
+
+/// Top-level packages known to not work when imported from memory.
+///
+/// These don't necessarily reference `__file__`, but expect their modules or
+/// data files to exist on the filesystem. e.g. `certifi.where()` returns the
+/// path of a bundled CA certificates file and `pkg_resources` scans `sys.path`
+/// entries on the filesystem.
+pub const IN_MEMORY_INCOMPATIBLE_PACKAGES: &[&str] =
+    &["certifi", "pip", "pkg_resources", "setuptools"];
+
+/// Find top-level packages which should be installed on the filesystem.
+///
+/// `modules` holds names of packaged Python modules and `dunder_file_modules`
+/// names of those referencing `__file__`. A top-level package is returned if
+/// it is known to not work from memory or if one of its modules references
+/// `__file__`, unless it is one of `stdlib_packages`: the standard library
+/// is tested from memory and references `__file__` in code paths which aren't
+/// relevant to applications, e.g. `lib2to3` and `pydoc_data`.
+pub fn find_in_memory_incompatible_packages(
+    modules: &BTreeSet<String>,
+    dunder_file_modules: &BTreeSet<String>,
+    stdlib_packages: &BTreeSet<String>,
+) -> BTreeSet<String> {
+    let top_level = |name: &String| name.split('.').next().unwrap_or_default().to_string();
+
+    modules
+        .iter()
+        .map(top_level)
+        .filter(|package| IN_MEMORY_INCOMPATIBLE_PACKAGES.contains(&package.as_str()))
+        .chain(
+            dunder_file_modules
+                .iter()
+                .map(top_level)
+                .filter(|package| !stdlib_packages.contains(package)),
+        )
+        .collect()
+}
+
+/// Find modules likely to break when imported from memory.
+///
+/// `in_memory_modules` holds names of Python modules loaded from memory and
+/// `references` the patterns found in their code which suggest they expect to
+/// exist on the filesystem. An issue is reported for every packaged top-level
+/// package known to not work from memory and for every module having
+/// patterns.
+pub fn find_in_memory_import_issues(
+    in_memory_modules: &BTreeSet<String>,
+    references: &BTreeMap<String, BTreeSet<FilesystemReference>>,
+) -> Vec<CompatibilityIssue> {
+    let top_level = |name: &str| name.split('.').next().unwrap_or_default().to_string();
+
+    let mut res = in_memory_modules
+        .iter()
+        .map(|name| top_level(name))
+        .filter(|package| IN_MEMORY_INCOMPATIBLE_PACKAGES.contains(&package.as_str()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|package| CompatibilityIssue {
+            message: format!(
+                "{} is known to not work when imported from memory; install it on the \
+                 filesystem with the location argument of add_python_resource() or the \
+                 prefer-in-memory-fallback-filesystem resources policy",
+                package
+            ),
+            package,
+        })
+        .collect::<Vec<_>>();
+
+    for (name, patterns) in references {
+        if !in_memory_modules.contains(name) {
+            continue;
+        }
+
+        res.push(CompatibilityIssue {
+            package: top_level(name),
+            message: format!(
+                "{} {}, which may not work when imported from memory",
+                name,
+                patterns
+                    .iter()
+                    .map(|pattern| pattern.description())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        });
+    }
+
+    res
+}
+
+/// Render documentation of the supported configuration for known packages.
+///
+/// The result is reStructuredText. `docs/packaging_known_packages.rst` is
+/// generated from it.
+pub fn supported_configuration_rst() -> String {
+    let mut lines = vec![
+        ".. _packaging_known_packages:".to_string(),
+        "".to_string(),
+        "=========================================".to_string(),
+        "Packages Sensitive to Build Configuration".to_string(),
+        "=========================================".to_string(),
+        "".to_string(),
+        ".. This file is generated by".to_string(),
+        "   ``pyoxidizer::py_packaging::compatibility::supported_configuration_rst()``."
+            .to_string(),
+        "   Do not edit it manually.".to_string(),
+        "".to_string(),
+        "Some packages only work if they are packaged in a specific way. When".to_string(),
+        "building a binary, PyOxidizer checks packaged resources against the".to_string(),
+        "requirements below and emits a warning for each requirement that isn't".to_string(),
+        "met.".to_string(),
+        "".to_string(),
+        "Compiled extension modules of third party packages must be loadable by".to_string(),
+        "the binary. With statically linked Python distributions (see".to_string(),
+        ":ref:`packaging_static_linking`), this means they must be compiled into the".to_string(),
+        "binary from source, as binary wheels don't contain the object files needed".to_string(),
+        "to do so.".to_string(),
+    ];
+
+    for requirements in KNOWN_PACKAGE_REQUIREMENTS {
+        let title = format!("``{}``", requirements.package);
+
+        lines.push("".to_string());
+        lines.push(title.clone());
+        lines.push("=".repeat(title.len()));
+        lines.push("".to_string());
+        lines.push("At least one of these extension modules must be packaged:".to_string());
+        lines.push("".to_string());
+        for name in requirements.extension_modules {
+            lines.push(format!("* ``{}``", name));
+        }
+
+        if !requirements.required_modules.is_empty() {
+            lines.push("".to_string());
+            lines.push("These modules must be packaged:".to_string());
+            lines.push("".to_string());
+            for name in requirements.required_modules {
+                lines.push(format!("* ``{}``", name));
+            }
+        }
+
+        if !requirements.extension_module_dependencies.is_empty() {
+            lines.push("".to_string());
+            lines.push("Extension modules with dependencies:".to_string());
+            lines.push("".to_string());
+            for (name, dependency) in requirements.extension_module_dependencies {
+                lines.push(format!("* ``{}`` requires ``{}``", name, dependency));
+            }
+        }
+
+        lines.push("".to_string());
+        lines.push(requirements.notes.to_string());
+    }
+
+    lines.push("".to_string());
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(values: &[&str]) -> BTreeSet<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_known_packages() {
+        assert!(
+            find_compatibility_issues(&names(&["foo"]), &names(&["_socket"]), false).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_in_memory_incompatible_packages() {
+        assert_eq!(
+            find_in_memory_incompatible_packages(
+                &names(&["certifi", "certifi.core", "foo", "foo.bar", "baz"]),
+                &names(&["foo.bar"]),
+                &BTreeSet::new(),
+            ),
+            names(&["certifi", "foo"])
+        );
+
+        assert!(find_in_memory_incompatible_packages(
+            &names(&["foo"]),
+            &BTreeSet::new(),
+            &BTreeSet::new()
+        )
+        .is_empty());
+
+        // Standard library packages referencing __file__ stay in memory.
+        assert_eq!(
+            find_in_memory_incompatible_packages(
+                &names(&["lib2to3", "lib2to3.pygram", "pydoc_data", "foo"]),
+                &names(&["lib2to3.pygram", "pydoc_data", "foo"]),
+                &names(&["lib2to3", "pydoc_data"]),
+            ),
+            names(&["foo"])
+        );
+    }
+
+    #[test]
+    fn test_in_memory_import_issues() {
+        let mut references = BTreeMap::new();
+        references.insert(
+            "foo.bar".to_string(),
+            vec![
+                FilesystemReference::DunderFile,
+                FilesystemReference::CtypesLibrary,
+            ]
+            .into_iter()
+            .collect(),
+        );
+        references.insert(
+            "filesystem".to_string(),
+            vec![FilesystemReference::DunderFile].into_iter().collect(),
+        );
+
+        let issues = find_in_memory_import_issues(
+            &names(&["certifi", "certifi.core", "foo", "foo.bar"]),
+            &references,
+        );
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| i.package.as_str())
+                .collect::<Vec<_>>(),
+            vec!["certifi", "foo"]
+        );
+        assert_eq!(
+            issues[1].message,
+            "foo.bar references __file__, loads shared libraries with ctypes, which may not \
+             work when imported from memory"
+        );
+    }
+
+    #[test]
+    fn test_greenlet_missing_extension() {
+        let issues = find_compatibility_issues(&names(&["greenlet"]), &BTreeSet::new(), true);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].package, "greenlet");
+        assert!(issues[0].message.contains("--no-binary greenlet"));
+
+        assert!(find_compatibility_issues(
+            &names(&["greenlet"]),
+            &names(&["greenlet._greenlet"]),
+            true
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_gevent() {
+        let modules = names(&["gevent", "gevent.monkey", "greenlet"]);
+        let extension_modules = names(&[
+            "greenlet._greenlet",
+            "gevent.libuv._corecffi",
+            "_socket",
+            "select",
+        ]);
+
+        let issues = find_compatibility_issues(&modules, &extension_modules, false);
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| i.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "gevent requires the _ssl module, which is not packaged",
+                "gevent.libuv._corecffi requires the _cffi_backend extension module, which is not packaged",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_supported_configuration_docs() {
+        let rst = supported_configuration_rst();
+        assert!(rst.contains("\n``gevent``\n==========\n"));
+
+        // Ensure the checked in documentation is up to date.
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("docs")
+            .join("packaging_known_packages.rst");
+
+        if path.exists() {
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), rst);
+        }
+    }
+}
@@ -0,0 +1,417 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Reading of conda environments.
+
+A conda environment installs Python packages in a `site-packages`
+directory like a virtualenv. Every conda package installed in the
+environment is described by a JSON file in its `conda-meta` directory,
+listing the files it installed relative to the environment.
+
+`noarch: python` packages are built once for all Python versions. Their
+files are listed under `site-packages/` in the package and are moved to the
+`site-packages` directory of the environment when installed.
+
+Extension modules of conda packages commonly load shared libraries
+installed by other conda packages, e.g. `_ssl` loads OpenSSL from `lib`.
+These libraries are found through the file lists in `conda-meta` and
+distributed with the extension modules loading them.
+*/
+
+use {
+    super::distribution::PythonDistribution,
+    super::packaging_tool::find_resources,
+    super::standalone_distribution::resolve_python_paths,
+    crate::analyze::find_shared_library_dependencies,
+    anyhow::{anyhow, Context, Result},
+    python_packaging::resource::{DataLocation, PythonResource},
+    slog::warn,
+    std::collections::BTreeMap,
+    std::path::{Path, PathBuf},
+};
+
+/// A conda package installed in an environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CondaPackage {
+    pub name: String,
+    pub version: String,
+
+    /// Whether this is a `noarch: python` package.
+    pub noarch_python: bool,
+
+    /// Paths of installed files, relative to the environment.
+    pub files: Vec<String>,
+}
+
+impl CondaPackage {
+    /// Parse a `conda-meta/*.json` file.
+    pub fn from_json(data: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(data)?;
+
+        let string = |key: &str| {
+            value
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or_else(|| anyhow!("{} is not defined", key))
+        };
+
+        // Depending on the conda version, this is `"python"` or `{"type": "python"}`.
+        let noarch = match value.get("noarch") {
+            Some(serde_json::Value::String(kind)) => Some(kind.as_str()),
+            Some(serde_json::Value::Object(noarch)) => noarch.get("type").and_then(|v| v.as_str()),
+            _ => None,
+        };
+        let package_type = value.get("package_type").and_then(|v| v.as_str());
+        let noarch_python = noarch == Some("python") || package_type == Some("noarch_python");
+
+        let files = match value.get("files") {
+            Some(serde_json::Value::Array(files)) => files
+                .iter()
+                .map(|f| {
+                    f.as_str()
+                        .map(|f| f.to_string())
+                        .ok_or_else(|| anyhow!("files must be strings"))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            name: string("name")?,
+            version: string("version")?,
+            noarch_python,
+            files,
+        })
+    }
+
+    /// Resolve the path of an installed file in an environment.
+    ///
+    /// Files of `noarch: python` packages may be recorded with their path in
+    /// the package, under `site-packages/`.
+    fn resolve_file(&self, env_path: &Path, site_packages: &Path, file: &str) -> PathBuf {
+        if self.noarch_python && file.starts_with("site-packages/") {
+            site_packages.join(&file["site-packages/".len()..])
+        } else {
+            env_path.join(file)
+        }
+    }
+}
+
+/// Whether a file name is that of a shared library.
+fn is_shared_library(file_name: &str) -> bool {
+    file_name.ends_with(".so")
+        || file_name.contains(".so.")
+        || file_name.ends_with(".dylib")
+        || file_name.to_lowercase().ends_with(".dll")
+}
+
+/// Find the shared libraries installed by conda packages, keyed by file name.
+///
+/// Shared libraries in `site-packages` are extension modules and are
+/// ignored.
+pub fn conda_shared_libraries(
+    packages: &[CondaPackage],
+    env_path: &Path,
+    site_packages: &Path,
+) -> BTreeMap<String, PathBuf> {
+    let mut res = BTreeMap::new();
+
+    for package in packages {
+        for file in &package.files {
+            let path = package.resolve_file(env_path, site_packages, file);
+
+            if path.starts_with(site_packages) {
+                continue;
+            }
+
+            if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
+                if is_shared_library(file_name) {
+                    res.insert(file_name.to_string(), path.clone());
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/// Resolve the shared libraries a binary loads from those available.
+///
+/// Libraries loaded by the resolved libraries are resolved as well.
+/// Libraries that aren't available, like system libraries, are ignored.
+fn resolve_shared_libraries(
+    data: &[u8],
+    available: &BTreeMap<String, PathBuf>,
+) -> Result<BTreeMap<String, DataLocation>> {
+    let mut res = BTreeMap::new();
+    let mut pending = find_shared_library_dependencies(data)?;
+
+    while let Some(name) = pending.pop() {
+        if res.contains_key(&name) {
+            continue;
+        }
+
+        if let Some(path) = available.get(&name) {
+            let data = std::fs::read(path).context(format!("reading {}", path.display()))?;
+            pending.extend(
+                find_shared_library_dependencies(&data)
+                    .context(format!("parsing {}", path.display()))?,
+            );
+
+            res.insert(name, DataLocation::Path(path.clone()));
+        }
+    }
+
+    Ok(res)
+}
+
+/// Read the packages installed in a conda environment.
+pub fn read_conda_meta(env_path: &Path) -> Result<Vec<CondaPackage>> {
+    let meta_path = env_path.join("conda-meta");
+
+    if !meta_path.is_dir() {
+        return Err(anyhow!(
+            "{} is not a conda environment: conda-meta directory not found",
+            env_path.display()
+        ));
+    }
+
+    let mut paths = std::fs::read_dir(&meta_path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    paths
+        .iter()
+        .filter(|path| path.extension() == Some(std::ffi::OsStr::new("json")))
+        .map(|path| {
+            let data =
+                std::fs::read_to_string(path).context(format!("reading {}", path.display()))?;
+
+            CondaPackage::from_json(&data).context(format!("parsing {}", path.display()))
+        })
+        .collect()
+}
+
+/// Discover Python resources from a conda environment.
+///
+/// The environment must have the same Python major and minor version as
+/// the distribution, as extension modules are built for it. Extension
+/// modules carry the shared libraries of the environment they load.
+pub fn read_conda_env(
+    logger: &slog::Logger,
+    dist: &dyn PythonDistribution,
+    env_path: &Path,
+    strict: bool,
+) -> Result<Vec<PythonResource>> {
+    let packages = read_conda_meta(env_path)?;
+
+    let python_version = dist.python_major_minor_version();
+
+    match packages.iter().find(|p| p.name == "python") {
+        Some(python)
+            if python.version == python_version
+                || python.version.starts_with(&format!("{}.", python_version)) => {}
+        Some(python) => {
+            return Err(anyhow!(
+                "conda environment has Python {}; distribution has Python {}",
+                python.version,
+                python_version
+            ))
+        }
+        None => {
+            return Err(anyhow!(
+                "Python isn't installed in conda environment {}",
+                env_path.display()
+            ))
+        }
+    }
+
+    let python_paths = resolve_python_paths(env_path, &python_version);
+    let site_packages = &python_paths.site_packages;
+
+    warn!(
+        logger,
+        "found {} conda packages ({} noarch) in {}",
+        packages.len(),
+        packages.iter().filter(|p| p.noarch_python).count(),
+        env_path.display()
+    );
+
+    // Files removed from site-packages behind conda's back, e.g. by
+    // `pip uninstall`, indicate an environment conda doesn't describe.
+    for package in &packages {
+        let missing = package
+            .files
+            .iter()
+            .map(|file| package.resolve_file(env_path, site_packages, file))
+            .filter(|path| path.starts_with(site_packages) && !path.exists())
+            .count();
+
+        if missing > 0 {
+            warn!(
+                logger,
+                "{} files of conda package {} {} are missing from {}",
+                missing,
+                package.name,
+                package.version,
+                site_packages.display()
+            );
+        }
+    }
+
+    let libraries = conda_shared_libraries(&packages, env_path, site_packages);
+
+    find_resources(logger, dist, site_packages, None, strict)?
+        .into_iter()
+        .map(|resource| match resource {
+            PythonResource::ExtensionModuleDynamicLibrary(mut em) => {
+                if let Some(data) = &em.extension_data {
+                    em.shared_libraries = resolve_shared_libraries(&data.resolve()?, &libraries)
+                        .context(format!("resolving shared libraries of {}", em.name))?;
+                }
+
+                for name in em.shared_libraries.keys() {
+                    warn!(logger, "{} loads shared library {}", em.name, name);
+                }
+
+                Ok(PythonResource::ExtensionModuleDynamicLibrary(em))
+            }
+            resource => Ok(resource),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conda_package_from_json() -> Result<()> {
+        let package = CondaPackage::from_json(
+            r#"{
+                "name": "six",
+                "version": "1.15.0",
+                "noarch": {"type": "python"},
+                "files": [
+                    "site-packages/six.py",
+                    "lib/python3.8/site-packages/six-1.15.0.dist-info/METADATA"
+                ]
+            }"#,
+        )?;
+
+        assert_eq!(package.name, "six");
+        assert_eq!(package.version, "1.15.0");
+        assert!(package.noarch_python);
+
+        let env = Path::new("/env");
+        let site_packages = Path::new("/env/lib/python3.8/site-packages");
+        assert_eq!(
+            package.resolve_file(env, site_packages, &package.files[0]),
+            site_packages.join("six.py")
+        );
+        assert_eq!(
+            package.resolve_file(env, site_packages, &package.files[1]),
+            site_packages.join("six-1.15.0.dist-info/METADATA")
+        );
+
+        let package = CondaPackage::from_json(
+            r#"{"name": "numpy", "version": "1.19.1", "noarch": null, "files": []}"#,
+        )?;
+        assert!(!package.noarch_python);
+
+        assert!(CondaPackage::from_json(r#"{"version": "1.0"}"#).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_conda_meta() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        assert!(read_conda_meta(temp_dir.path()).is_err());
+
+        let meta = temp_dir.path().join("conda-meta");
+        std::fs::create_dir(&meta)?;
+        std::fs::write(meta.join("history"), "")?;
+        std::fs::write(
+            meta.join("python-3.8.3-hcff3b4d_0.json"),
+            r#"{"name": "python", "version": "3.8.3", "files": ["bin/python3.8"]}"#,
+        )?;
+
+        let packages = read_conda_meta(temp_dir.path())?;
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "python");
+        assert_eq!(packages[0].files, vec!["bin/python3.8".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conda_shared_libraries() -> Result<()> {
+        let package = CondaPackage::from_json(
+            r#"{
+                "name": "openssl",
+                "version": "1.1.1g",
+                "files": [
+                    "include/openssl/ssl.h",
+                    "lib/libssl.so",
+                    "lib/libssl.so.1.1",
+                    "lib/libcrypto.1.1.dylib",
+                    "Library/bin/libssl-1_1-x64.DLL",
+                    "lib/python3.8/site-packages/_ssl.cpython-38-x86_64-linux-gnu.so"
+                ]
+            }"#,
+        )?;
+
+        let env = Path::new("/env");
+        let site_packages = Path::new("/env/lib/python3.8/site-packages");
+
+        assert_eq!(
+            conda_shared_libraries(&[package], env, site_packages)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "libcrypto.1.1.dylib".to_string(),
+                    env.join("lib/libcrypto.1.1.dylib")
+                ),
+                (
+                    "libssl-1_1-x64.DLL".to_string(),
+                    env.join("Library/bin/libssl-1_1-x64.DLL")
+                ),
+                ("libssl.so".to_string(), env.join("lib/libssl.so")),
+                ("libssl.so.1.1".to_string(), env.join("lib/libssl.so.1.1")),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_shared_libraries() -> Result<()> {
+        let exe = std::env::current_exe()?;
+        let data = std::fs::read(&exe)?;
+
+        let depends = find_shared_library_dependencies(&data)?;
+        let name = depends.first().expect("test binary loads shared libraries");
+
+        assert!(resolve_shared_libraries(&data, &BTreeMap::new())?.is_empty());
+
+        // Stand in for the library with the test binary, which loads it again.
+        let mut available = BTreeMap::new();
+        available.insert(name.clone(), exe.clone());
+
+        assert_eq!(
+            resolve_shared_libraries(&data, &available)?
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![(name.clone(), DataLocation::Path(exe))]
+        );
+
+        Ok(())
+    }
+}
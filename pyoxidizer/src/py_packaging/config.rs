@@ -6,6 +6,12 @@
 Configuring a Python interpreter.
 */
 
+use {
+    anyhow::{anyhow, Result},
+    python_packaging::package_metadata::PythonPackageEntryPoint,
+    std::collections::BTreeMap,
+};
+
 /// Determine the default raw allocator for a target triple.
 pub fn default_raw_allocator(target_triple: &str) -> RawAllocator {
     // Jemalloc doesn't work on Windows.
@@ -30,9 +36,85 @@ pub enum RawAllocator {
 pub enum RunMode {
     Noop,
     Repl,
-    Module { module: String },
-    Eval { code: String },
-    File { path: String },
+    Module {
+        module: String,
+    },
+    Eval {
+        code: String,
+    },
+    File {
+        path: String,
+    },
+    /// Run an entry point of a package distribution.
+    ///
+    /// Entry points are resolved to other run modes when building binaries.
+    EntryPoint {
+        group: String,
+        name: String,
+    },
+}
+
+impl RunMode {
+    /// Resolve an entry point run mode to the code running the entry point.
+    ///
+    /// `entry_points` holds entry points of package distributions, keyed by
+    /// package name. Other run modes are returned unmodified.
+    pub fn resolve_entry_point(
+        &self,
+        entry_points: &BTreeMap<String, Vec<PythonPackageEntryPoint>>,
+    ) -> Result<RunMode> {
+        let (group, name) = match self {
+            RunMode::EntryPoint { group, name } => (group, name),
+            _ => return Ok(self.clone()),
+        };
+
+        let candidates = entry_points
+            .iter()
+            .flat_map(|(package, entry_points)| {
+                entry_points
+                    .iter()
+                    .filter(|ep| &ep.group == group && &ep.name == name)
+                    .map(move |ep| (package, ep))
+            })
+            .collect::<Vec<_>>();
+
+        let entry_point = match candidates.as_slice() {
+            [(_, entry_point)] => entry_point,
+            [] => {
+                return Err(anyhow!(
+                    "entry point {}:{} not found in package distributions",
+                    group,
+                    name
+                ))
+            }
+            _ => {
+                return Err(anyhow!(
+                    "entry point {}:{} is defined by multiple package distributions: {}",
+                    group,
+                    name,
+                    candidates
+                        .iter()
+                        .map(|(package, _)| package.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        };
+
+        Ok(match &entry_point.attr {
+            Some(attr) => RunMode::Eval {
+                code: format!(
+                    "import sys\nfrom {} import {}\nsys.exit({}())",
+                    entry_point.module,
+                    attr.split('.').next().unwrap_or_default(),
+                    attr
+                ),
+            },
+            None => RunMode::Module {
+                module: entry_point.module.clone(),
+            },
+        })
+    }
 }
 
 /// How the `terminfo` database is resolved at run-time.
@@ -46,6 +128,10 @@ pub enum TerminfoResolution {
 #[derive(Clone, Debug, PartialEq)]
 pub struct EmbeddedPythonConfig {
     pub bytes_warning: i32,
+    pub ctypes_find_library_redirect: bool,
+    pub debugpy_allow_remote_hosts: bool,
+    pub debugpy_listen_env: Option<String>,
+    pub extract_shared_libraries_to_cache: bool,
     pub ignore_environment: bool,
     pub inspect: bool,
     pub interactive: bool,
@@ -53,15 +139,25 @@ pub struct EmbeddedPythonConfig {
     pub legacy_windows_fs_encoding: bool,
     pub legacy_windows_stdio: bool,
     pub optimize_level: i64,
+    pub origin: Option<String>,
     pub parser_debug: bool,
+    pub python_executable_env: Option<String>,
     pub stdio_encoding_name: Option<String>,
     pub stdio_encoding_errors: Option<String>,
     pub unbuffered_stdio: bool,
     pub filesystem_importer: bool,
     pub quiet: bool,
     pub raw_allocator: RawAllocator,
+    pub raw_allocator_stats: bool,
+    pub remote_chunk_fetcher: Option<String>,
+    pub remote_resources_url: Option<String>,
+    pub resources_verification_key: Option<[u8; 32]>,
     pub run_mode: RunMode,
     pub site_import: bool,
+    pub startup_cache_module: Option<String>,
+    pub startup_error_catalog: BTreeMap<String, String>,
+    pub startup_error_message: Option<String>,
+    pub startup_progress_callback: Option<String>,
     pub sys_frozen: bool,
     pub sys_meipass: bool,
     pub sys_paths: Vec<String>,
@@ -70,13 +166,19 @@ pub struct EmbeddedPythonConfig {
     pub user_site_directory: bool,
     pub verbose: i32,
     pub write_bytecode: bool,
+    pub write_memory_stats_env: Option<String>,
     pub write_modules_directory_env: Option<String>,
+    pub zipimport_archive: Option<String>,
 }
 
 impl Default for EmbeddedPythonConfig {
     fn default() -> Self {
         EmbeddedPythonConfig {
             bytes_warning: 0,
+            ctypes_find_library_redirect: false,
+            debugpy_allow_remote_hosts: false,
+            debugpy_listen_env: None,
+            extract_shared_libraries_to_cache: false,
             ignore_environment: true,
             inspect: false,
             interactive: false,
@@ -84,7 +186,9 @@ impl Default for EmbeddedPythonConfig {
             legacy_windows_fs_encoding: false,
             legacy_windows_stdio: false,
             optimize_level: 0,
+            origin: None,
             parser_debug: false,
+            python_executable_env: None,
             quiet: false,
             stdio_encoding_name: None,
             stdio_encoding_errors: None,
@@ -93,15 +197,25 @@ impl Default for EmbeddedPythonConfig {
             verbose: 0,
             filesystem_importer: false,
             site_import: false,
+            startup_cache_module: None,
+            startup_error_catalog: BTreeMap::new(),
+            startup_error_message: None,
+            startup_progress_callback: None,
             sys_frozen: false,
             sys_meipass: false,
             sys_paths: Vec::new(),
             raw_allocator: RawAllocator::System,
+            raw_allocator_stats: false,
+            remote_chunk_fetcher: None,
+            remote_resources_url: None,
+            resources_verification_key: None,
             run_mode: RunMode::Repl,
             terminfo_resolution: TerminfoResolution::None,
             user_site_directory: false,
             write_bytecode: false,
+            write_memory_stats_env: None,
             write_modules_directory_env: None,
+            zipimport_archive: None,
         }
     }
 }
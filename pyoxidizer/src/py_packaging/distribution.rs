@@ -9,24 +9,28 @@ Defining and manipulating Python distributions.
 use {
     super::binary::PythonBinaryBuilder,
     super::config::EmbeddedPythonConfig,
+    super::download::{download_to_path, get_http_client},
+    super::fixture_distribution::{write_fixture_distribution, FIXTURE_PYTHON_VERSION},
     super::standalone_distribution::{DistributionExtensionModule, StandaloneDistribution},
-    crate::python_distributions::PYTHON_DISTRIBUTIONS,
+    super::wheel_tags::WheelCompatibilityTags,
+    crate::build_metrics::record_distribution_cache_lookup,
+    crate::python_distributions::{PythonDistributionCollection, PYTHON_DISTRIBUTIONS},
     anyhow::{anyhow, Context, Result},
     fs2::FileExt,
     python_packaging::bytecode::BytecodeCompiler,
     python_packaging::module_util::PythonModuleSuffixes,
-    python_packaging::resource::{PythonModuleSource, PythonPackageResource, PythonResource},
+    python_packaging::resource::{
+        DataLocation, PythonModuleSource, PythonPackageResource, PythonResource,
+    },
     python_packaging::resource_collection::PythonResourcesPolicy,
     sha2::{Digest, Sha256},
     slog::warn,
     std::collections::HashMap,
     std::convert::TryFrom,
-    std::fs,
     std::fs::{create_dir_all, File},
     std::io::Read,
     std::path::{Path, PathBuf},
     url::Url,
-    uuid::Uuid,
 };
 
 const STDLIB_TEST_PACKAGES: &[&str] = &[
@@ -56,6 +60,20 @@ pub fn is_stdlib_test_package(name: &str) -> bool {
     false
 }
 
+/// Obtain a name describing a resource in messages.
+pub fn resource_display_name(resource: &PythonResource) -> String {
+    let data = match resource {
+        PythonResource::EggFile(egg) => &egg.data,
+        PythonResource::PathExtension(pth) => &pth.data,
+        _ => return resource.full_name(),
+    };
+
+    match data {
+        DataLocation::Path(path) => path.display().to_string(),
+        DataLocation::Memory(_) => "<memory>".to_string(),
+    }
+}
+
 /// Denotes methods to filter extension modules.
 #[derive(Clone, Debug, PartialEq)]
 pub enum ExtensionModuleFilter {
@@ -81,8 +99,18 @@ impl TryFrom<&str> for ExtensionModuleFilter {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum PythonDistributionLocation {
-    Local { local_path: String, sha256: String },
-    Url { url: String, sha256: String },
+    Local {
+        local_path: String,
+        sha256: String,
+    },
+    Url {
+        url: String,
+        sha256: String,
+    },
+    /// A fixture distribution generated for a target triple.
+    Fixture {
+        target_triple: String,
+    },
 }
 
 /// Describes an obtainable Python distribution.
@@ -91,6 +119,9 @@ pub struct PythonDistributionRecord {
     /// Where the distribution can be obtained from.
     pub location: PythonDistributionLocation,
 
+    /// Python major and minor version of the distribution, e.g. `3.8`.
+    pub python_major_minor_version: String,
+
     /// Rust target triple this distribution runs on.
     pub target_triple: String,
 
@@ -104,6 +135,10 @@ pub trait PythonDistribution {
     fn clone_box(&self) -> Box<dyn PythonDistribution>;
 
     /// Obtain the filesystem path to a `python` executable for this distribution.
+    ///
+    /// The executable runs on the build machine. For distributions targeting
+    /// another platform, it may belong to a distribution of the same Python
+    /// version for the build machine.
     fn python_exe_path(&self) -> &Path;
 
     /// Obtain the X.Y Python version component. e.g. `3.7`.
@@ -112,6 +147,12 @@ pub trait PythonDistribution {
     /// Obtain the cache tag to apply to Python bytecode modules.
     fn cache_tag(&self) -> &str;
 
+    /// Obtain the Rust target triple this distribution runs on.
+    fn target_triple(&self) -> &str;
+
+    /// Obtain the tags of wheels compatible with this distribution.
+    fn wheel_compatibility_tags(&self) -> Result<WheelCompatibilityTags>;
+
     /// Obtain file suffixes for various Python module flavors.
     fn python_module_suffixes(&self) -> Result<PythonModuleSuffixes>;
 
@@ -124,6 +165,15 @@ pub trait PythonDistribution {
     /// from a Python distribution. Using the returned `PythonBinaryBuilder` instance,
     /// you can manipulate resources, etc and then eventually build a new executable
     /// with it.
+    ///
+    /// `allow_in_memory_shared_library_loading` controls whether extension modules
+    /// defined as shared libraries can be loaded from memory on platforms supporting
+    /// it. This feature has known caveats and must be explicitly enabled.
+    ///
+    /// `extract_shared_libraries_to_cache` controls whether extension modules
+    /// defined as shared libraries are embedded in the binary on targets that
+    /// can't load them from memory. These libraries are extracted to a per-user
+    /// cache directory at run-time and loaded from there.
     #[allow(clippy::too_many_arguments)]
     fn as_python_executable_builder(
         &self,
@@ -138,6 +188,8 @@ pub trait PythonDistribution {
         include_sources: bool,
         include_resources: bool,
         include_test: bool,
+        allow_in_memory_shared_library_loading: bool,
+        extract_shared_libraries_to_cache: bool,
     ) -> Result<Box<dyn PythonBinaryBuilder>>;
 
     /// Obtain extension modules matching a specified filter and variant selection preferences.
@@ -177,27 +229,27 @@ pub trait PythonDistribution {
     /// We will throw away resources that aren't compatible with us. For
     /// example, on statically linked Windows distributions, dynamically linked
     /// extension module files are ignored.
+    ///
+    /// If `strict` is true, incompatible resources are an error instead.
     fn filter_compatible_python_resources(
         &self,
         logger: &slog::Logger,
         resources: &[PythonResource],
+        strict: bool,
     ) -> Result<Vec<PythonResource>>;
 }
 
 /// Multiple threads or processes could race to extract the archive.
-/// So we use a lock file to ensure exclusive access.
-/// TODO use more granular lock based on the output directory (possibly
-/// by putting lock in output directory itself).
+/// So we use a lock file in the output directory to ensure exclusive access.
+/// Different distributions can be extracted concurrently.
 pub struct DistributionExtractLock {
     file: std::fs::File,
 }
 
 impl DistributionExtractLock {
     pub fn new(extract_dir: &Path) -> Result<Self> {
-        let lock_path = extract_dir
-            .parent()
-            .unwrap()
-            .join("distribution-extract-lock");
+        create_dir_all(extract_dir).context(format!("creating {}", extract_dir.display()))?;
+        let lock_path = extract_dir.join("distribution-extract-lock");
 
         let file = File::create(&lock_path)
             .context(format!("could not create {}", lock_path.display()))?;
@@ -233,36 +285,17 @@ fn sha256_path(path: &PathBuf) -> Vec<u8> {
     hasher.result().to_vec()
 }
 
-pub fn get_http_client() -> reqwest::Result<reqwest::blocking::Client> {
-    let mut builder = reqwest::blocking::ClientBuilder::new();
-
-    for (key, value) in std::env::vars() {
-        let key = key.to_lowercase();
-        if key.ends_with("_proxy") {
-            let end = key.len() - "_proxy".len();
-            let schema = &key[..end];
-
-            if let Ok(url) = Url::parse(&value) {
-                if let Some(proxy) = match schema {
-                    "http" => Some(reqwest::Proxy::http(url.as_str())),
-                    "https" => Some(reqwest::Proxy::https(url.as_str())),
-                    _ => None,
-                } {
-                    if let Ok(proxy) = proxy {
-                        builder = builder.proxy(proxy);
-                    }
-                }
-            }
-        }
-    }
-
-    builder.build()
-}
-
 /// Ensure a Python distribution at a URL is available in a local directory.
 ///
-/// The path to the downloaded and validated file is returned.
-pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> Result<PathBuf> {
+/// The path to the downloaded and validated file is returned. If `offline`
+/// is true, the file must already be in `cache_dir`.
+pub fn download_distribution(
+    logger: &slog::Logger,
+    url: &str,
+    sha256: &str,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<PathBuf> {
     let expected_hash = hex::decode(sha256)?;
     let u = Url::parse(url)?;
 
@@ -280,51 +313,41 @@ pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> Resul
 
         // We don't care about timing side-channels from the string compare.
         if file_hash == expected_hash {
+            record_distribution_cache_lookup(true);
             return Ok(cache_path);
         }
     }
 
-    let mut data: Vec<u8> = Vec::new();
-
-    println!("downloading {}", u);
-    let client = get_http_client()?;
-    let mut response = client.get(u.as_str()).send()?;
-    response.read_to_end(&mut data)?;
-
-    let mut hasher = Sha256::new();
-    hasher.input(&data);
+    record_distribution_cache_lookup(false);
 
-    let url_hash = hasher.result().to_vec();
-    if url_hash != expected_hash {
-        return Err(anyhow!("sha256 of Python distribution does not validate"));
+    if offline {
+        return Err(anyhow!(
+            "{} is not available at {} and downloading is disabled in offline mode",
+            url,
+            cache_path.display()
+        ));
     }
 
-    let mut temp_cache_path = cache_path.clone();
-    temp_cache_path.set_file_name(format!("{}.tmp", Uuid::new_v4()));
-
-    fs::write(&temp_cache_path, data).context("unable to write distribution file")?;
-
-    fs::rename(&temp_cache_path, &cache_path)
-        .or_else(|e| -> Result<()> {
-            fs::remove_file(&temp_cache_path)
-                .context("unable to remove temporary distribution file")?;
-
-            if cache_path.exists() {
-                download_distribution(url, sha256, cache_dir)?;
-                return Ok(());
-            }
-
-            Err(e.into())
-        })
-        .context("unable to rename downloaded distribution file")?;
+    let client = get_http_client()?;
+    download_to_path(logger, &client, u.as_str(), sha256, &cache_path)?;
 
     Ok(cache_path)
 }
 
+/// Ensure a Python distribution at a local path is available in a local directory.
+///
+/// The file is copied under a lock, so concurrent copies to the same
+/// `cache_dir` don't observe a partially written file.
 pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -> Result<PathBuf> {
     let expected_hash = hex::decode(sha256)?;
     let basename = path.file_name().unwrap().to_str().unwrap().to_string();
-    let cache_path = cache_dir.join(basename);
+    let cache_path = cache_dir.join(&basename);
+
+    let lock_path = cache_dir.join(format!("{}.lock", basename));
+    let lock =
+        File::create(&lock_path).context(format!("could not create {}", lock_path.display()))?;
+    lock.lock_exclusive()
+        .context(format!("failed to obtain lock for {}", lock_path.display()))?;
 
     if cache_path.exists() {
         let file_hash = sha256_path(&cache_path);
@@ -345,7 +368,11 @@ pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -
     }
 
     println!("copying {}", path.display());
-    std::fs::copy(path, &cache_path)?;
+    let partial_path = cache_dir.join(format!("{}.partial", basename));
+    std::fs::copy(path, &partial_path)?;
+    std::fs::rename(&partial_path, &cache_path)?;
+
+    lock.unlock()?;
 
     Ok(cache_path)
 }
@@ -359,9 +386,12 @@ pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -
 /// in ``cache_dir``, it will be verified and returned.
 ///
 /// Local filesystem paths are preferred over remote URLs if both are defined.
+/// If `offline` is true, remote URLs are not fetched.
 pub fn resolve_python_distribution_archive(
+    logger: &slog::Logger,
     dist: &PythonDistributionLocation,
     cache_dir: &Path,
+    offline: bool,
 ) -> Result<PathBuf> {
     if !cache_dir.exists() {
         create_dir_all(cache_dir).unwrap();
@@ -373,33 +403,53 @@ pub fn resolve_python_distribution_archive(
             copy_local_distribution(&p, sha256, cache_dir)
         }
         PythonDistributionLocation::Url { url, sha256 } => {
-            download_distribution(url, sha256, cache_dir)
+            download_distribution(logger, url, sha256, cache_dir, offline)
         }
+        PythonDistributionLocation::Fixture { .. } => Err(anyhow!(
+            "fixture distributions are generated and have no archive"
+        )),
     }
 }
 
 /// Resolve a Python distribution archive.
 ///
-/// Returns a tuple of (archive path, extract directory).
+/// Returns a tuple of (archive path, extract directory). Both are in a
+/// directory of `distributions_dir` named after the SHA-256 of the archive,
+/// so `distributions_dir` can be shared by different projects.
 pub fn resolve_python_distribution_from_location(
     logger: &slog::Logger,
     location: &PythonDistributionLocation,
     distributions_dir: &Path,
+    offline: bool,
 ) -> Result<(PathBuf, PathBuf)> {
     warn!(logger, "resolving Python distribution {:?}", location);
-    let path = resolve_python_distribution_archive(location, distributions_dir)?;
-    warn!(
-        logger,
-        "Python distribution available at {}",
-        path.display()
-    );
 
     let distribution_hash = match location {
         PythonDistributionLocation::Local { sha256, .. } => sha256,
         PythonDistributionLocation::Url { sha256, .. } => sha256,
+        PythonDistributionLocation::Fixture { .. } => {
+            return Err(anyhow!(
+                "fixture distributions are generated and have no archive"
+            ))
+        }
     };
 
-    let distribution_path = distributions_dir.join(format!("python.{}", &distribution_hash[0..12]));
+    // The hash names a directory, so it mustn't be able to point elsewhere.
+    if distribution_hash.len() != 64 || !distribution_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!(
+            "invalid SHA-256 of Python distribution: {}",
+            distribution_hash
+        ));
+    }
+
+    let distribution_path = distributions_dir.join(distribution_hash);
+
+    let path = resolve_python_distribution_archive(logger, location, &distribution_path, offline)?;
+    warn!(
+        logger,
+        "Python distribution available at {}",
+        path.display()
+    );
 
     Ok((path, distribution_path))
 }
@@ -415,6 +465,11 @@ pub enum DistributionFlavor {
 
     /// Dynamically linked distributions coming from the `python-build-standalone` project.
     StandaloneDynamic,
+
+    /// Synthetic distributions for evaluating configurations in tests.
+    ///
+    /// See the `fixture_distribution` module.
+    Fixture,
 }
 
 impl Default for DistributionFlavor {
@@ -425,39 +480,174 @@ impl Default for DistributionFlavor {
 
 /// Obtain a `PythonDistribution` implementation of a flavor and from a location.
 ///
-/// The distribution will be written to `dest_dir`.
+/// The distribution will be written to `dest_dir`. If `offline` is true, a
+/// distribution at a URL must already be in `dest_dir`.
 pub fn resolve_distribution(
     logger: &slog::Logger,
     flavor: &DistributionFlavor,
     location: &PythonDistributionLocation,
     dest_dir: &Path,
+    offline: bool,
 ) -> Result<Box<dyn PythonDistribution>> {
+    Ok(Box::new(resolve_standalone_distribution(
+        logger, flavor, location, dest_dir, offline,
+    )?) as Box<dyn PythonDistribution>)
+}
+
+/// Resolve a distribution able to run build-time operations on a build machine.
+///
+/// A distribution targeting another platform than `host_triple` can't run
+/// its own `python`. The default distribution of the same Python version for
+/// `host_triple` among `distributions` then installs packages and compiles
+/// bytecode (see `host_distribution_location()`). If there is no such
+/// distribution, the distribution's own `python` is used, which works when
+/// the build machine can run it, e.g. for `musl` distributions on Linux.
+pub fn resolve_distribution_for_host(
+    logger: &slog::Logger,
+    flavor: &DistributionFlavor,
+    location: &PythonDistributionLocation,
+    distributions: &PythonDistributionCollection,
+    host_triple: &str,
+    dest_dir: &Path,
+    offline: bool,
+) -> Result<Box<dyn PythonDistribution>> {
+    let mut dist = resolve_standalone_distribution(logger, flavor, location, dest_dir, offline)?;
+
+    if dist.target_triple == host_triple {
+        return Ok(Box::new(dist) as Box<dyn PythonDistribution>);
+    }
+
+    let version = dist.python_major_minor_version();
+
+    match host_distribution_location(distributions, flavor, host_triple, &version) {
+        Ok(host_location) => {
+            warn!(
+                logger,
+                "using Python {} distribution for {} to build for {}",
+                version,
+                host_triple,
+                dist.target_triple
+            );
+            let host =
+                resolve_standalone_distribution(logger, flavor, &host_location, dest_dir, offline)?;
+            dist.set_host_distribution(host)?;
+        }
+        Err(e) => {
+            warn!(
+                logger,
+                "building for {} with its own Python distribution: {}", dist.target_triple, e
+            );
+        }
+    }
+
+    Ok(Box::new(dist) as Box<dyn PythonDistribution>)
+}
+
+/// Find the default distribution for `host_triple` running build-time operations.
+///
+/// Only the Python version must match the target distribution: how
+/// `libpython` is linked doesn't matter for running `python`. A distribution
+/// of the same flavor is preferred, but any standalone distribution of
+/// `python_major_minor_version` is accepted.
+pub fn host_distribution_location(
+    distributions: &PythonDistributionCollection,
+    flavor: &DistributionFlavor,
+    host_triple: &str,
+    python_major_minor_version: &str,
+) -> Result<PythonDistributionLocation> {
+    let version = Some(python_major_minor_version);
+
+    match default_distribution_location(distributions, flavor, host_triple, version) {
+        Ok(location) => Ok(location),
+        Err(e) => match flavor {
+            DistributionFlavor::StandaloneStatic | DistributionFlavor::StandaloneDynamic => {
+                default_distribution_location(
+                    distributions,
+                    &DistributionFlavor::Standalone,
+                    host_triple,
+                    version,
+                )
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+fn resolve_standalone_distribution(
+    logger: &slog::Logger,
+    flavor: &DistributionFlavor,
+    location: &PythonDistributionLocation,
+    dest_dir: &Path,
+    offline: bool,
+) -> Result<StandaloneDistribution> {
     // TODO is there a way we can define PythonDistribution::from_location()
-    Ok(match flavor {
-        DistributionFlavor::Standalone => Box::new(StandaloneDistribution::from_location(
-            logger, &location, dest_dir,
-        )?) as Box<dyn PythonDistribution>,
-
-        DistributionFlavor::StandaloneStatic => Box::new(StandaloneDistribution::from_location(
-            logger, &location, dest_dir,
-        )?) as Box<dyn PythonDistribution>,
-
-        DistributionFlavor::StandaloneDynamic => Box::new(StandaloneDistribution::from_location(
-            logger, &location, dest_dir,
-        )?) as Box<dyn PythonDistribution>,
-    })
+    match flavor {
+        DistributionFlavor::Standalone
+        | DistributionFlavor::StandaloneStatic
+        | DistributionFlavor::StandaloneDynamic => {
+            StandaloneDistribution::from_location(logger, &location, dest_dir, offline)
+        }
+
+        DistributionFlavor::Fixture => match location {
+            PythonDistributionLocation::Fixture { target_triple } => {
+                StandaloneDistribution::from_directory(&write_fixture_distribution(
+                    dest_dir,
+                    target_triple,
+                )?)
+            }
+            _ => Err(anyhow!(
+                "fixture distributions can't be obtained from {:?}",
+                location
+            )),
+        },
+    }
 }
 
 /// Resolve the location of the default Python distribution of a given flavor and build target.
+///
+/// `distributions` holds the known distributions. `python_version` is the major and minor version of Python the
+/// distribution must provide. If not defined, the first registered
+/// distribution is used.
 pub fn default_distribution_location(
+    distributions: &PythonDistributionCollection,
     flavor: &DistributionFlavor,
     target: &str,
+    python_version: Option<&str>,
 ) -> Result<PythonDistributionLocation> {
-    let dist = PYTHON_DISTRIBUTIONS
-        .find_distribution(target, flavor)
-        .ok_or_else(|| anyhow!("could not find default Python distribution for {}", target))?;
+    if *flavor == DistributionFlavor::Fixture {
+        return match python_version {
+            Some(version) if !FIXTURE_PYTHON_VERSION.starts_with(&format!("{}.", version)) => {
+                Err(anyhow!(
+                    "fixture distributions provide Python {}; got {}",
+                    FIXTURE_PYTHON_VERSION,
+                    version
+                ))
+            }
+            _ => Ok(PythonDistributionLocation::Fixture {
+                target_triple: target.to_string(),
+            }),
+        };
+    }
 
-    Ok(dist.location)
+    match distributions.find_distribution(target, flavor, python_version) {
+        Some(dist) => Ok(dist.location),
+        None => {
+            let versions = distributions.python_versions(target, flavor);
+
+            match (python_version, versions.is_empty()) {
+                (Some(version), false) => Err(anyhow!(
+                    "could not find default Python {} distribution for {}; available versions: {}",
+                    version,
+                    target,
+                    versions.join(", ")
+                )),
+                _ => Err(anyhow!(
+                    "could not find default Python distribution for {}",
+                    target
+                )),
+            }
+        }
+    }
 }
 
 /// Resolve the default Python distribution for a build target.
@@ -473,9 +663,9 @@ pub fn default_distribution(
     target: &str,
     dest_dir: &Path,
 ) -> Result<Box<dyn PythonDistribution>> {
-    let location = default_distribution_location(flavor, target)?;
+    let location = default_distribution_location(&PYTHON_DISTRIBUTIONS, flavor, target, None)?;
 
-    resolve_distribution(logger, flavor, &location, dest_dir)
+    resolve_distribution(logger, flavor, &location, dest_dir, false)
 }
 
 /// Obtain the crc32 of a filesystem path.
@@ -541,4 +731,212 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_default_fixture_distribution() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let dist = default_distribution(
+            &logger,
+            &DistributionFlavor::Fixture,
+            "x86_64-pc-windows-msvc",
+            temp_dir.path(),
+        )?;
+        assert_eq!(dist.python_major_minor_version(), "3.8");
+        assert!(dist
+            .source_modules()?
+            .iter()
+            .any(|module| module.name == "json.decoder"));
+
+        assert!(default_distribution_location(
+            &PYTHON_DISTRIBUTIONS,
+            &DistributionFlavor::Fixture,
+            "x86_64-pc-windows-msvc",
+            Some("3.7"),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_distribution_for_host() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let host = resolve_distribution(
+            &logger,
+            &DistributionFlavor::Fixture,
+            &PythonDistributionLocation::Fixture {
+                target_triple: "x86_64-unknown-linux-gnu".to_string(),
+            },
+            temp_dir.path(),
+            false,
+        )?;
+
+        let dist = resolve_distribution_for_host(
+            &logger,
+            &DistributionFlavor::Fixture,
+            &PythonDistributionLocation::Fixture {
+                target_triple: "x86_64-pc-windows-msvc".to_string(),
+            },
+            &PYTHON_DISTRIBUTIONS,
+            "x86_64-unknown-linux-gnu",
+            temp_dir.path(),
+            false,
+        )?;
+        assert_eq!(dist.target_triple(), "x86_64-pc-windows-msvc");
+        assert_eq!(dist.python_exe_path(), host.python_exe_path());
+
+        // Distributions for the build machine run their own python.
+        let dist = resolve_distribution_for_host(
+            &logger,
+            &DistributionFlavor::Fixture,
+            &PythonDistributionLocation::Fixture {
+                target_triple: "x86_64-unknown-linux-gnu".to_string(),
+            },
+            &PYTHON_DISTRIBUTIONS,
+            "x86_64-unknown-linux-gnu",
+            temp_dir.path(),
+            false,
+        )?;
+        assert_eq!(dist.python_exe_path(), host.python_exe_path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_distribution_location() -> Result<()> {
+        // The build machine only has a dynamic distribution.
+        assert_eq!(
+            host_distribution_location(
+                &PYTHON_DISTRIBUTIONS,
+                &DistributionFlavor::StandaloneStatic,
+                "x86_64-unknown-linux-gnu",
+                "3.8",
+            )?,
+            default_distribution_location(
+                &PYTHON_DISTRIBUTIONS,
+                &DistributionFlavor::StandaloneDynamic,
+                "x86_64-unknown-linux-gnu",
+                Some("3.8"),
+            )?
+        );
+
+        // The build machine only has a static distribution.
+        assert_eq!(
+            host_distribution_location(
+                &PYTHON_DISTRIBUTIONS,
+                &DistributionFlavor::StandaloneDynamic,
+                "x86_64-unknown-linux-musl",
+                "3.8",
+            )?,
+            default_distribution_location(
+                &PYTHON_DISTRIBUTIONS,
+                &DistributionFlavor::StandaloneStatic,
+                "x86_64-unknown-linux-musl",
+                Some("3.8"),
+            )?
+        );
+
+        assert!(host_distribution_location(
+            &PYTHON_DISTRIBUTIONS,
+            &DistributionFlavor::StandaloneDynamic,
+            "x86_64-unknown-linux-gnu",
+            "2.7",
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_distribution_offline() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let res = download_distribution(
+            &logger,
+            "https://example.com/python.tar.zst",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            temp_dir.path(),
+            true,
+        );
+
+        assert!(res.unwrap_err().to_string().contains("offline mode"));
+        assert!(!temp_dir.path().join("python.tar.zst").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_local_distribution_keyed_by_sha256() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let source_path = temp_dir.path().join("python.tar.zst");
+        std::fs::write(&source_path, b"distribution")?;
+        let sha256 = hex::encode(Sha256::digest(b"distribution"));
+
+        let location = PythonDistributionLocation::Local {
+            local_path: source_path.display().to_string(),
+            sha256: sha256.clone(),
+        };
+        let cache_dir = temp_dir.path().join("cache");
+
+        let (archive_path, extract_path) =
+            resolve_python_distribution_from_location(&logger, &location, &cache_dir, true)?;
+
+        assert_eq!(extract_path, cache_dir.join(&sha256));
+        assert_eq!(archive_path, extract_path.join("python.tar.zst"));
+        assert_eq!(std::fs::read(&archive_path)?, b"distribution");
+
+        // Resolving again reuses the cached archive.
+        std::fs::remove_file(&source_path)?;
+        resolve_python_distribution_from_location(&logger, &location, &cache_dir, true)?;
+
+        // Hashes name directories of the cache.
+        for sha256 in &[
+            "../../etc".to_string(),
+            sha256[0..63].to_string(),
+            format!("{}/", &sha256[0..63]),
+        ] {
+            let location = PythonDistributionLocation::Local {
+                local_path: source_path.display().to_string(),
+                sha256: sha256.clone(),
+            };
+
+            assert!(resolve_python_distribution_from_location(
+                &logger, &location, &cache_dir, true
+            )
+            .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_distribution_location_python_version() -> Result<()> {
+        let target = env!("HOST");
+        let flavor = DistributionFlavor::Standalone;
+
+        assert_eq!(
+            default_distribution_location(&PYTHON_DISTRIBUTIONS, &flavor, target, Some("3.8"))?,
+            default_distribution_location(&PYTHON_DISTRIBUTIONS, &flavor, target, None)?
+        );
+
+        let err =
+            default_distribution_location(&PYTHON_DISTRIBUTIONS, &flavor, target, Some("2.7"))
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "could not find default Python 2.7 distribution for {}; available versions: 3.8",
+                target
+            )
+        );
+
+        Ok(())
+    }
 }
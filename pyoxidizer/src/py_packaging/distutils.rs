@@ -7,6 +7,7 @@ Interacting with distutils.
 */
 
 use {
+    crate::path_util::{join_search_paths, path_str},
     anyhow::{Context, Result},
     lazy_static::lazy_static,
     python_packaging::resource::DataLocation,
@@ -97,18 +98,16 @@ pub fn prepare_hacked_distutils(
     let state_dir = dest_dir.join("pyoxidizer-build-state");
     create_dir_all(&state_dir)?;
 
-    let mut python_paths = vec![extra_sys_path.display().to_string()];
-    python_paths.extend(extra_python_paths.iter().map(|p| p.display().to_string()));
+    let mut python_paths = vec![extra_sys_path.as_path()];
+    python_paths.extend(extra_python_paths.iter().copied());
 
-    let path_separator = if cfg!(windows) { ";" } else { ":" };
-
-    let python_path = python_paths.join(path_separator);
+    let python_path = join_search_paths(python_paths)?;
 
     let mut res = HashMap::new();
     res.insert("PYTHONPATH".to_string(), python_path);
     res.insert(
         "PYOXIDIZER_DISTUTILS_STATE_DIR".to_string(),
-        state_dir.display().to_string(),
+        path_str(&state_dir)?.to_string(),
     );
     res.insert("PYOXIDIZER".to_string(), "1".to_string());
 
@@ -194,6 +193,7 @@ pub fn read_built_extensions(state_dir: &Path) -> Result<Vec<PythonExtensionModu
             is_package: final_name == "__init__",
             libraries: info.libraries,
             library_dirs: info.library_dirs.iter().map(PathBuf::from).collect(),
+            shared_libraries: BTreeMap::new(),
         });
     }
 
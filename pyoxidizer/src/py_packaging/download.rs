@@ -0,0 +1,575 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Downloading files over HTTP.
+
+Python distributions are tens of megabytes and are often fetched through
+slow or unreliable networks. Downloads are written to a `.partial` file next
+to their destination. When a request fails, it is retried with exponential
+backoff and resumes from the data already received using an HTTP range
+request. The `.partial` file is kept when all attempts fail, so the next
+invocation resumes the download too.
+
+Progress is reported through the logger. Failed downloads are described by
+the details of the returned error. See `crate::error_details`.
+*/
+
+use {
+    crate::error_details::{detailed_error, redact_url_credentials},
+    anyhow::{anyhow, Context, Result},
+    fs2::FileExt,
+    reqwest::header::{CONTENT_RANGE, RANGE},
+    reqwest::StatusCode,
+    sha2::{Digest, Sha256},
+    slog::warn,
+    std::fs::{File, OpenOptions},
+    std::io::{Read, Write},
+    std::path::{Path, PathBuf},
+    std::time::Duration,
+    url::Url,
+};
+
+/// Number of times a download is attempted before giving up.
+pub const DOWNLOAD_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a download.
+///
+/// The delay doubles after each failed attempt.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Width of progress bars, in characters.
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// Amount of data between progress reports when the size is unknown.
+const PROGRESS_UNKNOWN_SIZE_INTERVAL: u64 = 10_000_000;
+
+/// Obtain an HTTP client honoring `*_proxy` environment variables.
+pub fn get_http_client() -> reqwest::Result<reqwest::blocking::Client> {
+    let mut builder = reqwest::blocking::ClientBuilder::new();
+
+    for (key, value) in std::env::vars() {
+        let key = key.to_lowercase();
+        if key.ends_with("_proxy") {
+            let end = key.len() - "_proxy".len();
+            let schema = &key[..end];
+
+            if let Ok(url) = Url::parse(&value) {
+                if let Some(proxy) = match schema {
+                    "http" => Some(reqwest::Proxy::http(url.as_str())),
+                    "https" => Some(reqwest::Proxy::https(url.as_str())),
+                    _ => None,
+                } {
+                    if let Ok(proxy) = proxy {
+                        builder = builder.proxy(proxy);
+                    }
+                }
+            }
+        }
+    }
+
+    builder.build()
+}
+
+/// Delay before retrying a download after `attempt` failed attempts.
+pub fn retry_delay(attempt: u32) -> Duration {
+    INITIAL_RETRY_DELAY * 2u32.pow(attempt.saturating_sub(1))
+}
+
+/// Render a progress bar for `downloaded` bytes out of `total`.
+pub fn progress_bar(downloaded: u64, total: u64) -> String {
+    let fraction = if total == 0 {
+        1.0
+    } else {
+        (downloaded as f64 / total as f64).min(1.0)
+    };
+    let filled = (fraction * PROGRESS_BAR_WIDTH as f64) as usize;
+
+    format!(
+        "[{}{}] {:>3}% {:.1}/{:.1} MB",
+        "#".repeat(filled),
+        " ".repeat(PROGRESS_BAR_WIDTH - filled),
+        (fraction * 100.0) as u64,
+        downloaded as f64 / 1_000_000.0,
+        total as f64 / 1_000_000.0
+    )
+}
+
+/// Reports progress of a download through a logger.
+///
+/// A report is emitted each time another tenth of the download completes.
+struct DownloadProgress<'a> {
+    logger: &'a slog::Logger,
+    name: String,
+    total: Option<u64>,
+    next_report: u64,
+}
+
+impl<'a> DownloadProgress<'a> {
+    fn new(logger: &'a slog::Logger, name: &str, total: Option<u64>, downloaded: u64) -> Self {
+        let mut progress = DownloadProgress {
+            logger,
+            name: name.to_string(),
+            total,
+            next_report: 0,
+        };
+        progress.next_report = progress.next_threshold(downloaded);
+
+        progress
+    }
+
+    fn interval(&self) -> u64 {
+        match self.total {
+            Some(total) => std::cmp::max(total / 10, 1),
+            None => PROGRESS_UNKNOWN_SIZE_INTERVAL,
+        }
+    }
+
+    fn next_threshold(&self, downloaded: u64) -> u64 {
+        (downloaded / self.interval() + 1) * self.interval()
+    }
+
+    fn update(&mut self, downloaded: u64) {
+        if downloaded < self.next_report {
+            return;
+        }
+
+        self.next_report = self.next_threshold(downloaded);
+
+        match self.total {
+            Some(total) => warn!(
+                self.logger,
+                "{} {}",
+                self.name,
+                progress_bar(downloaded, total)
+            ),
+            None => warn!(
+                self.logger,
+                "{} {:.1} MB",
+                self.name,
+                downloaded as f64 / 1_000_000.0
+            ),
+        }
+    }
+}
+
+/// Outcome of a failed download attempt.
+enum AttemptError {
+    /// The download may succeed if attempted again.
+    Retryable(anyhow::Error),
+    /// The download can't succeed.
+    Fatal(anyhow::Error),
+}
+
+/// A request failed with an HTTP status.
+#[derive(Debug)]
+struct HttpStatusError(StatusCode);
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "HTTP error: {}", self.0)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Whether a failed request with an HTTP status may succeed later.
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Attempt to download `url` into `partial_path`, resuming from its content.
+fn download_attempt(
+    logger: &slog::Logger,
+    client: &reqwest::blocking::Client,
+    url: &str,
+    name: &str,
+    partial_path: &Path,
+) -> std::result::Result<(), AttemptError> {
+    let mut offset = match std::fs::metadata(partial_path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    let mut request = client.get(url);
+    if offset > 0 {
+        warn!(logger, "resuming download of {} at byte {}", name, offset);
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+
+    let mut response = request
+        .send()
+        .map_err(|e| AttemptError::Retryable(e.into()))?;
+    let status = response.status();
+
+    if offset > 0 && status == StatusCode::PARTIAL_CONTENT {
+        let content_range = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+
+        if !content_range.starts_with(&format!("bytes {}-", offset)) {
+            let _ = std::fs::remove_file(partial_path);
+            return Err(AttemptError::Retryable(anyhow!(
+                "server returned unexpected range {:?}",
+                content_range
+            )));
+        }
+    } else if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+        let _ = std::fs::remove_file(partial_path);
+        return Err(AttemptError::Retryable(anyhow!(
+            "server cannot resume download at byte {}",
+            offset
+        )));
+    } else if status.is_success() {
+        if offset > 0 {
+            warn!(logger, "server does not support resuming downloads");
+            offset = 0;
+        }
+    } else if is_retryable_status(status) {
+        return Err(AttemptError::Retryable(HttpStatusError(status).into()));
+    } else {
+        return Err(AttemptError::Fatal(HttpStatusError(status).into()));
+    }
+
+    let mut fh = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(partial_path)
+        .map_err(|e| AttemptError::Fatal(e.into()))?;
+
+    let total = response.content_length().map(|length| offset + length);
+    let mut progress = DownloadProgress::new(logger, name, total, offset);
+    let mut downloaded = offset;
+    let mut buffer = [0; 65536];
+
+    loop {
+        let count = response
+            .read(&mut buffer)
+            .map_err(|e| AttemptError::Retryable(e.into()))?;
+        if count == 0 {
+            break;
+        }
+
+        fh.write_all(&buffer[..count])
+            .map_err(|e| AttemptError::Fatal(e.into()))?;
+        downloaded += count as u64;
+        progress.update(downloaded);
+    }
+
+    match total {
+        Some(total) if downloaded < total => Err(AttemptError::Retryable(anyhow!(
+            "connection closed after {} of {} bytes",
+            downloaded,
+            total
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Obtain the SHA-256 of a file.
+fn sha256_file(path: &Path) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    let mut fh = File::open(path)?;
+    let mut buffer = [0; 65536];
+
+    loop {
+        let count = fh.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.input(&buffer[..count]);
+    }
+
+    Ok(hasher.result().to_vec())
+}
+
+/// Describe a download of `url` that failed after `attempts` attempts.
+///
+/// Credentials are redacted from the URL, including where errors from the
+/// HTTP client mention it.
+fn download_error(
+    logger: &slog::Logger,
+    url: &str,
+    attempts: u32,
+    error: anyhow::Error,
+) -> anyhow::Error {
+    let url = redact_url_credentials(url);
+    let status = error
+        .downcast_ref::<HttpStatusError>()
+        .map(|e| e.0.as_u16());
+
+    detailed_error(
+        logger,
+        format!(
+            "downloading {} failed after {} attempts: {}",
+            url,
+            attempts,
+            redact_url_credentials(&error.to_string())
+        ),
+        serde_json::json!({
+            "operation": "download",
+            "url": url,
+            "attempts": attempts,
+            "status": status,
+            "error": redact_url_credentials(&format!("{:#}", error)),
+        }),
+    )
+}
+
+/// Download a URL to a path, verifying its SHA-256.
+///
+/// Failed requests are retried up to `DOWNLOAD_ATTEMPTS` times and resume
+/// from data already downloaded. Concurrent downloads to the same path wait
+/// for each other. `path` isn't modified unless the download validates.
+pub fn download_to_path(
+    logger: &slog::Logger,
+    client: &reqwest::blocking::Client,
+    url: &str,
+    sha256: &str,
+    path: &Path,
+) -> Result<()> {
+    let expected_hash = hex::decode(sha256)?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file path", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let sibling =
+        |suffix: &str| -> PathBuf { path.with_file_name(format!("{}{}", file_name, suffix)) };
+
+    let lock_path = sibling(".lock");
+    let lock =
+        File::create(&lock_path).context(format!("could not create {}", lock_path.display()))?;
+    lock.lock_exclusive()
+        .context(format!("failed to obtain lock for {}", lock_path.display()))?;
+
+    let result = (|| -> Result<()> {
+        // Another process may have downloaded the file while we waited.
+        if path.exists() && sha256_file(path)? == expected_hash {
+            return Ok(());
+        }
+
+        let partial_path = sibling(".partial");
+
+        warn!(logger, "downloading {}", redact_url_credentials(url));
+        let mut attempt = 1;
+        loop {
+            match download_attempt(logger, client, url, &file_name, &partial_path) {
+                Ok(()) => break,
+                Err(AttemptError::Fatal(e)) => {
+                    return Err(download_error(logger, url, attempt, e));
+                }
+                Err(AttemptError::Retryable(e)) if attempt >= DOWNLOAD_ATTEMPTS => {
+                    return Err(download_error(logger, url, attempt, e));
+                }
+                Err(AttemptError::Retryable(e)) => {
+                    let delay = retry_delay(attempt);
+                    warn!(
+                        logger,
+                        "downloading {} failed: {}; retrying in {}s",
+                        file_name,
+                        redact_url_credentials(&e.to_string()),
+                        delay.as_secs()
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+            }
+        }
+
+        if sha256_file(&partial_path)? != expected_hash {
+            std::fs::remove_file(&partial_path)?;
+            return Err(anyhow!(
+                "sha256 of {} does not validate",
+                redact_url_credentials(url)
+            ));
+        }
+
+        std::fs::rename(&partial_path, path).context(format!(
+            "unable to rename downloaded file to {}",
+            path.display()
+        ))?;
+
+        Ok(())
+    })();
+
+    lock.unlock()?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::error_details::DetailedError,
+        std::io::{BufRead, BufReader},
+        std::net::TcpListener,
+    };
+
+    /// Serve `data` for two requests, closing the first one halfway.
+    ///
+    /// Returns the URL and a handle resolving to the `Range` header of the
+    /// second request.
+    fn serve_interrupted(data: Vec<u8>) -> (String, std::thread::JoinHandle<Option<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.tar.zst", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            let mut range = None;
+
+            for (i, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if line.to_lowercase().starts_with("range:") {
+                        range = Some(line[6..].trim().to_string());
+                    }
+                }
+
+                let half = data.len() / 2;
+                if i == 0 {
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        data.len()
+                    )
+                    .unwrap();
+                    stream.write_all(&data[..half]).unwrap();
+                } else {
+                    write!(
+                        stream,
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\
+                         Content-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                        data.len() - half,
+                        half,
+                        data.len() - 1,
+                        data.len()
+                    )
+                    .unwrap();
+                    stream.write_all(&data[half..]).unwrap();
+                }
+            }
+
+            range
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+        assert_eq!(retry_delay(4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(
+            progress_bar(25_000_000, 50_000_000),
+            format!("[{}{}]  50% 25.0/50.0 MB", "#".repeat(15), " ".repeat(15))
+        );
+        assert_eq!(
+            progress_bar(0, 0),
+            format!("[{}] 100% 0.0/0.0 MB", "#".repeat(30))
+        );
+    }
+
+    #[test]
+    fn test_download_resumes() -> Result<()> {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let dest = temp_dir.path().join("file.tar.zst");
+
+        let data = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut hasher = Sha256::new();
+        hasher.input(&data);
+        let sha256 = hex::encode(hasher.result());
+
+        let client = reqwest::blocking::ClientBuilder::new().no_proxy().build()?;
+        let (url, handle) = serve_interrupted(data.clone());
+
+        download_to_path(&logger, &client, &url, &sha256, &dest)?;
+
+        assert_eq!(handle.join().unwrap(), Some("bytes=50000-".to_string()));
+        assert_eq!(std::fs::read(&dest)?, data);
+        assert!(!temp_dir.path().join("file.tar.zst.partial").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_bad_hash() -> Result<()> {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let dest = temp_dir.path().join("file.tar.zst");
+
+        let client = reqwest::blocking::ClientBuilder::new().no_proxy().build()?;
+        let (url, handle) = serve_interrupted(b"hello, world".to_vec());
+
+        assert!(download_to_path(&logger, &client, &url, &"00".repeat(32), &dest).is_err());
+        handle.join().unwrap();
+        assert!(!dest.exists());
+        assert!(!temp_dir.path().join("file.tar.zst.partial").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_http_error() -> Result<()> {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let dest = temp_dir.path().join("file.tar.zst");
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://user:secret@{}/file.tar.zst", listener.local_addr()?);
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+        });
+
+        let client = reqwest::blocking::ClientBuilder::new().no_proxy().build()?;
+        let err = download_to_path(&logger, &client, &url, &"00".repeat(32), &dest).unwrap_err();
+        handle.join().unwrap();
+
+        let message = format!("{:?}", err);
+        assert!(message.contains("404"));
+        assert!(!message.contains("secret"));
+        assert!(!message.contains("\"operation\""));
+
+        let details = err.downcast_ref::<DetailedError>().unwrap().details();
+        assert_eq!(details["operation"], "download");
+        assert_eq!(details["status"], 404);
+        assert_eq!(details["attempts"], 1);
+        assert!(!details.to_string().contains("secret"));
+
+        Ok(())
+    }
+}
@@ -7,24 +7,40 @@ Embedded Python resources in a binary.
 */
 
 use {
+    super::compatibility::{
+        find_in_memory_import_issues, find_in_memory_incompatible_packages, CompatibilityIssue,
+    },
     super::filtering::{filter_btreemap, resolve_resource_names_from_files},
     super::standalone_distribution::DistributionExtensionModule,
     crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::otlp::build_phase,
     anyhow::{anyhow, Result},
+    python_packaging::bytecode::BytecodeCompilerBackend,
+    python_packaging::package_metadata::{PythonPackageEntryPoint, PythonPackageRecord},
     python_packaging::resource::{
-        DataLocation, PythonExtensionModule, PythonModuleBytecodeFromSource, PythonModuleSource,
-        PythonPackageDistributionResource, PythonPackageResource,
+        DataLocation, PythonExtensionModule, PythonModuleBytecode, PythonModuleBytecodeFromSource,
+        PythonModuleSource, PythonPackageDistributionResource, PythonPackageResource,
     },
     python_packaging::resource_collection::{
-        PreparedPythonResources, PythonResourceCollector, PythonResourcesPolicy,
+        FilesystemModuleLayout, PrePackagedResource, PreparedPythonResources,
+        PythonResourceCollector, PythonResourcesPolicy,
     },
+    python_packed_resources::chunking::ChunkingParameters,
+    python_packed_resources::data::ResourceFlavor,
     slog::{info, warn},
+    std::borrow::Cow,
     std::collections::{BTreeMap, BTreeSet},
     std::io::Write,
     std::iter::FromIterator,
-    std::path::Path,
+    std::path::{Path, PathBuf},
 };
 
+/// zstd compression level of compressed in-memory module data.
+///
+/// Data is compressed once at build time and decompression speed doesn't
+/// depend on the level, so favor a small binary over a fast build.
+const RESOURCES_COMPRESSION_LEVEL: i32 = 19;
+
 /// Holds state necessary to link an extension module into libpython.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ExtensionModuleBuildState {
@@ -58,6 +74,7 @@ pub struct ExtensionModuleBuildState {
 pub struct PrePackagedResources {
     collector: PythonResourceCollector,
     extension_module_states: BTreeMap<String, ExtensionModuleBuildState>,
+    stdlib_packages: BTreeSet<String>,
 }
 
 impl PrePackagedResources {
@@ -65,9 +82,91 @@ impl PrePackagedResources {
         Self {
             collector: PythonResourceCollector::new(policy, cache_tag),
             extension_module_states: BTreeMap::new(),
+            stdlib_packages: BTreeSet::new(),
         }
     }
 
+    /// Define the top-level packages of the Python standard library.
+    ///
+    /// These are never moved to the filesystem because they reference
+    /// `__file__`.
+    pub fn set_stdlib_packages(&mut self, packages: BTreeSet<String>) {
+        self.stdlib_packages = packages;
+    }
+
+    /// Count resources in this instance by location.
+    ///
+    /// Returns the number of resources loaded from memory and the number of
+    /// resources installed relative to the binary.
+    pub fn count_resources_by_location(&self) -> (usize, usize) {
+        self.collector.count_resources_by_location()
+    }
+
+    /// Obtain the total size of the data of resources in this instance, in bytes.
+    pub fn resources_size(&self) -> Result<u64> {
+        self.collector.resources_size()
+    }
+
+    /// Find modules likely to break when imported from memory.
+    ///
+    /// Issues of standard library packages are filtered out unless
+    /// `include_stdlib` is true.
+    pub fn in_memory_import_issues(&self, include_stdlib: bool) -> Result<Vec<CompatibilityIssue>> {
+        Ok(find_in_memory_import_issues(
+            &self.collector.get_in_memory_module_names(),
+            &self.collector.find_filesystem_references()?,
+        )
+        .into_iter()
+        .filter(|issue| include_stdlib || !self.stdlib_packages.contains(&issue.package))
+        .collect())
+    }
+
+    /// Obtain a named resource in this instance.
+    ///
+    /// Extension modules linked into the binary are not returned.
+    pub fn get_resource(&self, name: &str) -> Option<&PrePackagedResource> {
+        self.collector.get_resource(name)
+    }
+
+    /// Derive the files of a zip application from resources in this instance.
+    ///
+    /// See `PythonResourceCollector::derive_zipapp_files()`. Extension modules
+    /// linked into the binary can't be imported from a zip file either, so
+    /// they are returned as skipped.
+    pub fn derive_zipapp_files(
+        &self,
+        exclude_packages: &BTreeSet<String>,
+    ) -> Result<(BTreeMap<PathBuf, DataLocation>, BTreeSet<String>)> {
+        let (files, mut skipped) = self.collector.derive_zipapp_files(exclude_packages)?;
+
+        skipped.extend(
+            self.extension_module_states
+                .keys()
+                .filter(|name| {
+                    !exclude_packages.contains(name.split('.').next().unwrap_or_default())
+                })
+                .cloned(),
+        );
+
+        Ok((files, skipped))
+    }
+
+    /// Remove resources of top-level packages, e.g. of the standard library.
+    pub fn remove_packages(&mut self, packages: &BTreeSet<String>) -> Result<()> {
+        let top_level = |name: &str| name.split('.').next().unwrap_or_default().to_string();
+
+        self.collector
+            .filter_resources_mut(|resource| !packages.contains(&top_level(&resource.name)))?;
+
+        let states = std::mem::replace(&mut self.extension_module_states, BTreeMap::new());
+        self.extension_module_states = states
+            .into_iter()
+            .filter(|(name, _)| !packages.contains(&top_level(name)))
+            .collect();
+
+        Ok(())
+    }
+
     /// Obtain `PythonModuleSource` in this instance.
     pub fn get_in_memory_module_sources(&self) -> BTreeMap<String, PythonModuleSource> {
         self.collector.get_in_memory_module_sources()
@@ -78,6 +177,70 @@ impl PrePackagedResources {
         self.collector.get_in_memory_package_resources()
     }
 
+    /// Obtain package distributions and their versions.
+    pub fn get_package_distributions(&self) -> BTreeMap<String, String> {
+        self.collector.get_package_distributions().clone()
+    }
+
+    /// Obtain parsed `RECORD` files of package distributions.
+    pub fn get_package_records(&self) -> Result<BTreeMap<String, PythonPackageRecord>> {
+        self.collector.get_package_records()
+    }
+
+    /// Obtain entry points declared by package distributions.
+    pub fn get_package_entry_points(
+        &self,
+    ) -> Result<BTreeMap<String, Vec<PythonPackageEntryPoint>>> {
+        self.collector.get_package_entry_points()
+    }
+
+    /// Define the layout of Python modules installed on the filesystem.
+    pub fn set_filesystem_module_layout(&mut self, layout: FilesystemModuleLayout) {
+        self.collector.set_filesystem_module_layout(layout)
+    }
+
+    /// Obtain the original paths of module sources installed on the filesystem.
+    pub fn get_filesystem_module_source_paths(&self) -> BTreeMap<PathBuf, PathBuf> {
+        self.collector.get_filesystem_module_source_paths()
+    }
+
+    /// Obtain names of Python modules in this instance.
+    ///
+    /// Extension modules are not included.
+    pub fn get_module_names(&self) -> BTreeSet<String> {
+        BTreeSet::from_iter(
+            self.collector
+                .get_resource_flavors()
+                .into_iter()
+                .filter_map(|(name, flavor)| {
+                    if flavor == ResourceFlavor::Module {
+                        Some(name)
+                    } else {
+                        None
+                    }
+                }),
+        )
+    }
+
+    /// Obtain names of extension modules in this instance.
+    ///
+    /// This includes extension modules linked into the binary and extension
+    /// modules loaded from shared libraries.
+    pub fn get_extension_module_names(&self) -> BTreeSet<String> {
+        let mut res = BTreeSet::from_iter(self.extension_module_states.keys().cloned());
+
+        for (name, flavor) in self.collector.get_resource_flavors() {
+            match flavor {
+                ResourceFlavor::Extension | ResourceFlavor::BuiltinExtensionModule => {
+                    res.insert(name);
+                }
+                _ => {}
+            }
+        }
+
+        res
+    }
+
     /// Add a source module to the collection of embedded source modules.
     pub fn add_in_memory_module_source(&mut self, module: &PythonModuleSource) -> Result<()> {
         self.collector.add_in_memory_python_module_source(module)
@@ -112,6 +275,24 @@ impl PrePackagedResources {
             .add_relative_path_python_module_bytecode_from_source(module, prefix)
     }
 
+    /// Add compiled bytecode of a module to be loaded from memory.
+    pub fn add_in_memory_compiled_module_bytecode(
+        &mut self,
+        module: &PythonModuleBytecode,
+    ) -> Result<()> {
+        self.collector.add_in_memory_python_module_bytecode(module)
+    }
+
+    /// Add compiled bytecode of a module to be loaded from the filesystem relative to some entity.
+    pub fn add_relative_path_compiled_module_bytecode(
+        &mut self,
+        module: &PythonModuleBytecode,
+        prefix: &str,
+    ) -> Result<()> {
+        self.collector
+            .add_relative_path_python_module_bytecode(module, prefix)
+    }
+
     /// Add resource data.
     ///
     /// Resource data belongs to a Python package and has a name and bytes data.
@@ -282,6 +463,7 @@ impl PrePackagedResources {
             is_package: false,
             libraries: vec![],
             library_dirs: vec![],
+            shared_libraries: BTreeMap::new(),
         };
 
         self.collector
@@ -347,28 +529,63 @@ impl PrePackagedResources {
     }
 
     /// Add an extension module shared library that should be imported from memory.
+    ///
+    /// `shared_libraries` are loaded from memory before the extension module.
     pub fn add_in_memory_extension_module_shared_library(
         &mut self,
         module: &str,
         is_package: bool,
         data: &[u8],
+        shared_libraries: &BTreeMap<String, DataLocation>,
     ) -> Result<()> {
-        self.collector
-            .add_in_memory_python_extension_module_shared_library(module, is_package, data, &[])?;
+        for (name, location) in shared_libraries {
+            self.collector
+                .add_in_memory_shared_library(name, location)?;
+        }
+
+        let depends = shared_libraries
+            .keys()
+            .map(|x| x.as_str())
+            .collect::<Vec<&str>>();
 
-        // TODO add shared library dependencies to be packaged as well.
+        self.collector
+            .add_in_memory_python_extension_module_shared_library(
+                module, is_package, data, &depends,
+            )?;
 
         Ok(())
     }
 
     /// Add an extension module to be loaded from the filesystem as a dynamic library.
+    ///
+    /// Shared libraries of the extension module are installed in `prefix`,
+    /// like those of distribution extension modules.
     pub fn add_relative_path_extension_module(
         &mut self,
         em: &PythonExtensionModule,
         prefix: &str,
     ) -> Result<()> {
         self.collector
-            .add_relative_path_python_extension_module(em, prefix)
+            .add_relative_path_python_extension_module(em, prefix)?;
+
+        for (name, location) in &em.shared_libraries {
+            self.collector
+                .add_relative_path_shared_library(prefix, name, location)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move all resources to files relative to the produced binary.
+    ///
+    /// See `PythonResourceCollector::relocate_to_relative_path()`.
+    pub fn relocate_to_relative_path(
+        &mut self,
+        prefix: &str,
+        resources_prefix: &str,
+    ) -> Result<()> {
+        self.collector
+            .relocate_to_relative_path(prefix, resources_prefix)
     }
 
     /// Filter the entities in this instance against names in files.
@@ -397,17 +614,58 @@ impl PrePackagedResources {
         Ok(())
     }
 
+    /// Apply the `prefer-in-memory-fallback-filesystem` resources policy.
+    ///
+    /// With that policy, resources of top-level packages which aren't expected
+    /// to work from memory are installed relative to the binary instead.
+    /// Otherwise, resources are left as is.
+    fn resolve_in_memory_incompatible_packages(
+        &self,
+        logger: &slog::Logger,
+    ) -> Result<Cow<PythonResourceCollector>> {
+        let prefix = match self.collector.get_policy() {
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(prefix) => prefix,
+            _ => return Ok(Cow::Borrowed(&self.collector)),
+        };
+
+        let packages = find_in_memory_incompatible_packages(
+            &self.get_module_names(),
+            &self.collector.find_dunder_file()?,
+            &self.stdlib_packages,
+        );
+
+        for package in &packages {
+            warn!(
+                logger,
+                "installing {} in {} because it may not work from memory", package, prefix
+            );
+        }
+
+        let mut collector = self.collector.clone();
+        collector.relocate_packages_to_relative_path(&packages, prefix)?;
+
+        Ok(Cow::Owned(collector))
+    }
+
     /// Transform this instance into embedded resources data.
     ///
     /// This method performs actions necessary to produce entities which will allow the
-    /// resources to be embedded in a binary.
+    /// resources to be embedded in a binary. Bytecode is compiled by
+    /// `bytecode_compiler` and is cached in `bytecode_cache_path`, if defined.
     pub fn package(
         &self,
         logger: &slog::Logger,
-        python_exe: &Path,
+        bytecode_compiler: &BytecodeCompilerBackend,
+        bytecode_cache_path: Option<&Path>,
     ) -> Result<EmbeddedPythonResources> {
+        let (collector, dunder_file_modules) = build_phase("collect", &[], || {
+            let collector = self.resolve_in_memory_incompatible_packages(logger)?;
+            let modules = collector.find_dunder_file()?;
+
+            Ok((collector, modules))
+        })?;
         let mut file_seen = false;
-        for module in self.collector.find_dunder_file()? {
+        for module in dunder_file_modules {
             file_seen = true;
             warn!(logger, "warning: {} contains __file__", module);
         }
@@ -424,7 +682,9 @@ impl PrePackagedResources {
             );
         }
 
-        let resources = self.collector.to_prepared_python_resources(python_exe)?;
+        let resources = build_phase("compile", &[], || {
+            collector.to_prepared_python_resources(bytecode_compiler, bytecode_cache_path)
+        })?;
 
         Ok(EmbeddedPythonResources {
             resources,
@@ -483,6 +743,36 @@ impl<'a> EmbeddedPythonResources<'a> {
             .collect()
     }
 
+    /// Move bytecode of modules in the given packages to a remote archive.
+    ///
+    /// Returns the archive data, which must be hosted at the URL the
+    /// interpreter is configured to fetch remote resources from.
+    pub fn move_bytecode_to_remote_archive(
+        &mut self,
+        packages: &BTreeSet<String>,
+    ) -> Result<Vec<u8>> {
+        self.resources
+            .move_bytecode_to_remote_archive(packages, &ChunkingParameters::default())
+    }
+
+    /// Move modules in the given packages to files of a zip archive.
+    ///
+    /// Returns the files, keyed by their path in the archive.
+    pub fn move_packages_to_zip_files(
+        &mut self,
+        packages: &BTreeSet<String>,
+    ) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        self.resources.move_packages_to_zip_files(packages)
+    }
+
+    /// Compress in-memory module sources and bytecode.
+    ///
+    /// The data is decompressed by the importer when modules are imported.
+    pub fn compress_module_data(&mut self) -> Result<()> {
+        self.resources
+            .compress_module_data(RESOURCES_COMPRESSION_LEVEL)
+    }
+
     /// Obtain a FileManifest of extra files to install relative to the produced binary.
     pub fn extra_install_files(&self) -> Result<FileManifest> {
         let mut res = FileManifest::default();
@@ -621,6 +911,7 @@ mod tests {
             is_package: false,
             libraries: vec![],
             library_dirs: vec![],
+            shared_libraries: BTreeMap::new(),
         };
 
         r.add_builtin_extension_module(&em)?;
@@ -640,4 +931,74 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_in_memory_incompatible_packages() -> Result<()> {
+        let logger = crate::testutil::get_logger()?;
+
+        for (policy, in_memory) in &[
+            (
+                PythonResourcesPolicy::PreferInMemoryFallbackFilesystem("lib".to_string()),
+                vec!["foo", "pydoc_data"],
+            ),
+            (
+                PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("lib".to_string()),
+                vec!["bar", "bar.baz", "certifi", "foo", "pydoc_data"],
+            ),
+        ] {
+            let mut r = PrePackagedResources::new(policy, DEFAULT_CACHE_TAG);
+            r.set_stdlib_packages(BTreeSet::from_iter(vec!["pydoc_data".to_string()]));
+            for (name, source) in &[
+                ("foo", "import os"),
+                ("bar", ""),
+                ("bar.baz", "os.path.dirname(__file__)"),
+                ("certifi", ""),
+                ("pydoc_data", "os.path.dirname(__file__)"),
+            ] {
+                r.add_in_memory_module_source(&PythonModuleSource {
+                    name: name.to_string(),
+                    source: DataLocation::Memory(source.as_bytes().to_vec()),
+                    is_package: *name == "bar",
+                    cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                })?;
+            }
+
+            let collector = r.resolve_in_memory_incompatible_packages(&logger)?;
+            assert_eq!(
+                collector
+                    .get_in_memory_module_sources()
+                    .keys()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>(),
+                *in_memory
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_packages() -> Result<()> {
+        let mut r =
+            PrePackagedResources::new(&PythonResourcesPolicy::InMemoryOnly, DEFAULT_CACHE_TAG);
+        for name in &["email", "email.parser", "foo", "foo.email"] {
+            r.add_in_memory_module_source(&PythonModuleSource {
+                name: name.to_string(),
+                source: DataLocation::Memory(vec![]),
+                is_package: !name.contains('.'),
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            })?;
+        }
+
+        r.remove_packages(&BTreeSet::from_iter(vec!["email".to_string()]))?;
+        assert_eq!(
+            r.get_in_memory_module_sources()
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["foo", "foo.email"]
+        );
+
+        Ok(())
+    }
 }
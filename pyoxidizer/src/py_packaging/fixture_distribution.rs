@@ -0,0 +1,182 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Synthetic Python distributions for testing configurations.
+
+A fixture distribution has the layout of a `python-build-standalone`
+distribution but only holds a handful of small standard library modules
+and a single extension module without object files. It is generated
+locally, so configurations can be evaluated without downloading and
+extracting a real distribution. It has no Python interpreter, so it can't
+compile bytecode, run `pip` or build binaries.
+*/
+
+use {
+    super::distribution::DistributionExtractLock,
+    anyhow::{Context, Result},
+    std::path::{Path, PathBuf},
+};
+
+/// Python version of fixture distributions.
+pub const FIXTURE_PYTHON_VERSION: &str = "3.8.2";
+
+/// Standard library files of fixture distributions, relative to the stdlib directory.
+const FIXTURE_STDLIB_FILES: &[(&str, &str)] = &[
+    ("abc.py", "class ABC:\n    pass\n"),
+    ("codecs.py", "def lookup(encoding):\n    return None\n"),
+    (
+        "encodings/__init__.py",
+        "def search_function(encoding):\n    return None\n",
+    ),
+    (
+        "encodings/utf_8.py",
+        "def getregentry():\n    return None\n",
+    ),
+    ("io.py", "DEFAULT_BUFFER_SIZE = 8192\n"),
+    ("json/__init__.py", "from .decoder import JSONDecoder\n"),
+    ("json/decoder.py", "class JSONDecoder:\n    pass\n"),
+    ("json/fixture.txt", "fixture resource\n"),
+    ("os.py", "sep = '/'\n"),
+    ("test/__init__.py", ""),
+    ("test/test_fixture.py", "def test_fixture():\n    pass\n"),
+];
+
+/// Obtain the content of `PYTHON.json` of a fixture distribution.
+fn fixture_python_json(target_triple: &str) -> serde_json::Value {
+    let windows = target_triple.contains("-windows-");
+
+    serde_json::json!({
+        "version": "5",
+        "target_triple": target_triple,
+        "optimizations": "noopt",
+        "python_tag": "cp38",
+        "python_abi_tag": "cp38",
+        "python_platform_tag": "fixture",
+        "python_implementation_cache_tag": "cpython-38",
+        "python_implementation_hex_version": 0x0308_02f0,
+        "python_implementation_name": "cpython",
+        "python_implementation_version": ["3", "8", "2", "final", "0"],
+        "python_version": FIXTURE_PYTHON_VERSION,
+        "python_major_minor_version": "3.8",
+        "python_paths": {
+            "include": "install/include/python3.8",
+            "stdlib": "install/lib/python3.8",
+        },
+        "python_exe": if windows { "install/python.exe" } else { "install/bin/python3" },
+        "python_stdlib_test_packages": ["test"],
+        "python_suffixes": {
+            "source": [".py"],
+            "bytecode": [".pyc"],
+            "debug_bytecode": [".pyc"],
+            "optimized_bytecode": [".pyc"],
+            "extension": [if windows { ".pyd" } else { ".so" }],
+        },
+        "python_bytecode_magic_number": "550d0d0a",
+        "python_symbol_visibility": "global-default",
+        "python_extension_module_loading": ["builtin"],
+        "libpython_link_mode": "static",
+        "crt_features": [],
+        "run_tests": "build/run_tests.py",
+        "build_info": {
+            "core": {
+                "objs": [],
+                "links": [],
+                "shared_lib": null,
+                "static_lib": null,
+            },
+            "extensions": {
+                "_json": [{
+                    "in_core": false,
+                    "init_fn": "PyInit__json",
+                    "licenses": null,
+                    "license_paths": null,
+                    "license_public_domain": null,
+                    "links": [],
+                    "objs": [],
+                    "required": false,
+                    "static_lib": null,
+                    "shared_lib": null,
+                    "variant": "default",
+                }],
+            },
+            "inittab_object": "build/Modules/config.o",
+            "inittab_source": "build/Modules/config.c",
+            "inittab_cflags": [],
+            "object_file_format": if windows { "coff" } else { "elf" },
+        },
+        "licenses": null,
+        "license_path": null,
+        "tcl_library_path": null,
+        "tcl_library_paths": null,
+    })
+}
+
+/// Write a fixture distribution for a target triple.
+///
+/// The distribution is written to a child directory of `dest_dir`, unless
+/// it already exists. Returns the path of the distribution.
+pub fn write_fixture_distribution(dest_dir: &Path, target_triple: &str) -> Result<PathBuf> {
+    let _lock = DistributionExtractLock::new(dest_dir)?;
+
+    let dist_dir = dest_dir.join(format!("fixture-{}", target_triple));
+    let python_json_path = dist_dir.join("python").join("PYTHON.json");
+
+    if python_json_path.exists() {
+        return Ok(dist_dir);
+    }
+
+    let install_dir = dist_dir.join("python").join("install");
+    std::fs::create_dir_all(install_dir.join("include").join("python3.8"))?;
+
+    let stdlib_dir = install_dir.join("lib").join("python3.8");
+    for (path, content) in FIXTURE_STDLIB_FILES {
+        let path = stdlib_dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
+    }
+
+    // PYTHON.json is written last, as it marks the distribution as complete.
+    std::fs::write(
+        &python_json_path,
+        serde_json::to_vec_pretty(&fixture_python_json(target_triple))?,
+    )?;
+
+    Ok(dist_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::py_packaging::distribution::PythonDistribution,
+        crate::py_packaging::standalone_distribution::StandaloneDistribution,
+    };
+
+    #[test]
+    fn test_write_fixture_distribution() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let dist_dir = write_fixture_distribution(temp_dir.path(), "x86_64-unknown-linux-gnu")?;
+        assert_eq!(
+            dist_dir,
+            temp_dir.path().join("fixture-x86_64-unknown-linux-gnu")
+        );
+
+        // Writing again reuses the existing distribution.
+        assert_eq!(
+            write_fixture_distribution(temp_dir.path(), "x86_64-unknown-linux-gnu")?,
+            dist_dir
+        );
+
+        let dist = StandaloneDistribution::from_directory(&dist_dir)?;
+        assert_eq!(dist.python_major_minor_version(), "3.8");
+        assert_eq!(dist.cache_tag(), "cpython-38");
+        assert!(dist.py_modules.contains_key("json.decoder"));
+        assert!(dist.py_modules.contains_key("test.test_fixture"));
+        assert!(dist.resources["json"].contains_key("fixture.txt"));
+        assert!(dist.extension_modules.contains_key("_json"));
+
+        Ok(())
+    }
+}
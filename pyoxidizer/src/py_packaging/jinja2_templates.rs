@@ -0,0 +1,187 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Precompilation of Jinja2 templates to Python modules.
+
+Jinja2 compiles templates to Python code the first time they are loaded.
+Templates can instead be compiled at build time with
+`Environment.compile_templates()`, which produces a module per template.
+Embedding these modules in a binary avoids the compilation on first use.
+
+Compiled modules are placed in a package whose `loader()` function returns
+a Jinja2 loader importing them. Jinja2's `ModuleLoader` imports templates
+from a directory, so the package defines a subclass importing them from the
+package instead.
+*/
+
+use {
+    super::distribution::PythonDistribution,
+    anyhow::{anyhow, Context, Result},
+    python_packaging::resource::{DataLocation, PythonModuleSource},
+    slog::warn,
+    std::path::Path,
+};
+
+/// Python script compiling templates.
+///
+/// Arguments are the templates directory, the output directory and the
+/// Jinja2 extensions to enable.
+const COMPILE_SCRIPT: &str = indoc::indoc!(
+    r#"
+    import sys
+    from jinja2 import Environment, FileSystemLoader
+
+    env = Environment(loader=FileSystemLoader(sys.argv[1]), extensions=sys.argv[3:])
+    env.compile_templates(sys.argv[2], zip=None, ignore_errors=False)
+    "#
+);
+
+/// Source code of the package holding compiled templates.
+const PACKAGE_CODE: &str = indoc::indoc!(
+    r#"
+    """Jinja2 templates compiled by PyOxidizer."""
+
+    import importlib
+
+    from jinja2 import ModuleLoader
+
+    __all__ = ["loader"]
+
+
+    class EmbeddedModuleLoader(ModuleLoader):
+        """A ``jinja2.ModuleLoader`` importing templates from a package."""
+
+        def __init__(self, package):
+            self.package_name = package
+            self.module = importlib.import_module(package)
+
+
+    def loader():
+        """Obtain a loader of the templates in this package."""
+        return EmbeddedModuleLoader(__name__)
+    "#
+);
+
+/// Compile Jinja2 templates in a directory to modules of a package.
+///
+/// Jinja2 is installed from `jinja2_requirement` to compile templates. It
+/// should resolve to the version of Jinja2 packaged with the application.
+pub fn compile_jinja2_templates(
+    logger: &slog::Logger,
+    dist: &dyn PythonDistribution,
+    templates_dir: &Path,
+    package: &str,
+    jinja2_requirement: &str,
+    extensions: &[String],
+) -> Result<Vec<PythonModuleSource>> {
+    if !templates_dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", templates_dir.display()));
+    }
+
+    let temp_dir = tempdir::TempDir::new("pyoxidizer-jinja2")?;
+    let jinja2_dir = temp_dir.path().join("jinja2");
+    let compiled_dir = temp_dir.path().join("compiled");
+
+    dist.ensure_pip(logger)?;
+
+    warn!(
+        logger,
+        "installing {} to compile templates", jinja2_requirement
+    );
+    let status = std::process::Command::new(dist.python_exe_path())
+        .args(&[
+            "-m",
+            "pip",
+            "--disable-pip-version-check",
+            "install",
+            "--target",
+        ])
+        .arg(&jinja2_dir)
+        .arg(jinja2_requirement)
+        .status()
+        .context("running pip")?;
+    if !status.success() {
+        return Err(anyhow!("error installing {}", jinja2_requirement));
+    }
+
+    warn!(logger, "compiling templates in {}", templates_dir.display());
+    let status = std::process::Command::new(dist.python_exe_path())
+        .arg("-c")
+        .arg(COMPILE_SCRIPT)
+        .arg(templates_dir)
+        .arg(&compiled_dir)
+        .args(extensions)
+        .env("PYTHONPATH", &jinja2_dir)
+        .status()
+        .context("running Python")?;
+    if !status.success() {
+        return Err(anyhow!("error compiling templates"));
+    }
+
+    let mut res = vec![PythonModuleSource {
+        name: package.to_string(),
+        source: DataLocation::Memory(PACKAGE_CODE.as_bytes().to_vec()),
+        is_package: true,
+        cache_tag: dist.cache_tag().to_string(),
+    }];
+
+    let mut paths = std::fs::read_dir(&compiled_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
+        let stem = match path.file_stem() {
+            Some(stem) if path.extension() == Some(std::ffi::OsStr::new("py")) => {
+                stem.to_string_lossy().to_string()
+            }
+            _ => continue,
+        };
+
+        res.push(PythonModuleSource {
+            name: format!("{}.{}", package, stem),
+            source: DataLocation::Memory(std::fs::read(&path)?),
+            is_package: false,
+            cache_tag: dist.cache_tag().to_string(),
+        });
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*, std::ops::Deref};
+
+    #[test]
+    fn test_compile_templates() -> Result<()> {
+        let logger = get_logger()?;
+        let distribution = get_default_distribution()?;
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::create_dir(temp_dir.path().join("pages"))?;
+        std::fs::write(
+            temp_dir.path().join("pages").join("index.html"),
+            "Hello {{ name }}!",
+        )?;
+
+        let modules = compile_jinja2_templates(
+            &logger,
+            distribution.deref().as_ref(),
+            temp_dir.path(),
+            "app_templates",
+            "jinja2==2.11.2",
+            &[],
+        )?;
+
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0].name, "app_templates");
+        assert!(modules[0].is_package);
+        assert!(modules[1].name.starts_with("app_templates.tmpl_"));
+        assert!(String::from_utf8(modules[1].source.resolve()?)?.contains("def root("));
+
+        Ok(())
+    }
+}
@@ -9,13 +9,28 @@ This module tree holds functionality that is centered around Python.
 */
 
 pub mod binary;
+pub mod compatibility;
+pub mod conda;
 pub mod config;
 pub mod distribution;
 pub mod distutils;
+pub mod download;
 pub mod embedded_resource;
 pub mod filtering;
+pub mod fixture_distribution;
+pub mod jinja2_templates;
 pub mod libpython;
 pub mod packaging_tool;
+pub mod protobuf;
 pub mod pyembed;
+pub mod requirements;
 pub mod resource;
+pub mod resource_signing;
+pub mod resource_transform;
 pub mod standalone_distribution;
+pub mod startup_cache;
+pub mod stdlib_tests;
+pub mod venv;
+pub mod wheel;
+pub mod wheel_tags;
+pub mod zipapp;
@@ -0,0 +1,300 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Generation of Python modules from Protocol Buffers definitions.
+
+`.proto` files are compiled with a `protoc` executable or with the `protoc`
+bundled in the `grpcio-tools` package, which can also generate gRPC stubs.
+
+Generated modules are cached under a key derived from the content of the
+`.proto` files and the compilation options, so they are only generated
+again when their inputs change.
+*/
+
+use {
+    super::distribution::PythonDistribution,
+    super::packaging_tool::find_resources,
+    anyhow::{anyhow, Context, Result},
+    python_packaging::resource::PythonResource,
+    sha2::{Digest, Sha256},
+    slog::warn,
+    std::ffi::OsString,
+    std::path::{Path, PathBuf},
+    std::process::{Command, ExitStatus},
+};
+
+/// Python script running the `protoc` of `grpcio-tools`.
+///
+/// The `.proto` files of well-known types bundled with `grpcio-tools` are
+/// added to the include path, as they are with a standalone `protoc`.
+const GRPC_TOOLS_SCRIPT: &str = indoc::indoc!(
+    r#"
+    import os
+    import sys
+
+    import grpc_tools
+    from grpc_tools import protoc
+
+    include = os.path.join(os.path.dirname(grpc_tools.__file__), "_proto")
+    sys.exit(protoc.main(["protoc", "-I" + include] + sys.argv[1:]))
+    "#
+);
+
+/// How Python code is generated from `.proto` files.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtobufCompiler {
+    /// A `protoc` executable, generating message modules.
+    Protoc(PathBuf),
+
+    /// The `protoc` of a `grpcio-tools` requirement installed with pip.
+    ///
+    /// gRPC stubs are generated along with message modules when `grpc` is
+    /// true.
+    GrpcioTools { requirement: String, grpc: bool },
+}
+
+/// Compute the key of modules generated from `.proto` files in the cache.
+fn cache_key(
+    compiler: &ProtobufCompiler,
+    sources: &[PathBuf],
+    include_dirs: &[PathBuf],
+) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    // Values are terminated so their boundaries are part of the key.
+    hasher.input(format!("{:?}\0", compiler));
+
+    for dir in include_dirs {
+        hasher.input(format!("-I{}\0", dir.display()));
+    }
+
+    for source in sources {
+        let data = std::fs::read(source).context(format!("reading {}", source.display()))?;
+
+        hasher.input(format!("{}\0{}\0", source.display(), data.len()));
+        hasher.input(&data);
+    }
+
+    Ok(hex::encode(hasher.result()))
+}
+
+/// Run the Protocol Buffers compiler, writing modules to a directory.
+fn run_compiler(
+    logger: &slog::Logger,
+    dist: &dyn PythonDistribution,
+    compiler: &ProtobufCompiler,
+    sources: &[PathBuf],
+    include_dirs: &[PathBuf],
+    out_dir: &Path,
+) -> Result<ExitStatus> {
+    // Arguments embed paths, which are passed as is rather than converted
+    // to strings.
+    let prefixed = |prefix: &str, path: &Path| {
+        let mut arg = OsString::from(prefix);
+        arg.push(path);
+        arg
+    };
+
+    let mut args = include_dirs
+        .iter()
+        .map(|dir| prefixed("-I", dir))
+        .collect::<Vec<OsString>>();
+    args.push(prefixed("--python_out=", out_dir));
+
+    match compiler {
+        ProtobufCompiler::Protoc(protoc) => {
+            args.extend(sources.iter().map(|p| p.as_os_str().to_os_string()));
+
+            Command::new(protoc)
+                .args(&args)
+                .status()
+                .context(format!("running {}", protoc.display()))
+        }
+        ProtobufCompiler::GrpcioTools { requirement, grpc } => {
+            if *grpc {
+                args.push(prefixed("--grpc_python_out=", out_dir));
+            }
+            args.extend(sources.iter().map(|p| p.as_os_str().to_os_string()));
+
+            let temp_dir = tempdir::TempDir::new("pyoxidizer-grpcio-tools")?;
+
+            dist.ensure_pip(logger)?;
+
+            warn!(logger, "installing {} to compile .proto files", requirement);
+            let status = Command::new(dist.python_exe_path())
+                .args(&[
+                    "-m",
+                    "pip",
+                    "--disable-pip-version-check",
+                    "install",
+                    "--target",
+                ])
+                .arg(temp_dir.path())
+                .arg(requirement)
+                .status()
+                .context("running pip")?;
+            if !status.success() {
+                return Err(anyhow!("error installing {}", requirement));
+            }
+
+            Command::new(dist.python_exe_path())
+                .arg("-c")
+                .arg(GRPC_TOOLS_SCRIPT)
+                .args(&args)
+                .env("PYTHONPATH", temp_dir.path())
+                .status()
+                .context("running Python")
+        }
+    }
+}
+
+/// Generate Python modules from `.proto` files.
+///
+/// Each source must be in one of `include_dirs`, and its modules are named
+/// after its path relative to that directory. Generated modules are cached
+/// in `cache_dir`.
+pub fn compile_protobuf(
+    logger: &slog::Logger,
+    dist: &dyn PythonDistribution,
+    compiler: &ProtobufCompiler,
+    sources: &[PathBuf],
+    include_dirs: &[PathBuf],
+    cache_dir: &Path,
+    strict: bool,
+) -> Result<Vec<PythonResource>> {
+    if sources.is_empty() {
+        return Err(anyhow!("no .proto files to compile"));
+    }
+
+    if include_dirs.is_empty() {
+        return Err(anyhow!("no include directories"));
+    }
+
+    let out_dir = cache_dir.join(cache_key(compiler, sources, include_dirs)?);
+
+    if out_dir.is_dir() {
+        warn!(
+            logger,
+            "using modules generated from .proto files in {}",
+            out_dir.display()
+        );
+    } else {
+        std::fs::create_dir_all(cache_dir).context(format!("creating {}", cache_dir.display()))?;
+
+        // Modules are generated in a temporary directory moved into the
+        // cache once complete, so failures don't leave partial output.
+        let temp_dir = tempdir::TempDir::new_in(cache_dir, "generating")?;
+
+        warn!(logger, "compiling {} .proto files", sources.len());
+        let status = run_compiler(
+            logger,
+            dist,
+            compiler,
+            sources,
+            include_dirs,
+            temp_dir.path(),
+        )?;
+        if !status.success() {
+            return Err(anyhow!("error compiling .proto files"));
+        }
+
+        std::fs::rename(temp_dir.into_path(), &out_dir)
+            .context(format!("moving generated modules to {}", out_dir.display()))?;
+    }
+
+    find_resources(logger, dist, &out_dir, None, strict)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*, std::ops::Deref};
+
+    #[test]
+    fn test_cache_key() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let source = temp_dir.path().join("foo.proto");
+        let compiler = ProtobufCompiler::GrpcioTools {
+            requirement: "grpcio-tools".to_string(),
+            grpc: false,
+        };
+        let include_dirs = vec![temp_dir.path().to_path_buf()];
+
+        std::fs::write(&source, "syntax = \"proto3\";\n")?;
+        let key = cache_key(&compiler, &[source.clone()], &include_dirs)?;
+        assert_eq!(key, cache_key(&compiler, &[source.clone()], &include_dirs)?);
+
+        let grpc = ProtobufCompiler::GrpcioTools {
+            requirement: "grpcio-tools".to_string(),
+            grpc: true,
+        };
+        assert_ne!(key, cache_key(&grpc, &[source.clone()], &include_dirs)?);
+
+        std::fs::write(&source, "syntax = \"proto2\";\n")?;
+        assert_ne!(key, cache_key(&compiler, &[source], &include_dirs)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_grpc() -> Result<()> {
+        let logger = get_logger()?;
+        let distribution = get_default_distribution()?;
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let proto_dir = temp_dir.path().join("protos");
+        let cache_dir = temp_dir.path().join("cache");
+        std::fs::create_dir_all(proto_dir.join("greeter"))?;
+
+        let source = proto_dir.join("greeter").join("greeter.proto");
+        std::fs::write(
+            &source,
+            "syntax = \"proto3\";\n\
+             message HelloRequest { string name = 1; }\n\
+             message HelloReply { string message = 1; }\n\
+             service Greeter { rpc SayHello (HelloRequest) returns (HelloReply); }\n",
+        )?;
+
+        let compiler = ProtobufCompiler::GrpcioTools {
+            requirement: "grpcio-tools==1.29.0".to_string(),
+            grpc: true,
+        };
+
+        let resources = compile_protobuf(
+            &logger,
+            distribution.deref().as_ref(),
+            &compiler,
+            &[source.clone()],
+            &[proto_dir.clone()],
+            &cache_dir,
+            false,
+        )?;
+
+        let mut names = resources
+            .iter()
+            .map(|r| r.full_name())
+            .collect::<Vec<String>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["greeter.greeter_pb2", "greeter.greeter_pb2_grpc"]
+        );
+
+        // Modules are now obtained from the cache.
+        assert_eq!(std::fs::read_dir(&cache_dir)?.count(), 1);
+        let cached = compile_protobuf(
+            &logger,
+            distribution.deref().as_ref(),
+            &compiler,
+            &[source],
+            &[proto_dir],
+            &cache_dir,
+            false,
+        )?;
+        assert_eq!(cached.len(), resources.len());
+        assert_eq!(std::fs::read_dir(&cache_dir)?.count(), 1);
+
+        Ok(())
+    }
+}
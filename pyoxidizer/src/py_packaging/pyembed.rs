@@ -8,18 +8,67 @@ Functionality related to the pyembed crate.
 
 use anyhow::Result;
 use itertools::Itertools;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use super::config::{EmbeddedPythonConfig, RawAllocator, RunMode, TerminfoResolution};
+use crate::app_packaging::resource::FileManifest;
+use crate::path_util::{path_str, rust_str_literal};
+
+/// Obtain names `ctypes.util.find_library()` may be called with to find a file.
+///
+/// Returns an empty list if the filename isn't a shared library or is a
+/// Python extension module.
+fn find_library_names(filename: &str) -> Vec<String> {
+    let is_shared_library = filename.ends_with(".dll")
+        || filename.ends_with(".dylib")
+        || filename.ends_with(".so")
+        || filename.contains(".so.");
+
+    if !is_shared_library || filename.contains(".cpython-") || filename.contains(".abi3.") {
+        return vec![];
+    }
+
+    let stem = filename.split('.').next().unwrap();
+
+    let mut names = vec![stem.to_string()];
+    if stem.starts_with("lib") && stem.len() > 3 {
+        names.push(stem[3..].to_string());
+    }
+
+    names
+}
+
+/// Derive a map of library names to shared libraries installed next to a binary.
+///
+/// Keys are names that can be passed to `ctypes.util.find_library()`. Values
+/// are paths relative to the binary.
+pub fn derive_ctypes_library_map(files: &FileManifest) -> BTreeMap<String, PathBuf> {
+    let mut res = BTreeMap::new();
+
+    for (path, _) in files.entries() {
+        if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
+            for name in find_library_names(filename) {
+                res.entry(name).or_insert_with(|| path.clone());
+            }
+        }
+    }
+
+    res
+}
 
 /// Obtain the Rust source code to construct a PythonConfig instance.
+///
+/// Paths are embedded as escaped string literals, so they may contain any
+/// character but must be valid Unicode.
 pub fn derive_python_config(
     embedded: &EmbeddedPythonConfig,
     embedded_resources_path: &PathBuf,
-) -> String {
-    format!(
+    ctypes_library_map: &BTreeMap<String, PathBuf>,
+) -> Result<String> {
+    Ok(format!(
         "pyembed::PythonConfig {{\n    \
          standard_io_encoding: {},\n    \
          standard_io_errors: {},\n    \
@@ -42,14 +91,28 @@ pub fn derive_python_config(
          quiet: {},\n    \
          use_hash_seed: {},\n    \
          verbose: {},\n    \
-         packed_resources: include_bytes!(r#\"{}\"#),\n    \
+         packed_resources: include_bytes!({}),\n    \
          extra_extension_modules: vec![],\n    \
          argvb: false,\n    \
          sys_frozen: {},\n    \
          sys_meipass: {},\n    \
          raw_allocator: {},\n    \
+         ctypes_library_map: {},\n    \
+         startup_cache_module: {},\n    \
+         python_executable_env: {},\n    \
+         debugpy_listen_env: {},\n    \
+         debugpy_allow_remote_hosts: {},\n    \
+         startup_error_messages: {},\n    \
+         startup_progress_callback: {},\n    \
+         remote_resources_url: {},\n    \
+         remote_chunk_fetcher: {},\n    \
+         resources_verification_key: {},\n    \
+         zipimport_archive: {},\n    \
+         origin: {},\n    \
+         extract_shared_libraries_to_cache: {},\n    \
          terminfo_resolution: {},\n    \
          write_modules_directory_env: {},\n    \
+         write_memory_stats_env: {},\n    \
          run: {},\n\
          }}",
         match &embedded.stdio_encoding_name {
@@ -65,7 +128,7 @@ pub fn derive_python_config(
         &embedded
             .sys_paths
             .iter()
-            .map(|p| "\"".to_owned() + p + "\".to_string()")
+            .map(|p| rust_str_literal(p) + ".to_string()")
             .collect::<Vec<String>>()
             .join(", "),
         embedded.bytes_warning,
@@ -83,14 +146,101 @@ pub fn derive_python_config(
         embedded.quiet,
         embedded.use_hash_seed,
         embedded.verbose,
-        embedded_resources_path.display(),
+        rust_str_literal(path_str(embedded_resources_path)?),
         embedded.sys_frozen,
         embedded.sys_meipass,
-        match embedded.raw_allocator {
-            RawAllocator::Jemalloc => "pyembed::PythonRawAllocator::jemalloc()",
-            RawAllocator::Rust => "pyembed::PythonRawAllocator::rust()",
-            RawAllocator::System => "pyembed::PythonRawAllocator::system()",
+        {
+            let raw_allocator = match embedded.raw_allocator {
+                RawAllocator::Jemalloc => "pyembed::PythonRawAllocator::jemalloc()",
+                RawAllocator::Rust => "pyembed::PythonRawAllocator::rust()",
+                RawAllocator::System => "pyembed::PythonRawAllocator::system()",
+            };
+
+            if embedded.raw_allocator_stats {
+                format!(
+                    "pyembed::PythonRawAllocator {{ stats: true, ..{} }}",
+                    raw_allocator
+                )
+            } else {
+                raw_allocator.to_string()
+            }
+        },
+        if embedded.ctypes_find_library_redirect {
+            format!(
+                "Some(vec![{}])",
+                ctypes_library_map
+                    .iter()
+                    .map(|(name, path)| Ok(format!(
+                        "(\"{}\".to_string(), std::path::PathBuf::from({}))",
+                        name,
+                        rust_str_literal(path_str(path)?)
+                    )))
+                    .collect::<Result<Vec<String>>>()?
+                    .join(", ")
+            )
+        } else {
+            "None".to_owned()
+        },
+        match &embedded.startup_cache_module {
+            Some(module) => "Some(\"".to_owned() + &module + "\".to_string())",
+            _ => "None".to_owned(),
+        },
+        match &embedded.python_executable_env {
+            Some(key) => "Some(\"".to_owned() + &key + "\".to_string())",
+            _ => "None".to_owned(),
+        },
+        match &embedded.debugpy_listen_env {
+            Some(key) => "Some(\"".to_owned() + &key + "\".to_string())",
+            _ => "None".to_owned(),
+        },
+        embedded.debugpy_allow_remote_hosts,
+        if embedded.startup_error_message.is_some() || !embedded.startup_error_catalog.is_empty() {
+            format!(
+                "Some(pyembed::StartupErrorMessages {{ template: {:?}.to_string(), \
+                 catalog: vec![{}] }})",
+                embedded
+                    .startup_error_message
+                    .as_ref()
+                    .map(|s| s.as_str())
+                    .unwrap_or("{error}"),
+                embedded
+                    .startup_error_catalog
+                    .iter()
+                    .map(|(locale, template)| format!(
+                        "({:?}.to_string(), {:?}.to_string())",
+                        locale, template
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        } else {
+            "None".to_owned()
+        },
+        match &embedded.startup_progress_callback {
+            Some(path) => format!("Some({})", path),
+            None => "None".to_owned(),
+        },
+        match &embedded.remote_resources_url {
+            Some(url) => format!("Some(r###\"{}\"###.to_string())", url),
+            None => "None".to_owned(),
+        },
+        match &embedded.remote_chunk_fetcher {
+            Some(path) => format!("Some({})", path),
+            None => "None".to_owned(),
+        },
+        match &embedded.resources_verification_key {
+            Some(key) => format!("Some({:?})", key),
+            None => "None".to_owned(),
+        },
+        match &embedded.zipimport_archive {
+            Some(path) => format!("Some(std::path::PathBuf::from(r###\"{}\"###))", path),
+            None => "None".to_owned(),
+        },
+        match &embedded.origin {
+            Some(path) => format!("Some(std::path::PathBuf::from({}))", rust_str_literal(path)),
+            None => "None".to_owned(),
         },
+        embedded.extract_shared_libraries_to_cache,
         match embedded.terminfo_resolution {
             TerminfoResolution::Dynamic => "pyembed::TerminfoResolution::Dynamic".to_string(),
             TerminfoResolution::None => "pyembed::TerminfoResolution::None".to_string(),
@@ -102,6 +252,10 @@ pub fn derive_python_config(
             Some(path) => "Some(\"".to_owned() + &path + "\".to_string())",
             _ => "None".to_owned(),
         },
+        match &embedded.write_memory_stats_env {
+            Some(key) => "Some(\"".to_owned() + &key + "\".to_string())",
+            _ => "None".to_owned(),
+        },
         match embedded.run_mode {
             RunMode::Noop => "pyembed::PythonRunMode::None".to_owned(),
             RunMode::Repl => "pyembed::PythonRunMode::Repl".to_owned(),
@@ -115,13 +269,15 @@ pub fn derive_python_config(
                     + code
                     + "\"###.to_string() }"
             }
-            RunMode::File { ref path } => {
-                "pyembed::PythonRunMode::File { path: std::path::PathBuf::new(r###\"".to_owned()
-                    + path
-                    + "\"###) }"
+            RunMode::File { ref path } => format!(
+                "pyembed::PythonRunMode::File {{ path: std::path::PathBuf::from({}) }}",
+                rust_str_literal(path)
+            ),
+            RunMode::EntryPoint { .. } => {
+                panic!("entry point run mode should be resolved before deriving config")
             }
         },
-    )
+    ))
 }
 
 /// Write a standalone .rs file containing a function for obtaining the default PythonConfig.
@@ -148,3 +304,73 @@ pub fn write_default_python_config_rs(path: &Path, python_config_rs: &str) -> Re
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_library_names() {
+        assert_eq!(find_library_names("libssl.so.1.1"), vec!["libssl", "ssl"]);
+        assert_eq!(find_library_names("libffi.7.dylib"), vec!["libffi", "ffi"]);
+        assert_eq!(find_library_names("sqlite3.dll"), vec!["sqlite3"]);
+        assert!(find_library_names("_ssl.cpython-38-x86_64-linux-gnu.so").is_empty());
+        assert!(find_library_names("_ssl.pyd").is_empty());
+        assert!(find_library_names("foo.py").is_empty());
+    }
+
+    #[test]
+    fn test_startup_error_messages() -> Result<()> {
+        let mut config = EmbeddedPythonConfig::default();
+        let resources = PathBuf::from("packed-resources");
+
+        let code = derive_python_config(&config, &resources, &BTreeMap::new())?;
+        assert!(code.contains("\n    startup_error_messages: None,\n"));
+
+        config
+            .startup_error_catalog
+            .insert("de".to_string(), "Fehler: {error}".to_string());
+        let code = derive_python_config(&config, &resources, &BTreeMap::new())?;
+        assert!(code.contains(
+            "\n    startup_error_messages: Some(pyembed::StartupErrorMessages { \
+             template: \"{error}\".to_string(), \
+             catalog: vec![(\"de\".to_string(), \"Fehler: {error}\".to_string())] }),\n"
+        ));
+
+        config.startup_error_message = Some("\"###\\ {error}".to_string());
+        let code = derive_python_config(&config, &resources, &BTreeMap::new())?;
+        assert!(code.contains("template: \"\\\"###\\\\ {error}\".to_string()"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_paths_with_special_characters() -> Result<()> {
+        let mut config = EmbeddedPythonConfig::default();
+        config.ctypes_find_library_redirect = true;
+        config.sys_paths = vec![r"$ORIGIN\lib".to_string()];
+        config.run_mode = RunMode::File {
+            path: "/home/jöhn doe/\"main\".py".to_string(),
+        };
+
+        let mut ctypes_library_map = BTreeMap::new();
+        ctypes_library_map.insert("ssl".to_string(), PathBuf::from("lib dir/libssl.so"));
+
+        let resources = PathBuf::from(r"C:\Users\Jöhn Doe\build\packed-resources");
+        let code = derive_python_config(&config, &resources, &ctypes_library_map)?;
+
+        assert!(code.contains("\n    sys_paths: [\"$ORIGIN\\\\lib\".to_string()].to_vec(),\n"));
+        assert!(code.contains(
+            "\n    packed_resources: include_bytes!(\"C:\\\\Users\\\\Jöhn Doe\\\\build\\\\packed-resources\"),\n"
+        ));
+        assert!(
+            code.contains("(\"ssl\".to_string(), std::path::PathBuf::from(\"lib dir/libssl.so\"))")
+        );
+        assert!(code.contains(
+            "\n    run: pyembed::PythonRunMode::File { \
+             path: std::path::PathBuf::from(\"/home/jöhn doe/\\\"main\\\".py\") },\n"
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Parsing of pip requirements files.
+
+Requirements files are validated before being passed to `pip` so problems
+are reported per requirement, with the line they are defined on. Only the
+subset of the format that doesn't reference other files or source trees is
+supported.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::path::Path,
+};
+
+/// Options applying to the whole file that are passed through to pip.
+const GLOBAL_OPTIONS: &[&str] = &[
+    "-i",
+    "--index-url",
+    "--extra-index-url",
+    "--no-index",
+    "-f",
+    "--find-links",
+    "--trusted-host",
+    "--prefer-binary",
+    "--only-binary",
+    "--no-binary",
+];
+
+/// Options taking a value.
+const VALUE_OPTIONS: &[&str] = &[
+    "-i",
+    "--index-url",
+    "--extra-index-url",
+    "-f",
+    "--find-links",
+    "--trusted-host",
+    "--only-binary",
+    "--no-binary",
+];
+
+/// Hash algorithms accepted by pip and the length of their hex digests.
+const HASH_ALGORITHMS: &[(&str, usize)] = &[("sha256", 64), ("sha384", 96), ("sha512", 128)];
+
+/// A requirement in a requirements file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Requirement {
+    /// Line the requirement starts on, starting at 1.
+    pub line: usize,
+
+    /// The requirement specifier, e.g. `foo==1.0`.
+    pub specifier: String,
+
+    /// `--hash` pins, e.g. `sha256:<digest>`.
+    pub hashes: Vec<String>,
+}
+
+impl Requirement {
+    /// The name of the required project.
+    pub fn name(&self) -> &str {
+        let end = self
+            .specifier
+            .find(|c: char| "<>=!~;[@ ".contains(c))
+            .unwrap_or(self.specifier.len());
+
+        &self.specifier[0..end]
+    }
+
+    /// Whether the requirement pins an exact version.
+    pub fn is_pinned(&self) -> bool {
+        let version = self.specifier.split(';').next().unwrap_or("");
+
+        version.contains("==") && !version.contains(',') && !version.contains(".*")
+    }
+}
+
+/// A parsed requirements file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequirementsFile {
+    pub requirements: Vec<Requirement>,
+
+    /// Global options, as pip arguments.
+    pub options: Vec<String>,
+}
+
+/// Split a line into whitespace delimited tokens, splitting `--option=value`.
+fn tokenize(line: &str) -> Vec<String> {
+    line.split_whitespace()
+        .flat_map(|token| {
+            if token.starts_with('-') && token.contains('=') {
+                let mut parts = token.splitn(2, '=');
+                vec![
+                    parts.next().unwrap().to_string(),
+                    parts.next().unwrap().to_string(),
+                ]
+            } else {
+                vec![token.to_string()]
+            }
+        })
+        .collect()
+}
+
+/// Validate a `--hash` value.
+fn validate_hash(hash: &str) -> Result<()> {
+    let mut parts = hash.splitn(2, ':');
+    let algorithm = parts.next().unwrap();
+    let digest = parts
+        .next()
+        .ok_or_else(|| anyhow!("hash {} is not of the form <algorithm>:<digest>", hash))?;
+
+    match HASH_ALGORITHMS.iter().find(|(name, _)| *name == algorithm) {
+        Some((_, length)) => {
+            if digest.len() != *length || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                Err(anyhow!("hash {} is not a valid {} digest", hash, algorithm))
+            } else {
+                Ok(())
+            }
+        }
+        None => Err(anyhow!(
+            "hash algorithm must be one of sha256, sha384 or sha512; got {}",
+            algorithm
+        )),
+    }
+}
+
+impl RequirementsFile {
+    /// Parse the content of a requirements file.
+    ///
+    /// All problems are reported in the returned error, one per line.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut res = Self::default();
+        let mut errors = Vec::new();
+
+        // Logical lines, which continue over physical lines ending with `\`.
+        let mut lines = Vec::new();
+        let mut current: Option<(usize, String)> = None;
+
+        for (i, line) in data.lines().enumerate() {
+            let (start, mut text) = current.take().unwrap_or((i + 1, String::new()));
+
+            if line.ends_with('\\') {
+                text.push_str(&line[0..line.len() - 1]);
+                text.push(' ');
+                current = Some((start, text));
+            } else {
+                text.push_str(line);
+                lines.push((start, text));
+            }
+        }
+
+        if let Some(line) = current {
+            lines.push(line);
+        }
+
+        for (number, line) in lines {
+            // Comments start with `#` at the start of a line or after whitespace.
+            let line = match line.find(" #").or_else(|| line.find("\t#")) {
+                Some(pos) => &line[0..pos],
+                None if line.trim_start().starts_with('#') => "",
+                None => &line,
+            };
+
+            let tokens = tokenize(line);
+
+            if tokens.is_empty() {
+                continue;
+            }
+
+            if tokens[0].starts_with('-') {
+                if let Err(e) = res.parse_option_line(&tokens) {
+                    errors.push(format!("line {}: {}", number, e));
+                }
+
+                continue;
+            }
+
+            match Self::parse_requirement_line(number, &tokens) {
+                Ok(requirement) => res.requirements.push(requirement),
+                Err(e) => errors.push(format!("line {}: {}: {}", number, tokens[0], e)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(res)
+        } else {
+            Err(anyhow!("invalid requirements:\n{}", errors.join("\n")))
+        }
+    }
+
+    /// Parse a requirements file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path).context(format!("reading {}", path.display()))?;
+
+        Self::parse(&data).context(format!("parsing {}", path.display()))
+    }
+
+    fn parse_option_line(&mut self, tokens: &[String]) -> Result<()> {
+        let option = tokens[0].as_str();
+
+        match option {
+            "-r" | "--requirement" | "-c" | "--constraint" => {
+                return Err(anyhow!(
+                    "{} referencing other files is not supported",
+                    option
+                ))
+            }
+            "-e" | "--editable" => {
+                return Err(anyhow!(
+                    "{} installs from source trees are not supported",
+                    option
+                ))
+            }
+            _ => {}
+        }
+
+        if !GLOBAL_OPTIONS.contains(&option) {
+            return Err(anyhow!("unsupported option {}", option));
+        }
+
+        let expected = if VALUE_OPTIONS.contains(&option) {
+            2
+        } else {
+            1
+        };
+
+        if tokens.len() != expected {
+            return Err(anyhow!(
+                "{} requires {} value",
+                option,
+                if expected == 2 { "a" } else { "no" }
+            ));
+        }
+
+        self.options.extend(tokens.iter().cloned());
+
+        Ok(())
+    }
+
+    fn parse_requirement_line(line: usize, tokens: &[String]) -> Result<Requirement> {
+        let split = tokens
+            .iter()
+            .position(|t| t.starts_with("--"))
+            .unwrap_or(tokens.len());
+
+        let specifier = tokens[0..split].join(" ");
+
+        if specifier.contains("://") || specifier.starts_with('.') || specifier.contains('/') {
+            return Err(anyhow!("only requirements of named projects are supported"));
+        }
+
+        let mut hashes = Vec::new();
+        let mut options = tokens[split..].iter();
+
+        while let Some(option) = options.next() {
+            if option != "--hash" {
+                return Err(anyhow!("unsupported option {}", option));
+            }
+
+            let hash = options
+                .next()
+                .ok_or_else(|| anyhow!("--hash requires a value"))?;
+            validate_hash(hash)?;
+            hashes.push(hash.clone());
+        }
+
+        Ok(Requirement {
+            line,
+            specifier,
+            hashes,
+        })
+    }
+
+    /// Ensure every requirement is pinned to a version and hashes.
+    ///
+    /// These are the conditions pip's `--require-hashes` mode enforces.
+    pub fn validate_hashes(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        for requirement in &self.requirements {
+            if !requirement.is_pinned() {
+                errors.push(format!(
+                    "line {}: {}: version is not pinned with ==",
+                    requirement.line,
+                    requirement.name()
+                ));
+            }
+
+            if requirement.hashes.is_empty() {
+                errors.push(format!(
+                    "line {}: {}: no --hash defined",
+                    requirement.line,
+                    requirement.name()
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("requirements not pinned:\n{}", errors.join("\n")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "2cd3f4f1b9abd0dc5d1cc9d1bb3b8e0e2a5b4f4c1a1c1ad1e8ab9aa21b4b2b9c";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let data = format!(
+            "# Comment\n\
+             --index-url https://example.com/simple\n\
+             \n\
+             foo==1.0 \\\n    --hash=sha256:{} \\\n    --hash sha256:{}\n\
+             bar >= 2.0 ; python_version >= \"3.8\"  # Trailing comment\n",
+            SHA256, SHA256
+        );
+
+        let res = RequirementsFile::parse(&data)?;
+        assert_eq!(
+            res.options,
+            vec!["--index-url", "https://example.com/simple"]
+        );
+        assert_eq!(res.requirements.len(), 2);
+
+        let foo = &res.requirements[0];
+        assert_eq!(foo.line, 4);
+        assert_eq!(foo.name(), "foo");
+        assert_eq!(foo.specifier, "foo==1.0");
+        assert_eq!(foo.hashes.len(), 2);
+        assert!(foo.is_pinned());
+
+        let bar = &res.requirements[1];
+        assert_eq!(bar.line, 7);
+        assert_eq!(bar.name(), "bar");
+        assert_eq!(bar.specifier, "bar >= 2.0 ; python_version >= \"3.8\"");
+        assert!(!bar.is_pinned());
+
+        let err = res.validate_hashes().unwrap_err().to_string();
+        assert!(err.contains("line 7: bar: version is not pinned with =="));
+        assert!(err.contains("line 7: bar: no --hash defined"));
+        assert!(!err.contains("foo"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = RequirementsFile::parse(
+            "-r other.txt\n\
+             -e ./src\n\
+             foo==1.0 --hash=md5:abcd\n\
+             bar==1.0 --hash=sha256:abcd\n\
+             baz==1.0 --install-option=--prefix\n\
+             ./qux\n",
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.contains("line 1: -r referencing other files is not supported"));
+        assert!(err.contains("line 2: -e installs from source trees are not supported"));
+        assert!(err.contains("line 3: foo==1.0: hash algorithm must be one of"));
+        assert!(err.contains("line 4: bar==1.0: hash sha256:abcd is not a valid sha256 digest"));
+        assert!(err.contains("line 5: baz==1.0: unsupported option --install-option"));
+        assert!(err.contains("line 6: ./qux: only requirements of named projects"));
+    }
+}
@@ -0,0 +1,148 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Signing of resource files installed next to binaries.
+
+Resources embedded in a binary are as trustworthy as the binary itself.
+Resources installed as files next to it can be modified after installation.
+Each such file can be signed with an Ed25519 key at build time: the
+signature covers the file's install path and content and is written to a
+file with the same path plus a `.sig` suffix. The public key is embedded in
+the binary, whose importer refuses files whose signature doesn't verify.
+*/
+
+use {
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    anyhow::{anyhow, Result},
+    ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey},
+    python_packed_resources::signing::{signed_message, SIGNATURE_SUFFIX},
+    std::ffi::OsString,
+    std::path::PathBuf,
+};
+
+/// An Ed25519 key used to sign resource files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceSigningKey {
+    secret: [u8; 32],
+}
+
+impl ResourceSigningKey {
+    /// Construct an instance from the content of a key file.
+    ///
+    /// The file holds the 32 byte secret key, either as raw bytes or as
+    /// hex, optionally surrounded by whitespace.
+    pub fn from_key_file_data(data: &[u8]) -> Result<Self> {
+        let secret = if data.len() == 32 {
+            data.to_vec()
+        } else {
+            let text = String::from_utf8_lossy(data);
+            hex::decode(text.trim())
+                .or_else(|_| Err(anyhow!("signing key is not 32 bytes or hex")))?
+        };
+
+        if secret.len() != 32 {
+            return Err(anyhow!(
+                "signing key must be 32 bytes; got {}",
+                secret.len()
+            ));
+        }
+
+        let mut res = [0; 32];
+        res.copy_from_slice(&secret);
+
+        Ok(Self { secret: res })
+    }
+
+    fn secret_key(&self) -> SecretKey {
+        SecretKey::from_bytes(&self.secret).expect("secret key is 32 bytes")
+    }
+
+    /// Obtain the public key verifying signatures made with this key.
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.secret_key()).to_bytes()
+    }
+
+    /// Sign data.
+    pub fn sign(&self, data: &[u8]) -> [u8; 64] {
+        let secret = self.secret_key();
+        let public = PublicKey::from(&secret);
+
+        ExpandedSecretKey::from(&secret)
+            .sign(data, &public)
+            .to_bytes()
+    }
+
+    /// Obtain a manifest of signatures of files in a manifest.
+    pub fn sign_manifest(&self, manifest: &FileManifest) -> Result<FileManifest> {
+        let mut res = FileManifest::default();
+
+        for (path, content) in manifest.entries() {
+            let mut signature_path = OsString::from(path.as_os_str());
+            signature_path.push(SIGNATURE_SUFFIX);
+
+            res.add_file(
+                &PathBuf::from(signature_path),
+                &FileContent {
+                    data: self.sign(&signed_message(path, &content.data)).to_vec(),
+                    executable: false,
+                },
+            )?;
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, ed25519_dalek::Signature, std::convert::TryFrom, std::path::Path};
+
+    #[test]
+    fn test_key_file_data() -> Result<()> {
+        let raw = ResourceSigningKey::from_key_file_data(&[7; 32])?;
+        let hex =
+            ResourceSigningKey::from_key_file_data(format!("{}\n", "07".repeat(32)).as_bytes())?;
+        assert_eq!(raw, hex);
+
+        assert!(ResourceSigningKey::from_key_file_data(&[7; 31]).is_err());
+        assert!(ResourceSigningKey::from_key_file_data(b"not a key").is_err());
+        assert!(ResourceSigningKey::from_key_file_data("07".repeat(31).as_bytes()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_manifest() -> Result<()> {
+        let key = ResourceSigningKey::from_key_file_data(&[7; 32])?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file(
+            Path::new("lib/foo.py"),
+            &FileContent {
+                data: b"print('hello')".to_vec(),
+                executable: false,
+            },
+        )?;
+
+        let signatures = key.sign_manifest(&manifest)?;
+        let entries = signatures.entries().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, &PathBuf::from("lib/foo.py.sig"));
+
+        let public = PublicKey::from_bytes(&key.public_key())?;
+        let signature = Signature::try_from(&entries[0].1.data[..])?;
+        public.verify_strict(b"lib/foo.py\0print('hello')", &signature)?;
+        assert!(public
+            .verify_strict(b"lib/foo.py\0print('world')", &signature)
+            .is_err());
+
+        // The signature doesn't verify the same content at another path.
+        assert!(public
+            .verify_strict(b"lib/bar.py\0print('hello')", &signature)
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,754 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Transformation of Python resources as they are collected.
+
+Transforms are declared for resource name patterns and are applied in
+declaration order to module sources and package resources as they are added
+to a binary. A transform can replace a resource or drop it.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    flate2::{write::GzEncoder, Compression},
+    python_packaging::resource::{DataLocation, PythonModuleSource, PythonPackageResource},
+    std::collections::HashMap,
+    std::io::{Read, Write},
+    std::path::{Path, PathBuf},
+    std::process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+/// Python script applying transforms to data sent by PyOxidizer.
+///
+/// Requests are read from stdin as a count of frames followed by the frames,
+/// each prefixed by its length: the name of the operation, its arguments and
+/// the data. Responses are written to stdout prefixed by their length: `\x00`
+/// to drop the data, `\x01` followed by new data or `\x02` followed by an
+/// error message. Hooks are loaded once and their output goes to stderr, so
+/// it doesn't mix with responses.
+///
+/// `minify` removes comments and blank lines by tokenizing the source, so
+/// string literals are preserved.
+///
+/// `strip` removes what its arguments name: `docstrings`, `annotations` or
+/// `asserts`. Statements are replaced by no-op expressions spanning the same
+/// lines, so line numbers in tracebacks are preserved. Docstrings are
+/// replaced by empty strings, since code often assumes `__doc__` is a string.
+/// Source that doesn't parse or isn't UTF-8 is left as is.
+///
+/// `hook` calls a user defined function. Arguments are the path of the file
+/// defining it, the name of the function and the name of the resource.
+const TRANSFORM_SCRIPT: &str = r#"
+import ast, importlib.util, io, struct, sys, tokenize, traceback
+
+
+def minify(data):
+    tokens = [
+        (t.type, t.string)
+        for t in tokenize.tokenize(io.BytesIO(data).readline)
+        if t.type not in (tokenize.COMMENT, tokenize.NL)
+    ]
+    return tokenize.untokenize(tokens)
+
+
+def strip(data, *what):
+    if sys.version_info < (3, 8):
+        raise Exception("stripping source requires Python 3.8 or newer")
+    bom = b"\xef\xbb\xbf" if data.startswith(b"\xef\xbb\xbf") else b""
+    body = data[len(bom):]
+    try:
+        encoding = tokenize.detect_encoding(io.BytesIO(data).readline)[0]
+        tree = ast.parse(body)
+    except SyntaxError:
+        return data
+    if encoding not in ("utf-8", "utf-8-sig"):
+        return data
+    starts = [0]
+    for line in body.splitlines(keepends=True):
+        starts.append(starts[-1] + len(line))
+
+    def offset(lineno, col):
+        return starts[lineno - 1] + col
+
+    edits = []
+
+    def replace_statement(node, value):
+        start = offset(node.lineno, node.col_offset)
+        end = offset(node.end_lineno, node.end_col_offset)
+        newlines = body.count(b"\n", start, end)
+        if newlines:
+            value = b"(" + b"\n" * newlines + value + b")"
+        edits.append((start, end, value))
+
+    def delete_annotation(start, annotation):
+        end = offset(annotation.end_lineno, annotation.end_col_offset)
+        depth = body.count(b"(", start, end) - body.count(b")", start, end)
+        while depth > 0:
+            end = body.index(b")", end) + 1
+            depth -= 1
+        edits.append((start, end, b"\\\n" * body.count(b"\n", start, end)))
+
+    for node in ast.walk(tree):
+        if "docstrings" in what and isinstance(
+            node, (ast.Module, ast.ClassDef, ast.FunctionDef, ast.AsyncFunctionDef)
+        ):
+            first = node.body[0] if node.body else None
+            if (
+                isinstance(first, ast.Expr)
+                and isinstance(first.value, ast.Constant)
+                and isinstance(first.value.value, str)
+            ):
+                replace_statement(first, b'""')
+        if "asserts" in what and isinstance(node, ast.Assert):
+            replace_statement(node, b"None")
+        if "annotations" in what:
+            if isinstance(node, ast.arg) and node.annotation:
+                start = body.index(b":", offset(node.lineno, node.col_offset))
+                delete_annotation(start, node.annotation)
+            elif isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)) and node.returns:
+                returns = offset(node.returns.lineno, node.returns.col_offset)
+                delete_annotation(body.rindex(b"->", 0, returns), node.returns)
+            elif isinstance(node, ast.AnnAssign) and node.value is None:
+                replace_statement(node, b"None")
+            elif isinstance(node, ast.AnnAssign):
+                target = offset(node.target.end_lineno, node.target.end_col_offset)
+                delete_annotation(body.index(b":", target), node.annotation)
+    res = body
+    last = len(body)
+    for start, end, value in sorted(edits, reverse=True):
+        if end <= last:
+            res = res[:start] + value + res[end:]
+            last = start
+    compile(res, "<stripped>", "exec")
+    return bom + res
+
+
+hooks = {}
+
+
+def hook(data, path, function, name):
+    if path not in hooks:
+        spec = importlib.util.spec_from_file_location(
+            "pyoxidizer_resource_transform_%d" % len(hooks), path
+        )
+        module = importlib.util.module_from_spec(spec)
+        spec.loader.exec_module(module)
+        hooks[path] = module
+    res = getattr(hooks[path], function)(name, data)
+    return None if res is None else bytes(res)
+
+
+OPERATIONS = {"minify": minify, "strip": strip, "hook": hook}
+
+stdin = sys.stdin.buffer
+stdout = sys.stdout.buffer
+sys.stdout = sys.stderr
+
+
+def read(size):
+    data = stdin.read(size)
+    if len(data) != size:
+        raise EOFError
+    return data
+
+
+def read_frame():
+    return read(struct.unpack("<I", read(4))[0])
+
+
+while True:
+    header = stdin.read(4)
+    if not header:
+        break
+    frames = [read_frame() for _ in range(struct.unpack("<I", header)[0])]
+    operation, args, data = frames[0].decode(), frames[1:-1], frames[-1]
+    try:
+        res = OPERATIONS[operation](data, *(arg.decode() for arg in args))
+        response = b"\x00" if res is None else b"\x01" + res
+    except Exception:
+        response = b"\x02" + traceback.format_exc().encode()
+    stdout.write(struct.pack("<I", len(response)) + response)
+    stdout.flush()
+"#;
+
+/// What a transform does to matching resources.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResourceTransformAction {
+    /// Drop package resources in `__pycache__` directories.
+    StripPycache,
+
+    /// Remove comments and blank lines from module source.
+    Minify,
+
+    /// Compress package resources with gzip, adding a `.gz` suffix to their name.
+    Gzip,
+
+    /// Remove docstrings from module source.
+    StripDocstrings,
+
+    /// Remove type annotations of functions and variables from module source.
+    StripAnnotations,
+
+    /// Remove assert statements from module source.
+    StripAsserts,
+
+    /// Call a Python function with the resource name and data.
+    ///
+    /// The function returns the new data, or `None` to drop the resource.
+    PythonHook { path: PathBuf, function: String },
+}
+
+impl ResourceTransformAction {
+    /// Resolve a built-in transform from its name.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "strip_pycache" => Ok(ResourceTransformAction::StripPycache),
+            "minify" => Ok(ResourceTransformAction::Minify),
+            "gzip" => Ok(ResourceTransformAction::Gzip),
+            "strip_docstrings" => Ok(ResourceTransformAction::StripDocstrings),
+            "strip_annotations" => Ok(ResourceTransformAction::StripAnnotations),
+            "strip_asserts" => Ok(ResourceTransformAction::StripAsserts),
+            _ => Err(anyhow!(
+                "transform must be one of strip_pycache, minify, gzip, strip_docstrings, strip_annotations or strip_asserts; got {}",
+                name
+            )),
+        }
+    }
+}
+
+/// A transform applied to resources whose name matches a pattern.
+#[derive(Clone, Debug)]
+pub struct ResourceTransform {
+    /// Glob pattern matched against module names and package resource
+    /// symbolic names (`<package>:<relative name>`).
+    pub pattern: glob::Pattern,
+
+    pub action: ResourceTransformAction,
+}
+
+/// A running Python process applying transforms.
+struct TransformProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+impl TransformProcess {
+    fn start(python_exe: &Path) -> Result<Self> {
+        let mut child = Command::new(python_exe)
+            .arg("-c")
+            .arg(TRANSFORM_SCRIPT)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("running Python")?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("unable to get stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("unable to get stdout"))?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn run(&mut self, operation: &str, args: &[&str], data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let frames = std::iter::once(operation.as_bytes())
+            .chain(args.iter().map(|arg| arg.as_bytes()))
+            .chain(std::iter::once(data));
+
+        let mut request = ((args.len() + 2) as u32).to_le_bytes().to_vec();
+        for frame in frames {
+            request.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            request.extend_from_slice(frame);
+        }
+
+        self.stdin
+            .write_all(&request)
+            .and_then(|_| self.stdin.flush())
+            .context("writing to Python")?;
+
+        let mut size = [0; 4];
+        self.stdout
+            .read_exact(&mut size)
+            .context("reading from Python")?;
+        let mut response = vec![0; u32::from_le_bytes(size) as usize];
+        self.stdout
+            .read_exact(&mut response)
+            .context("reading from Python")?;
+
+        match response.split_first() {
+            Some((&0, _)) => Ok(None),
+            Some((&1, data)) => Ok(Some(data.to_vec())),
+            Some((&2, message)) => Err(anyhow!("{}", String::from_utf8_lossy(message))),
+            _ => Err(anyhow!("unexpected output from Python")),
+        }
+    }
+}
+
+impl Drop for TransformProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Runs transforms implemented in Python.
+///
+/// A single Python process is started on first use and serves every
+/// transform afterwards, so transforms don't start a process per resource.
+struct PythonTransformer {
+    python_exe: PathBuf,
+    process: Option<TransformProcess>,
+}
+
+impl PythonTransformer {
+    fn run(&mut self, operation: &str, args: &[&str], data: &[u8]) -> Result<Option<Vec<u8>>> {
+        if self.process.is_none() {
+            self.process = Some(TransformProcess::start(&self.python_exe)?);
+        }
+
+        let process = self.process.as_mut().unwrap();
+        let res = process.run(operation, args, data);
+
+        // Start a new process next time if this one exited.
+        if let Ok(Some(_)) = process.child.try_wait() {
+            self.process = None;
+        }
+
+        res
+    }
+}
+
+/// Strip `what` (`docstrings`, `annotations` or `asserts`) from module source.
+fn strip_source(
+    python: &mut PythonTransformer,
+    what: &str,
+    name: &str,
+    source: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    python
+        .run("strip", &[what], source)
+        .context(format!("stripping {} from {}", what, name))
+}
+
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+impl ResourceTransform {
+    pub fn new(pattern: &str, action: ResourceTransformAction) -> Result<Self> {
+        Ok(Self {
+            pattern: glob::Pattern::new(pattern)
+                .or_else(|e| Err(anyhow!("invalid pattern {}: {}", pattern, e)))?,
+            action,
+        })
+    }
+
+    fn run_hook(
+        &self,
+        python: &mut PythonTransformer,
+        name: &str,
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        match &self.action {
+            ResourceTransformAction::PythonHook { path, function } => python
+                .run("hook", &[&path.display().to_string(), function, name], data)
+                .context(format!("running {} on {}", function, name)),
+            _ => Ok(Some(data.to_vec())),
+        }
+    }
+
+    /// Apply this transform to module source.
+    fn transform_module_source(
+        &self,
+        python: &mut PythonTransformer,
+        module: &PythonModuleSource,
+    ) -> Result<Option<PythonModuleSource>> {
+        if !self.pattern.matches(&module.name) {
+            return Ok(Some(module.clone()));
+        }
+
+        let source = module.source.resolve()?;
+
+        let source = match &self.action {
+            ResourceTransformAction::StripPycache | ResourceTransformAction::Gzip => Some(source),
+            ResourceTransformAction::Minify => python
+                .run("minify", &[], &source)
+                .context(format!("minifying {}", module.name))?,
+            ResourceTransformAction::StripDocstrings => {
+                strip_source(python, "docstrings", &module.name, &source)?
+            }
+            ResourceTransformAction::StripAnnotations => {
+                strip_source(python, "annotations", &module.name, &source)?
+            }
+            ResourceTransformAction::StripAsserts => {
+                strip_source(python, "asserts", &module.name, &source)?
+            }
+            ResourceTransformAction::PythonHook { .. } => {
+                self.run_hook(python, &module.name, &source)?
+            }
+        };
+
+        Ok(source.map(|source| PythonModuleSource {
+            source: DataLocation::Memory(source),
+            ..module.clone()
+        }))
+    }
+
+    /// Apply this transform to a package resource.
+    fn transform_package_resource(
+        &self,
+        python: &mut PythonTransformer,
+        resource: &PythonPackageResource,
+    ) -> Result<Option<PythonPackageResource>> {
+        let name = resource.symbolic_name();
+
+        if !self.pattern.matches(&name) {
+            return Ok(Some(resource.clone()));
+        }
+
+        match &self.action {
+            ResourceTransformAction::StripPycache => {
+                if Path::new(&resource.relative_name)
+                    .components()
+                    .any(|c| c.as_os_str() == "__pycache__")
+                {
+                    Ok(None)
+                } else {
+                    Ok(Some(resource.clone()))
+                }
+            }
+            ResourceTransformAction::Minify
+            | ResourceTransformAction::StripDocstrings
+            | ResourceTransformAction::StripAnnotations
+            | ResourceTransformAction::StripAsserts => Ok(Some(resource.clone())),
+            ResourceTransformAction::Gzip => Ok(Some(PythonPackageResource {
+                leaf_package: resource.leaf_package.clone(),
+                relative_name: format!("{}.gz", resource.relative_name),
+                data: DataLocation::Memory(gzip(&resource.data.resolve()?)?),
+            })),
+            ResourceTransformAction::PythonHook { .. } => Ok(self
+                .run_hook(python, &name, &resource.data.resolve()?)?
+                .map(|data| PythonPackageResource {
+                    leaf_package: resource.leaf_package.clone(),
+                    relative_name: resource.relative_name.clone(),
+                    data: DataLocation::Memory(data),
+                })),
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.action {
+            ResourceTransformAction::StripPycache => write!(f, "strip_pycache")?,
+            ResourceTransformAction::Minify => write!(f, "minify")?,
+            ResourceTransformAction::Gzip => write!(f, "gzip")?,
+            ResourceTransformAction::StripDocstrings => write!(f, "strip_docstrings")?,
+            ResourceTransformAction::StripAnnotations => write!(f, "strip_annotations")?,
+            ResourceTransformAction::StripAsserts => write!(f, "strip_asserts")?,
+            ResourceTransformAction::PythonHook { path, function } => {
+                write!(f, "{}() in {}", function, path.display())?
+            }
+        }
+
+        write!(f, " on {}", self.pattern)
+    }
+}
+
+/// Transforms applied in order to resources.
+///
+/// Resources dropped by a transform are recorded, so callers can report why
+/// they weren't added.
+pub struct ResourceTransforms {
+    transforms: Vec<ResourceTransform>,
+    python: PythonTransformer,
+    dropped: HashMap<String, String>,
+}
+
+impl ResourceTransforms {
+    /// Create an instance running transforms implemented in Python with `python_exe`.
+    pub fn new(python_exe: &Path) -> Self {
+        Self {
+            transforms: Vec::new(),
+            python: PythonTransformer {
+                python_exe: python_exe.to_path_buf(),
+                process: None,
+            },
+            dropped: HashMap::new(),
+        }
+    }
+
+    /// Add a transform applied after the existing ones.
+    pub fn push(&mut self, transform: ResourceTransform) {
+        self.transforms.push(transform);
+    }
+
+    /// Apply transforms in order to module source.
+    pub fn transform_module_source(
+        &mut self,
+        module: &PythonModuleSource,
+    ) -> Result<Option<PythonModuleSource>> {
+        let name = module.name.clone();
+        let mut module = module.clone();
+
+        for transform in &self.transforms {
+            module = match transform.transform_module_source(&mut self.python, &module)? {
+                Some(module) => module,
+                None => {
+                    self.dropped.insert(name, transform.to_string());
+                    return Ok(None);
+                }
+            };
+        }
+
+        self.dropped.remove(&name);
+
+        Ok(Some(module))
+    }
+
+    /// Apply transforms in order to a package resource.
+    pub fn transform_package_resource(
+        &mut self,
+        resource: &PythonPackageResource,
+    ) -> Result<Option<PythonPackageResource>> {
+        let name = resource.symbolic_name();
+        let mut resource = resource.clone();
+
+        for transform in &self.transforms {
+            resource = match transform.transform_package_resource(&mut self.python, &resource)? {
+                Some(resource) => resource,
+                None => {
+                    self.dropped.insert(name, transform.to_string());
+                    return Ok(None);
+                }
+            };
+        }
+
+        self.dropped.remove(&name);
+
+        Ok(Some(resource))
+    }
+
+    /// Obtain the description of the transform that dropped a resource.
+    ///
+    /// `name` is a module name or package resource symbolic name. Only the
+    /// last time a resource was transformed is considered.
+    pub fn dropped_by(&self, name: &str) -> Option<&str> {
+        self.dropped.get(name).map(|transform| transform.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::distribution::PythonDistribution, super::*, crate::testutil::*};
+
+    fn package_resource(relative_name: &str, data: &[u8]) -> PythonPackageResource {
+        PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: relative_name.to_string(),
+            data: DataLocation::Memory(data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            ResourceTransformAction::from_name("gzip").unwrap(),
+            ResourceTransformAction::Gzip
+        );
+        assert!(ResourceTransformAction::from_name("brotli").is_err());
+    }
+
+    #[test]
+    fn test_package_resource_transforms() -> Result<()> {
+        let mut transforms = ResourceTransforms::new(Path::new("python"));
+        transforms.push(ResourceTransform::new(
+            "foo:*",
+            ResourceTransformAction::StripPycache,
+        )?);
+        transforms.push(ResourceTransform::new(
+            "foo:static/*.js",
+            ResourceTransformAction::Gzip,
+        )?);
+
+        assert!(transforms
+            .transform_package_resource(&package_resource("__pycache__/bar.cpython-37.pyc", b""))?
+            .is_none());
+        assert_eq!(
+            transforms.dropped_by("foo:__pycache__/bar.cpython-37.pyc"),
+            Some("strip_pycache on foo:*")
+        );
+
+        let res = transforms
+            .transform_package_resource(&package_resource(
+                "static/app.js",
+                b"console.log('hello');",
+            ))?
+            .unwrap();
+        assert_eq!(res.relative_name, "static/app.js.gz");
+        assert_eq!(&res.data.resolve()?[0..2], &[0x1f, 0x8b]);
+
+        let res = transforms
+            .transform_package_resource(&package_resource("static/app.css", b"body {}"))?
+            .unwrap();
+        assert_eq!(res, package_resource("static/app.css", b"body {}"));
+        assert_eq!(transforms.dropped_by("foo:static/app.css"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minify() -> Result<()> {
+        let distribution = get_default_distribution()?;
+        let mut transforms = ResourceTransforms::new(distribution.python_exe_path());
+        transforms.push(ResourceTransform::new(
+            "foo.*",
+            ResourceTransformAction::Minify,
+        )?);
+
+        let module = PythonModuleSource {
+            name: "foo.bar".to_string(),
+            source: DataLocation::Memory(
+                b"# Comment.\n\ndef f():\n    \"\"\"# Not a comment.\"\"\"\n    return 1  # One.\n"
+                    .to_vec(),
+            ),
+            is_package: false,
+            cache_tag: distribution.cache_tag().to_string(),
+        };
+
+        let res = transforms.transform_module_source(&module)?.unwrap();
+        let source = String::from_utf8(res.source.resolve()?)?;
+        assert!(!source.contains("# Comment."));
+        assert!(!source.contains("# One."));
+        assert!(source.contains("\"\"\"# Not a comment.\"\"\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip() -> Result<()> {
+        let distribution = get_default_distribution()?;
+        let mut transforms = ResourceTransforms::new(distribution.python_exe_path());
+        for action in vec![
+            ResourceTransformAction::StripDocstrings,
+            ResourceTransformAction::StripAnnotations,
+            ResourceTransformAction::StripAsserts,
+        ] {
+            transforms.push(ResourceTransform::new("foo.*", action)?);
+        }
+
+        let source = indoc::indoc!(
+            r#"
+            """Module docstring."""
+            from __future__ import annotations
+
+            def f(x: int, y: dict[
+                    str, int] = None) -> int:
+                """Function
+                docstring."""
+                assert x > 0, (
+                    "x must be positive")
+                z: int = x
+                w: str
+                return z
+
+            class C:
+                """Class docstring."""
+                a: int
+            "#
+        );
+
+        let module = PythonModuleSource {
+            name: "foo.bar".to_string(),
+            source: DataLocation::Memory(source.as_bytes().to_vec()),
+            is_package: false,
+            cache_tag: distribution.cache_tag().to_string(),
+        };
+
+        let res = transforms.transform_module_source(&module)?.unwrap();
+        let stripped = String::from_utf8(res.source.resolve()?)?;
+        assert!(!stripped.contains("docstring"));
+        assert!(!stripped.contains("int"));
+        assert!(!stripped.contains("assert"));
+        assert!(stripped.contains("def f(x, y"));
+        assert!(stripped.contains("z = x\n"));
+        // Line numbers are preserved.
+        assert_eq!(stripped.lines().count(), source.lines().count());
+
+        // Source that doesn't parse is left as is.
+        let module = PythonModuleSource {
+            source: DataLocation::Memory(b"print 'hello'\n".to_vec()),
+            ..module
+        };
+        let res = transforms.transform_module_source(&module)?.unwrap();
+        assert_eq!(res.source.resolve()?, b"print 'hello'\n".to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_python_hook() -> Result<()> {
+        let distribution = get_default_distribution()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let hook_path = temp_dir.path().join("hook.py");
+        std::fs::write(
+            &hook_path,
+            indoc::indoc!(
+                r#"
+                calls = []
+
+                def transform(name, data):
+                    calls.append(name)
+                    print("transforming", name)
+                    return b"%d" % len(calls)
+
+                def fail(name, data):
+                    raise ValueError("bad resource")
+                "#
+            ),
+        )?;
+
+        let mut transforms = ResourceTransforms::new(distribution.python_exe_path());
+        for (pattern, function) in &[("foo:*", "transform"), ("bar:*", "fail")] {
+            transforms.push(ResourceTransform::new(
+                pattern,
+                ResourceTransformAction::PythonHook {
+                    path: hook_path.clone(),
+                    function: function.to_string(),
+                },
+            )?);
+        }
+
+        // Resources are transformed by the same process.
+        for expected in &[b"1", b"2"] {
+            let res = transforms
+                .transform_package_resource(&package_resource("data.txt", b""))?
+                .unwrap();
+            assert_eq!(res.data.resolve()?, expected.to_vec());
+        }
+
+        let err = transforms
+            .transform_package_resource(&PythonPackageResource {
+                leaf_package: "bar".to_string(),
+                ..package_resource("data.txt", b"")
+            })
+            .unwrap_err();
+        assert!(format!("{:?}", err).contains("ValueError: bad resource"));
+
+        Ok(())
+    }
+}
@@ -6,30 +6,47 @@
 
 use {
     super::binary::{
-        EmbeddedPythonBinaryData, EmbeddedResourcesBlobs, PythonBinaryBuilder, PythonLinkingInfo,
+        EmbeddedPythonBinaryData, EmbeddedPythonResourcesData, EmbeddedResourcesBlobs,
+        PythonBinaryBuilder, PythonLinkingInfo,
     },
-    super::config::{EmbeddedPythonConfig, RawAllocator},
+    super::compatibility::{find_compatibility_issues, CompatibilityIssue},
+    super::config::{EmbeddedPythonConfig, RawAllocator, RunMode},
     super::distribution::{
-        is_stdlib_test_package, resolve_python_distribution_from_location, DistributionExtractLock,
-        ExtensionModuleFilter, PythonDistribution, PythonDistributionLocation,
+        is_stdlib_test_package, resolve_python_distribution_from_location, resource_display_name,
+        DistributionExtractLock, ExtensionModuleFilter, PythonDistribution,
+        PythonDistributionLocation,
     },
     super::distutils::prepare_hacked_distutils,
     super::embedded_resource::{EmbeddedPythonResources, PrePackagedResources},
     super::libpython::link_libpython,
-    crate::app_packaging::resource::FileContent,
+    super::packaging_tool::pip_install,
+    super::resource_signing::ResourceSigningKey,
+    super::startup_cache::{startup_cache_module_source, STARTUP_CACHE_MODULE_NAME},
+    super::venv::{venv_module_source, VENV_MODULE_NAME, VENV_REQUIRED_PACKAGES},
+    super::wheel_tags::WheelCompatibilityTags,
+    super::zipapp::{write_zipapp, zipapp_main_source, ZIPAPP_MAIN},
+    crate::app_packaging::linux_service::LinuxServiceOptions,
+    crate::app_packaging::macos_signing::MacOsSigningOptions,
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::app_packaging::windows_manifest::WindowsManifestOptions,
     crate::licensing::NON_GPL_LICENSES,
+    crate::path_util::join_search_paths,
     anyhow::{anyhow, Context, Result},
     copy_dir::copy_dir,
     lazy_static::lazy_static,
-    python_packaging::bytecode::BytecodeCompiler,
+    python_packaging::bytecode::{BytecodeCompiler, BytecodeCompilerBackend},
     python_packaging::filesystem_scanning::{find_python_resources, walk_tree_files},
     python_packaging::module_util::{is_package_from_path, PythonModuleSuffixes},
+    python_packaging::package_metadata::PythonPackageRecord,
     python_packaging::resource::{
-        BytecodeOptimizationLevel, DataLocation, PythonExtensionModule,
+        BytecodeOptimizationLevel, DataLocation, PythonExtensionModule, PythonModuleBytecode,
         PythonModuleBytecodeFromSource, PythonModuleSource, PythonPackageDistributionResource,
         PythonPackageResource, PythonResource,
     },
-    python_packaging::resource_collection::PythonResourcesPolicy,
+    python_packaging::resource_collection::{
+        target_supports_in_memory_shared_library_loading, FilesystemModuleLayout,
+        PrePackagedResource, PythonResourcesPolicy,
+    },
     serde::{Deserialize, Serialize},
     slog::{info, warn},
     std::collections::{BTreeMap, BTreeSet, HashMap},
@@ -56,6 +73,12 @@ const PIP_EXE_BASENAME: &str = "pip3.exe";
 #[cfg(unix)]
 const PIP_EXE_BASENAME: &str = "pip3";
 
+/// Name of the installed file holding bytecode stored remotely.
+const REMOTE_RESOURCES_ARCHIVE_NAME: &str = "remote-resources";
+
+/// Name of the installed zip archive holding modules imported via `zipimport`.
+const ZIPIMPORT_ARCHIVE_NAME: &str = "python-modules.zip";
+
 lazy_static! {
     /// Distribution extensions with known problems on Linux.
     ///
@@ -540,6 +563,65 @@ pub struct StandaloneDistribution {
 
     /// Suffixes for Python module types.
     module_suffixes: PythonModuleSuffixes,
+
+    /// Distribution for the build machine running build-time operations, if any.
+    ///
+    /// Only set for distributions targeting another platform. See
+    /// `set_host_distribution()`.
+    host_distribution: Option<Box<StandaloneDistribution>>,
+}
+
+/// Name of the file marking a completely extracted distribution.
+const EXTRACT_COMPLETE_MARKER: &str = "distribution-extract-complete";
+
+/// Extract a distribution from a tar stream to `extract_dir`.
+///
+/// Nothing is extracted if the distribution was already extracted. A marker
+/// file written after the last extracted file tells complete extractions
+/// from interrupted ones, which are extracted again.
+fn extract_tar<R: Read>(source: R, extract_dir: &Path) -> Result<()> {
+    let mut tf = tar::Archive::new(source);
+
+    let _lock = DistributionExtractLock::new(extract_dir)?;
+
+    let marker_path = extract_dir.join(EXTRACT_COMPLETE_MARKER);
+    if marker_path.exists() {
+        return Ok(());
+    }
+
+    let python_path = extract_dir.join("python");
+    if python_path.exists() {
+        std::fs::remove_dir_all(&python_path)
+            .with_context(|| format!("removing {}", python_path.display()))?;
+    }
+
+    let absolute_path = std::fs::canonicalize(extract_dir)?;
+    tf.unpack(&absolute_path)
+        .with_context(|| "unable to extract tar archive")?;
+
+    // Ensure unpacked files are writable. We've had issues where we
+    // consume archives with read-only file permissions. When we later
+    // copy these files, we can run into trouble overwriting a read-only
+    // file.
+    let walk = walkdir::WalkDir::new(&absolute_path);
+    for entry in walk.into_iter() {
+        let entry = entry?;
+
+        let metadata = entry.metadata()?;
+        let mut permissions = metadata.permissions();
+
+        if permissions.readonly() {
+            permissions.set_readonly(false);
+            std::fs::set_permissions(entry.path(), permissions).with_context(|| {
+                format!("unable to mark {} as writable", entry.path().display())
+            })?;
+        }
+    }
+
+    std::fs::write(&marker_path, b"")
+        .with_context(|| format!("writing {}", marker_path.display()))?;
+
+    Ok(())
 }
 
 impl StandaloneDistribution {
@@ -547,9 +629,14 @@ impl StandaloneDistribution {
         logger: &slog::Logger,
         location: &PythonDistributionLocation,
         distributions_dir: &Path,
+        offline: bool,
     ) -> Result<Self> {
-        let (archive_path, extract_path) =
-            resolve_python_distribution_from_location(logger, location, distributions_dir)?;
+        let (archive_path, extract_path) = resolve_python_distribution_from_location(
+            logger,
+            location,
+            distributions_dir,
+            offline,
+        )?;
 
         Self::from_tar_zst_file(logger, &archive_path, &extract_path)
     }
@@ -589,40 +676,7 @@ impl StandaloneDistribution {
 
     /// Extract and analyze a standalone distribution from a tar stream.
     pub fn from_tar<R: Read>(source: R, extract_dir: &Path) -> Result<Self> {
-        let mut tf = tar::Archive::new(source);
-
-        {
-            let _lock = DistributionExtractLock::new(extract_dir)?;
-
-            // The content of the distribution could change between runs. But caching
-            // the extraction does keep things fast.
-            let test_path = extract_dir.join("python").join("PYTHON.json");
-            if !test_path.exists() {
-                std::fs::create_dir_all(extract_dir)?;
-                let absolute_path = std::fs::canonicalize(extract_dir)?;
-                tf.unpack(&absolute_path)
-                    .with_context(|| "unable to extract tar archive")?;
-
-                // Ensure unpacked files are writable. We've had issues where we
-                // consume archives with read-only file permissions. When we later
-                // copy these files, we can run into trouble overwriting a read-only
-                // file.
-                let walk = walkdir::WalkDir::new(&absolute_path);
-                for entry in walk.into_iter() {
-                    let entry = entry?;
-
-                    let metadata = entry.metadata()?;
-                    let mut permissions = metadata.permissions();
-
-                    if permissions.readonly() {
-                        permissions.set_readonly(false);
-                        std::fs::set_permissions(entry.path(), permissions).with_context(|| {
-                            format!("unable to mark {} as writable", entry.path().display())
-                        })?;
-                    }
-                }
-            }
-        }
+        extract_tar(source, extract_dir)?;
 
         Self::from_directory(extract_dir)
     }
@@ -922,9 +976,32 @@ impl StandaloneDistribution {
             inittab_cflags: pi.build_info.inittab_cflags,
             cache_tag: pi.python_implementation_cache_tag,
             module_suffixes,
+            host_distribution: None,
         })
     }
 
+    /// Run build-time operations with a distribution for the build machine.
+    ///
+    /// A distribution targeting another platform can't run its own `python`
+    /// on the build machine. The `python` of `host`, which must provide the
+    /// same Python version, then installs packages and compiles bytecode.
+    pub fn set_host_distribution(&mut self, host: StandaloneDistribution) -> Result<()> {
+        if host.python_major_minor_version() != self.python_major_minor_version() {
+            return Err(anyhow!(
+                "distribution for {} provides Python {}; distribution for {} provides Python {}",
+                host.target_triple,
+                host.python_major_minor_version(),
+                self.target_triple,
+                self.python_major_minor_version()
+            ));
+        }
+
+        self.python_exe = host.python_exe.clone();
+        self.host_distribution = Some(Box::new(host));
+
+        Ok(())
+    }
+
     /// Duplicate the python distribution, with distutils hacked
     #[allow(unused)]
     pub fn create_hacked_base(&self, logger: &slog::Logger) -> PythonPaths {
@@ -985,19 +1062,13 @@ impl StandaloneDistribution {
 
         let prefix_s = python_paths.prefix.display().to_string();
 
-        let venv_path_bin_s = python_paths.bin_dir.display().to_string();
-
-        let path_separator = if cfg!(windows) { ";" } else { ":" };
-
-        if let Ok(path) = std::env::var("PATH") {
-            extra_envs.insert(
-                "PATH".to_string(),
-                format!("{}{}{}", venv_path_bin_s, path_separator, path),
-            );
-        } else {
-            extra_envs.insert("PATH".to_string(), venv_path_bin_s);
+        let mut search_paths = vec![python_paths.bin_dir.clone()];
+        if let Some(path) = std::env::var_os("PATH") {
+            search_paths.extend(std::env::split_paths(&path));
         }
 
+        extra_envs.insert("PATH".to_string(), join_search_paths(search_paths)?);
+
         let site_packages_s = python_paths.site_packages.display().to_string();
         if site_packages_s.starts_with("\\\\?\\") {
             panic!("unexpected Windows UNC path in site-packages");
@@ -1035,6 +1106,19 @@ impl PythonDistribution for StandaloneDistribution {
         &self.cache_tag
     }
 
+    fn target_triple(&self) -> &str {
+        &self.target_triple
+    }
+
+    fn wheel_compatibility_tags(&self) -> Result<WheelCompatibilityTags> {
+        WheelCompatibilityTags::new(
+            &self.python_major_minor_version(),
+            self.python_abi_tag.as_deref(),
+            &self.python_platform_tag,
+            &self.target_triple,
+        )
+    }
+
     fn python_module_suffixes(&self) -> Result<PythonModuleSuffixes> {
         Ok(self.module_suffixes.clone())
     }
@@ -1056,20 +1140,44 @@ impl PythonDistribution for StandaloneDistribution {
         include_sources: bool,
         include_resources: bool,
         include_test: bool,
+        allow_in_memory_shared_library_loading: bool,
+        extract_shared_libraries_to_cache: bool,
     ) -> Result<Box<dyn PythonBinaryBuilder>> {
         let python_exe = self.python_exe.clone();
 
+        let mut config = config.clone();
+        // Lets the importer extract libraries failing to load from memory.
+        config.extract_shared_libraries_to_cache = extract_shared_libraries_to_cache;
+
+        let mut resources = PrePackagedResources::new(resources_policy, &self.cache_tag);
+        resources.set_stdlib_packages(
+            self.py_modules
+                .keys()
+                .map(|name| name.split('.').next().unwrap_or_default().to_string())
+                .collect(),
+        );
+
         let mut builder = Box::new(StandalonePythonExecutableBuilder {
             host_triple: host_triple.to_string(),
             target_triple: target_triple.to_string(),
             exe_name: name.to_string(),
             distribution: self.clone(),
             resources_policy: resources_policy.clone(),
-            resources: PrePackagedResources::new(resources_policy, &self.cache_tag),
-            config: config.clone(),
+            resources,
+            config,
             python_exe,
             extension_module_filter: extension_module_filter.clone(),
             extension_module_variants: preferred_extension_module_variants,
+            allow_in_memory_shared_library_loading,
+            extract_shared_libraries_to_cache,
+            remote_resources_packages: BTreeSet::new(),
+            zipimport_packages: BTreeSet::new(),
+            resources_signing_key: None,
+            windows_manifest: WindowsManifestOptions::default(),
+            macos_signing: None,
+            linux_service: None,
+            debug_build: false,
+            compress_resources: false,
         });
 
         builder.add_distribution_resources(
@@ -1243,11 +1351,19 @@ impl PythonDistribution for StandaloneDistribution {
 
     /// Ensure pip is available to run in the distribution.
     fn ensure_pip(&self, logger: &slog::Logger) -> Result<PathBuf> {
+        if let Some(host) = &self.host_distribution {
+            return host.ensure_pip(logger);
+        }
+
         let dist_prefix = self.base_dir.join("python").join("install");
         let python_paths = resolve_python_paths(&dist_prefix, &self.version);
 
         let pip_path = python_paths.bin_dir.join(PIP_EXE_BASENAME);
 
+        // ensurepip modifies the extracted distribution, which other builds
+        // may be using.
+        let _lock = DistributionExtractLock::new(&self.base_dir)?;
+
         if !pip_path.exists() {
             warn!(logger, "{} doesnt exist", pip_path.display().to_string());
             invoke_python(&python_paths, &logger, &["-m", "ensurepip"]);
@@ -1279,38 +1395,60 @@ impl PythonDistribution for StandaloneDistribution {
         &self,
         logger: &slog::Logger,
         resources: &[PythonResource],
+        strict: bool,
     ) -> Result<Vec<PythonResource>> {
-        Ok(resources
-            .iter()
-            .filter(|resource| match resource {
+        let mut res = Vec::new();
+        let mut incompatible = Vec::new();
+
+        for resource in resources {
+            let reason = match resource {
                 // Extension modules defined as shared libraries are only compatible
                 // with some configurations.
                 PythonResource::ExtensionModuleDynamicLibrary { .. } => {
                     if self.is_extension_module_file_loadable() {
-                        true
+                        None
                     } else {
                         warn!(logger, "ignoring extension module {} because it isn't loadable for the target configuration",
                             resource.full_name());
-                        false
+                        Some("extension module isn't loadable for the target configuration")
                     }
                 }
 
                 // Only look at the raw object files if the distribution produces
                 // them.
                 // TODO have PythonDistribution expose API to determine this.
-                PythonResource::ExtensionModuleStaticallyLinked(_) =>
-                    self.link_mode == StandaloneDistributionLinkMode::Static,
-
-                PythonResource::ModuleSource { .. } => true,
-                PythonResource::ModuleBytecodeRequest { .. } => true,
-                PythonResource::ModuleBytecode { .. } => true,
-                PythonResource::Resource { .. } => true,
-                PythonResource::DistributionResource(_) => true,
-                PythonResource::EggFile(_) => false,
-                PythonResource::PathExtension(_) => false,
-            })
-            .cloned()
-            .collect())
+                PythonResource::ExtensionModuleStaticallyLinked(_) => {
+                    if self.link_mode == StandaloneDistributionLinkMode::Static {
+                        None
+                    } else {
+                        Some("statically linked extension modules require a statically linked distribution")
+                    }
+                }
+
+                PythonResource::ModuleSource { .. } => None,
+                PythonResource::ModuleBytecodeRequest { .. } => None,
+                PythonResource::ModuleBytecode { .. } => None,
+                PythonResource::Resource { .. } => None,
+                PythonResource::DistributionResource(_) => None,
+                PythonResource::EggFile(_) => Some("egg files aren't supported"),
+                PythonResource::PathExtension(_) => Some("path extensions aren't supported"),
+            };
+
+            if let Some(reason) = reason {
+                incompatible.push(format!("{}: {}", resource_display_name(resource), reason));
+            } else {
+                res.push(resource.clone());
+            }
+        }
+
+        if strict && !incompatible.is_empty() {
+            return Err(anyhow!(
+                "resources incompatible with the distribution:\n{}",
+                incompatible.join("\n")
+            ));
+        }
+
+        Ok(res)
     }
 }
 
@@ -1349,14 +1487,75 @@ pub struct StandalonePythonExecutableBuilder {
 
     /// Preferred extension module variants.
     extension_module_variants: Option<HashMap<String, String>>,
+
+    /// Whether extension modules can be loaded from memory on targets supporting it.
+    allow_in_memory_shared_library_loading: bool,
+
+    /// Whether extension modules can be extracted to a cache directory at run-time
+    /// on targets not supporting loading from memory.
+    extract_shared_libraries_to_cache: bool,
+
+    /// Packages whose bytecode is stored in a remote archive.
+    remote_resources_packages: BTreeSet<String>,
+
+    /// Packages whose modules are imported from a zip archive via `zipimport`.
+    zipimport_packages: BTreeSet<String>,
+
+    /// Key to sign resource files installed next to the binary with.
+    resources_signing_key: Option<ResourceSigningKey>,
+
+    /// Settings of the Windows application manifest.
+    windows_manifest: WindowsManifestOptions,
+
+    /// How to sign the binary on macOS.
+    macos_signing: Option<MacOsSigningOptions>,
+
+    /// Sandbox of the binary when deployed as a Linux service.
+    linux_service: Option<LinuxServiceOptions>,
+
+    /// Whether the binary is configured for debugging and coverage tools.
+    debug_build: bool,
+
+    /// Whether in-memory module data is compressed.
+    compress_resources: bool,
 }
 
 impl StandalonePythonExecutableBuilder {
-    /// Whether we're building for a target that supports loading extension modules
-    /// from memory.
+    /// Top-level packages of modules and extension modules of the distribution.
+    fn distribution_packages(&self) -> BTreeSet<String> {
+        self.distribution
+            .py_modules
+            .keys()
+            .chain(self.distribution.extension_modules.keys())
+            .map(|name| name.split('.').next().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// Whether we're building for a configuration where extension module shared
+    /// libraries can be embedded in the binary.
+    ///
+    /// This is true when loading from memory is supported by the target and has
+    /// been enabled. Or when the target doesn't support loading from memory but
+    /// extracting libraries to a cache directory at run-time has been enabled.
     fn supports_in_memory_dynamically_linked_extension_loading(&self) -> bool {
-        self.distribution.link_mode == StandaloneDistributionLinkMode::Dynamic
-            && self.target_triple.contains("pc-windows")
+        if target_supports_in_memory_shared_library_loading(&self.target_triple) {
+            self.allow_in_memory_shared_library_loading
+                && self.distribution.link_mode == StandaloneDistributionLinkMode::Dynamic
+        } else {
+            self.extract_shared_libraries_to_cache
+                && self.distribution.is_extension_module_file_loadable()
+        }
+    }
+
+    /// Resolve the resources policy to apply to dynamically linked extension modules.
+    ///
+    /// Policies requesting in-memory loading are routed to the filesystem on
+    /// targets where loading extension modules from memory isn't supported.
+    fn extension_module_resources_policy(&self) -> PythonResourcesPolicy {
+        self.resources_policy.resolve_extension_module_policy(
+            self.supports_in_memory_dynamically_linked_extension_loading(),
+            self.distribution.is_extension_module_file_loadable(),
+        )
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -1468,6 +1667,181 @@ impl StandalonePythonExecutableBuilder {
             cargo_metadata,
         })
     }
+
+    /// Resolve the run mode of the interpreter from collected resources.
+    ///
+    /// Entry points are resolved from `entry_points.txt` files of package
+    /// distributions.
+    fn resolve_run_mode(&self) -> Result<RunMode> {
+        match &self.config.run_mode {
+            RunMode::EntryPoint { .. } => self
+                .config
+                .run_mode
+                .resolve_entry_point(&self.resources.get_package_entry_points()?),
+            run_mode => Ok(run_mode.clone()),
+        }
+    }
+
+    /// Package resources for embedding.
+    ///
+    /// Returns packaged resources and files to install next to the binary,
+    /// including archives of remote and zip imported packages.
+    fn package_resources(
+        &self,
+        logger: &slog::Logger,
+        bytecode_compiler_jobs: usize,
+        bytecode_compiler_in_process: bool,
+        bytecode_cache_path: Option<&Path>,
+    ) -> Result<(EmbeddedPythonResources, FileManifest)> {
+        let issues = find_compatibility_issues(
+            &self.resources.get_module_names(),
+            &self.resources.get_extension_module_names(),
+            self.distribution.link_mode == StandaloneDistributionLinkMode::Static,
+        );
+        for issue in &issues {
+            warn!(logger, "warning: {}", issue.message);
+        }
+        if !issues.is_empty() {
+            warn!(
+                logger,
+                "see https://pyoxidizer.readthedocs.io/en/latest/packaging_known_packages.html for supported configurations"
+            );
+        }
+
+        let libpython = if bytecode_compiler_in_process {
+            if self.host_triple != self.target_triple {
+                return Err(anyhow!(
+                    "in-process bytecode compilation requires a distribution for the host"
+                ));
+            }
+
+            if self.distribution.libpython_shared_library.is_none() {
+                warn!(
+                    logger,
+                    "distribution has no shared libpython; compiling bytecode with Python processes"
+                );
+            }
+
+            self.distribution.libpython_shared_library.clone()
+        } else {
+            None
+        };
+
+        let bytecode_compiler = if let Some(libpython) = libpython {
+            BytecodeCompilerBackend::InProcess {
+                libpython,
+                stdlib_path: self.distribution.stdlib_path.clone(),
+            }
+        } else {
+            BytecodeCompilerBackend::Process {
+                python_exe: self.python_exe.clone(),
+                jobs: bytecode_compiler_jobs,
+            }
+        };
+
+        let mut resources =
+            self.resources
+                .package(logger, &bytecode_compiler, bytecode_cache_path)?;
+        let mut extra_files = resources.extra_install_files()?;
+
+        if let Some(key) = &self.resources_signing_key {
+            let signatures = key.sign_manifest(&extra_files)?;
+            extra_files.add_manifest(&signatures)?;
+        }
+
+        if !self.remote_resources_packages.is_empty() {
+            let archive =
+                resources.move_bytecode_to_remote_archive(&self.remote_resources_packages)?;
+
+            extra_files.add_file(
+                Path::new(REMOTE_RESOURCES_ARCHIVE_NAME),
+                &FileContent {
+                    data: archive,
+                    executable: false,
+                },
+            )?;
+        }
+
+        if !self.zipimport_packages.is_empty() {
+            let mut archive_files = FileManifest::default();
+            for (path, data) in resources.move_packages_to_zip_files(&self.zipimport_packages)? {
+                archive_files.add_file(
+                    &path,
+                    &FileContent {
+                        data,
+                        executable: false,
+                    },
+                )?;
+            }
+
+            extra_files.add_file(
+                Path::new(ZIPIMPORT_ARCHIVE_NAME),
+                &FileContent {
+                    data: write_zipapp(&archive_files, None)?,
+                    executable: false,
+                },
+            )?;
+        }
+
+        // Remote and zip archives hold uncompressed data, so compression
+        // happens once modules have been moved to them.
+        if self.compress_resources {
+            resources.compress_module_data()?;
+        }
+
+        Ok((resources, extra_files))
+    }
+
+    /// Add files derived from settings of the binary, such as service units.
+    fn add_extra_files(&self, extra_files: &mut FileManifest) -> Result<()> {
+        if self.debug_build {
+            let sources = self
+                .resources
+                .get_filesystem_module_source_paths()
+                .into_iter()
+                .map(|(installed, original)| {
+                    (
+                        installed.display().to_string(),
+                        serde_json::Value::from(original.display().to_string()),
+                    )
+                })
+                .collect::<serde_json::Map<_, _>>();
+
+            extra_files.add_file(
+                Path::new(&format!("{}.sourcemap.json", self.exe_name)),
+                &FileContent {
+                    data: serde_json::to_vec_pretty(&serde_json::json!({
+                        "version": 1,
+                        "sources": sources,
+                    }))?,
+                    executable: false,
+                },
+            )?;
+        }
+
+        if let Some(service) = &self.linux_service {
+            if self.target_triple.contains("-linux-") {
+                extra_files.add_file(
+                    Path::new(&format!("{}.service", self.exe_name)),
+                    &FileContent {
+                        data: service.to_systemd_unit(&self.exe_name)?.into_bytes(),
+                        executable: false,
+                    },
+                )?;
+                extra_files.add_file(
+                    Path::new(&format!("{}.seccomp.json", self.exe_name)),
+                    &FileContent {
+                        data: service
+                            .to_seccomp_profile(&self.target_triple)?
+                            .into_bytes(),
+                        executable: false,
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
@@ -1487,6 +1861,18 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         &self.python_exe
     }
 
+    fn python_version(&self) -> String {
+        self.distribution.version.clone()
+    }
+
+    fn package_distributions(&self) -> BTreeMap<String, String> {
+        self.resources.get_package_distributions()
+    }
+
+    fn package_records(&self) -> Result<BTreeMap<String, PythonPackageRecord>> {
+        self.resources.get_package_records()
+    }
+
     fn in_memory_module_sources(&self) -> BTreeMap<String, PythonModuleSource> {
         self.resources.get_in_memory_module_sources()
     }
@@ -1495,6 +1881,14 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.resources.get_in_memory_package_resources()
     }
 
+    fn resource(&self, name: &str) -> Option<&PrePackagedResource> {
+        self.resources.get_resource(name)
+    }
+
+    fn extension_module_names(&self) -> BTreeSet<String> {
+        self.resources.get_extension_module_names()
+    }
+
     fn add_in_memory_module_source(&mut self, module: &PythonModuleSource) -> Result<()> {
         self.resources.add_in_memory_module_source(module)
     }
@@ -1524,6 +1918,23 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             .add_relative_path_module_bytecode(module, prefix)
     }
 
+    fn add_in_memory_compiled_module_bytecode(
+        &mut self,
+        module: &PythonModuleBytecode,
+    ) -> Result<()> {
+        self.resources
+            .add_in_memory_compiled_module_bytecode(module)
+    }
+
+    fn add_relative_path_compiled_module_bytecode(
+        &mut self,
+        prefix: &str,
+        module: &PythonModuleBytecode,
+    ) -> Result<()> {
+        self.resources
+            .add_relative_path_compiled_module_bytecode(module, prefix)
+    }
+
     fn add_in_memory_package_resource(&mut self, resource: &PythonPackageResource) -> Result<()> {
         self.resources.add_in_memory_package_resource(resource)
     }
@@ -1604,7 +2015,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             return self.add_builtin_distribution_extension_module(extension_module);
         }
 
-        match self.resources_policy.clone() {
+        match self.extension_module_resources_policy() {
             PythonResourcesPolicy::InMemoryOnly => match self.distribution.link_mode {
                 StandaloneDistributionLinkMode::Static => {
                     self.add_builtin_distribution_extension_module(extension_module)
@@ -1622,7 +2033,8 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                         .add_relative_path_distribution_extension_module(&prefix, extension_module),
                 }
             }
-            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(prefix) => {
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(prefix)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(prefix) => {
                 match self.distribution.link_mode {
                     StandaloneDistributionLinkMode::Static => {
                         self.add_builtin_distribution_extension_module(extension_module)
@@ -1662,6 +2074,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                         .as_ref()
                         .unwrap()
                         .resolve()?,
+                    &extension_module.shared_libraries,
                 )
         } else if !extension_module.object_file_data.is_empty() {
             // TODO we shouldn't be adding a builtin extension module from this API.
@@ -1709,7 +2122,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             ));
         }
 
-        match self.resources_policy {
+        match self.extension_module_resources_policy() {
             PythonResourcesPolicy::InMemoryOnly => {
                 if self.supports_in_memory_dynamically_linked_extension_loading() {
                     self.resources
@@ -1721,6 +2134,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                                 .as_ref()
                                 .unwrap()
                                 .resolve()?,
+                            &extension_module.shared_libraries,
                         )
                 } else {
                     Err(anyhow!("in-memory-only resources policy active but in-memory extension module importing not supported by this configuration"))
@@ -1734,7 +2148,8 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                     Err(anyhow!("filesystem-relative-only policy active but file-based extension module loading not supported by this configuration"))
                 }
             }
-            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(ref prefix) => {
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(ref prefix)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(ref prefix) => {
                 if self.supports_in_memory_dynamically_linked_extension_loading() {
                     self.resources
                         .add_in_memory_extension_module_shared_library(
@@ -1745,12 +2160,13 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                                 .as_ref()
                                 .unwrap()
                                 .resolve()?,
+                            &extension_module.shared_libraries,
                         )
                 } else if self.distribution.is_extension_module_file_loadable() {
                     self.resources
                         .add_relative_path_extension_module(extension_module, prefix)
                 } else {
-                    Err(anyhow!("prefer-in-memory-fallback-filesystem-relative policy active but could not find a mechanism to add an extension module"))
+                    Err(anyhow!("prefer-in-memory resources policy active but could not find a mechanism to add an extension module"))
                 }
             }
         }
@@ -1774,17 +2190,324 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             .filter_from_files(logger, files, glob_patterns)
     }
 
+    fn relocate_resources_to_filesystem(
+        &mut self,
+        prefix: &str,
+        resources_prefix: &str,
+    ) -> Result<()> {
+        self.resources
+            .relocate_to_relative_path(prefix, resources_prefix)?;
+        self.resources_policy = PythonResourcesPolicy::FilesystemRelativeOnly(prefix.to_string());
+
+        Ok(())
+    }
+
+    fn add_venv_support(&mut self) -> Result<()> {
+        // Environments are created with a shell script launcher.
+        if self.target_triple.contains("pc-windows") {
+            return Err(anyhow!(
+                "virtual environment support is not available for Windows targets"
+            ));
+        }
+
+        let key = self.config.python_executable_env.clone().ok_or_else(|| {
+            anyhow!("virtual environment support requires python_executable_env to be set")
+        })?;
+
+        let is_required = |name: &str| {
+            VENV_REQUIRED_PACKAGES
+                .iter()
+                .any(|package| name == *package || name.starts_with(&format!("{}.", package)))
+        };
+
+        for source in self.distribution.source_modules()? {
+            if is_required(&source.name) {
+                self.add_module_bytecode(
+                    &source.as_bytecode_module(BytecodeOptimizationLevel::Zero),
+                )?;
+            }
+        }
+
+        for resource in self.distribution.resource_datas()? {
+            if is_required(&resource.leaf_package) {
+                self.add_package_resource(&resource)?;
+            }
+        }
+
+        let module = PythonModuleSource {
+            name: VENV_MODULE_NAME.to_string(),
+            source: DataLocation::Memory(venv_module_source(&key)?.into_bytes()),
+            is_package: false,
+            cache_tag: self.distribution.cache_tag.clone(),
+        };
+
+        self.add_module_source(&module)?;
+        self.add_module_bytecode(&module.as_bytecode_module(BytecodeOptimizationLevel::Zero))
+    }
+
+    fn add_remote_resources_package(&mut self, package: &str) -> Result<()> {
+        if self.config.remote_resources_url.is_none() {
+            return Err(anyhow!(
+                "storing resources remotely requires remote_resources_url to be set"
+            ));
+        }
+
+        self.remote_resources_packages.insert(package.to_string());
+
+        Ok(())
+    }
+
+    fn add_zipimport_package(&mut self, package: &str) -> Result<()> {
+        self.zipimport_packages.insert(package.to_string());
+        self.config.zipimport_archive = Some(ZIPIMPORT_ARCHIVE_NAME.to_string());
+
+        Ok(())
+    }
+
+    fn add_startup_cache(
+        &mut self,
+        codecs: &[String],
+        regex_patterns: &[(String, u32)],
+    ) -> Result<()> {
+        let module = PythonModuleSource {
+            name: STARTUP_CACHE_MODULE_NAME.to_string(),
+            source: DataLocation::Memory(
+                startup_cache_module_source(&self.python_exe, codecs, regex_patterns)?.into_bytes(),
+            ),
+            is_package: false,
+            cache_tag: self.distribution.cache_tag.clone(),
+        };
+
+        self.add_module_source(&module)?;
+        self.add_module_bytecode(&module.as_bytecode_module(BytecodeOptimizationLevel::Zero))?;
+        self.config.startup_cache_module = Some(STARTUP_CACHE_MODULE_NAME.to_string());
+
+        Ok(())
+    }
+
+    fn add_debugpy_listener(
+        &mut self,
+        logger: &slog::Logger,
+        env: &str,
+        requirement: &str,
+        allow_remote_hosts: bool,
+        pip_envs: &HashMap<String, String>,
+    ) -> Result<()> {
+        // debugpy locates its vendored pydevd relative to its own files.
+        let prefix = match &self.resources_policy {
+            PythonResourcesPolicy::InMemoryOnly => {
+                return Err(anyhow!(
+                    "debugpy listener requires a policy allowing filesystem-relative resources"
+                ));
+            }
+            PythonResourcesPolicy::FilesystemRelativeOnly(prefix) => prefix.clone(),
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(prefix)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(prefix) => prefix.clone(),
+        };
+
+        let resources = pip_install(
+            logger,
+            &self.distribution,
+            false,
+            &[requirement.to_string()],
+            pip_envs,
+            false,
+        )?;
+
+        for resource in resources {
+            match resource {
+                PythonResource::ModuleSource(module) => {
+                    self.add_relative_path_module_source(&prefix, &module)?;
+                    self.add_relative_path_module_bytecode(
+                        &prefix,
+                        &module.as_bytecode_module(BytecodeOptimizationLevel::Zero),
+                    )?;
+                }
+                PythonResource::Resource(resource) => {
+                    self.add_relative_path_package_resource(&prefix, &resource)?;
+                }
+                PythonResource::DistributionResource(resource) => {
+                    self.add_relative_path_package_distribution_resource(&prefix, &resource)?;
+                }
+                PythonResource::ExtensionModuleDynamicLibrary(module) => {
+                    self.add_relative_path_dynamic_extension_module(&prefix, &module)?;
+                }
+                _ => {}
+            }
+        }
+
+        self.config.debugpy_listen_env = Some(env.to_string());
+        self.config.debugpy_allow_remote_hosts = allow_remote_hosts;
+
+        Ok(())
+    }
+
+    fn sign_external_resources(&mut self, key: &ResourceSigningKey) -> Result<()> {
+        self.config.resources_verification_key = Some(key.public_key());
+        self.resources_signing_key = Some(key.clone());
+
+        Ok(())
+    }
+
+    fn set_windows_manifest(&mut self, options: &WindowsManifestOptions) -> Result<()> {
+        self.windows_manifest = options.clone();
+
+        Ok(())
+    }
+
+    fn set_macos_signing(&mut self, options: &MacOsSigningOptions) -> Result<()> {
+        self.macos_signing = Some(options.clone());
+
+        Ok(())
+    }
+
+    fn set_linux_service(&mut self, options: &LinuxServiceOptions) -> Result<()> {
+        options.validate()?;
+        self.linux_service = Some(options.clone());
+
+        Ok(())
+    }
+
+    fn set_resources_origin(&mut self, origin: &str) -> Result<()> {
+        self.config.origin = Some(origin.to_string());
+
+        Ok(())
+    }
+
+    fn set_filesystem_module_layout(&mut self, layout: FilesystemModuleLayout) -> Result<()> {
+        self.resources.set_filesystem_module_layout(layout);
+
+        Ok(())
+    }
+
+    fn set_debug_build(&mut self) -> Result<()> {
+        let prefix = match &self.resources_policy {
+            PythonResourcesPolicy::InMemoryOnly => "lib".to_string(),
+            PythonResourcesPolicy::FilesystemRelativeOnly(prefix) => prefix.clone(),
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(prefix)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(prefix) => prefix.clone(),
+        };
+
+        self.relocate_resources_to_filesystem(&prefix, &prefix)?;
+        self.resources
+            .set_filesystem_module_layout(FilesystemModuleLayout::Pycache);
+
+        // Assertions and docstrings are preserved and tools looking for
+        // sources or honoring environment variables aren't turned away.
+        self.config.optimize_level = 0;
+        self.config.sys_frozen = false;
+        self.config.ignore_environment = false;
+        self.debug_build = true;
+
+        Ok(())
+    }
+
+    fn set_compress_resources(&mut self) -> Result<()> {
+        self.compress_resources = true;
+
+        Ok(())
+    }
+
     fn requires_jemalloc(&self) -> bool {
         self.config.raw_allocator == RawAllocator::Jemalloc
     }
 
+    fn links_shared_libpython(&self) -> bool {
+        self.distribution.link_mode == StandaloneDistributionLinkMode::Dynamic
+    }
+
+    fn resource_location_counts(&self) -> (usize, usize) {
+        self.resources.count_resources_by_location()
+    }
+
+    fn resources_size(&self) -> Result<u64> {
+        self.resources.resources_size()
+    }
+
+    fn estimated_build_size(&self) -> Result<u64> {
+        Ok(self.resources.resources_size()?
+            + crate::build_budget::directory_size(&self.distribution.base_dir))
+    }
+
+    fn in_memory_import_issues(&self, include_stdlib: bool) -> Result<Vec<CompatibilityIssue>> {
+        self.resources.in_memory_import_issues(include_stdlib)
+    }
+
+    fn remove_distribution_resources(&mut self) -> Result<()> {
+        let distribution_packages = self.distribution_packages();
+
+        self.resources.remove_packages(&distribution_packages)
+    }
+
+    fn zipapp_files(&self) -> Result<(FileManifest, BTreeSet<String>)> {
+        let (files, skipped) = self
+            .resources
+            .derive_zipapp_files(&self.distribution_packages())?;
+
+        let mut manifest = FileManifest::default();
+        for (path, location) in files {
+            manifest.add_file(
+                &path,
+                &FileContent {
+                    data: location.resolve()?,
+                    executable: false,
+                },
+            )?;
+        }
+
+        if !manifest.has_path(Path::new(ZIPAPP_MAIN)) {
+            manifest.add_file(
+                Path::new(ZIPAPP_MAIN),
+                &FileContent {
+                    data: zipapp_main_source(&self.resolve_run_mode()?).into_bytes(),
+                    executable: false,
+                },
+            )?;
+        }
+
+        Ok((manifest, skipped))
+    }
+
+    fn as_embedded_python_resources_data(
+        &self,
+        logger: &slog::Logger,
+        bytecode_compiler_jobs: usize,
+        bytecode_compiler_in_process: bool,
+        bytecode_cache_path: Option<&Path>,
+    ) -> Result<EmbeddedPythonResourcesData> {
+        let (resources, mut extra_files) = self.package_resources(
+            logger,
+            bytecode_compiler_jobs,
+            bytecode_compiler_in_process,
+            bytecode_cache_path,
+        )?;
+        let resources = EmbeddedResourcesBlobs::try_from(resources)?;
+        self.add_extra_files(&mut extra_files)?;
+
+        Ok(EmbeddedPythonResourcesData {
+            config: EmbeddedPythonConfig {
+                run_mode: self.resolve_run_mode()?,
+                ..self.config.clone()
+            },
+            resources,
+            extra_files,
+        })
+    }
+
     fn as_embedded_python_binary_data(
         &self,
         logger: &slog::Logger,
         opt_level: &str,
+        bytecode_compiler_jobs: usize,
+        bytecode_compiler_in_process: bool,
+        bytecode_cache_path: Option<&Path>,
     ) -> Result<EmbeddedPythonBinaryData> {
-        let resources = self.resources.package(logger, &self.python_exe)?;
-        let mut extra_files = resources.extra_install_files()?;
+        let (resources, mut extra_files) = self.package_resources(
+            logger,
+            bytecode_compiler_jobs,
+            bytecode_compiler_in_process,
+            bytecode_cache_path,
+        )?;
         let linking_info = self.resolve_python_linking_info(logger, opt_level, &resources)?;
         let resources = EmbeddedResourcesBlobs::try_from(resources)?;
 
@@ -1800,13 +2523,22 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
         }
 
+        self.add_extra_files(&mut extra_files)?;
+
+        let config = EmbeddedPythonConfig {
+            run_mode: self.resolve_run_mode()?,
+            ..self.config.clone()
+        };
+
         Ok(EmbeddedPythonBinaryData {
-            config: self.config.clone(),
+            config,
             linking_info,
             resources,
             extra_files,
             host: self.host_triple.clone(),
             target: self.target_triple.clone(),
+            windows_manifest: self.windows_manifest.clone(),
+            macos_signing: self.macos_signing.clone(),
         })
     }
 }
@@ -1814,8 +2546,14 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
 #[cfg(test)]
 pub mod tests {
     use {
-        super::*, crate::py_packaging::standalone_distribution::ExtensionModuleFilter,
-        crate::testutil::*, std::ops::Deref,
+        super::*,
+        crate::py_packaging::standalone_distribution::ExtensionModuleFilter,
+        crate::testutil::*,
+        python_packaging::bytecode::{
+            BytecodeCache, BytecodeCompileRequest, BytecodeCompilerPool, CompileMode,
+        },
+        python_packaging::resource::PythonPackageDistributionResourceFlavor,
+        std::ops::Deref,
     };
 
     pub fn get_standalone_executable_builder(
@@ -1854,12 +2592,22 @@ pub mod tests {
             python_exe,
             extension_module_filter: ExtensionModuleFilter::Minimal,
             extension_module_variants: None,
+            allow_in_memory_shared_library_loading: false,
+            extract_shared_libraries_to_cache: false,
+            remote_resources_packages: BTreeSet::new(),
+            zipimport_packages: BTreeSet::new(),
+            resources_signing_key: None,
+            windows_manifest: WindowsManifestOptions::default(),
+            macos_signing: None,
+            linux_service: None,
+            debug_build: false,
+            compress_resources: false,
         })
     }
 
     pub fn get_embedded(logger: &slog::Logger) -> Result<EmbeddedPythonBinaryData> {
         let exe = get_standalone_executable_builder(logger)?;
-        exe.as_embedded_python_binary_data(&get_logger()?, "0")
+        exe.as_embedded_python_binary_data(&get_logger()?, "0", 2, false, None)
     }
 
     #[test]
@@ -1872,4 +2620,304 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_in_process_bytecode_compiler_fallback() -> Result<()> {
+        let logger = get_logger()?;
+        let mut exe = get_standalone_executable_builder(&logger)?;
+        exe.distribution.libpython_shared_library = None;
+        exe.add_module_bytecode(&PythonModuleBytecodeFromSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(b"x = 1\n".to_vec()),
+            optimize_level: BytecodeOptimizationLevel::Zero,
+            is_package: false,
+            cache_tag: exe.distribution.cache_tag.clone(),
+        })?;
+
+        // Bytecode is compiled with Python processes instead.
+        exe.as_embedded_python_resources_data(&logger, 1, true, None)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_tar_interrupted() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let extract_dir = temp_dir.path().join("extract");
+
+        let archive = |content: &[u8]| -> Result<Vec<u8>> {
+            let mut builder = tar::Builder::new(Vec::new());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, "python/PYTHON.json", content)?;
+            Ok(builder.into_inner()?)
+        };
+
+        // An interrupted extraction is thrown away.
+        std::fs::create_dir_all(extract_dir.join("python"))?;
+        std::fs::write(extract_dir.join("python").join("PYTHON.json"), b"partial")?;
+        std::fs::write(extract_dir.join("python").join("stale"), b"")?;
+
+        extract_tar(&archive(b"first")?[..], &extract_dir)?;
+        assert_eq!(
+            std::fs::read(extract_dir.join("python").join("PYTHON.json"))?,
+            b"first"
+        );
+        assert!(!extract_dir.join("python").join("stale").exists());
+        assert!(extract_dir.join(EXTRACT_COMPLETE_MARKER).exists());
+
+        // A complete extraction is reused.
+        extract_tar(&archive(b"second")?[..], &extract_dir)?;
+        assert_eq!(
+            std::fs::read(extract_dir.join("python").join("PYTHON.json"))?,
+            b"first"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_venv_support_windows() -> Result<()> {
+        let logger = get_logger()?;
+        let mut builder = get_standalone_executable_builder(&logger)?;
+        builder.config.python_executable_env = Some("TESTAPP_PYTHON".to_string());
+        builder.target_triple = "x86_64-pc-windows-msvc".to_string();
+
+        assert!(builder.add_venv_support().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_build_source_map() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let source_path = temp_dir.path().join("foo.py");
+        std::fs::write(&source_path, "import sys\n")?;
+
+        let mut exe = get_standalone_executable_builder(&logger)?;
+        exe.set_debug_build()?;
+        exe.add_module_bytecode(&PythonModuleBytecodeFromSource {
+            name: "foo".to_string(),
+            source: DataLocation::Path(source_path.clone()),
+            optimize_level: BytecodeOptimizationLevel::Zero,
+            is_package: false,
+            cache_tag: exe.distribution.cache_tag.clone(),
+        })?;
+
+        assert_eq!(exe.config.optimize_level, 0);
+        assert!(!exe.config.sys_frozen);
+
+        let embedded = exe.as_embedded_python_binary_data(&logger, "0", 2, false, None)?;
+        let content = embedded
+            .extra_files
+            .entries()
+            .find_map(|(path, content)| {
+                if path == Path::new("testapp.sourcemap.json") {
+                    Some(content.data.clone())
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        let source_map: serde_json::Value = serde_json::from_slice(&content)?;
+
+        assert_eq!(
+            source_map["sources"][Path::new("lib").join("foo.py").display().to_string()],
+            serde_json::Value::from(source_path.display().to_string())
+        );
+        assert!(embedded
+            .extra_files
+            .has_path(&Path::new("lib").join("foo.py")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zipimport_package() -> Result<()> {
+        let logger = get_logger()?;
+
+        let mut exe = get_standalone_executable_builder(&logger)?;
+        let module = PythonModuleSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(b"import sys\n".to_vec()),
+            is_package: false,
+            cache_tag: exe.distribution.cache_tag.clone(),
+        };
+        exe.add_module_source(&module)?;
+        exe.add_module_bytecode(&module.as_bytecode_module(BytecodeOptimizationLevel::Zero))?;
+        exe.add_zipimport_package("foo")?;
+
+        // The filesystem importer isn't needed to import from the archive.
+        assert!(!exe.config.filesystem_importer);
+
+        let embedded = exe.as_embedded_python_binary_data(&logger, "0", 2, false, None)?;
+        assert_eq!(
+            embedded.config.zipimport_archive,
+            Some(ZIPIMPORT_ARCHIVE_NAME.to_string())
+        );
+
+        let content = embedded
+            .extra_files
+            .entries()
+            .find_map(|(path, content)| {
+                if path == Path::new(ZIPIMPORT_ARCHIVE_NAME) {
+                    Some(content.data.clone())
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        let mut zf = zip::ZipArchive::new(std::io::Cursor::new(content))?;
+        assert!(zf.by_name("foo.py").is_ok());
+        assert!(zf.by_name("foo.pyc").is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_point_run_mode() -> Result<()> {
+        let logger = get_logger()?;
+
+        let mut exe = get_standalone_executable_builder(&logger)?;
+        exe.config.run_mode = RunMode::EntryPoint {
+            group: "console_scripts".to_string(),
+            name: "mycli".to_string(),
+        };
+
+        assert!(exe
+            .as_embedded_python_binary_data(&logger, "0", 2, false, None)
+            .is_err());
+
+        exe.add_in_memory_package_distribution_resource(&PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "mypkg".to_string(),
+            version: "1.0".to_string(),
+            name: "entry_points.txt".to_string(),
+            data: DataLocation::Memory(b"[console_scripts]\nmycli = mypkg.cli:main\n".to_vec()),
+        })?;
+
+        let embedded = exe.as_embedded_python_binary_data(&logger, "0", 2, false, None)?;
+        assert_eq!(
+            embedded.config.run_mode,
+            RunMode::Eval {
+                code: "import sys\nfrom mypkg.cli import main\nsys.exit(main())".to_string()
+            }
+        );
+
+        exe.add_in_memory_package_distribution_resource(&PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "otherpkg".to_string(),
+            version: "1.0".to_string(),
+            name: "entry_points.txt".to_string(),
+            data: DataLocation::Memory(b"[console_scripts]\nmycli = otherpkg:main\n".to_vec()),
+        })?;
+
+        assert!(exe
+            .as_embedded_python_binary_data(&logger, "0", 2, false, None)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress_resources() -> Result<()> {
+        let logger = get_logger()?;
+
+        let mut exe = get_standalone_executable_builder(&logger)?;
+        exe.set_compress_resources()?;
+        exe.add_module_source(&PythonModuleSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(b"import sys\n".repeat(100)),
+            is_package: false,
+            cache_tag: exe.distribution.cache_tag.clone(),
+        })?;
+
+        let embedded = exe.as_embedded_python_binary_data(&logger, "0", 2, false, None)?;
+        let resource =
+            python_packed_resources::parser::load_resources(&embedded.resources.resources)
+                .map_err(|e| anyhow!(e))?
+                .map(|r| r.map_err(|e| anyhow!(e)))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .find(|r| r.name == "foo")
+                .unwrap();
+
+        assert!(resource.in_memory_module_data_compressed);
+        assert_eq!(
+            zstd::decode_all(resource.in_memory_source.unwrap().as_ref())?,
+            b"import sys\n".repeat(100)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytecode_compiler_pool() -> Result<()> {
+        let distribution = get_default_distribution()?;
+
+        let requests = (0..20)
+            .map(|i| BytecodeCompileRequest {
+                source: format!("x = {}\n", i).into_bytes(),
+                filename: format!("m{}", i),
+                optimize: BytecodeOptimizationLevel::Zero,
+                output_mode: CompileMode::Bytecode,
+            })
+            .collect::<Vec<_>>();
+
+        let mut compiler = distribution.create_bytecode_compiler()?;
+        let expected = requests
+            .iter()
+            .map(|r| compiler.compile(&r.source, &r.filename, r.optimize, r.output_mode))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut pool = BytecodeCompilerPool::new(&distribution.python_exe, 4)?;
+        assert_eq!(pool.magic_number, compiler.magic_number);
+        assert_eq!(pool.compile_all(requests.clone())?, expected);
+        // Compilers are reused.
+        assert_eq!(pool.compile_all(requests)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytecode_compiler_pool_cache() -> Result<()> {
+        let distribution = get_default_distribution()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        let request = |source: &str| BytecodeCompileRequest {
+            source: source.as_bytes().to_vec(),
+            filename: "foo".to_string(),
+            optimize: BytecodeOptimizationLevel::Zero,
+            output_mode: CompileMode::Bytecode,
+        };
+
+        let mut pool = BytecodeCompilerPool::new(&distribution.python_exe, 2)?;
+        let expected = pool.compile_all(vec![request("x = 1\n"), request("x = 2\n")])?;
+
+        pool.set_cache(BytecodeCache::new(temp_dir.path()));
+        assert_eq!(
+            pool.compile_all(vec![request("x = 1\n"), request("x = 2\n")])?,
+            expected
+        );
+        let entries = || -> Result<usize> {
+            Ok(walkdir::WalkDir::new(temp_dir.path())
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .count())
+        };
+        assert_eq!(entries()?, 2);
+
+        // Cached bytecode is returned in the order of requests and only
+        // modified sources are compiled.
+        let res = pool.compile_all(vec![request("x = 3\n"), request("x = 2\n")])?;
+        assert_eq!(res[1], expected[1]);
+        assert_ne!(res[0], expected[0]);
+        assert_eq!(entries()?, 3);
+
+        Ok(())
+    }
 }
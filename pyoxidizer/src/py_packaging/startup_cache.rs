@@ -0,0 +1,371 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Interpreter caches computed at build time and loaded at startup.
+
+Compiling regular expressions is relatively slow: patterns are parsed and
+compiled to `_sre` code by the pure Python `sre_parse` and `sre_compile`
+modules. Command line applications compiling many patterns when their
+modules are imported spend a noticeable part of their startup doing so.
+
+The startup cache module generated here holds the `_sre` code of regular
+expressions, compiled at build time by the Python distribution. It is
+imported at the end of interpreter initialization and seeds the cache of
+the `re` module with pattern objects created from this code, so
+`re.compile()` calls for these patterns are cache hits. It also looks up
+codecs, which imports their modules and populates the codec registry's
+cache.
+
+Compiled code depends on the version of `_sre`: patterns aren't loaded if
+`_sre.MAGIC` at run-time differs from the one at build time.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packaging::resource::PythonModuleSource,
+    std::path::Path,
+};
+
+/// Name of the module populating caches at startup.
+pub const STARTUP_CACHE_MODULE_NAME: &str = "_pyoxidizer_startup_cache";
+
+/// Python script finding regular expressions compiled by modules.
+///
+/// The argument is a JSON file holding a list of module sources. Calls to
+/// `re.compile()` whose pattern is a string literal and whose flags are
+/// literals or `re` flag attributes are found. A JSON list of
+/// `[pattern, flags]` lists is written to stdout.
+const FIND_PATTERNS_SCRIPT: &str = indoc::indoc!(
+    r#"
+    import ast
+    import json
+    import re
+    import sys
+
+    FLAGS = {
+        name: int(getattr(re, name))
+        for name in (
+            "A", "ASCII", "I", "IGNORECASE", "L", "LOCALE", "M", "MULTILINE",
+            "S", "DOTALL", "U", "UNICODE", "X", "VERBOSE",
+        )
+    }
+
+
+    def flags_value(node):
+        if node is None:
+            return 0
+        if isinstance(node, ast.Constant) and isinstance(node.value, int):
+            return node.value
+        if (
+            isinstance(node, ast.Attribute)
+            and isinstance(node.value, ast.Name)
+            and node.value.id == "re"
+        ):
+            return FLAGS.get(node.attr)
+        if isinstance(node, ast.BinOp) and isinstance(node.op, ast.BitOr):
+            left = flags_value(node.left)
+            right = flags_value(node.right)
+            if left is not None and right is not None:
+                return left | right
+        return None
+
+
+    def is_re_compile(node):
+        return (
+            isinstance(node, ast.Attribute)
+            and node.attr == "compile"
+            and isinstance(node.value, ast.Name)
+            and node.value.id == "re"
+        )
+
+
+    with open(sys.argv[1], "r", encoding="utf-8") as fh:
+        sources = json.load(fh)
+
+    patterns = []
+    seen = set()
+
+    for source in sources:
+        try:
+            tree = ast.parse(source)
+        except (SyntaxError, ValueError):
+            continue
+
+        for node in ast.walk(tree):
+            if not isinstance(node, ast.Call) or not is_re_compile(node.func):
+                continue
+            if not node.args or any(k.arg != "flags" for k in node.keywords):
+                continue
+
+            pattern = node.args[0]
+            if not isinstance(pattern, ast.Constant) or not isinstance(pattern.value, str):
+                continue
+
+            if len(node.args) > 1:
+                flags = flags_value(node.args[1])
+            else:
+                flags = flags_value(node.keywords[0].value if node.keywords else None)
+            if flags is None:
+                continue
+
+            if (pattern.value, flags) not in seen:
+                seen.add((pattern.value, flags))
+                patterns.append([pattern.value, flags])
+
+    json.dump(patterns, sys.stdout)
+    "#
+);
+
+/// Python script compiling regular expressions to `_sre` code.
+///
+/// The argument is a JSON file holding a list of `[pattern, flags]` lists.
+/// Python code defining `MAGIC` and `PATTERNS` is written to stdout.
+/// Patterns failing to compile are reported on stderr and skipped.
+const COMPILE_PATTERNS_SCRIPT: &str = indoc::indoc!(
+    r#"
+    import _sre
+    import json
+    import sre_compile
+    import sre_constants
+    import sre_parse
+    import sys
+
+    with open(sys.argv[1], "r", encoding="utf-8") as fh:
+        patterns = json.load(fh)
+
+    entries = []
+
+    for pattern, flags in patterns:
+        if flags & sre_constants.SRE_FLAG_DEBUG:
+            continue
+
+        try:
+            p = sre_parse.parse(pattern, flags)
+            code = sre_compile._code(p, flags)
+        except Exception as e:
+            print("not caching regular expression %r: %s" % (pattern, e), file=sys.stderr)
+            continue
+
+        state = p.state
+        groupindex = dict(state.groupdict)
+        indexgroup = [None] * state.groups
+        for name, index in groupindex.items():
+            indexgroup[index] = name
+
+        entries.append(
+            (pattern, flags, flags | state.flags, code, state.groups - 1, groupindex,
+             tuple(indexgroup))
+        )
+
+    print("MAGIC = %d" % _sre.MAGIC)
+    print("PATTERNS = %r" % (entries,))
+    "#
+);
+
+/// Source code of the startup cache module.
+///
+/// `CODECS`, `MAGIC` and `PATTERNS` are defined before this code.
+const MODULE_CODE: &str = indoc::indoc!(
+    r#"
+    import _sre
+    import codecs
+    import re
+
+
+    def _load():
+        for name in CODECS:
+            try:
+                codecs.lookup(name)
+            except LookupError:
+                pass
+
+        if _sre.MAGIC != MAGIC:
+            return
+
+        for pattern, flags, final_flags, code, groups, groupindex, indexgroup in PATTERNS:
+            if len(re._cache) >= re._MAXCACHE:
+                break
+
+            re._cache[type(pattern), pattern, flags] = _sre.compile(
+                pattern, final_flags, code, groups, groupindex, indexgroup
+            )
+
+
+    _load()
+    "#
+);
+
+/// Run a script with the Python interpreter of a distribution.
+///
+/// `input` is serialized to a JSON file passed as argument. Returns stdout.
+fn run_script(python_exe: &Path, script: &str, input: &serde_json::Value) -> Result<String> {
+    let temp_dir = tempdir::TempDir::new("pyoxidizer-startup-cache")?;
+    let input_path = temp_dir.path().join("input.json");
+    std::fs::write(&input_path, serde_json::to_vec(input)?)?;
+
+    let output = std::process::Command::new(python_exe)
+        .arg("-c")
+        .arg(script)
+        .arg(&input_path)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .context("running Python")?;
+    if !output.status.success() {
+        return Err(anyhow!("error running Python script"));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Find regular expressions compiled by modules.
+///
+/// Returns `(pattern, flags)` tuples of `re.compile()` calls with literal
+/// arguments in the source of `modules`.
+pub fn find_regex_patterns(
+    python_exe: &Path,
+    modules: &[PythonModuleSource],
+) -> Result<Vec<(String, u32)>> {
+    let sources = modules
+        .iter()
+        .map(|module| {
+            Ok(serde_json::Value::from(
+                String::from_utf8_lossy(&module.source.resolve()?).to_string(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let output = run_script(
+        python_exe,
+        FIND_PATTERNS_SCRIPT,
+        &serde_json::Value::from(sources),
+    )?;
+
+    let patterns: Vec<(String, u32)> = serde_json::from_str(&output)?;
+
+    Ok(patterns)
+}
+
+/// Obtain the source code of the startup cache module.
+///
+/// `codecs` are names of codecs to look up. `regex_patterns` are
+/// `(pattern, flags)` tuples of regular expressions to compile.
+pub fn startup_cache_module_source(
+    python_exe: &Path,
+    codecs: &[String],
+    regex_patterns: &[(String, u32)],
+) -> Result<String> {
+    for name in codecs {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            return Err(anyhow!("invalid codec name: {:?}", name));
+        }
+    }
+
+    let compiled = run_script(
+        python_exe,
+        COMPILE_PATTERNS_SCRIPT,
+        &serde_json::to_value(regex_patterns)?,
+    )?;
+
+    Ok(format!(
+        "\"\"\"Interpreter caches computed when building this application.\"\"\"\n\n\
+         CODECS = [{}]\n\
+         {}\n\
+         {}",
+        codecs
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        compiled,
+        MODULE_CODE
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::py_packaging::distribution::PythonDistribution, crate::testutil::*,
+        python_packaging::resource::DataLocation,
+    };
+
+    #[test]
+    fn test_find_regex_patterns() -> Result<()> {
+        let distribution = get_default_distribution()?;
+
+        let module = PythonModuleSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(
+                b"import re\n\
+                  A = re.compile('a+')\n\
+                  B = re.compile(r'(?P<b>b)', re.I | re.M)\n\
+                  C = re.compile('a+')\n\
+                  D = re.compile(PATTERN)\n\
+                  E = re.compile('e', flags=re.X)\n\
+                  F = re.compile('f', FLAGS)\n"
+                    .to_vec(),
+            ),
+            is_package: false,
+            cache_tag: distribution.cache_tag().to_string(),
+        };
+
+        let patterns = find_regex_patterns(distribution.python_exe_path(), &[module])?;
+        assert_eq!(
+            patterns,
+            vec![
+                ("a+".to_string(), 0),
+                ("(?P<b>b)".to_string(), 2 | 8),
+                ("e".to_string(), 64),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_startup_cache_module_source() -> Result<()> {
+        let distribution = get_default_distribution()?;
+
+        let source = startup_cache_module_source(
+            distribution.python_exe_path(),
+            &["utf-16".to_string()],
+            &[("(?P<b>b+)".to_string(), 2), ("(".to_string(), 0)],
+        )?;
+        assert!(source.contains("CODECS = [\"utf-16\"]\n"));
+        assert!(source.contains("MAGIC = "));
+
+        // Loading the module in the distribution seeds the cache of re.
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::write(
+            temp_dir
+                .path()
+                .join(format!("{}.py", STARTUP_CACHE_MODULE_NAME)),
+            &source,
+        )?;
+        let output = std::process::Command::new(distribution.python_exe_path())
+            .arg("-c")
+            .arg(format!(
+                "import re, {}; p = re._cache[str, '(?P<b>b+)', 2]; \
+                 print(len(re._cache), p.match('BB').group('b'))",
+                STARTUP_CACHE_MODULE_NAME
+            ))
+            .env("PYTHONPATH", temp_dir.path())
+            .output()?;
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)?.ends_with(" BB\n"));
+
+        assert!(startup_cache_module_source(
+            distribution.python_exe_path(),
+            &["utf-16\"".to_string()],
+            &[]
+        )
+        .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Running the Python standard library test suite inside built binaries.
+
+Custom Python distributions need to be qualified before they are used. This
+module defines Python code that runs a selection of the CPython test suite
+from within an embedded interpreter and reports a pass/fail status per test
+module.
+*/
+
+/// Test modules run when no explicit selection is made.
+///
+/// These exercise core functionality without depending on network access,
+/// GUI toolkits, or long run times.
+pub const DEFAULT_STDLIB_TESTS: &[&str] = &[
+    "test_abc",
+    "test_base64",
+    "test_bisect",
+    "test_codecs",
+    "test_collections",
+    "test_datetime",
+    "test_decimal",
+    "test_functools",
+    "test_hashlib",
+    "test_itertools",
+    "test_json",
+    "test_math",
+    "test_re",
+    "test_string",
+    "test_struct",
+    "test_unicode",
+    "test_zlib",
+];
+
+/// Python code driving execution of test modules.
+///
+/// The `TESTS_JSON` variable, holding a JSON array of test module names, must
+/// be defined before this code runs.
+const RUNNER_CODE: &str = indoc::indoc!(
+    r#"
+    import json
+    import sys
+    import unittest
+
+    TESTS = json.loads(TESTS_JSON)
+    results = []
+
+    for name in TESTS:
+        try:
+            module = __import__("test.%s" % name, fromlist=["*"])
+            suite = unittest.defaultTestLoader.loadTestsFromModule(module)
+            result = unittest.TextTestRunner(stream=sys.stderr, verbosity=0).run(suite)
+            passed = result.wasSuccessful()
+            detail = "%d tests, %d failures, %d errors, %d skipped" % (
+                result.testsRun,
+                len(result.failures),
+                len(result.errors),
+                len(result.skipped),
+            )
+        except BaseException as e:
+            if isinstance(e, KeyboardInterrupt):
+                raise
+            passed = False
+            detail = "%s: %s" % (type(e).__name__, e)
+
+        results.append({"module": name, "passed": passed, "detail": detail})
+        print("%s %s (%s)" % ("PASS" if passed else "FAIL", name, detail), flush=True)
+
+    failed = [r for r in results if not r["passed"]]
+    print("%d of %d test modules passed" % (len(results) - len(failed), len(results)))
+
+    if len(sys.argv) > 1:
+        with open(sys.argv[1], "w") as fh:
+            json.dump(results, fh, indent=2)
+
+    sys.exit(1 if failed else 0)
+    "#
+);
+
+/// Obtain Python code running the specified stdlib test modules.
+///
+/// Test module names are relative to the `test` package. e.g. `test_json`.
+/// When the resulting code runs, a line is printed for each module and an
+/// optional JSON report is written to the path given as the first argument
+/// to the program. The process exits non-zero if any module failed.
+pub fn stdlib_test_runner_code(tests: &[String]) -> String {
+    // A JSON string literal is a valid Python string literal.
+    let tests = serde_json::to_string(&serde_json::to_string(tests).unwrap()).unwrap();
+
+    format!("TESTS_JSON = {}\n{}", tests, RUNNER_CODE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runner_code() {
+        let code = stdlib_test_runner_code(&["test_json".to_string(), "test_re".to_string()]);
+
+        assert!(
+            code.starts_with("TESTS_JSON = \"[\\\"test_json\\\",\\\"test_re\\\"]\"\nimport json\n")
+        );
+        assert!(code.contains("\nTESTS = json.loads(TESTS_JSON)\n"));
+        assert!(code.contains("\nfor name in TESTS:\n"));
+
+        let code = stdlib_test_runner_code(&["test\"\n\\".to_string()]);
+        assert!(code.starts_with("TESTS_JSON = \"[\\\"test\\\\\\\"\\\\n\\\\\\\\\\\"]\"\n"));
+    }
+}
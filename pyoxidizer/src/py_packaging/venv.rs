@@ -0,0 +1,203 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Support for creating virtual environments from built binaries.
+
+Applications managing Python environments (e.g. installing tools into
+isolated environments) need to create virtual environments at run-time.
+The stdlib `venv` module copies `sys.executable` into the environment,
+which doesn't work for built binaries because running them runs the
+application.
+
+The `oxidized_venv` module defined here creates environments whose `python`
+is a launcher running the built binary in `python` executable mode (see
+the `python_executable_env` interpreter setting), pointing it at the
+environment. Environments are seeded with the `pip` and `setuptools`
+wheels bundled with `ensurepip`.
+*/
+
+use anyhow::{anyhow, Result};
+
+/// Name of the module providing the run-time API.
+pub const VENV_MODULE_NAME: &str = "oxidized_venv";
+
+/// Packages from the Python distribution needed by the run-time API.
+pub const VENV_REQUIRED_PACKAGES: &[&str] = &["ensurepip", "venv"];
+
+/// Source code of the run-time API.
+///
+/// The `PYTHON_EXECUTABLE_ENV` variable is defined by `venv_module_source()`.
+const VENV_MODULE_CODE: &str = indoc::indoc!(
+    r##"
+    import os
+    import pkgutil
+    import shlex
+    import subprocess
+    import sys
+    import tempfile
+    import venv
+
+    __all__ = ["EnvBuilder", "create", "install_seed_packages"]
+
+    _LAUNCHER = """#!/bin/sh
+    {env}={env_dir} exec {executable} "$@"
+    """
+
+    # Activation scripts are package resources of ``venv``.
+    _SCRIPTS = ("common/activate", "posix/activate.csh", "posix/activate.fish")
+
+
+    def _base_executable():
+        # sys.executable is an environment's launcher when running within one.
+        return getattr(sys, "_base_executable", sys.executable)
+
+
+    class EnvBuilder(venv.EnvBuilder):
+        """A ``venv.EnvBuilder`` creating environments running this binary."""
+
+        def __init__(self, clear=False, with_pip=True, prompt=None):
+            super().__init__(clear=clear, with_pip=with_pip, prompt=prompt)
+
+        def create(self, env_dir):
+            if os.name == "nt":
+                raise NotImplementedError(
+                    "creating virtual environments is not supported on Windows"
+                )
+
+            super().create(env_dir)
+
+        def ensure_directories(self, env_dir):
+            context = super().ensure_directories(env_dir)
+            context.env_exe = os.path.join(context.bin_path, "python")
+            return context
+
+        def setup_python(self, context):
+            with open(context.env_exe, "w") as fh:
+                fh.write(
+                    _LAUNCHER.format(
+                        env=PYTHON_EXECUTABLE_ENV,
+                        env_dir=shlex.quote(context.env_dir),
+                        executable=shlex.quote(_base_executable()),
+                    )
+                )
+
+            os.chmod(context.env_exe, 0o755)
+
+            for name in ("python3", "python%d.%d" % sys.version_info[:2]):
+                path = os.path.join(context.bin_path, name)
+                if not os.path.lexists(path):
+                    os.symlink("python", path)
+
+        def setup_scripts(self, context):
+            for name in _SCRIPTS:
+                try:
+                    data = pkgutil.get_data("venv", "scripts/" + name)
+                except OSError:
+                    data = None
+
+                if data is None:
+                    continue
+
+                path = os.path.join(context.bin_path, os.path.basename(name))
+                with open(path, "w") as fh:
+                    fh.write(self.replace_variables(data.decode("utf-8"), context))
+
+        def _setup_pip(self, context):
+            install_seed_packages(context.env_exe)
+
+
+    def _bundled_wheels():
+        import ensurepip
+
+        for project, version in ensurepip._PROJECTS:
+            name = "%s-%s-py2.py3-none-any.whl" % (project, version)
+            data = pkgutil.get_data("ensurepip", "_bundled/" + name)
+            if data is None:
+                raise RuntimeError("%s is not available to ensurepip" % name)
+
+            yield project, name, data
+
+
+    def install_seed_packages(python):
+        """Install the ``pip`` and ``setuptools`` wheels bundled with ``ensurepip``.
+
+        ``python`` is the interpreter of the environment to install into.
+        """
+        with tempfile.TemporaryDirectory() as tmpdir:
+            paths = []
+            projects = []
+
+            for project, name, data in _bundled_wheels():
+                path = os.path.join(tmpdir, name)
+                with open(path, "wb") as fh:
+                    fh.write(data)
+
+                paths.append(path)
+                projects.append(project)
+
+            # Run pip from its wheel, like ensurepip does.
+            code = (
+                "import runpy, sys; sys.path[0:0] = %r; "
+                "runpy.run_module('pip', run_name='__main__', alter_sys=True)" % paths
+            )
+
+            subprocess.check_call(
+                [python, "-c", code, "install", "--no-cache-dir", "--no-index",
+                 "--find-links", tmpdir] + projects
+            )
+
+
+    def create(env_dir, clear=False, with_pip=True, prompt=None):
+        """Create a virtual environment in ``env_dir``.
+
+        The environment's ``python`` runs this binary as a Python interpreter.
+        If ``with_pip`` is true, ``pip`` and ``setuptools`` are installed into
+        the environment from wheels embedded in this binary.
+        """
+        EnvBuilder(clear=clear, with_pip=with_pip, prompt=prompt).create(env_dir)
+    "##
+);
+
+/// Obtain the source code of the `oxidized_venv` module.
+///
+/// `python_executable_env` is the name of the environment variable putting
+/// the binary in `python` executable mode.
+pub fn venv_module_source(python_executable_env: &str) -> Result<String> {
+    if python_executable_env.is_empty()
+        || !python_executable_env
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(anyhow!(
+            "{} is not a valid environment variable name for virtual environments",
+            python_executable_env
+        ));
+    }
+
+    Ok(format!(
+        "\"\"\"Create virtual environments running this binary as a Python interpreter.\"\"\"\n\n\
+         PYTHON_EXECUTABLE_ENV = \"{}\"\n\n{}",
+        python_executable_env, VENV_MODULE_CODE
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_venv_module_source() -> Result<()> {
+        let source = venv_module_source("MYAPP_PYTHON")?;
+
+        assert!(source.starts_with("\"\"\"Create virtual environments"));
+        assert!(source.contains("\nPYTHON_EXECUTABLE_ENV = \"MYAPP_PYTHON\"\n"));
+        assert!(source.contains("\nclass EnvBuilder(venv.EnvBuilder):\n"));
+
+        assert!(venv_module_source("").is_err());
+        assert!(venv_module_source("FOO BAR").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Reading of Python wheels.
+
+Wheels are zip archives of the files a package installs. They are read
+directly, without running `pip`, so resources can be collected from them
+offline. Every file in a wheel is verified against the hashes in its
+`.dist-info/RECORD` file before being used.
+*/
+
+use {
+    super::distribution::PythonDistribution,
+    super::packaging_tool::find_resources,
+    anyhow::{anyhow, Context, Result},
+    python_packaging::long_path::extended_length_path,
+    python_packaging::package_metadata::{PythonPackageRecord, PythonPackageRecordEntry},
+    python_packaging::resource::PythonResource,
+    sha2::{Digest, Sha256, Sha384, Sha512},
+    slog::warn,
+    std::collections::HashMap,
+    std::io::Read,
+    std::path::{Component, Path, PathBuf},
+};
+
+/// Compute the hash of data as written in `RECORD` files.
+pub fn record_hash(algorithm: &str, data: &[u8]) -> Result<String> {
+    let digest = match algorithm {
+        "sha256" => Sha256::digest(data).to_vec(),
+        "sha384" => Sha384::digest(data).to_vec(),
+        "sha512" => Sha512::digest(data).to_vec(),
+        _ => {
+            return Err(anyhow!(
+                "hash algorithm must be one of sha256, sha384 or sha512; got {}",
+                algorithm
+            ))
+        }
+    };
+
+    Ok(format!(
+        "{}={}",
+        algorithm,
+        base64::encode_config(&digest, base64::URL_SAFE_NO_PAD)
+    ))
+}
+
+/// Verify a file in a wheel against its `RECORD` entry.
+fn verify_record_entry(entry: &PythonPackageRecordEntry, data: &[u8]) -> Result<()> {
+    let hash = entry
+        .hash
+        .as_ref()
+        .ok_or_else(|| anyhow!("{} has no hash in RECORD", entry.path))?;
+
+    let algorithm = hash.splitn(2, '=').next().unwrap();
+
+    if record_hash(algorithm, data)? != *hash {
+        return Err(anyhow!("{} does not match its hash in RECORD", entry.path));
+    }
+
+    if let Some(size) = entry.size {
+        if size != data.len() as u64 {
+            return Err(anyhow!("{} does not match its size in RECORD", entry.path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve the path a file in a wheel is installed to, relative to site-packages.
+///
+/// Returns `None` for files not installed to site-packages, such as scripts
+/// and headers.
+pub fn wheel_install_path(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+
+    match components.next() {
+        Some(Component::Normal(first)) if first.to_string_lossy().ends_with(".data") => {
+            match components.next() {
+                Some(Component::Normal(scheme)) if scheme == "purelib" || scheme == "platlib" => {
+                    Some(components.as_path().to_path_buf())
+                }
+                _ => None,
+            }
+        }
+        Some(Component::Normal(_)) => Some(path.to_path_buf()),
+        _ => None,
+    }
+}
+
+/// Find the name of the `.dist-info` directory of a wheel.
+fn dist_info_dir(names: &[String]) -> Result<String> {
+    let mut dirs = names
+        .iter()
+        .filter_map(|name| {
+            let mut parts = name.splitn(2, '/');
+            match (parts.next(), parts.next()) {
+                (Some(dir), Some(_)) if dir.ends_with(".dist-info") => Some(dir.to_string()),
+                _ => None,
+            }
+        })
+        .collect::<Vec<String>>();
+    dirs.sort();
+    dirs.dedup();
+
+    match dirs.len() {
+        1 => Ok(dirs.remove(0)),
+        0 => Err(anyhow!("no .dist-info directory")),
+        _ => Err(anyhow!(
+            "multiple .dist-info directories: {}",
+            dirs.join(", ")
+        )),
+    }
+}
+
+/// Ensure the `WHEEL` metadata file describes a supported wheel version.
+fn validate_wheel_metadata(data: &str) -> Result<()> {
+    let version = data
+        .lines()
+        .find_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some("Wheel-Version"), Some(version)) => Some(version.trim()),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| anyhow!("WHEEL does not define Wheel-Version"))?;
+
+    if version.split('.').next() != Some("1") {
+        return Err(anyhow!("unsupported Wheel-Version {}", version));
+    }
+
+    Ok(())
+}
+
+/// Extract the files a wheel installs to site-packages into a directory.
+///
+/// Every file is verified against the wheel's `RECORD` file. Extraction
+/// fails if a file isn't listed in `RECORD`, if its content doesn't match
+/// the recorded hash or if a recorded file is missing. Only `RECORD` and
+/// its signatures may be listed without a hash.
+pub fn extract_wheel(wheel_path: &Path, dest_dir: &Path) -> Result<()> {
+    let fh =
+        std::fs::File::open(wheel_path).context(format!("opening {}", wheel_path.display()))?;
+    let mut zf = zip::ZipArchive::new(fh)?;
+
+    let mut names = Vec::new();
+    for i in 0..zf.len() {
+        let f = zf.by_index(i)?;
+        if f.is_file() {
+            names.push(f.name().to_string());
+        }
+    }
+
+    let dist_info = dist_info_dir(&names)?;
+
+    let mut wheel_metadata = String::new();
+    zf.by_name(&format!("{}/WHEEL", dist_info))
+        .context("reading WHEEL")?
+        .read_to_string(&mut wheel_metadata)?;
+    validate_wheel_metadata(&wheel_metadata)?;
+
+    let mut record_data = Vec::new();
+    zf.by_name(&format!("{}/RECORD", dist_info))
+        .context("reading RECORD")?
+        .read_to_end(&mut record_data)?;
+
+    let mut records = PythonPackageRecord::from_record(&record_data)?
+        .entries
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect::<HashMap<String, PythonPackageRecordEntry>>();
+
+    // RECORD and its signatures can't contain their own hashes, so their
+    // entries have none.
+    let unhashed = vec![
+        format!("{}/RECORD", dist_info),
+        format!("{}/RECORD.jws", dist_info),
+        format!("{}/RECORD.p7s", dist_info),
+    ];
+
+    for i in 0..zf.len() {
+        let mut f = zf.by_index(i)?;
+
+        if !f.is_file() {
+            continue;
+        }
+
+        let name = f.name().to_string();
+
+        let mut data = Vec::new();
+        f.read_to_end(&mut data)?;
+
+        match records.remove(&name) {
+            Some(entry) if entry.hash.is_none() && unhashed.contains(&name) => {}
+            Some(entry) => verify_record_entry(&entry, &data)?,
+            None if unhashed.contains(&name) => {}
+            None => return Err(anyhow!("{} is not listed in RECORD", name)),
+        }
+
+        let dest_path = match wheel_install_path(&f.sanitized_name()) {
+            Some(p) => dest_dir.join(p),
+            None => continue,
+        };
+
+        let parent = dest_path
+            .parent()
+            .ok_or_else(|| anyhow!("could not resolve parent"))?;
+        std::fs::create_dir_all(extended_length_path(parent))
+            .context(format!("creating parent directory {}", parent.display()))?;
+
+        std::fs::write(extended_length_path(&dest_path), data)
+            .context(format!("writing {}", dest_path.display()))?;
+    }
+
+    let mut missing = records
+        .values()
+        .filter(|entry| !unhashed.contains(&entry.path))
+        .map(|entry| entry.path.clone())
+        .collect::<Vec<String>>();
+    missing.sort();
+
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "files listed in RECORD are missing: {}",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Read Python resources from a wheel.
+///
+/// No Python code is executed, so resources are obtained from the wheel
+/// content alone.
+pub fn read_wheel(
+    logger: &slog::Logger,
+    dist: &dyn PythonDistribution,
+    path: &Path,
+    strict: bool,
+) -> Result<Vec<PythonResource>> {
+    let temp_dir = tempdir::TempDir::new("pyoxidizer-wheel")?;
+
+    warn!(logger, "reading {}", path.display());
+    extract_wheel(path, temp_dir.path()).context(format!("extracting {}", path.display()))?;
+    dist.wheel_compatibility_tags()?
+        .validate_installed_wheels(temp_dir.path())?;
+
+    find_resources(logger, dist, temp_dir.path(), None, strict)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*, std::io::Write, std::ops::Deref};
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("foo/__init__.py", b"print('hello')\n"),
+        ("foo/data.txt", b"data"),
+        ("foo-1.0.data/scripts/foo", b"#!python\n"),
+        ("foo-1.0.dist-info/METADATA", b"Name: foo\nVersion: 1.0\n"),
+        ("foo-1.0.dist-info/WHEEL", b"Wheel-Version: 1.0\n"),
+    ];
+
+    #[test]
+    fn test_record_hash() -> Result<()> {
+        assert_eq!(
+            record_hash("sha256", b"")?,
+            "sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+        assert!(record_hash("md5", b"").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wheel_install_path() {
+        assert_eq!(
+            wheel_install_path(Path::new("foo/__init__.py")),
+            Some(PathBuf::from("foo/__init__.py"))
+        );
+        assert_eq!(
+            wheel_install_path(Path::new("foo-1.0.data/purelib/foo/__init__.py")),
+            Some(PathBuf::from("foo/__init__.py"))
+        );
+        assert_eq!(
+            wheel_install_path(Path::new("foo-1.0.data/platlib/_foo.so")),
+            Some(PathBuf::from("_foo.so"))
+        );
+        assert_eq!(
+            wheel_install_path(Path::new("foo-1.0.data/scripts/foo")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_extract_wheel() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let wheel_path = temp_dir.path().join("foo-1.0-py3-none-any.whl");
+        let dest_dir = temp_dir.path().join("dest");
+
+        write_wheel(&wheel_path, FILES, &[])?;
+        extract_wheel(&wheel_path, &dest_dir)?;
+
+        assert!(dest_dir.join("foo").join("__init__.py").exists());
+        assert!(dest_dir.join("foo").join("data.txt").exists());
+        assert!(dest_dir.join("foo-1.0.dist-info").join("RECORD").exists());
+        assert!(!dest_dir.join("foo-1.0.data").exists());
+        assert!(!dest_dir.join("scripts").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_wheel_unlisted() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let wheel_path = temp_dir.path().join("foo-1.0-py3-none-any.whl");
+
+        write_wheel(&wheel_path, FILES, &[("foo/evil.py", b"")])?;
+        let err = extract_wheel(&wheel_path, &temp_dir.path().join("dest"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "foo/evil.py is not listed in RECORD");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_wheel_modified() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let wheel_path = temp_dir.path().join("foo-1.0-py3-none-any.whl");
+
+        let mut zf = zip::ZipWriter::new(std::fs::File::create(&wheel_path)?);
+        for (name, data) in FILES {
+            zf.start_file(*name, zip::write::FileOptions::default())?;
+            zf.write_all(data)?;
+        }
+        zf.start_file(
+            "foo-1.0.dist-info/RECORD",
+            zip::write::FileOptions::default(),
+        )?;
+        let mut record = String::new();
+        for (name, data) in FILES {
+            let data: &[u8] = if *name == "foo/data.txt" {
+                b"other"
+            } else {
+                data
+            };
+            record.push_str(&format!("{},{},\n", name, record_hash("sha256", data)?));
+        }
+        zf.write_all(record.as_bytes())?;
+        zf.finish()?;
+
+        let err = extract_wheel(&wheel_path, &temp_dir.path().join("dest"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "foo/data.txt does not match its hash in RECORD");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_wheel_record_self_entry() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let wheel_path = temp_dir.path().join("foo-1.0-py3-none-any.whl");
+
+        // Layout written by bdist_wheel: RECORD lists itself last without a
+        // hash or size.
+        let files: &[(&str, &[u8])] = &[
+            ("foo/__init__.py", b"print('hello')\n"),
+            ("foo-1.0.data/purelib/foo/bar.py", b""),
+            ("foo-1.0.dist-info/LICENSE", b"MPL-2.0\n"),
+            (
+                "foo-1.0.dist-info/METADATA",
+                b"Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n",
+            ),
+            (
+                "foo-1.0.dist-info/WHEEL",
+                b"Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py3-none-any\n",
+            ),
+            ("foo-1.0.dist-info/top_level.txt", b"foo\n"),
+        ];
+
+        let write = |record_extra: &str| -> Result<()> {
+            let mut zf = zip::ZipWriter::new(std::fs::File::create(&wheel_path)?);
+            let mut record = String::new();
+            for (name, data) in files {
+                zf.start_file(*name, zip::write::FileOptions::default())?;
+                zf.write_all(data)?;
+                record.push_str(&format!(
+                    "{},{},{}\n",
+                    name,
+                    record_hash("sha256", data)?,
+                    data.len()
+                ));
+            }
+            record.push_str(record_extra);
+            record.push_str("foo-1.0.dist-info/RECORD,,\n");
+            zf.start_file(
+                "foo-1.0.dist-info/RECORD",
+                zip::write::FileOptions::default(),
+            )?;
+            zf.write_all(record.as_bytes())?;
+            zf.finish()?;
+
+            Ok(())
+        };
+
+        write("")?;
+        let dest_dir = temp_dir.path().join("dest");
+        extract_wheel(&wheel_path, &dest_dir)?;
+        assert!(dest_dir.join("foo").join("bar.py").exists());
+        assert!(dest_dir.join("foo-1.0.dist-info").join("RECORD").exists());
+
+        // Other files must have a hash.
+        write("foo/__init__.py,,\n")?;
+        let err = extract_wheel(&wheel_path, &temp_dir.path().join("dest2"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "foo/__init__.py has no hash in RECORD");
+
+        // Listed files without a hash must exist.
+        write("foo/missing.py,,\n")?;
+        let err = extract_wheel(&wheel_path, &temp_dir.path().join("dest3"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "files listed in RECORD are missing: foo/missing.py");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_wheel() -> Result<()> {
+        let logger = get_logger()?;
+        let distribution = get_default_distribution()?;
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let wheel_path = temp_dir.path().join("foo-1.0-py3-none-any.whl");
+        write_wheel(&wheel_path, FILES, &[])?;
+
+        let resources = read_wheel(&logger, distribution.deref().as_ref(), &wheel_path, false)?;
+
+        let names = resources
+            .iter()
+            .map(|r| r.full_name())
+            .collect::<Vec<String>>();
+        assert!(names.contains(&"foo".to_string()));
+        assert!(names.contains(&"foo.data.txt".to_string()));
+        assert!(names.contains(&"foo:METADATA".to_string()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+PEP 425 compatibility tags of wheels.
+
+The tags of wheels compatible with a Python distribution are derived from
+the distribution's metadata rather than from the interpreter running `pip`,
+so wheels can be resolved for a distribution targeting another platform.
+Installed wheels are validated against these tags, so incompatible wheels
+are rejected when building rather than failing when imported.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::path::Path,
+};
+
+/// Wheel architectures compatible with a macOS architecture.
+fn macos_arches(arch: &str) -> Vec<&str> {
+    match arch {
+        "x86_64" => vec![
+            "x86_64",
+            "intel",
+            "fat64",
+            "fat32",
+            "universal2",
+            "universal",
+        ],
+        "arm64" => vec!["arm64", "universal2"],
+        "i386" => vec!["i386", "intel", "fat32", "fat", "universal"],
+        arch => vec![arch],
+    }
+}
+
+/// Platform tags compatible with a distribution's platform tag, most specific first.
+fn compatible_platform_tags(platform_tag: &str, target_triple: &str) -> Vec<String> {
+    let platform_tag = platform_tag.replace('-', "_").replace('.', "_");
+    let mut tags = Vec::new();
+
+    if platform_tag.starts_with("linux_") {
+        let arch = &platform_tag["linux_".len()..];
+
+        // Binaries of musl distributions can't load manylinux extension modules.
+        if !target_triple.contains("musl") {
+            // Distributions require glibc 2.17, the baseline of manylinux2014.
+            // Older glibc versions are only supported on x86. PEP 600 tags
+            // name the glibc version, legacy tags are aliases of some of them.
+            let min_glibc_minor = if arch == "x86_64" || arch == "i686" {
+                5
+            } else {
+                17
+            };
+
+            for minor in (min_glibc_minor..=17).rev() {
+                match minor {
+                    17 => tags.push(format!("manylinux2014_{}", arch)),
+                    12 => tags.push(format!("manylinux2010_{}", arch)),
+                    5 => tags.push(format!("manylinux1_{}", arch)),
+                    _ => {}
+                }
+
+                tags.push(format!("manylinux_2_{}_{}", minor, arch));
+            }
+        }
+    } else if platform_tag.starts_with("macosx_") {
+        let parts = platform_tag.splitn(4, '_').collect::<Vec<_>>();
+
+        if let [_, major, minor, arch] = parts[..] {
+            if let (Ok(major), Ok(minor)) = (major.parse::<u32>(), minor.parse::<u32>()) {
+                let mut versions = Vec::new();
+
+                if major == 10 {
+                    versions.extend((0..=minor).rev().map(|minor| (10, minor)));
+                } else {
+                    versions.extend((11..=major).rev().map(|major| (major, 0)));
+
+                    if arch == "x86_64" {
+                        versions.extend((0..=16).rev().map(|minor| (10, minor)));
+                    }
+                }
+
+                for (major, minor) in versions {
+                    for arch in macos_arches(arch) {
+                        tags.push(format!("macosx_{}_{}_{}", major, minor, arch));
+                    }
+                }
+            }
+        }
+    }
+
+    if !tags.contains(&platform_tag) {
+        tags.push(platform_tag);
+    }
+    tags.push("any".to_string());
+
+    tags
+}
+
+/// PEP 425 tags of wheels compatible with a Python distribution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WheelCompatibilityTags {
+    /// Python major version.
+    pub major: u32,
+
+    /// Python minor version.
+    pub minor: u32,
+
+    /// ABI tag of the distribution, e.g. `cp38`.
+    pub abi_tag: String,
+
+    /// Compatible platform tags, most specific first.
+    pub platform_tags: Vec<String>,
+}
+
+impl WheelCompatibilityTags {
+    /// Derive compatible tags from the metadata of a CPython distribution.
+    ///
+    /// `version` is the `X.Y` Python version and `platform_tag` the
+    /// distribution's platform, as reported by `sysconfig.get_platform()`.
+    pub fn new(
+        version: &str,
+        abi_tag: Option<&str>,
+        platform_tag: &str,
+        target_triple: &str,
+    ) -> Result<Self> {
+        let mut parts = version.splitn(2, '.');
+        let (major, minor) = match (
+            parts.next().map(|v| v.parse::<u32>()),
+            parts.next().map(|v| v.parse::<u32>()),
+        ) {
+            (Some(Ok(major)), Some(Ok(minor))) => (major, minor),
+            _ => return Err(anyhow!("invalid Python version: {}", version)),
+        };
+
+        Ok(Self {
+            major,
+            minor,
+            // Before 3.8, builds used by default have the `m` (pymalloc) ABI flag.
+            abi_tag: match abi_tag {
+                Some(tag) => tag.to_string(),
+                None if major == 3 && minor < 8 => format!("cp{}{}m", major, minor),
+                None => format!("cp{}{}", major, minor),
+            },
+            platform_tags: compatible_platform_tags(platform_tag, target_triple),
+        })
+    }
+
+    /// Whether a single `<python>-<abi>-<platform>` tag is compatible.
+    fn is_compatible_tag(&self, python: &str, abi: &str, platform: &str) -> bool {
+        if !self.platform_tags.iter().any(|tag| tag == platform) {
+            return false;
+        }
+
+        let cpython = format!("cp{}{}", self.major, self.minor);
+
+        // Versions of the stable ABI and of pure Python code not newer than ours.
+        let older_version = |prefix: &str| {
+            (0..=self.minor).any(|minor| python == format!("{}{}{}", prefix, self.major, minor))
+        };
+
+        match abi {
+            "none" => {
+                python == cpython || python == format!("py{}", self.major) || older_version("py")
+            }
+            "abi3" => older_version("cp"),
+            abi => abi == self.abi_tag && python == cpython,
+        }
+    }
+
+    /// Whether a wheel tag is compatible.
+    ///
+    /// Each component of the tag can be a compressed tag set, e.g.
+    /// `py2.py3-none-any`.
+    pub fn is_compatible(&self, tag: &str) -> bool {
+        let parts = tag.split('-').collect::<Vec<_>>();
+
+        if let [python, abi, platform] = parts[..] {
+            python.split('.').any(|python| {
+                abi.split('.').any(|abi| {
+                    platform
+                        .split('.')
+                        .any(|platform| self.is_compatible_tag(python, abi, platform))
+                })
+            })
+        } else {
+            false
+        }
+    }
+
+    /// Arguments to `pip install` and `pip download` selecting compatible wheels.
+    ///
+    /// pip then only resolves wheels, as packages can't be built for another
+    /// platform. pip derives less specific platforms, e.g. `manylinux1`
+    /// wheels for `manylinux2014`, from the most specific one.
+    pub fn pip_args(&self) -> Vec<String> {
+        vec![
+            "--implementation".to_string(),
+            "cp".to_string(),
+            "--python-version".to_string(),
+            format!("{}.{}", self.major, self.minor),
+            "--abi".to_string(),
+            self.abi_tag.clone(),
+            "--platform".to_string(),
+            self.platform_tags[0].clone(),
+            "--only-binary".to_string(),
+            ":all:".to_string(),
+        ]
+    }
+
+    /// Validate wheels installed in a directory.
+    ///
+    /// Each `*.dist-info/WHEEL` file in `path` declaring tags must declare a
+    /// compatible one. Packages not installed from wheels have no `WHEEL` file
+    /// and are ignored.
+    pub fn validate_installed_wheels(&self, path: &Path) -> Result<()> {
+        if !path.is_dir() {
+            return Ok(());
+        }
+
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if !name.ends_with(".dist-info") {
+                continue;
+            }
+
+            let wheel_path = entry.path().join("WHEEL");
+            if !wheel_path.exists() {
+                continue;
+            }
+
+            let data = std::fs::read_to_string(&wheel_path)
+                .with_context(|| format!("reading {}", wheel_path.display()))?;
+            let tags = data
+                .lines()
+                .filter(|line| line.starts_with("Tag:"))
+                .map(|line| line["Tag:".len()..].trim())
+                .collect::<Vec<_>>();
+
+            if !tags.is_empty() && !tags.iter().any(|tag| self.is_compatible(tag)) {
+                return Err(anyhow!(
+                    "{} is not compatible with the target distribution: wheel tags {} don't match cp{}{}-{}-{}",
+                    &name[..name.len() - ".dist-info".len()],
+                    tags.join(", "),
+                    self.major,
+                    self.minor,
+                    self.abi_tag,
+                    self.platform_tags[0]
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linux_tags() -> Result<()> {
+        let tags = WheelCompatibilityTags::new(
+            "3.8",
+            Some("cp38"),
+            "linux-x86_64",
+            "x86_64-unknown-linux-gnu",
+        )?;
+
+        assert_eq!(
+            tags.platform_tags[..2],
+            ["manylinux2014_x86_64", "manylinux_2_17_x86_64"]
+        );
+        assert_eq!(
+            tags.platform_tags[tags.platform_tags.len() - 4..],
+            [
+                "manylinux1_x86_64",
+                "manylinux_2_5_x86_64",
+                "linux_x86_64",
+                "any"
+            ]
+        );
+        assert!(tags
+            .platform_tags
+            .contains(&"manylinux2010_x86_64".to_string()));
+
+        assert!(tags.is_compatible("py2.py3-none-any"));
+        assert!(tags.is_compatible("cp38-cp38-manylinux1_x86_64"));
+        assert!(tags.is_compatible("cp36-abi3-manylinux2010_x86_64"));
+        assert!(tags.is_compatible("cp38-cp38-manylinux1_i686.manylinux1_x86_64"));
+        assert!(tags.is_compatible("cp38-cp38-manylinux_2_17_x86_64.manylinux2014_x86_64"));
+        assert!(tags.is_compatible("cp38-cp38-manylinux_2_12_x86_64"));
+        assert!(!tags.is_compatible("cp38-cp38-manylinux_2_24_x86_64"));
+        assert!(!tags.is_compatible("cp38-cp38-manylinux_2_17_aarch64"));
+        assert!(!tags.is_compatible("cp37-cp37m-manylinux1_x86_64"));
+        assert!(!tags.is_compatible("cp39-abi3-manylinux1_x86_64"));
+        assert!(!tags.is_compatible("cp38-cp38-win_amd64"));
+        assert!(!tags.is_compatible("cp38-cp38-manylinux2014_aarch64"));
+        assert!(!tags.is_compatible("py3-none"));
+
+        assert_eq!(
+            tags.pip_args(),
+            vec![
+                "--implementation",
+                "cp",
+                "--python-version",
+                "3.8",
+                "--abi",
+                "cp38",
+                "--platform",
+                "manylinux2014_x86_64",
+                "--only-binary",
+                ":all:"
+            ]
+        );
+
+        let tags = WheelCompatibilityTags::new(
+            "3.8",
+            Some("cp38"),
+            "linux-x86_64",
+            "x86_64-unknown-linux-musl",
+        )?;
+        assert_eq!(tags.platform_tags, vec!["linux_x86_64", "any"]);
+
+        let tags = WheelCompatibilityTags::new(
+            "3.8",
+            Some("cp38"),
+            "linux-aarch64",
+            "aarch64-unknown-linux-gnu",
+        )?;
+        assert_eq!(
+            tags.platform_tags,
+            vec![
+                "manylinux2014_aarch64",
+                "manylinux_2_17_aarch64",
+                "linux_aarch64",
+                "any"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_macos_tags() -> Result<()> {
+        let tags =
+            WheelCompatibilityTags::new("3.8", None, "macosx-10.9-x86_64", "x86_64-apple-darwin")?;
+
+        assert_eq!(tags.abi_tag, "cp38");
+        assert_eq!(tags.platform_tags[0], "macosx_10_9_x86_64");
+        assert!(tags.is_compatible("cp38-cp38-macosx_10_6_intel"));
+        assert!(!tags.is_compatible("cp38-cp38-macosx_10_14_x86_64"));
+        assert!(!tags.is_compatible("cp38-cp38-macosx_11_0_arm64"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_windows_tags() -> Result<()> {
+        let tags = WheelCompatibilityTags::new("3.7", None, "win-amd64", "x86_64-pc-windows-msvc")?;
+
+        assert_eq!(tags.platform_tags, vec!["win_amd64", "any"]);
+        assert_eq!(tags.abi_tag, "cp37m");
+        assert!(tags.is_compatible("cp37-cp37m-win_amd64"));
+        assert!(tags.is_compatible("py37-none-any"));
+        assert!(!tags.is_compatible("py38-none-any"));
+        assert!(!tags.is_compatible("cp37-cp37m-win32"));
+
+        assert!(WheelCompatibilityTags::new("3", None, "win-amd64", "").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_installed_wheels() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let tags = WheelCompatibilityTags::new(
+            "3.8",
+            Some("cp38"),
+            "linux-x86_64",
+            "x86_64-unknown-linux-gnu",
+        )?;
+
+        let write_wheel = |name: &str, tag: &str| -> Result<()> {
+            let dist_info = temp_dir.path().join(format!("{}.dist-info", name));
+            std::fs::create_dir_all(&dist_info)?;
+            std::fs::write(
+                dist_info.join("WHEEL"),
+                format!("Wheel-Version: 1.0\nRoot-Is-Purelib: false\nTag: {}\n", tag),
+            )?;
+
+            Ok(())
+        };
+
+        std::fs::create_dir_all(temp_dir.path().join("legacy-1.0.dist-info"))?;
+        write_wheel("foo-1.0", "py3-none-any")?;
+        tags.validate_installed_wheels(temp_dir.path())?;
+
+        write_wheel("bar-2.0", "cp38-cp38-win_amd64")?;
+        let err = tags.validate_installed_wheels(temp_dir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "bar-2.0 is not compatible with the target distribution: wheel tags cp38-cp38-win_amd64 don't match cp38-cp38-manylinux2014_x86_64"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Python zip applications.
+
+A zip application ([PEP 441](https://www.python.org/dev/peps/pep-0441/)) is
+a zip archive containing Python code and a `__main__.py` file. It is run by
+an existing Python interpreter, optionally found through a shebang line
+prepended to the archive.
+*/
+
+use {
+    super::config::RunMode,
+    crate::app_packaging::resource::FileManifest,
+    anyhow::Result,
+    std::io::{Cursor, Write},
+    std::path::Path,
+};
+
+/// Name of the file executed when running a zip application.
+pub const ZIPAPP_MAIN: &str = "__main__.py";
+
+/// Obtain the source of a `__main__.py` running code like an embedded interpreter would.
+pub fn zipapp_main_source(run_mode: &RunMode) -> String {
+    match run_mode {
+        RunMode::Noop => "".to_string(),
+        RunMode::Repl => "import code\ncode.interact()\n".to_string(),
+        RunMode::Module { module } => format!(
+            "import runpy\nrunpy.run_module({:?}, run_name=\"__main__\", alter_sys=True)\n",
+            module
+        ),
+        RunMode::Eval { code } => format!("{}\n", code),
+        RunMode::File { path } => format!(
+            "import runpy\nrunpy.run_path({:?}, run_name=\"__main__\")\n",
+            path
+        ),
+        RunMode::EntryPoint { group, name } => panic!(
+            "entry point {}:{} should be resolved before deriving __main__.py",
+            group, name
+        ),
+    }
+}
+
+/// Obtain the name of an archive entry for a path.
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Write the files of a manifest to a zip application.
+///
+/// If `interpreter` is defined, the archive starts with a shebang line
+/// running it.
+pub fn write_zipapp(manifest: &FileManifest, interpreter: Option<&str>) -> Result<Vec<u8>> {
+    let mut zf = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    for (path, content) in manifest.entries() {
+        zf.start_file(entry_name(path), zip::write::FileOptions::default())?;
+        zf.write_all(&content.data)?;
+    }
+
+    let archive = zf.finish()?.into_inner();
+
+    let mut res = Vec::new();
+    if let Some(interpreter) = interpreter {
+        res.extend(format!("#!{}\n", interpreter).as_bytes());
+    }
+    res.extend(archive);
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::app_packaging::resource::FileContent, std::io::Read};
+
+    #[test]
+    fn test_main_source() {
+        assert_eq!(
+            zipapp_main_source(&RunMode::Module {
+                module: "app.main".to_string()
+            }),
+            "import runpy\nrunpy.run_module(\"app.main\", run_name=\"__main__\", alter_sys=True)\n"
+        );
+        assert_eq!(
+            zipapp_main_source(&RunMode::Eval {
+                code: "import app; app.run()".to_string()
+            }),
+            "import app; app.run()\n"
+        );
+    }
+
+    #[test]
+    fn test_write_zipapp() -> Result<()> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file(
+            Path::new(ZIPAPP_MAIN),
+            &FileContent {
+                data: b"import app".to_vec(),
+                executable: false,
+            },
+        )?;
+        manifest.add_file(
+            &Path::new("app").join("__init__.py"),
+            &FileContent {
+                data: vec![],
+                executable: false,
+            },
+        )?;
+
+        let data = write_zipapp(&manifest, Some("/usr/bin/env python3"))?;
+        assert!(data.starts_with(b"#!/usr/bin/env python3\nPK"));
+
+        let mut zf = zip::ZipArchive::new(Cursor::new(&data[23..]))?;
+        assert_eq!(zf.len(), 2);
+
+        let mut main = String::new();
+        zf.by_name(ZIPAPP_MAIN)?.read_to_string(&mut main)?;
+        assert_eq!(main, "import app");
+        assert!(zf.by_name("app/__init__.py").is_ok());
+
+        assert!(write_zipapp(&manifest, None)?.starts_with(b"PK"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1169 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Conversion of PyInstaller spec files to PyOxidizer configuration.
+
+A spec file is a Python script calling `Analysis()`, `PYZ()`, `EXE()`,
+`COLLECT()` and `BUNDLE()`. Spec files are not executed: the arguments of
+these calls are read as Python literals, resolving names assigned a literal
+elsewhere in the file (e.g. `block_cipher = None`). Arguments computed by
+other expressions, like `collect_data_files()`, are reported as having no
+equivalent.
+
+Starlark fragments replicating the entry script, hidden imports, data files
+and binaries of the spec are generated for inclusion in a configuration
+file created by `pyoxidizer init-config-file`.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::collections::HashMap,
+    std::path::Path,
+};
+
+/// Spec functions whose calls are read.
+const SPEC_FUNCTIONS: &[&str] = &["Analysis", "PYZ", "EXE", "COLLECT", "BUNDLE"];
+
+/// Value of an argument in a spec file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpecValue {
+    Str(String),
+    Bool(bool),
+    None,
+    /// A list or a tuple.
+    List(Vec<SpecValue>),
+    Dict(Vec<(SpecValue, SpecValue)>),
+    /// A call to a function, e.g. `Executable("app.py")`.
+    Call(SpecCall),
+    /// Source of an expression that isn't a literal.
+    Expr(String),
+}
+
+impl SpecValue {
+    /// Whether this value is a default value of arguments.
+    pub(crate) fn is_default(&self) -> bool {
+        match self {
+            SpecValue::Bool(false) | SpecValue::None => true,
+            SpecValue::List(values) => values.is_empty(),
+            SpecValue::Dict(entries) => entries.is_empty(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            SpecValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// A call to a spec function.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpecCall {
+    pub function: String,
+    pub args: Vec<SpecValue>,
+    pub kwargs: Vec<(String, SpecValue)>,
+}
+
+impl SpecCall {
+    pub(crate) fn kwarg(&self, name: &str) -> Option<&SpecValue> {
+        self.kwargs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Name(String),
+    Str(String),
+    Number,
+    Punct(char),
+    Newline,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+/// Whether an identifier is a prefix of a string literal.
+fn is_string_prefix(ident: &str) -> bool {
+    ident.len() <= 2 && ident.chars().all(|c| "rRbBuUfF".contains(c))
+}
+
+/// Split Python source into tokens.
+///
+/// Newlines are only emitted outside of brackets, where they end statements.
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars = source.char_indices().collect::<Vec<_>>();
+    let offset = |i: usize| chars.get(i).map(|(o, _)| *o).unwrap_or(source.len());
+
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i].1;
+
+        if c == '\n' {
+            if depth == 0 {
+                tokens.push(Token {
+                    kind: TokenKind::Newline,
+                    start: offset(i),
+                    end: offset(i + 1),
+                });
+            }
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '\\' && chars.get(i + 1).map(|(_, c)| *c) == Some('\n') {
+            i += 2;
+        } else if c == '#' {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                i += 1;
+            }
+            let ident = source[offset(start)..offset(i)].to_string();
+
+            match chars.get(i).map(|(_, c)| *c) {
+                Some(quote) if (quote == '"' || quote == '\'') && is_string_prefix(&ident) => {
+                    let raw = ident.contains('r') || ident.contains('R');
+                    let (value, end) = read_string(&chars, i, raw)?;
+                    tokens.push(Token {
+                        kind: TokenKind::Str(value),
+                        start: offset(start),
+                        end: offset(end),
+                    });
+                    i = end;
+                }
+                _ => tokens.push(Token {
+                    kind: TokenKind::Name(ident),
+                    start: offset(start),
+                    end: offset(i),
+                }),
+            }
+        } else if c == '"' || c == '\'' {
+            let (value, end) = read_string(&chars, i, false)?;
+            tokens.push(Token {
+                kind: TokenKind::Str(value),
+                start: offset(i),
+                end: offset(end),
+            });
+            i = end;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || "._".contains(chars[i].1)) {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Number,
+                start: offset(start),
+                end: offset(i),
+            });
+        } else {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            tokens.push(Token {
+                kind: TokenKind::Punct(c),
+                start: offset(i),
+                end: offset(i + 1),
+            });
+            i += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read a string literal starting with a quote at `start`.
+///
+/// Returns the value of the string and the index following it.
+fn read_string(chars: &[(usize, char)], start: usize, raw: bool) -> Result<(String, usize)> {
+    let quote = chars[start].1;
+    let at = |i: usize| chars.get(i).map(|(_, c)| *c);
+
+    let triple = at(start + 1) == Some(quote) && at(start + 2) == Some(quote);
+    let mut i = if triple { start + 3 } else { start + 1 };
+    let mut value = String::new();
+
+    loop {
+        let c = at(i).ok_or_else(|| anyhow!("unterminated string literal"))?;
+
+        if c == quote && (!triple || (at(i + 1) == Some(quote) && at(i + 2) == Some(quote))) {
+            return Ok((value, if triple { i + 3 } else { i + 1 }));
+        } else if c == '\n' && !triple {
+            return Err(anyhow!("unterminated string literal"));
+        } else if c == '\\' {
+            let next = at(i + 1).ok_or_else(|| anyhow!("unterminated string literal"))?;
+
+            if raw {
+                value.push(c);
+                value.push(next);
+            } else {
+                match next {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    '\n' => {}
+                    '\\' | '\'' | '"' => value.push(next),
+                    _ => {
+                        value.push(c);
+                        value.push(next);
+                    }
+                }
+            }
+            i += 2;
+        } else {
+            value.push(c);
+            i += 1;
+        }
+    }
+}
+
+/// Split tokens on a separator outside of brackets.
+///
+/// A trailing separator doesn't produce an empty item.
+fn split_on(tokens: &[Token], separator: char) -> Vec<&[Token]> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Punct('(') | TokenKind::Punct('[') | TokenKind::Punct('{') => depth += 1,
+            TokenKind::Punct(')') | TokenKind::Punct(']') | TokenKind::Punct('}') => {
+                depth = depth.saturating_sub(1)
+            }
+            TokenKind::Punct(c) if c == separator && depth == 0 => {
+                items.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if start < tokens.len() {
+        items.push(&tokens[start..]);
+    }
+
+    items
+}
+
+/// Find the index of the bracket closing the one at `open`.
+fn find_closing(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::Punct('(') | TokenKind::Punct('[') | TokenKind::Punct('{') => depth += 1,
+            TokenKind::Punct(')') | TokenKind::Punct(']') | TokenKind::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Evaluate tokens as a Python literal.
+///
+/// Names are resolved from `variables`. Calls to a function named by an
+/// identifier are also evaluated, their arguments being read with
+/// `parse_value()`. `None` is returned if the tokens aren't a literal.
+fn parse_literal(
+    source: &str,
+    tokens: &[Token],
+    variables: &HashMap<String, SpecValue>,
+) -> Option<SpecValue> {
+    let operands = split_on(tokens, '+');
+    if operands.len() > 1 {
+        // Lists are commonly concatenated, e.g. `datas=added_files + [...]`.
+        let mut values = Vec::new();
+        for operand in operands {
+            match parse_literal(source, operand, variables)? {
+                SpecValue::List(operand) => values.extend(operand),
+                _ => return None,
+            }
+        }
+
+        return Some(SpecValue::List(values));
+    }
+
+    let first = tokens.first()?;
+
+    match &first.kind {
+        TokenKind::Str(_) => {
+            // Adjacent string literals are concatenated.
+            let mut value = String::new();
+            for token in tokens {
+                match &token.kind {
+                    TokenKind::Str(s) => value.push_str(s),
+                    _ => return None,
+                }
+            }
+
+            Some(SpecValue::Str(value))
+        }
+        TokenKind::Name(name) if tokens.len() == 1 => match name.as_str() {
+            "True" => Some(SpecValue::Bool(true)),
+            "False" => Some(SpecValue::Bool(false)),
+            "None" => Some(SpecValue::None),
+            _ => variables.get(name).cloned(),
+        },
+        TokenKind::Name(name)
+            if tokens[1].kind == TokenKind::Punct('(')
+                && find_closing(tokens, 1)? == tokens.len() - 1 =>
+        {
+            Some(SpecValue::Call(parse_call(
+                source,
+                name,
+                &tokens[2..tokens.len() - 1],
+                variables,
+            )))
+        }
+        TokenKind::Punct('{') => {
+            if find_closing(tokens, 0)? != tokens.len() - 1 {
+                return None;
+            }
+
+            let entries = split_on(&tokens[1..tokens.len() - 1], ',')
+                .into_iter()
+                .map(|item| match split_on(item, ':').as_slice() {
+                    [key, value] => Some((
+                        parse_literal(source, key, variables)?,
+                        parse_literal(source, value, variables)?,
+                    )),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(SpecValue::Dict(entries))
+        }
+        TokenKind::Punct(open @ '(') | TokenKind::Punct(open @ '[') => {
+            if find_closing(tokens, 0)? != tokens.len() - 1 {
+                return None;
+            }
+
+            let inner = &tokens[1..tokens.len() - 1];
+            let comma = split_on(inner, ',').len() > 1
+                || inner.last().map(|t| &t.kind) == Some(&TokenKind::Punct(','));
+            let values = split_on(inner, ',')
+                .into_iter()
+                .map(|item| parse_literal(source, item, variables))
+                .collect::<Option<Vec<_>>>()?;
+
+            // Parentheses without a comma only group an expression.
+            if *open == '(' && !comma && values.len() == 1 {
+                values.into_iter().next()
+            } else {
+                Some(SpecValue::List(values))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Evaluate tokens as a spec argument value.
+fn parse_value(
+    source: &str,
+    tokens: &[Token],
+    variables: &HashMap<String, SpecValue>,
+) -> SpecValue {
+    parse_literal(source, tokens, variables).unwrap_or_else(|| {
+        let start = tokens.first().map(|t| t.start).unwrap_or(0);
+        let end = tokens.last().map(|t| t.end).unwrap_or(0);
+
+        SpecValue::Expr(source[start..end].to_string())
+    })
+}
+
+/// Read the arguments of a call to `function`.
+fn parse_call(
+    source: &str,
+    function: &str,
+    tokens: &[Token],
+    variables: &HashMap<String, SpecValue>,
+) -> SpecCall {
+    let mut call = SpecCall {
+        function: function.to_string(),
+        args: Vec::new(),
+        kwargs: Vec::new(),
+    };
+
+    for item in split_on(tokens, ',') {
+        match (item.first().map(|t| &t.kind), item.get(1).map(|t| &t.kind)) {
+            (Some(TokenKind::Name(key)), Some(TokenKind::Punct('=')))
+                if item.get(2).map(|t| &t.kind) != Some(&TokenKind::Punct('=')) =>
+            {
+                call.kwargs
+                    .push((key.clone(), parse_value(source, &item[2..], variables)));
+            }
+            _ => call.args.push(parse_value(source, item, variables)),
+        }
+    }
+
+    call
+}
+
+/// Read the calls to spec functions in a spec file.
+pub fn parse_spec(source: &str) -> Result<Vec<SpecCall>> {
+    parse_calls(source, SPEC_FUNCTIONS)
+}
+
+/// Read the calls to `functions` in a Python script.
+///
+/// Arguments are evaluated as by `parse_spec()`.
+pub fn parse_calls(source: &str, functions: &[&str]) -> Result<Vec<SpecCall>> {
+    let tokens = tokenize(source)?;
+
+    let mut variables = HashMap::new();
+    let mut calls = Vec::new();
+    let mut statement_start = true;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let next = tokens.get(i + 1).map(|t| &t.kind);
+
+        match &token.kind {
+            // Record `name = <literal>` assignments to resolve arguments.
+            TokenKind::Name(name) if statement_start && next == Some(&TokenKind::Punct('=')) => {
+                let end = tokens[i + 2..]
+                    .iter()
+                    .position(|t| t.kind == TokenKind::Newline)
+                    .map(|p| i + 2 + p)
+                    .unwrap_or_else(|| tokens.len());
+
+                if let Some(value) = parse_literal(source, &tokens[i + 2..end], &variables) {
+                    variables.insert(name.clone(), value);
+                }
+
+                i += 2;
+                statement_start = false;
+                continue;
+            }
+            TokenKind::Name(name)
+                if functions.contains(&name.as_str()) && next == Some(&TokenKind::Punct('(')) =>
+            {
+                let close = find_closing(&tokens, i + 1)
+                    .ok_or_else(|| anyhow!("unterminated call to {}()", name))?;
+
+                calls.push(parse_call(source, name, &tokens[i + 2..close], &variables));
+                i = close + 1;
+                statement_start = false;
+                continue;
+            }
+            _ => {}
+        }
+
+        statement_start = token.kind == TokenKind::Newline;
+        i += 1;
+    }
+
+    Ok(calls)
+}
+
+/// Describe a value in messages.
+pub(crate) fn describe(value: &SpecValue) -> String {
+    match value {
+        SpecValue::Expr(expr) => expr.clone(),
+        SpecValue::Call(call) => format!("{}(...)", call.function),
+        value => format!("{:?}", value),
+    }
+}
+
+/// Format a string as a Starlark string literal.
+pub(crate) fn starlark_str(value: &str) -> String {
+    let mut res = String::with_capacity(value.len() + 2);
+    res.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+
+    res.push('"');
+    res
+}
+
+/// Format strings as a Starlark list literal.
+pub(crate) fn starlark_list(values: &[String]) -> String {
+    format!(
+        "[{}]",
+        values
+            .iter()
+            .map(|v| starlark_str(v))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Normalize a path of a spec file to use `/` separators.
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.trim_end_matches('/');
+
+    path.strip_prefix("./").unwrap_or(path).to_string()
+}
+
+/// Whether a path is absolute on any platform.
+fn is_absolute(path: &str) -> bool {
+    path.starts_with('/') || path.chars().nth(1) == Some(':')
+}
+
+/// Derive the `glob()` call placing files of a `datas` or `binaries` entry.
+///
+/// PyInstaller copies files matching `source` to the `dest` directory, or
+/// the content of `source` if it is a directory. `glob()` can only strip a
+/// prefix from paths of matched files, so `None` is returned unless `dest`
+/// is a suffix of the directory containing the files.
+pub(crate) fn placement_glob(spec_dir: &Path, source: &str, dest: &str) -> Option<String> {
+    let source = normalize_path(source);
+    let dest = match normalize_path(dest).as_str() {
+        "." => String::new(),
+        dest => dest.to_string(),
+    };
+    let is_pattern = |s: &str| s.contains(|c: char| "*?[".contains(c));
+
+    let (pattern, dir) = if !is_pattern(&source) && spec_dir.join(&source).is_dir() {
+        (format!("{}/**/*", source), source.clone())
+    } else {
+        let dir = match source.rfind('/') {
+            Some(index) => source[..index].to_string(),
+            None => String::new(),
+        };
+
+        (source.clone(), dir)
+    };
+
+    if is_pattern(&dir) {
+        return None;
+    }
+
+    let prefix = if dest.is_empty() {
+        dir
+    } else if dir == dest {
+        String::new()
+    } else if dir.ends_with(&format!("/{}", dest)) {
+        dir[..dir.len() - dest.len() - 1].to_string()
+    } else {
+        return None;
+    };
+
+    let strip_prefix = if is_absolute(&pattern) {
+        starlark_str(&prefix)
+    } else if prefix.is_empty() {
+        "CWD".to_string()
+    } else {
+        format!("CWD + {}", starlark_str(&format!("/{}", prefix)))
+    };
+
+    Some(format!(
+        "glob([{}], strip_prefix={})",
+        starlark_str(&pattern),
+        strip_prefix
+    ))
+}
+
+/// Resolve the module name of a Python script and the Starlark expression of
+/// the directory containing it, relative to `CWD`.
+pub(crate) fn script_module(script: &str) -> (String, String) {
+    let script = normalize_path(script);
+
+    let (script_dir, script_file) = match script.rfind('/') {
+        Some(index) => (&script[..index], &script[index + 1..]),
+        None => ("", script.as_str()),
+    };
+    let module = script_file.trim_end_matches(".py").to_string();
+    let root = if is_absolute(&script) {
+        starlark_str(script_dir)
+    } else if script_dir.is_empty() {
+        "CWD".to_string()
+    } else {
+        format!("CWD + {}", starlark_str(&format!("/{}", script_dir)))
+    };
+
+    (root, module)
+}
+
+/// Starlark configuration derived from a spec file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpecConversion {
+    /// Starlark fragments.
+    pub starlark: String,
+
+    /// Descriptions of spec constructs without an equivalent.
+    pub unsupported: Vec<String>,
+}
+
+impl SpecConversion {
+    pub(crate) fn flag(&mut self, message: String) {
+        if !self.unsupported.contains(&message) {
+            self.unsupported.push(message);
+        }
+    }
+
+    /// Set the Starlark to `lines` followed by unsupported constructs.
+    ///
+    /// `source` describes the converted file in comments.
+    pub(crate) fn finish(&mut self, mut lines: Vec<String>, source: &str) {
+        if !self.unsupported.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "# Constructs of the {} without an equivalent:",
+                source
+            ));
+            lines.push("#".to_string());
+            for message in &self.unsupported {
+                lines.push(format!("# - {}", message));
+            }
+        }
+
+        self.starlark = lines.join("\n") + "\n";
+    }
+}
+
+/// Arguments of spec functions with an equivalent or no effect.
+fn handled_arguments(function: &str) -> &'static [&'static str] {
+    match function {
+        "Analysis" => &["scripts", "pathex", "binaries", "datas", "hiddenimports"],
+        "PYZ" => &[],
+        "EXE" => &["name", "exclude_binaries", "uac_admin", "codesign_identity"],
+        "COLLECT" => &["name"],
+        _ => &[],
+    }
+}
+
+/// Convert spec function calls to Starlark.
+///
+/// `spec_name` is the name of the spec file and `spec_dir` the directory
+/// containing it, which relative paths are resolved against.
+pub fn convert_spec(
+    spec_name: &str,
+    spec_dir: &Path,
+    calls: &[SpecCall],
+) -> Result<SpecConversion> {
+    let analysis = calls
+        .iter()
+        .find(|call| call.function == "Analysis")
+        .ok_or_else(|| anyhow!("no Analysis() call found in spec"))?;
+    let exe = calls.iter().find(|call| call.function == "EXE");
+    let collect = calls.iter().find(|call| call.function == "COLLECT");
+
+    let mut res = SpecConversion::default();
+
+    for call in calls {
+        if call.function == "BUNDLE" {
+            res.flag(
+                "`BUNDLE()`: macOS application bundles aren't produced; \
+                 executables are installed to a directory"
+                    .to_string(),
+            );
+            continue;
+        }
+
+        for (key, value) in &call.kwargs {
+            if handled_arguments(&call.function).contains(&key.as_str())
+                || value.is_default()
+                || (key == "console" && value == &SpecValue::Bool(true))
+            {
+                continue;
+            }
+
+            let message = match key.as_str() {
+                "cipher" => "bytecode encryption isn't supported".to_string(),
+                "excludes" => "modules can't be excluded from packages; filter resources with \
+                     `exe.filter_resources_from_files()`"
+                    .to_string(),
+                "hookspath" | "hooksconfig" | "runtime_hooks" => {
+                    "PyInstaller hooks aren't run; packages are added whole".to_string()
+                }
+                "upx" | "upx_exclude" | "strip" => {
+                    "binaries aren't compressed or stripped".to_string()
+                }
+                "console" => "windowed (non-console) executables aren't supported".to_string(),
+                _ => "no equivalent".to_string(),
+            };
+
+            res.flag(format!("`{}({}=...)`: {}", call.function, key, message));
+        }
+    }
+
+    let mut lines = vec![
+        format!(
+            "# Generated from {} by `pyoxidizer import-pyinstaller-spec`.",
+            spec_name
+        ),
+        "#".to_string(),
+        "# These fragments replicate the spec in a configuration file created by".to_string(),
+        "# `pyoxidizer init-config-file`. Paths are relative to the directory".to_string(),
+        "# containing the spec, which should also contain the configuration file.".to_string(),
+        String::new(),
+        "# In make_exe():".to_string(),
+    ];
+
+    let scripts = match analysis.kwarg("scripts").or_else(|| analysis.args.first()) {
+        Some(SpecValue::List(scripts)) => scripts
+            .iter()
+            .map(|s| s.as_str().map(normalize_path))
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Analysis() scripts must be a list of strings"))?;
+
+    let script = scripts
+        .first()
+        .ok_or_else(|| anyhow!("Analysis() has no scripts"))?;
+    if scripts.len() > 1 {
+        res.flag(
+            "`Analysis(scripts=...)`: only the first script is run; run others from it".to_string(),
+        );
+    }
+
+    let (script_root, module) = script_module(script);
+    let module = module.as_str();
+
+    let name = exe
+        .and_then(|exe| exe.kwarg("name"))
+        .and_then(|name| name.as_str())
+        .unwrap_or(module)
+        .to_string();
+
+    lines.extend(vec![
+        "python_config = PythonInterpreterConfig(".to_string(),
+        format!("    run_module={},", starlark_str(module)),
+        "    # Emulate PyInstaller for code checking sys.frozen or sys._MEIPASS.".to_string(),
+        "    #sys_frozen=True,".to_string(),
+        "    #sys_meipass=True,".to_string(),
+        ")".to_string(),
+        String::new(),
+        "exe = dist.to_python_executable(".to_string(),
+        format!("    name={},", starlark_str(&name)),
+        "    config=python_config,".to_string(),
+        "    extension_module_filter=\"all\",".to_string(),
+        "    include_sources=True,".to_string(),
+        "    include_resources=True,".to_string(),
+        ")".to_string(),
+        String::new(),
+        format!(
+            "# The entry script, run as the `{}` module. Add local packages it",
+            module
+        ),
+        "# imports the same way.".to_string(),
+        format!(
+            "exe.add_in_memory_python_resources(dist.read_package_root({}, {}))",
+            script_root,
+            starlark_list(&[module.to_string()])
+        ),
+    ]);
+
+    if let Some(value) = analysis.kwarg("pathex") {
+        if !value.is_default() {
+            res.flag(
+                "`Analysis(pathex=...)`: add packages from these directories with \
+                 `dist.read_package_root()`"
+                    .to_string(),
+            );
+        }
+    }
+
+    match analysis.kwarg("hiddenimports") {
+        Some(SpecValue::List(values)) if !values.is_empty() => {
+            let mut packages: Vec<String> = Vec::new();
+
+            for value in values {
+                match value.as_str() {
+                    Some(module) => {
+                        let package = module.split('.').next().unwrap_or(module).to_string();
+                        if !packages.contains(&package) {
+                            packages.push(package);
+                        }
+                    }
+                    None => res.flag(format!(
+                        "`Analysis(hiddenimports=...)`: {} isn't a module name",
+                        describe(value)
+                    )),
+                }
+            }
+
+            lines.extend(vec![
+                String::new(),
+                "# Hidden imports. Imports aren't analyzed and packages are added whole,"
+                    .to_string(),
+                "# so hidden imports only need their package to be installed. Package".to_string(),
+                "# names are used as requirements: adjust those differing from their".to_string(),
+                "# distribution name and remove standard library modules.".to_string(),
+                format!(
+                    "exe.add_in_memory_python_resources(dist.pip_install({}))",
+                    starlark_list(&packages)
+                ),
+            ]);
+        }
+        Some(value) if !value.is_default() => res.flag(format!(
+            "`Analysis(hiddenimports={})`: only lists of module names are converted",
+            describe(value)
+        )),
+        _ => {}
+    }
+
+    if let Some(exe) = exe {
+        if exe.kwarg("uac_admin") == Some(&SpecValue::Bool(true)) {
+            lines.extend(vec![
+                String::new(),
+                "exe.set_windows_manifest(uac_execution_level=\"requireAdministrator\")"
+                    .to_string(),
+            ]);
+        }
+
+        if let Some(identity) = exe.kwarg("codesign_identity").and_then(|v| v.as_str()) {
+            lines.extend(vec![
+                String::new(),
+                format!("exe.set_macos_signing({})", starlark_str(identity)),
+            ]);
+        }
+    }
+
+    lines.extend(vec![
+        String::new(),
+        "# In make_install():".to_string(),
+        "files = FileManifest()".to_string(),
+        "files.add_python_resource(\".\", exe)".to_string(),
+    ]);
+
+    for key in &["datas", "binaries"] {
+        let entries = match analysis.kwarg(key) {
+            Some(SpecValue::List(entries)) if !entries.is_empty() => entries,
+            Some(value) if !value.is_default() => {
+                res.flag(format!(
+                    "`Analysis({}={})`: only lists of (source, destination) tuples are converted",
+                    key,
+                    describe(value)
+                ));
+                continue;
+            }
+            _ => continue,
+        };
+
+        lines.push(String::new());
+        lines.push(format!("# `{}` of the spec.", key));
+
+        for entry in entries {
+            let placement = match entry {
+                SpecValue::List(pair) if pair.len() == 2 => {
+                    match (pair[0].as_str(), pair[1].as_str()) {
+                        (Some(source), Some(dest)) => Some((source, dest)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            match placement {
+                Some((source, dest)) => match placement_glob(spec_dir, source, dest) {
+                    Some(glob) => lines.push(format!("files.add_manifest({})", glob)),
+                    None => res.flag(format!(
+                        "`Analysis({}=...)`: ({:?}, {:?}) can't be placed with glob(), \
+                         whose strip_prefix must leave the destination directory",
+                        key, source, dest
+                    )),
+                },
+                None => res.flag(format!(
+                    "`Analysis({}=...)`: {} isn't a (source, destination) tuple",
+                    key,
+                    describe(entry)
+                )),
+            }
+        }
+    }
+
+    let install_dir = collect
+        .and_then(|collect| collect.kwarg("name"))
+        .and_then(|name| name.as_str())
+        .unwrap_or(&name)
+        .to_string();
+    lines.extend(vec![
+        String::new(),
+        "# Write files to a directory of the build path, like COLLECT().".to_string(),
+        format!("files.install({})", starlark_str(&install_dir)),
+    ]);
+
+    res.finish(lines, "spec");
+
+    Ok(res)
+}
+
+/// Read a spec file and convert it to Starlark.
+pub fn import_spec(path: &Path) -> Result<SpecConversion> {
+    let source = std::fs::read_to_string(path).context(format!("reading {}", path.display()))?;
+
+    let spec_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("unable to determine file name"))?
+        .to_string_lossy()
+        .to_string();
+    let spec_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    convert_spec(&spec_name, spec_dir, &parse_spec(&source)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"# -*- mode: python ; coding: utf-8 -*-
+
+block_cipher = None
+added_files = [
+    ('assets/*.png', 'assets'),
+    ("data/config", "config"),  # A directory.
+]
+
+a = Analysis(['app.py'],
+             pathex=['/home/user/app'],
+             binaries=[('lib/libfoo.so', '.')],
+             datas=added_files + [('README.md', 'docs')],
+             hiddenimports=['yaml', 'pkg_resources.py2_warn', "pkg_resources" ".extern"],
+             hookspath=[],
+             runtime_hooks=[],
+             excludes=['tkinter'],
+             win_no_prefer_redirects=False,
+             cipher=block_cipher,
+             noarchive=False)
+pyz = PYZ(a.pure, a.zipped_data,
+             cipher=block_cipher)
+exe = EXE(pyz,
+          a.scripts,
+          [],
+          exclude_binaries=True,
+          name='myapp',
+          debug=False,
+          strip=False,
+          upx=True,
+          console=True )
+coll = COLLECT(exe,
+               a.binaries,
+               a.zipfiles,
+               a.datas,
+               strip=False,
+               upx=True,
+               upx_exclude=[],
+               name='myapp-dist')
+"#;
+
+    #[test]
+    fn test_parse_spec() -> Result<()> {
+        let calls = parse_spec(SPEC)?;
+
+        assert_eq!(
+            calls
+                .iter()
+                .map(|c| c.function.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Analysis", "PYZ", "EXE", "COLLECT"]
+        );
+
+        let analysis = &calls[0];
+        assert_eq!(
+            analysis.args,
+            vec![SpecValue::List(vec![SpecValue::Str("app.py".to_string())])]
+        );
+        assert_eq!(
+            analysis.kwarg("hiddenimports"),
+            Some(&SpecValue::List(vec![
+                SpecValue::Str("yaml".to_string()),
+                SpecValue::Str("pkg_resources.py2_warn".to_string()),
+                SpecValue::Str("pkg_resources.extern".to_string()),
+            ]))
+        );
+        assert_eq!(analysis.kwarg("cipher"), Some(&SpecValue::None));
+        let pair = |source: &str, dest: &str| {
+            SpecValue::List(vec![
+                SpecValue::Str(source.to_string()),
+                SpecValue::Str(dest.to_string()),
+            ])
+        };
+        assert_eq!(
+            analysis.kwarg("datas"),
+            Some(&SpecValue::List(vec![
+                pair("assets/*.png", "assets"),
+                pair("data/config", "config"),
+                pair("README.md", "docs"),
+            ]))
+        );
+
+        assert_eq!(calls[1].args.len(), 2);
+        assert_eq!(
+            calls[2].kwarg("name"),
+            Some(&SpecValue::Str("myapp".to_string()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_literals() -> Result<()> {
+        let calls = parse_spec(
+            "EXE(a='\\\\x\\n', b=r'C:\\dir', c=\"\"\"x\"y\"\"\", d=('x',), e=('x'), f=1)",
+        )?;
+
+        assert_eq!(
+            calls[0].kwargs,
+            vec![
+                ("a".to_string(), SpecValue::Str("\\x\n".to_string())),
+                ("b".to_string(), SpecValue::Str("C:\\dir".to_string())),
+                ("c".to_string(), SpecValue::Str("x\"y".to_string())),
+                (
+                    "d".to_string(),
+                    SpecValue::List(vec![SpecValue::Str("x".to_string())])
+                ),
+                ("e".to_string(), SpecValue::Str("x".to_string())),
+                ("f".to_string(), SpecValue::Expr("1".to_string())),
+            ]
+        );
+
+        let calls = parse_spec("EXE(g={'a': ['x'], 'b': dict(c=y)}, h={})")?;
+
+        assert_eq!(
+            calls[0].kwargs,
+            vec![
+                (
+                    "g".to_string(),
+                    SpecValue::Dict(vec![
+                        (
+                            SpecValue::Str("a".to_string()),
+                            SpecValue::List(vec![SpecValue::Str("x".to_string())])
+                        ),
+                        (
+                            SpecValue::Str("b".to_string()),
+                            SpecValue::Call(SpecCall {
+                                function: "dict".to_string(),
+                                args: vec![],
+                                kwargs: vec![("c".to_string(), SpecValue::Expr("y".to_string()))],
+                            })
+                        ),
+                    ])
+                ),
+                ("h".to_string(), SpecValue::Dict(vec![])),
+            ]
+        );
+
+        assert!(parse_spec("EXE(name='x)").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_placement_glob() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::create_dir_all(temp_dir.path().join("data").join("config"))?;
+        let dir = temp_dir.path();
+
+        assert_eq!(
+            placement_glob(dir, "assets/*.png", "assets").unwrap(),
+            "glob([\"assets/*.png\"], strip_prefix=CWD)"
+        );
+        assert_eq!(
+            placement_glob(dir, "data/config", "config").unwrap(),
+            "glob([\"data/config/**/*\"], strip_prefix=CWD + \"/data\")"
+        );
+        assert_eq!(
+            placement_glob(dir, "lib\\libfoo.so", ".").unwrap(),
+            "glob([\"lib/libfoo.so\"], strip_prefix=CWD + \"/lib\")"
+        );
+        assert_eq!(
+            placement_glob(dir, "/usr/lib/libbar.so", "").unwrap(),
+            "glob([\"/usr/lib/libbar.so\"], strip_prefix=\"/usr/lib\")"
+        );
+        assert_eq!(placement_glob(dir, "README.md", "docs"), None);
+        assert_eq!(placement_glob(dir, "*/x.txt", "."), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_spec() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let spec_path = temp_dir.path().join("myapp.spec");
+        std::fs::create_dir_all(temp_dir.path().join("data").join("config"))?;
+        std::fs::write(&spec_path, SPEC)?;
+
+        let res = import_spec(&spec_path)?;
+
+        assert!(res.starlark.contains("    run_module=\"app\",\n"));
+        assert!(res.starlark.contains("    name=\"myapp\",\n"));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(dist.read_package_root(CWD, [\"app\"]))\n"
+        ));
+        assert!(res.starlark.contains(
+            "exe.add_in_memory_python_resources(dist.pip_install([\"yaml\", \"pkg_resources\"]))\n"
+        ));
+        assert!(res
+            .starlark
+            .contains("files.add_manifest(glob([\"assets/*.png\"], strip_prefix=CWD))\n"));
+        assert!(res.starlark.contains(
+            "files.add_manifest(glob([\"data/config/**/*\"], strip_prefix=CWD + \"/data\"))\n"
+        ));
+        assert!(res.starlark.contains(
+            "files.add_manifest(glob([\"lib/libfoo.so\"], strip_prefix=CWD + \"/lib\"))\n"
+        ));
+        assert!(res.starlark.contains("files.install(\"myapp-dist\")\n"));
+
+        assert_eq!(
+            res.unsupported,
+            vec![
+                "`Analysis(excludes=...)`: modules can't be excluded from packages; \
+                 filter resources with `exe.filter_resources_from_files()`",
+                "`EXE(upx=...)`: binaries aren't compressed or stripped",
+                "`COLLECT(upx=...)`: binaries aren't compressed or stripped",
+                "`Analysis(pathex=...)`: add packages from these directories with \
+                 `dist.read_package_root()`",
+                "`Analysis(datas=...)`: (\"README.md\", \"docs\") can't be placed with glob(), \
+                 whose strip_prefix must leave the destination directory",
+            ]
+        );
+        assert!(res
+            .starlark
+            .contains("# - `EXE(upx=...)`: binaries aren't compressed or stripped\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_spec_without_analysis() {
+        assert!(convert_spec("x.spec", Path::new("."), &parse_spec("x = 1").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_starlark_str() {
+        assert_eq!(starlark_str("my app"), "\"my app\"");
+        assert_eq!(
+            starlark_str("a\"b\\c\nd\re\tf\u{7}"),
+            "\"a\\\"b\\\\c\\nd\\re\\tf\\u0007\""
+        );
+    }
+}
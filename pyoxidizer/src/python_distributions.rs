@@ -8,7 +8,10 @@ use {
     crate::py_packaging::distribution::{
         DistributionFlavor, PythonDistributionLocation, PythonDistributionRecord,
     },
+    anyhow::{anyhow, Result},
     lazy_static::lazy_static,
+    serde::Deserialize,
+    std::path::Path,
 };
 
 /// Describes a Python distribution available at a URL.
@@ -17,39 +20,136 @@ pub struct HostedDistribution {
     pub sha256: String,
 }
 
+#[derive(Clone, Debug)]
 pub struct PythonDistributionCollection {
     dists: Vec<PythonDistributionRecord>,
 }
 
+/// An entry of a distributions index file.
+#[derive(Debug, Deserialize)]
+struct IndexDistribution {
+    target_triple: String,
+    python_version: String,
+    url: Option<String>,
+    local_path: Option<String>,
+    sha256: String,
+    #[serde(default = "default_supports_prebuilt_extension_modules")]
+    supports_prebuilt_extension_modules: bool,
+}
+
+fn default_supports_prebuilt_extension_modules() -> bool {
+    true
+}
+
+/// A distributions index file.
+#[derive(Debug, Deserialize)]
+struct DistributionsIndex {
+    #[serde(default, rename = "distribution")]
+    distributions: Vec<IndexDistribution>,
+}
+
 impl PythonDistributionCollection {
+    /// Parse a TOML index of distributions.
+    ///
+    /// Each `[[distribution]]` table defines the `target_triple`,
+    /// `python_version` and `sha256` of a distribution and either its `url`
+    /// or its `local_path`. Relative local paths are relative to `base_path`.
+    pub fn from_index(data: &str, base_path: &Path) -> Result<Self> {
+        let index: DistributionsIndex = toml::from_str(data)?;
+
+        let dists = index
+            .distributions
+            .into_iter()
+            .map(|dist| {
+                let location = match (dist.url, dist.local_path) {
+                    (Some(url), None) => PythonDistributionLocation::Url {
+                        url,
+                        sha256: dist.sha256,
+                    },
+                    (None, Some(local_path)) => PythonDistributionLocation::Local {
+                        local_path: base_path.join(local_path).display().to_string(),
+                        sha256: dist.sha256,
+                    },
+                    _ => {
+                        return Err(anyhow!(
+                            "distribution for {} must define exactly one of url and local_path",
+                            dist.target_triple
+                        ))
+                    }
+                };
+
+                Ok(PythonDistributionRecord {
+                    location,
+                    python_major_minor_version: dist.python_version,
+                    target_triple: dist.target_triple,
+                    supports_prebuilt_extension_modules: dist.supports_prebuilt_extension_modules,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { dists })
+    }
+
+    /// Register distributions of another collection.
+    ///
+    /// They take precedence over distributions of this collection.
+    pub fn register(&mut self, other: &PythonDistributionCollection) {
+        let mut dists = other.dists.clone();
+        dists.extend(self.dists.drain(..));
+        self.dists = dists;
+    }
+
+    /// Find a distribution for a target triple and flavor.
+    ///
+    /// If `python_major_minor_version` is defined, only distributions of this
+    /// Python version (e.g. `3.8`) are considered. Otherwise the first
+    /// matching distribution is returned.
     pub fn find_distribution(
         &self,
         target_triple: &str,
         flavor: &DistributionFlavor,
+        python_major_minor_version: Option<&str>,
     ) -> Option<PythonDistributionRecord> {
+        self.dists
+            .iter()
+            .filter(|dist| Self::is_compatible(dist, target_triple, flavor))
+            .find(|dist| match python_major_minor_version {
+                Some(version) => dist.python_major_minor_version == version,
+                None => true,
+            })
+            .cloned()
+    }
+
+    /// Obtain the Python versions of distributions for a target triple and flavor.
+    pub fn python_versions(&self, target_triple: &str, flavor: &DistributionFlavor) -> Vec<String> {
+        let mut versions = Vec::new();
+
         for dist in &self.dists {
-            if dist.target_triple != target_triple {
-                continue;
+            if Self::is_compatible(dist, target_triple, flavor)
+                && !versions.contains(&dist.python_major_minor_version)
+            {
+                versions.push(dist.python_major_minor_version.clone());
             }
+        }
 
-            match flavor {
-                DistributionFlavor::Standalone => {
-                    return Some(dist.clone());
-                }
-                DistributionFlavor::StandaloneStatic => {
-                    if !dist.supports_prebuilt_extension_modules {
-                        return Some(dist.clone());
-                    }
-                }
-                DistributionFlavor::StandaloneDynamic => {
-                    if dist.supports_prebuilt_extension_modules {
-                        return Some(dist.clone());
-                    }
-                }
-            }
+        versions
+    }
+
+    fn is_compatible(
+        dist: &PythonDistributionRecord,
+        target_triple: &str,
+        flavor: &DistributionFlavor,
+    ) -> bool {
+        if dist.target_triple != target_triple {
+            return false;
         }
 
-        None
+        match flavor {
+            DistributionFlavor::Standalone => true,
+            DistributionFlavor::StandaloneStatic => !dist.supports_prebuilt_extension_modules,
+            DistributionFlavor::StandaloneDynamic => dist.supports_prebuilt_extension_modules,
+            DistributionFlavor::Fixture => false,
+        }
     }
 }
 
@@ -62,6 +162,7 @@ lazy_static! {
                     url: "https://github.com/indygreg/python-build-standalone/releases/download/20200418/cpython-3.8.2-x86_64-unknown-linux-gnu-pgo-20200418T2243.tar.zst".to_string(),
                     sha256: "c7aa51b5deb220e2254a7e32ae7106748d5854b978762f8eb83468c8946dcdbb".to_string(),
                 },
+                python_major_minor_version: "3.8".to_string(),
                 target_triple: "x86_64-unknown-linux-gnu".to_string(),
                 supports_prebuilt_extension_modules: true,
             },
@@ -72,6 +173,7 @@ lazy_static! {
                     url: "https://github.com/indygreg/python-build-standalone/releases/download/20200418/cpython-3.8.2-x86_64-unknown-linux-musl-noopt-20200418T2309.tar.zst".to_string(),
                     sha256: "44d6864e5caafb029f94d6d92e5d33f0d1cbc3cb6b14736b4f526609e3a700da".to_string(),
                 },
+                python_major_minor_version: "3.8".to_string(),
                 target_triple: "x86_64-unknown-linux-musl".to_string(),
                 supports_prebuilt_extension_modules: false,
             },
@@ -88,6 +190,7 @@ lazy_static! {
                     url: "https://github.com/indygreg/python-build-standalone/releases/download/20200418/cpython-3.8.2-i686-pc-windows-msvc-shared-pgo-20200418T2315.tar.zst".to_string(),
                     sha256: "9b449b079cce7837cd60f1d0d4d0bcbf421018f972555e02f5bd4e219a059220".to_string(),
                 },
+                python_major_minor_version: "3.8".to_string(),
                 target_triple: "i686-pc-windows-msvc".to_string(),
                 supports_prebuilt_extension_modules: true,
             },
@@ -96,6 +199,7 @@ lazy_static! {
                     url: "https://github.com/indygreg/python-build-standalone/releases/download/20200418/cpython-3.8.2-x86_64-pc-windows-msvc-shared-pgo-20200418T2315.tar.zst".to_string(),
                     sha256: "022b3630265a05475d554ca97d1d85c2d7270cc0c95fb4af1b2155bec7e5bd5d".to_string(),
                 },
+                python_major_minor_version: "3.8".to_string(),
                 target_triple: "x86_64-pc-windows-msvc".to_string(),
                 supports_prebuilt_extension_modules: true,
             },
@@ -106,6 +210,7 @@ lazy_static! {
                     url: "https://github.com/indygreg/python-build-standalone/releases/download/20200418/cpython-3.8.2-x86_64-apple-darwin-pgo-20200418T2238.tar.zst".to_string(),
                     sha256: "f6e11a18c3fe841a1a45fc3a786ef54c1540c48aa75b6d47ad8d6ae74b44ce1d".to_string(),
                 },
+                python_major_minor_version: "3.8".to_string(),
                 target_triple: "x86_64-apple-darwin".to_string(),
                 supports_prebuilt_extension_modules: true,
             },
@@ -124,3 +229,77 @@ lazy_static! {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_index() -> Result<()> {
+        let index = PythonDistributionCollection::from_index(
+            r#"
+            [[distribution]]
+            target_triple = "x86_64-unknown-linux-gnu"
+            python_version = "3.8"
+            url = "https://mirror.example.com/cpython-3.8.tar.zst"
+            sha256 = "deadbeef"
+
+            [[distribution]]
+            target_triple = "x86_64-unknown-linux-musl"
+            python_version = "3.8"
+            local_path = "dists/cpython-3.8-musl.tar.zst"
+            sha256 = "cafebabe"
+            supports_prebuilt_extension_modules = false
+            "#,
+            Path::new("/config"),
+        )?;
+
+        let dist = index
+            .find_distribution(
+                "x86_64-unknown-linux-musl",
+                &DistributionFlavor::StandaloneStatic,
+                Some("3.8"),
+            )
+            .unwrap();
+        assert_eq!(
+            dist.location,
+            PythonDistributionLocation::Local {
+                local_path: Path::new("/config")
+                    .join("dists/cpython-3.8-musl.tar.zst")
+                    .display()
+                    .to_string(),
+                sha256: "cafebabe".to_string(),
+            }
+        );
+
+        let mut dists = PYTHON_DISTRIBUTIONS.clone();
+        dists.register(&index);
+        assert_eq!(
+            dists
+                .find_distribution(
+                    "x86_64-unknown-linux-gnu",
+                    &DistributionFlavor::Standalone,
+                    None
+                )
+                .unwrap()
+                .location,
+            PythonDistributionLocation::Url {
+                url: "https://mirror.example.com/cpython-3.8.tar.zst".to_string(),
+                sha256: "deadbeef".to_string(),
+            }
+        );
+
+        assert!(PythonDistributionCollection::from_index(
+            r#"
+            [[distribution]]
+            target_triple = "x86_64-unknown-linux-gnu"
+            python_version = "3.8"
+            sha256 = "deadbeef"
+            "#,
+            Path::new("/config"),
+        )
+        .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::env::EnvironmentContext,
+    super::file_resource::FileManifest,
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    super::util::{
+        optional_list_arg, optional_str_arg, required_bool_arg, required_str_arg, required_type_arg,
+    },
+    crate::app_packaging::appimage::{build_appimage, AppImageOptions},
+    crate::app_packaging::desktop_entry::DesktopEntry,
+    crate::app_packaging::resource::FileManifest as RawFileManifest,
+    crate::project_building::build_python_executable,
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::{anyhow, Result},
+    slog::warn,
+    starlark::environment::Environment,
+    starlark::values::{
+        default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult,
+        INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+    },
+    starlark::{
+        any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::any::Any,
+    std::cmp::Ordering,
+};
+
+/// Convert Starlark arguments to a desktop entry.
+///
+/// `Exec` and `Icon` are left for packages to define.
+pub fn starlark_to_desktop_entry(
+    label: &str,
+    name: &Value,
+    categories: &Value,
+    comment: &Value,
+    terminal: &Value,
+) -> Result<DesktopEntry, ValueError> {
+    let name = required_str_arg("name", name)?;
+    optional_list_arg("categories", "string", categories)?;
+    let comment = optional_str_arg("comment", comment)?;
+    let terminal = required_bool_arg("terminal", terminal)?;
+
+    let categories = match categories.get_type() {
+        "list" => categories
+            .into_iter()?
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>(),
+        _ => Vec::new(),
+    };
+
+    for category in &categories {
+        DesktopEntry::validate_category(category).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: label.to_string(),
+            }
+            .into())
+        })?;
+    }
+
+    Ok(DesktopEntry {
+        name,
+        exec: String::new(),
+        icon: None,
+        comment,
+        categories,
+        terminal,
+    })
+}
+
+/// Read an icon file relative to the current directory of the environment.
+///
+/// Returns the extension and the content of the file.
+pub fn starlark_read_icon(
+    env: &Environment,
+    label: &str,
+    path: &Value,
+) -> Result<(String, Vec<u8>), ValueError> {
+    let path = required_str_arg("icon_path", path)?;
+
+    let context = env.get("CONTEXT").expect("CONTEXT not defined");
+    let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
+
+    let path = cwd.join(path);
+
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .filter(|ext| ext == "png" || ext == "svg")
+        .ok_or_else(|| anyhow!("icon must be a .png or .svg file: {}", path.display()));
+
+    extension
+        .and_then(|extension| {
+            std::fs::read(&path)
+                .map(|data| (extension, data))
+                .or_else(|e| Err(anyhow!("reading {}: {}", path.display(), e)))
+        })
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: label.to_string(),
+            }
+            .into())
+        })
+}
+
+/// Represents an AppImage of a Python executable.
+pub struct AppImageBuilder {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub options: AppImageOptions,
+    pub files: RawFileManifest,
+}
+
+impl AppImageBuilder {
+    /// AppImageBuilder.add_files_manifest(manifest)
+    pub fn starlark_add_files_manifest(&mut self, manifest: &Value) -> ValueResult {
+        required_type_arg("manifest", "FileManifest", manifest)?;
+
+        let manifest = manifest.downcast_apply(|m: &FileManifest| m.manifest.clone());
+
+        self.files.add_manifest(&manifest).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_files_manifest()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+}
+
+impl TypedValue for AppImageBuilder {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("AppImageBuilder<{}>", self.options.app_id)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "AppImageBuilder"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "app_id" => Value::new(self.options.app_id.clone()),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "AppImageBuilder".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "app_id" => true,
+            _ => false,
+        })
+    }
+}
+
+impl BuildTarget for AppImageBuilder {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        // Fail before building the executable if the AppImage can't be built.
+        self.options.appimage_filename(&context.target_triple)?;
+
+        let build = build_python_executable(
+            &context.logger,
+            &self.exe.name(),
+            self.exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let mut files = build.binary_data.extra_files.clone();
+        files.add_manifest(&self.files)?;
+
+        let manifest =
+            self.options
+                .to_app_dir_manifest(&build.exe_name, &build.exe_data, &files)?;
+
+        let app_dir = context
+            .output_path
+            .join(format!("{}.AppDir", self.options.app_id));
+        if app_dir.exists() {
+            std::fs::remove_dir_all(&app_dir)?;
+        }
+
+        warn!(&context.logger, "writing AppDir to {}", app_dir.display());
+        manifest.write_to_path(&app_dir)?;
+
+        let appimage_path = build_appimage(
+            &context.logger,
+            &self.options,
+            &app_dir,
+            &context.target_triple,
+            &context.output_path,
+        )?;
+        warn!(
+            &context.logger,
+            "AppImage written to {}",
+            appimage_path.display()
+        );
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::Path {
+                path: appimage_path,
+            },
+            output_path: context.output_path.clone(),
+        })
+    }
+}
+
+starlark_module! { appimage_builder_env =>
+    #[allow(clippy::ptr_arg)]
+    AppImageBuilder.add_files_manifest(this, manifest) {
+        this.downcast_apply_mut(|builder: &mut AppImageBuilder| {
+            builder.starlark_add_files_manifest(&manifest)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*, std::path::Path};
+
+    #[test]
+    fn test_to_appimage_builder() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let icon_path = temp_dir.path().join("myapp.png");
+        std::fs::write(&icon_path, b"png")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+
+        let builder = starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.to_appimage_builder('org.example.myapp', 'My App', '{}', categories=['Development'], comment='Does things')",
+                icon_path.display()
+            ),
+        )
+        .unwrap();
+        assert_eq!(builder.get_type(), "AppImageBuilder");
+
+        builder.downcast_apply(|builder: &AppImageBuilder| {
+            assert_eq!(builder.options.app_id, "org.example.myapp");
+            assert_eq!(builder.options.icon_extension, "png");
+            assert_eq!(builder.options.icon_data, b"png".to_vec());
+            assert_eq!(builder.options.desktop_entry.name, "My App");
+            assert_eq!(
+                builder.options.desktop_entry.categories,
+                vec!["Development".to_string()]
+            );
+            assert_eq!(
+                builder.options.desktop_entry.comment,
+                Some("Does things".to_string())
+            );
+            assert!(!builder.options.desktop_entry.terminal);
+        });
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.to_appimage_builder('my app', 'My App', '{}')",
+                icon_path.display()
+            ),
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.to_appimage_builder('myapp', 'My App', '{}', categories=['A;B'])",
+                icon_path.display()
+            ),
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.to_appimage_builder('myapp', 'My App', '{}')",
+                temp_dir.path().join("missing.png").display()
+            ),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_files_manifest() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let icon_path = temp_dir.path().join("myapp.svg");
+        std::fs::write(&icon_path, b"svg")?;
+        std::fs::create_dir(temp_dir.path().join("data"))?;
+        std::fs::write(temp_dir.path().join("data").join("foo.txt"), b"foo")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "appimage = exe.to_appimage_builder('myapp', 'My App', '{}')",
+                icon_path.display()
+            ),
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "m = glob(['{}/data/*'], strip_prefix='{}/')",
+                temp_dir.path().display(),
+                temp_dir.path().display()
+            ),
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "appimage.add_files_manifest(m)").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "appimage.add_files_manifest(None)").is_err());
+
+        let appimage = starlark_eval_in_env(&mut env, "appimage").unwrap();
+        appimage.downcast_apply(|builder: &AppImageBuilder| {
+            assert_eq!(builder.options.icon_extension, "svg");
+            assert!(builder.files.has_path(Path::new("data/foo.txt")));
+        });
+
+        Ok(())
+    }
+}
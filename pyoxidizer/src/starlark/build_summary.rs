@@ -0,0 +1,108 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::python_executable::PythonExecutable,
+    super::target::{BuildContext, BuildTarget, ResolvedTarget},
+    crate::atomic_output::write_file_atomic,
+    crate::build_manifest::{BuildManifest, SummaryFormat},
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    crate::py_packaging::resource_transform::ResourceTransforms,
+    anyhow::{Context, Result},
+    slog::warn,
+    starlark::values::{default_compare, TypedValue, ValueError},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+};
+
+/// Filename the JSON build manifest is written to.
+pub const BUILD_MANIFEST_FILENAME: &str = "build-manifest.json";
+
+/// Filename the consolidated `RECORD` file is written to.
+pub const RECORD_FILENAME: &str = "RECORD";
+
+/// Represents a Python executable whose build is summarized.
+pub struct BuildSummary {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub format: SummaryFormat,
+    /// Whether to collect `RECORD` files of embedded package distributions.
+    pub include_record: bool,
+}
+
+impl TypedValue for BuildSummary {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        "BuildSummary<>".to_string()
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "BuildSummary"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
+impl BuildTarget for BuildSummary {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let mut exe = PythonExecutable {
+            exe: self.exe.clone_box(),
+            resource_transforms: ResourceTransforms::new(self.exe.python_exe_path()),
+            include_type_stubs: false,
+            type_stubs: Vec::new(),
+        };
+        let resolved = exe.build(context)?;
+
+        let mut manifest =
+            BuildManifest::from_binary_builder(self.exe.as_ref(), &context.target_triple);
+        manifest.add_artifacts_from_directory(
+            &context.output_path,
+            &[
+                BUILD_MANIFEST_FILENAME,
+                RECORD_FILENAME,
+                self.format.filename(),
+            ],
+        )?;
+
+        if self.include_record {
+            manifest.add_package_records(&self.exe.package_records()?);
+
+            let record_path = context.output_path.join(RECORD_FILENAME);
+            write_file_atomic(&record_path, &manifest.to_record(), false)
+                .context(format!("writing {}", record_path.display()))?;
+        }
+
+        let manifest_path = context.output_path.join(BUILD_MANIFEST_FILENAME);
+        write_file_atomic(&manifest_path, manifest.to_json()?.as_bytes(), false)
+            .context(format!("writing {}", manifest_path.display()))?;
+
+        let summary_path = context.output_path.join(self.format.filename());
+        warn!(
+            &context.logger,
+            "writing build summary to {}",
+            summary_path.display()
+        );
+        write_file_atomic(
+            &summary_path,
+            manifest.render_summary(self.format).as_bytes(),
+            false,
+        )
+        .context(format!("writing {}", summary_path.display()))?;
+
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::project_building::{build_python_library, CLibraryKind},
+    crate::project_layout::{c_api_header, c_api_lib_rs},
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::Result,
+    slog::warn,
+    starlark::values::{default_compare, TypedValue, ValueError},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+    std::path::Path,
+};
+
+/// Represents a library embedding Python, with a C API.
+///
+/// The library and a C header declaring its API are written at the root of
+/// the output directory, next to files installed relative to the library.
+pub struct PythonCLibrary {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub name: String,
+    pub kind: CLibraryKind,
+}
+
+impl TypedValue for PythonCLibrary {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("PythonCLibrary<name={}, kind={:?}>", self.name, self.kind)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "PythonCLibrary"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
+impl BuildTarget for PythonCLibrary {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let build = build_python_library(
+            &context.logger,
+            &self.name,
+            self.kind,
+            &c_api_lib_rs()?,
+            self.exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let mut manifest = FileManifest::default();
+        for (filename, data) in build.files {
+            manifest.add_file(
+                Path::new(&filename),
+                &FileContent {
+                    data,
+                    executable: false,
+                },
+            )?;
+        }
+        manifest.add_file(
+            Path::new(&format!("{}.h", self.name)),
+            &FileContent {
+                data: c_api_header(&self.name)?.into_bytes(),
+                executable: false,
+            },
+        )?;
+        manifest.add_manifest(&build.binary_data.extra_files)?;
+
+        warn!(
+            &context.logger,
+            "writing {} library to {}",
+            self.name,
+            context.output_path.display()
+        );
+        manifest.replace_path(&context.output_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: context.output_path.clone(),
+        })
+    }
+}
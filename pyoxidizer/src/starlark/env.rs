@@ -3,16 +3,42 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    super::appimage_builder::AppImageBuilder,
+    super::build_summary::BuildSummary,
+    super::c_library::PythonCLibrary,
+    super::extension_bundle::PythonExtensionBundle,
     super::file_resource::FileManifest,
+    super::hooks::{call_hooks, TargetResult},
+    super::macos_application_bundle_builder::MacOsApplicationBundleBuilder,
+    super::platform::Platform,
+    super::plugin_library::PythonPluginLibrary,
     super::python_embedded_resources::PythonEmbeddedResources,
     super::python_executable::PythonExecutable,
+    super::snap_builder::SnapBuilder,
+    super::standalone_folder::StandaloneFolder,
     super::target::{BuildContext, BuildTarget, ResolvedTarget},
-    super::util::{optional_list_arg, required_bool_arg, required_str_arg, required_type_arg},
+    super::util::{
+        optional_dict_arg, optional_list_arg, optional_str_arg, required_bool_arg,
+        required_dict_arg, required_str_arg, required_type_arg,
+    },
+    super::wix_msi_builder::WiXMSIBuilder,
+    super::zipapp::ZipApp,
+    crate::atomic_output::OutputSnapshot,
+    crate::build_budget::{parse_size, BuildBudget},
+    crate::checksums::{ArtifactSigning, SigningTool},
+    crate::environment::{pip_cache_dir, python_distributions_cache_dir, tools_cache_dir},
+    crate::otlp::record_build_phase,
+    crate::output_retention::{rotate_output, PREVIOUS_OUTPUTS_DIR},
+    crate::python_distributions::{PythonDistributionCollection, PYTHON_DISTRIBUTIONS},
+    crate::webhooks::BuildWebhook,
     anyhow::{anyhow, Context, Result},
     path_dedot::ParseDot,
     slog::warn,
     starlark::environment::{Environment, EnvironmentError},
-    starlark::values::{default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult},
+    starlark::values::{
+        default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult,
+        INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+    },
     starlark::{
         any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
         starlark_signature_extraction, starlark_signatures,
@@ -20,7 +46,9 @@ use {
     std::any::Any,
     std::cmp::Ordering,
     std::collections::{BTreeMap, HashMap},
+    std::convert::TryFrom,
     std::path::{Path, PathBuf},
+    std::time::{Duration, Instant, SystemTime},
 };
 
 /// Represents a registered target in the Starlark environment.
@@ -32,6 +60,22 @@ pub struct Target {
     /// Other targets this one depends on.
     pub depends: Vec<String>,
 
+    /// Keyword arguments passed to the callable.
+    pub parameters: HashMap<String, Value>,
+
+    /// Files and directories the target is built from.
+    ///
+    /// Used to determine whether the target is affected by changes.
+    pub inputs: Vec<PathBuf>,
+
+    /// Source directories read by packaging operations of the callable.
+    ///
+    /// Known once the target is resolved. Used like `inputs`.
+    pub source_paths: Vec<PathBuf>,
+
+    /// Tags used to select the target for building.
+    pub tags: Vec<String>,
+
     /// What calling callable returned, if it has been called.
     pub resolved_value: Option<Value>,
 
@@ -63,6 +107,12 @@ pub struct EnvironmentContext {
     /// Target triple we are building for.
     pub build_target_triple: String,
 
+    /// Platform we are building from.
+    pub build_host: Platform,
+
+    /// Platform we are building for.
+    pub build_target: Platform,
+
     /// Whether we are building a debug or release binary.
     pub build_release: bool,
 
@@ -73,8 +123,29 @@ pub struct EnvironmentContext {
     pub build_path: PathBuf,
 
     /// Path where Python distributions are written.
+    ///
+    /// This is a user-level cache shared by all projects unless the user
+    /// has no cache directory.
     pub python_distributions_path: PathBuf,
 
+    /// Path where tools used by builds, like the WiX Toolset, are cached.
+    ///
+    /// Like `python_distributions_path`, this is a user-level cache.
+    pub tools_path: PathBuf,
+
+    /// Known Python distributions default distributions are chosen from.
+    pub python_distributions: PythonDistributionCollection,
+
+    /// `PythonDistribution` values already obtained, keyed by flavor and location.
+    ///
+    /// Targets obtaining the same distribution, e.g. several executables,
+    /// share a single value, so the distribution is resolved once and
+    /// resources it collects are reused.
+    pub distribution_values: HashMap<String, Value>,
+
+    /// Path where compiled bytecode is cached.
+    pub bytecode_cache_path: PathBuf,
+
     /// Registered build targets.
     ///
     /// A target consists of a name and a Starlark callable.
@@ -96,6 +167,60 @@ pub struct EnvironmentContext {
     ///
     /// This will change the default target to resolve.
     pub build_script_mode: bool,
+
+    /// Whether resources that can't be added as requested are errors.
+    ///
+    /// Otherwise, they are dropped or placed differently, with at most a
+    /// log message.
+    pub strict: bool,
+
+    /// Whether network access is disabled.
+    ///
+    /// Distributions and packages must then be available locally.
+    pub offline: bool,
+
+    /// Directory where pip caches downloaded packages across builds.
+    ///
+    /// If `None`, pip doesn't cache packages.
+    pub pip_cache_path: Option<PathBuf>,
+
+    /// Maximum number of Python processes compiling bytecode in parallel.
+    ///
+    /// Defaults to the number of CPUs.
+    pub bytecode_compiler_jobs: usize,
+
+    /// Whether bytecode is compiled by a libpython loaded in this process.
+    ///
+    /// Otherwise, bytecode is compiled by Python processes.
+    pub bytecode_compiler_in_process: bool,
+
+    /// Callables registered for each hook event.
+    pub hooks: BTreeMap<String, Vec<Value>>,
+
+    /// Number of previous outputs kept for each target.
+    ///
+    /// If `None`, targets are built over their previous output.
+    pub output_retention: Option<usize>,
+
+    /// Whether all previous outputs are kept, regardless of `output_retention`.
+    pub keep_all_outputs: bool,
+
+    /// Packaging operations, such as `pip install`, performed while evaluating.
+    pub packaging_operations: Vec<String>,
+
+    /// Source directories read by packaging operations while evaluating.
+    ///
+    /// e.g. package roots and `setup.py` projects.
+    pub source_paths: Vec<PathBuf>,
+
+    /// Webhook notified of build events, as defined by the config file.
+    pub build_webhook: Option<BuildWebhook>,
+
+    /// Limits of disk space and memory used when building targets.
+    pub build_budget: BuildBudget,
+
+    /// How the checksums of artifacts are signed, if at all.
+    pub artifact_signing: Option<ArtifactSigning>,
 }
 
 impl EnvironmentContext {
@@ -110,6 +235,9 @@ impl EnvironmentContext {
         build_opt_level: &str,
         resolve_targets: Option<Vec<String>>,
         build_script_mode: bool,
+        strict: bool,
+        offline: bool,
+        pip_cache: bool,
     ) -> Result<EnvironmentContext> {
         let parent = config_path
             .parent()
@@ -130,16 +258,39 @@ impl EnvironmentContext {
             config_path: config_path.to_path_buf(),
             build_host_triple: build_host_triple.to_string(),
             build_target_triple: build_target_triple.to_string(),
+            build_host: Platform::from_triple(build_host_triple)?,
+            build_target: Platform::from_triple(build_target_triple)?,
             build_release,
             build_opt_level: build_opt_level.to_string(),
             build_path: build_path.clone(),
-            python_distributions_path: build_path.join("python_distributions"),
+            python_distributions_path: python_distributions_cache_dir(&build_path),
+            tools_path: tools_cache_dir(&build_path),
+            python_distributions: PYTHON_DISTRIBUTIONS.clone(),
+            distribution_values: HashMap::new(),
+            bytecode_cache_path: build_path.join("bytecode_cache"),
             targets: BTreeMap::new(),
             targets_order: Vec::new(),
             default_target: None,
             default_build_script_target: None,
             resolve_targets,
             build_script_mode,
+            strict,
+            offline,
+            pip_cache_path: if pip_cache {
+                Some(pip_cache_dir(&build_path))
+            } else {
+                None
+            },
+            bytecode_compiler_jobs: num_cpus::get(),
+            bytecode_compiler_in_process: false,
+            hooks: BTreeMap::new(),
+            output_retention: None,
+            keep_all_outputs: false,
+            packaging_operations: Vec::new(),
+            source_paths: Vec::new(),
+            build_webhook: None,
+            build_budget: BuildBudget::default(),
+            artifact_signing: None,
         })
     }
 
@@ -152,17 +303,43 @@ impl EnvironmentContext {
         .parse_dot()?;
 
         self.build_path = path.clone();
-        self.python_distributions_path = path.join("python_distributions");
+        self.python_distributions_path = python_distributions_cache_dir(&path);
+        self.tools_path = tools_cache_dir(&path);
+        if self.pip_cache_path.is_some() {
+            self.pip_cache_path = Some(pip_cache_dir(&path));
+        }
+        self.bytecode_cache_path = path.join("bytecode_cache");
+
+        Ok(())
+    }
+
+    /// Register the distributions defined by an index file.
+    ///
+    /// They take precedence over previously known distributions.
+    pub fn register_distributions(&mut self, path: &Path) -> Result<()> {
+        let path = self.cwd.join(path);
+        let data = std::fs::read_to_string(&path).context(format!("reading {}", path.display()))?;
+        let base_path = path
+            .parent()
+            .ok_or_else(|| anyhow!("could not resolve parent of {}", path.display()))?;
+
+        let index = PythonDistributionCollection::from_index(&data, base_path)
+            .context(format!("parsing {}", path.display()))?;
+        self.python_distributions.register(&index);
 
         Ok(())
     }
 
     /// Register a named target.
+    #[allow(clippy::too_many_arguments)]
     pub fn register_target(
         &mut self,
         target: String,
         callable: Value,
         depends: Vec<String>,
+        parameters: HashMap<String, Value>,
+        inputs: Vec<PathBuf>,
+        tags: Vec<String>,
         default: bool,
         default_build_script: bool,
     ) {
@@ -175,6 +352,10 @@ impl EnvironmentContext {
             Target {
                 callable,
                 depends,
+                parameters,
+                inputs,
+                source_paths: Vec::new(),
+                tags,
                 resolved_value: None,
                 built_target: None,
             },
@@ -189,6 +370,19 @@ impl EnvironmentContext {
         }
     }
 
+    /// Obtain the directory outputs of targets are written to.
+    ///
+    /// Each target is written to a subdirectory named after it.
+    pub fn profile_path(&self) -> PathBuf {
+        self.build_path
+            .join(&self.build_target_triple)
+            .join(if self.build_release {
+                "release"
+            } else {
+                "debug"
+            })
+    }
+
     /// Determine what targets should be resolved.
     ///
     /// This isn't the full list of targets that will be resolved, only the main
@@ -205,6 +399,134 @@ impl EnvironmentContext {
         }
     }
 
+    /// Obtain the names of targets having all the specified tags.
+    ///
+    /// Targets are returned in the order they were registered in.
+    pub fn targets_with_tags(&self, tags: &[String]) -> Vec<String> {
+        self.targets_order
+            .iter()
+            .filter(|target| {
+                let target_tags = &self.targets[*target].tags;
+
+                tags.iter().all(|tag| target_tags.contains(tag))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Obtain targets and the targets they depend on, dependencies first.
+    ///
+    /// Targets are otherwise in the order given, each appearing once. Fails
+    /// if a target isn't registered or if dependencies form a cycle.
+    pub fn targets_with_dependencies(&self, targets: &[String]) -> Result<Vec<String>> {
+        fn visit(
+            context: &EnvironmentContext,
+            target: &str,
+            stack: &mut Vec<String>,
+            res: &mut Vec<String>,
+        ) -> Result<()> {
+            if res.iter().any(|t| t == target) {
+                return Ok(());
+            }
+
+            if let Some(index) = stack.iter().position(|t| t == target) {
+                let mut cycle = stack[index..].to_vec();
+                cycle.push(target.to_string());
+
+                return Err(anyhow!(
+                    "targets depend on each other: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+
+            let entry = match context.targets.get(target) {
+                Some(entry) => entry,
+                None => match stack.last() {
+                    Some(parent) => {
+                        return Err(anyhow!(
+                            "target {} depends on target {}, which does not exist",
+                            parent,
+                            target
+                        ))
+                    }
+                    None => return Err(anyhow!("target {} does not exist", target)),
+                },
+            };
+
+            stack.push(target.to_string());
+            for depend in &entry.depends {
+                visit(context, depend, stack, res)?;
+            }
+            stack.pop();
+
+            res.push(target.to_string());
+
+            Ok(())
+        }
+
+        let mut res = Vec::new();
+
+        for target in targets {
+            visit(self, target, &mut Vec::new(), &mut res)?;
+        }
+
+        Ok(res)
+    }
+
+    /// Order targets so those depending on others come after them.
+    ///
+    /// Only the given targets are returned. Targets not depending on each
+    /// other keep their order.
+    pub fn order_targets(&self, targets: &[String]) -> Result<Vec<String>> {
+        Ok(self
+            .targets_with_dependencies(targets)?
+            .into_iter()
+            .filter(|target| targets.contains(target))
+            .collect())
+    }
+
+    /// Describe registered targets, one per line, in registration order.
+    ///
+    /// Lines hold the name of the target, marked with `*` if it is the
+    /// default target, then the targets it depends on and its tags, if any.
+    /// Only registration metadata is described, so targets needn't be resolved.
+    pub fn describe_targets(&self) -> String {
+        let name_width = self
+            .targets_order
+            .iter()
+            .map(|t| t.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut res = String::new();
+
+        for name in &self.targets_order {
+            let target = &self.targets[name];
+            let marker = if self.default_target.as_ref() == Some(name) {
+                "*"
+            } else {
+                " "
+            };
+
+            let mut line = format!("{} {:name_width$}", marker, name, name_width = name_width);
+            if !target.depends.is_empty() {
+                line.push_str(&format!("  depends on: {}", target.depends.join(", ")));
+            }
+            if !target.tags.is_empty() {
+                line.push_str(&format!("  tags: {}", target.tags.join(", ")));
+            }
+
+            res.push_str(line.trim_end());
+            res.push('\n');
+        }
+
+        if let Some(target) = &self.default_target {
+            res.push_str(&format!("\ndefault target: {}\n", target));
+        }
+
+        res
+    }
+
     /// Build a resolved target.
     pub fn build_resolved_target(&mut self, target: &str) -> Result<ResolvedTarget> {
         let resolved_value = if let Some(t) = self.targets.get(target) {
@@ -224,15 +546,54 @@ impl EnvironmentContext {
         let mut raw_value = resolved_value.0.borrow_mut();
         let raw_any = raw_value.as_any_mut();
 
-        let output_path = self
-            .build_path
-            .join(&self.build_target_triple)
-            .join(if self.build_release {
-                "release"
+        let profile_path = self.profile_path();
+        let output_path = profile_path.join(target);
+
+        if self.output_retention.is_some() || self.keep_all_outputs {
+            rotate_output(
+                &self.logger,
+                &output_path,
+                &profile_path.join(PREVIOUS_OUTPUTS_DIR).join(target),
+                if self.keep_all_outputs {
+                    None
+                } else {
+                    self.output_retention
+                },
+            )?;
+        }
+
+        if !self.build_budget.is_empty() {
+            let exe = if raw_any.is::<PythonExecutable>() {
+                Some(&raw_any.downcast_ref::<PythonExecutable>().unwrap().exe)
+            } else if raw_any.is::<PythonCLibrary>() {
+                Some(&raw_any.downcast_ref::<PythonCLibrary>().unwrap().exe)
+            } else if raw_any.is::<PythonPluginLibrary>() {
+                Some(&raw_any.downcast_ref::<PythonPluginLibrary>().unwrap().exe)
+            } else if raw_any.is::<PythonExtensionBundle>() {
+                Some(&raw_any.downcast_ref::<PythonExtensionBundle>().unwrap().exe)
             } else {
-                "debug"
-            })
-            .join(target);
+                None
+            };
+
+            let (disk_needed, memory_needed) = match exe {
+                // Resources are loaded in memory when building the binary.
+                Some(exe) => (exe.estimated_build_size()?, exe.resources_size()?),
+                None => (0, 0),
+            };
+
+            self.build_budget.check_estimate(
+                target,
+                &self.build_path,
+                disk_needed,
+                memory_needed,
+            )?;
+        }
+
+        let snapshot = OutputSnapshot::capture(&output_path)?;
+
+        // Processes such as cargo are killed if the budget is exceeded while
+        // they run.
+        let budget_guard = self.build_budget.enforce(target, &self.build_path);
 
         std::fs::create_dir_all(&output_path).context("creating output path")?;
 
@@ -243,9 +604,14 @@ impl EnvironmentContext {
             release: self.build_release,
             opt_level: self.build_opt_level.clone(),
             output_path,
+            bytecode_compiler_jobs: self.bytecode_compiler_jobs,
+            bytecode_compiler_in_process: self.bytecode_compiler_in_process,
+            bytecode_cache_path: self.bytecode_cache_path.clone(),
+            offline: self.offline,
+            tools_path: self.tools_path.clone(),
         };
 
-        let resolved_target: ResolvedTarget = if raw_any.is::<FileManifest>() {
+        let resolved_target = if raw_any.is::<FileManifest>() {
             raw_any
                 .downcast_mut::<FileManifest>()
                 .unwrap()
@@ -260,9 +626,75 @@ impl EnvironmentContext {
                 .downcast_mut::<PythonEmbeddedResources>()
                 .unwrap()
                 .build(&context)
+        } else if raw_any.is::<BuildSummary>() {
+            raw_any
+                .downcast_mut::<BuildSummary>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<PythonCLibrary>() {
+            raw_any
+                .downcast_mut::<PythonCLibrary>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<PythonExtensionBundle>() {
+            raw_any
+                .downcast_mut::<PythonExtensionBundle>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<PythonPluginLibrary>() {
+            raw_any
+                .downcast_mut::<PythonPluginLibrary>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<StandaloneFolder>() {
+            raw_any
+                .downcast_mut::<StandaloneFolder>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<WiXMSIBuilder>() {
+            raw_any
+                .downcast_mut::<WiXMSIBuilder>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<MacOsApplicationBundleBuilder>() {
+            raw_any
+                .downcast_mut::<MacOsApplicationBundleBuilder>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<AppImageBuilder>() {
+            raw_any
+                .downcast_mut::<AppImageBuilder>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<SnapBuilder>() {
+            raw_any
+                .downcast_mut::<SnapBuilder>()
+                .unwrap()
+                .build(&context)
+        } else if raw_any.is::<ZipApp>() {
+            raw_any.downcast_mut::<ZipApp>().unwrap().build(&context)
         } else {
             Err(anyhow!("could not determine type of target"))
-        }?;
+        };
+
+        let resolved_target = resolved_target
+            .and_then(|resolved_target| budget_guard.check().map(|_| resolved_target));
+        drop(budget_guard);
+
+        // Don't leave artifacts of a failed build behind for later builds.
+        let resolved_target = match resolved_target {
+            Ok(resolved_target) => resolved_target,
+            Err(e) => {
+                if let Err(rollback_error) = snapshot.rollback(&self.logger) {
+                    warn!(
+                        &self.logger,
+                        "error rolling back output of {}: {}", target, rollback_error
+                    );
+                }
+
+                return Err(e);
+            }
+        };
 
         self.targets.get_mut(target).unwrap().built_target = Some(resolved_target.clone());
 
@@ -334,12 +766,40 @@ impl TypedValue for EnvironmentContext {
     }
 }
 
-/// register_target(target, callable, depends=None, default=false)
+/// Resolve the `inputs` argument of target registration functions.
+fn inputs_arg(env: &Environment, inputs: &Value) -> Result<Vec<PathBuf>, ValueError> {
+    optional_list_arg("inputs", "string", &inputs)?;
+
+    let cwd = env.get("CWD").expect("CWD not defined").to_string();
+
+    Ok(match inputs.get_type() {
+        "list" => inputs
+            .into_iter()?
+            .map(|x| Path::new(&cwd).join(x.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// Resolve the `tags` argument of target registration functions.
+fn tags_arg(tags: &Value) -> Result<Vec<String>, ValueError> {
+    optional_list_arg("tags", "string", &tags)?;
+
+    Ok(match tags.get_type() {
+        "list" => tags.into_iter()?.map(|x| x.to_string()).collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// register_target(target, callable, depends=None, inputs=None, tags=None, default=false)
+#[allow(clippy::too_many_arguments)]
 fn starlark_register_target(
     env: &Environment,
     target: &Value,
     callable: &Value,
     depends: &Value,
+    inputs: &Value,
+    tags: &Value,
     default: &Value,
     default_build_script: &Value,
 ) -> ValueResult {
@@ -348,6 +808,8 @@ fn starlark_register_target(
     optional_list_arg("depends", "string", &depends)?;
     let default = required_bool_arg("default", &default)?;
     let default_build_script = required_bool_arg("default_build_script", &default_build_script)?;
+    let inputs = inputs_arg(env, inputs)?;
+    let tags = tags_arg(tags)?;
 
     let depends = match depends.get_type() {
         "list" => depends
@@ -365,6 +827,9 @@ fn starlark_register_target(
             target.clone(),
             callable.clone(),
             depends.clone(),
+            HashMap::new(),
+            inputs.clone(),
+            tags.clone(),
             default,
             default_build_script,
         )
@@ -373,6 +838,144 @@ fn starlark_register_target(
     Ok(Value::new(None))
 }
 
+/// Obtain the name of a target expanded from a matrix value.
+fn matrix_target_name_part(value: &Value) -> String {
+    value
+        .to_str()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// foreach_target(name, callable, matrix, depends=None, inputs=None, tags=None)
+///
+/// Registers a target for each combination of values in `matrix`. Targets are
+/// named after `name` and the values of the combination. The callable receives
+/// the values of the combination as keyword arguments.
+fn starlark_foreach_target(
+    env: &Environment,
+    name: &Value,
+    callable: &Value,
+    matrix: &Value,
+    depends: &Value,
+    inputs: &Value,
+    tags: &Value,
+) -> ValueResult {
+    let name = required_str_arg("name", &name)?;
+    required_type_arg("callable", "function", &callable)?;
+    required_dict_arg("matrix", "string", "list", &matrix)?;
+    optional_list_arg("depends", "string", &depends)?;
+    let inputs = inputs_arg(env, inputs)?;
+    let tags = tags_arg(tags)?;
+
+    let depends: Vec<String> = match depends.get_type() {
+        "list" => depends.into_iter()?.map(|x| x.to_string()).collect(),
+        _ => Vec::new(),
+    };
+
+    let mut combinations: Vec<Vec<(String, Value)>> = vec![vec![]];
+
+    for key in matrix.into_iter()? {
+        let values = matrix.at(key.clone())?.into_iter()?.collect::<Vec<_>>();
+
+        if values.is_empty() {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("matrix values of {} cannot be empty", key.to_str()),
+                label: "foreach_target()".to_string(),
+            }
+            .into());
+        }
+
+        let mut expanded = Vec::new();
+        for combination in &combinations {
+            for value in &values {
+                let mut combination = combination.clone();
+                combination.push((key.to_str(), value.clone()));
+                expanded.push(combination);
+            }
+        }
+        combinations = expanded;
+    }
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    let names = combinations
+        .iter()
+        .map(|combination| {
+            std::iter::once(name.clone())
+                .chain(
+                    combination
+                        .iter()
+                        .map(|(_, value)| matrix_target_name_part(value)),
+                )
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect::<Vec<_>>();
+
+    // Check names before registering any target, so a failed call doesn't
+    // leave some of the targets registered.
+    for (i, target) in names.iter().enumerate() {
+        let message = if names[..i].contains(target) {
+            format!("matrix expands to target {} more than once", target)
+        } else if context.downcast_apply(|x: &EnvironmentContext| x.targets.contains_key(target)) {
+            format!("target {} is already registered", target)
+        } else {
+            continue;
+        };
+
+        return Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message,
+            label: "foreach_target()".to_string(),
+        }
+        .into());
+    }
+
+    for (target, combination) in names.iter().zip(combinations) {
+        // Dependencies and tags can refer to values of the combination, e.g.
+        // `dist-{python_version}`.
+        let expand = |values: &[String]| {
+            values
+                .iter()
+                .map(|s| {
+                    combination.iter().fold(s.clone(), |s, (key, value)| {
+                        s.replace(&format!("{{{}}}", key), &matrix_target_name_part(value))
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let target_depends = expand(&depends);
+        let target_tags = expand(&tags);
+
+        let parameters = combination.into_iter().collect::<HashMap<_, _>>();
+
+        context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+            x.register_target(
+                target.clone(),
+                callable.clone(),
+                target_depends.clone(),
+                parameters.clone(),
+                inputs.clone(),
+                target_tags.clone(),
+                false,
+                false,
+            )
+        });
+    }
+
+    Ok(Value::from(
+        names.into_iter().map(Value::new).collect::<Vec<Value>>(),
+    ))
+}
+
 /// resolve_target(target)
 ///
 /// This will return a Value returned from the called function.
@@ -414,6 +1017,18 @@ fn starlark_resolve_target(
         return Ok(v);
     }
 
+    // Dependency cycles would otherwise recurse endlessly.
+    context
+        .downcast_apply(|x: &EnvironmentContext| x.targets_with_dependencies(&[target.clone()]))
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_target()".to_string(),
+            }
+            .into())
+        })?;
+
     let target_entry = context.downcast_apply(|x: &EnvironmentContext| {
         warn!(&x.logger, "resolving target {}", target);
 
@@ -436,17 +1051,54 @@ fn starlark_resolve_target(
         args.push(starlark_resolve_target(env, call_stack, &depend_target)?);
     }
 
-    let res =
-        target_entry
-            .callable
-            .call(call_stack, env.clone(), args, HashMap::new(), None, None)?;
+    call_hooks(
+        env,
+        call_stack,
+        "pre_target",
+        vec![Value::new(target.clone())],
+    )?;
+
+    // Source paths recorded from now on are read by this target.
+    let source_paths_start = context.downcast_apply(|x: &EnvironmentContext| x.source_paths.len());
+
+    let start = Instant::now();
+    let phase_start = SystemTime::now();
+    let res = target_entry.callable.call(
+        call_stack,
+        env.clone(),
+        args,
+        target_entry.parameters,
+        None,
+        None,
+    );
+    record_build_phase(
+        "resolve",
+        phase_start,
+        &[("pyoxidizer.target", &target)],
+        res.as_ref().err().map(|e| format!("{:?}", e)),
+    );
+
+    let result = TargetResult {
+        target: target.clone(),
+        success: res.is_ok(),
+        duration: start.elapsed(),
+        artifacts: Vec::new(),
+    };
+    let hooks_res = call_hooks(env, call_stack, "post_target", vec![Value::new(result)]);
+
+    // Errors of the target take precedence over errors of hooks.
+    let res = res?;
+    hooks_res?;
 
     // TODO consider replacing the target's callable with a new function that returns the
     // resolved value. This will ensure a target function is only ever called once.
 
     context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        let source_paths = x.source_paths[source_paths_start..].to_vec();
+
         if let Some(target_entry) = x.targets.get_mut(&target) {
             target_entry.resolved_value = Some(res.clone());
+            target_entry.source_paths = source_paths;
         }
     });
 
@@ -458,8 +1110,18 @@ fn starlark_resolve_target(
 fn starlark_resolve_targets(env: &Environment, call_stack: &Vec<(String, String)>) -> ValueResult {
     let context = env.get("CONTEXT").expect("CONTEXT not set");
 
-    let targets =
-        context.downcast_apply(|context: &EnvironmentContext| context.targets_to_resolve());
+    let targets = context
+        .downcast_apply(|context: &EnvironmentContext| {
+            context.order_targets(&context.targets_to_resolve())
+        })
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_targets()".to_string(),
+            }
+            .into())
+        })?;
 
     println!("resolving {} targets", targets.len());
     for target in targets {
@@ -478,6 +1140,32 @@ fn starlark_resolve_targets(env: &Environment, call_stack: &Vec<(String, String)
     Ok(Value::new(None))
 }
 
+/// depends_on(target)
+fn starlark_depends_on(env: &Environment, target: &Value) -> ValueResult {
+    let target = required_str_arg("target", &target)?;
+
+    let context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    let targets = context
+        .downcast_apply(|x: &EnvironmentContext| x.targets_with_dependencies(&[target.clone()]))
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "depends_on()".to_string(),
+            }
+            .into())
+        })?;
+
+    Ok(Value::from(
+        targets
+            .into_iter()
+            .filter(|t| t != &target)
+            .map(Value::new)
+            .collect::<Vec<Value>>(),
+    ))
+}
+
 /// set_build_path(path)
 fn starlark_set_build_path(env: &Environment, path: &Value) -> ValueResult {
     let path = required_str_arg("path", &path)?;
@@ -497,50 +1185,350 @@ fn starlark_set_build_path(env: &Environment, path: &Value) -> ValueResult {
     Ok(Value::new(None))
 }
 
-starlark_module! { global_module =>
-    #[allow(clippy::ptr_arg)]
-    register_target(
-        env env,
-        target,
-        callable,
-        depends=None,
-        default=false,
-        default_build_script=false
-    ) {
-        starlark_register_target(
-            &env,
-            &target,
-            &callable,
-            &depends,
-            &default,
-            &default_build_script,
-        )
-    }
-
-    #[allow(clippy::ptr_arg)]
-    resolve_target(env env, call_stack cs, target) {
-        starlark_resolve_target(&env, &cs, &target)
-    }
-
-    #[allow(clippy::ptr_arg)]
-    resolve_targets(env env, call_stack cs) {
-        starlark_resolve_targets(&env, &cs)
-    }
+/// register_distributions(path)
+fn starlark_register_distributions(env: &Environment, path: &Value) -> ValueResult {
+    let path = required_str_arg("path", &path)?;
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context
+        .downcast_apply_mut(|x: &mut EnvironmentContext| {
+            x.register_distributions(&PathBuf::from(&path))
+        })
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: format!("{:#}", e),
+                label: "register_distributions()".to_string(),
+            }
+            .into())
+        })?;
+
+    Ok(Value::new(None))
+}
+
+/// set_strict(strict)
+fn starlark_set_strict(env: &Environment, strict: &Value) -> ValueResult {
+    let strict = required_bool_arg("strict", &strict)?;
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| x.strict = strict);
+
+    Ok(Value::new(None))
+}
+
+/// set_bytecode_compiler_jobs(jobs)
+fn starlark_set_bytecode_compiler_jobs(env: &Environment, jobs: &Value) -> ValueResult {
+    required_type_arg("jobs", "int", &jobs)?;
+
+    let jobs = match jobs.to_int().unwrap() {
+        jobs if jobs >= 1 => jobs as usize,
+        jobs => {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("jobs must be at least 1: got {}", jobs),
+                label: "set_bytecode_compiler_jobs()".to_string(),
+            }
+            .into())
+        }
+    };
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| x.bytecode_compiler_jobs = jobs);
+
+    Ok(Value::new(None))
+}
+
+/// set_output_retention(keep)
+fn starlark_set_output_retention(env: &Environment, keep: &Value) -> ValueResult {
+    required_type_arg("keep", "int", &keep)?;
+
+    let keep = match keep.to_int().unwrap() {
+        keep if keep >= 0 => keep as usize,
+        keep => {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("keep must be at least 0: got {}", keep),
+                label: "set_output_retention()".to_string(),
+            }
+            .into())
+        }
+    };
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| x.output_retention = Some(keep));
+
+    Ok(Value::new(None))
+}
+
+/// Obtain a size in bytes from a Starlark value, either an int or a string like `2G`.
+fn optional_size_arg(
+    label: &str,
+    arg_name: &str,
+    value: &Value,
+) -> Result<Option<u64>, ValueError> {
+    let size = match value.get_type() {
+        "NoneType" => return Ok(None),
+        "int" => match value.to_int().unwrap() {
+            size if size >= 0 => Ok(size as u64),
+            size => Err(format!("{} must be at least 0: got {}", arg_name, size)),
+        },
+        "string" => parse_size(&value.to_string()).map_err(|e| e.to_string()),
+        t => Err(format!(
+            "{} must be an int or a string: got {}",
+            arg_name, t
+        )),
+    };
+
+    match size {
+        Ok(size) => Ok(Some(size)),
+        Err(message) => Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message,
+            label: label.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// set_build_budget(disk_usage=None, memory=None, time=None)
+fn starlark_set_build_budget(
+    env: &Environment,
+    disk_usage: &Value,
+    memory: &Value,
+    time: &Value,
+) -> ValueResult {
+    let time = match time.get_type() {
+        "NoneType" => None,
+        "int" => match time.to_int().unwrap() {
+            seconds if seconds > 0 => Some(Duration::from_secs(seconds as u64)),
+            seconds => {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!("time must be at least 1: got {}", seconds),
+                    label: "set_build_budget()".to_string(),
+                }
+                .into())
+            }
+        },
+        t => {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("time must be an int: got {}", t),
+                label: "set_build_budget()".to_string(),
+            }
+            .into())
+        }
+    };
+
+    let budget = BuildBudget {
+        disk_usage: optional_size_arg("set_build_budget()", "disk_usage", &disk_usage)?,
+        memory: optional_size_arg("set_build_budget()", "memory", &memory)?,
+        time,
+    };
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| x.build_budget = budget);
+
+    Ok(Value::new(None))
+}
+
+/// set_build_webhook(url, headers=None)
+fn starlark_set_build_webhook(env: &Environment, url: &Value, headers: &Value) -> ValueResult {
+    let url = required_str_arg("url", &url)?;
+    optional_dict_arg("headers", "string", "string", &headers)?;
+
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!("url must be an http:// or https:// URL: got {}", url),
+            label: "set_build_webhook()".to_string(),
+        }
+        .into());
+    }
+
+    let headers = match headers.get_type() {
+        "dict" => headers
+            .into_iter()?
+            .map(|key| {
+                let value = headers.at(key.clone()).unwrap().to_string();
+                (key.to_string(), value)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        x.build_webhook = Some(BuildWebhook { url, headers })
+    });
+
+    Ok(Value::new(None))
+}
+
+/// set_artifact_signing(tool, key=None)
+fn starlark_set_artifact_signing(env: &Environment, tool: &Value, key: &Value) -> ValueResult {
+    let tool = required_str_arg("tool", &tool)?;
+    let key = optional_str_arg("key", &key)?;
+
+    let tool = SigningTool::try_from(tool.as_str()).or_else(|message| {
+        Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message,
+            label: "set_artifact_signing()".to_string(),
+        }
+        .into())
+    })?;
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        x.artifact_signing = Some(ArtifactSigning { tool, key })
+    });
+
+    Ok(Value::new(None))
+}
+
+/// set_bytecode_compiler_backend(backend)
+fn starlark_set_bytecode_compiler_backend(env: &Environment, backend: &Value) -> ValueResult {
+    let backend = required_str_arg("backend", &backend)?;
+
+    let in_process = match backend.as_str() {
+        "process" => false,
+        "in-process" => true,
+        _ => {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("backend must be 'process' or 'in-process': got {}", backend),
+                label: "set_bytecode_compiler_backend()".to_string(),
+            }
+            .into())
+        }
+    };
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        x.bytecode_compiler_in_process = in_process
+    });
+
+    Ok(Value::new(None))
+}
+
+starlark_module! { global_module =>
+    #[allow(clippy::ptr_arg)]
+    register_target(
+        env env,
+        target,
+        callable,
+        depends=None,
+        inputs=None,
+        tags=None,
+        default=false,
+        default_build_script=false
+    ) {
+        starlark_register_target(
+            &env,
+            &target,
+            &callable,
+            &depends,
+            &inputs,
+            &tags,
+            &default,
+            &default_build_script,
+        )
+    }
+
+    #[allow(clippy::ptr_arg)]
+    foreach_target(
+        env env,
+        name,
+        callable,
+        matrix,
+        depends=None,
+        inputs=None,
+        tags=None
+    ) {
+        starlark_foreach_target(&env, &name, &callable, &matrix, &depends, &inputs, &tags)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    resolve_target(env env, call_stack cs, target) {
+        starlark_resolve_target(&env, &cs, &target)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    resolve_targets(env env, call_stack cs) {
+        starlark_resolve_targets(&env, &cs)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    depends_on(env env, target) {
+        starlark_depends_on(&env, &target)
+    }
 
     #[allow(clippy::ptr_arg)]
     set_build_path(env env, path) {
         starlark_set_build_path(&env, &path)
     }
+
+    #[allow(clippy::ptr_arg)]
+    register_distributions(env env, path) {
+        starlark_register_distributions(&env, &path)
+    }
+
+    set_strict(env env, strict) {
+        starlark_set_strict(&env, &strict)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    set_bytecode_compiler_jobs(env env, jobs) {
+        starlark_set_bytecode_compiler_jobs(&env, &jobs)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    set_bytecode_compiler_backend(env env, backend) {
+        starlark_set_bytecode_compiler_backend(&env, &backend)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    set_output_retention(env env, keep) {
+        starlark_set_output_retention(&env, &keep)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    set_build_webhook(env env, url, headers=None) {
+        starlark_set_build_webhook(&env, &url, &headers)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    set_build_budget(env env, disk_usage=None, memory=None, time=None) {
+        starlark_set_build_budget(&env, &disk_usage, &memory, &time)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    set_artifact_signing(env env, tool, key=None) {
+        starlark_set_artifact_signing(&env, &tool, &key)
+    }
 }
 
 /// Obtain a Starlark environment for evaluating PyOxidizer configurations.
 pub fn global_environment(context: &EnvironmentContext) -> Result<Environment, EnvironmentError> {
     let env = starlark::stdlib::global_environment();
     let env = global_module(env);
+    let env = super::hooks::hooks_module(env);
     let env = super::file_resource::file_resource_env(env);
     let env = super::python_distribution::python_distribution_module(env);
     let env = super::python_executable::python_executable_env(env);
     let env = super::python_interpreter_config::embedded_python_config_module(env);
+    let env = super::wix_msi_builder::wix_msi_builder_env(env);
+    let env = super::macos_application_bundle_builder::macos_application_bundle_builder_env(env);
+    let env = super::appimage_builder::appimage_builder_env(env);
+    let env = super::snap_builder::snap_builder_env(env);
+    let env = super::platform::platform_module(env);
 
     env.set("CONTEXT", Value::new(context.clone()))?;
 
@@ -553,6 +1541,8 @@ pub fn global_environment(context: &EnvironmentContext) -> Result<Environment, E
         "BUILD_TARGET_TRIPLE",
         Value::from(context.build_target_triple.clone()),
     )?;
+    env.set("BUILD_HOST", Value::new(context.build_host.clone()))?;
+    env.set("BUILD_TARGET", Value::new(context.build_target.clone()))?;
 
     Ok(env)
 }
@@ -618,4 +1608,521 @@ pub mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_target_dependencies() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def foo(*args): return len(args)").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('a', foo)").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('b', foo, depends=['a'])").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('c', foo, depends=['b', 'a'])").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('d', foo)").unwrap();
+
+        let context = env.get("CONTEXT").unwrap();
+
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(
+                x.targets_with_dependencies(&["c".to_string()]).unwrap(),
+                vec!["a".to_string(), "b".to_string(), "c".to_string()]
+            );
+            assert_eq!(
+                x.order_targets(&["c".to_string(), "d".to_string(), "a".to_string()])
+                    .unwrap(),
+                vec!["a".to_string(), "c".to_string(), "d".to_string()]
+            );
+        });
+
+        let depends = starlark_eval_in_env(&mut env, "depends_on('c')").unwrap();
+        assert_eq!(
+            depends
+                .into_iter()
+                .unwrap()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>(),
+            vec!["a".to_string(), "b".to_string()]
+        );
+
+        let value = starlark_eval_in_env(&mut env, "resolve_target('c')").unwrap();
+        assert_eq!(value.to_int().unwrap(), 2);
+
+        starlark_eval_in_env(&mut env, "register_target('x', foo, depends=['y'])").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('y', foo, depends=['x'])").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('z', foo, depends=['missing'])").unwrap();
+
+        let err = starlark_eval_in_env(&mut env, "resolve_target('x')").unwrap_err();
+        assert_eq!(err.message, "targets depend on each other: x -> y -> x");
+
+        let err = starlark_eval_in_env(&mut env, "depends_on('z')").unwrap_err();
+        assert_eq!(
+            err.message,
+            "target z depends on target missing, which does not exist"
+        );
+    }
+
+    #[test]
+    fn test_describe_targets() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def dist(): return default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "def files(dist): return FileManifest()").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('dist', dist)").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "register_target('install', files, depends=['dist'], default=True, tags=['app'])",
+        )
+        .unwrap();
+
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.describe_targets()),
+            [
+                "  dist",
+                "* install  depends on: dist  tags: app",
+                "",
+                "default target: install",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_register_target_inputs() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def foo(): pass").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "register_target('foo', foo, inputs=['src', 'requirements.txt'])",
+        )
+        .unwrap();
+
+        let cwd = std::env::current_dir().unwrap();
+        let context = env.get("CONTEXT").unwrap();
+
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(
+                x.targets.get("foo").unwrap().inputs,
+                vec![cwd.join("src"), cwd.join("requirements.txt")]
+            );
+        });
+
+        assert!(
+            starlark_eval_in_env(&mut env, "register_target('bar', foo, inputs='src')").is_err()
+        );
+    }
+
+    #[test]
+    fn test_target_source_paths() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+        std::fs::write(root.join("foo.py"), "# foo")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "def packages(): return dist.read_package_root('{}', packages=['foo'])",
+                root.display()
+            ),
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "def app(packages): return packages").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('packages', packages)").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "register_target('app', app, depends=['packages'])",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "resolve_target('app')").unwrap();
+
+        let context = env.get("CONTEXT").unwrap();
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(x.source_paths, vec![root.to_path_buf()]);
+            assert_eq!(
+                x.targets.get("packages").unwrap().source_paths,
+                vec![root.to_path_buf()]
+            );
+            assert!(x.targets.get("app").unwrap().source_paths.is_empty());
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_target_tags() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def foo(): pass").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "register_target('a', foo, tags=['release', 'linux'])",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "register_target('b', foo, tags=['release'])").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('c', foo)").unwrap();
+
+        let context = env.get("CONTEXT").unwrap();
+
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(
+                x.targets.get("a").unwrap().tags,
+                vec!["release".to_string(), "linux".to_string()]
+            );
+            assert_eq!(
+                x.targets_with_tags(&["release".to_string()]),
+                vec!["a".to_string(), "b".to_string()]
+            );
+            assert_eq!(
+                x.targets_with_tags(&["release".to_string(), "linux".to_string()]),
+                vec!["a".to_string()]
+            );
+            assert!(x.targets_with_tags(&["windows".to_string()]).is_empty());
+        });
+
+        assert!(starlark_eval_in_env(&mut env, "register_target('d', foo, tags=[1])").is_err());
+    }
+
+    #[test]
+    fn test_foreach_target() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def dist(python_version): return python_version").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "def exe(dist, python_version, target_triple): \
+             return dist + ' ' + target_triple",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "foreach_target('dist', dist, {'python_version': ['3.8']})",
+        )
+        .unwrap();
+
+        let names = starlark_eval_in_env(
+            &mut env,
+            "foreach_target('exe', exe, {\
+             'python_version': ['3.8'], \
+             'target_triple': ['x86_64-unknown-linux-gnu', 'x86_64-pc-windows-msvc'], \
+             }, depends=['dist-{python_version}'], tags=['{target_triple}'])",
+        )
+        .unwrap();
+        assert_eq!(
+            names.to_str(),
+            "[\"exe-3.8-x86_64-unknown-linux-gnu\", \"exe-3.8-x86_64-pc-windows-msvc\"]"
+        );
+
+        let context = env.get("CONTEXT").unwrap();
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(x.targets.len(), 3);
+            assert_eq!(x.default_target, Some("dist-3.8".to_string()));
+            assert_eq!(
+                &x.targets
+                    .get("exe-3.8-x86_64-pc-windows-msvc")
+                    .unwrap()
+                    .depends,
+                &vec!["dist-3.8".to_string()],
+            );
+            assert_eq!(
+                x.targets_with_tags(&["x86_64-pc-windows-msvc".to_string()]),
+                vec!["exe-3.8-x86_64-pc-windows-msvc".to_string()]
+            );
+        });
+
+        let value =
+            starlark_eval_in_env(&mut env, "resolve_target('exe-3.8-x86_64-pc-windows-msvc')")
+                .unwrap();
+        assert_eq!(value.to_str(), "3.8 x86_64-pc-windows-msvc");
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "foreach_target('exe', exe, {'python_version': []})"
+        )
+        .is_err());
+
+        // Generated names can't collide with each other or registered targets.
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "foreach_target('exe', exe, {'python_version': ['3.8'], \
+             'target_triple': ['a b', 'a_b']})"
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "foreach_target('dist', dist, {'python_version': ['3.8', '3.9']})"
+        )
+        .is_err());
+        let context = env.get("CONTEXT").unwrap();
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(x.targets.len(), 3);
+        });
+    }
+
+    #[test]
+    fn test_set_strict() {
+        let mut env = starlark_env();
+        let context = env.get("CONTEXT").unwrap();
+        assert!(!context.downcast_apply(|x: &EnvironmentContext| x.strict));
+
+        starlark_eval_in_env(&mut env, "set_strict(True)").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert!(context.downcast_apply(|x: &EnvironmentContext| x.strict));
+
+        assert!(starlark_eval_in_env(&mut env, "set_strict('yes')").is_err());
+    }
+
+    #[test]
+    fn test_set_bytecode_compiler_jobs() {
+        let mut env = starlark_env();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.bytecode_compiler_jobs),
+            num_cpus::get()
+        );
+
+        starlark_eval_in_env(&mut env, "set_bytecode_compiler_jobs(3)").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.bytecode_compiler_jobs),
+            3
+        );
+
+        assert!(starlark_eval_in_env(&mut env, "set_bytecode_compiler_jobs(0)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_bytecode_compiler_jobs('2')").is_err());
+    }
+
+    #[test]
+    fn test_set_output_retention() {
+        let mut env = starlark_env();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.output_retention),
+            None
+        );
+
+        starlark_eval_in_env(&mut env, "set_output_retention(2)").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.output_retention),
+            Some(2)
+        );
+
+        starlark_eval_in_env(&mut env, "set_output_retention(0)").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "set_output_retention(-1)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_output_retention(None)").is_err());
+    }
+
+    #[test]
+    fn test_set_build_budget() {
+        let mut env = starlark_env();
+        let context = env.get("CONTEXT").unwrap();
+        assert!(context.downcast_apply(|x: &EnvironmentContext| x.build_budget.is_empty()));
+
+        starlark_eval_in_env(&mut env, "set_build_budget(disk_usage='2G', memory=1024)").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.build_budget.clone()),
+            BuildBudget {
+                disk_usage: Some(2 << 30),
+                memory: Some(1024),
+                time: None,
+            }
+        );
+
+        starlark_eval_in_env(&mut env, "set_build_budget(memory='512M', time=600)").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.build_budget.clone()),
+            BuildBudget {
+                disk_usage: None,
+                memory: Some(512 << 20),
+                time: Some(Duration::from_secs(600)),
+            }
+        );
+
+        assert!(starlark_eval_in_env(&mut env, "set_build_budget(disk_usage='lots')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_build_budget(memory=-1)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_build_budget(memory=[])").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_build_budget(time=0)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_build_budget(time='1h')").is_err());
+    }
+
+    #[test]
+    fn test_set_build_webhook() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(
+            &mut env,
+            "set_build_webhook('https://example.com/hook', headers={'Authorization': 'Bearer token'})",
+        )
+        .unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.build_webhook.clone()),
+            Some(BuildWebhook {
+                url: "https://example.com/hook".to_string(),
+                headers: vec![("Authorization".to_string(), "Bearer token".to_string())],
+            })
+        );
+
+        assert!(starlark_eval_in_env(&mut env, "set_build_webhook('example.com')").is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "set_build_webhook('https://example.com', headers={'a': 1})"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_artifact_signing() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(
+            &mut env,
+            "set_artifact_signing('gpg', key='release@example.com')",
+        )
+        .unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert_eq!(
+            context.downcast_apply(|x: &EnvironmentContext| x.artifact_signing.clone()),
+            Some(ArtifactSigning {
+                tool: SigningTool::Gpg,
+                key: Some("release@example.com".to_string()),
+            })
+        );
+
+        assert!(starlark_eval_in_env(&mut env, "set_artifact_signing('signify')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_artifact_signing('gpg', key=1)").is_err());
+    }
+
+    #[test]
+    fn test_failed_build_rollback() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let build_path = temp_dir.path().join("build");
+        let source_path = temp_dir.path().join("source");
+        std::fs::create_dir(&source_path)?;
+        std::fs::write(source_path.join("a"), b"a")?;
+        std::fs::write(source_path.join("b"), b"b")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(
+            &mut env,
+            &format!("set_build_path({:?})", build_path.display().to_string()),
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "def make():\n    m = FileManifest()\n    m.add_directory({:?})\n    return m\n",
+                source_path.display().to_string()
+            ),
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "register_target('files', make)").unwrap();
+        starlark_eval_in_env(&mut env, "resolve_target('files')").unwrap();
+
+        let context = env.get("CONTEXT").unwrap();
+        let output_path = context.downcast_apply(|x: &EnvironmentContext| {
+            x.build_path
+                .join(&x.build_target_triple)
+                .join("debug")
+                .join("files")
+        });
+        std::fs::create_dir_all(&output_path)?;
+        std::fs::write(output_path.join("previous"), b"previous")?;
+
+        // Interrupt the build while writing each file, then while swapping
+        // the output directory.
+        for count in 0..3 {
+            crate::atomic_output::inject_failure(Some(count));
+            let mut context = env.get("CONTEXT").unwrap();
+            assert!(context
+                .downcast_apply_mut(|x: &mut EnvironmentContext| x.build_resolved_target("files"))
+                .is_err());
+
+            let entries = std::fs::read_dir(output_path.parent().unwrap())?
+                .map(|entry| entry.map(|entry| entry.file_name()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            assert_eq!(entries, vec![std::ffi::OsString::from("files")]);
+            assert_eq!(std::fs::read(output_path.join("previous"))?, b"previous");
+            assert_eq!(std::fs::read_dir(&output_path)?.count(), 1);
+        }
+
+        let mut context = env.get("CONTEXT").unwrap();
+        context
+            .downcast_apply_mut(|x: &mut EnvironmentContext| x.build_resolved_target("files"))?;
+        assert!(!output_path.join("previous").exists());
+        assert_eq!(std::fs::read(output_path.join("a"))?, b"a");
+        assert_eq!(std::fs::read(output_path.join("b"))?, b"b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_bytecode_compiler_backend() {
+        let mut env = starlark_env();
+        let context = env.get("CONTEXT").unwrap();
+        assert!(!context.downcast_apply(|x: &EnvironmentContext| x.bytecode_compiler_in_process));
+
+        starlark_eval_in_env(&mut env, "set_bytecode_compiler_backend('in-process')").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert!(context.downcast_apply(|x: &EnvironmentContext| x.bytecode_compiler_in_process));
+
+        starlark_eval_in_env(&mut env, "set_bytecode_compiler_backend('process')").unwrap();
+        let context = env.get("CONTEXT").unwrap();
+        assert!(!context.downcast_apply(|x: &EnvironmentContext| x.bytecode_compiler_in_process));
+
+        assert!(starlark_eval_in_env(&mut env, "set_bytecode_compiler_backend('thread')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "set_bytecode_compiler_backend(1)").is_err());
+    }
+
+    #[test]
+    fn test_register_distributions() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let index_path = temp_dir.path().join("distributions.toml");
+        std::fs::write(
+            &index_path,
+            format!(
+                "[[distribution]]\n\
+                 target_triple = \"{}\"\n\
+                 python_version = \"2.7\"\n\
+                 local_path = \"cpython-2.7.tar.zst\"\n\
+                 sha256 = \"deadbeef\"\n",
+                crate::project_building::HOST
+            ),
+        )?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "register_distributions({:?})",
+                index_path.display().to_string()
+            ),
+        )
+        .unwrap();
+
+        let dist = starlark_eval_in_env(
+            &mut env,
+            "default_python_distribution(python_version='2.7')",
+        )
+        .unwrap();
+        dist.downcast_apply(
+            |x: &super::super::python_distribution::PythonDistribution| {
+                assert_eq!(
+                    x.source,
+                    crate::py_packaging::distribution::PythonDistributionLocation::Local {
+                        local_path: temp_dir
+                            .path()
+                            .join("cpython-2.7.tar.zst")
+                            .display()
+                            .to_string(),
+                        sha256: "deadbeef".to_string(),
+                    }
+                )
+            },
+        );
+
+        assert!(starlark_eval_in_env(&mut env, "register_distributions('missing.toml')").is_err());
+
+        Ok(())
+    }
 }
@@ -20,6 +20,7 @@ pub struct EvalResult {
 }
 
 /// Evaluate a Starlark configuration file, returning a low-level result.
+#[allow(clippy::too_many_arguments)]
 pub fn evaluate_file(
     logger: &slog::Logger,
     config_path: &Path,
@@ -28,6 +29,9 @@ pub fn evaluate_file(
     verbose: bool,
     resolve_targets: Option<Vec<String>>,
     build_script_mode: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
 ) -> Result<EvalResult, Diagnostic> {
     let context = EnvironmentContext::new(
         logger,
@@ -40,6 +44,9 @@ pub fn evaluate_file(
         "0",
         resolve_targets,
         build_script_mode,
+        strict,
+        offline,
+        pip_cache,
     )
     .or_else(|e| {
         Err(Diagnostic {
@@ -92,6 +99,7 @@ pub fn evaluate_file(
 }
 
 /// Evaluate a Starlark configuration file and return its result.
+#[allow(clippy::too_many_arguments)]
 pub fn eval_starlark_config_file(
     logger: &slog::Logger,
     path: &Path,
@@ -100,6 +108,9 @@ pub fn eval_starlark_config_file(
     verbose: bool,
     resolve_targets: Option<Vec<String>>,
     build_script_mode: bool,
+    strict: bool,
+    offline: bool,
+    pip_cache: bool,
 ) -> Result<EvalResult> {
     crate::starlark::eval::evaluate_file(
         logger,
@@ -109,6 +120,9 @@ pub fn eval_starlark_config_file(
         verbose,
         resolve_targets,
         build_script_mode,
+        strict,
+        offline,
+        pip_cache,
     )
     .or_else(|d| Err(anyhow!(d.message)))
 }
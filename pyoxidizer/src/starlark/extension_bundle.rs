@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::project_building::build_python_bundle,
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::Result,
+    slog::warn,
+    starlark::values::{default_compare, TypedValue, ValueError},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+    std::path::Path,
+};
+
+/// Represents an extension module bundling the resources of an application.
+///
+/// Importing the extension module from an existing Python interpreter makes
+/// modules of the application importable. The interpreter provides the
+/// standard library, so resources of the distribution aren't bundled. The
+/// extension module is written at the root of the output directory, next to
+/// files installed relative to it.
+pub struct PythonExtensionBundle {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub name: String,
+}
+
+impl PythonExtensionBundle {
+    /// Name of the extension module file for a target triple.
+    pub fn filename(&self, target: &str) -> String {
+        if target.contains("pc-windows") {
+            format!("{}.pyd", self.name)
+        } else {
+            format!("{}.so", self.name)
+        }
+    }
+}
+
+impl TypedValue for PythonExtensionBundle {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("PythonExtensionBundle<name={}>", self.name)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "PythonExtensionBundle"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
+impl BuildTarget for PythonExtensionBundle {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let mut exe = self.exe.clone_box();
+        exe.remove_distribution_resources()?;
+
+        let build = build_python_bundle(
+            &context.logger,
+            &self.name,
+            exe.as_ref(),
+            &context.target_triple,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file(
+            Path::new(&self.filename(&context.target_triple)),
+            &FileContent {
+                data: build.data,
+                executable: false,
+            },
+        )?;
+        manifest.add_manifest(&build.resources.extra_files)?;
+
+        warn!(
+            &context.logger,
+            "writing {} extension module to {}",
+            self.name,
+            context.output_path.display()
+        );
+        manifest.replace_path(&context.output_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: context.output_path.clone(),
+        })
+    }
+}
@@ -38,10 +38,10 @@ use {
     },
     std::any::Any,
     std::cmp::Ordering,
-    std::collections::{HashMap, HashSet},
+    std::collections::{BTreeSet, HashMap},
     std::convert::TryFrom,
     std::ops::Deref,
-    std::path::Path,
+    std::path::{Component, Path, PathBuf},
 };
 
 #[derive(Clone, Debug)]
@@ -100,8 +100,21 @@ impl FileManifest {
         target: &str,
         release: bool,
         opt_level: &str,
+        bytecode_compiler_jobs: usize,
+        bytecode_compiler_in_process: bool,
+        bytecode_cache_path: &Path,
     ) -> Result<()> {
-        let build = build_python_executable(logger, &exe.name(), exe, target, opt_level, release)?;
+        let build = build_python_executable(
+            logger,
+            &exe.name(),
+            exe,
+            target,
+            opt_level,
+            release,
+            bytecode_compiler_jobs,
+            bytecode_compiler_in_process,
+            Some(bytecode_cache_path),
+        )?;
 
         let content = RawFileContent {
             data: build.exe_data.clone(),
@@ -210,6 +223,156 @@ impl FileManifest {
         Ok(Value::new(None))
     }
 
+    /// FileManifest.add_file(path, dest=None)
+    pub fn add_file(&mut self, env: &Environment, path: &Value, dest: &Value) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+        let dest = optional_str_arg("dest", &dest)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
+
+        let source_path = cwd.join(&path);
+        if !source_path.is_file() {
+            return Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: format!("{} is not a file", source_path.display()),
+                label: "add_file()".to_string(),
+            }
+            .into());
+        }
+
+        let dest_path = match dest {
+            Some(dest) => PathBuf::from(dest),
+            None => PathBuf::from(source_path.file_name().unwrap()),
+        };
+
+        let content = RawFileContent::try_from(source_path.as_path()).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_file()".to_string(),
+            }
+            .into())
+        })?;
+
+        self.manifest.add_file(&dest_path, &content).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_file()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// FileManifest.add_directory(path, dest="", glob="**/*")
+    pub fn add_directory(
+        &mut self,
+        env: &Environment,
+        path: &Value,
+        dest: &Value,
+        glob: &Value,
+    ) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+        let dest = required_str_arg("dest", &dest)?;
+        let glob = required_str_arg("glob", &glob)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
+
+        let source_path = cwd.join(&path);
+        if !source_path.is_dir() {
+            return Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: format!("{} is not a directory", source_path.display()),
+                label: "add_directory()".to_string(),
+            }
+            .into());
+        }
+
+        let mut manifest = RawFileManifest::default();
+
+        for path in evaluate_glob(&source_path, &glob).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_directory()".to_string(),
+            }
+            .into())
+        })? {
+            let content = RawFileContent::try_from(path.as_path()).or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "add_directory()".to_string(),
+                }
+                .into())
+            })?;
+
+            // Absolute globs and globs with `..` components can match files
+            // outside the directory, which have no path relative to it.
+            let relative_path = match path.strip_prefix(&source_path) {
+                Ok(relative_path)
+                    if relative_path.components().all(|c| match c {
+                        Component::Normal(_) => true,
+                        _ => false,
+                    }) =>
+                {
+                    relative_path
+                }
+                _ => {
+                    return Err(RuntimeError {
+                        code: "PYOXIDIZER_BUILD",
+                        message: format!(
+                            "{} matched by glob {} is outside {}",
+                            path.display(),
+                            glob,
+                            source_path.display()
+                        ),
+                        label: "add_directory()".to_string(),
+                    }
+                    .into());
+                }
+            };
+            let dest_path = Path::new(&dest).join(relative_path);
+
+            manifest.add_file(&dest_path, &content).or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "add_directory()".to_string(),
+                }
+                .into())
+            })?;
+        }
+
+        self.manifest.add_manifest(&manifest).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_directory()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// FileManifest.add_python_executable(prefix, exe)
+    pub fn starlark_add_python_executable(
+        &mut self,
+        env: &Environment,
+        prefix: &Value,
+        exe: &Value,
+    ) -> ValueResult {
+        required_str_arg("prefix", &prefix)?;
+        required_type_arg("exe", "PythonExecutable", &exe)?;
+
+        self.add_python_resource(env, prefix, exe)
+    }
+
     /// FileManifest.add_python_resource(prefix, resource)
     pub fn add_python_resource(
         &mut self,
@@ -328,14 +491,23 @@ impl FileManifest {
             }
             "PythonExecutable" => {
                 let context = env.get("CONTEXT").expect("CONTEXT not defined");
-                let (target, release, opt_level) =
-                    context.downcast_apply(|x: &EnvironmentContext| {
-                        (
-                            x.build_target_triple.clone(),
-                            x.build_release,
-                            x.build_opt_level.clone(),
-                        )
-                    });
+                let (
+                    target,
+                    release,
+                    opt_level,
+                    bytecode_compiler_jobs,
+                    bytecode_compiler_in_process,
+                    bytecode_cache_path,
+                ) = context.downcast_apply(|x: &EnvironmentContext| {
+                    (
+                        x.build_target_triple.clone(),
+                        x.build_release,
+                        x.build_opt_level.clone(),
+                        x.bytecode_compiler_jobs,
+                        x.bytecode_compiler_in_process,
+                        x.bytecode_cache_path.clone(),
+                    )
+                });
 
                 let raw_exe = resource.0.borrow();
                 let exe = raw_exe.as_any().downcast_ref::<PythonExecutable>().unwrap();
@@ -352,6 +524,9 @@ impl FileManifest {
                     &target,
                     release,
                     &opt_level,
+                    bytecode_compiler_jobs,
+                    bytecode_compiler_in_process,
+                    &bytecode_cache_path,
                 )
                 .or_else(|e| {
                     Err(RuntimeError {
@@ -418,16 +593,15 @@ impl FileManifest {
     }
 }
 
-/// glob(include, exclude=None, relative_to=None)
-fn starlark_glob(
-    env: &Environment,
+/// Resolve the files matched by `include` patterns and not by `exclude` patterns.
+fn resolve_glob(
+    cwd: &Path,
     include: &Value,
     exclude: &Value,
-    strip_prefix: &Value,
-) -> ValueResult {
+    label: &str,
+) -> Result<BTreeSet<PathBuf>, ValueError> {
     required_list_arg("include", "string", include)?;
     optional_list_arg("exclude", "string", exclude)?;
-    let strip_prefix = optional_str_arg("strip_prefix", strip_prefix)?;
 
     let include = include
         .into_iter()?
@@ -439,18 +613,15 @@ fn starlark_glob(
         _ => Vec::new(),
     };
 
-    let context = env.get("CONTEXT").expect("unable to get CONTEXT");
-    let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
-
-    let mut result = HashSet::new();
+    let mut result = BTreeSet::new();
 
     // Evaluate all the includes first.
     for v in include {
-        for p in evaluate_glob(&cwd, &v).or_else(|e| {
+        for p in evaluate_glob(cwd, &v).or_else(|e| {
             Err(RuntimeError {
                 code: "PYOXIDIZER_BUILD",
                 message: e.to_string(),
-                label: "glob()".to_string(),
+                label: label.to_string(),
             }
             .into())
         })? {
@@ -460,11 +631,11 @@ fn starlark_glob(
 
     // Then apply excludes.
     for v in exclude {
-        for p in evaluate_glob(&cwd, &v).or_else(|e| {
+        for p in evaluate_glob(cwd, &v).or_else(|e| {
             Err(RuntimeError {
                 code: "PYOXIDIZER_BUILD",
                 message: e.to_string(),
-                label: "glob()".to_string(),
+                label: label.to_string(),
             }
             .into())
         })? {
@@ -472,6 +643,23 @@ fn starlark_glob(
         }
     }
 
+    Ok(result)
+}
+
+/// glob(include, exclude=None, strip_prefix=None)
+fn starlark_glob(
+    env: &Environment,
+    include: &Value,
+    exclude: &Value,
+    strip_prefix: &Value,
+) -> ValueResult {
+    let strip_prefix = optional_str_arg("strip_prefix", strip_prefix)?;
+
+    let context = env.get("CONTEXT").expect("unable to get CONTEXT");
+    let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
+
+    let result = resolve_glob(&cwd, include, exclude, "glob()")?;
+
     let mut manifest = RawFileManifest::default();
 
     for path in result {
@@ -512,12 +700,41 @@ fn starlark_glob(
     Ok(Value::new(FileManifest { manifest }))
 }
 
+/// glob_files(include, exclude=None)
+fn starlark_glob_files(env: &Environment, include: &Value, exclude: &Value) -> ValueResult {
+    let context = env.get("CONTEXT").expect("unable to get CONTEXT");
+    let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
+
+    let result = resolve_glob(&cwd, include, exclude, "glob_files()")?;
+
+    // Paths under the current directory are relative to it, so they can be
+    // passed to functions resolving paths relative to the config file.
+    Ok(Value::from(
+        result
+            .iter()
+            .map(|path| {
+                Value::from(
+                    path.strip_prefix(&cwd)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string(),
+                )
+            })
+            .collect::<Vec<Value>>(),
+    ))
+}
+
 starlark_module! { file_resource_env =>
     #[allow(clippy::ptr_arg)]
     glob(env env, include, exclude=None, strip_prefix=None) {
         starlark_glob(&env, &include, &exclude, &strip_prefix)
     }
 
+    #[allow(clippy::ptr_arg)]
+    glob_files(env env, include, exclude=None) {
+        starlark_glob_files(&env, &include, &exclude)
+    }
+
     #[allow(non_snake_case, clippy::ptr_arg)]
     FileManifest(env _env) {
         FileManifest::new_from_args()
@@ -530,6 +747,27 @@ starlark_module! { file_resource_env =>
         })
     }
 
+    #[allow(clippy::ptr_arg)]
+    FileManifest.add_file(env env, this, path, dest=None) {
+        this.downcast_apply_mut(|manifest: &mut FileManifest| {
+            manifest.add_file(&env, &path, &dest)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    FileManifest.add_directory(env env, this, path, dest="", glob="**/*") {
+        this.downcast_apply_mut(|manifest: &mut FileManifest| {
+            manifest.add_directory(&env, &path, &dest, &glob)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    FileManifest.add_python_executable(env env, this, prefix, exe) {
+        this.downcast_apply_mut(|manifest: &mut FileManifest| {
+            manifest.starlark_add_python_executable(&env, &prefix, &exe)
+        })
+    }
+
     #[allow(clippy::ptr_arg)]
     FileManifest.add_python_resource(env env, this, prefix, resource) {
         this.downcast_apply_mut(|manifest: &mut FileManifest| {
@@ -560,7 +798,6 @@ mod tests {
         python_packaging::resource::{
             DataLocation, PythonModuleSource, PythonPackageResource as RawPackageResource,
         },
-        std::path::PathBuf,
     };
 
     const DEFAULT_CACHE_TAG: &str = "cpython-37";
@@ -683,6 +920,135 @@ mod tests {
         starlark_eval_in_env(&mut env, "m.add_python_resource('bin', exe)").unwrap();
     }
 
+    #[test]
+    fn test_add_python_executable_method() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(&mut env, "m = FileManifest()").unwrap();
+
+        starlark_eval_in_env(&mut env, "m.add_python_executable('bin', exe)").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "m.add_python_executable('bin', dist)").is_err());
+
+        let m = env.get("m").unwrap();
+        m.downcast_apply(|m: &FileManifest| {
+            let exe_name = if cfg!(windows) {
+                "bin/testapp.exe"
+            } else {
+                "bin/testapp"
+            };
+            assert!(m.manifest.has_path(Path::new(exe_name)));
+        });
+    }
+
+    #[test]
+    fn test_add_file() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let license_path = temp_dir.path().join("LICENSE");
+        std::fs::write(&license_path, b"license")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "m = FileManifest()").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!("m.add_file({:?})", license_path.display().to_string()),
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "m.add_file({:?}, dest='share/doc/LICENSE.txt')",
+                license_path.display().to_string()
+            ),
+        )
+        .unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "m.add_file({:?})",
+                temp_dir.path().join("missing").display().to_string()
+            )
+        )
+        .is_err());
+
+        let m = env.get("m").unwrap();
+        m.downcast_apply(|m: &FileManifest| {
+            let entries = m.manifest.entries().collect::<Vec<_>>();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].0, &PathBuf::from("LICENSE"));
+            assert_eq!(entries[0].1.data, b"license".to_vec());
+            assert_eq!(entries[1].0, &PathBuf::from("share/doc/LICENSE.txt"));
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_directory() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let data_path = temp_dir.path().join("data");
+        std::fs::create_dir_all(data_path.join("sub"))?;
+        std::fs::write(data_path.join("a.json"), b"a")?;
+        std::fs::write(data_path.join("b.txt"), b"b")?;
+        std::fs::write(data_path.join("sub").join("c.json"), b"c")?;
+
+        let data_path = data_path.display().to_string();
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "m = FileManifest()").unwrap();
+        starlark_eval_in_env(&mut env, &format!("m.add_directory({:?})", data_path)).unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "m.add_directory({:?}, dest='etc', glob='**/*.json')",
+                data_path
+            ),
+        )
+        .unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!("m.add_directory({:?})", format!("{}/a.json", data_path))
+        )
+        .is_err());
+
+        // Globs matching files outside the directory are refused.
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "m.add_directory({:?}, glob={:?})",
+                format!("{}/sub", data_path),
+                format!("{}/*.txt", data_path)
+            )
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "m.add_directory({:?}, glob='../*.txt')",
+                format!("{}/sub", data_path)
+            )
+        )
+        .is_err());
+
+        let m = env.get("m").unwrap();
+        m.downcast_apply(|m: &FileManifest| {
+            let paths = m.manifest.entries().map(|(p, _)| p.clone()).collect_vec();
+            assert_eq!(
+                paths,
+                vec![
+                    PathBuf::from("a.json"),
+                    PathBuf::from("b.txt"),
+                    PathBuf::from("etc/a.json"),
+                    PathBuf::from("etc/sub/c.json"),
+                    PathBuf::from("sub/c.json"),
+                ]
+            );
+        });
+
+        Ok(())
+    }
+
     #[test]
     fn test_install() {
         let mut env = starlark_env();
@@ -716,4 +1082,42 @@ mod tests {
 
         assert!(app_exe.exists());
     }
+
+    #[test]
+    fn test_glob_files() -> Result<()> {
+        let files =
+            starlark_ok("glob_files(['src/starlark/*.rs'], exclude=['src/starlark/testutil.rs'])");
+        assert_eq!(files.get_type(), "list");
+
+        let files = files
+            .into_iter()
+            .unwrap()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        assert!(files.contains(&"src/starlark/file_resource.rs".to_string()));
+        assert!(!files.contains(&"src/starlark/testutil.rs".to_string()));
+
+        let mut sorted = files.clone();
+        sorted.sort();
+        assert_eq!(files, sorted);
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let path = temp_dir.path().join("foo.txt");
+        std::fs::write(&path, b"foo")?;
+
+        let files = starlark_ok(&format!(
+            "glob_files([{:?}])",
+            format!("{}/*.txt", temp_dir.path().display())
+        ));
+        assert_eq!(
+            files
+                .into_iter()
+                .unwrap()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>(),
+            vec![path.display().to_string()]
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,308 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Starlark callables invoked around the resolution and building of targets.
+
+Hooks are registered for an event by `register_hook()`. They can be used to
+send notifications, write custom manifests or enforce policies: an error
+raised by a hook fails the build.
+*/
+
+use {
+    super::env::EnvironmentContext,
+    super::util::{required_str_arg, required_type_arg},
+    anyhow::{anyhow, Result},
+    starlark::environment::Environment,
+    starlark::values::{
+        default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult,
+        INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+    },
+    starlark::{
+        any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::any::Any,
+    std::cmp::Ordering,
+    std::collections::HashMap,
+    std::path::Path,
+    std::time::Duration,
+};
+
+/// Events hooks can be registered for.
+///
+/// * `pre_target` is called with the name of a target before its function
+///   is called.
+/// * `post_target` is called with a `TargetResult` after a target's function
+///   returned or failed.
+/// * `post_build` is called with a `TargetResult` after `pyoxidizer build`
+///   built a target or failed to.
+/// * `build_end` is called with the list of `TargetResult` of built targets
+///   at the end of `pyoxidizer build`.
+pub const HOOK_EVENTS: &[&str] = &["pre_target", "post_target", "post_build", "build_end"];
+
+/// Outcome of resolving or building a target.
+#[derive(Clone, Debug)]
+pub struct TargetResult {
+    pub target: String,
+
+    /// Whether the target was resolved or built successfully.
+    pub success: bool,
+
+    pub duration: Duration,
+
+    /// Paths of files produced by building the target.
+    pub artifacts: Vec<String>,
+}
+
+impl TargetResult {
+    /// Obtain the result of building a target.
+    ///
+    /// `output_path` is the directory holding the files of the built target,
+    /// or `None` if building failed.
+    pub fn built(target: &str, duration: Duration, output_path: Option<&Path>) -> Self {
+        let mut artifacts = match output_path {
+            Some(path) => walkdir::WalkDir::new(path)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.path().display().to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+        artifacts.sort();
+
+        TargetResult {
+            target: target.to_string(),
+            success: output_path.is_some(),
+            duration,
+            artifacts,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        if self.success {
+            "success"
+        } else {
+            "error"
+        }
+    }
+}
+
+impl TypedValue for TargetResult {
+    immutable!();
+    any!();
+    not_supported!(
+        binop, dir_attr, function, get_hash, indexable, iterable, sequence, set_attr, to_int
+    );
+
+    fn to_str(&self) -> String {
+        format!(
+            "TargetResult<target={}, status={}, duration_ms={}>",
+            self.target,
+            self.status(),
+            self.duration.as_millis()
+        )
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "TargetResult"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "target" => Value::new(self.target.clone()),
+            "status" => Value::new(self.status().to_string()),
+            "duration_ms" => Value::new(self.duration.as_millis() as i64),
+            "artifacts" => Value::from(
+                self.artifacts
+                    .iter()
+                    .map(|path| Value::new(path.clone()))
+                    .collect::<Vec<Value>>(),
+            ),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "TargetResult".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "target" => true,
+            "status" => true,
+            "duration_ms" => true,
+            "artifacts" => true,
+            _ => false,
+        })
+    }
+}
+
+/// Call the hooks registered for an event.
+#[allow(clippy::ptr_arg)]
+pub fn call_hooks(
+    env: &Environment,
+    call_stack: &Vec<(String, String)>,
+    event: &str,
+    args: Vec<Value>,
+) -> ValueResult {
+    let context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    let hooks = context.downcast_apply(|x: &EnvironmentContext| {
+        x.hooks.get(event).cloned().unwrap_or_else(Vec::new)
+    });
+
+    for hook in hooks {
+        hook.call(
+            call_stack,
+            env.clone(),
+            args.clone(),
+            HashMap::new(),
+            None,
+            None,
+        )?;
+    }
+
+    Ok(Value::new(None))
+}
+
+/// Call the hooks registered for an event outside of Starlark evaluation.
+pub fn run_hooks(env: &Environment, event: &str, args: Vec<Value>) -> Result<()> {
+    call_hooks(env, &Vec::new(), event, args)
+        .map(|_| ())
+        .or_else(|e| Err(anyhow!("error running {} hook: {:?}", event, e)))
+}
+
+/// register_hook(event, callable)
+fn starlark_register_hook(env: &Environment, event: &Value, callable: &Value) -> ValueResult {
+    let event = required_str_arg("event", &event)?;
+    required_type_arg("callable", "function", &callable)?;
+
+    if !HOOK_EVENTS.contains(&event.as_str()) {
+        return Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!(
+                "event must be one of {}; got {}",
+                HOOK_EVENTS.join(", "),
+                event
+            ),
+            label: "register_hook()".to_string(),
+        }
+        .into());
+    }
+
+    let mut context = env.get("CONTEXT").expect("CONTEXT not set");
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        x.hooks
+            .entry(event.clone())
+            .or_insert_with(Vec::new)
+            .push(callable.clone())
+    });
+
+    Ok(Value::new(None))
+}
+
+starlark_module! { hooks_module =>
+    #[allow(clippy::ptr_arg)]
+    register_hook(env env, event, callable) {
+        starlark_register_hook(&env, &event, &callable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testutil::*;
+    use super::*;
+
+    #[test]
+    fn test_register_hook() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def hook(target): pass").unwrap();
+        starlark_eval_in_env(&mut env, "register_hook('pre_target', hook)").unwrap();
+
+        let context = env.get("CONTEXT").unwrap();
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(x.hooks.get("pre_target").unwrap().len(), 1);
+        });
+
+        assert!(starlark_eval_in_env(&mut env, "register_hook('pre_build', hook)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "register_hook('pre_target', 1)").is_err());
+    }
+
+    #[test]
+    fn test_target_hooks() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "def foo(): return 'foo'").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('foo', foo)").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "def post(result):\n    \
+             if result.target != 'foo' or result.status != 'success':\n        \
+             fail('unexpected result')\n    \
+             if result.duration_ms < 0 or result.artifacts != []:\n        \
+             fail('unexpected result')",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "register_hook('post_target', post)").unwrap();
+        starlark_eval_in_env(&mut env, "resolve_target('foo')").unwrap();
+
+        starlark_eval_in_env(&mut env, "def bar(): return 'bar'").unwrap();
+        starlark_eval_in_env(&mut env, "register_target('bar', bar)").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "def deny(target):\n    \
+             if target == 'bar':\n        \
+             fail('bar is not allowed')",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "register_hook('pre_target', deny)").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "resolve_target('bar')").is_err());
+    }
+
+    #[test]
+    fn test_target_result_built() -> anyhow::Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("lib"))?;
+        std::fs::write(root.join("app"), "")?;
+        std::fs::write(root.join("lib").join("foo.py"), "")?;
+
+        let result = TargetResult::built("exe", Duration::from_millis(1500), Some(root));
+        assert_eq!(
+            result.artifacts,
+            vec![
+                root.join("app").display().to_string(),
+                root.join("lib").join("foo.py").display().to_string(),
+            ]
+        );
+        assert_eq!(
+            result.to_str(),
+            "TargetResult<target=exe, status=success, duration_ms=1500>"
+        );
+
+        let result = TargetResult::built("exe", Duration::from_millis(0), None);
+        assert!(!result.success);
+        assert!(result.artifacts.is_empty());
+
+        Ok(())
+    }
+}
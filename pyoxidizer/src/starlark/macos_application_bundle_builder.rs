@@ -0,0 +1,409 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::env::EnvironmentContext,
+    super::file_resource::FileManifest,
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    super::util::{required_str_arg, required_type_arg},
+    crate::app_packaging::macos_application_bundle::{
+        nested_code_paths, relink_frameworks, MacOsApplicationBundle, PlistValue, RESOURCES_ORIGIN,
+    },
+    crate::atomic_output::produce_path_atomic,
+    crate::project_building::build_python_executable,
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::{anyhow, Result},
+    slog::warn,
+    starlark::environment::Environment,
+    starlark::values::{
+        default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult,
+        INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+    },
+    starlark::{
+        any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::any::Any,
+    std::cmp::Ordering,
+    std::collections::BTreeMap,
+    std::path::PathBuf,
+};
+
+/// Convert a Starlark value to a property list value.
+pub fn starlark_to_plist_value(value: &Value) -> Result<PlistValue, ValueError> {
+    match value.get_type() {
+        "bool" => Ok(PlistValue::Bool(value.to_bool())),
+        "int" => Ok(PlistValue::Integer(value.to_int()?)),
+        "string" => Ok(PlistValue::String(value.to_str())),
+        "list" => Ok(PlistValue::Array(
+            value
+                .into_iter()?
+                .map(|v| starlark_to_plist_value(&v))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        "dict" => Ok(PlistValue::Dict(starlark_to_plist_dict(value)?)),
+        t => Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!("cannot store {} values in a property list", t),
+            label: format!("unsupported type {}", t),
+        }
+        .into()),
+    }
+}
+
+/// Convert a Starlark dict with string keys to property list values.
+pub fn starlark_to_plist_dict(value: &Value) -> Result<BTreeMap<String, PlistValue>, ValueError> {
+    let mut res = BTreeMap::new();
+
+    for key in value.into_iter()? {
+        if key.get_type() != "string" {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("property list keys must be strings; got {}", key.get_type()),
+                label: format!("expected type string; got {}", key.get_type()),
+            }
+            .into());
+        }
+
+        res.insert(key.to_str(), starlark_to_plist_value(&value.at(key)?)?);
+    }
+
+    Ok(res)
+}
+
+/// Represents a macOS application bundle of a Python executable.
+pub struct MacOsApplicationBundleBuilder {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub bundle: MacOsApplicationBundle,
+}
+
+impl MacOsApplicationBundleBuilder {
+    /// MacOsApplicationBundleBuilder.add_icon(path)
+    pub fn starlark_add_icon(&mut self, env: &Environment, path: &Value) -> ValueResult {
+        let path = required_str_arg("path", path)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let cwd = context.downcast_apply(|x: &EnvironmentContext| x.cwd.clone());
+
+        let path = cwd.join(path);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        std::fs::read(&path)
+            .or_else(|e| Err(anyhow!("reading {}: {}", path.display(), e)))
+            .and_then(|data| self.bundle.add_icon(&file_name, data))
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "add_icon()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// MacOsApplicationBundleBuilder.add_resources_manifest(manifest)
+    pub fn starlark_add_resources_manifest(&mut self, manifest: &Value) -> ValueResult {
+        required_type_arg("manifest", "FileManifest", manifest)?;
+
+        let manifest = manifest.downcast_apply(|m: &FileManifest| m.manifest.clone());
+
+        self.bundle.resources.add_manifest(&manifest).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_resources_manifest()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// MacOsApplicationBundleBuilder.set_info_plist_value(key, value)
+    pub fn starlark_set_info_plist_value(&mut self, key: &Value, value: &Value) -> ValueResult {
+        let key = required_str_arg("key", key)?;
+        let value = starlark_to_plist_value(value)?;
+
+        self.bundle.info_plist.insert(key, value);
+
+        Ok(Value::new(None))
+    }
+}
+
+impl TypedValue for MacOsApplicationBundleBuilder {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("MacOsApplicationBundleBuilder<{}>", self.bundle.bundle_name)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "MacOsApplicationBundleBuilder"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "bundle_name" => Value::new(self.bundle.bundle_name.clone()),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "MacOsApplicationBundleBuilder".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "bundle_name" => true,
+            _ => false,
+        })
+    }
+}
+
+impl BuildTarget for MacOsApplicationBundleBuilder {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        if !context.target_triple.contains("-apple-darwin") {
+            return Err(anyhow!(
+                "application bundles can only be built for macOS targets; got {}",
+                context.target_triple
+            ));
+        }
+
+        // Files installed next to the executable go in Contents/Resources.
+        let mut exe = self.exe.clone_box();
+        exe.set_resources_origin(RESOURCES_ORIGIN)?;
+
+        let build = build_python_executable(
+            &context.logger,
+            &exe.name(),
+            exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let files = &build.binary_data.extra_files;
+
+        // The libpython of distributions linked dynamically is a framework.
+        let frameworks = build
+            .binary_data
+            .linking_info
+            .libpython_filename
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(PathBuf::from)
+            .filter(|path| files.has_path(path))
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let manifest =
+            self.bundle
+                .to_manifest(&build.exe_name, &build.exe_data, files, &frameworks)?;
+
+        let bundle_path = context
+            .output_path
+            .join(format!("{}.app", self.bundle.bundle_name));
+
+        warn!(
+            &context.logger,
+            "writing application bundle to {}",
+            bundle_path.display()
+        );
+
+        // The bundle is assembled, relinked and signed in a staging directory
+        // and only moved into place once complete.
+        produce_path_atomic(&bundle_path, |staged_bundle_path| {
+            let staging_path = staged_bundle_path
+                .parent()
+                .ok_or_else(|| anyhow!("unable to resolve bundle staging directory"))?;
+            manifest.write_to_path(staging_path)?;
+
+            relink_frameworks(
+                &context.logger,
+                staged_bundle_path,
+                &build.exe_name,
+                &frameworks,
+            )?;
+
+            // Nested code is signed before the bundle, whose signature covers
+            // it and the executable. Relinking invalidated signatures of
+            // frameworks.
+            if let Some(signing) = &build.binary_data.macos_signing {
+                let temp_dir = tempdir::TempDir::new("pyoxidizer-bundle")?;
+                let entitlements_path = temp_dir.path().join("entitlements.plist");
+                std::fs::write(&entitlements_path, signing.to_entitlements_plist())?;
+
+                for path in nested_code_paths(&manifest) {
+                    signing.sign(
+                        &context.logger,
+                        &staging_path.join(path),
+                        &entitlements_path,
+                    )?;
+                }
+                signing.sign(&context.logger, staged_bundle_path, &entitlements_path)?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::Path {
+                path: bundle_path
+                    .join("Contents")
+                    .join("MacOS")
+                    .join(&build.exe_name),
+            },
+            output_path: context.output_path.clone(),
+        })
+    }
+}
+
+starlark_module! { macos_application_bundle_builder_env =>
+    #[allow(clippy::ptr_arg)]
+    MacOsApplicationBundleBuilder.add_icon(env env, this, path) {
+        this.downcast_apply_mut(|builder: &mut MacOsApplicationBundleBuilder| {
+            builder.starlark_add_icon(&env, &path)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    MacOsApplicationBundleBuilder.add_resources_manifest(this, manifest) {
+        this.downcast_apply_mut(|builder: &mut MacOsApplicationBundleBuilder| {
+            builder.starlark_add_resources_manifest(&manifest)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    MacOsApplicationBundleBuilder.set_info_plist_value(this, key, value) {
+        this.downcast_apply_mut(|builder: &mut MacOsApplicationBundleBuilder| {
+            builder.starlark_set_info_plist_value(&key, &value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*, std::path::Path};
+
+    #[test]
+    fn test_to_macos_application_bundle_builder() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+
+        let builder = starlark_eval_in_env(
+            &mut env,
+            "exe.to_macos_application_bundle_builder('My App', {'CFBundleIdentifier': 'com.example.myapp'})",
+        )
+        .unwrap();
+        assert_eq!(builder.get_type(), "MacOsApplicationBundleBuilder");
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_macos_application_bundle_builder('My App', {})"
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_macos_application_bundle_builder('My App', {'CFBundleIdentifier': 42})"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_info_plist_value() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "bundle = exe.to_macos_application_bundle_builder('My App', {'CFBundleIdentifier': 'com.example.myapp'})",
+        )
+        .unwrap();
+
+        starlark_eval_in_env(
+            &mut env,
+            "bundle.set_info_plist_value('LSEnvironment', {'A': ['b', 1, True]})",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "bundle.add_resources_manifest(FileManifest())").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "bundle.set_info_plist_value('A', None)").is_err());
+
+        let bundle = starlark_eval_in_env(&mut env, "bundle").unwrap();
+        bundle.downcast_apply(|builder: &MacOsApplicationBundleBuilder| {
+            let mut environment = BTreeMap::new();
+            environment.insert(
+                "A".to_string(),
+                PlistValue::Array(vec![
+                    PlistValue::String("b".to_string()),
+                    PlistValue::Integer(1),
+                    PlistValue::Bool(true),
+                ]),
+            );
+
+            assert_eq!(
+                builder.bundle.info_plist.get("LSEnvironment"),
+                Some(&PlistValue::Dict(environment))
+            );
+        });
+    }
+
+    #[test]
+    fn test_add_icon() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let icon_path = temp_dir.path().join("myapp.icns");
+        std::fs::write(&icon_path, b"icns")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "bundle = exe.to_macos_application_bundle_builder('My App', {'CFBundleIdentifier': 'com.example.myapp'})",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!("bundle.add_icon('{}')", icon_path.display()),
+        )
+        .unwrap();
+
+        let bundle = starlark_eval_in_env(&mut env, "bundle").unwrap();
+        bundle.downcast_apply(|builder: &MacOsApplicationBundleBuilder| {
+            assert!(builder.bundle.resources.has_path(Path::new("myapp.icns")));
+            assert_eq!(
+                builder.bundle.info_plist.get("CFBundleIconFile"),
+                Some(&PlistValue::String("myapp.icns".to_string()))
+            );
+        });
+
+        Ok(())
+    }
+}
@@ -8,15 +8,29 @@ The `starlark` module and related sub-modules define the
 define Oxidized Python binaries.
 */
 
+pub mod appimage_builder;
+pub mod build_summary;
+pub mod c_library;
 pub mod env;
 pub mod eval;
+pub mod extension_bundle;
 pub mod file_resource;
+pub mod hooks;
+pub mod macos_application_bundle_builder;
+pub mod platform;
+pub mod plugin_library;
 pub mod python_distribution;
 pub mod python_embedded_resources;
 pub mod python_executable;
 pub mod python_interpreter_config;
 pub mod python_resource;
+pub mod python_resources_summary;
+pub mod snap_builder;
+pub mod standalone_folder;
 pub mod target;
+pub mod testing;
 #[cfg(test)]
-mod testutil;
+pub(crate) mod testutil;
 pub mod util;
+pub mod wix_msi_builder;
+pub mod zipapp;
@@ -0,0 +1,385 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::util::{optional_type_arg, required_str_arg, required_type_arg},
+    anyhow::{anyhow, Result},
+    starlark::environment::Environment,
+    starlark::values::{
+        default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult,
+        INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+    },
+    starlark::{
+        any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::any::Any,
+    std::cmp::Ordering,
+};
+
+/// Vendors of target triples having 3 components.
+const VENDORS: &[&str] = &["apple", "pc", "unknown"];
+
+/// A platform described by a Rust target triple.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Platform {
+    pub triple: String,
+    pub arch: String,
+    pub vendor: String,
+    /// Operating system, with `darwin` normalized to `macos`.
+    pub os: String,
+    /// ABI of the platform, e.g. `gnu` or `msvc`. Empty if the triple doesn't define one.
+    pub abi: String,
+}
+
+impl Platform {
+    /// Parse a target triple.
+    pub fn from_triple(triple: &str) -> Result<Self> {
+        let parts = triple.split('-').collect::<Vec<_>>();
+
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(anyhow!("invalid target triple: {}", triple));
+        }
+
+        let (arch, vendor, os, abi) = match parts.as_slice() {
+            [arch, vendor, os, abi] => (*arch, *vendor, *os, *abi),
+            [arch, vendor, os] if VENDORS.contains(vendor) => (*arch, *vendor, *os, ""),
+            [arch, os, abi] => (*arch, "unknown", *os, *abi),
+            [arch, os] => (*arch, "unknown", *os, ""),
+            _ => return Err(anyhow!("invalid target triple: {}", triple)),
+        };
+
+        Ok(Self {
+            triple: triple.to_string(),
+            arch: arch.to_string(),
+            vendor: vendor.to_string(),
+            os: if os == "darwin" { "macos" } else { os }.to_string(),
+            abi: abi.to_string(),
+        })
+    }
+
+    /// Width of pointers in bits.
+    pub fn pointer_width(&self) -> i64 {
+        if self.arch.contains("64") || self.arch == "s390x" {
+            64
+        } else {
+            32
+        }
+    }
+
+    /// Whether a `select()` key matches this platform.
+    ///
+    /// Returns the precedence of the match, higher values being more
+    /// specific, or `None` if the key doesn't match.
+    fn match_key(&self, key: &str) -> Option<u32> {
+        if key == self.triple {
+            Some(4)
+        } else if key == format!("{}-{}", self.os, self.arch) {
+            Some(3)
+        } else if key == self.os {
+            Some(2)
+        } else if key == self.arch {
+            Some(1)
+        } else if key == "default" {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+impl TypedValue for Platform {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        self.triple.clone()
+    }
+
+    fn to_repr(&self) -> String {
+        format!("Platform<{}>", self.triple)
+    }
+
+    fn get_type(&self) -> &'static str {
+        "Platform"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        if other.get_type() == "Platform" {
+            Ok(self.triple.cmp(&other.to_str()))
+        } else {
+            default_compare(self, other)
+        }
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "triple" => Value::new(self.triple.clone()),
+            "arch" => Value::new(self.arch.clone()),
+            "vendor" => Value::new(self.vendor.clone()),
+            "os" => Value::new(self.os.clone()),
+            "abi" => Value::new(self.abi.clone()),
+            "is_windows" => Value::new(self.os == "windows"),
+            "is_macos" => Value::new(self.os == "macos"),
+            "is_linux" => Value::new(self.os == "linux"),
+            "pointer_width" => Value::new(self.pointer_width()),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "Platform".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "triple" => true,
+            "arch" => true,
+            "vendor" => true,
+            "os" => true,
+            "abi" => true,
+            "is_windows" => true,
+            "is_macos" => true,
+            "is_linux" => true,
+            "pointer_width" => true,
+            _ => false,
+        })
+    }
+}
+
+/// Obtain a target triple from an optional string or `Platform` argument.
+pub fn optional_triple_arg(name: &str, value: &Value) -> Result<Option<String>, ValueError> {
+    match value.get_type() {
+        "NoneType" => Ok(None),
+        "string" | "Platform" => Ok(Some(value.to_str())),
+        t => Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!(
+                "function expects an optional string or Platform for {}; got type {}",
+                name, t
+            ),
+            label: format!("expected type string or Platform; got {}", t),
+        }
+        .into()),
+    }
+}
+
+/// Platform(triple)
+fn starlark_platform(triple: &Value) -> ValueResult {
+    let triple = required_str_arg("triple", triple)?;
+
+    let platform = Platform::from_triple(&triple).or_else(|e| {
+        Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: e.to_string(),
+            label: "Platform()".to_string(),
+        }
+        .into())
+    })?;
+
+    Ok(Value::new(platform))
+}
+
+/// select(choices, platform=None)
+fn starlark_select(env: &Environment, choices: &Value, platform: &Value) -> ValueResult {
+    required_type_arg("choices", "dict", choices)?;
+    optional_type_arg("platform", "Platform", platform)?;
+
+    let platform = match platform.get_type() {
+        "Platform" => platform.clone(),
+        _ => env.get("BUILD_TARGET").unwrap(),
+    };
+
+    let mut selected: Option<(u32, Value)> = None;
+
+    for key in choices.into_iter()? {
+        let key_str = required_str_arg("choices key", &key)?;
+
+        let precedence = platform.downcast_apply(|p: &Platform| p.match_key(&key_str));
+
+        if let Some(precedence) = precedence {
+            if selected
+                .as_ref()
+                .map(|(best, _)| precedence > *best)
+                .unwrap_or(true)
+            {
+                selected = Some((precedence, choices.at(key)?));
+            }
+        }
+    }
+
+    match selected {
+        Some((_, value)) => Ok(value),
+        None => Err(RuntimeError {
+            code: "PYOXIDIZER_BUILD",
+            message: format!(
+                "no choice matches platform {} and no default is defined",
+                platform.to_str()
+            ),
+            label: "select()".to_string(),
+        }
+        .into()),
+    }
+}
+
+starlark_module! { platform_module =>
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    Platform(triple) {
+        starlark_platform(&triple)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    select(env env, choices, platform=None) {
+        starlark_select(&env, &choices, &platform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*};
+
+    #[test]
+    fn test_from_triple() -> Result<()> {
+        let p = Platform::from_triple("x86_64-unknown-linux-gnu")?;
+        assert_eq!(
+            (
+                p.arch.as_str(),
+                p.vendor.as_str(),
+                p.os.as_str(),
+                p.abi.as_str()
+            ),
+            ("x86_64", "unknown", "linux", "gnu")
+        );
+        assert_eq!(p.pointer_width(), 64);
+
+        let p = Platform::from_triple("x86_64-apple-darwin")?;
+        assert_eq!(
+            (
+                p.arch.as_str(),
+                p.vendor.as_str(),
+                p.os.as_str(),
+                p.abi.as_str()
+            ),
+            ("x86_64", "apple", "macos", "")
+        );
+
+        let p = Platform::from_triple("i686-pc-windows-msvc")?;
+        assert_eq!((p.os.as_str(), p.abi.as_str()), ("windows", "msvc"));
+        assert_eq!(p.pointer_width(), 32);
+
+        let p = Platform::from_triple("aarch64-linux-android")?;
+        assert_eq!(
+            (p.vendor.as_str(), p.os.as_str(), p.abi.as_str()),
+            ("unknown", "linux", "android")
+        );
+
+        assert!(Platform::from_triple("x86_64").is_err());
+        assert!(Platform::from_triple("x86_64--linux").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_target() {
+        let platform = starlark_ok("BUILD_TARGET");
+        assert_eq!(platform.get_type(), "Platform");
+        assert_eq!(platform.to_str(), crate::project_building::HOST);
+
+        let platform = starlark_ok("BUILD_HOST");
+        assert_eq!(platform.to_str(), crate::project_building::HOST);
+
+        assert!(starlark_ok("BUILD_TARGET == BUILD_HOST").to_bool());
+        assert_eq!(
+            starlark_ok("BUILD_TARGET.triple").to_str(),
+            starlark_ok("BUILD_TARGET_TRIPLE").to_str()
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "p = Platform('x86_64-pc-windows-msvc')").unwrap();
+
+        assert_eq!(
+            starlark_eval_in_env(&mut env, "p.os").unwrap().to_str(),
+            "windows"
+        );
+        assert_eq!(
+            starlark_eval_in_env(&mut env, "p.arch").unwrap().to_str(),
+            "x86_64"
+        );
+        assert_eq!(
+            starlark_eval_in_env(&mut env, "p.abi").unwrap().to_str(),
+            "msvc"
+        );
+        assert!(starlark_eval_in_env(&mut env, "p.is_windows")
+            .unwrap()
+            .to_bool());
+        assert!(!starlark_eval_in_env(&mut env, "p.is_linux")
+            .unwrap()
+            .to_bool());
+        assert_eq!(
+            starlark_eval_in_env(&mut env, "p.pointer_width")
+                .unwrap()
+                .to_int()
+                .unwrap(),
+            64
+        );
+        assert!(
+            starlark_eval_in_env(&mut env, "p == Platform('x86_64-pc-windows-msvc')")
+                .unwrap()
+                .to_bool()
+        );
+        assert!(
+            !starlark_eval_in_env(&mut env, "p == Platform('i686-pc-windows-msvc')")
+                .unwrap()
+                .to_bool()
+        );
+        assert!(starlark_eval_in_env(&mut env, "Platform('x86_64')").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "p = Platform('x86_64-unknown-linux-gnu')").unwrap();
+
+        for (choices, expected) in &[
+            ("{'linux': 'a', 'default': 'b'}", "a"),
+            ("{'windows': 'a', 'default': 'b'}", "b"),
+            ("{'x86_64': 'a', 'linux': 'b'}", "b"),
+            ("{'linux-x86_64': 'a', 'linux': 'b'}", "a"),
+            (
+                "{'x86_64-unknown-linux-gnu': 'a', 'linux-x86_64': 'b'}",
+                "a",
+            ),
+        ] {
+            assert_eq!(
+                starlark_eval_in_env(&mut env, &format!("select({}, platform=p)", choices))
+                    .unwrap()
+                    .to_str(),
+                *expected
+            );
+        }
+
+        assert!(starlark_eval_in_env(&mut env, "select({'windows': 'a'}, platform=p)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "select({1: 'a'}, platform=p)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "select(['a'])").is_err());
+
+        assert_eq!(
+            starlark_eval_in_env(&mut env, "select({'default': 'a'})")
+                .unwrap()
+                .to_str(),
+            "a"
+        );
+    }
+}
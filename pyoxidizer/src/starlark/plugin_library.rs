@@ -0,0 +1,259 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    crate::app_packaging::macos_application_bundle::{to_plist_xml, PlistValue, RESOURCES_ORIGIN},
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::plugin_exports::PluginExport,
+    crate::project_building::{build_python_library, CLibraryKind},
+    crate::project_layout::{plugin_header, plugin_lib_rs},
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::{anyhow, Result},
+    slog::warn,
+    starlark::values::{default_compare, TypedValue, ValueError},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+    std::collections::BTreeMap,
+    std::path::{Path, PathBuf},
+};
+
+/// Represents a plugin library whose exported functions are implemented in Python.
+///
+/// The library and a C header declaring its functions are written at the
+/// root of the output directory, next to files installed relative to the
+/// library. Bundled libraries are written inside a bundle directory with the
+/// layout hosts expect, and files installed relative to them are written in
+/// the bundle as well.
+pub struct PythonPluginLibrary {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub name: String,
+    pub exports: Vec<PluginExport>,
+
+    /// Extension of the library file, overriding the platform's default.
+    ///
+    /// e.g. `vst3` or `mll`. The library file is then named after the
+    /// library, without platform specific prefix.
+    pub extension: Option<String>,
+
+    /// Whether the library is written in a bundle directory.
+    pub bundle: bool,
+}
+
+/// Name of the directory of VST3 bundles holding the library of a target triple.
+///
+/// e.g. `x86_64-linux` or `x86_64-win`.
+fn vst3_architecture(target: &str) -> Option<String> {
+    let os = if target.contains("-pc-windows-") {
+        "win"
+    } else if target.contains("-linux-") {
+        "linux"
+    } else {
+        return None;
+    };
+
+    let architecture = match (target.split('-').next().unwrap_or_default(), os) {
+        ("x86_64", _) => "x86_64",
+        ("i686", "win") => "x86",
+        ("i686", _) => "i386",
+        ("aarch64", "win") => "arm64",
+        ("aarch64", _) => "aarch64",
+        ("armv7", "linux") => "armv7l",
+        _ => return None,
+    };
+
+    Some(format!("{}-{}", architecture, os))
+}
+
+impl PythonPluginLibrary {
+    /// Name of the library file built for a target triple.
+    pub fn filename(&self, target: &str) -> String {
+        match &self.extension {
+            Some(extension) => format!("{}.{}", self.name, extension),
+            None => CLibraryKind::Dynamic.filename(&self.name, target),
+        }
+    }
+
+    /// Paths of the library and of the directory of files installed relative
+    /// to it, relative to the output directory.
+    ///
+    /// Bundles are macOS bundles, holding the library in `Contents/MacOS` and
+    /// files in `Contents/Resources`, or VST3 bundles, holding the library
+    /// and files in a directory named after the platform, e.g.
+    /// `Contents/x86_64-linux`.
+    pub fn bundle_paths(&self, target: &str) -> Result<(PathBuf, PathBuf)> {
+        if !self.bundle {
+            return Ok((PathBuf::from(self.filename(target)), PathBuf::new()));
+        }
+
+        let extension = self.extension.as_ref().map(|e| e.as_str());
+
+        if target.contains("-apple-darwin") {
+            let contents =
+                PathBuf::from(format!("{}.{}", self.name, extension.unwrap_or("bundle")))
+                    .join("Contents");
+
+            return Ok((
+                contents.join("MacOS").join(&self.name),
+                contents.join("Resources"),
+            ));
+        }
+
+        match (extension, vst3_architecture(target)) {
+            (Some("vst3"), Some(architecture)) => {
+                let directory = PathBuf::from(format!("{}.vst3", self.name))
+                    .join("Contents")
+                    .join(architecture);
+                let filename = if target.contains("-pc-windows-") {
+                    format!("{}.vst3", self.name)
+                } else {
+                    format!("{}.so", self.name)
+                };
+
+                Ok((directory.join(filename), directory))
+            }
+            _ => Err(anyhow!(
+                "plugin bundles are only supported for macOS and VST3; got {} for {}",
+                extension.unwrap_or("no extension"),
+                target
+            )),
+        }
+    }
+
+    /// Obtain the `Info.plist` of the macOS bundle of the library.
+    fn info_plist(&self) -> String {
+        let mut values = BTreeMap::new();
+
+        for (key, value) in &[
+            ("CFBundleExecutable", self.name.as_str()),
+            ("CFBundleName", self.name.as_str()),
+            ("CFBundlePackageType", "BNDL"),
+            ("CFBundleInfoDictionaryVersion", "6.0"),
+        ] {
+            values.insert(key.to_string(), PlistValue::String(value.to_string()));
+        }
+
+        to_plist_xml(&values)
+    }
+}
+
+impl TypedValue for PythonPluginLibrary {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!(
+            "PythonPluginLibrary<name={}, exports={}>",
+            self.name,
+            self.exports
+                .iter()
+                .map(|export| export.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "PythonPluginLibrary"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
+impl BuildTarget for PythonPluginLibrary {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let (library_path, files_path) = self.bundle_paths(&context.target_triple)?;
+        let macos_bundle = self.bundle && context.target_triple.contains("-apple-darwin");
+
+        // Files of macOS bundles go in Contents/Resources.
+        let mut exe = self.exe.clone_box();
+        if macos_bundle {
+            exe.set_resources_origin(RESOURCES_ORIGIN)?;
+        }
+
+        let mut build = build_python_library(
+            &context.logger,
+            &self.name,
+            CLibraryKind::Dynamic,
+            &plugin_lib_rs(&self.exports)?,
+            exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        // Hosts load plugins at run-time, so import libraries aren't needed.
+        let data = build
+            .files
+            .remove(&CLibraryKind::Dynamic.filename(&self.name, &context.target_triple))
+            .ok_or_else(|| anyhow!("plugin library {} wasn't built", self.name))?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file(
+            &library_path,
+            &FileContent {
+                data,
+                executable: false,
+            },
+        )?;
+
+        if macos_bundle {
+            let contents = files_path.parent().unwrap_or(&files_path);
+
+            manifest.add_file(
+                &contents.join("Info.plist"),
+                &FileContent {
+                    data: self.info_plist().into_bytes(),
+                    executable: false,
+                },
+            )?;
+            manifest.add_file(
+                &contents.join("PkgInfo"),
+                &FileContent {
+                    data: b"BNDL????".to_vec(),
+                    executable: false,
+                },
+            )?;
+        }
+
+        manifest.add_file(
+            Path::new(&format!("{}.h", self.name)),
+            &FileContent {
+                data: plugin_header(&self.name, &self.exports)?.into_bytes(),
+                executable: false,
+            },
+        )?;
+        for (path, content) in build.binary_data.extra_files.entries() {
+            manifest.add_file(&files_path.join(path), content)?;
+        }
+
+        warn!(
+            &context.logger,
+            "writing {} plugin library to {}",
+            self.name,
+            context.output_path.display()
+        );
+        manifest.replace_path(&context.output_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: context.output_path.clone(),
+        })
+    }
+}
@@ -4,29 +4,46 @@
 
 use {
     super::env::EnvironmentContext,
+    super::platform::optional_triple_arg,
     super::python_executable::PythonExecutable,
     super::python_resource::{
-        python_resource_to_value, PythonExtensionModule, PythonExtensionModuleFlavor,
-        PythonPackageResource, PythonSourceModule,
+        python_resource_to_value, PythonBytecodeModule, PythonExtensionModule,
+        PythonExtensionModuleFlavor, PythonPackageResource, PythonSourceModule,
     },
     super::util::{
         optional_dict_arg, optional_list_arg, optional_str_arg, optional_type_arg,
-        required_bool_arg, required_list_arg, required_str_arg,
+        required_bool_arg, required_list_arg, required_str_arg, required_type_arg,
     },
-    crate::py_packaging::config::EmbeddedPythonConfig,
+    crate::app_packaging::glob::evaluate_glob,
+    crate::path_util::join_search_paths,
+    crate::py_packaging::conda::read_conda_env as raw_read_conda_env,
+    crate::py_packaging::config::{EmbeddedPythonConfig, RunMode},
     crate::py_packaging::distribution::{
-        default_distribution_location, is_stdlib_test_package, resolve_distribution,
+        default_distribution_location, is_stdlib_test_package, resolve_distribution_for_host,
         DistributionFlavor, ExtensionModuleFilter, PythonDistribution as PythonDistributionTrait,
         PythonDistributionLocation,
     },
+    crate::py_packaging::jinja2_templates::compile_jinja2_templates as raw_compile_jinja2_templates,
     crate::py_packaging::packaging_tool::{
-        find_resources, pip_install as raw_pip_install, read_virtualenv as raw_read_virtualenv,
-        setup_py_install as raw_setup_py_install,
+        build_wheel as raw_build_wheel, ensure_packaging_toolchain, find_resources,
+        packaging_tool_versions, pip_download as raw_pip_download, pip_install as raw_pip_install,
+        pip_install_requirements as raw_pip_install_requirements,
+        poetry_install as raw_poetry_install, read_virtualenv as raw_read_virtualenv,
+        setup_py_install as raw_setup_py_install, BinaryPolicy,
     },
+    crate::py_packaging::protobuf::{compile_protobuf as raw_compile_protobuf, ProtobufCompiler},
+    crate::py_packaging::requirements::RequirementsFile,
+    crate::py_packaging::resource_transform::ResourceTransforms,
+    crate::py_packaging::stdlib_tests::{stdlib_test_runner_code, DEFAULT_STDLIB_TESTS},
+    crate::py_packaging::wheel::read_wheel as raw_read_wheel,
+    crate::python_distributions::PythonDistributionCollection,
     anyhow::{anyhow, Result},
     itertools::Itertools,
     python_packaging::bytecode::{BytecodeCompiler, CompileMode},
-    python_packaging::resource::BytecodeOptimizationLevel,
+    python_packaging::module_util::packages_from_module_name,
+    python_packaging::resource::{
+        BytecodeOptimizationLevel, DataLocation, PythonModuleBytecodeFromSource, PythonResource,
+    },
     python_packaging::resource_collection::PythonResourcesPolicy,
     slog::warn,
     starlark::environment::Environment,
@@ -40,22 +57,218 @@ use {
     },
     std::any::Any,
     std::cmp::Ordering,
-    std::collections::HashMap,
+    std::collections::{BTreeMap, HashMap},
     std::convert::TryFrom,
     std::ops::Deref,
     std::path::{Path, PathBuf},
     std::sync::Arc,
 };
 
+/// Derive the name of a module from the relative path of its source file.
+///
+/// Returns the module name and whether it is a package, or `None` if the
+/// path isn't a relative path to a `.py` file.
+fn module_name_from_filename(filename: &str) -> Option<(String, bool)> {
+    if !filename.ends_with(".py") {
+        return None;
+    }
+
+    let stem = &filename[..filename.len() - ".py".len()];
+    let mut parts = stem.split('/').collect::<Vec<_>>();
+
+    let is_package = parts.len() > 1 && parts.last() == Some(&"__init__");
+    if is_package {
+        parts.pop();
+    }
+
+    if parts
+        .iter()
+        .any(|part| part.is_empty() || part.contains('.') || part.contains('\\'))
+    {
+        return None;
+    }
+
+    Some((parts.join("."), is_package))
+}
+
+/// Whether a module or package is one of `packages` or belongs to one.
+fn is_in_packages(name: &str, packages: &[String]) -> bool {
+    let parents = packages_from_module_name(name);
+
+    packages
+        .iter()
+        .any(|package| name == package || parents.contains(package))
+}
+
+/// Resolve the value of an `exclude` argument.
+fn exclude_arg(exclude: &Value) -> Result<Vec<String>, ValueError> {
+    optional_list_arg("exclude", "string", exclude)?;
+
+    Ok(match exclude.get_type() {
+        "list" => exclude.into_iter()?.map(|x| x.to_string()).collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// Convert resources to a Starlark list.
+fn resources_value(resources: &[PythonResource]) -> Value {
+    Value::from(
+        resources
+            .iter()
+            .map(python_resource_to_value)
+            .collect::<Vec<Value>>(),
+    )
+}
+
+/// Record a packaging operation performed while evaluating the config.
+fn record_packaging_operation(env: &Environment, operation: String, resources_count: usize) {
+    let context = env.get("CONTEXT").expect("CONTEXT not defined");
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        x.packaging_operations
+            .push(format!("{} ({} resources)", operation, resources_count));
+    });
+}
+
+/// Record a source directory read by a packaging operation.
+fn record_source_path(env: &Environment, path: &Path) {
+    let context = env.get("CONTEXT").expect("CONTEXT not defined");
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            x.cwd.join(path)
+        };
+
+        if !x.source_paths.contains(&path) {
+            x.source_paths.push(path);
+        }
+    });
+}
+
+/// Record local paths in arguments to pip, such as projects and requirements files.
+///
+/// Like pip, arguments are considered paths if they contain a path separator
+/// or start with `.`, or if they name an existing file. Extras, e.g. in
+/// `.[dev]`, are ignored.
+fn record_pip_source_paths(env: &Environment, args: &[String]) {
+    let cwd = PathBuf::from(env.get("CWD").expect("CWD not defined").to_string());
+
+    for arg in args {
+        let value = if arg.starts_with("--") {
+            match arg.find('=') {
+                Some(pos) => &arg[pos + 1..],
+                None => continue,
+            }
+        } else if arg.starts_with('-') {
+            continue;
+        } else {
+            arg.as_str()
+        };
+
+        let value = match value.find('[') {
+            Some(pos) => &value[..pos],
+            None => value,
+        };
+        if value.is_empty() {
+            continue;
+        }
+
+        let path = cwd.join(value);
+        let is_path =
+            value.starts_with('.') || value.contains('/') || value.contains('\\') || path.is_file();
+
+        if is_path && path.exists() {
+            record_source_path(env, &path);
+        }
+    }
+}
+
+/// Obtain the `PythonDistribution` value of a distribution.
+///
+/// All callers obtaining the same distribution share a value, so it is
+/// resolved once per evaluation and packaging operations aren't repeated.
+fn distribution_value(
+    env: &Environment,
+    flavor: DistributionFlavor,
+    location: PythonDistributionLocation,
+) -> Value {
+    let context = env.get("CONTEXT").expect("CONTEXT not defined");
+    let (dest_dir, offline, pip_cache_path, host_triple, distributions) =
+        context.downcast_apply(|x: &EnvironmentContext| {
+            (
+                x.python_distributions_path.clone(),
+                x.offline,
+                x.pip_cache_path.clone(),
+                x.build_host_triple.clone(),
+                x.python_distributions.clone(),
+            )
+        });
+
+    let key = format!("{:?} {:?} {}", flavor, location, dest_dir.display());
+
+    if let Some(value) =
+        context.downcast_apply(|x: &EnvironmentContext| x.distribution_values.get(&key).cloned())
+    {
+        return value;
+    }
+
+    let value = Value::new(PythonDistribution::from_location(
+        flavor,
+        location,
+        &dest_dir,
+        offline,
+        pip_cache_path,
+        host_triple,
+        distributions,
+    ));
+
+    context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+        x.distribution_values.insert(key, value.clone());
+    });
+
+    value
+}
+
 pub struct PythonDistribution {
     flavor: DistributionFlavor,
     pub source: PythonDistributionLocation,
 
     dest_dir: PathBuf,
 
+    /// Rust target triple of the build machine.
+    ///
+    /// Distributions targeting another platform run build-time operations
+    /// with a distribution for this triple, among `distributions`.
+    host_triple: String,
+
+    /// Known distributions.
+    distributions: PythonDistributionCollection,
+
     pub distribution: Option<Arc<Box<dyn PythonDistributionTrait>>>,
 
     compiler: Option<BytecodeCompiler>,
+
+    /// Whether the distribution and packages must not be fetched from the network.
+    offline: bool,
+
+    /// Directory where pip caches downloaded packages, if any.
+    pip_cache_path: Option<PathBuf>,
+
+    /// Directory of packaging tools overriding those of the distribution, if any.
+    ///
+    /// Defined by `ensure_pip()`.
+    packaging_toolchain_path: Option<PathBuf>,
+
+    /// Requirements of the packaging tools in `packaging_toolchain_path`.
+    ///
+    /// e.g. `pip==20.3.3`.
+    packaging_tool_requirements: Vec<String>,
+
+    /// Resources collected by packaging operations, keyed by operation.
+    ///
+    /// Repeating an operation, e.g. when several executables install the
+    /// same packages, reuses its resources instead of running it again.
+    collected_resources: HashMap<String, Vec<PythonResource>>,
 }
 
 impl PythonDistribution {
@@ -63,13 +276,24 @@ impl PythonDistribution {
         flavor: DistributionFlavor,
         location: PythonDistributionLocation,
         dest_dir: &Path,
+        offline: bool,
+        pip_cache_path: Option<PathBuf>,
+        host_triple: String,
+        distributions: PythonDistributionCollection,
     ) -> PythonDistribution {
         PythonDistribution {
             flavor,
             source: location,
             dest_dir: dest_dir.to_path_buf(),
+            host_triple,
+            distributions,
             distribution: None,
             compiler: None,
+            offline,
+            pip_cache_path,
+            packaging_toolchain_path: None,
+            packaging_tool_requirements: Vec::new(),
+            collected_resources: HashMap::new(),
         }
     }
 
@@ -78,7 +302,15 @@ impl PythonDistribution {
             return Ok(());
         }
 
-        let dist = resolve_distribution(logger, &self.flavor, &self.source, &self.dest_dir)?;
+        let dist = resolve_distribution_for_host(
+            logger,
+            &self.flavor,
+            &self.source,
+            &self.distributions,
+            &self.host_triple,
+            &self.dest_dir,
+            self.offline,
+        )?;
         //warn!(logger, "distribution info: {:#?}", dist.as_minimal_info());
 
         self.distribution = Some(Arc::new(dist));
@@ -86,6 +318,143 @@ impl PythonDistribution {
         Ok(())
     }
 
+    /// Fail if an operation requiring network access runs in offline mode.
+    fn ensure_online(&self, label: &str) -> Result<(), ValueError> {
+        if self.offline {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_OFFLINE",
+                message: format!(
+                    "{} requires network access, which is disabled in offline mode",
+                    label
+                ),
+                label: label.to_string(),
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Environment variables for pip processes.
+    ///
+    /// In offline mode, pip doesn't access package indexes, so only local
+    /// paths and archives can be installed. Downloaded packages are cached in
+    /// the pip cache directory, if any, so they aren't downloaded again by
+    /// subsequent builds.
+    ///
+    /// Packaging tools installed by `ensure_pip()`, if any, take precedence
+    /// over those of the distribution.
+    fn pip_envs(&self, mut envs: HashMap<String, String>) -> HashMap<String, String> {
+        if let Some(path) = &self.packaging_toolchain_path {
+            let mut paths = vec![path.clone()];
+            if let Some(existing) = envs.get("PYTHONPATH") {
+                paths.extend(std::env::split_paths(existing));
+            }
+
+            if let Ok(value) = join_search_paths(&paths) {
+                envs.insert("PYTHONPATH".to_string(), value);
+            }
+
+            // Recent setuptools replace distutils by their own copy, which
+            // would bypass the distutils modified for static distributions.
+            envs.insert("SETUPTOOLS_USE_DISTUTILS".to_string(), "stdlib".to_string());
+        }
+
+        if self.offline {
+            envs.insert("PIP_NO_INDEX".to_string(), "1".to_string());
+        }
+
+        match &self.pip_cache_path {
+            Some(path) => {
+                envs.insert("PIP_CACHE_DIR".to_string(), path.display().to_string());
+            }
+            None => {
+                envs.insert("PIP_NO_CACHE_DIR".to_string(), "1".to_string());
+            }
+        }
+
+        envs
+    }
+
+    /// Resolve the `wheel_dir` and `no_index` arguments of `pip install` methods.
+    ///
+    /// `wheel_dir` is a directory of wheels, e.g. a cache populated by
+    /// `pip download` or `pip wheel`, searched for packages. With `no_index`,
+    /// package indexes aren't accessed, so only wheels in `wheel_dir` and
+    /// local paths can be installed.
+    fn pip_index_args(
+        env: &Environment,
+        wheel_dir: &Value,
+        no_index: &Value,
+        label: &str,
+    ) -> Result<Vec<String>, ValueError> {
+        let wheel_dir = optional_str_arg("wheel_dir", &wheel_dir)?;
+        let no_index = required_bool_arg("no_index", &no_index)?;
+
+        let mut args = Vec::new();
+
+        if no_index {
+            args.push("--no-index".to_string());
+        }
+
+        if let Some(wheel_dir) = wheel_dir {
+            let cwd = env.get("CWD").expect("CWD not defined").to_string();
+            let path = PathBuf::from(cwd).join(wheel_dir);
+
+            if !path.is_dir() {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!("wheel_dir {} is not a directory", path.display()),
+                    label: label.to_string(),
+                }
+                .into());
+            }
+
+            // Wheels added to the directory affect what gets installed.
+            record_source_path(env, &path);
+
+            args.push("--find-links".to_string());
+            args.push(path.display().to_string());
+        }
+
+        Ok(args)
+    }
+
+    /// Key of the resources collected by a packaging operation.
+    ///
+    /// Other versions of packaging tools may collect other resources, so the
+    /// versions installed by `ensure_pip()` are part of the key.
+    fn resources_key(&self, operation: &str) -> String {
+        if self.packaging_tool_requirements.is_empty() {
+            operation.to_string()
+        } else {
+            format!(
+                "{} with {}",
+                operation,
+                self.packaging_tool_requirements.join(" ")
+            )
+        }
+    }
+
+    /// Obtain the resources of a packaging operation performed earlier, if any.
+    fn reuse_resources(&self, logger: &slog::Logger, operation: &str) -> Option<Value> {
+        self.collected_resources
+            .get(&self.resources_key(operation))
+            .map(|resources| {
+                warn!(logger, "reusing resources of {}", operation);
+                resources_value(resources)
+            })
+    }
+
+    /// Record the resources collected by a packaging operation.
+    fn collect_resources(&mut self, operation: String, resources: Vec<PythonResource>) -> Value {
+        let value = resources_value(&resources);
+        self.collected_resources
+            .insert(self.resources_key(&operation), resources);
+
+        value
+    }
+
     /// Compile bytecode using this distribution.
     ///
     /// A bytecode compiler will be lazily instantiated and preserved for the
@@ -107,11 +476,18 @@ impl PythonDistribution {
             }
         }
 
-        if let Some(compiler) = &mut self.compiler {
+        let res = if let Some(compiler) = &mut self.compiler {
             compiler.compile(source, filename, optimize, output_mode)
         } else {
             Err(anyhow!("bytecode compiler should exist"))
+        };
+
+        // The compiler process exits when compilation fails.
+        if res.is_err() {
+            self.compiler = None;
         }
+
+        res
     }
 }
 
@@ -147,14 +523,16 @@ impl TypedValue for PythonDistribution {
 
 // Starlark functions.
 impl PythonDistribution {
-    /// default_python_distribution(flavor, build_target=None)
+    /// default_python_distribution(flavor, build_target=None, python_version=None)
     fn default_python_distribution(
         env: &Environment,
         flavor: &Value,
         build_target: &Value,
+        python_version: &Value,
     ) -> ValueResult {
         let flavor = required_str_arg("flavor", flavor)?;
-        let build_target = optional_str_arg("build_target", build_target)?;
+        let build_target = optional_triple_arg("build_target", build_target)?;
+        let python_version = optional_str_arg("python_version", python_version)?;
 
         let build_target = match build_target {
             Some(t) => t,
@@ -165,6 +543,7 @@ impl PythonDistribution {
             "standalone" => DistributionFlavor::Standalone,
             "standalone_static" => DistributionFlavor::StandaloneStatic,
             "standalone_dynamic" => DistributionFlavor::StandaloneDynamic,
+            "fixture" => DistributionFlavor::Fixture,
             v => {
                 return Err(RuntimeError {
                     code: "PYOXIDIZER_BUILD",
@@ -175,7 +554,17 @@ impl PythonDistribution {
             }
         };
 
-        let location = default_distribution_location(&flavor, &build_target).or_else(|e| {
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let distributions =
+            context.downcast_apply(|x: &EnvironmentContext| x.python_distributions.clone());
+
+        let location = default_distribution_location(
+            &distributions,
+            &flavor,
+            &build_target,
+            python_version.as_ref().map(|v| v.as_str()),
+        )
+        .or_else(|e| {
             Err(RuntimeError {
                 code: "PYOXIDIZER_BUILD",
                 message: e.to_string(),
@@ -184,13 +573,7 @@ impl PythonDistribution {
             .into())
         })?;
 
-        let context = env.get("CONTEXT").expect("CONTEXT not defined");
-        let dest_dir =
-            context.downcast_apply(|x: &EnvironmentContext| x.python_distributions_path.clone());
-
-        Ok(Value::new(PythonDistribution::from_location(
-            flavor, location, &dest_dir,
-        )))
+        Ok(distribution_value(env, flavor, location))
     }
 
     /// PythonDistribution()
@@ -239,15 +622,7 @@ impl PythonDistribution {
             }
         };
 
-        let context = env.get("CONTEXT").expect("CONTEXT not defined");
-        let dest_dir =
-            context.downcast_apply(|x: &EnvironmentContext| x.python_distributions_path.clone());
-
-        Ok(Value::new(PythonDistribution::from_location(
-            flavor,
-            distribution,
-            &dest_dir,
-        )))
+        Ok(distribution_value(env, flavor, distribution))
     }
 
     /// PythonDistribution.to_python_executable(
@@ -258,6 +633,11 @@ impl PythonDistribution {
     ///     include_sources=true,
     ///     include_resources=true,
     ///     include_test=false,
+    ///     allow_in_memory_shared_library_loading=false,
+    ///     extract_shared_libraries_to_cache=false,
+    ///     include_type_stubs=false,
+    ///     debug_build=false,
+    ///     compress_resources=false,
     /// )
     #[allow(clippy::ptr_arg, clippy::too_many_arguments)]
     fn as_python_executable_starlark(
@@ -272,6 +652,11 @@ impl PythonDistribution {
         include_sources: &Value,
         include_resources: &Value,
         include_test: &Value,
+        allow_in_memory_shared_library_loading: &Value,
+        extract_shared_libraries_to_cache: &Value,
+        include_type_stubs: &Value,
+        debug_build: &Value,
+        compress_resources: &Value,
     ) -> ValueResult {
         let name = required_str_arg("name", &name)?;
         let resources_policy = required_str_arg("resources_policy", &resources_policy)?;
@@ -287,6 +672,17 @@ impl PythonDistribution {
         let include_sources = required_bool_arg("include_sources", &include_sources)?;
         let include_resources = required_bool_arg("include_resources", &include_resources)?;
         let include_test = required_bool_arg("include_test", &include_test)?;
+        let allow_in_memory_shared_library_loading = required_bool_arg(
+            "allow_in_memory_shared_library_loading",
+            &allow_in_memory_shared_library_loading,
+        )?;
+        let extract_shared_libraries_to_cache = required_bool_arg(
+            "extract_shared_libraries_to_cache",
+            &extract_shared_libraries_to_cache,
+        )?;
+        let include_type_stubs = required_bool_arg("include_type_stubs", &include_type_stubs)?;
+        let debug_build = required_bool_arg("debug_build", &debug_build)?;
+        let compress_resources = required_bool_arg("compress_resources", &compress_resources)?;
 
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
@@ -352,32 +748,124 @@ impl PythonDistribution {
             config.downcast_apply(|c: &EmbeddedPythonConfig| c.clone())
         };
 
+        let mut exe = dist
+            .as_python_executable_builder(
+                &logger,
+                &host_triple,
+                &target_triple,
+                &name,
+                &resources_policy,
+                &config,
+                &extension_module_filter,
+                preferred_extension_module_variants,
+                include_sources,
+                include_resources,
+                include_test,
+                allow_in_memory_shared_library_loading,
+                extract_shared_libraries_to_cache,
+            )
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "to_python_executable()".to_string(),
+                }
+                .into())
+            })?;
+
+        if debug_build {
+            exe.set_debug_build().or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "to_python_executable()".to_string(),
+                }
+                .into())
+            })?;
+        }
+
+        if compress_resources {
+            exe.set_compress_resources().or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "to_python_executable()".to_string(),
+                }
+                .into())
+            })?;
+        }
+
+        let resource_transforms = ResourceTransforms::new(exe.python_exe_path());
+
         Ok(Value::new(PythonExecutable {
-            exe: dist
-                .as_python_executable_builder(
-                    &logger,
-                    &host_triple,
-                    &target_triple,
-                    &name,
-                    &resources_policy,
-                    &config,
-                    &extension_module_filter,
-                    preferred_extension_module_variants,
-                    include_sources,
-                    include_resources,
-                    include_test,
-                )
-                .or_else(|e| {
-                    Err(RuntimeError {
-                        code: "PYOXIDIZER_BUILD",
-                        message: e.to_string(),
-                        label: "to_python_executable()".to_string(),
-                    }
-                    .into())
-                })?,
+            exe,
+            resource_transforms,
+            include_type_stubs,
+            type_stubs: Vec::new(),
         }))
     }
 
+    /// PythonDistribution.to_stdlib_test_executable(
+    ///     name,
+    ///     tests=None,
+    ///     resources_policy="filesystem-relative-only:lib",
+    ///     extension_module_filter="all",
+    /// )
+    #[allow(clippy::ptr_arg)]
+    fn as_stdlib_test_executable_starlark(
+        &mut self,
+        env: Environment,
+        call_stack: &Vec<(String, String)>,
+        name: &Value,
+        tests: &Value,
+        resources_policy: &Value,
+        extension_module_filter: &Value,
+    ) -> ValueResult {
+        optional_list_arg("tests", "string", &tests)?;
+
+        let tests = match tests.get_type() {
+            "list" => tests.into_iter()?.map(|x| x.to_string()).collect_vec(),
+            "NoneType" => DEFAULT_STDLIB_TESTS
+                .iter()
+                .map(|x| x.to_string())
+                .collect_vec(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        let v = env
+            .get("PythonInterpreterConfig")
+            .expect("PythonInterpreterConfig not defined");
+        let mut config = v
+            .call(
+                call_stack,
+                env.clone(),
+                Vec::new(),
+                HashMap::new(),
+                None,
+                None,
+            )?
+            .downcast_apply(|c: &EmbeddedPythonConfig| c.clone());
+        config.run_mode = RunMode::Eval {
+            code: stdlib_test_runner_code(&tests),
+        };
+
+        self.as_python_executable_starlark(
+            env,
+            call_stack,
+            name,
+            resources_policy,
+            &Value::new(config),
+            extension_module_filter,
+            &Value::new(None),
+            &Value::new(true),
+            &Value::new(true),
+            &Value::new(true),
+            &Value::new(false),
+            &Value::new(false),
+            &Value::new(false),
+        )
+    }
+
     /// PythonDistribution.extension_modules(filter="all", preferred_variants=None)
     pub fn extension_modules(
         &mut self,
@@ -453,18 +941,49 @@ impl PythonDistribution {
         ))
     }
 
-    /// PythonDistribution.pip_install(args, extra_envs=None)
+    /// PythonDistribution.pip_install(args, extra_envs=None, prefer_binary=false, only_binary=None, no_binary=None, wheel_dir=None, no_index=false)
+    #[allow(clippy::too_many_arguments)]
     pub fn pip_install(
         &mut self,
         env: &Environment,
         args: &Value,
         extra_envs: &Value,
+        prefer_binary: &Value,
+        only_binary: &Value,
+        no_binary: &Value,
+        wheel_dir: &Value,
+        no_index: &Value,
     ) -> ValueResult {
         required_list_arg("args", "string", &args)?;
         optional_dict_arg("extra_envs", "string", "string", &extra_envs)?;
+        let prefer_binary = required_bool_arg("prefer_binary", &prefer_binary)?;
+        optional_list_arg("only_binary", "string", &only_binary)?;
+        optional_list_arg("no_binary", "string", &no_binary)?;
+        let index_args = Self::pip_index_args(env, wheel_dir, no_index, "pip_install()")?;
 
         let args: Vec<String> = args.into_iter()?.map(|x| x.to_string()).collect();
 
+        let package_names = |value: &Value| -> Result<Vec<String>, ValueError> {
+            Ok(match value.get_type() {
+                "list" => value.into_iter()?.map(|x| x.to_string()).collect(),
+                _ => Vec::new(),
+            })
+        };
+
+        let policy = BinaryPolicy::new(
+            prefer_binary,
+            &package_names(&only_binary)?,
+            &package_names(&no_binary)?,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "pip_install()".to_string(),
+            }
+            .into())
+        })?;
+
         let extra_envs = match extra_envs.get_type() {
             "dict" => extra_envs
                 .into_iter()?
@@ -479,8 +998,8 @@ impl PythonDistribution {
         };
 
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
-        let (logger, verbose) =
-            context.downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose));
+        let (logger, verbose, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose, x.strict));
 
         self.ensure_distribution_resolved(&logger).or_else(|e| {
             Err(RuntimeError {
@@ -492,80 +1011,716 @@ impl PythonDistribution {
         })?;
         let dist = self.distribution.as_ref().unwrap();
 
-        let resources =
-            raw_pip_install(&logger, dist.deref().as_ref(), verbose, &args, &extra_envs).or_else(
-                |e| {
-                    Err(RuntimeError {
-                        code: "PIP_INSTALL_ERROR",
-                        message: format!("error running pip install: {}", e),
-                        label: "pip_install()".to_string(),
-                    }
-                    .into())
-                },
-            )?;
-
-        Ok(Value::from(
-            resources
-                .iter()
-                .map(python_resource_to_value)
-                .collect::<Vec<Value>>(),
-        ))
-    }
+        let mut pip_args = policy.pip_args(dist.deref().as_ref()).or_else(|e| {
+            Err(RuntimeError {
+                code: "PIP_INSTALL_ERROR",
+                message: e.to_string(),
+                label: "pip_install()".to_string(),
+            }
+            .into())
+        })?;
+        pip_args.extend(index_args);
+        pip_args.extend(args);
 
-    /// PythonDistribution.read_package_root(path, packages)
-    pub fn read_package_root(
-        &mut self,
-        env: &Environment,
-        path: &Value,
-        packages: &Value,
-    ) -> ValueResult {
-        let path = required_str_arg("path", &path)?;
-        required_list_arg("packages", "string", &packages)?;
+        // Local projects and requirements files affect what gets installed.
+        record_pip_source_paths(env, &pip_args);
 
-        let packages = packages
-            .into_iter()?
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>();
+        let operation = format!("pip install {}", pip_args.join(" "));
+        let key = if extra_envs.is_empty() {
+            operation.clone()
+        } else {
+            format!(
+                "{} with {:?}",
+                operation,
+                extra_envs.iter().collect::<BTreeMap<_, _>>()
+            )
+        };
 
-        let context = env.get("CONTEXT").expect("CONTEXT not defined");
-        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+        if let Some(value) = self.reuse_resources(&logger, &key) {
+            return Ok(value);
+        }
 
-        self.ensure_distribution_resolved(&logger).or_else(|e| {
+        let resources = raw_pip_install(
+            &logger,
+            dist.deref().as_ref(),
+            verbose,
+            &pip_args,
+            &self.pip_envs(extra_envs),
+            strict,
+        )
+        .or_else(|e| {
             Err(RuntimeError {
-                code: "PYOXIDIZER_BUILD",
-                message: e.to_string(),
-                label: "resolve_distribution()".to_string(),
+                code: "PIP_INSTALL_ERROR",
+                message: format!("error running pip install: {}", e),
+                label: "pip_install()".to_string(),
             }
             .into())
         })?;
 
-        let dist = self.distribution.as_ref().unwrap();
+        record_packaging_operation(env, operation, resources.len());
 
-        let resources = find_resources(&logger, dist.deref().as_ref(), Path::new(&path), None)
-            .or_else(|e| {
-                Err(RuntimeError {
-                    code: "PACKAGE_ROOT_ERROR",
-                    message: format!("could not find resources: {}", e),
-                    label: "read_package_root()".to_string(),
-                }
-                .into())
-            })?;
+        Ok(self.collect_resources(key, resources))
+    }
 
-        Ok(Value::from(
-            resources
-                .iter()
-                .filter(|x| x.is_in_packages(&packages))
-                .map(python_resource_to_value)
+    /// PythonDistribution.compile_source(source, filename, optimize=1)
+    pub fn compile_source(
+        &mut self,
+        env: &Environment,
+        source: &Value,
+        filename: &Value,
+        optimize: &Value,
+    ) -> ValueResult {
+        let source = required_str_arg("source", &source)?;
+        let filename = required_str_arg("filename", &filename)?;
+        required_type_arg("optimize", "int", &optimize)?;
+
+        let optimize_level = match optimize.to_int().unwrap() {
+            0 => BytecodeOptimizationLevel::Zero,
+            1 => BytecodeOptimizationLevel::One,
+            2 => BytecodeOptimizationLevel::Two,
+            i => {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!("optimize must be 0, 1, or 2: got {}", i),
+                    label: "invalid optimize value".to_string(),
+                }
+                .into());
+            }
+        };
+
+        let (name, is_package) =
+            module_name_from_filename(&filename).ok_or_else(|| RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!(
+                    "filename must be a relative path to a .py file: got {}",
+                    filename
+                ),
+                label: "compile_source()".to_string(),
+            })?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        let bytecode = self
+            .compile_bytecode(
+                &logger,
+                source.as_bytes(),
+                &filename,
+                optimize_level,
+                CompileMode::Bytecode,
+            )
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "compile_source()".to_string(),
+                }
+                .into())
+            })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        Ok(Value::new(PythonBytecodeModule {
+            module: PythonModuleBytecodeFromSource {
+                name,
+                source: DataLocation::Memory(source.into_bytes()),
+                optimize_level,
+                is_package,
+                cache_tag: dist.cache_tag().to_string(),
+            },
+            bytecode: Some(bytecode),
+        }))
+    }
+
+    /// PythonDistribution.compile_jinja2_templates(path, package, jinja2_requirement="jinja2", extensions=None)
+    pub fn compile_jinja2_templates(
+        &mut self,
+        env: &Environment,
+        path: &Value,
+        package: &Value,
+        jinja2_requirement: &Value,
+        extensions: &Value,
+    ) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+        let package = required_str_arg("package", &package)?;
+        let jinja2_requirement = required_str_arg("jinja2_requirement", &jinja2_requirement)?;
+        optional_list_arg("extensions", "string", &extensions)?;
+
+        let extensions = match extensions.get_type() {
+            "list" => extensions.into_iter()?.map(|x| x.to_string()).collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("should have validated type above"),
+        };
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let cwd = env.get("CWD").expect("CWD not defined").to_string();
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        let path = PathBuf::from(cwd).join(path);
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let modules = raw_compile_jinja2_templates(
+            &logger,
+            dist.deref().as_ref(),
+            &path,
+            &package,
+            &jinja2_requirement,
+            &extensions,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "JINJA2_ERROR",
+                message: format!("error compiling templates: {}", e),
+                label: "compile_jinja2_templates()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::from(
+            modules
+                .into_iter()
+                .map(|module| Value::new(PythonSourceModule { module }))
+                .collect_vec(),
+        ))
+    }
+
+    /// PythonDistribution.compile_protobuf(sources, include_dirs=None, grpc=false, protoc=None, grpcio_tools_requirement="grpcio-tools")
+    #[allow(clippy::too_many_arguments)]
+    pub fn compile_protobuf(
+        &mut self,
+        env: &Environment,
+        sources: &Value,
+        include_dirs: &Value,
+        grpc: &Value,
+        protoc: &Value,
+        grpcio_tools_requirement: &Value,
+    ) -> ValueResult {
+        required_list_arg("sources", "string", &sources)?;
+        optional_list_arg("include_dirs", "string", &include_dirs)?;
+        let grpc = required_bool_arg("grpc", &grpc)?;
+        let protoc = optional_str_arg("protoc", &protoc)?;
+        let grpcio_tools_requirement =
+            required_str_arg("grpcio_tools_requirement", &grpcio_tools_requirement)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, cwd, build_path, strict) = context.downcast_apply(|x: &EnvironmentContext| {
+            (
+                x.logger.clone(),
+                x.cwd.clone(),
+                x.build_path.clone(),
+                x.strict,
+            )
+        });
+
+        let sources = sources
+            .into_iter()?
+            .map(|x| cwd.join(x.to_string()))
+            .collect::<Vec<PathBuf>>();
+
+        let include_dirs = match include_dirs.get_type() {
+            "list" => include_dirs
+                .into_iter()?
+                .map(|x| cwd.join(x.to_string()))
+                .collect(),
+            "NoneType" => vec![cwd.clone()],
+            _ => panic!("should have validated type above"),
+        };
+
+        let compiler = match protoc {
+            Some(_) if grpc => {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: "grpc can't be used with protoc".to_string(),
+                    label: "compile_protobuf()".to_string(),
+                }
+                .into());
+            }
+            // Executables without a directory are searched in PATH.
+            Some(protoc) if Path::new(&protoc).components().count() > 1 => {
+                ProtobufCompiler::Protoc(cwd.join(protoc))
+            }
+            Some(protoc) => ProtobufCompiler::Protoc(PathBuf::from(protoc)),
+            None => {
+                // grpcio-tools is installed from package indexes.
+                self.ensure_online("compile_protobuf()")?;
+
+                ProtobufCompiler::GrpcioTools {
+                    requirement: grpcio_tools_requirement,
+                    grpc,
+                }
+            }
+        };
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let resources = raw_compile_protobuf(
+            &logger,
+            dist.deref().as_ref(),
+            &compiler,
+            &sources,
+            &include_dirs,
+            &build_path.join("protobuf"),
+            strict,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PROTOBUF_ERROR",
+                message: format!("error compiling .proto files: {}", e),
+                label: "compile_protobuf()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .map(python_resource_to_value)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonDistribution.ensure_pip(version=None, setuptools_version=None, wheel_version=None)
+    pub fn ensure_pip(
+        &mut self,
+        env: &Environment,
+        version: &Value,
+        setuptools_version: &Value,
+        wheel_version: &Value,
+    ) -> ValueResult {
+        let mut requirements = Vec::new();
+
+        for (name, arg, value) in &[
+            ("pip", "version", version),
+            ("setuptools", "setuptools_version", setuptools_version),
+            ("wheel", "wheel_version", wheel_version),
+        ] {
+            if let Some(version) = optional_str_arg(arg, value)? {
+                if version.is_empty()
+                    || !version
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || ".+!-_".contains(c))
+                {
+                    return Err(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!("{} {} is not a valid version", arg, version),
+                        label: "ensure_pip()".to_string(),
+                    }
+                    .into());
+                }
+
+                requirements.push(format!("{}=={}", name, version));
+            }
+        }
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap().clone();
+
+        if !requirements.is_empty() {
+            let path = ensure_packaging_toolchain(
+                &logger,
+                dist.deref().as_ref(),
+                &self.dest_dir,
+                &requirements,
+                &self.pip_envs(HashMap::new()),
+                self.offline,
+            )
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PIP_INSTALL_ERROR",
+                    message: format!("error installing packaging tools: {}", e),
+                    label: "ensure_pip()".to_string(),
+                }
+                .into())
+            })?;
+
+            self.packaging_toolchain_path = Some(path);
+            self.packaging_tool_requirements = requirements;
+        } else {
+            dist.ensure_pip(&logger).or_else(|e| {
+                Err(RuntimeError {
+                    code: "PIP_INSTALL_ERROR",
+                    message: format!("error installing pip: {}", e),
+                    label: "ensure_pip()".to_string(),
+                }
+                .into())
+            })?;
+        }
+
+        let versions =
+            packaging_tool_versions(dist.deref().as_ref(), &self.pip_envs(HashMap::new()))
+                .or_else(|e| {
+                    Err(RuntimeError {
+                        code: "PYOXIDIZER_BUILD",
+                        message: e.to_string(),
+                        label: "ensure_pip()".to_string(),
+                    }
+                    .into())
+                })?;
+
+        Ok(Value::from(
+            versions
+                .into_iter()
+                .map(Value::from)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonDistribution.pip_install_requirements(path, require_hashes=true, wheel_dir=None, no_index=false)
+    pub fn pip_install_requirements(
+        &mut self,
+        env: &Environment,
+        path: &Value,
+        require_hashes: &Value,
+        wheel_dir: &Value,
+        no_index: &Value,
+    ) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+        let require_hashes = required_bool_arg("require_hashes", &require_hashes)?;
+        let index_args =
+            Self::pip_index_args(env, wheel_dir, no_index, "pip_install_requirements()")?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let cwd = env.get("CWD").expect("CWD not defined").to_string();
+        let (logger, verbose, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose, x.strict));
+
+        let path = PathBuf::from(cwd).join(path);
+
+        // The requirements file and local paths it references affect what
+        // gets installed.
+        record_source_path(env, &path);
+        if let Ok(requirements) = RequirementsFile::from_path(&path) {
+            let args = requirements
+                .requirements
+                .iter()
+                .map(|r| r.specifier.clone())
+                .chain(requirements.options.iter().cloned())
+                .collect::<Vec<_>>();
+            record_pip_source_paths(env, &args);
+        }
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        // Resources installed without verifying hashes aren't reused when
+        // hashes are required.
+        let mut operation_args = index_args.clone();
+        if require_hashes {
+            operation_args.push("--require-hashes".to_string());
+        }
+        operation_args.push("-r".to_string());
+        operation_args.push(path.display().to_string());
+        let operation = format!("pip install {}", operation_args.join(" "));
+        if let Some(value) = self.reuse_resources(&logger, &operation) {
+            return Ok(value);
+        }
+
+        let resources = raw_pip_install_requirements(
+            &logger,
+            dist.deref().as_ref(),
+            verbose,
+            &path,
+            require_hashes,
+            &index_args,
+            &self.pip_envs(HashMap::new()),
+            strict,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PIP_INSTALL_ERROR",
+                message: format!("error installing requirements: {}", e),
+                label: "pip_install_requirements()".to_string(),
+            }
+            .into())
+        })?;
+
+        record_packaging_operation(env, operation.clone(), resources.len());
+
+        Ok(self.collect_resources(operation, resources))
+    }
+
+    /// PythonDistribution.pip_download(args)
+    pub fn pip_download(&mut self, env: &Environment, args: &Value) -> ValueResult {
+        required_list_arg("args", "string", &args)?;
+        self.ensure_online("pip_download()")?;
+
+        let args: Vec<String> = args.into_iter()?.map(|x| x.to_string()).collect();
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, verbose, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose, x.strict));
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let operation = format!("pip download {}", args.join(" "));
+        if let Some(value) = self.reuse_resources(&logger, &operation) {
+            return Ok(value);
+        }
+
+        let resources = raw_pip_download(
+            &logger,
+            dist.deref().as_ref(),
+            verbose,
+            &args,
+            &self.pip_envs(HashMap::new()),
+            strict,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PIP_DOWNLOAD_ERROR",
+                message: format!("error running pip download: {}", e),
+                label: "pip_download()".to_string(),
+            }
+            .into())
+        })?;
+
+        record_packaging_operation(env, operation.clone(), resources.len());
+
+        Ok(self.collect_resources(operation, resources))
+    }
+
+    /// PythonDistribution.read_wheel(path)
+    pub fn read_wheel(&mut self, env: &Environment, path: &Value) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, cwd, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.cwd.clone(), x.strict));
+
+        let path = cwd.join(path);
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let resources =
+            raw_read_wheel(&logger, dist.deref().as_ref(), &path, strict).or_else(|e| {
+                Err(RuntimeError {
+                    code: "WHEEL_ERROR",
+                    message: format!("error reading wheel: {}", e),
+                    label: "read_wheel()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .map(python_resource_to_value)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonDistribution.read_wheels(pattern)
+    pub fn read_wheels(&mut self, env: &Environment, pattern: &Value) -> ValueResult {
+        let pattern = required_str_arg("pattern", &pattern)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, cwd, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.cwd.clone(), x.strict));
+
+        let mut paths = evaluate_glob(&cwd, &pattern).or_else(|e| {
+            Err(RuntimeError {
+                code: "WHEEL_ERROR",
+                message: e.to_string(),
+                label: "read_wheels()".to_string(),
+            }
+            .into())
+        })?;
+        paths.sort();
+
+        if paths.is_empty() {
+            return Err(RuntimeError {
+                code: "WHEEL_ERROR",
+                message: format!("no wheels match {}", pattern),
+                label: "read_wheels()".to_string(),
+            }
+            .into());
+        }
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let mut resources = Vec::new();
+
+        for path in paths {
+            resources.extend(
+                raw_read_wheel(&logger, dist.deref().as_ref(), &path, strict).or_else(|e| {
+                    Err(RuntimeError {
+                        code: "WHEEL_ERROR",
+                        message: format!("error reading wheel: {}", e),
+                        label: "read_wheels()".to_string(),
+                    }
+                    .into())
+                })?,
+            );
+        }
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .map(python_resource_to_value)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonDistribution.read_package_root(path, packages)
+    pub fn read_package_root(
+        &mut self,
+        env: &Environment,
+        path: &Value,
+        packages: &Value,
+    ) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+        required_list_arg("packages", "string", &packages)?;
+
+        let packages = packages
+            .into_iter()?
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, strict) =
+            context.downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.strict));
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+
+        let dist = self.distribution.as_ref().unwrap();
+
+        let resources = find_resources(
+            &logger,
+            dist.deref().as_ref(),
+            Path::new(&path),
+            None,
+            strict,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PACKAGE_ROOT_ERROR",
+                message: format!("could not find resources: {}", e),
+                label: "read_package_root()".to_string(),
+            }
+            .into())
+        })?;
+
+        record_source_path(env, Path::new(&path));
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .filter(|x| x.is_in_packages(&packages))
+                .map(python_resource_to_value)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonDistribution.read_virtualenv(path)
+    pub fn read_virtualenv(&mut self, env: &Environment, path: &Value) -> ValueResult {
+        let path = required_str_arg("path", &path)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, strict) =
+            context.downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.strict));
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let resources =
+            raw_read_virtualenv(&logger, dist.deref().as_ref(), &Path::new(&path), strict)
+                .or_else(|e| {
+                    Err(RuntimeError {
+                        code: "VIRTUALENV_ERROR",
+                        message: format!("could not find resources: {}", e),
+                        label: "read_virtualenv()".to_string(),
+                    }
+                    .into())
+                })?;
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .map(python_resource_to_value)
                 .collect::<Vec<Value>>(),
         ))
     }
 
-    /// PythonDistribution.read_virtualenv(path)
-    pub fn read_virtualenv(&mut self, env: &Environment, path: &Value) -> ValueResult {
+    /// PythonDistribution.read_conda_env(path)
+    pub fn read_conda_env(&mut self, env: &Environment, path: &Value) -> ValueResult {
         let path = required_str_arg("path", &path)?;
 
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
-        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+        let (logger, cwd, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.cwd.clone(), x.strict));
+
+        let path = cwd.join(path);
 
         self.ensure_distribution_resolved(&logger).or_else(|e| {
             Err(RuntimeError {
@@ -577,12 +1732,12 @@ impl PythonDistribution {
         })?;
         let dist = self.distribution.as_ref().unwrap();
 
-        let resources = raw_read_virtualenv(&logger, dist.deref().as_ref(), &Path::new(&path))
-            .or_else(|e| {
+        let resources =
+            raw_read_conda_env(&logger, dist.deref().as_ref(), &path, strict).or_else(|e| {
                 Err(RuntimeError {
-                    code: "VIRTUALENV_ERROR",
+                    code: "CONDA_ERROR",
                     message: format!("could not find resources: {}", e),
-                    label: "read_virtualenv()".to_string(),
+                    label: "read_conda_env()".to_string(),
                 }
                 .into())
             })?;
@@ -595,9 +1750,15 @@ impl PythonDistribution {
         ))
     }
 
-    /// PythonDistribution.package_resources(include_test=false)
-    pub fn package_resources(&mut self, env: &Environment, include_test: &Value) -> ValueResult {
+    /// PythonDistribution.package_resources(include_test=false, exclude=None)
+    pub fn package_resources(
+        &mut self,
+        env: &Environment,
+        include_test: &Value,
+        exclude: &Value,
+    ) -> ValueResult {
         let include_test = required_bool_arg("include_test", &include_test)?;
+        let exclude = exclude_arg(exclude)?;
 
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
 
@@ -630,7 +1791,9 @@ impl PythonDistribution {
             resources
                 .iter()
                 .filter_map(|data| {
-                    if !include_test && is_stdlib_test_package(&data.leaf_package) {
+                    if (!include_test && is_stdlib_test_package(&data.leaf_package))
+                        || is_in_packages(&data.leaf_package, &exclude)
+                    {
                         None
                     } else {
                         Some(Value::new(PythonPackageResource { data: data.clone() }))
@@ -640,6 +1803,126 @@ impl PythonDistribution {
         ))
     }
 
+    /// PythonDistribution.build_wheel(project_path)
+    pub fn build_wheel(&mut self, env: &Environment, project_path: &Value) -> ValueResult {
+        let project_path = required_str_arg("project_path", &project_path)?;
+        // Build requirements are installed from package indexes.
+        self.ensure_online("build_wheel()")?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, cwd, verbose, strict) = context.downcast_apply(|x: &EnvironmentContext| {
+            (x.logger.clone(), x.cwd.clone(), x.verbose, x.strict)
+        });
+
+        let project_path = cwd.join(project_path);
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let resources = raw_build_wheel(
+            &logger,
+            dist.deref().as_ref(),
+            verbose,
+            &project_path,
+            &self.pip_envs(HashMap::new()),
+            strict,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "WHEEL_ERROR",
+                message: format!("error building wheel: {}", e),
+                label: "build_wheel()".to_string(),
+            }
+            .into())
+        })?;
+
+        record_packaging_operation(
+            env,
+            format!("build wheel from {}", project_path.display()),
+            resources.len(),
+        );
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .map(python_resource_to_value)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonDistribution.poetry_install(project_path, no_dev=true)
+    pub fn poetry_install(
+        &mut self,
+        env: &Environment,
+        project_path: &Value,
+        no_dev: &Value,
+    ) -> ValueResult {
+        let project_path = required_str_arg("project_path", &project_path)?;
+        let no_dev = required_bool_arg("no_dev", &no_dev)?;
+        self.ensure_online("poetry_install()")?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let cwd = env.get("CWD").expect("CWD not defined").to_string();
+        let (logger, verbose, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose, x.strict));
+
+        let project_path = PathBuf::from(cwd).join(project_path);
+
+        self.ensure_distribution_resolved(&logger).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "resolve_distribution()".to_string(),
+            }
+            .into())
+        })?;
+        let dist = self.distribution.as_ref().unwrap();
+
+        let resources = raw_poetry_install(
+            &logger,
+            dist.deref().as_ref(),
+            verbose,
+            &project_path,
+            no_dev,
+            &self.pip_envs(HashMap::new()),
+            strict,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "POETRY_INSTALL_ERROR",
+                message: format!("error installing poetry project: {}", e),
+                label: "poetry_install()".to_string(),
+            }
+            .into())
+        })?;
+
+        warn!(
+            logger,
+            "collected {} resources from poetry project",
+            resources.len()
+        );
+
+        record_packaging_operation(
+            env,
+            format!("poetry install {}", project_path.display()),
+            resources.len(),
+        );
+
+        Ok(Value::from(
+            resources
+                .iter()
+                .map(python_resource_to_value)
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
     /// PythonDistribution.setup_py_install(package_path, extra_envs=None, extra_global_arguments=None)
     pub fn setup_py_install(
         &mut self,
@@ -677,8 +1960,8 @@ impl PythonDistribution {
 
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
         let cwd = env.get("CWD").expect("CWD not defined").to_string();
-        let (logger, verbose) =
-            context.downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose));
+        let (logger, verbose, strict) = context
+            .downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.verbose, x.strict));
 
         let package_path = if package_path.is_absolute() {
             package_path
@@ -701,8 +1984,9 @@ impl PythonDistribution {
             dist.deref().as_ref(),
             &package_path,
             verbose,
-            &extra_envs,
+            &self.pip_envs(extra_envs),
             &extra_global_arguments,
+            strict,
         )
         .or_else(|e| {
             Err(RuntimeError {
@@ -719,6 +2003,13 @@ impl PythonDistribution {
             resources.len()
         );
 
+        record_packaging_operation(
+            env,
+            format!("setup.py install {}", package_path.display()),
+            resources.len(),
+        );
+        record_source_path(env, &package_path);
+
         Ok(Value::from(
             resources
                 .iter()
@@ -727,8 +2018,10 @@ impl PythonDistribution {
         ))
     }
 
-    /// PythonDistribution.source_modules()
-    pub fn source_modules(&mut self, env: &Environment) -> ValueResult {
+    /// PythonDistribution.source_modules(exclude=None)
+    pub fn source_modules(&mut self, env: &Environment, exclude: &Value) -> ValueResult {
+        let exclude = exclude_arg(exclude)?;
+
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
 
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
@@ -759,6 +2052,7 @@ impl PythonDistribution {
         Ok(Value::from(
             modules
                 .iter()
+                .filter(|module| !is_in_packages(&module.name, &exclude))
                 .map(|module| {
                     Value::new(PythonSourceModule {
                         module: module.clone(),
@@ -783,23 +2077,133 @@ starlark_module! { python_distribution_module =>
     }
 
     #[allow(clippy::ptr_arg)]
-    PythonDistribution.source_modules(env env, this) {
+    PythonDistribution.source_modules(env env, this, exclude=None) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.source_modules(&env, &exclude)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.package_resources(env env, this, include_test=false, exclude=None) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.package_resources(&env, &include_test, &exclude)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.pip_install(
+        env env,
+        this,
+        args,
+        extra_envs=None,
+        prefer_binary=false,
+        only_binary=None,
+        no_binary=None,
+        wheel_dir=None,
+        no_index=false
+    ) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.pip_install(
+                &env,
+                &args,
+                &extra_envs,
+                &prefer_binary,
+                &only_binary,
+                &no_binary,
+                &wheel_dir,
+                &no_index,
+            )
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.compile_source(env env, this, source, filename, optimize=1) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.compile_source(&env, &source, &filename, &optimize)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.compile_jinja2_templates(
+        env env,
+        this,
+        path,
+        package,
+        jinja2_requirement="jinja2",
+        extensions=None
+    ) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.compile_jinja2_templates(&env, &path, &package, &jinja2_requirement, &extensions)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.compile_protobuf(
+        env env,
+        this,
+        sources,
+        include_dirs=None,
+        grpc=false,
+        protoc=None,
+        grpcio_tools_requirement="grpcio-tools"
+    ) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.compile_protobuf(
+                &env,
+                &sources,
+                &include_dirs,
+                &grpc,
+                &protoc,
+                &grpcio_tools_requirement,
+            )
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.ensure_pip(
+        env env,
+        this,
+        version=None,
+        setuptools_version=None,
+        wheel_version=None
+    ) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.ensure_pip(&env, &version, &setuptools_version, &wheel_version)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.pip_install_requirements(
+        env env,
+        this,
+        path,
+        require_hashes=true,
+        wheel_dir=None,
+        no_index=false
+    ) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.pip_install_requirements(&env, &path, &require_hashes, &wheel_dir, &no_index)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.pip_download(env env, this, args) {
         this.downcast_apply_mut(|dist: &mut PythonDistribution| {
-            dist.source_modules(&env)
+            dist.pip_download(&env, &args)
         })
     }
 
     #[allow(clippy::ptr_arg)]
-    PythonDistribution.package_resources(env env, this, include_test=false) {
+    PythonDistribution.read_wheel(env env, this, path) {
         this.downcast_apply_mut(|dist: &mut PythonDistribution| {
-            dist.package_resources(&env, &include_test)
+            dist.read_wheel(&env, &path)
         })
     }
 
     #[allow(clippy::ptr_arg)]
-    PythonDistribution.pip_install(env env, this, args, extra_envs=None) {
+    PythonDistribution.read_wheels(env env, this, pattern) {
         this.downcast_apply_mut(|dist: &mut PythonDistribution| {
-            dist.pip_install(&env, &args, &extra_envs)
+            dist.read_wheels(&env, &pattern)
         })
     }
 
@@ -826,6 +2230,27 @@ starlark_module! { python_distribution_module =>
         })
     }
 
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.read_conda_env(env env, this, path) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.read_conda_env(&env, &path)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.build_wheel(env env, this, project_path) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.build_wheel(&env, &project_path)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonDistribution.poetry_install(env env, this, project_path, no_dev=true) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.poetry_install(&env, &project_path, &no_dev)
+        })
+    }
+
     #[allow(clippy::ptr_arg)]
     PythonDistribution.setup_py_install(
         env env,
@@ -851,7 +2276,12 @@ starlark_module! { python_distribution_module =>
         preferred_extension_module_variants=None,
         include_sources=true,
         include_resources=false,
-        include_test=false
+        include_test=false,
+        allow_in_memory_shared_library_loading=false,
+        extract_shared_libraries_to_cache=false,
+        include_type_stubs=false,
+        debug_build=false,
+        compress_resources=false
     ) {
         this.downcast_apply_mut(|dist: &mut PythonDistribution| {
             dist.as_python_executable_starlark(
@@ -865,13 +2295,40 @@ starlark_module! { python_distribution_module =>
                 &include_sources,
                 &include_resources,
                 &include_test,
+                &allow_in_memory_shared_library_loading,
+                &extract_shared_libraries_to_cache,
+                &include_type_stubs,
+                &debug_build,
+                &compress_resources,
+            )
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonDistribution.to_stdlib_test_executable(
+        env env,
+        call_stack call_stack,
+        this,
+        name,
+        tests=None,
+        resources_policy="filesystem-relative-only:lib",
+        extension_module_filter="all"
+    ) {
+        this.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            dist.as_stdlib_test_executable_starlark(
+                env.clone(),
+                call_stack,
+                &name,
+                &tests,
+                &resources_policy,
+                &extension_module_filter,
             )
         })
     }
 
     #[allow(clippy::ptr_arg)]
-    default_python_distribution(env env, flavor="standalone", build_target=None) {
-        PythonDistribution::default_python_distribution(&env, &flavor, &build_target)
+    default_python_distribution(env env, flavor="standalone", build_target=None, python_version=None) {
+        PythonDistribution::default_python_distribution(&env, &flavor, &build_target, &python_version)
     }
 }
 
@@ -883,14 +2340,155 @@ mod tests {
     };
 
     #[test]
-    fn test_default_python_distribution() {
-        let dist = starlark_ok("default_python_distribution()");
+    fn test_default_python_distribution() {
+        let dist = starlark_ok("default_python_distribution()");
+        assert_eq!(dist.get_type(), "PythonDistribution");
+
+        let host_distribution = PYTHON_DISTRIBUTIONS
+            .find_distribution(
+                crate::project_building::HOST,
+                &DistributionFlavor::Standalone,
+                None,
+            )
+            .unwrap();
+
+        dist.downcast_apply(|x: &PythonDistribution| {
+            assert_eq!(x.source, host_distribution.location)
+        });
+    }
+
+    #[test]
+    fn test_default_python_distribution_platform() {
+        let dist = starlark_ok("default_python_distribution(build_target=BUILD_TARGET)");
+        assert_eq!(dist.get_type(), "PythonDistribution");
+
+        let err = starlark_nok("default_python_distribution(build_target=42)");
+        assert_eq!(
+            err.message,
+            "function expects an optional string or Platform for build_target; got type int"
+        );
+    }
+
+    #[test]
+    fn test_default_python_distribution_python_version() {
+        let dist = starlark_ok("default_python_distribution(python_version='3.8')");
+        assert_eq!(dist.get_type(), "PythonDistribution");
+
+        let err = starlark_nok("default_python_distribution(python_version='2.7')");
+        assert_eq!(
+            err.message,
+            format!(
+                "could not find default Python 2.7 distribution for {}; available versions: 3.8",
+                crate::project_building::HOST
+            )
+        );
+    }
+
+    #[test]
+    fn test_default_python_distribution_bad_arg() {
+        let err = starlark_nok("default_python_distribution(False)");
+        assert_eq!(
+            err.message,
+            "function expects a string for flavor; got type bool"
+        );
+    }
+
+    #[test]
+    fn test_offline_network_operations() {
+        let mut env = starlark_env();
+        let mut context = env.get("CONTEXT").unwrap();
+        context.downcast_apply_mut(|x: &mut EnvironmentContext| x.offline = true);
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+
+        let err = starlark_eval_in_env(&mut env, "dist.pip_download(['pyflakes'])").unwrap_err();
+        assert_eq!(
+            err.message,
+            "pip_download() requires network access, which is disabled in offline mode"
+        );
+
+        let err = starlark_eval_in_env(&mut env, "dist.poetry_install('.')").unwrap_err();
+        assert_eq!(
+            err.message,
+            "poetry_install() requires network access, which is disabled in offline mode"
+        );
+
+        let err =
+            starlark_eval_in_env(&mut env, "dist.compile_protobuf(['foo.proto'])").unwrap_err();
+        assert_eq!(
+            err.message,
+            "compile_protobuf() requires network access, which is disabled in offline mode"
+        );
+    }
+
+    #[test]
+    fn test_pip_envs_cache() {
+        let mut env = starlark_env();
+        let dist = starlark_eval_in_env(&mut env, "default_python_distribution()").unwrap();
+        let envs = dist.downcast_apply(|x: &PythonDistribution| x.pip_envs(HashMap::new()));
+        assert_eq!(envs.get("PIP_NO_CACHE_DIR"), Some(&"1".to_string()));
+        assert!(!envs.contains_key("PIP_CACHE_DIR"));
+
+        let mut context = env.get("CONTEXT").unwrap();
+        context.downcast_apply_mut(|x: &mut EnvironmentContext| {
+            x.pip_cache_path = Some(PathBuf::from("/cache/pip"))
+        });
+
+        let dist = starlark_eval_in_env(&mut env, "default_python_distribution()").unwrap();
+        let envs = dist.downcast_apply(|x: &PythonDistribution| x.pip_envs(HashMap::new()));
+        assert_eq!(envs.get("PIP_CACHE_DIR"), Some(&"/cache/pip".to_string()));
+        assert!(!envs.contains_key("PIP_NO_CACHE_DIR"));
+    }
+
+    #[test]
+    fn test_ensure_pip_invalid_version() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+
+        let err =
+            starlark_eval_in_env(&mut env, "dist.ensure_pip(version='20.3 --pre')").unwrap_err();
+        assert_eq!(err.message, "version 20.3 --pre is not a valid version");
+
+        let err =
+            starlark_eval_in_env(&mut env, "dist.ensure_pip(setuptools_version='')").unwrap_err();
+        assert_eq!(err.message, "setuptools_version  is not a valid version");
+    }
+
+    #[test]
+    fn test_pip_envs_packaging_toolchain() {
+        let mut env = starlark_env();
+        let mut dist = starlark_eval_in_env(&mut env, "default_python_distribution()").unwrap();
+        dist.downcast_apply_mut(|x: &mut PythonDistribution| {
+            x.packaging_toolchain_path = Some(PathBuf::from("/toolchain"))
+        });
+
+        let envs = dist.downcast_apply(|x: &PythonDistribution| x.pip_envs(HashMap::new()));
+        assert_eq!(envs.get("PYTHONPATH"), Some(&"/toolchain".to_string()));
+        assert_eq!(
+            envs.get("SETUPTOOLS_USE_DISTUTILS"),
+            Some(&"stdlib".to_string())
+        );
+
+        let mut extra_envs = HashMap::new();
+        extra_envs.insert("PYTHONPATH".to_string(), "/extra".to_string());
+        let envs = dist.downcast_apply(|x: &PythonDistribution| x.pip_envs(extra_envs.clone()));
+        assert_eq!(
+            envs.get("PYTHONPATH"),
+            Some(&join_search_paths(&["/toolchain", "/extra"]).unwrap())
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_default_python_distribution_dynamic_windows() {
+        let dist = starlark_ok("default_python_distribution(flavor='standalone_dynamic')");
         assert_eq!(dist.get_type(), "PythonDistribution");
 
         let host_distribution = PYTHON_DISTRIBUTIONS
             .find_distribution(
                 crate::project_building::HOST,
-                &DistributionFlavor::Standalone,
+                &DistributionFlavor::StandaloneDynamic,
+                None,
             )
             .unwrap();
 
@@ -900,29 +2498,61 @@ mod tests {
     }
 
     #[test]
-    fn test_default_python_distribution_bad_arg() {
-        let err = starlark_nok("default_python_distribution(False)");
+    fn test_default_python_distribution_fixture() {
+        let dist = starlark_ok("default_python_distribution(flavor='fixture')");
+        assert_eq!(dist.get_type(), "PythonDistribution");
+
+        dist.downcast_apply(|x: &PythonDistribution| {
+            assert_eq!(x.flavor, DistributionFlavor::Fixture);
+            assert_eq!(
+                x.source,
+                PythonDistributionLocation::Fixture {
+                    target_triple: crate::project_building::HOST.to_string(),
+                }
+            );
+        });
+
+        let names = starlark_ok("default_python_distribution(flavor='fixture').source_modules()")
+            .into_iter()
+            .unwrap()
+            .map(|m| m.get_attr("name").unwrap().to_string())
+            .collect::<Vec<String>>();
+        assert!(names.contains(&"json.decoder".to_string()));
+
+        let err =
+            starlark_nok("default_python_distribution(flavor='fixture', python_version='3.7')");
         assert_eq!(
             err.message,
-            "function expects a string for flavor; got type bool"
+            "fixture distributions provide Python 3.8.2; got 3.7"
         );
     }
 
     #[test]
-    #[cfg(windows)]
-    fn test_default_python_distribution_dynamic_windows() {
-        let dist = starlark_ok("default_python_distribution(flavor='standalone_dynamic')");
-        assert_eq!(dist.get_type(), "PythonDistribution");
+    fn test_fixture_to_python_executable() {
+        let exe = starlark_ok(
+            "default_python_distribution(flavor='fixture').to_python_executable('testapp')",
+        );
+        assert_eq!(exe.get_type(), "PythonExecutable");
 
-        let host_distribution = PYTHON_DISTRIBUTIONS
-            .find_distribution(
-                crate::project_building::HOST,
-                &DistributionFlavor::StandaloneDynamic,
-            )
-            .unwrap();
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert_eq!(exe.exe.name(), "testapp");
+            assert_eq!(exe.exe.python_version(), "3.8.2");
 
-        dist.downcast_apply(|x: &PythonDistribution| {
-            assert_eq!(x.source, host_distribution.location)
+            let sources = exe.exe.in_memory_module_sources();
+            assert!(sources.contains_key("json.decoder"));
+            assert!(sources.contains_key("encodings.utf_8"));
+            // Test packages are excluded by default.
+            assert!(!sources.contains_key("test.test_fixture"));
+        });
+
+        let exe = starlark_ok(
+            "default_python_distribution(flavor='fixture').to_python_executable('testapp', include_test=True)",
+        );
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert!(exe
+                .exe
+                .in_memory_module_sources()
+                .contains_key("test.test_fixture"));
         });
     }
 
@@ -968,12 +2598,104 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_module_name_from_filename() {
+        assert_eq!(
+            module_name_from_filename("foo.py"),
+            Some(("foo".to_string(), false))
+        );
+        assert_eq!(
+            module_name_from_filename("foo/bar.py"),
+            Some(("foo.bar".to_string(), false))
+        );
+        assert_eq!(
+            module_name_from_filename("foo/__init__.py"),
+            Some(("foo".to_string(), true))
+        );
+        assert_eq!(module_name_from_filename("foo.txt"), None);
+        assert_eq!(module_name_from_filename("/foo.py"), None);
+        assert_eq!(module_name_from_filename("foo/../bar.py"), None);
+    }
+
+    #[test]
+    fn test_compile_source() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "m = dist.compile_source('x = 1', 'generated/__init__.py')",
+        )
+        .unwrap();
+        let m = starlark_eval_in_env(&mut env, "m").unwrap();
+        assert_eq!(m.get_type(), "PythonBytecodeModule");
+        assert_eq!(m.get_attr("name").unwrap().to_string(), "generated");
+        assert_eq!(m.get_attr("optimize_level").unwrap().to_int().unwrap(), 1);
+        assert!(m.get_attr("is_package").unwrap().to_bool());
+        m.downcast_apply(|m: &PythonBytecodeModule| {
+            assert!(!m.bytecode.as_ref().unwrap().is_empty());
+        });
+
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(&mut env, "exe.add_python_resource(m)").unwrap();
+
+        // The compiler is recreated after failing.
+        assert!(starlark_eval_in_env(&mut env, "dist.compile_source('x = (', 'bad.py')").is_err());
+        starlark_eval_in_env(
+            &mut env,
+            "dist.compile_source('y = 2', 'good.py', optimize=0)",
+        )
+        .unwrap();
+
+        assert!(starlark_eval_in_env(&mut env, "dist.compile_source('x = 1', 'x.txt')").is_err());
+        assert!(
+            starlark_eval_in_env(&mut env, "dist.compile_source('x = 1', 'x.py', optimize=3)")
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_source_modules() {
         let mods = starlark_ok("default_python_distribution().source_modules()");
         assert_eq!(mods.get_type(), "list");
     }
 
+    #[test]
+    fn test_source_modules_exclude() {
+        let names = |code: &str| {
+            starlark_ok(code)
+                .into_iter()
+                .unwrap()
+                .map(|m| m.get_attr("name").unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        let all = names("default_python_distribution().source_modules()");
+        let excluded = names(
+            "default_python_distribution().source_modules(exclude=['tkinter', 'idlelib', 'distutils'])",
+        );
+
+        assert!(all.contains(&"tkinter.ttk".to_string()));
+        assert!(all.contains(&"distutils".to_string()));
+        assert!(excluded.len() < all.len());
+        assert!(!excluded.iter().any(|name| name == "distutils"
+            || name.starts_with("tkinter.")
+            || name.starts_with("idlelib.")));
+
+        let err = starlark_nok("default_python_distribution().source_modules(exclude='tkinter')");
+        assert!(err.message.contains("exclude"));
+    }
+
+    #[test]
+    fn test_package_resources_exclude() {
+        let data_default = starlark_ok("default_python_distribution().package_resources()");
+        let data_excluded = starlark_ok(
+            "default_python_distribution().package_resources(exclude=['idlelib', 'lib2to3'])",
+        );
+
+        assert!(data_excluded.length().unwrap() < data_default.length().unwrap());
+    }
+
     #[test]
     fn test_package_resources() {
         let data_default = starlark_ok("default_python_distribution().package_resources()");
@@ -986,6 +2708,91 @@ mod tests {
         assert!(default_length < data_length);
     }
 
+    #[test]
+    fn test_to_python_executable_allow_in_memory_shared_library_loading() {
+        let exe = starlark_ok(
+            "default_python_distribution().to_python_executable('testapp', allow_in_memory_shared_library_loading=True)",
+        );
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        let err = starlark_nok(
+            "default_python_distribution().to_python_executable('testapp', allow_in_memory_shared_library_loading='yes')",
+        );
+        assert_eq!(
+            err.message,
+            "function expects an optional bool for allow_in_memory_shared_library_loading; got type string"
+        );
+    }
+
+    #[test]
+    fn test_to_python_executable_extract_shared_libraries_to_cache() {
+        let exe = starlark_ok(
+            "default_python_distribution().to_python_executable('testapp', extract_shared_libraries_to_cache=True)",
+        );
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        let err = starlark_nok(
+            "default_python_distribution().to_python_executable('testapp', extract_shared_libraries_to_cache='yes')",
+        );
+        assert_eq!(
+            err.message,
+            "function expects an optional bool for extract_shared_libraries_to_cache; got type string"
+        );
+    }
+
+    #[test]
+    fn test_to_python_executable_debug_build() {
+        let exe = starlark_ok(
+            "default_python_distribution().to_python_executable('testapp', debug_build=True)",
+        );
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert_eq!(
+                exe.exe.python_resources_policy(),
+                &PythonResourcesPolicy::FilesystemRelativeOnly("lib".to_string())
+            );
+            assert!(exe.exe.in_memory_module_sources().is_empty());
+        });
+
+        let exe = starlark_ok(
+            "default_python_distribution().to_python_executable('testapp', resources_policy='prefer-in-memory-fallback-filesystem-relative:app', debug_build=True)",
+        );
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert_eq!(
+                exe.exe.python_resources_policy(),
+                &PythonResourcesPolicy::FilesystemRelativeOnly("app".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn test_to_python_executable_compress_resources() {
+        let exe = starlark_ok(
+            "default_python_distribution().to_python_executable('testapp', compress_resources=True)",
+        );
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        let err = starlark_nok(
+            "default_python_distribution().to_python_executable('testapp', compress_resources='yes')",
+        );
+        assert_eq!(
+            err.message,
+            "function expects an optional bool for compress_resources; got type string"
+        );
+    }
+
+    #[test]
+    fn test_to_stdlib_test_executable() {
+        let exe = starlark_ok(
+            "default_python_distribution().to_stdlib_test_executable('testapp', tests=['test_json'])",
+        );
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        let err = starlark_nok(
+            "default_python_distribution().to_stdlib_test_executable('testapp', tests='test_json')",
+        );
+        assert!(err.message.contains("tests"));
+    }
+
     #[test]
     fn test_pip_install_simple() {
         let resources =
@@ -1002,6 +2809,328 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_pip_install_binary_policy() {
+        let resources = starlark_ok(
+            "default_python_distribution().pip_install(['pyflakes==2.1.1'], prefer_binary=True, only_binary=['PyFlakes'])",
+        );
+        assert_eq!(resources.get_type(), "list");
+
+        let err = starlark_nok(
+            "default_python_distribution().pip_install(['foo'], only_binary=['foo_bar'], no_binary=['Foo.Bar'])",
+        );
+        assert_eq!(
+            err.message,
+            "packages can't be both binary only and no binary: foo-bar"
+        );
+    }
+
+    #[test]
+    fn test_executables_share_distribution() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(
+            &mut env,
+            "def make_exe(name):\n    dist = default_python_distribution()\n    exe = dist.to_python_executable(name)\n    exe.add_python_resources(dist.pip_install(['pyflakes==2.1.1']))\n    return exe",
+        )
+        .unwrap();
+
+        for name in &["server", "worker", "cli"] {
+            let exe = starlark_eval_in_env(&mut env, &format!("make_exe('{}')", name)).unwrap();
+            assert_eq!(exe.get_type(), "PythonExecutable");
+        }
+
+        let server_dist = starlark_eval_in_env(&mut env, "default_python_distribution()").unwrap();
+        let cli_dist = starlark_eval_in_env(&mut env, "default_python_distribution()").unwrap();
+        assert!(server_dist.downcast_apply(|a: &PythonDistribution| {
+            cli_dist.downcast_apply(|b: &PythonDistribution| {
+                Arc::ptr_eq(
+                    a.distribution.as_ref().unwrap(),
+                    b.distribution.as_ref().unwrap(),
+                )
+            })
+        }));
+
+        let context = env.get("CONTEXT").unwrap();
+        let operations =
+            context.downcast_apply(|x: &EnvironmentContext| x.packaging_operations.clone());
+        assert_eq!(operations.len(), 1);
+        assert!(operations[0].starts_with("pip install "));
+    }
+
+    #[test]
+    fn test_record_pip_source_paths() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("project"))?;
+        std::fs::write(root.join("requirements.txt"), "")?;
+        std::fs::write(root.join("constraints.txt"), "")?;
+
+        let env = starlark_env();
+        record_pip_source_paths(
+            &env,
+            &[
+                format!("{}[dev]", root.join("project").display()),
+                "-r".to_string(),
+                root.join("requirements.txt").display().to_string(),
+                format!("--constraint={}", root.join("constraints.txt").display()),
+                "--no-deps".to_string(),
+                "black==19.10b0".to_string(),
+                "https://example.com/foo.whl".to_string(),
+                root.join("missing").display().to_string(),
+            ],
+        );
+
+        let context = env.get("CONTEXT").unwrap();
+        context.downcast_apply(|x: &EnvironmentContext| {
+            assert_eq!(
+                x.source_paths,
+                vec![
+                    root.join("project"),
+                    root.join("requirements.txt"),
+                    root.join("constraints.txt"),
+                ]
+            );
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pip_install_requirements() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let path = temp_dir.path().join("requirements.txt");
+        std::fs::write(&path, "pyflakes==2.1.1\n")?;
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().pip_install_requirements({:?})",
+            path.display().to_string()
+        ));
+        assert!(err.message.contains("line 1: pyflakes: no --hash defined"));
+
+        let resources = starlark_ok(&format!(
+            "default_python_distribution().pip_install_requirements({:?}, require_hashes=False)",
+            path.display().to_string()
+        ));
+        assert!(resources
+            .into_iter()
+            .unwrap()
+            .any(|v| v.get_type() == "PythonSourceModule"
+                && v.downcast_apply(|x: &PythonSourceModule| x.module.name == "pyflakes")));
+
+        // Resources installed without hashes aren't reused when hashes are required.
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "dist.pip_install_requirements({:?}, require_hashes=False)",
+                path.display().to_string()
+            ),
+        )
+        .unwrap();
+        let err = starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "dist.pip_install_requirements({:?})",
+                path.display().to_string()
+            ),
+        )
+        .unwrap_err();
+        assert!(err.message.contains("line 1: pyflakes: no --hash defined"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resources_key() {
+        let dist = starlark_ok("default_python_distribution()");
+
+        dist.downcast_apply_mut(|dist: &mut PythonDistribution| {
+            assert_eq!(dist.resources_key("pip install foo"), "pip install foo");
+
+            // Resources collected with other versions of packaging tools
+            // aren't reused.
+            dist.packaging_tool_requirements =
+                vec!["pip==20.3.3".to_string(), "wheel==0.36.2".to_string()];
+            assert_eq!(
+                dist.resources_key("pip install foo"),
+                "pip install foo with pip==20.3.3 wheel==0.36.2"
+            );
+        });
+    }
+
+    #[test]
+    fn test_pip_install_wheel_dir() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let wheel_dir = temp_dir.path().join("wheels");
+        let path = temp_dir.path().join("requirements.txt");
+        std::fs::write(&path, "pyflakes==2.1.1\n")?;
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().pip_install(['pyflakes==2.1.1'], wheel_dir={:?})",
+            wheel_dir.display().to_string()
+        ));
+        assert!(err.message.contains("is not a directory"));
+
+        // Packages aren't found in an empty wheel cache without an index.
+        std::fs::create_dir(&wheel_dir)?;
+        let err = starlark_nok(&format!(
+            "default_python_distribution().pip_install(['pyflakes==2.1.1'], wheel_dir={:?}, no_index=True)",
+            wheel_dir.display().to_string()
+        ));
+        assert!(err.message.contains("error running pip install"));
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().pip_install_requirements({:?}, require_hashes=False, wheel_dir={:?}, no_index=True)",
+            path.display().to_string(),
+            wheel_dir.display().to_string()
+        ));
+        assert!(err.message.contains("error installing requirements"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_conda_env_invalid() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let path = temp_dir.path().display().to_string();
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().read_conda_env({:?})",
+            path
+        ));
+        assert!(err.message.contains("conda-meta directory not found"));
+
+        let meta = temp_dir.path().join("conda-meta");
+        std::fs::create_dir(&meta)?;
+        std::fs::write(
+            meta.join("python-2.7.18-h15b4118_1.json"),
+            r#"{"name": "python", "version": "2.7.18", "files": []}"#,
+        )?;
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().read_conda_env({:?})",
+            path
+        ));
+        assert!(err.message.contains("conda environment has Python 2.7.18"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_wheel() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let project_path = temp_dir.path();
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().build_wheel({:?})",
+            project_path.display().to_string()
+        ));
+        assert!(err
+            .message
+            .contains("contains neither pyproject.toml nor setup.py"));
+
+        std::fs::create_dir(project_path.join("foo"))?;
+        std::fs::write(project_path.join("foo").join("__init__.py"), "")?;
+        std::fs::write(
+            project_path.join("setup.py"),
+            "from setuptools import setup\nsetup(name='foo', version='1.0', packages=['foo'])\n",
+        )?;
+
+        let resources = starlark_ok(&format!(
+            "default_python_distribution().build_wheel({:?})",
+            project_path.display().to_string()
+        ));
+        assert!(resources
+            .into_iter()
+            .unwrap()
+            .any(|v| v.get_type() == "PythonSourceModule"
+                && v.downcast_apply(|x: &PythonSourceModule| x.module.name == "foo")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_poetry_install_not_poetry_project() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::write(
+            temp_dir.path().join("pyproject.toml"),
+            "[build-system]\nrequires = [\"setuptools\"]\n",
+        )?;
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().poetry_install({:?})",
+            temp_dir.path().display().to_string()
+        ));
+        assert!(err.message.contains("no poetry project is defined by"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pip_download_simple() {
+        let resources =
+            starlark_ok("default_python_distribution().pip_download(['pyflakes==2.1.1'])");
+        assert_eq!(resources.get_type(), "list");
+
+        let mut it = resources.into_iter().unwrap();
+
+        let v = it.next().unwrap();
+        assert_eq!(v.get_type(), "PythonSourceModule");
+        v.downcast_apply(|x: &PythonSourceModule| {
+            assert_eq!(x.module.name, "pyflakes");
+            assert!(x.module.is_package);
+        });
+    }
+
+    #[test]
+    fn test_compile_protobuf_invalid() {
+        let err = starlark_nok(
+            "default_python_distribution().compile_protobuf(['foo.proto'], grpc=True, protoc='protoc')",
+        );
+        assert!(err.message.contains("grpc can't be used with protoc"));
+
+        let err = starlark_nok("default_python_distribution().compile_protobuf([])");
+        assert!(err.message.contains("no .proto files to compile"));
+    }
+
+    #[test]
+    fn test_read_wheels() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+
+        crate::testutil::write_wheel(
+            &temp_dir.path().join("foo-1.0-py3-none-any.whl"),
+            &[
+                ("foo/__init__.py", b"# foo"),
+                ("foo-1.0.dist-info/METADATA", b"Name: foo\nVersion: 1.0\n"),
+                ("foo-1.0.dist-info/WHEEL", b"Wheel-Version: 1.0\n"),
+            ],
+            &[],
+        )?;
+
+        let resources = starlark_ok(&format!(
+            "default_python_distribution().read_wheels(\"{}/*.whl\")",
+            temp_dir.path().display()
+        ));
+        assert_eq!(resources.get_type(), "list");
+
+        let v = resources.into_iter().unwrap().next().unwrap();
+        assert_eq!(v.get_type(), "PythonSourceModule");
+        v.downcast_apply(|x: &PythonSourceModule| {
+            assert_eq!(x.module.name, "foo");
+            assert_eq!(x.module.source.resolve().unwrap(), b"# foo");
+        });
+
+        let err = starlark_nok(&format!(
+            "default_python_distribution().read_wheels(\"{}/*.tar.gz\")",
+            temp_dir.path().display()
+        ));
+        assert!(err.message.contains("no wheels match"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_package_root_simple() -> Result<()> {
         let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
@@ -3,9 +3,12 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    super::build_summary::BUILD_MANIFEST_FILENAME,
     super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    crate::atomic_output::write_file_atomic,
+    crate::build_manifest::BuildManifest,
     crate::py_packaging::binary::PythonBinaryBuilder,
-    anyhow::Result,
+    anyhow::{Context, Result},
     slog::warn,
     starlark::environment::Environment,
     starlark::values::{default_compare, TypedValue, Value, ValueError, ValueResult},
@@ -17,6 +20,11 @@ use {
 
 pub struct PythonEmbeddedResources {
     pub exe: Box<dyn PythonBinaryBuilder>,
+
+    /// Whether only data files are written, without linking libpython.
+    ///
+    /// For binaries built by other build systems, which link Python themselves.
+    pub data_only: bool,
 }
 
 impl TypedValue for PythonEmbeddedResources {
@@ -57,15 +65,149 @@ impl BuildTarget for PythonEmbeddedResources {
             context.output_path.display()
         );
 
-        let embedded = self
-            .exe
-            .as_embedded_python_binary_data(&context.logger, &context.opt_level)?;
+        if self.data_only {
+            return self.build_data(context);
+        }
+
+        let embedded = self.exe.as_embedded_python_binary_data(
+            &context.logger,
+            &context.opt_level,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        embedded.write_files(&context.output_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: context.output_path.clone(),
+        })
+    }
+}
+
+impl PythonEmbeddedResources {
+    /// Write packed resources, the `pyembed` config, extra files and a manifest.
+    fn build_data(&self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let embedded = self.exe.as_embedded_python_resources_data(
+            &context.logger,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
 
         embedded.write_files(&context.output_path)?;
 
+        let mut manifest =
+            BuildManifest::from_binary_builder(self.exe.as_ref(), &context.target_triple);
+        manifest.add_artifacts_from_directory(&context.output_path, &[BUILD_MANIFEST_FILENAME])?;
+
+        let manifest_path = context.output_path.join(BUILD_MANIFEST_FILENAME);
+        write_file_atomic(&manifest_path, manifest.to_json()?.as_bytes(), false)
+            .context(format!("writing {}", manifest_path.display()))?;
+
         Ok(ResolvedTarget {
             run_mode: RunMode::None,
             output_path: context.output_path.clone(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::super::python_executable::PythonExecutable,
+        super::super::testutil::*,
+        super::*,
+        sha2::{Digest, Sha256},
+        std::collections::BTreeSet,
+    };
+
+    #[test]
+    fn test_build_data_only() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let output_path = temp_dir.path().join("out");
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+
+        let exe = env.get("exe").unwrap();
+        let mut resources = exe.downcast_apply(|exe: &PythonExecutable| PythonEmbeddedResources {
+            exe: exe.exe.clone_box(),
+            data_only: true,
+        });
+
+        let context = BuildContext {
+            logger: crate::logging::logger_from_env(slog::Level::Error).logger,
+            host_triple: crate::project_building::HOST.to_string(),
+            target_triple: crate::project_building::HOST.to_string(),
+            release: false,
+            opt_level: "0".to_string(),
+            output_path: output_path.clone(),
+            bytecode_compiler_jobs: 1,
+            bytecode_compiler_in_process: false,
+            bytecode_cache_path: temp_dir.path().join("bytecode_cache"),
+            offline: true,
+            tools_path: temp_dir.path().join("tools"),
+        };
+
+        let resolved = resources.build(&context)?;
+        assert_eq!(resolved.output_path, output_path);
+        match resolved.run_mode {
+            RunMode::None => {}
+            _ => panic!("embedded resources shouldn't be runnable"),
+        }
+
+        let files = walkdir::WalkDir::new(&output_path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(&output_path)
+                    .unwrap()
+                    .display()
+                    .to_string()
+                    .replace('\\', "/")
+            })
+            .collect::<BTreeSet<_>>();
+        assert!(files.contains("packed-resources"));
+        assert!(files.contains("default_python_config.rs"));
+        assert!(files.contains(BUILD_MANIFEST_FILENAME));
+
+        // libpython isn't linked.
+        assert!(!files.iter().any(|f| f.starts_with("libpython")));
+        assert!(!files.contains("py-module-names"));
+
+        let config_rs = std::fs::read_to_string(output_path.join("default_python_config.rs"))?;
+        assert!(config_rs.contains("packed-resources"));
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(output_path.join(BUILD_MANIFEST_FILENAME))?)?;
+        assert_eq!(manifest["name"], "testapp");
+        assert_eq!(manifest["target_triple"], crate::project_building::HOST);
+
+        // Every written file but the manifest is described with its digest.
+        let artifacts = manifest["artifacts"].as_array().unwrap();
+        let paths = artifacts
+            .iter()
+            .map(|a| a["path"].as_str().unwrap().to_string())
+            .collect::<BTreeSet<_>>();
+        let mut expected = files.clone();
+        expected.remove(BUILD_MANIFEST_FILENAME);
+        assert_eq!(paths, expected);
+
+        for artifact in artifacts {
+            let data = std::fs::read(output_path.join(artifact["path"].as_str().unwrap()))?;
+            let mut hasher = Sha256::new();
+            hasher.input(&data);
+
+            assert_eq!(artifact["size"], data.len() as u64);
+            assert_eq!(artifact["sha256"], hex::encode(hasher.result()));
+        }
+
+        Ok(())
+    }
+}
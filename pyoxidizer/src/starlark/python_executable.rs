@@ -3,18 +3,62 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    super::appimage_builder::{starlark_read_icon, starlark_to_desktop_entry, AppImageBuilder},
+    super::build_summary::BuildSummary,
+    super::c_library::PythonCLibrary,
     super::env::EnvironmentContext,
+    super::extension_bundle::PythonExtensionBundle,
+    super::file_resource::FileManifest,
+    super::macos_application_bundle_builder::{
+        starlark_to_plist_dict, MacOsApplicationBundleBuilder,
+    },
+    super::plugin_library::PythonPluginLibrary,
     super::python_embedded_resources::PythonEmbeddedResources,
     super::python_resource::{
-        PythonExtensionModule, PythonExtensionModuleFlavor, PythonPackageDistributionResource,
-        PythonPackageResource, PythonSourceModule,
+        PythonBytecodeModule, PythonExtensionModule, PythonExtensionModuleFlavor,
+        PythonPackageDistributionResource, PythonPackageResource, PythonSourceModule,
     },
+    super::python_resources_summary::{PythonResourcesSummary, ResourcePlacement},
+    super::snap_builder::SnapBuilder,
+    super::standalone_folder::StandaloneFolder,
     super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
-    super::util::{optional_list_arg, required_bool_arg, required_str_arg, required_type_arg},
-    crate::project_building::build_python_executable,
+    super::util::{
+        optional_list_arg, optional_str_arg, required_bool_arg, required_dict_arg,
+        required_str_arg, required_type_arg,
+    },
+    super::wix_msi_builder::WiXMSIBuilder,
+    super::zipapp::ZipApp,
+    crate::app_packaging::appimage::AppImageOptions,
+    crate::app_packaging::linux_service::LinuxServiceOptions,
+    crate::app_packaging::macos_application_bundle::MacOsApplicationBundle,
+    crate::app_packaging::macos_signing::MacOsSigningOptions,
+    crate::app_packaging::resource::FileManifest as RawFileManifest,
+    crate::app_packaging::snap::SnapOptions,
+    crate::app_packaging::windows_manifest::{
+        DpiAwareness, UacExecutionLevel, WindowsManifestOptions,
+    },
+    crate::app_packaging::wix::WixMsiOptions,
+    crate::atomic_output::write_file_atomic,
+    crate::build_manifest::SummaryFormat,
+    crate::plugin_exports::PluginExport,
+    crate::project_building::{build_python_executable, CLibraryKind},
     crate::py_packaging::binary::PythonBinaryBuilder,
+    crate::py_packaging::filtering::resolve_resource_names_from_files,
+    crate::py_packaging::packaging_tool::canonical_package_name,
+    crate::py_packaging::resource::AddToFileManifest,
+    crate::py_packaging::resource_signing::ResourceSigningKey,
+    crate::py_packaging::resource_transform::{
+        ResourceTransform, ResourceTransformAction, ResourceTransforms,
+    },
+    crate::py_packaging::startup_cache::find_regex_patterns,
     anyhow::{anyhow, Context, Result},
-    python_packaging::resource::{BytecodeOptimizationLevel, PythonModuleBytecodeFromSource},
+    python_packaging::resource::{
+        BytecodeOptimizationLevel, PythonModuleBytecode, PythonModuleBytecodeFromSource,
+        PythonModuleSource,
+        PythonPackageDistributionResource as RawPythonPackageDistributionResource,
+        PythonPackageResource as RawPythonPackageResource,
+    },
+    python_packaging::resource_collection::{FilesystemModuleLayout, PythonResourcesPolicy},
     slog::{info, warn},
     starlark::environment::Environment,
     starlark::values::{
@@ -27,15 +71,144 @@ use {
     },
     std::any::Any,
     std::cmp::Ordering,
-    std::collections::HashMap,
-    std::io::Write,
+    std::collections::{BTreeSet, HashMap},
+    std::convert::TryFrom,
     std::ops::Deref,
-    std::path::{Path, PathBuf},
+    std::path::{Component, Path, PathBuf},
 };
 
+/// Package distribution files `importlib.metadata` answers queries from.
+///
+/// `METADATA` and `PKG-INFO` define versions and requirements of `.dist-info`
+/// and `.egg-info` distributions, `RECORD` their files, `entry_points.txt`
+/// their entry points and `requires.txt` requirements of `.egg-info`
+/// distributions.
+pub const PACKAGE_DISTRIBUTION_METADATA_FILES: &[&str] = &[
+    "METADATA",
+    "PKG-INFO",
+    "RECORD",
+    "entry_points.txt",
+    "requires.txt",
+];
+
+/// Where a `PythonBytecodeModule` is added.
+enum BytecodeModulePlacement<'a> {
+    InMemory,
+    FilesystemRelative(&'a str),
+    /// As determined by the resources policy.
+    Policy,
+}
+
+/// Where a resource passed to `add_python_resource()` is added.
+#[derive(Clone, Debug, PartialEq)]
+enum RequestedLocation {
+    /// As determined by the resources policy.
+    Default,
+    InMemory,
+    FilesystemRelative(String),
+}
+
+/// Parse the `location` argument of `add_python_resource()`.
+fn optional_location_arg(value: &Value) -> Result<RequestedLocation, ValueError> {
+    let location = match optional_str_arg("location", value)? {
+        Some(location) => location,
+        None => return Ok(RequestedLocation::Default),
+    };
+
+    if location == "default" {
+        Ok(RequestedLocation::Default)
+    } else if location == "in-memory" {
+        Ok(RequestedLocation::InMemory)
+    } else if location.starts_with("filesystem-relative:") {
+        Ok(RequestedLocation::FilesystemRelative(
+            location["filesystem-relative:".len()..].to_string(),
+        ))
+    } else {
+        Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!(
+                "location must be default, in-memory or filesystem-relative:<prefix>; got {}",
+                location
+            ),
+            label: "add_python_resource()".to_string(),
+        }
+        .into())
+    }
+}
+
+/// Parse the `optimize_level` argument of functions adding bytecode.
+///
+/// Either a single level or a list of levels is accepted. Bytecode is added
+/// for each level, allowing the interpreter to use the bytecode matching
+/// its `-O` setting.
+fn optimize_levels_arg(value: &Value) -> Result<Vec<BytecodeOptimizationLevel>, ValueError> {
+    let values = match value.get_type() {
+        "int" => vec![value.clone()],
+        "list" => value.into_iter()?.collect(),
+        t => {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!(
+                    "function expects an int or list of int for optimize_level; got type {}",
+                    t
+                ),
+                label: format!("expect type int or list; got {}", t),
+            }
+            .into())
+        }
+    };
+
+    let mut levels = Vec::new();
+
+    for value in values {
+        required_type_arg("optimize_level", "int", &value)?;
+
+        let level = match value.to_int().unwrap() {
+            0 => BytecodeOptimizationLevel::Zero,
+            1 => BytecodeOptimizationLevel::One,
+            2 => BytecodeOptimizationLevel::Two,
+            i => {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!("optimize_level must be 0, 1, or 2: got {}", i),
+                    label: "invalid optimize_level value".to_string(),
+                }
+                .into());
+            }
+        };
+
+        if !levels.contains(&level) {
+            levels.push(level);
+        }
+    }
+
+    if levels.is_empty() {
+        return Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: "optimize_level must define at least one level".to_string(),
+            label: "invalid optimize_level value".to_string(),
+        }
+        .into());
+    }
+
+    Ok(levels)
+}
+
 /// Represents a builder for a Python executable.
 pub struct PythonExecutable {
     pub exe: Box<dyn PythonBinaryBuilder>,
+
+    /// Transforms applied to resources as they are added.
+    pub resource_transforms: ResourceTransforms,
+
+    /// Whether `.pyi` stubs and `py.typed` markers are added to the executable.
+    pub include_type_stubs: bool,
+
+    /// Type information resources seen when adding resources.
+    ///
+    /// These are collected whether or not they are added to the executable,
+    /// so they can be distributed separately.
+    pub type_stubs: Vec<RawPythonPackageResource>,
 }
 
 impl TypedValue for PythonExecutable {
@@ -75,6 +248,9 @@ impl BuildTarget for PythonExecutable {
             &context.target_triple,
             &context.opt_level,
             context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
         )?;
 
         let dest_path = context.output_path.join(build.exe_name);
@@ -83,14 +259,9 @@ impl BuildTarget for PythonExecutable {
             "writing executable to {}",
             dest_path.display()
         );
-        let mut fh = std::fs::File::create(&dest_path)
-            .context(format!("creating {}", dest_path.display()))?;
-        fh.write_all(&build.exe_data)
+        write_file_atomic(&dest_path, &build.exe_data, true)
             .context(format!("writing {}", dest_path.display()))?;
 
-        crate::app_packaging::resource::set_executable(&mut fh)
-            .context("making binary executable")?;
-
         Ok(ResolvedTarget {
             run_mode: RunMode::Path { path: dest_path },
             output_path: context.output_path.clone(),
@@ -98,6 +269,322 @@ impl BuildTarget for PythonExecutable {
     }
 }
 
+/// Error for a resource missing from the resources collection after adding it.
+fn resource_not_added(name: &str) -> anyhow::Error {
+    anyhow!("{} isn't in the resources collection after adding it", name)
+}
+
+/// Obtain the strings in a list argument validated by `optional_list_arg`.
+fn optional_string_list(value: &Value) -> Result<Vec<String>, ValueError> {
+    match value.get_type() {
+        "list" => Ok(value.into_iter()?.map(|x| x.to_string()).collect()),
+        "NoneType" => Ok(Vec::new()),
+        _ => panic!("type should have been validated above"),
+    }
+}
+
+impl PythonExecutable {
+    /// Where added resources are looked up, in order of preference.
+    ///
+    /// `requested` is the placement requested when adding. Otherwise it
+    /// derives from the resources policy. Resources may still be placed
+    /// elsewhere when the requested placement isn't supported.
+    fn resource_placements(&self, requested: Option<ResourcePlacement>) -> [ResourcePlacement; 2] {
+        let preferred = requested.unwrap_or_else(|| match self.exe.python_resources_policy() {
+            PythonResourcesPolicy::FilesystemRelativeOnly(_) => {
+                ResourcePlacement::FilesystemRelative
+            }
+            PythonResourcesPolicy::InMemoryOnly
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => {
+                ResourcePlacement::InMemory
+            }
+        });
+
+        match preferred {
+            ResourcePlacement::InMemory => [
+                ResourcePlacement::InMemory,
+                ResourcePlacement::FilesystemRelative,
+            ],
+            ResourcePlacement::FilesystemRelative => [
+                ResourcePlacement::FilesystemRelative,
+                ResourcePlacement::InMemory,
+            ],
+        }
+    }
+
+    /// Apply resource transforms to module source.
+    ///
+    /// Returns `None` if a transform dropped the module.
+    fn transform_module_source(
+        &mut self,
+        logger: &slog::Logger,
+        module: &PythonModuleSource,
+    ) -> Result<Option<PythonModuleSource>, ValueError> {
+        let res = self
+            .resource_transforms
+            .transform_module_source(module)
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "resource transform".to_string(),
+                }
+                .into())
+            })?;
+
+        if res.is_none() {
+            info!(logger, "resource transform dropped {}", module.name);
+        }
+
+        Ok(res)
+    }
+
+    /// Apply resource transforms to a package resource.
+    ///
+    /// Returns `None` if a transform dropped the resource.
+    fn transform_package_resource(
+        &mut self,
+        logger: &slog::Logger,
+        resource: &RawPythonPackageResource,
+    ) -> Result<Option<RawPythonPackageResource>, ValueError> {
+        let res = self
+            .resource_transforms
+            .transform_package_resource(resource)
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "resource transform".to_string(),
+                }
+                .into())
+            })?;
+
+        if res.is_none() {
+            info!(
+                logger,
+                "resource transform dropped {}",
+                resource.symbolic_name()
+            );
+        }
+
+        Ok(res)
+    }
+
+    /// Obtain the package resource to add to the executable.
+    ///
+    /// Resource transforms are applied and type information is set aside
+    /// unless `include_type_stubs` is set. Returns `None` if the resource
+    /// isn't added.
+    fn prepare_package_resource(
+        &mut self,
+        logger: &slog::Logger,
+        resource: &RawPythonPackageResource,
+    ) -> Result<Option<RawPythonPackageResource>, ValueError> {
+        let resource = match self.transform_package_resource(logger, resource)? {
+            Some(resource) => resource,
+            None => return Ok(None),
+        };
+
+        if !resource.is_type_information() {
+            return Ok(Some(resource));
+        }
+
+        self.type_stubs.push(resource.clone());
+
+        if self.include_type_stubs {
+            Ok(Some(resource))
+        } else {
+            info!(
+                logger,
+                "excluding type information {}",
+                resource.symbolic_name()
+            );
+            Ok(None)
+        }
+    }
+
+    /// Add a resource resulting from resource transforms.
+    ///
+    /// Nothing is added if a transform dropped the resource. Otherwise `add`
+    /// adds it to the executable.
+    fn add_transformed<T>(
+        &mut self,
+        resource: Option<T>,
+        label: &str,
+        add: impl FnOnce(&mut dyn PythonBinaryBuilder, T) -> Result<()>,
+    ) -> ValueResult {
+        if let Some(resource) = resource {
+            add(self.exe.as_mut(), resource).or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: label.to_string(),
+                }
+                .into())
+            })?;
+        }
+
+        Ok(Value::new(None))
+    }
+
+    /// Record a resource dropped by a resource transform as skipped.
+    ///
+    /// Adding anything else the executable doesn't accept fails, so a
+    /// resource missing from the resources collection is an error otherwise.
+    fn add_dropped(
+        &self,
+        summary: &mut PythonResourcesSummary,
+        name: &str,
+        what: &str,
+    ) -> Result<()> {
+        match self.resource_transforms.dropped_by(name) {
+            Some(transform) => {
+                summary.add_skipped(
+                    name,
+                    &format!("{}: dropped by resource transform {}", what, transform),
+                );
+                Ok(())
+            }
+            None => Err(resource_not_added(name)),
+        }
+    }
+
+    /// Summarize the addition of a Python resource.
+    ///
+    /// The summary is derived from what the resources collection holds for
+    /// the resource after adding it. Parts that weren't requested, type
+    /// information left out by `include_type_stubs` and resources dropped by
+    /// resource transforms are reported as skipped, along with why.
+    fn summarize_python_resource(
+        &self,
+        summary: &mut PythonResourcesSummary,
+        placements: &[ResourcePlacement],
+        resource: &Value,
+        add_source_module: bool,
+        add_bytecode_module: bool,
+        optimize_levels: &[BytecodeOptimizationLevel],
+    ) -> Result<()> {
+        match resource.get_type() {
+            "PythonSourceModule" => {
+                let name = resource.downcast_apply(|m: &PythonSourceModule| m.module.name.clone());
+                let entry = self.exe.resource(&name);
+
+                if !add_source_module {
+                    summary.add_skipped(&name, "source not added: add_source_module is False");
+                } else {
+                    let added = match entry {
+                        Some(entry) => summary.add_module_source(placements, entry)?,
+                        None => false,
+                    };
+                    if !added {
+                        self.add_dropped(summary, &name, "source not added")?;
+                    }
+                }
+
+                if !add_bytecode_module {
+                    summary.add_skipped(&name, "bytecode not added: add_bytecode_module is False");
+                } else {
+                    let added = match entry {
+                        Some(entry) => {
+                            summary.add_module_bytecode(placements, entry, optimize_levels)?
+                        }
+                        None => false,
+                    };
+                    if !added {
+                        self.add_dropped(summary, &name, "bytecode not added")?;
+                    }
+                }
+            }
+            "PythonBytecodeModule" => {
+                let m = resource.downcast_apply(|m: &PythonBytecodeModule| m.module.clone());
+
+                let added = match self.exe.resource(&m.name) {
+                    Some(entry) => {
+                        summary.add_module_bytecode(placements, entry, &[m.optimize_level])?
+                    }
+                    None => false,
+                };
+                if !added {
+                    self.add_dropped(summary, &m.name, "bytecode not added")?;
+                }
+            }
+            "PythonPackageResource" => {
+                let r = resource.downcast_apply(|r: &PythonPackageResource| r.data.clone());
+
+                let added = match self.exe.resource(&r.leaf_package) {
+                    Some(entry) => {
+                        summary.add_package_resource(placements, entry, &r.relative_name)?
+                    }
+                    None => false,
+                };
+                if !added {
+                    let name = r.symbolic_name();
+
+                    if r.is_type_information()
+                        && !self.include_type_stubs
+                        && self.resource_transforms.dropped_by(&name).is_none()
+                    {
+                        summary.add_skipped(
+                            &name,
+                            "type information not added: include_type_stubs is False",
+                        );
+                    } else {
+                        self.add_dropped(summary, &name, "not added")?;
+                    }
+                }
+            }
+            "PythonPackageDistributionResource" => {
+                let r = resource
+                    .downcast_apply(|r: &PythonPackageDistributionResource| r.resource.clone());
+                self.summarize_package_distribution_resource(summary, placements, &r)?;
+            }
+            "PythonExtensionModule" => {
+                let name = resource.downcast_apply(|m: &PythonExtensionModule| m.em.name());
+
+                let added = match self.exe.resource(&name) {
+                    Some(entry) => summary.add_extension_module(placements, entry)?,
+                    None => false,
+                };
+
+                if !added {
+                    if self.exe.extension_module_names().contains(&name) {
+                        summary.add_builtin_extension_module();
+                    } else {
+                        return Err(resource_not_added(&name));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Summarize the addition of a package distribution resource.
+    fn summarize_package_distribution_resource(
+        &self,
+        summary: &mut PythonResourcesSummary,
+        placements: &[ResourcePlacement],
+        resource: &RawPythonPackageDistributionResource,
+    ) -> Result<()> {
+        let added = match self.exe.resource(&resource.package) {
+            Some(entry) => {
+                summary.add_package_distribution_resource(placements, entry, &resource.name)?
+            }
+            None => false,
+        };
+        if !added {
+            return Err(resource_not_added(&format!(
+                "{}:{}",
+                resource.package, resource.name
+            )));
+        }
+
+        Ok(())
+    }
+}
+
 // Starlark functions.
 impl PythonExecutable {
     /// PythonExecutable.add_in_memory_module_source(module)
@@ -112,19 +599,11 @@ impl PythonExecutable {
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
         let m = module.downcast_apply(|m: &PythonSourceModule| m.module.clone());
-        info!(&logger, "adding in-memory source module {}", m.name);
-        self.exe.add_in_memory_module_source(&m).or_else(|e| {
-            {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_in_memory_module_source".to_string(),
-                }
-                .into())
-            }
-        })?;
-
-        Ok(Value::new(None))
+        let m = self.transform_module_source(&logger, &m)?;
+        self.add_transformed(m, "add_in_memory_module_source", |exe, m| {
+            info!(&logger, "adding in-memory source module {}", m.name);
+            exe.add_in_memory_module_source(&m)
+        })
     }
 
     /// PythonExecutable.add_filesystem_relative_module_source(module, prefix="")
@@ -141,22 +620,14 @@ impl PythonExecutable {
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
         let m = module.downcast_apply(|m: &PythonSourceModule| m.module.clone());
-        info!(
-            &logger,
-            "adding executable relative source module {}", m.name
-        );
-        self.exe
-            .add_relative_path_module_source(&prefix, &m)
-            .or_else(|e| {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_filesystem_relative_module_source".to_string(),
-                }
-                .into())
-            })?;
-
-        Ok(Value::new(None))
+        let m = self.transform_module_source(&logger, &m)?;
+        self.add_transformed(m, "add_filesystem_relative_module_source", |exe, m| {
+            info!(
+                &logger,
+                "adding executable relative source module {}", m.name
+            );
+            exe.add_relative_path_module_source(&prefix, &m)
+        })
     }
 
     /// PythonExecutable.add_module_source(module)
@@ -167,19 +638,11 @@ impl PythonExecutable {
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
         let m = module.downcast_apply(|m: &PythonSourceModule| m.module.clone());
-        info!(&logger, "adding source module {}", m.name);
-        self.exe.add_module_source(&m).or_else(|e| {
-            {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_module_source".to_string(),
-                }
-                .into())
-            }
-        })?;
-
-        Ok(Value::new(None))
+        let m = self.transform_module_source(&logger, &m)?;
+        self.add_transformed(m, "add_module_source", |exe, m| {
+            info!(&logger, "adding source module {}", m.name);
+            exe.add_module_source(&m)
+        })
     }
 
     /// PythonExecutable.add_in_memory_module_bytecode(module, optimize_level=0)
@@ -190,49 +653,27 @@ impl PythonExecutable {
         optimize_level: &Value,
     ) -> ValueResult {
         required_type_arg("module", "PythonSourceModule", &module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        let optimize_levels = optimize_levels_arg(&optimize_level)?;
 
         let context = env.get("CONTEXT").expect("CONTEXT not set");
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
-        let optimize_level = optimize_level.to_int().unwrap();
-
-        let optimize_level = match optimize_level {
-            0 => BytecodeOptimizationLevel::Zero,
-            1 => BytecodeOptimizationLevel::One,
-            2 => BytecodeOptimizationLevel::Two,
-            i => {
-                return Err(RuntimeError {
-                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
-                    message: format!("optimize_level must be 0, 1, or 2: got {}", i),
-                    label: "invalid optimize_level value".to_string(),
-                }
-                .into());
-            }
-        };
-
         let m = module.downcast_apply(|m: &PythonSourceModule| m.module.clone());
-        info!(&logger, "adding in-memory bytecode module {}", m.name);
-        self.exe
-            .add_in_memory_module_bytecode(&PythonModuleBytecodeFromSource {
-                name: m.name.clone(),
-                source: m.source.clone(),
-                optimize_level,
-                is_package: m.is_package,
-                cache_tag: m.cache_tag,
-            })
-            .or_else(|e| {
-                {
-                    Err(RuntimeError {
-                        code: "PYOXIDIZER_BUILD",
-                        message: e.to_string(),
-                        label: "add_in_memory_module_bytecode".to_string(),
-                    }
-                    .into())
-                }
-            })?;
+        let m = self.transform_module_source(&logger, &m)?;
+        self.add_transformed(m, "add_in_memory_module_bytecode", |exe, m| {
+            info!(&logger, "adding in-memory bytecode module {}", m.name);
+            for optimize_level in optimize_levels {
+                exe.add_in_memory_module_bytecode(&PythonModuleBytecodeFromSource {
+                    name: m.name.clone(),
+                    source: m.source.clone(),
+                    optimize_level,
+                    is_package: m.is_package,
+                    cache_tag: m.cache_tag.clone(),
+                })?;
+            }
 
-        Ok(Value::new(None))
+            Ok(())
+        })
     }
 
     /// PythonExecutable.add_filesystem_relative_module_bytecode(prefix, module, optimize_level=0)
@@ -245,53 +686,33 @@ impl PythonExecutable {
     ) -> ValueResult {
         let prefix = required_str_arg("prefix", &prefix)?;
         required_type_arg("module", "PythonSourceModule", &module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        let optimize_levels = optimize_levels_arg(&optimize_level)?;
 
         let context = env.get("CONTEXT").expect("CONTEXT not set");
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
-        let optimize_level = optimize_level.to_int().unwrap();
-
-        let optimize_level = match optimize_level {
-            0 => BytecodeOptimizationLevel::Zero,
-            1 => BytecodeOptimizationLevel::One,
-            2 => BytecodeOptimizationLevel::Two,
-            i => {
-                return Err(RuntimeError {
-                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
-                    message: format!("optimize_level must be 0, 1, or 2: got {}", i),
-                    label: "invalid optimize_level value".to_string(),
-                }
-                .into());
-            }
-        };
-
         let m = module.downcast_apply(|m: &PythonSourceModule| m.module.clone());
-        info!(
-            &logger,
-            "adding executable relative bytecode module {}", m.name
-        );
-        self.exe
-            .add_relative_path_module_bytecode(
-                &prefix,
-                &PythonModuleBytecodeFromSource {
-                    name: m.name.clone(),
-                    source: m.source.clone(),
-                    optimize_level,
-                    is_package: m.is_package,
-                    cache_tag: m.cache_tag,
-                },
-            )
-            .or_else(|e| {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_filesystem_relative_module_bytecode".to_string(),
-                }
-                .into())
-            })?;
+        let m = self.transform_module_source(&logger, &m)?;
+        self.add_transformed(m, "add_filesystem_relative_module_bytecode", |exe, m| {
+            info!(
+                &logger,
+                "adding executable relative bytecode module {}", m.name
+            );
+            for optimize_level in optimize_levels {
+                exe.add_relative_path_module_bytecode(
+                    &prefix,
+                    &PythonModuleBytecodeFromSource {
+                        name: m.name.clone(),
+                        source: m.source.clone(),
+                        optimize_level,
+                        is_package: m.is_package,
+                        cache_tag: m.cache_tag.clone(),
+                    },
+                )?;
+            }
 
-        Ok(Value::new(None))
+            Ok(())
+        })
     }
 
     /// PythonExecutable.add_module_bytecode(module, optimize_level=0)
@@ -302,49 +723,27 @@ impl PythonExecutable {
         optimize_level: &Value,
     ) -> ValueResult {
         required_type_arg("module", "PythonSourceModule", &module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        let optimize_levels = optimize_levels_arg(&optimize_level)?;
 
         let context = env.get("CONTEXT").expect("CONTEXT not set");
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
-        let optimize_level = optimize_level.to_int().unwrap();
-
-        let optimize_level = match optimize_level {
-            0 => BytecodeOptimizationLevel::Zero,
-            1 => BytecodeOptimizationLevel::One,
-            2 => BytecodeOptimizationLevel::Two,
-            i => {
-                return Err(RuntimeError {
-                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
-                    message: format!("optimize_level must be 0, 1, or 2: got {}", i),
-                    label: "invalid optimize_level value".to_string(),
-                }
-                .into());
-            }
-        };
-
         let m = module.downcast_apply(|m: &PythonSourceModule| m.module.clone());
-        info!(&logger, "adding bytecode module {}", m.name);
-        self.exe
-            .add_module_bytecode(&PythonModuleBytecodeFromSource {
-                name: m.name.clone(),
-                source: m.source.clone(),
-                optimize_level,
-                is_package: m.is_package,
-                cache_tag: m.cache_tag,
-            })
-            .or_else(|e| {
-                {
-                    Err(RuntimeError {
-                        code: "PYOXIDIZER_BUILD",
-                        message: e.to_string(),
-                        label: "add_module_bytecode".to_string(),
-                    }
-                    .into())
-                }
-            })?;
+        let m = self.transform_module_source(&logger, &m)?;
+        self.add_transformed(m, "add_module_bytecode", |exe, m| {
+            info!(&logger, "adding bytecode module {}", m.name);
+            for optimize_level in optimize_levels {
+                exe.add_module_bytecode(&PythonModuleBytecodeFromSource {
+                    name: m.name.clone(),
+                    source: m.source.clone(),
+                    optimize_level,
+                    is_package: m.is_package,
+                    cache_tag: m.cache_tag.clone(),
+                })?;
+            }
 
-        Ok(Value::new(None))
+            Ok(())
+        })
     }
 
     /// PythonExecutable.add_in_memory_package_resource(resource)
@@ -359,23 +758,15 @@ impl PythonExecutable {
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
         let r = resource.downcast_apply(|r: &PythonPackageResource| r.data.clone());
-        info!(
-            &logger,
-            "adding in-memory resource data {}",
-            r.symbolic_name()
-        );
-        self.exe.add_in_memory_package_resource(&r).or_else(|e| {
-            {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_in_memory_package_resource".to_string(),
-                }
-                .into())
-            }
-        })?;
-
-        Ok(Value::new(None))
+        let r = self.prepare_package_resource(&logger, &r)?;
+        self.add_transformed(r, "add_in_memory_package_resource", |exe, r| {
+            info!(
+                &logger,
+                "adding in-memory resource data {}",
+                r.symbolic_name()
+            );
+            exe.add_in_memory_package_resource(&r)
+        })
     }
 
     /// PythonExecutable.add_package_resource(resource)
@@ -390,19 +781,11 @@ impl PythonExecutable {
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
         let r = resource.downcast_apply(|r: &PythonPackageResource| r.data.clone());
-        info!(&logger, "adding resource data {}", r.symbolic_name());
-        self.exe.add_package_resource(&r).or_else(|e| {
-            {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_package_resource".to_string(),
-                }
-                .into())
-            }
-        })?;
-
-        Ok(Value::new(None))
+        let r = self.prepare_package_resource(&logger, &r)?;
+        self.add_transformed(r, "add_package_resource", |exe, r| {
+            info!(&logger, "adding resource data {}", r.symbolic_name());
+            exe.add_package_resource(&r)
+        })
     }
 
     /// PythonExecutable.add_filesystem_relative_package_resource(prefix, resource)
@@ -419,23 +802,15 @@ impl PythonExecutable {
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
         let r = resource.downcast_apply(|r: &PythonPackageResource| r.data.clone());
-        info!(
-            &logger,
-            "adding executable relative resource data {}",
-            r.symbolic_name()
-        );
-        self.exe
-            .add_relative_path_package_resource(&prefix, &r)
-            .or_else(|e| {
-                Err(RuntimeError {
-                    code: "PYOXIDIZER_BUILD",
-                    message: e.to_string(),
-                    label: "add_filesystem_relative_package_resource".to_string(),
-                }
-                .into())
-            })?;
-
-        Ok(Value::new(None))
+        let r = self.prepare_package_resource(&logger, &r)?;
+        self.add_transformed(r, "add_filesystem_relative_package_resource", |exe, r| {
+            info!(
+                &logger,
+                "adding executable relative resource data {}",
+                r.symbolic_name()
+            );
+            exe.add_relative_path_package_resource(&prefix, &r)
+        })
     }
 
     /// PythonExecutable.add_in_memory_package_distribution_resource(resource)
@@ -534,6 +909,70 @@ impl PythonExecutable {
         Ok(Value::new(None))
     }
 
+    /// PythonExecutable.add_package_distribution_metadata(resources, packages=None)
+    pub fn starlark_add_package_distribution_metadata(
+        &mut self,
+        env: &Environment,
+        resources: &Value,
+        packages: &Value,
+    ) -> ValueResult {
+        required_type_arg("resources", "list", &resources)?;
+        optional_list_arg("packages", "string", &packages)?;
+
+        let packages = match packages.get_type() {
+            "list" => Some(
+                packages
+                    .into_iter()?
+                    .map(|x| canonical_package_name(&x.to_string()))
+                    .collect::<BTreeSet<_>>(),
+            ),
+            _ => None,
+        };
+
+        let placements = self.resource_placements(None);
+        let mut summary = PythonResourcesSummary::default();
+
+        for resource in resources.into_iter()? {
+            if resource.get_type() != "PythonPackageDistributionResource" {
+                continue;
+            }
+
+            let r =
+                resource.downcast_apply(|r: &PythonPackageDistributionResource| r.resource.clone());
+
+            if !PACKAGE_DISTRIBUTION_METADATA_FILES.contains(&r.name.as_str()) {
+                continue;
+            }
+
+            if let Some(packages) = &packages {
+                if !packages.contains(&canonical_package_name(&r.package)) {
+                    continue;
+                }
+            }
+
+            self.starlark_add_package_distribution_resource(env, &resource)?;
+            self.summarize_package_distribution_resource(&mut summary, &placements, &r)
+                .or_else(|e| {
+                    Err(RuntimeError {
+                        code: "PYOXIDIZER_BUILD",
+                        message: e.to_string(),
+                        label: "add_package_distribution_metadata()".to_string(),
+                    }
+                    .into())
+                })?;
+        }
+
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+        info!(
+            &logger,
+            "added package distribution metadata: {}",
+            summary.to_str()
+        );
+
+        Ok(Value::new(summary))
+    }
+
     /// PythonExecutable.add_in_memory_extension_module(module)
     pub fn starlark_add_in_memory_extension_module(
         &mut self,
@@ -657,6 +1096,90 @@ impl PythonExecutable {
         Ok(Value::new(None))
     }
 
+    /// Add a `PythonBytecodeModule` value.
+    ///
+    /// Resource transforms are applied to the module source. Bytecode already
+    /// compiled is added as is unless a transform changed the source.
+    /// Otherwise, it is compiled from the module source when the binary is
+    /// built.
+    fn add_python_bytecode_module(
+        &mut self,
+        env: &Environment,
+        resource: &Value,
+        placement: BytecodeModulePlacement,
+    ) -> ValueResult {
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        let mut m = resource.downcast_apply(|m: &PythonBytecodeModule| m.clone());
+
+        let source = PythonModuleSource {
+            name: m.module.name.clone(),
+            source: m.module.source.clone(),
+            is_package: m.module.is_package,
+            cache_tag: m.module.cache_tag.clone(),
+        };
+        let source = match self.transform_module_source(&logger, &source)? {
+            Some(source) => source.source,
+            None => return Ok(Value::new(None)),
+        };
+
+        // Bytecode compiled from the original source is stale.
+        if source != m.module.source {
+            m.bytecode = None;
+            m.module.source = source;
+        }
+
+        let res = match &m.bytecode {
+            Some(bytecode) => {
+                let module = PythonModuleBytecode::new(
+                    &m.module.name,
+                    m.module.optimize_level,
+                    m.module.is_package,
+                    &m.module.cache_tag,
+                    bytecode,
+                );
+                info!(&logger, "adding compiled bytecode module {}", module.name);
+
+                match placement {
+                    BytecodeModulePlacement::InMemory => {
+                        self.exe.add_in_memory_compiled_module_bytecode(&module)
+                    }
+                    BytecodeModulePlacement::FilesystemRelative(prefix) => self
+                        .exe
+                        .add_relative_path_compiled_module_bytecode(prefix, &module),
+                    BytecodeModulePlacement::Policy => {
+                        self.exe.add_compiled_module_bytecode(&module)
+                    }
+                }
+            }
+            None => {
+                info!(&logger, "adding bytecode module {}", m.module.name);
+
+                match placement {
+                    BytecodeModulePlacement::InMemory => {
+                        self.exe.add_in_memory_module_bytecode(&m.module)
+                    }
+                    BytecodeModulePlacement::FilesystemRelative(prefix) => self
+                        .exe
+                        .add_relative_path_module_bytecode(prefix, &m.module),
+                    BytecodeModulePlacement::Policy => self.exe.add_module_bytecode(&m.module),
+                }
+            }
+        };
+
+        res.or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_python_resource()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
     /// PythonExecutable.add_in_memory_python_resource(resource, add_source_module=true, add_bytecode_module=true, optimize_level=0)
     pub fn starlark_add_in_memory_python_resource(
         &mut self,
@@ -666,9 +1189,38 @@ impl PythonExecutable {
         add_bytecode_module: &Value,
         optimize_level: &Value,
     ) -> ValueResult {
+        let mut summary = PythonResourcesSummary::default();
+        self.add_in_memory_python_resource(
+            env,
+            &mut summary,
+            resource,
+            add_source_module,
+            add_bytecode_module,
+            optimize_level,
+        )?;
+
+        Ok(Value::new(summary))
+    }
+
+    /// Add a Python resource to be loaded from memory, recording it in `summary`.
+    fn add_in_memory_python_resource(
+        &mut self,
+        env: &Environment,
+        summary: &mut PythonResourcesSummary,
+        resource: &Value,
+        add_source_module: &Value,
+        add_bytecode_module: &Value,
+        optimize_level: &Value,
+    ) -> Result<(), ValueError> {
         let add_source_module = required_bool_arg("add_source_module", &add_source_module)?;
         let add_bytecode_module = required_bool_arg("add_bytecode_module", &add_bytecode_module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        let optimize_levels = optimize_levels_arg(&optimize_level)?;
+
+        // Placement of distribution resources otherwise derives from the
+        // resources policy. Strict mode honors the requested placement or
+        // fails. Extension modules linked into the binary count as in-memory.
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let strict = context.downcast_apply(|x: &EnvironmentContext| x.strict);
 
         match resource.get_type() {
             "PythonSourceModule" => {
@@ -682,9 +1234,12 @@ impl PythonExecutable {
                 Ok(Value::new(None))
             }
             "PythonBytecodeModule" => {
-                self.starlark_add_in_memory_module_bytecode(env, resource, optimize_level)
+                self.add_python_bytecode_module(env, resource, BytecodeModulePlacement::InMemory)
             }
             "PythonPackageResource" => self.starlark_add_in_memory_package_resource(env, resource),
+            "PythonPackageDistributionResource" if strict => {
+                self.starlark_add_in_memory_package_distribution_resource(env, resource)
+            }
             "PythonPackageDistributionResource" => {
                 self.starlark_add_package_distribution_resource(env, resource)
             }
@@ -695,7 +1250,24 @@ impl PythonExecutable {
                 label: ".add_in_memory_python_resource()".to_string(),
             }
             .into()),
-        }
+        }?;
+
+        self.summarize_python_resource(
+            summary,
+            &self.resource_placements(Some(ResourcePlacement::InMemory)),
+            resource,
+            add_source_module,
+            add_bytecode_module,
+            &optimize_levels,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: ".add_in_memory_python_resource()".to_string(),
+            }
+            .into())
+        })
     }
 
     /// PythonExecutable.add_filesystem_relative_python_resource(prefix, resource, add_source_module=true, add_bytecode_module=true, optimize_level=0)
@@ -708,10 +1280,42 @@ impl PythonExecutable {
         add_bytecode_module: &Value,
         optimize_level: &Value,
     ) -> ValueResult {
+        let mut summary = PythonResourcesSummary::default();
+        self.add_filesystem_relative_python_resource(
+            env,
+            &mut summary,
+            prefix,
+            resource,
+            add_source_module,
+            add_bytecode_module,
+            optimize_level,
+        )?;
+
+        Ok(Value::new(summary))
+    }
+
+    /// Add a Python resource to be loaded from the filesystem, recording it in `summary`.
+    #[allow(clippy::too_many_arguments)]
+    fn add_filesystem_relative_python_resource(
+        &mut self,
+        env: &Environment,
+        summary: &mut PythonResourcesSummary,
+        prefix: &Value,
+        resource: &Value,
+        add_source_module: &Value,
+        add_bytecode_module: &Value,
+        optimize_level: &Value,
+    ) -> Result<(), ValueError> {
         required_str_arg("prefix", &prefix)?;
         let add_source_module = required_bool_arg("add_source_module", &add_source_module)?;
         let add_bytecode_module = required_bool_arg("add_bytecode_module", &add_bytecode_module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        let optimize_levels = optimize_levels_arg(&optimize_level)?;
+
+        // Placement of extension modules and distribution resources otherwise
+        // derives from the resources policy. Strict mode honors the requested
+        // placement or fails.
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let strict = context.downcast_apply(|x: &EnvironmentContext| x.strict);
 
         match resource.get_type() {
             "PythonSourceModule" => {
@@ -729,11 +1333,10 @@ impl PythonExecutable {
 
                 Ok(Value::new(None))
             }
-            "PythonBytecodeModule" => self.starlark_add_filesystem_relative_module_bytecode(
+            "PythonBytecodeModule" => self.add_python_bytecode_module(
                 env,
-                prefix,
                 resource,
-                optimize_level,
+                BytecodeModulePlacement::FilesystemRelative(&prefix.to_string()),
             ),
             "PythonPackageResource" => {
                 self.starlark_add_filesystem_relative_package_resource(env, prefix, resource)
@@ -742,6 +1345,9 @@ impl PythonExecutable {
                 .starlark_add_filesystem_relative_package_distribution_resource(
                     env, prefix, resource,
                 ),
+            "PythonExtensionModule" if strict => {
+                self.starlark_add_filesystem_relative_extension_module(env, prefix, resource)
+            }
             "PythonExtensionModule" => self.starlark_add_extension_module(env, resource),
             _ => Err(RuntimeError {
                 code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
@@ -749,10 +1355,27 @@ impl PythonExecutable {
                 label: ".add_in_memory_python_resource()".to_string(),
             }
             .into()),
-        }
+        }?;
+
+        self.summarize_python_resource(
+            summary,
+            &self.resource_placements(Some(ResourcePlacement::FilesystemRelative)),
+            resource,
+            add_source_module,
+            add_bytecode_module,
+            &optimize_levels,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: ".add_filesystem_relative_python_resource()".to_string(),
+            }
+            .into())
+        })
     }
 
-    /// PythonExecutable.add_python_resource(resource, add_source_module=true, add_bytecode_module=true, optimize_level=0)
+    /// PythonExecutable.add_python_resource(resource, add_source_module=true, add_bytecode_module=true, optimize_level=0, location=None)
     pub fn starlark_add_python_resource(
         &mut self,
         env: &Environment,
@@ -760,10 +1383,57 @@ impl PythonExecutable {
         add_source_module: &Value,
         add_bytecode_module: &Value,
         optimize_level: &Value,
+        location: &Value,
     ) -> ValueResult {
+        let mut summary = PythonResourcesSummary::default();
+        self.add_python_resource(
+            env,
+            &mut summary,
+            resource,
+            add_source_module,
+            add_bytecode_module,
+            optimize_level,
+        )?;
+
+        Ok(Value::new(summary))
+    }
+
+    /// Add a Python resource, recording it in `summary`.
+    fn add_python_resource(
+        &mut self,
+        env: &Environment,
+        summary: &mut PythonResourcesSummary,
+        resource: &Value,
+        add_source_module: &Value,
+        add_bytecode_module: &Value,
+        optimize_level: &Value,
+    ) -> Result<(), ValueError> {
+        match optional_location_arg(location)? {
+            RequestedLocation::Default => {}
+            RequestedLocation::InMemory => {
+                return self.starlark_add_in_memory_python_resource(
+                    env,
+                    resource,
+                    add_source_module,
+                    add_bytecode_module,
+                    optimize_level,
+                );
+            }
+            RequestedLocation::FilesystemRelative(prefix) => {
+                return self.starlark_add_filesystem_relative_python_resource(
+                    env,
+                    &Value::from(prefix),
+                    resource,
+                    add_source_module,
+                    add_bytecode_module,
+                    optimize_level,
+                );
+            }
+        }
+
         let add_source_module = required_bool_arg("add_source_module", &add_source_module)?;
         let add_bytecode_module = required_bool_arg("add_bytecode_module", &add_bytecode_module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        let optimize_levels = optimize_levels_arg(&optimize_level)?;
 
         match resource.get_type() {
             "PythonSourceModule" => {
@@ -777,7 +1447,7 @@ impl PythonExecutable {
                 Ok(Value::new(None))
             }
             "PythonBytecodeModule" => {
-                self.starlark_add_module_bytecode(env, resource, optimize_level)
+                self.add_python_bytecode_module(env, resource, BytecodeModulePlacement::Policy)
             }
             "PythonPackageResource" => self.starlark_add_package_resource(env, resource),
             "PythonPackageDistributionResource" => {
@@ -790,7 +1460,24 @@ impl PythonExecutable {
                 label: ".add_python_resource()".to_string(),
             }
             .into()),
-        }
+        }?;
+
+        self.summarize_python_resource(
+            summary,
+            &self.resource_placements(None),
+            resource,
+            add_source_module,
+            add_bytecode_module,
+            &optimize_levels,
+        )
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: ".add_python_resource()".to_string(),
+            }
+            .into())
+        })
     }
 
     /// PythonExecutable.add_in_memory_python_resources(resources, add_source_module=true, add_bytecode_module=true, optimize_level=0)
@@ -804,11 +1491,14 @@ impl PythonExecutable {
     ) -> ValueResult {
         required_bool_arg("add_source_module", &add_source_module)?;
         required_bool_arg("add_bytecode_module", &add_bytecode_module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        optimize_levels_arg(&optimize_level)?;
+
+        let mut summary = PythonResourcesSummary::default();
 
         for resource in resources.into_iter()? {
-            self.starlark_add_in_memory_python_resource(
+            self.add_in_memory_python_resource(
                 env,
+                &mut summary,
                 &resource,
                 add_source_module,
                 add_bytecode_module,
@@ -816,7 +1506,11 @@ impl PythonExecutable {
             )?;
         }
 
-        Ok(Value::new(None))
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+        info!(&logger, "added resources: {}", summary.to_str());
+
+        Ok(Value::new(summary))
     }
 
     /// PythonExecutable.add_filesystem_relative_python_resources(prefix, resources, add_source_module=true, add_bytecode_module=true, optimize_level=0)
@@ -832,11 +1526,14 @@ impl PythonExecutable {
         required_str_arg("prefix", &prefix)?;
         required_bool_arg("add_source_module", &add_source_module)?;
         required_bool_arg("add_bytecode_module", &add_bytecode_module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        optimize_levels_arg(&optimize_level)?;
+
+        let mut summary = PythonResourcesSummary::default();
 
         for resource in resources.into_iter()? {
-            self.starlark_add_filesystem_relative_python_resource(
+            self.add_filesystem_relative_python_resource(
                 env,
+                &mut summary,
                 prefix,
                 &resource,
                 add_source_module,
@@ -845,10 +1542,14 @@ impl PythonExecutable {
             )?;
         }
 
-        Ok(Value::new(None))
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+        info!(&logger, "added resources: {}", summary.to_str());
+
+        Ok(Value::new(summary))
     }
 
-    /// PythonExecutable.add_python_resources(resources, add_source_module=true, add_bytecode_module=true, optimize_level=0)
+    /// PythonExecutable.add_python_resources(resources, add_source_module=true, add_bytecode_module=true, optimize_level=0, location=None)
     pub fn starlark_add_python_resources(
         &mut self,
         env: &Environment,
@@ -856,368 +1557,2577 @@ impl PythonExecutable {
         add_source_module: &Value,
         add_bytecode_module: &Value,
         optimize_level: &Value,
+        location: &Value,
     ) -> ValueResult {
         required_bool_arg("add_source_module", &add_source_module)?;
         required_bool_arg("add_bytecode_module", &add_bytecode_module)?;
-        required_type_arg("optimize_level", "int", &optimize_level)?;
+        optimize_levels_arg(&optimize_level)?;
+        optional_location_arg(location)?;
+
+        let mut summary = PythonResourcesSummary::default();
 
         for resource in resources.into_iter()? {
-            self.starlark_add_python_resource(
+            self.add_python_resource(
                 env,
+                &mut summary,
                 &resource,
                 add_source_module,
                 add_bytecode_module,
                 optimize_level,
+                location,
             )?;
         }
 
-        Ok(Value::new(None))
-    }
+        let context = env.get("CONTEXT").expect("CONTEXT not set");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+        info!(&logger, "added resources: {}", summary.to_str());
 
-    /// PythonExecutable.to_embedded_resources()
-    pub fn starlark_to_embedded_resources(&self) -> ValueResult {
-        Ok(Value::new(PythonEmbeddedResources {
-            exe: self.exe.clone_box(),
-        }))
+        Ok(Value::new(summary))
     }
 
-    /// PythonExecutable.filter_resources_from_files(files=None, glob_files=None)
-    pub fn starlark_filter_resources_from_files(
+    /// PythonExecutable.add_resource_transform(pattern, transform=None, python_hook=None, python_hook_function="transform")
+    pub fn starlark_add_resource_transform(
         &mut self,
         env: &Environment,
-        files: &Value,
-        glob_files: &Value,
+        pattern: &Value,
+        transform: &Value,
+        python_hook: &Value,
+        python_hook_function: &Value,
     ) -> ValueResult {
-        optional_list_arg("files", "string", &files)?;
-        optional_list_arg("glob_files", "string", &glob_files)?;
+        let pattern = required_str_arg("pattern", &pattern)?;
+        let transform = optional_str_arg("transform", &transform)?;
+        let python_hook = optional_str_arg("python_hook", &python_hook)?;
+        let python_hook_function = required_str_arg("python_hook_function", &python_hook_function)?;
+
+        let action = match (transform, python_hook) {
+            (Some(transform), None) => ResourceTransformAction::from_name(&transform),
+            (None, Some(path)) => {
+                let cwd = env.get("CWD").expect("CWD not defined").to_string();
+
+                Ok(ResourceTransformAction::PythonHook {
+                    path: PathBuf::from(cwd).join(path),
+                    function: python_hook_function,
+                })
+            }
+            _ => Err(anyhow!(
+                "exactly one of transform and python_hook must be defined"
+            )),
+        }
+        .and_then(|action| ResourceTransform::new(&pattern, action))
+        .or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "add_resource_transform()".to_string(),
+            }
+            .into())
+        })?;
 
-        let files = match files.get_type() {
-            "list" => files
-                .into_iter()?
-                .map(|x| PathBuf::from(x.to_string()))
-                .collect(),
-            "NoneType" => Vec::new(),
-            _ => panic!("type should have been validated above"),
-        };
+        self.resource_transforms.push(action);
 
-        let glob_files = match glob_files.get_type() {
-            "list" => glob_files.into_iter()?.map(|x| x.to_string()).collect(),
+        Ok(Value::new(None))
+    }
+
+    /// PythonExecutable.add_venv_support()
+    pub fn starlark_add_venv_support(&mut self, env: &Environment) -> ValueResult {
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        info!(&logger, "adding virtual environment support");
+        self.exe.add_venv_support().or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_venv_support()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// PythonExecutable.add_debugpy_listener(listen_env, requirement="debugpy", allow_remote_hosts=false)
+    pub fn starlark_add_debugpy_listener(
+        &mut self,
+        env: &Environment,
+        listen_env: &Value,
+        requirement: &Value,
+        allow_remote_hosts: &Value,
+    ) -> ValueResult {
+        let listen_env = required_str_arg("listen_env", &listen_env)?;
+        let requirement = required_str_arg("requirement", &requirement)?;
+        let allow_remote_hosts = required_bool_arg("allow_remote_hosts", &allow_remote_hosts)?;
+
+        if listen_env.is_empty() || listen_env.contains('=') || listen_env.contains('\0') {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("invalid environment variable name: {}", listen_env),
+                label: "add_debugpy_listener()".to_string(),
+            }
+            .into());
+        }
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, offline, pip_cache_path) = context.downcast_apply(|x: &EnvironmentContext| {
+            (x.logger.clone(), x.offline, x.pip_cache_path.clone())
+        });
+
+        // Like PythonDistribution.pip_install(), pip doesn't access package
+        // indexes in offline mode.
+        let mut pip_envs = HashMap::new();
+        if offline {
+            pip_envs.insert("PIP_NO_INDEX".to_string(), "1".to_string());
+        }
+        match pip_cache_path {
+            Some(path) => {
+                pip_envs.insert("PIP_CACHE_DIR".to_string(), path.display().to_string());
+            }
+            None => {
+                pip_envs.insert("PIP_NO_CACHE_DIR".to_string(), "1".to_string());
+            }
+        }
+
+        info!(&logger, "adding debugpy listener enabled by {}", listen_env);
+        self.exe
+            .add_debugpy_listener(
+                &logger,
+                &listen_env,
+                &requirement,
+                allow_remote_hosts,
+                &pip_envs,
+            )
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "add_debugpy_listener()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// PythonExecutable.add_remote_resources_package(package)
+    pub fn starlark_add_remote_resources_package(
+        &mut self,
+        env: &Environment,
+        package: &Value,
+    ) -> ValueResult {
+        let package = required_str_arg("package", &package)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        info!(&logger, "storing {} in remote resources", package);
+        self.exe
+            .add_remote_resources_package(&package)
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "add_remote_resources_package()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// PythonExecutable.add_zipimport_package(package)
+    pub fn starlark_add_zipimport_package(
+        &mut self,
+        env: &Environment,
+        package: &Value,
+    ) -> ValueResult {
+        let package = required_str_arg("package", &package)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        info!(&logger, "importing {} from zip archive", package);
+        self.exe.add_zipimport_package(&package).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_zipimport_package()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// PythonExecutable.add_startup_cache(codecs=None, regex_patterns=None, profile_files=None, profile_glob_files=None)
+    pub fn starlark_add_startup_cache(
+        &mut self,
+        env: &Environment,
+        codecs: &Value,
+        regex_patterns: &Value,
+        profile_files: &Value,
+        profile_glob_files: &Value,
+    ) -> ValueResult {
+        optional_list_arg("codecs", "string", &codecs)?;
+        optional_list_arg("regex_patterns", "string", &regex_patterns)?;
+        optional_list_arg("profile_files", "string", &profile_files)?;
+        optional_list_arg("profile_glob_files", "string", &profile_glob_files)?;
+
+        let codecs: Vec<String> = match codecs.get_type() {
+            "list" => codecs.into_iter()?.map(|x| x.to_string()).collect(),
             "NoneType" => Vec::new(),
             _ => panic!("type should have been validated above"),
         };
 
-        let files_refs = files.iter().map(|x| x.as_ref()).collect::<Vec<&Path>>();
-        let glob_files_refs = glob_files.iter().map(|x| x.as_ref()).collect::<Vec<&str>>();
+        let mut patterns: Vec<(String, u32)> = match regex_patterns.get_type() {
+            "list" => regex_patterns
+                .into_iter()?
+                .map(|x| (x.to_string(), 0))
+                .collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        let profile_files = match profile_files.get_type() {
+            "list" => profile_files
+                .into_iter()?
+                .map(|x| PathBuf::from(x.to_string()))
+                .collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        let profile_glob_files = match profile_glob_files.get_type() {
+            "list" => profile_glob_files
+                .into_iter()?
+                .map(|x| x.to_string())
+                .collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        let profile_files_refs = profile_files
+            .iter()
+            .map(|x| x.as_ref())
+            .collect::<Vec<&Path>>();
+        let profile_glob_files_refs = profile_glob_files
+            .iter()
+            .map(|x| x.as_ref())
+            .collect::<Vec<&str>>();
 
         let context = env.get("CONTEXT").expect("CONTEXT not defined");
         let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
 
+        if !profile_files_refs.is_empty() || !profile_glob_files_refs.is_empty() {
+            let names =
+                resolve_resource_names_from_files(&profile_files_refs, &profile_glob_files_refs)
+                    .or_else(|e| {
+                        Err(RuntimeError {
+                            code: "PYOXIDIZER_BUILD",
+                            message: e.to_string(),
+                            label: "add_startup_cache()".to_string(),
+                        }
+                        .into())
+                    })?;
+
+            let modules = self
+                .exe
+                .in_memory_module_sources()
+                .into_iter()
+                .filter_map(|(name, module)| {
+                    if names.contains(&name) {
+                        Some(module)
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            info!(
+                &logger,
+                "searching regular expressions in {} profiled modules",
+                modules.len()
+            );
+            let found = find_regex_patterns(self.exe.python_exe_path(), &modules).or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "add_startup_cache()".to_string(),
+                }
+                .into())
+            })?;
+            for pattern in found {
+                if !patterns.contains(&pattern) {
+                    patterns.push(pattern);
+                }
+            }
+        }
+
+        info!(
+            &logger,
+            "adding startup cache with {} codecs and {} regular expressions",
+            codecs.len(),
+            patterns.len()
+        );
         self.exe
-            .filter_resources_from_files(&logger, &files_refs, &glob_files_refs)
+            .add_startup_cache(&codecs, &patterns)
             .or_else(|e| {
                 Err(RuntimeError {
-                    code: "RUNTIME_ERROR",
+                    code: "PYOXIDIZER_BUILD",
                     message: e.to_string(),
-                    label: "filter_from_files()".to_string(),
+                    label: "add_startup_cache()".to_string(),
                 }
                 .into())
             })?;
 
         Ok(Value::new(None))
     }
-}
 
-starlark_module! { python_executable_env =>
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_module_source(env env, this, module) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_module_source(&env, &module)
-        })
-    }
+    /// PythonExecutable.sign_external_resources(signing_key_path)
+    pub fn starlark_sign_external_resources(
+        &mut self,
+        env: &Environment,
+        signing_key_path: &Value,
+    ) -> ValueResult {
+        let signing_key_path = required_str_arg("signing_key_path", &signing_key_path)?;
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_module_source(env env, this, prefix, module) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_module_source(&env, &prefix, &module)
-        })
-    }
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let (logger, cwd) =
+            context.downcast_apply(|x: &EnvironmentContext| (x.logger.clone(), x.cwd.clone()));
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_module_source(env env, this, module) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_module_source(&env, &module)
-        })
-    }
+        let path = cwd.join(signing_key_path);
 
-    // TODO consider unifying with add_module_source() so there only needs to be
-    // a single function call.
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_module_bytecode(env env, this, module, optimize_level=0) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_module_bytecode(&env, &module, &optimize_level)
-        })
-    }
+        info!(
+            &logger,
+            "signing external resources with {}",
+            path.display()
+        );
+        std::fs::read(&path)
+            .or_else(|e| Err(anyhow!("reading {}: {}", path.display(), e)))
+            .and_then(|data| ResourceSigningKey::from_key_file_data(&data))
+            .and_then(|key| self.exe.sign_external_resources(&key))
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "sign_external_resources()".to_string(),
+                }
+                .into())
+            })?;
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_module_bytecode(env env, this, prefix, module, optimize_level=0) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_module_bytecode(&env, &prefix, &module, &optimize_level)
-        })
+        Ok(Value::new(None))
     }
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_module_bytecode(env env, this, module, optimize_level=0) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_module_bytecode(&env, &module, &optimize_level)
-        })
-    }
+    /// PythonExecutable.set_windows_manifest(dpi_awareness=None, long_path_aware=True, uac_execution_level=None)
+    pub fn starlark_set_windows_manifest(
+        &mut self,
+        dpi_awareness: &Value,
+        long_path_aware: &Value,
+        uac_execution_level: &Value,
+    ) -> ValueResult {
+        let dpi_awareness = optional_str_arg("dpi_awareness", &dpi_awareness)?;
+        let long_path_aware = required_bool_arg("long_path_aware", &long_path_aware)?;
+        let uac_execution_level = optional_str_arg("uac_execution_level", &uac_execution_level)?;
+
+        dpi_awareness
+            .map(|name| DpiAwareness::from_name(&name))
+            .transpose()
+            .and_then(|dpi_awareness| {
+                Ok(WindowsManifestOptions {
+                    dpi_awareness,
+                    long_path_aware,
+                    uac_execution_level: uac_execution_level
+                        .map(|name| UacExecutionLevel::from_name(&name))
+                        .transpose()?,
+                })
+            })
+            .and_then(|options| self.exe.set_windows_manifest(&options))
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: e.to_string(),
+                    label: "set_windows_manifest()".to_string(),
+                }
+                .into())
+            })?;
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_package_resource(env env, this, resource) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_package_resource(&env, &resource)
-        })
+        Ok(Value::new(None))
     }
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_package_resource(env env, this, prefix, resource) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_package_resource(&env, &prefix, &resource)
-        })
+    /// PythonExecutable.set_macos_signing(identity, hardened_runtime=True, entitlements=None)
+    pub fn starlark_set_macos_signing(
+        &mut self,
+        identity: &Value,
+        hardened_runtime: &Value,
+        entitlements: &Value,
+    ) -> ValueResult {
+        let identity = required_str_arg("identity", &identity)?;
+        let hardened_runtime = required_bool_arg("hardened_runtime", &hardened_runtime)?;
+        optional_list_arg("entitlements", "string", &entitlements)?;
+
+        let entitlements: Vec<String> = match entitlements.get_type() {
+            "list" => entitlements.into_iter()?.map(|x| x.to_string()).collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        MacOsSigningOptions::new(&identity, hardened_runtime, &entitlements)
+            .and_then(|options| self.exe.set_macos_signing(&options))
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: e.to_string(),
+                    label: "set_macos_signing()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::new(None))
     }
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_package_resource(env env, this, resource) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_package_resource(&env, &resource)
-        })
+    /// PythonExecutable.set_linux_service(description=None, install_dir=None, network=False, capabilities=None, writable_paths=None, executable_memory=False, extra_syscalls=None)
+    #[allow(clippy::too_many_arguments)]
+    pub fn starlark_set_linux_service(
+        &mut self,
+        description: &Value,
+        install_dir: &Value,
+        network: &Value,
+        capabilities: &Value,
+        writable_paths: &Value,
+        executable_memory: &Value,
+        extra_syscalls: &Value,
+    ) -> ValueResult {
+        let description = optional_str_arg("description", &description)?;
+        let install_dir = optional_str_arg("install_dir", &install_dir)?;
+        let network = required_bool_arg("network", &network)?;
+        optional_list_arg("capabilities", "string", &capabilities)?;
+        optional_list_arg("writable_paths", "string", &writable_paths)?;
+        let executable_memory = required_bool_arg("executable_memory", &executable_memory)?;
+        optional_list_arg("extra_syscalls", "string", &extra_syscalls)?;
+
+        let name = self.exe.name();
+
+        let options = LinuxServiceOptions {
+            description: description.unwrap_or_else(|| name.clone()),
+            install_dir: install_dir.unwrap_or_else(|| format!("/opt/{}", name)),
+            network,
+            capabilities: optional_string_list(&capabilities)?.into_iter().collect(),
+            writable_paths: optional_string_list(&writable_paths)?,
+            executable_memory,
+            extra_syscalls: optional_string_list(&extra_syscalls)?.into_iter().collect(),
+        };
+
+        self.exe.set_linux_service(&options).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "set_linux_service()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
     }
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_package_distribution_resource(env env, this, resource) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_package_distribution_resource(&env, &resource)
-        })
+    /// PythonExecutable.set_filesystem_module_layout(layout)
+    pub fn starlark_set_filesystem_module_layout(&mut self, layout: &Value) -> ValueResult {
+        let layout = required_str_arg("layout", &layout)?;
+
+        FilesystemModuleLayout::try_from(layout.as_str())
+            .and_then(|layout| self.exe.set_filesystem_module_layout(layout))
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: e.to_string(),
+                    label: "set_filesystem_module_layout()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::new(None))
     }
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_package_distribution_resource(env env, this, prefix, resource) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_package_distribution_resource(&env, &prefix, &resource)
-        })
+    /// PythonExecutable.analyze_resource_compatibility(include_stdlib=false)
+    pub fn starlark_analyze_resource_compatibility(
+        &self,
+        env: &Environment,
+        include_stdlib: &Value,
+    ) -> ValueResult {
+        let include_stdlib = required_bool_arg("include_stdlib", &include_stdlib)?;
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        let issues = self
+            .exe
+            .in_memory_import_issues(include_stdlib)
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: e.to_string(),
+                    label: "analyze_resource_compatibility()".to_string(),
+                }
+                .into())
+            })?;
+
+        for issue in &issues {
+            warn!(&logger, "{}", issue.message);
+        }
+
+        Ok(Value::from(
+            issues
+                .into_iter()
+                .map(|issue| Value::new(issue.message))
+                .collect::<Vec<Value>>(),
+        ))
+    }
+
+    /// PythonExecutable.to_embedded_resources(data_only=false)
+    pub fn starlark_to_embedded_resources(&self, data_only: &Value) -> ValueResult {
+        let data_only = required_bool_arg("data_only", &data_only)?;
+
+        Ok(Value::new(PythonEmbeddedResources {
+            exe: self.exe.clone_box(),
+            data_only,
+        }))
+    }
+
+    /// PythonExecutable.to_build_summary(format="markdown", include_record=false)
+    pub fn starlark_to_build_summary(&self, format: &Value, include_record: &Value) -> ValueResult {
+        let format = required_str_arg("format", &format)?;
+        let include_record = required_bool_arg("include_record", &include_record)?;
+
+        let format = SummaryFormat::try_from(format.as_str()).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e,
+                label: "to_build_summary()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(BuildSummary {
+            exe: self.exe.clone_box(),
+            format,
+            include_record,
+        }))
+    }
+
+    /// PythonExecutable.to_standalone_folder(lib_dir="lib", data_dir="data")
+    pub fn starlark_to_standalone_folder(&self, lib_dir: &Value, data_dir: &Value) -> ValueResult {
+        let lib_dir = required_str_arg("lib_dir", &lib_dir)?;
+        let data_dir = required_str_arg("data_dir", &data_dir)?;
+
+        for (name, value) in &[("lib_dir", &lib_dir), ("data_dir", &data_dir)] {
+            let path = Path::new(value.as_str());
+
+            if path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!(
+                        "{} must be a path relative to the output directory; got {}",
+                        name, value
+                    ),
+                    label: "to_standalone_folder()".to_string(),
+                }
+                .into());
+            }
+        }
+
+        Ok(Value::new(StandaloneFolder {
+            exe: self.exe.clone_box(),
+            lib_dir,
+            data_dir,
+        }))
+    }
+
+    /// PythonExecutable.to_c_library(name=None, kind="static")
+    pub fn starlark_to_c_library(&self, name: &Value, kind: &Value) -> ValueResult {
+        let name = self.library_name(name, "to_c_library()")?;
+        let kind = required_str_arg("kind", &kind)?;
+
+        let kind = CLibraryKind::try_from(kind.as_str()).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e,
+                label: "to_c_library()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(PythonCLibrary {
+            exe: self.exe.clone_box(),
+            name,
+            kind,
+        }))
+    }
+
+    /// Resolve the `name` argument of methods building libraries.
+    ///
+    /// Defaults to the name of the executable.
+    fn library_name(&self, name: &Value, label: &str) -> Result<String, ValueError> {
+        let name =
+            optional_str_arg("name", &name)?.unwrap_or_else(|| self.exe.name().replace('-', "_"));
+
+        // The name is used in file names and in the include guard of the header.
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!(
+                    "name must only contain ASCII letters, digits and underscores and not start with a digit; got {}",
+                    name
+                ),
+                label: label.to_string(),
+            }
+            .into());
+        }
+
+        Ok(name)
+    }
+
+    /// PythonExecutable.to_extension_bundle(name=None)
+    pub fn starlark_to_extension_bundle(&self, name: &Value) -> ValueResult {
+        // The name is the name of the module, so it must be a valid identifier.
+        let name = match name.get_type() {
+            "NoneType" => Value::from(format!("{}_bundle", self.exe.name().replace('-', "_"))),
+            _ => name.clone(),
+        };
+        let name = self.library_name(&name, "to_extension_bundle()")?;
+
+        Ok(Value::new(PythonExtensionBundle {
+            exe: self.exe.clone_box(),
+            name,
+        }))
+    }
+
+    /// PythonExecutable.to_plugin_library(exports, name=None, extension=None, bundle=false)
+    pub fn starlark_to_plugin_library(
+        &self,
+        exports: &Value,
+        name: &Value,
+        extension: &Value,
+        bundle: &Value,
+    ) -> ValueResult {
+        required_dict_arg("exports", "string", "string", &exports)?;
+        let name = self.library_name(name, "to_plugin_library()")?;
+        let extension = optional_str_arg("extension", &extension)?;
+        let bundle = required_bool_arg("bundle", &bundle)?;
+
+        let error = |message: String| -> ValueError {
+            RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message,
+                label: "to_plugin_library()".to_string(),
+            }
+            .into()
+        };
+
+        let mut plugin_exports: Vec<PluginExport> = Vec::new();
+        for declaration in exports.into_iter()? {
+            let callable = exports.at(declaration.clone())?.to_string();
+            let export = PluginExport::parse(&declaration.to_string(), &callable)
+                .map_err(|e| error(e.to_string()))?;
+
+            if plugin_exports.iter().any(|e| e.name == export.name) {
+                return Err(error(format!("{} is exported more than once", export.name)));
+            }
+
+            plugin_exports.push(export);
+        }
+
+        if plugin_exports.is_empty() {
+            return Err(error(
+                "exports must declare at least one function".to_string(),
+            ));
+        }
+
+        let extension = match extension {
+            Some(extension) => {
+                let extension = extension.trim_start_matches('.').to_string();
+
+                if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(error(format!(
+                        "extension must only contain ASCII letters and digits; got {}",
+                        extension
+                    )));
+                }
+
+                Some(extension)
+            }
+            None => None,
+        };
+
+        // Plugin libraries don't export the symbols of an interpreter linked
+        // statically, so extension modules loaded from shared libraries can't
+        // resolve them.
+        if !self.exe.links_shared_libpython() {
+            for module in self.exe.extension_module_names() {
+                let loaded_from_library = match self.exe.resource(&module) {
+                    Some(resource) => {
+                        resource.in_memory_extension_module_shared_library.is_some()
+                            || resource
+                                .relative_path_extension_module_shared_library
+                                .is_some()
+                    }
+                    None => false,
+                };
+
+                if loaded_from_library {
+                    return Err(error(format!(
+                        "plugins can't load extension module {} from a shared library",
+                        module
+                    )));
+                }
+            }
+        }
+
+        Ok(Value::new(PythonPluginLibrary {
+            exe: self.exe.clone_box(),
+            name,
+            exports: plugin_exports,
+            extension,
+            bundle,
+        }))
+    }
+
+    /// PythonExecutable.to_zipapp(name, interpreter=None)
+    pub fn starlark_to_zipapp(&self, name: &Value, interpreter: &Value) -> ValueResult {
+        let name = required_str_arg("name", &name)?;
+        let interpreter = optional_str_arg("interpreter", &interpreter)?;
+
+        Ok(Value::new(ZipApp {
+            exe: self.exe.clone_box(),
+            name,
+            interpreter,
+        }))
+    }
+
+    /// PythonExecutable.to_wix_msi_builder(id_prefix, product_name, product_version, product_manufacturer, upgrade_code=None)
+    pub fn starlark_to_wix_msi_builder(
+        &self,
+        id_prefix: &Value,
+        product_name: &Value,
+        product_version: &Value,
+        product_manufacturer: &Value,
+        upgrade_code: &Value,
+    ) -> ValueResult {
+        let id_prefix = required_str_arg("id_prefix", &id_prefix)?;
+        let product_name = required_str_arg("product_name", &product_name)?;
+        let product_version = required_str_arg("product_version", &product_version)?;
+        let product_manufacturer = required_str_arg("product_manufacturer", &product_manufacturer)?;
+        let upgrade_code = optional_str_arg("upgrade_code", &upgrade_code)?;
+
+        if id_prefix.is_empty()
+            || !id_prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!(
+                    "id_prefix must only contain letters, digits, _ and .; got {}",
+                    id_prefix
+                ),
+                label: "to_wix_msi_builder()".to_string(),
+            }
+            .into());
+        }
+
+        WixMsiOptions::validate_version(&product_version).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "to_wix_msi_builder()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(WiXMSIBuilder {
+            exe: self.exe.clone_box(),
+            options: WixMsiOptions {
+                id_prefix,
+                product_name,
+                product_version,
+                product_manufacturer,
+                upgrade_code,
+                shortcuts: Vec::new(),
+            },
+            program_files: RawFileManifest::default(),
+        }))
+    }
+
+    /// PythonExecutable.to_macos_application_bundle_builder(bundle_name, info_plist)
+    pub fn starlark_to_macos_application_bundle_builder(
+        &self,
+        bundle_name: &Value,
+        info_plist: &Value,
+    ) -> ValueResult {
+        let bundle_name = required_str_arg("bundle_name", &bundle_name)?;
+        required_type_arg("info_plist", "dict", &info_plist)?;
+
+        let info_plist = starlark_to_plist_dict(&info_plist)?;
+
+        let bundle = MacOsApplicationBundle::new(&bundle_name, info_plist).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "to_macos_application_bundle_builder()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(MacOsApplicationBundleBuilder {
+            exe: self.exe.clone_box(),
+            bundle,
+        }))
+    }
+
+    /// PythonExecutable.to_appimage_builder(app_id, name, icon_path, categories=None, comment=None, terminal=False)
+    #[allow(clippy::too_many_arguments)]
+    pub fn starlark_to_appimage_builder(
+        &self,
+        env: &Environment,
+        app_id: &Value,
+        name: &Value,
+        icon_path: &Value,
+        categories: &Value,
+        comment: &Value,
+        terminal: &Value,
+    ) -> ValueResult {
+        let app_id = required_str_arg("app_id", &app_id)?;
+
+        AppImageOptions::validate_app_id(&app_id).or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "to_appimage_builder()".to_string(),
+            }
+            .into())
+        })?;
+
+        let desktop_entry = starlark_to_desktop_entry(
+            "to_appimage_builder()",
+            name,
+            categories,
+            comment,
+            terminal,
+        )?;
+        let (icon_extension, icon_data) =
+            starlark_read_icon(env, "to_appimage_builder()", icon_path)?;
+
+        Ok(Value::new(AppImageBuilder {
+            exe: self.exe.clone_box(),
+            options: AppImageOptions {
+                app_id,
+                desktop_entry,
+                icon_extension,
+                icon_data,
+            },
+            files: RawFileManifest::default(),
+        }))
+    }
+
+    /// PythonExecutable.to_snap_builder(name, version, summary, description="", base="core18", grade="stable", confinement="strict", plugs=None)
+    #[allow(clippy::too_many_arguments)]
+    pub fn starlark_to_snap_builder(
+        &self,
+        name: &Value,
+        version: &Value,
+        summary: &Value,
+        description: &Value,
+        base: &Value,
+        grade: &Value,
+        confinement: &Value,
+        plugs: &Value,
+    ) -> ValueResult {
+        let name = required_str_arg("name", &name)?;
+        let version = required_str_arg("version", &version)?;
+        let summary = required_str_arg("summary", &summary)?;
+        let description = required_str_arg("description", &description)?;
+        let base = required_str_arg("base", &base)?;
+        let grade = required_str_arg("grade", &grade)?;
+        let confinement = required_str_arg("confinement", &confinement)?;
+        optional_list_arg("plugs", "string", &plugs)?;
+
+        let plugs = match plugs.get_type() {
+            "list" => plugs.into_iter()?.map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let options = SnapOptions {
+            name,
+            version,
+            summary,
+            description,
+            base,
+            grade,
+            confinement,
+            plugs,
+            desktop_entry: None,
+            icon: None,
+        };
+
+        options.validate().or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: "to_snap_builder()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(SnapBuilder {
+            exe: self.exe.clone_box(),
+            options,
+            files: RawFileManifest::default(),
+        }))
+    }
+
+    /// PythonExecutable.to_type_stubs(prefix="")
+    pub fn starlark_to_type_stubs(&self, prefix: &Value) -> ValueResult {
+        let prefix = required_str_arg("prefix", &prefix)?;
+
+        let mut manifest = RawFileManifest::default();
+
+        for resource in &self.type_stubs {
+            resource
+                .add_to_file_manifest(&mut manifest, &prefix)
+                .or_else(|e| {
+                    Err(RuntimeError {
+                        code: "PYOXIDIZER_BUILD",
+                        message: e.to_string(),
+                        label: "to_type_stubs()".to_string(),
+                    }
+                    .into())
+                })?;
+        }
+
+        Ok(Value::new(FileManifest { manifest }))
+    }
+
+    /// PythonExecutable.filter_resources_from_files(files=None, glob_files=None)
+    pub fn starlark_filter_resources_from_files(
+        &mut self,
+        env: &Environment,
+        files: &Value,
+        glob_files: &Value,
+    ) -> ValueResult {
+        optional_list_arg("files", "string", &files)?;
+        optional_list_arg("glob_files", "string", &glob_files)?;
+
+        let files = match files.get_type() {
+            "list" => files
+                .into_iter()?
+                .map(|x| PathBuf::from(x.to_string()))
+                .collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        let glob_files = match glob_files.get_type() {
+            "list" => glob_files.into_iter()?.map(|x| x.to_string()).collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("type should have been validated above"),
+        };
+
+        let files_refs = files.iter().map(|x| x.as_ref()).collect::<Vec<&Path>>();
+        let glob_files_refs = glob_files.iter().map(|x| x.as_ref()).collect::<Vec<&str>>();
+
+        let context = env.get("CONTEXT").expect("CONTEXT not defined");
+        let logger = context.downcast_apply(|x: &EnvironmentContext| x.logger.clone());
+
+        self.exe
+            .filter_resources_from_files(&logger, &files_refs, &glob_files_refs)
+            .or_else(|e| {
+                Err(RuntimeError {
+                    code: "RUNTIME_ERROR",
+                    message: e.to_string(),
+                    label: "filter_from_files()".to_string(),
+                }
+                .into())
+            })?;
+
+        Ok(Value::new(None))
+    }
+}
+
+starlark_module! { python_executable_env =>
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_module_source(env env, this, module) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_module_source(&env, &module)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_module_source(env env, this, prefix, module) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_module_source(&env, &prefix, &module)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_module_source(env env, this, module) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_module_source(&env, &module)
+        })
+    }
+
+    // TODO consider unifying with add_module_source() so there only needs to be
+    // a single function call.
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_module_bytecode(env env, this, module, optimize_level=0) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_module_bytecode(&env, &module, &optimize_level)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_module_bytecode(env env, this, prefix, module, optimize_level=0) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_module_bytecode(&env, &prefix, &module, &optimize_level)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_module_bytecode(env env, this, module, optimize_level=0) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_module_bytecode(&env, &module, &optimize_level)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_package_resource(env env, this, resource) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_package_resource(&env, &resource)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_package_resource(env env, this, prefix, resource) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_package_resource(&env, &prefix, &resource)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_package_resource(env env, this, resource) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_package_resource(&env, &resource)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_package_distribution_resource(env env, this, resource) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_package_distribution_resource(&env, &resource)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_package_distribution_resource(env env, this, prefix, resource) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_package_distribution_resource(&env, &prefix, &resource)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_package_distribution_resource(env env, this, resource) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_package_distribution_resource(&env, &resource)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_package_distribution_metadata(env env, this, resources, packages=None) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_package_distribution_metadata(&env, &resources, &packages)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_extension_module(env env, this, module) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_extension_module(&env, &module)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_extension_module(env env, this, prefix, module) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_extension_module(&env, &prefix, &module)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.add_extension_module(env env, this, module) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_extension_module(&env, &module)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_python_resource(
+        env env,
+        this,
+        resource,
+        add_source_module=true,
+        add_bytecode_module=true,
+        optimize_level=0
+        )
+    {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_python_resource(
+                &env,
+                &resource,
+                &add_source_module,
+                &add_bytecode_module,
+                &optimize_level,
+            )
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_python_resource(
+        env env,
+        this,
+        prefix,
+        resource,
+        add_source_module=true,
+        add_bytecode_module=true,
+        optimize_level=0
+        )
+    {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_python_resource(
+                &env,
+                &prefix,
+                &resource,
+                &add_source_module,
+                &add_bytecode_module,
+                &optimize_level,
+            )
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_python_resource(
+        env env,
+        this,
+        resource,
+        add_source_module=true,
+        add_bytecode_module=true,
+        optimize_level=0,
+        location=None
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_python_resource(
+                &env,
+                &resource,
+                &add_source_module,
+                &add_bytecode_module,
+                &optimize_level,
+                &location,
+            )
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.add_in_memory_python_resources(
+        env env,
+        this,
+        resources,
+        add_source_module=true,
+        add_bytecode_module=true,
+        optimize_level=0
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_in_memory_python_resources(
+                &env,
+                &resources,
+                &add_source_module,
+                &add_bytecode_module,
+                &optimize_level,
+            )
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.add_filesystem_relative_python_resources(
+        env env,
+        this,
+        prefix,
+        resources,
+        add_source_module=true,
+        add_bytecode_module=true,
+        optimize_level=0
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_filesystem_relative_python_resources(
+                &env,
+                &prefix,
+                &resources,
+                &add_source_module,
+                &add_bytecode_module,
+                &optimize_level,
+            )
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.add_python_resources(
+        env env,
+        this,
+        resources,
+        add_source_module=true,
+        add_bytecode_module=true,
+        optimize_level=0,
+        location=None
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_python_resources(
+                &env,
+                &resources,
+                &add_source_module,
+                &add_bytecode_module,
+                &optimize_level,
+                &location,
+            )
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.filter_resources_from_files(
+        env env,
+        this,
+        files=None,
+        glob_files=None)
+    {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_filter_resources_from_files(&env, &files, &glob_files)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.add_resource_transform(
+        env env,
+        this,
+        pattern,
+        transform=None,
+        python_hook=None,
+        python_hook_function="transform"
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_resource_transform(
+                &env,
+                &pattern,
+                &transform,
+                &python_hook,
+                &python_hook_function,
+            )
+        })
+    }
+
+    PythonExecutable.add_venv_support(env env, this) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_venv_support(&env)
+        })
+    }
+
+    PythonExecutable.add_debugpy_listener(
+        env env,
+        this,
+        listen_env,
+        requirement="debugpy",
+        allow_remote_hosts=false
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_debugpy_listener(
+                &env,
+                &listen_env,
+                &requirement,
+                &allow_remote_hosts,
+            )
+        })
+    }
+
+    PythonExecutable.add_remote_resources_package(env env, this, package) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_remote_resources_package(&env, &package)
+        })
+    }
+
+    PythonExecutable.add_zipimport_package(env env, this, package) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_zipimport_package(&env, &package)
+        })
+    }
+
+    PythonExecutable.add_startup_cache(
+        env env,
+        this,
+        codecs=None,
+        regex_patterns=None,
+        profile_files=None,
+        profile_glob_files=None
+    ) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_add_startup_cache(
+                &env,
+                &codecs,
+                &regex_patterns,
+                &profile_files,
+                &profile_glob_files,
+            )
+        })
+    }
+
+    PythonExecutable.sign_external_resources(env env, this, signing_key_path) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_sign_external_resources(&env, &signing_key_path)
+        })
+    }
+
+    PythonExecutable.set_windows_manifest(
+        this,
+        dpi_awareness=None,
+        long_path_aware=true,
+        uac_execution_level=None)
+    {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_set_windows_manifest(&dpi_awareness, &long_path_aware, &uac_execution_level)
+        })
+    }
+
+    PythonExecutable.set_macos_signing(
+        this,
+        identity,
+        hardened_runtime=true,
+        entitlements=None)
+    {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_set_macos_signing(&identity, &hardened_runtime, &entitlements)
+        })
+    }
+
+    PythonExecutable.set_linux_service(
+        this,
+        description=None,
+        install_dir=None,
+        network=false,
+        capabilities=None,
+        writable_paths=None,
+        executable_memory=false,
+        extra_syscalls=None)
+    {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_set_linux_service(
+                &description,
+                &install_dir,
+                &network,
+                &capabilities,
+                &writable_paths,
+                &executable_memory,
+                &extra_syscalls,
+            )
+        })
+    }
+
+    PythonExecutable.set_filesystem_module_layout(this, layout) {
+        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
+            exe.starlark_set_filesystem_module_layout(&layout)
+        })
+    }
+
+    PythonExecutable.analyze_resource_compatibility(env env, this, include_stdlib=false) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_analyze_resource_compatibility(&env, &include_stdlib)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonExecutable.to_embedded_resources(this, data_only=false) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_embedded_resources(&data_only)
+        })
+    }
+
+    PythonExecutable.to_build_summary(this, format="markdown", include_record=false) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_build_summary(&format, &include_record)
+        })
+    }
+
+    PythonExecutable.to_standalone_folder(this, lib_dir="lib", data_dir="data") {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_standalone_folder(&lib_dir, &data_dir)
+        })
+    }
+
+    PythonExecutable.to_c_library(this, name=None, kind="static") {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_c_library(&name, &kind)
+        })
+    }
+
+    PythonExecutable.to_extension_bundle(this, name=None) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_extension_bundle(&name)
+        })
+    }
+
+    PythonExecutable.to_plugin_library(this, exports, name=None, extension=None, bundle=false) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_plugin_library(&exports, &name, &extension, &bundle)
+        })
+    }
+
+    PythonExecutable.to_zipapp(this, name, interpreter=None) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_zipapp(&name, &interpreter)
+        })
+    }
+
+    PythonExecutable.to_wix_msi_builder(
+        this,
+        id_prefix,
+        product_name,
+        product_version,
+        product_manufacturer,
+        upgrade_code=None
+    ) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_wix_msi_builder(
+                &id_prefix,
+                &product_name,
+                &product_version,
+                &product_manufacturer,
+                &upgrade_code,
+            )
+        })
+    }
+
+    PythonExecutable.to_macos_application_bundle_builder(this, bundle_name, info_plist) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_macos_application_bundle_builder(&bundle_name, &info_plist)
+        })
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonExecutable.to_appimage_builder(
+        env env,
+        this,
+        app_id,
+        name,
+        icon_path,
+        categories=None,
+        comment=None,
+        terminal=false
+    ) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_appimage_builder(
+                &env,
+                &app_id,
+                &name,
+                &icon_path,
+                &categories,
+                &comment,
+                &terminal,
+            )
+        })
+    }
+
+    PythonExecutable.to_snap_builder(
+        this,
+        name,
+        version,
+        summary,
+        description="",
+        base="core18",
+        grade="stable",
+        confinement="strict",
+        plugs=None
+    ) {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_snap_builder(
+                &name,
+                &version,
+                &summary,
+                &description,
+                &base,
+                &grade,
+                &confinement,
+                &plugs,
+            )
+        })
+    }
+
+    PythonExecutable.to_type_stubs(this, prefix="") {
+        this.downcast_apply(|exe: &PythonExecutable| {
+            exe.starlark_to_type_stubs(&prefix)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testutil::*;
+    use super::*;
+    use crate::py_packaging::startup_cache::STARTUP_CACHE_MODULE_NAME;
+    use python_packaging::resource::{
+        DataLocation, PythonExtensionModule as RawPythonExtensionModule,
+        PythonPackageDistributionResourceFlavor,
+    };
+    use python_packaging::resource_collection::PythonModuleBytecodeProvider;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_default_values() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+
+        let exe = starlark_eval_in_env(&mut env, "dist.to_python_executable('testapp')").unwrap();
+
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert!(!exe.exe.in_memory_module_sources().is_empty());
+            assert!(exe.exe.in_memory_package_resources().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_no_sources() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+
+        let exe = starlark_eval_in_env(
+            &mut env,
+            "dist.to_python_executable('testapp', include_sources=False)",
+        )
+        .unwrap();
+
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert!(exe.exe.in_memory_module_sources().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_add_debugpy_listener_errors() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+
+        // The default in-memory-only policy can't hold debugpy.
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.add_debugpy_listener('TESTAPP_DEBUGPY')").is_err()
+        );
+        assert!(starlark_eval_in_env(&mut env, "exe.add_debugpy_listener('')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "exe.add_debugpy_listener('A=B')").is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.add_debugpy_listener('TESTAPP_DEBUGPY', allow_remote_hosts='yes')"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_add_debugpy_listener_offline() {
+        let mut env = starlark_env();
+        let mut context = env.get("CONTEXT").unwrap();
+        context.downcast_apply_mut(|x: &mut EnvironmentContext| x.offline = true);
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe = dist.to_python_executable('testapp', resources_policy='filesystem-relative-only:lib')",
+        )
+        .unwrap();
+
+        // debugpy can't be downloaded from the package index.
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.add_debugpy_listener('TESTAPP_DEBUGPY')").is_err()
+        );
+    }
+
+    #[test]
+    fn test_add_venv_support() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "exe.add_venv_support()").is_err());
+
+        starlark_eval_in_env(
+            &mut env,
+            "config = PythonInterpreterConfig(python_executable_env='TESTAPP_PYTHON')",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe = dist.to_python_executable('testapp', config=config)",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "exe.add_venv_support()").unwrap();
+
+        let exe = starlark_eval_in_env(&mut env, "exe").unwrap();
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            assert!(exe
+                .exe
+                .in_memory_module_sources()
+                .contains_key("oxidized_venv"));
+            assert!(exe
+                .exe
+                .in_memory_package_resources()
+                .contains_key("ensurepip"));
+        });
+    }
+
+    #[test]
+    fn test_add_remote_resources_package() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.add_remote_resources_package('json')").is_err()
+        );
+
+        starlark_eval_in_env(
+            &mut env,
+            "config = PythonInterpreterConfig(remote_resources_url='http://example.com/testapp')",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe = dist.to_python_executable('testapp', config=config)",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "exe.add_remote_resources_package('json')").unwrap();
+    }
+
+    #[test]
+    fn test_add_zipimport_package() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(&mut env, "exe.add_zipimport_package('json')").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "exe.add_zipimport_package()").is_err());
+    }
+
+    #[test]
+    fn test_add_startup_cache() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let profile_path = temp_dir.path().join("modules-profile");
+        std::fs::write(&profile_path, "textwrap\n")?;
+
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.add_startup_cache(codecs=['utf-16'], regex_patterns=['a+'], profile_files=['{}'])",
+                profile_path.display().to_string().replace('\\', "/")
+            ),
+        )
+        .unwrap();
+
+        let exe = starlark_eval_in_env(&mut env, "exe").unwrap();
+        exe.downcast_apply(|exe: &PythonExecutable| -> Result<()> {
+            let sources = exe.exe.in_memory_module_sources();
+            let module = sources
+                .get(STARTUP_CACHE_MODULE_NAME)
+                .expect("startup cache module should be added");
+            let source = String::from_utf8(module.source.resolve()?)?;
+            assert!(source.contains("CODECS = [\"utf-16\"]\n"));
+            assert!(source.contains("('a+', 0, "));
+            assert!(source.contains("('^[ \\t]+$', 8, "));
+
+            Ok(())
+        })?;
+
+        assert!(starlark_eval_in_env(&mut env, "exe.add_startup_cache(codecs=['a b'])").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_external_resources() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::write(temp_dir.path().join("signing.key"), "07".repeat(32))?;
+        // Forward slashes avoid escaping Windows paths in Starlark strings.
+        let key_dir = temp_dir.path().display().to_string().replace('\\', "/");
+
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!("exe.sign_external_resources('{}/missing.key')", key_dir)
+        )
+        .is_err());
+        starlark_eval_in_env(
+            &mut env,
+            &format!("exe.sign_external_resources('{}/signing.key')", key_dir),
+        )
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_windows_manifest() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe.set_windows_manifest(dpi_awareness='per-monitor-v2', long_path_aware=True, \
+             uac_execution_level='requireAdministrator')",
+        )
+        .unwrap();
+
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.set_windows_manifest(dpi_awareness='high')")
+                .is_err()
+        );
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.set_windows_manifest(uac_execution_level='admin')"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_macos_signing() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe.set_macos_signing('-', entitlements=[\
+             'com.apple.security.cs.allow-unsigned-executable-memory', \
+             'com.apple.security.device.audio-input'])",
+        )
+        .unwrap();
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.set_macos_signing('-', entitlements=['com.apple.security.microphone'])"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_linux_service() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe.set_linux_service(network=True, capabilities=['CAP_NET_BIND_SERVICE'], \
+             writable_paths=['/var/lib/testapp'])",
+        )
+        .unwrap();
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.set_linux_service(capabilities=['CAP_EVERYTHING'])"
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.set_linux_service(writable_paths=['var/lib/testapp'])"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_filesystem_module_layout() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(&mut env, "exe.set_filesystem_module_layout('pycache')").unwrap();
+        starlark_eval_in_env(&mut env, "exe.set_filesystem_module_layout('indexed')").unwrap();
+
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.set_filesystem_module_layout('flat')").is_err()
+        );
+    }
+
+    #[test]
+    fn test_add_resource_transform() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let hook_path = temp_dir.path().join("hook.py");
+        std::fs::write(&hook_path, "def drop(name, data):\n    return None\n")?;
+
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "modules = [m for m in dist.source_modules() if m.name in ('json.decoder', 'json.encoder')]",
+        )
+        .unwrap();
+
+        assert!(starlark_eval_in_env(&mut env, "exe.add_resource_transform('*')").is_err());
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.add_resource_transform('*', 'brotli')").is_err()
+        );
+
+        starlark_eval_in_env(
+            &mut env,
+            "exe.add_resource_transform('json.decoder', 'minify')",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.add_resource_transform('json.encoder', python_hook={:?}, python_hook_function='drop')",
+                hook_path.display().to_string()
+            ),
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "summary = exe.add_python_resources(modules, add_bytecode_module=False)",
+        )
+        .unwrap();
+
+        // The summary reports the transform that dropped a module.
+        assert!(starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "summary.source_modules == 1 and 'json.encoder: source not added: dropped by resource transform drop() in {} on json.encoder' in summary.skipped",
+                hook_path.display()
+            )
+        )
+        .unwrap()
+        .to_bool());
+
+        let exe = env.get("exe").unwrap();
+        exe.downcast_apply(|exe: &PythonExecutable| -> Result<()> {
+            let sources = exe.exe.in_memory_module_sources();
+            assert!(!sources.contains_key("json.encoder"));
+
+            let source = sources.get("json.decoder").unwrap().source.resolve()?;
+            assert!(!String::from_utf8(source)?
+                .lines()
+                .any(|line| line.trim_start().starts_with('#')));
+
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_bytecode_module_resource_transform() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let hook_path = temp_dir.path().join("hook.py");
+        std::fs::write(&hook_path, "def drop(name, data):\n    return None\n")?;
+
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(&mut env, "exe.add_resource_transform('minified', 'minify')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.add_resource_transform('dropped', python_hook={:?}, python_hook_function='drop')",
+                hook_path.display().to_string()
+            ),
+        )
+        .unwrap();
+
+        for name in &["minified", "dropped", "kept"] {
+            starlark_eval_in_env(
+                &mut env,
+                &format!(
+                    "exe.add_python_resource(dist.compile_source('# comment\\nx = 1', '{}.py'))",
+                    name
+                ),
+            )
+            .unwrap();
+        }
+
+        let exe = env.get("exe").unwrap();
+        exe.downcast_apply(|exe: &PythonExecutable| -> Result<()> {
+            assert!(exe.exe.resource("dropped").is_none());
+
+            // Bytecode compiled before the transform is recompiled from the
+            // transformed source.
+            match &exe
+                .exe
+                .resource("minified")
+                .unwrap()
+                .in_memory_bytecode_opt1
+            {
+                Some(PythonModuleBytecodeProvider::FromSource(source)) => {
+                    assert!(!String::from_utf8(source.resolve()?)?.contains('#'));
+                }
+                bytecode => panic!("unexpected bytecode: {:?}", bytecode),
+            }
+
+            match &exe.exe.resource("kept").unwrap().in_memory_bytecode_opt1 {
+                Some(PythonModuleBytecodeProvider::Provided(_)) => {}
+                bytecode => panic!("unexpected bytecode: {:?}", bytecode),
+            }
+
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_python_resources_summary() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(&mut env, "modules = dist.source_modules()").unwrap();
+
+        starlark_eval_in_env(
+            &mut env,
+            "summary = exe.add_python_resources(modules, add_source_module=False)",
+        )
+        .unwrap();
+
+        let summary = starlark_eval_in_env(&mut env, "summary").unwrap();
+        assert_eq!(summary.get_type(), "PythonResourcesSummary");
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "summary.source_modules == 0 and summary.bytecode_modules == len(modules)"
+        )
+        .unwrap()
+        .to_bool());
+        assert!(
+            starlark_eval_in_env(&mut env, "len(summary.skipped) == len(modules)")
+                .unwrap()
+                .to_bool()
+        );
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "summary.in_memory_bytes > 0 and summary.filesystem_relative_bytes == 0"
+        )
+        .unwrap()
+        .to_bool());
+    }
+
+    #[test]
+    fn test_add_package_distribution_metadata() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+
+        let resource = |package: &str, name: &str| {
+            Value::new(PythonPackageDistributionResource {
+                resource: python_packaging::resource::PythonPackageDistributionResource {
+                    location: PythonPackageDistributionResourceFlavor::DistInfo,
+                    package: package.to_string(),
+                    version: "1.0".to_string(),
+                    name: name.to_string(),
+                    data: DataLocation::Memory(b"data".to_vec()),
+                },
+            })
+        };
+
+        env.set(
+            "resources",
+            Value::from(vec![
+                resource("foo_bar", "METADATA"),
+                resource("foo_bar", "entry_points.txt"),
+                resource("foo_bar", "INSTALLER"),
+                resource("baz", "METADATA"),
+            ]),
+        )
+        .unwrap();
+
+        starlark_eval_in_env(
+            &mut env,
+            "summary = exe.add_package_distribution_metadata(resources, packages=['Foo-Bar'])",
+        )
+        .unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "summary.package_distribution_resources == 2 and summary.in_memory_bytes == 8"
+        )
+        .unwrap()
+        .to_bool());
+
+        starlark_eval_in_env(
+            &mut env,
+            "summary = exe.add_package_distribution_metadata(resources)",
+        )
+        .unwrap();
+        assert!(
+            starlark_eval_in_env(&mut env, "summary.package_distribution_resources == 3")
+                .unwrap()
+                .to_bool()
+        );
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.add_package_distribution_metadata(resources, packages=[1])"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_add_python_resource_location() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "m = [m for m in dist.source_modules() if m.name == 'json'][0]",
+        )
+        .unwrap();
+
+        starlark_eval_in_env(
+            &mut env,
+            "summary = exe.add_python_resource(m, location='filesystem-relative:lib')",
+        )
+        .unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "summary.filesystem_relative_bytes > 0 and summary.in_memory_bytes == 0"
+        )
+        .unwrap()
+        .to_bool());
+
+        starlark_eval_in_env(
+            &mut env,
+            "summary = exe.add_python_resources([m], location='in-memory')",
+        )
+        .unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "summary.in_memory_bytes > 0 and summary.filesystem_relative_bytes == 0"
+        )
+        .unwrap()
+        .to_bool());
+
+        starlark_eval_in_env(&mut env, "exe.add_python_resource(m, location='default')").unwrap();
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.add_python_resource(m, location='memory')")
+                .is_err()
+        );
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.add_python_resources([m], location=True)").is_err()
+        );
+    }
+
+    #[test]
+    fn test_add_module_bytecode_optimize_levels() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "m = [m for m in dist.source_modules() if m.name == 'json'][0]",
+        )
+        .unwrap();
+
+        starlark_eval_in_env(&mut env, "exe.add_module_bytecode(m, optimize_level=2)").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe.add_in_memory_module_bytecode(m, optimize_level=[0, 1, 2])",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe.add_filesystem_relative_module_bytecode('lib', m, optimize_level=[1, 1])",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe.add_python_resources([m], add_source_module=False, optimize_level=[0, 2])",
+        )
+        .unwrap();
+
+        let err = starlark_eval_in_env(
+            &mut env,
+            "exe.add_module_bytecode(m, optimize_level=[0, 3])",
+        )
+        .unwrap_err();
+        assert_eq!(err.message, "optimize_level must be 0, 1, or 2: got 3");
+        let err = starlark_eval_in_env(&mut env, "exe.add_module_bytecode(m, optimize_level=[])")
+            .unwrap_err();
+        assert_eq!(err.message, "optimize_level must define at least one level");
+        let err = starlark_eval_in_env(&mut env, "exe.add_python_resource(m, optimize_level='1')")
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "function expects an int or list of int for optimize_level; got type string"
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_add_python_resource_strict() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "em = [m for m in dist.extension_modules() if m.name == '_ssl'][0]",
+        )
+        .unwrap();
+
+        // The static distribution can't load extension modules from files. So
+        // the requested placement is ignored unless strict.
+        starlark_eval_in_env(
+            &mut env,
+            "exe.add_filesystem_relative_python_resource('lib', em)",
+        )
+        .unwrap();
+
+        starlark_eval_in_env(&mut env, "set_strict(True)").unwrap();
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.add_filesystem_relative_python_resource('lib', em)"
+        )
+        .is_err());
+        starlark_eval_in_env(&mut env, "exe.add_in_memory_python_resource(em)").unwrap();
+    }
+
+    #[test]
+    fn test_analyze_resource_compatibility() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+
+        let issues = starlark_eval_in_env(
+            &mut env,
+            "exe.analyze_resource_compatibility(include_stdlib=True)",
+        )
+        .unwrap();
+        assert_eq!(issues.get_type(), "list");
+        assert!(issues.length().unwrap() > 0);
+        assert!(issues.to_str().contains("references __file__"));
+        assert!(issues.to_str().contains("lib2to3"));
+
+        // Standard library issues are filtered by default.
+        let issues =
+            starlark_eval_in_env(&mut env, "exe.analyze_resource_compatibility()").unwrap();
+        assert!(!issues.to_str().contains("lib2to3"));
+
+        starlark_eval_in_env(
+            &mut env,
+            "exe = dist.to_python_executable('testapp', resources_policy='filesystem-relative-only:lib')",
+        )
+        .unwrap();
+        let issues = starlark_eval_in_env(
+            &mut env,
+            "exe.analyze_resource_compatibility(include_stdlib=True)",
+        )
+        .unwrap();
+        assert_eq!(issues.length().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_to_build_summary() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+
+        let summary = starlark_eval_in_env(&mut env, "exe.to_build_summary()").unwrap();
+        assert_eq!(summary.get_type(), "BuildSummary");
+        summary.downcast_apply(|summary: &BuildSummary| {
+            assert_eq!(summary.format, SummaryFormat::Markdown);
+            assert!(!summary.include_record);
+        });
+
+        let summary =
+            starlark_eval_in_env(&mut env, "exe.to_build_summary(format='html')").unwrap();
+        summary.downcast_apply(|summary: &BuildSummary| {
+            assert_eq!(summary.format, SummaryFormat::Html);
+        });
+
+        let summary =
+            starlark_eval_in_env(&mut env, "exe.to_build_summary(include_record=True)").unwrap();
+        summary.downcast_apply(|summary: &BuildSummary| {
+            assert!(summary.include_record);
+        });
+
+        assert!(starlark_eval_in_env(&mut env, "exe.to_build_summary(format='pdf')").is_err());
     }
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_package_distribution_resource(env env, this, resource) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_package_distribution_resource(&env, &resource)
-        })
-    }
+    #[test]
+    fn test_to_embedded_resources() {
+        let mut env = starlark_env();
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_extension_module(env env, this, module) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_extension_module(&env, &module)
-        })
-    }
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_extension_module(env env, this, prefix, module) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_extension_module(&env, &prefix, &module)
-        })
-    }
+        let resources = starlark_eval_in_env(&mut env, "exe.to_embedded_resources()").unwrap();
+        assert_eq!(resources.get_type(), "PythonEmbeddedResources");
+        resources.downcast_apply(|resources: &PythonEmbeddedResources| {
+            assert!(!resources.data_only);
+        });
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.add_extension_module(env env, this, module) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_extension_module(&env, &module)
-        })
+        let resources =
+            starlark_eval_in_env(&mut env, "exe.to_embedded_resources(data_only=True)").unwrap();
+        resources.downcast_apply(|resources: &PythonEmbeddedResources| {
+            assert!(resources.data_only);
+        });
+
+        assert!(starlark_eval_in_env(&mut env, "exe.to_embedded_resources(data_only=1)").is_err());
     }
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_python_resource(
-        env env,
-        this,
-        resource,
-        add_source_module=true,
-        add_bytecode_module=true,
-        optimize_level=0
+    #[test]
+    fn test_to_standalone_folder() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+
+        let folder = starlark_eval_in_env(&mut env, "exe.to_standalone_folder()").unwrap();
+        assert_eq!(folder.get_type(), "StandaloneFolder");
+        folder.downcast_apply(|folder: &StandaloneFolder| {
+            assert_eq!(folder.lib_dir, "lib");
+            assert_eq!(folder.data_dir, "data");
+        });
+
+        let folder = starlark_eval_in_env(
+            &mut env,
+            "exe.to_standalone_folder(lib_dir='app/lib', data_dir='app/lib')",
         )
-    {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_python_resource(
-                &env,
-                &resource,
-                &add_source_module,
-                &add_bytecode_module,
-                &optimize_level,
-            )
-        })
+        .unwrap();
+        folder.downcast_apply(|folder: &StandaloneFolder| {
+            assert_eq!(folder.lib_dir, "app/lib");
+            assert_eq!(folder.data_dir, "app/lib");
+        });
+
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.to_standalone_folder(lib_dir='/lib')").is_err()
+        );
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.to_standalone_folder(data_dir='../data')").is_err()
+        );
     }
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_python_resource(
-        env env,
-        this,
-        prefix,
-        resource,
-        add_source_module=true,
-        add_bytecode_module=true,
-        optimize_level=0
+    #[test]
+    fn test_to_c_library() {
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('test-app')").unwrap();
+
+        let library = starlark_eval_in_env(&mut env, "exe.to_c_library()").unwrap();
+        assert_eq!(library.get_type(), "PythonCLibrary");
+        library.downcast_apply(|library: &PythonCLibrary| {
+            assert_eq!(library.name, "test_app");
+            assert_eq!(library.kind, CLibraryKind::Static);
+        });
+
+        let library = starlark_eval_in_env(
+            &mut env,
+            "exe.to_c_library(name='embedded', kind='dynamic')",
         )
-    {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_python_resource(
-                &env,
-                &prefix,
-                &resource,
-                &add_source_module,
-                &add_bytecode_module,
-                &optimize_level,
-            )
-        })
-    }
+        .unwrap();
+        library.downcast_apply(|library: &PythonCLibrary| {
+            assert_eq!(library.name, "embedded");
+            assert_eq!(library.kind, CLibraryKind::Dynamic);
+        });
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_python_resource(
-        env env,
-        this,
-        resource,
-        add_source_module=true,
-        add_bytecode_module=true,
-        optimize_level=0
-    ) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_python_resource(
-                &env,
-                &resource,
-                &add_source_module,
-                &add_bytecode_module,
-                &optimize_level
-            )
-        })
+        assert!(starlark_eval_in_env(&mut env, "exe.to_c_library(kind='shared')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "exe.to_c_library(name='my-lib')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "exe.to_c_library(name='1lib')").is_err());
     }
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.add_in_memory_python_resources(
-        env env,
-        this,
-        resources,
-        add_source_module=true,
-        add_bytecode_module=true,
-        optimize_level=0
-    ) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_in_memory_python_resources(
-                &env,
-                &resources,
-                &add_source_module,
-                &add_bytecode_module,
-                &optimize_level,
-            )
-        })
-    }
+    #[test]
+    fn test_to_extension_bundle() {
+        let mut env = starlark_env();
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.add_filesystem_relative_python_resources(
-        env env,
-        this,
-        prefix,
-        resources,
-        add_source_module=true,
-        add_bytecode_module=true,
-        optimize_level=0
-    ) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_filesystem_relative_python_resources(
-                &env,
-                &prefix,
-                &resources,
-                &add_source_module,
-                &add_bytecode_module,
-                &optimize_level,
-            )
-        })
-    }
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('my-app')").unwrap();
+
+        let bundle = starlark_eval_in_env(&mut env, "exe.to_extension_bundle()").unwrap();
+        assert_eq!(bundle.get_type(), "PythonExtensionBundle");
+        bundle.downcast_apply(|bundle: &PythonExtensionBundle| {
+            assert_eq!(bundle.name, "my_app_bundle");
+            assert_eq!(
+                bundle.filename("x86_64-unknown-linux-gnu"),
+                "my_app_bundle.so"
+            );
+            assert_eq!(
+                bundle.filename("x86_64-pc-windows-msvc"),
+                "my_app_bundle.pyd"
+            );
+        });
 
-    #[allow(non_snake_case, clippy::ptr_arg)]
-    PythonExecutable.add_python_resources(
-        env env,
-        this,
-        resources,
-        add_source_module=true,
-        add_bytecode_module=true,
-        optimize_level=0
-    ) {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_add_python_resources(
-                &env,
-                &resources,
-                &add_source_module,
-                &add_bytecode_module,
-                &optimize_level,
-            )
-        })
-    }
+        let bundle =
+            starlark_eval_in_env(&mut env, "exe.to_extension_bundle(name='myapp')").unwrap();
+        bundle.downcast_apply(|bundle: &PythonExtensionBundle| {
+            assert_eq!(bundle.name, "myapp");
+        });
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.filter_resources_from_files(
-        env env,
-        this,
-        files=None,
-        glob_files=None)
-    {
-        this.downcast_apply_mut(|exe: &mut PythonExecutable| {
-            exe.starlark_filter_resources_from_files(&env, &files, &glob_files)
-        })
+        assert!(starlark_eval_in_env(&mut env, "exe.to_extension_bundle(name='my.app')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "exe.to_extension_bundle(name='')").is_err());
     }
 
-    #[allow(clippy::ptr_arg)]
-    PythonExecutable.to_embedded_resources(this) {
-        this.downcast_apply(|exe: &PythonExecutable| {
-            exe.starlark_to_embedded_resources()
-        })
-    }
-}
+    #[test]
+    fn test_to_plugin_library() {
+        let mut env = starlark_env();
 
-#[cfg(test)]
-mod tests {
-    use super::super::testutil::*;
-    use super::*;
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('fx')").unwrap();
+
+        let library = starlark_eval_in_env(
+            &mut env,
+            "exe.to_plugin_library({'double process(double sample)': 'fx:process', 'void reset(void)': 'fx:reset'}, extension='.vst3')",
+        )
+        .unwrap();
+        assert_eq!(library.get_type(), "PythonPluginLibrary");
+        library.downcast_apply(|library: &PythonPluginLibrary| {
+            assert_eq!(library.name, "fx");
+            assert_eq!(
+                library
+                    .exports
+                    .iter()
+                    .map(|export| export.name.as_str())
+                    .collect::<Vec<_>>(),
+                vec!["process", "reset"]
+            );
+            assert_eq!(library.filename("x86_64-unknown-linux-gnu"), "fx.vst3");
+        });
+
+        let library = starlark_eval_in_env(
+            &mut env,
+            "exe.to_plugin_library({'int count(void)': 'fx:count'}, name='fx_plugin')",
+        )
+        .unwrap();
+        library.downcast_apply(|library: &PythonPluginLibrary| {
+            assert_eq!(
+                library.filename("x86_64-unknown-linux-gnu"),
+                "libfx_plugin.so"
+            );
+            assert_eq!(library.filename("x86_64-pc-windows-msvc"), "fx_plugin.dll");
+            assert_eq!(
+                library.bundle_paths("x86_64-unknown-linux-gnu").unwrap(),
+                (PathBuf::from("libfx_plugin.so"), PathBuf::new())
+            );
+        });
+
+        let library = starlark_eval_in_env(
+            &mut env,
+            "exe.to_plugin_library({'int count(void)': 'fx:count'}, extension='vst3', bundle=True)",
+        )
+        .unwrap();
+        library.downcast_apply(|library: &PythonPluginLibrary| {
+            assert!(library.bundle);
+            assert_eq!(
+                library.bundle_paths("x86_64-unknown-linux-gnu").unwrap(),
+                (
+                    PathBuf::from("fx.vst3/Contents/x86_64-linux/fx.so"),
+                    PathBuf::from("fx.vst3/Contents/x86_64-linux")
+                )
+            );
+            assert_eq!(
+                library.bundle_paths("i686-pc-windows-msvc").unwrap(),
+                (
+                    PathBuf::from("fx.vst3/Contents/x86-win/fx.vst3"),
+                    PathBuf::from("fx.vst3/Contents/x86-win")
+                )
+            );
+            assert_eq!(
+                library.bundle_paths("x86_64-apple-darwin").unwrap(),
+                (
+                    PathBuf::from("fx.vst3/Contents/MacOS/fx"),
+                    PathBuf::from("fx.vst3/Contents/Resources")
+                )
+            );
+        });
+
+        let library = starlark_eval_in_env(
+            &mut env,
+            "exe.to_plugin_library({'int count(void)': 'fx:count'}, bundle=True)",
+        )
+        .unwrap();
+        library.downcast_apply(|library: &PythonPluginLibrary| {
+            assert_eq!(
+                library.bundle_paths("x86_64-apple-darwin").unwrap().0,
+                PathBuf::from("fx.bundle/Contents/MacOS/fx")
+            );
+            assert!(library.bundle_paths("x86_64-unknown-linux-gnu").is_err());
+        });
+
+        for exports in &[
+            "{}",
+            "{'int count': 'fx:count'}",
+            "{'int count(void)': 'fx.count'}",
+            "{'int count(void)': 'fx:count', 'int  count()': 'fx:count2'}",
+        ] {
+            assert!(
+                starlark_eval_in_env(&mut env, &format!("exe.to_plugin_library({})", exports))
+                    .is_err()
+            );
+        }
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_plugin_library({'int count(void)': 'fx:count'}, extension='so/x')"
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_plugin_library({'int count(void)': 'fx:count'}, bundle=1)"
+        )
+        .is_err());
+
+        // Extension modules loaded from shared libraries can't resolve symbols
+        // of a libpython linked statically.
+        let exe = starlark_eval_in_env(&mut env, "exe").unwrap();
+        let links_shared_libpython =
+            exe.downcast_apply(|exe: &PythonExecutable| exe.exe.links_shared_libpython());
+        if !links_shared_libpython {
+            exe.downcast_apply_mut(|exe: &mut PythonExecutable| {
+                exe.exe
+                    .add_relative_path_dynamic_extension_module(
+                        "lib",
+                        &RawPythonExtensionModule {
+                            name: "fx_native".to_string(),
+                            init_fn: Some("PyInit_fx_native".to_string()),
+                            extension_file_suffix: ".so".to_string(),
+                            extension_data: Some(DataLocation::Memory(vec![42])),
+                            object_file_data: vec![],
+                            is_package: false,
+                            libraries: vec![],
+                            library_dirs: vec![],
+                            shared_libraries: BTreeMap::new(),
+                        },
+                    )
+                    .unwrap();
+            });
+
+            let err = starlark_eval_in_env(
+                &mut env,
+                "exe.to_plugin_library({'int count(void)': 'fx:count'})",
+            )
+            .unwrap_err();
+            assert!(err
+                .message
+                .contains("plugins can't load extension module fx_native from a shared library"));
+        }
+    }
 
     #[test]
-    fn test_default_values() {
+    fn test_to_zipapp() {
         let mut env = starlark_env();
 
         starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
 
-        let exe = starlark_eval_in_env(&mut env, "dist.to_python_executable('testapp')").unwrap();
-
-        assert_eq!(exe.get_type(), "PythonExecutable");
+        let app = starlark_eval_in_env(&mut env, "exe.to_zipapp('myapp')").unwrap();
+        assert_eq!(app.get_type(), "ZipApp");
+        app.downcast_apply(|app: &ZipApp| {
+            assert_eq!(app.file_name(), "myapp.pyz");
+            assert!(app.interpreter.is_none());
+        });
 
-        exe.downcast_apply(|exe: &PythonExecutable| {
-            assert!(!exe.exe.in_memory_module_sources().is_empty());
-            assert!(exe.exe.in_memory_package_resources().is_empty());
+        let app = starlark_eval_in_env(
+            &mut env,
+            "exe.to_zipapp('myapp.pyz', interpreter='/usr/bin/env python3')",
+        )
+        .unwrap();
+        app.downcast_apply(|app: &ZipApp| {
+            assert_eq!(app.file_name(), "myapp.pyz");
+            assert_eq!(app.interpreter, Some("/usr/bin/env python3".to_string()));
         });
     }
 
     #[test]
-    fn test_no_sources() {
+    fn test_type_stubs() {
         let mut env = starlark_env();
 
         starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('testapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "typed_exe = dist.to_python_executable('testapp', include_type_stubs=True)",
+        )
+        .unwrap();
 
-        let exe = starlark_eval_in_env(
+        for (name, relative_name) in &[
+            ("stub", "__init__.pyi"),
+            ("marker", "py.typed"),
+            ("data", "data.txt"),
+        ] {
+            let resource = Value::new(PythonPackageResource {
+                data: RawPythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: relative_name.to_string(),
+                    data: python_packaging::resource::DataLocation::Memory(vec![42]),
+                },
+            });
+            env.set(name, resource).unwrap();
+        }
+
+        let summary =
+            starlark_eval_in_env(&mut env, "exe.add_python_resources([stub, marker, data])")
+                .unwrap();
+        summary.downcast_apply(|s: &PythonResourcesSummary| {
+            assert_eq!(s.skipped.len(), 2);
+        });
+        starlark_eval_in_env(
             &mut env,
-            "dist.to_python_executable('testapp', include_sources=False)",
+            "typed_exe.add_python_resources([stub, marker, data])",
         )
         .unwrap();
 
-        assert_eq!(exe.get_type(), "PythonExecutable");
+        let exe = env.get("exe").unwrap();
+        exe.downcast_apply(|exe: &PythonExecutable| {
+            let resources = exe.exe.in_memory_package_resources();
+            let foo = resources.get("foo").unwrap();
+            assert_eq!(foo.keys().collect::<Vec<_>>(), vec!["data.txt"]);
+            assert_eq!(exe.type_stubs.len(), 2);
+        });
 
+        let exe = env.get("typed_exe").unwrap();
         exe.downcast_apply(|exe: &PythonExecutable| {
-            assert!(exe.exe.in_memory_module_sources().is_empty());
+            let resources = exe.exe.in_memory_package_resources();
+            assert_eq!(resources.get("foo").unwrap().len(), 3);
+            assert_eq!(exe.type_stubs.len(), 2);
+        });
+
+        let stubs = starlark_eval_in_env(&mut env, "exe.to_type_stubs(prefix='types')").unwrap();
+        stubs.downcast_apply(|m: &FileManifest| {
+            assert_eq!(
+                m.manifest
+                    .entries()
+                    .map(|(p, _)| p.clone())
+                    .collect::<Vec<_>>(),
+                vec![
+                    PathBuf::from("types/foo/__init__.pyi"),
+                    PathBuf::from("types/foo/py.typed"),
+                ]
+            );
         });
     }
 }
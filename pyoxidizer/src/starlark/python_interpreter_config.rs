@@ -4,7 +4,10 @@
 
 use crate::py_packaging::config::RunMode;
 use {
-    super::util::{optional_list_arg, optional_str_arg, required_bool_arg, required_type_arg},
+    super::util::{
+        optional_dict_arg, optional_list_arg, optional_str_arg, required_bool_arg,
+        required_type_arg,
+    },
     crate::py_packaging::config::{
         default_raw_allocator, EmbeddedPythonConfig, RawAllocator, TerminfoResolution,
     },
@@ -19,7 +22,7 @@ use {
     },
     std::any::Any,
     std::cmp::Ordering,
-    std::collections::HashMap,
+    std::collections::{BTreeMap, HashMap},
 };
 
 impl TypedValue for EmbeddedPythonConfig {
@@ -52,6 +55,15 @@ impl TypedValue for EmbeddedPythonConfig {
     }
 }
 
+/// Whether a string is a Rust path, such as `crate::module::function`.
+fn is_rust_path(path: &str) -> bool {
+    path.split("::").all(|s| {
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
 // Starlark functions.
 impl EmbeddedPythonConfig {
     /// PythonInterpreterConfig(...)
@@ -59,6 +71,7 @@ impl EmbeddedPythonConfig {
     pub fn starlark_new(
         env: &Environment,
         bytes_warning: &Value,
+        ctypes_find_library_redirect: &Value,
         ignore_environment: &Value,
         inspect: &Value,
         interactive: &Value,
@@ -67,29 +80,42 @@ impl EmbeddedPythonConfig {
         legacy_windows_stdio: &Value,
         optimize_level: &Value,
         parser_debug: &Value,
+        python_executable_env: &Value,
         stdio_encoding: &Value,
         unbuffered_stdio: &Value,
         filesystem_importer: &Value,
         quiet: &Value,
+        run_entry_point: &Value,
         run_eval: &Value,
         run_file: &Value,
         run_module: &Value,
         run_noop: &Value,
         run_repl: &Value,
         site_import: &Value,
+        startup_error_catalog: &Value,
+        startup_error_message: &Value,
+        startup_progress_callback: &Value,
         sys_frozen: &Value,
         sys_meipass: &Value,
         sys_paths: &Value,
         raw_allocator: &Value,
+        raw_allocator_stats: &Value,
+        remote_chunk_fetcher: &Value,
+        remote_resources_url: &Value,
         terminfo_resolution: &Value,
         terminfo_dirs: &Value,
         use_hash_seed: &Value,
         user_site_directory: &Value,
         verbose: &Value,
         write_bytecode: &Value,
+        write_memory_stats_env: &Value,
         write_modules_directory_env: &Value,
     ) -> ValueResult {
         required_type_arg("bytes_warning", "int", &bytes_warning)?;
+        let ctypes_find_library_redirect = required_bool_arg(
+            "ctypes_find_library_redirect",
+            &ctypes_find_library_redirect,
+        )?;
         let ignore_environment = required_bool_arg("ignore_environment", &ignore_environment)?;
         let inspect = required_bool_arg("inspect", &inspect)?;
         let interactive = required_bool_arg("interactive", &interactive)?;
@@ -100,10 +126,13 @@ impl EmbeddedPythonConfig {
             required_bool_arg("legacy_windows_stdio", &legacy_windows_stdio)?;
         required_type_arg("optimize_level", "int", &optimize_level)?;
         let parser_debug = required_bool_arg("parser_debug", &parser_debug)?;
+        let python_executable_env =
+            optional_str_arg("python_executable_env", &python_executable_env)?;
         let stdio_encoding = optional_str_arg("stdio_encoding", &stdio_encoding)?;
         let unbuffered_stdio = required_bool_arg("unbuffered_stdio", &unbuffered_stdio)?;
         let filesystem_importer = required_bool_arg("filesystem_importer", &filesystem_importer)?;
         let quiet = required_bool_arg("quiet", &quiet)?;
+        let run_entry_point = optional_str_arg("run_entry_point", &run_entry_point)?;
         let run_eval = optional_str_arg("run_eval", &run_eval)?;
         let run_file = optional_str_arg("run_file", &run_file)?;
         let run_module = optional_str_arg("run_module", &run_module)?;
@@ -113,19 +142,37 @@ impl EmbeddedPythonConfig {
         let sys_meipass = required_bool_arg("sys_meipass", &sys_meipass)?;
         optional_list_arg("sys_paths", "string", &sys_paths)?;
         let raw_allocator = optional_str_arg("raw_allocator", &raw_allocator)?;
+        let raw_allocator_stats = required_bool_arg("raw_allocator_stats", &raw_allocator_stats)?;
+        let remote_chunk_fetcher = optional_str_arg("remote_chunk_fetcher", &remote_chunk_fetcher)?;
+        let remote_resources_url = optional_str_arg("remote_resources_url", &remote_resources_url)?;
         let site_import = required_bool_arg("site_importer", &site_import)?;
+        optional_dict_arg(
+            "startup_error_catalog",
+            "string",
+            "string",
+            &startup_error_catalog,
+        )?;
+        let startup_error_message =
+            optional_str_arg("startup_error_message", &startup_error_message)?;
+        let startup_progress_callback =
+            optional_str_arg("startup_progress_callback", &startup_progress_callback)?;
         let terminfo_resolution = optional_str_arg("terminfo_resolution", &terminfo_resolution)?;
         let terminfo_dirs = optional_str_arg("terminfo_dirs", &terminfo_dirs)?;
         let use_hash_seed = required_bool_arg("use_hash_seed", &use_hash_seed)?;
         let user_site_directory = required_bool_arg("user_site_directory", &user_site_directory)?;
         required_type_arg("verbose", "int", &verbose)?;
         let write_bytecode = required_bool_arg("write_bytecode", &write_bytecode)?;
+        let write_memory_stats_env =
+            optional_str_arg("write_memory_stats_env", &write_memory_stats_env)?;
         let write_modules_directory_env =
             optional_str_arg("write_modules_directory_env", &write_modules_directory_env)?;
 
         let build_target = env.get("BUILD_TARGET_TRIPLE").unwrap().to_str();
 
         let mut run_count = 0;
+        if run_entry_point.is_some() {
+            run_count += 1;
+        }
         if run_eval.is_some() {
             run_count += 1;
         }
@@ -151,7 +198,28 @@ impl EmbeddedPythonConfig {
             .into());
         }
 
-        let run_mode = if let Some(code) = run_eval {
+        let run_mode = if let Some(entry_point) = run_entry_point {
+            let mut parts = entry_point.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(group), Some(name)) if !group.is_empty() && !name.is_empty() => {
+                    RunMode::EntryPoint {
+                        group: group.to_string(),
+                        name: name.to_string(),
+                    }
+                }
+                _ => {
+                    return Err(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!(
+                            "run_entry_point must be of the form <group>:<name>; got {}",
+                            entry_point
+                        ),
+                        label: "PythonInterpreterConfig()".to_string(),
+                    }
+                    .into())
+                }
+            }
+        } else if let Some(code) = run_eval {
             RunMode::Eval { code }
         } else if let Some(path) = run_file {
             RunMode::File { path }
@@ -163,6 +231,31 @@ impl EmbeddedPythonConfig {
             RunMode::Repl
         };
 
+        for (name, value) in &[
+            ("remote_chunk_fetcher", &remote_chunk_fetcher),
+            ("startup_progress_callback", &startup_progress_callback),
+        ] {
+            if let Some(path) = value {
+                if !is_rust_path(path) {
+                    return Err(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!("{} must be a Rust path to a function", name),
+                        label: "PythonInterpreterConfig()".to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        if remote_chunk_fetcher.is_some() && remote_resources_url.is_none() {
+            return Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: "remote_chunk_fetcher requires remote_resources_url".to_string(),
+                label: "PythonInterpreterConfig()".to_string(),
+            }
+            .into());
+        }
+
         let (stdio_encoding_name, stdio_encoding_errors) = if let Some(ref v) = stdio_encoding {
             let values: Vec<&str> = v.split(':').collect();
             (Some(values[0].to_string()), Some(values[1].to_string()))
@@ -224,8 +317,24 @@ impl EmbeddedPythonConfig {
 
         let filesystem_importer = filesystem_importer || !sys_paths.is_empty();
 
+        let startup_error_catalog = match startup_error_catalog.get_type() {
+            "dict" => startup_error_catalog
+                .into_iter()?
+                .map(|key| {
+                    let k = key.to_string();
+                    let v = startup_error_catalog.at(key).unwrap().to_string();
+                    (k, v)
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+
         Ok(Value::new(EmbeddedPythonConfig {
             bytes_warning: bytes_warning.to_int().unwrap() as i32,
+            ctypes_find_library_redirect,
+            debugpy_allow_remote_hosts: false,
+            debugpy_listen_env: None,
+            extract_shared_libraries_to_cache: false,
             ignore_environment,
             inspect,
             interactive,
@@ -233,24 +342,36 @@ impl EmbeddedPythonConfig {
             legacy_windows_fs_encoding,
             legacy_windows_stdio,
             optimize_level: optimize_level.to_int().unwrap(),
+            origin: None,
             parser_debug,
+            python_executable_env,
             quiet,
             stdio_encoding_name,
             stdio_encoding_errors,
             unbuffered_stdio,
             filesystem_importer,
             site_import,
+            startup_error_catalog,
+            startup_cache_module: None,
+            startup_error_message,
+            startup_progress_callback,
             sys_frozen,
             sys_meipass,
             sys_paths,
             raw_allocator,
+            raw_allocator_stats,
+            remote_chunk_fetcher,
+            remote_resources_url,
+            resources_verification_key: None,
             run_mode,
             terminfo_resolution,
             use_hash_seed,
             user_site_directory,
             verbose: verbose.to_int().unwrap() as i32,
             write_bytecode,
+            write_memory_stats_env,
             write_modules_directory_env,
+            zipimport_archive: None,
         }))
     }
 }
@@ -260,6 +381,7 @@ starlark_module! { embedded_python_config_module =>
     PythonInterpreterConfig(
         env env,
         bytes_warning=0,
+        ctypes_find_library_redirect=false,
         ignore_environment=true,
         inspect=false,
         interactive=false,
@@ -268,31 +390,41 @@ starlark_module! { embedded_python_config_module =>
         legacy_windows_stdio=false,
         optimize_level=0,
         parser_debug=false,
+        python_executable_env=None,
         stdio_encoding=None,
         unbuffered_stdio=false,
         filesystem_importer=false,
         quiet=false,
+        run_entry_point=None,
         run_eval=None,
         run_file=None,
         run_module=None,
         run_noop=false,
         run_repl=false,
         site_import=false,
+        startup_error_catalog=None,
+        startup_error_message=None,
+        startup_progress_callback=None,
         sys_frozen=false,
         sys_meipass=false,
         sys_paths=None,
         raw_allocator=None,
+        raw_allocator_stats=false,
+        remote_chunk_fetcher=None,
+        remote_resources_url=None,
         terminfo_resolution="dynamic",
         terminfo_dirs=None,
         use_hash_seed=false,
         user_site_directory=false,
         verbose=0,
         write_bytecode=false,
+        write_memory_stats_env=None,
         write_modules_directory_env=None
     ) {
         EmbeddedPythonConfig::starlark_new(
             &env,
             &bytes_warning,
+            &ctypes_find_library_redirect,
             &ignore_environment,
             &inspect,
             &interactive,
@@ -301,26 +433,35 @@ starlark_module! { embedded_python_config_module =>
             &legacy_windows_stdio,
             &optimize_level,
             &parser_debug,
+            &python_executable_env,
             &stdio_encoding,
             &unbuffered_stdio,
             &filesystem_importer,
             &quiet,
+            &run_entry_point,
             &run_eval,
             &run_file,
             &run_module,
             &run_noop,
             &run_repl,
             &site_import,
+            &startup_error_catalog,
+            &startup_error_message,
+            &startup_progress_callback,
             &sys_frozen,
             &sys_meipass,
             &sys_paths,
             &raw_allocator,
+            &raw_allocator_stats,
+            &remote_chunk_fetcher,
+            &remote_resources_url,
             &terminfo_resolution,
             &terminfo_dirs,
             &use_hash_seed,
             &user_site_directory,
             &verbose,
             &write_bytecode,
+            &write_memory_stats_env,
             &write_modules_directory_env
         )
     }
@@ -337,6 +478,10 @@ mod tests {
 
         let wanted = crate::py_packaging::config::EmbeddedPythonConfig {
             bytes_warning: 0,
+            ctypes_find_library_redirect: false,
+            debugpy_allow_remote_hosts: false,
+            debugpy_listen_env: None,
+            extract_shared_libraries_to_cache: false,
             ignore_environment: true,
             inspect: false,
             interactive: false,
@@ -344,7 +489,9 @@ mod tests {
             legacy_windows_fs_encoding: false,
             legacy_windows_stdio: false,
             optimize_level: 0,
+            origin: None,
             parser_debug: false,
+            python_executable_env: None,
             quiet: false,
             use_hash_seed: false,
             verbose: 0,
@@ -353,15 +500,25 @@ mod tests {
             unbuffered_stdio: false,
             filesystem_importer: false,
             site_import: false,
+            startup_error_catalog: BTreeMap::new(),
+            startup_cache_module: None,
+            startup_error_message: None,
+            startup_progress_callback: None,
             sys_frozen: false,
             sys_meipass: false,
             sys_paths: Vec::new(),
             raw_allocator: default_raw_allocator(crate::project_building::HOST),
+            raw_allocator_stats: false,
+            remote_chunk_fetcher: None,
+            remote_resources_url: None,
+            resources_verification_key: None,
             run_mode: RunMode::Repl,
             terminfo_resolution: TerminfoResolution::Dynamic,
             user_site_directory: false,
             write_bytecode: false,
+            write_memory_stats_env: None,
             write_modules_directory_env: None,
+            zipimport_archive: None,
         };
 
         c.downcast_apply(|x: &EmbeddedPythonConfig| assert_eq!(x, &wanted));
@@ -373,6 +530,12 @@ mod tests {
         c.downcast_apply(|x: &EmbeddedPythonConfig| assert_eq!(x.bytes_warning, 2));
     }
 
+    #[test]
+    fn test_ctypes_find_library_redirect() {
+        let c = starlark_ok("PythonInterpreterConfig(ctypes_find_library_redirect=True)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| assert!(x.ctypes_find_library_redirect));
+    }
+
     #[test]
     fn test_optimize_level() {
         let c = starlark_ok("PythonInterpreterConfig(optimize_level=1)");
@@ -398,6 +561,72 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_python_executable_env() {
+        let c = starlark_ok("PythonInterpreterConfig(python_executable_env='MYAPP_PYTHON')");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.python_executable_env, Some("MYAPP_PYTHON".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_startup_error_messages() {
+        let c = starlark_ok(
+            "PythonInterpreterConfig(startup_error_message='myapp failed: {error}', \
+             startup_error_catalog={'de': 'myapp fehlgeschlagen: {error}'})",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(
+                x.startup_error_message,
+                Some("myapp failed: {error}".to_string())
+            );
+            assert_eq!(
+                x.startup_error_catalog.get("de"),
+                Some(&"myapp fehlgeschlagen: {error}".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn test_startup_progress_callback() {
+        let c = starlark_ok(
+            "PythonInterpreterConfig(startup_progress_callback='crate::splash::on_progress')",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(
+                x.startup_progress_callback,
+                Some("crate::splash::on_progress".to_string())
+            );
+        });
+
+        starlark_nok("PythonInterpreterConfig(startup_progress_callback='on_progress()')");
+        starlark_nok("PythonInterpreterConfig(startup_progress_callback='crate::')");
+    }
+
+    #[test]
+    fn test_remote_resources() {
+        let c = starlark_ok(
+            "PythonInterpreterConfig(remote_resources_url='http://example.com/app.resources', \
+             remote_chunk_fetcher='crate::fetch::fetch_range')",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(
+                x.remote_resources_url,
+                Some("http://example.com/app.resources".to_string())
+            );
+            assert_eq!(
+                x.remote_chunk_fetcher,
+                Some("crate::fetch::fetch_range".to_string())
+            );
+        });
+
+        starlark_nok("PythonInterpreterConfig(remote_chunk_fetcher='crate::fetch::fetch_range')");
+        starlark_nok(
+            "PythonInterpreterConfig(remote_resources_url='http://example.com/app.resources', \
+             remote_chunk_fetcher='fetch range')",
+        );
+    }
+
     #[test]
     fn test_raw_allocator() {
         let c = starlark_ok("PythonInterpreterConfig(raw_allocator='system')");
@@ -414,6 +643,37 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_raw_allocator_stats() {
+        let c = starlark_ok(
+            "PythonInterpreterConfig(raw_allocator_stats=True, write_memory_stats_env='MEMSTATS')",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert!(x.raw_allocator_stats);
+            assert_eq!(x.write_memory_stats_env, Some("MEMSTATS".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_run_entry_point() {
+        let c = starlark_ok("PythonInterpreterConfig(run_entry_point='console_scripts:mycli')");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(
+                x.run_mode,
+                RunMode::EntryPoint {
+                    group: "console_scripts".to_string(),
+                    name: "mycli".to_string(),
+                }
+            );
+        });
+
+        starlark_nok("PythonInterpreterConfig(run_entry_point='mycli')");
+        starlark_nok("PythonInterpreterConfig(run_entry_point='console_scripts:')");
+        starlark_nok(
+            "PythonInterpreterConfig(run_entry_point='console_scripts:mycli', run_module='mycli')",
+        );
+    }
+
     #[test]
     fn test_run_eval() {
         let c = starlark_ok("PythonInterpreterConfig(run_eval='1')");
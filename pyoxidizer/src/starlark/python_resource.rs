@@ -82,6 +82,10 @@ impl TypedValue for PythonSourceModule {
 #[derive(Debug, Clone)]
 pub struct PythonBytecodeModule {
     pub module: PythonModuleBytecodeFromSource,
+    /// Bytecode already compiled from the module source.
+    ///
+    /// If not defined, bytecode is compiled when the binary is built.
+    pub bytecode: Option<Vec<u8>>,
 }
 
 impl TypedValue for PythonBytecodeModule {
@@ -355,9 +359,10 @@ pub fn python_resource_to_value(resource: &PythonResource) -> Value {
     match resource {
         PythonResource::ModuleSource(sm) => Value::new(PythonSourceModule { module: sm.clone() }),
 
-        PythonResource::ModuleBytecodeRequest(m) => {
-            Value::new(PythonBytecodeModule { module: m.clone() })
-        }
+        PythonResource::ModuleBytecodeRequest(m) => Value::new(PythonBytecodeModule {
+            module: m.clone(),
+            bytecode: None,
+        }),
 
         PythonResource::ModuleBytecode { .. } => {
             panic!("not yet implemented");
@@ -0,0 +1,457 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    anyhow::Result,
+    python_packaging::resource::{BytecodeOptimizationLevel, DataLocation},
+    python_packaging::resource_collection::{PrePackagedResource, PythonModuleBytecodeProvider},
+    starlark::values::{default_compare, TypedValue, Value, ValueError, ValueResult},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+};
+
+/// Size of the header of bytecode files.
+const BYTECODE_HEADER_SIZE: i64 = 16;
+
+/// Where a resource is loaded from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResourcePlacement {
+    InMemory,
+    FilesystemRelative,
+}
+
+/// Obtain the size of data without reading it.
+fn data_size(data: &DataLocation) -> Result<i64> {
+    Ok(match data {
+        DataLocation::Path(p) => std::fs::metadata(p)?.len() as i64,
+        DataLocation::Memory(data) => data.len() as i64,
+    })
+}
+
+/// Summarizes resources added to a `PythonExecutable`.
+///
+/// Summaries are derived from the executable's resources collection, so
+/// they reflect what the builder accepted rather than what was requested.
+/// Resources are recorded at the first of the given placements holding them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PythonResourcesSummary {
+    pub source_modules: i64,
+    pub bytecode_modules: i64,
+    pub package_resources: i64,
+    pub package_distribution_resources: i64,
+    pub extension_modules: i64,
+
+    /// Bytes of data loaded from memory.
+    pub in_memory_bytes: i64,
+
+    /// Bytes of data loaded from the filesystem relative to the binary.
+    pub filesystem_relative_bytes: i64,
+
+    /// Names of resources that weren't added and the reason why.
+    pub skipped: Vec<(String, String)>,
+}
+
+impl PythonResourcesSummary {
+    fn add_size(&mut self, placement: ResourcePlacement, size: i64) {
+        match placement {
+            ResourcePlacement::InMemory => self.in_memory_bytes += size,
+            ResourcePlacement::FilesystemRelative => self.filesystem_relative_bytes += size,
+        }
+    }
+
+    /// Record the data at the first of `placements` holding any.
+    ///
+    /// Returns whether there is any.
+    fn add_locations(
+        &mut self,
+        placements: &[ResourcePlacement],
+        in_memory: Option<&DataLocation>,
+        relative_path: Option<&DataLocation>,
+    ) -> Result<bool> {
+        for placement in placements {
+            let data = match placement {
+                ResourcePlacement::InMemory => in_memory,
+                ResourcePlacement::FilesystemRelative => relative_path,
+            };
+
+            if let Some(data) = data {
+                self.add_size(*placement, data_size(data)?);
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Record the source of a module in a resources collection entry.
+    ///
+    /// Returns whether the entry holds source.
+    pub fn add_module_source(
+        &mut self,
+        placements: &[ResourcePlacement],
+        entry: &PrePackagedResource,
+    ) -> Result<bool> {
+        let added = self.add_locations(
+            placements,
+            entry.in_memory_source.as_ref(),
+            entry
+                .relative_path_module_source
+                .as_ref()
+                .map(|(_, data)| data),
+        )?;
+        if added {
+            self.source_modules += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Record the bytecode of a module in a resources collection entry.
+    ///
+    /// Only bytecode of the given optimization levels is recorded. Bytecode
+    /// compiled from source is only compiled when building, so the size of
+    /// its source is recorded. Returns whether the entry holds bytecode of
+    /// any of the levels.
+    pub fn add_module_bytecode(
+        &mut self,
+        placements: &[ResourcePlacement],
+        entry: &PrePackagedResource,
+        levels: &[BytecodeOptimizationLevel],
+    ) -> Result<bool> {
+        let mut found = false;
+
+        for level in levels {
+            let (in_memory, relative_path) = match level {
+                BytecodeOptimizationLevel::Zero => {
+                    (&entry.in_memory_bytecode, &entry.relative_path_bytecode)
+                }
+                BytecodeOptimizationLevel::One => (
+                    &entry.in_memory_bytecode_opt1,
+                    &entry.relative_path_bytecode_opt1,
+                ),
+                BytecodeOptimizationLevel::Two => (
+                    &entry.in_memory_bytecode_opt2,
+                    &entry.relative_path_bytecode_opt2,
+                ),
+            };
+
+            for placement in placements {
+                let provider = match placement {
+                    ResourcePlacement::InMemory => in_memory.as_ref(),
+                    ResourcePlacement::FilesystemRelative => {
+                        relative_path.as_ref().map(|(_, _, provider)| provider)
+                    }
+                };
+
+                match provider {
+                    Some(PythonModuleBytecodeProvider::Provided(data)) => {
+                        self.add_size(*placement, data_size(data)?);
+                        if *placement == ResourcePlacement::FilesystemRelative {
+                            self.add_size(*placement, BYTECODE_HEADER_SIZE);
+                        }
+                    }
+                    Some(PythonModuleBytecodeProvider::FromSource(source)) => {
+                        self.add_size(*placement, data_size(source)?);
+                    }
+                    None => continue,
+                }
+
+                found = true;
+                break;
+            }
+        }
+
+        if found {
+            self.bytecode_modules += 1;
+        }
+
+        Ok(found)
+    }
+
+    /// Record a package resource in a resources collection entry.
+    ///
+    /// Returns whether the entry holds the resource.
+    pub fn add_package_resource(
+        &mut self,
+        placements: &[ResourcePlacement],
+        entry: &PrePackagedResource,
+        relative_name: &str,
+    ) -> Result<bool> {
+        let added = self.add_locations(
+            placements,
+            entry
+                .in_memory_resources
+                .as_ref()
+                .and_then(|resources| resources.get(relative_name)),
+            entry
+                .relative_path_package_resources
+                .as_ref()
+                .and_then(|resources| resources.get(relative_name))
+                .map(|(_, _, data)| data),
+        )?;
+        if added {
+            self.package_resources += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Record a package distribution resource in a resources collection entry.
+    ///
+    /// Returns whether the entry holds the resource.
+    pub fn add_package_distribution_resource(
+        &mut self,
+        placements: &[ResourcePlacement],
+        entry: &PrePackagedResource,
+        name: &str,
+    ) -> Result<bool> {
+        let added = self.add_locations(
+            placements,
+            entry
+                .in_memory_distribution_resources
+                .as_ref()
+                .and_then(|resources| resources.get(name)),
+            entry
+                .relative_path_distribution_resources
+                .as_ref()
+                .and_then(|resources| resources.get(name))
+                .map(|(_, _, data)| data),
+        )?;
+        if added {
+            self.package_distribution_resources += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Record an extension module loaded from a shared library.
+    ///
+    /// Returns whether the entry holds a shared library.
+    pub fn add_extension_module(
+        &mut self,
+        placements: &[ResourcePlacement],
+        entry: &PrePackagedResource,
+    ) -> Result<bool> {
+        let added = self.add_locations(
+            placements,
+            entry.in_memory_extension_module_shared_library.as_ref(),
+            entry
+                .relative_path_extension_module_shared_library
+                .as_ref()
+                .map(|(_, _, data)| data),
+        )?;
+        if added {
+            self.extension_modules += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Record the addition of an extension module linked into the binary.
+    ///
+    /// Its size isn't known until the binary is linked, so it isn't recorded.
+    pub fn add_builtin_extension_module(&mut self) {
+        self.extension_modules += 1;
+    }
+
+    /// Record a resource that wasn't added.
+    pub fn add_skipped(&mut self, name: &str, reason: &str) {
+        self.skipped.push((name.to_string(), reason.to_string()));
+    }
+
+    /// Merge another summary into this one.
+    pub fn merge(&mut self, other: &PythonResourcesSummary) {
+        self.source_modules += other.source_modules;
+        self.bytecode_modules += other.bytecode_modules;
+        self.package_resources += other.package_resources;
+        self.package_distribution_resources += other.package_distribution_resources;
+        self.extension_modules += other.extension_modules;
+        self.in_memory_bytes += other.in_memory_bytes;
+        self.filesystem_relative_bytes += other.filesystem_relative_bytes;
+        self.skipped.extend(other.skipped.iter().cloned());
+    }
+}
+
+impl TypedValue for PythonResourcesSummary {
+    immutable!();
+    any!();
+    not_supported!(
+        binop, dir_attr, function, get_hash, indexable, iterable, sequence, set_attr, to_int
+    );
+
+    fn to_str(&self) -> String {
+        format!(
+            "PythonResourcesSummary<source_modules={}, bytecode_modules={}, package_resources={}, package_distribution_resources={}, extension_modules={}, in_memory_bytes={}, filesystem_relative_bytes={}, skipped={}>",
+            self.source_modules,
+            self.bytecode_modules,
+            self.package_resources,
+            self.package_distribution_resources,
+            self.extension_modules,
+            self.in_memory_bytes,
+            self.filesystem_relative_bytes,
+            self.skipped.len()
+        )
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "PythonResourcesSummary"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "source_modules" => Value::new(self.source_modules),
+            "bytecode_modules" => Value::new(self.bytecode_modules),
+            "package_resources" => Value::new(self.package_resources),
+            "package_distribution_resources" => Value::new(self.package_distribution_resources),
+            "extension_modules" => Value::new(self.extension_modules),
+            "in_memory_bytes" => Value::new(self.in_memory_bytes),
+            "filesystem_relative_bytes" => Value::new(self.filesystem_relative_bytes),
+            "skipped" => Value::from(
+                self.skipped
+                    .iter()
+                    .map(|(name, reason)| Value::new(format!("{}: {}", name, reason)))
+                    .collect::<Vec<Value>>(),
+            ),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "PythonResourcesSummary".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "source_modules" => true,
+            "bytecode_modules" => true,
+            "package_resources" => true,
+            "package_distribution_resources" => true,
+            "extension_modules" => true,
+            "in_memory_bytes" => true,
+            "filesystem_relative_bytes" => true,
+            "skipped" => true,
+            _ => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, python_packed_resources::data::ResourceFlavor, std::collections::BTreeMap};
+
+    fn module(name: &str) -> PrePackagedResource {
+        PrePackagedResource {
+            flavor: ResourceFlavor::Module,
+            name: name.to_string(),
+            ..PrePackagedResource::default()
+        }
+    }
+
+    const PREFER_IN_MEMORY: &[ResourcePlacement] = &[
+        ResourcePlacement::InMemory,
+        ResourcePlacement::FilesystemRelative,
+    ];
+
+    #[test]
+    fn test_add_resources() -> Result<()> {
+        let mut summary = PythonResourcesSummary::default();
+
+        let mut foo = module("foo");
+        foo.in_memory_source = Some(DataLocation::Memory(b"foo".to_vec()));
+        let mut resources = BTreeMap::new();
+        resources.insert(
+            "data.txt".to_string(),
+            (
+                "lib".to_string(),
+                "lib/foo/data.txt".into(),
+                DataLocation::Memory(b"hello".to_vec()),
+            ),
+        );
+        foo.relative_path_package_resources = Some(resources);
+
+        assert!(summary.add_module_source(PREFER_IN_MEMORY, &foo)?);
+        assert!(summary.add_package_resource(PREFER_IN_MEMORY, &foo, "data.txt")?);
+
+        // Data the entry doesn't hold isn't recorded.
+        assert!(!summary.add_module_source(&[ResourcePlacement::FilesystemRelative], &foo)?);
+        assert!(!summary.add_module_bytecode(
+            PREFER_IN_MEMORY,
+            &foo,
+            &[BytecodeOptimizationLevel::Zero]
+        )?);
+        assert!(!summary.add_package_resource(PREFER_IN_MEMORY, &foo, "missing.txt")?);
+        assert!(!summary.add_package_distribution_resource(PREFER_IN_MEMORY, &foo, "METADATA")?);
+        assert!(!summary.add_extension_module(PREFER_IN_MEMORY, &foo)?);
+        summary.add_skipped("bar", "add_source_module is False");
+
+        let mut other = PythonResourcesSummary::default();
+        other.add_builtin_extension_module();
+        summary.merge(&other);
+
+        assert_eq!(summary.source_modules, 1);
+        assert_eq!(summary.bytecode_modules, 0);
+        assert_eq!(summary.package_resources, 1);
+        assert_eq!(summary.extension_modules, 1);
+        assert_eq!(summary.in_memory_bytes, 3);
+        assert_eq!(summary.filesystem_relative_bytes, 5);
+        assert_eq!(
+            summary.skipped,
+            vec![("bar".to_string(), "add_source_module is False".to_string())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytecode_size() -> Result<()> {
+        let source = b"def foo():\n    return 42\n".to_vec();
+
+        let mut foo = module("foo");
+        foo.in_memory_bytecode = Some(PythonModuleBytecodeProvider::FromSource(
+            DataLocation::Memory(source.clone()),
+        ));
+        foo.relative_path_bytecode = Some((
+            "lib".to_string(),
+            "cpython-37".to_string(),
+            PythonModuleBytecodeProvider::Provided(DataLocation::Memory(b"bytecode".to_vec())),
+        ));
+        foo.in_memory_bytecode_opt2 = Some(PythonModuleBytecodeProvider::FromSource(
+            DataLocation::Memory(source.clone()),
+        ));
+
+        let levels = &[BytecodeOptimizationLevel::Zero];
+        let mut summary = PythonResourcesSummary::default();
+        assert!(summary.add_module_bytecode(&[ResourcePlacement::InMemory], &foo, levels)?);
+        assert!(summary.add_module_bytecode(
+            &[ResourcePlacement::FilesystemRelative],
+            &foo,
+            levels
+        )?);
+
+        // Bytecode compiled from source is counted by the size of its source.
+        // Levels that weren't requested aren't recorded.
+        assert_eq!(summary.bytecode_modules, 2);
+        assert_eq!(summary.in_memory_bytes, source.len() as i64);
+        assert_eq!(summary.filesystem_relative_bytes, 8 + BYTECODE_HEADER_SIZE);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::appimage_builder::{starlark_read_icon, starlark_to_desktop_entry},
+    super::file_resource::FileManifest,
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    super::util::required_type_arg,
+    crate::app_packaging::resource::FileManifest as RawFileManifest,
+    crate::app_packaging::snap::{snapcraft_project_path, SnapOptions},
+    crate::project_building::build_python_executable,
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::{anyhow, Result},
+    slog::warn,
+    starlark::environment::Environment,
+    starlark::values::{default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult},
+    starlark::{
+        any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::any::Any,
+    std::cmp::Ordering,
+};
+
+/// Represents a snapcraft project of a Python executable.
+pub struct SnapBuilder {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub options: SnapOptions,
+    pub files: RawFileManifest,
+}
+
+impl SnapBuilder {
+    /// SnapBuilder.add_files_manifest(manifest)
+    pub fn starlark_add_files_manifest(&mut self, manifest: &Value) -> ValueResult {
+        required_type_arg("manifest", "FileManifest", manifest)?;
+
+        let manifest = manifest.downcast_apply(|m: &FileManifest| m.manifest.clone());
+
+        self.files.add_manifest(&manifest).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_files_manifest()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// SnapBuilder.add_icon(icon_path)
+    pub fn starlark_add_icon(&mut self, env: &Environment, icon_path: &Value) -> ValueResult {
+        self.options.icon = Some(starlark_read_icon(env, "add_icon()", icon_path)?);
+
+        Ok(Value::new(None))
+    }
+
+    /// SnapBuilder.set_desktop_entry(name, categories=None, comment=None, terminal=False)
+    pub fn starlark_set_desktop_entry(
+        &mut self,
+        name: &Value,
+        categories: &Value,
+        comment: &Value,
+        terminal: &Value,
+    ) -> ValueResult {
+        self.options.desktop_entry = Some(starlark_to_desktop_entry(
+            "set_desktop_entry()",
+            name,
+            categories,
+            comment,
+            terminal,
+        )?);
+
+        Ok(Value::new(None))
+    }
+}
+
+impl TypedValue for SnapBuilder {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("SnapBuilder<{}>", self.options.name)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "SnapBuilder"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "name" => Value::new(self.options.name.clone()),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "SnapBuilder".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "name" => true,
+            _ => false,
+        })
+    }
+}
+
+impl BuildTarget for SnapBuilder {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        if !context.target_triple.contains("-linux-") {
+            return Err(anyhow!(
+                "snaps can only be built for Linux targets; got {}",
+                context.target_triple
+            ));
+        }
+
+        let build = build_python_executable(
+            &context.logger,
+            &self.exe.name(),
+            self.exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let mut files = build.binary_data.extra_files.clone();
+        files.add_manifest(&self.files)?;
+
+        let manifest = self
+            .options
+            .to_manifest(&build.exe_name, &build.exe_data, &files)?;
+
+        let project_path = snapcraft_project_path(&context.output_path, &self.options.name);
+        if project_path.exists() {
+            std::fs::remove_dir_all(&project_path)?;
+        }
+
+        warn!(
+            &context.logger,
+            "writing snapcraft project to {}; run snapcraft in it to build the snap",
+            project_path.display()
+        );
+        manifest.write_to_path(&project_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: context.output_path.clone(),
+        })
+    }
+}
+
+starlark_module! { snap_builder_env =>
+    #[allow(clippy::ptr_arg)]
+    SnapBuilder.add_files_manifest(this, manifest) {
+        this.downcast_apply_mut(|builder: &mut SnapBuilder| {
+            builder.starlark_add_files_manifest(&manifest)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    SnapBuilder.add_icon(env env, this, icon_path) {
+        this.downcast_apply_mut(|builder: &mut SnapBuilder| {
+            builder.starlark_add_icon(&env, &icon_path)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    SnapBuilder.set_desktop_entry(this, name, categories=None, comment=None, terminal=false) {
+        this.downcast_apply_mut(|builder: &mut SnapBuilder| {
+            builder.starlark_set_desktop_entry(&name, &categories, &comment, &terminal)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*};
+
+    #[test]
+    fn test_to_snap_builder() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+
+        let builder = starlark_eval_in_env(
+            &mut env,
+            "exe.to_snap_builder('myapp', '1.0', 'My app', plugs=['network', 'home'])",
+        )
+        .unwrap();
+        assert_eq!(builder.get_type(), "SnapBuilder");
+
+        builder.downcast_apply(|builder: &SnapBuilder| {
+            assert_eq!(builder.options.name, "myapp");
+            assert_eq!(builder.options.version, "1.0");
+            assert_eq!(builder.options.base, "core18");
+            assert_eq!(builder.options.grade, "stable");
+            assert_eq!(builder.options.confinement, "strict");
+            assert_eq!(
+                builder.options.plugs,
+                vec!["network".to_string(), "home".to_string()]
+            );
+            assert!(builder.options.desktop_entry.is_none());
+        });
+
+        assert!(
+            starlark_eval_in_env(&mut env, "exe.to_snap_builder('MyApp', '1.0', 'My app')")
+                .is_err()
+        );
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_snap_builder('myapp', '1.0', 'My app', confinement='none')"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_desktop_entry() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let icon_path = temp_dir.path().join("myapp.png");
+        std::fs::write(&icon_path, b"png")?;
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "snap = exe.to_snap_builder('myapp', '1.0', 'My app')",
+        )
+        .unwrap();
+
+        starlark_eval_in_env(
+            &mut env,
+            "snap.set_desktop_entry('My App', categories=['Utility'], terminal=True)",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!("snap.add_icon('{}')", icon_path.display()),
+        )
+        .unwrap();
+        assert!(starlark_eval_in_env(&mut env, "snap.set_desktop_entry(None)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "snap.add_icon('myapp.ico')").is_err());
+
+        let snap = starlark_eval_in_env(&mut env, "snap").unwrap();
+        snap.downcast_apply(|builder: &SnapBuilder| {
+            let entry = builder.options.desktop_entry.as_ref().unwrap();
+            assert_eq!(entry.name, "My App");
+            assert_eq!(entry.categories, vec!["Utility".to_string()]);
+            assert!(entry.terminal);
+            assert_eq!(
+                builder.options.icon,
+                Some(("png".to_string(), b"png".to_vec()))
+            );
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::project_building::build_python_executable,
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::Result,
+    slog::warn,
+    starlark::values::{default_compare, TypedValue, ValueError},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+    std::path::Path,
+};
+
+/// Represents a Python executable installed with its resources as files.
+///
+/// The executable is written at the root of the output directory. Resources
+/// are installed in `lib_dir`, except package resources, which are installed
+/// in `data_dir`.
+pub struct StandaloneFolder {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub lib_dir: String,
+    pub data_dir: String,
+}
+
+impl TypedValue for StandaloneFolder {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        "StandaloneFolder<>".to_string()
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "StandaloneFolder"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
+impl BuildTarget for StandaloneFolder {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let mut exe = self.exe.clone_box();
+        exe.relocate_resources_to_filesystem(&self.lib_dir, &self.data_dir)?;
+
+        let build = build_python_executable(
+            &context.logger,
+            &exe.name(),
+            exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file(
+            Path::new(&build.exe_name),
+            &FileContent {
+                data: build.exe_data.clone(),
+                executable: true,
+            },
+        )?;
+        manifest.add_manifest(&build.binary_data.extra_files)?;
+
+        warn!(
+            &context.logger,
+            "writing standalone folder to {}",
+            context.output_path.display()
+        );
+        manifest.replace_path(&context.output_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::Path {
+                path: context.output_path.join(&build.exe_name),
+            },
+            output_path: context.output_path.clone(),
+        })
+    }
+}
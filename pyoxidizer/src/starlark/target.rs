@@ -43,6 +43,20 @@ impl ResolvedTarget {
             }
         }
     }
+
+    /// Start running the built target without waiting for it to exit.
+    ///
+    /// Returns `None` if the target can't be run.
+    pub fn spawn(&self) -> Result<Option<std::process::Child>> {
+        match &self.run_mode {
+            RunMode::None => Ok(None),
+            RunMode::Path { path } => Ok(Some(
+                std::process::Command::new(&path)
+                    .current_dir(&path.parent().unwrap())
+                    .spawn()?,
+            )),
+        }
+    }
 }
 
 /// Describes context that a target is built in.
@@ -68,6 +82,21 @@ pub struct BuildContext {
 
     /// Where generated files should be written.
     pub output_path: PathBuf,
+
+    /// Maximum number of Python processes compiling bytecode in parallel.
+    pub bytecode_compiler_jobs: usize,
+
+    /// Whether bytecode is compiled by a libpython loaded in this process.
+    pub bytecode_compiler_in_process: bool,
+
+    /// Where compiled bytecode is cached.
+    pub bytecode_cache_path: PathBuf,
+
+    /// Whether network access is disabled.
+    pub offline: bool,
+
+    /// Where tools used by builds are cached.
+    pub tools_path: PathBuf,
 }
 
 /// Trait that indicates a type can be resolved as a target.
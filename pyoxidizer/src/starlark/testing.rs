@@ -0,0 +1,477 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Unit testing of Starlark configuration logic.
+
+Test files are named `*_test.bzl`. If a file without the `_test` suffix
+exists next to a test file (e.g. `pyoxidizer.bzl` for `pyoxidizer_test.bzl`),
+it is evaluated first in the same environment, so its functions can be
+tested. Each function of the test file whose name starts with `test_` is then
+called without arguments. A test passes if its function returns.
+
+Test files are evaluated with assertion functions and a `CONTEXT` which
+doesn't resolve targets, doesn't access the network and writes build state
+to a temporary directory.
+*/
+
+use {
+    super::env::{global_environment, EnvironmentContext},
+    super::util::{optional_str_arg, required_type_arg},
+    anyhow::{anyhow, Result},
+    codemap::CodeMap,
+    starlark::environment::Environment,
+    starlark::values::{RuntimeError, Value, ValueResult},
+    starlark::{
+        starlark_fun, starlark_module, starlark_signature, starlark_signature_extraction,
+        starlark_signatures,
+    },
+    std::collections::HashMap,
+    std::path::{Path, PathBuf},
+    std::sync::{Arc, Mutex},
+};
+
+/// Suffix of the names of test files.
+pub const TEST_FILE_SUFFIX: &str = "_test.bzl";
+
+/// Prefix of the names of test functions.
+pub const TEST_FUNCTION_PREFIX: &str = "test_";
+
+const ASSERTION_FAILED_ERROR_CODE: &str = "ASSERTION_FAILED";
+
+/// Describes the outcome of a test function.
+#[derive(Clone, Debug)]
+pub struct TestCaseResult {
+    pub name: String,
+
+    /// Error raised by the test function, if it failed.
+    pub error: Option<String>,
+}
+
+/// Describes the outcome of a test file.
+#[derive(Clone, Debug)]
+pub struct TestFileResult {
+    pub path: PathBuf,
+
+    /// Error evaluating the file, in which case no test function was called.
+    pub error: Option<String>,
+
+    pub cases: Vec<TestCaseResult>,
+}
+
+impl TestFileResult {
+    /// Whether evaluating the file and all its test functions succeeded.
+    pub fn success(&self) -> bool {
+        self.error.is_none() && self.cases.iter().all(|case| case.error.is_none())
+    }
+}
+
+/// Find test files.
+///
+/// `path` is either a test file or a directory, which is searched
+/// recursively, skipping hidden directories. Paths are sorted.
+pub fn find_test_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    if !path.is_dir() {
+        return Err(anyhow!("{} does not exist", path.display()));
+    }
+
+    let mut res = Vec::new();
+
+    let walker = walkdir::WalkDir::new(path)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !entry.file_name().to_string_lossy().starts_with('.')
+        });
+
+    for entry in walker {
+        let entry = entry?;
+
+        if entry.file_type().is_file()
+            && entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(TEST_FILE_SUFFIX)
+        {
+            res.push(entry.path().to_path_buf());
+        }
+    }
+
+    Ok(res)
+}
+
+/// Find names of test functions defined by Starlark source code.
+///
+/// Only functions defined at the top level are returned, in the order they
+/// are defined.
+pub fn find_test_functions(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter(|line| line.starts_with("def "))
+        .map(|line| {
+            line["def ".len()..]
+                .split(|c: char| c == '(' || c.is_whitespace())
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .filter(|name| name.starts_with(TEST_FUNCTION_PREFIX))
+        .collect()
+}
+
+/// Obtain the path of the file tested by a test file, if it exists.
+fn tested_file(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy();
+
+    if !file_name.ends_with(TEST_FILE_SUFFIX) {
+        return None;
+    }
+
+    let tested = path.with_file_name(format!(
+        "{}.bzl",
+        &file_name[0..file_name.len() - TEST_FILE_SUFFIX.len()]
+    ));
+
+    if tested.is_file() {
+        Some(tested)
+    } else {
+        None
+    }
+}
+
+/// Obtain a global environment with assertion functions.
+pub fn testing_environment(context: &EnvironmentContext) -> Result<Environment> {
+    let env = global_environment(context)
+        .or_else(|e| Err(anyhow!("error creating environment: {:?}", e)))?;
+
+    Ok(testing_module(env))
+}
+
+fn eval_file_in_env(env: &mut Environment, path: &Path) -> Result<()> {
+    let map = Arc::new(Mutex::new(CodeMap::new()));
+
+    starlark::eval::simple::eval_file(&map, &path.display().to_string(), false, env).or_else(
+        |e| {
+            let mut msg = Vec::new();
+            let raw_map = map.lock().unwrap();
+            {
+                let mut emitter = codemap_diagnostic::Emitter::vec(&mut msg, Some(&raw_map));
+                emitter.emit(&[e]);
+            }
+
+            Err(anyhow!("{}", String::from_utf8_lossy(&msg).trim_end()))
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Evaluate a test file and call its test functions.
+///
+/// Errors are only returned if the test environment can't be created.
+/// Errors evaluating the file and failures of test functions are recorded in
+/// the result.
+pub fn run_test_file(
+    logger: &slog::Logger,
+    path: &Path,
+    build_target_triple: &str,
+    verbose: bool,
+) -> Result<TestFileResult> {
+    let build_dir = tempdir::TempDir::new("pyoxidizer-test-config")?;
+
+    let mut context = EnvironmentContext::new(
+        logger,
+        verbose,
+        path,
+        crate::project_building::HOST,
+        build_target_triple,
+        false,
+        "0",
+        // Only targets resolved explicitly with resolve_target() are resolved.
+        Some(vec![]),
+        false,
+        false,
+        true,
+        false,
+    )?;
+    context.set_build_path(build_dir.path())?;
+
+    let mut env = testing_environment(&context)?;
+
+    let mut res = TestFileResult {
+        path: path.to_path_buf(),
+        error: None,
+        cases: Vec::new(),
+    };
+
+    let mut files = Vec::new();
+    if let Some(tested) = tested_file(path) {
+        files.push(tested);
+    }
+    files.push(path.to_path_buf());
+
+    for file in files {
+        if let Err(e) = eval_file_in_env(&mut env, &file) {
+            res.error = Some(format!("error evaluating {}: {}", file.display(), e));
+            return Ok(res);
+        }
+    }
+
+    let source = std::fs::read_to_string(path)?;
+
+    for name in find_test_functions(&source) {
+        let error = match env.get(&name) {
+            Ok(function) => function
+                .call(&Vec::new(), env.clone(), vec![], HashMap::new(), None, None)
+                .err()
+                .map(|e| format!("{:?}", e)),
+            Err(e) => Some(format!("{} is not defined: {:?}", name, e)),
+        };
+
+        res.cases.push(TestCaseResult { name, error });
+    }
+
+    Ok(res)
+}
+
+fn assertion_failed(label: &str, message: String, msg: Option<String>) -> ValueResult {
+    Err(RuntimeError {
+        code: ASSERTION_FAILED_ERROR_CODE,
+        message: if let Some(msg) = msg {
+            format!("{}: {}", msg, message)
+        } else {
+            message
+        },
+        label: label.to_string(),
+    }
+    .into())
+}
+
+/// assert_eq(actual, expected, msg=None)
+fn starlark_assert_eq(actual: &Value, expected: &Value, msg: &Value) -> ValueResult {
+    let msg = optional_str_arg("msg", &msg)?;
+
+    if actual == expected {
+        Ok(Value::new(None))
+    } else {
+        assertion_failed(
+            "assert_eq()",
+            format!("{} != {}", actual.to_repr(), expected.to_repr()),
+            msg,
+        )
+    }
+}
+
+/// assert_ne(actual, expected, msg=None)
+fn starlark_assert_ne(actual: &Value, expected: &Value, msg: &Value) -> ValueResult {
+    let msg = optional_str_arg("msg", &msg)?;
+
+    if actual != expected {
+        Ok(Value::new(None))
+    } else {
+        assertion_failed(
+            "assert_ne()",
+            format!("{} == {}", actual.to_repr(), expected.to_repr()),
+            msg,
+        )
+    }
+}
+
+/// assert_true(value, msg=None)
+fn starlark_assert_true(value: &Value, msg: &Value) -> ValueResult {
+    let msg = optional_str_arg("msg", &msg)?;
+
+    if value.to_bool() {
+        Ok(Value::new(None))
+    } else {
+        assertion_failed(
+            "assert_true()",
+            format!("{} is not true", value.to_repr()),
+            msg,
+        )
+    }
+}
+
+/// assert_false(value, msg=None)
+fn starlark_assert_false(value: &Value, msg: &Value) -> ValueResult {
+    let msg = optional_str_arg("msg", &msg)?;
+
+    if !value.to_bool() {
+        Ok(Value::new(None))
+    } else {
+        assertion_failed(
+            "assert_false()",
+            format!("{} is not false", value.to_repr()),
+            msg,
+        )
+    }
+}
+
+/// assert_fails(callable, contains=None)
+#[allow(clippy::ptr_arg)]
+fn starlark_assert_fails(
+    env: &Environment,
+    call_stack: &Vec<(String, String)>,
+    callable: &Value,
+    contains: &Value,
+) -> ValueResult {
+    required_type_arg("callable", "function", &callable)?;
+    let contains = optional_str_arg("contains", &contains)?;
+
+    match callable.call(call_stack, env.clone(), vec![], HashMap::new(), None, None) {
+        Ok(_) => assertion_failed(
+            "assert_fails()",
+            format!("{} did not fail", callable.to_repr()),
+            None,
+        ),
+        Err(e) => {
+            let error = format!("{:?}", e);
+
+            match contains {
+                Some(contains) if !error.contains(&contains) => assertion_failed(
+                    "assert_fails()",
+                    format!("error does not contain {:?}: {}", contains, error),
+                    None,
+                ),
+                _ => Ok(Value::new(None)),
+            }
+        }
+    }
+}
+
+starlark_module! { testing_module =>
+    assert_eq(actual, expected, msg=None) {
+        starlark_assert_eq(&actual, &expected, &msg)
+    }
+
+    assert_ne(actual, expected, msg=None) {
+        starlark_assert_ne(&actual, &expected, &msg)
+    }
+
+    assert_true(value, msg=None) {
+        starlark_assert_true(&value, &msg)
+    }
+
+    assert_false(value, msg=None) {
+        starlark_assert_false(&value, &msg)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    assert_fails(env env, call_stack cs, callable, contains=None) {
+        starlark_assert_fails(&env, &cs, &callable, &contains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*};
+
+    fn testing_env() -> Environment {
+        testing_module(starlark_env())
+    }
+
+    #[test]
+    fn test_assertions() {
+        let mut env = testing_env();
+
+        starlark_eval_in_env(&mut env, "assert_eq([1, 'a'], [1, 'a'])").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "assert_eq(1, 2)").is_err());
+        starlark_eval_in_env(&mut env, "assert_ne(1, 2)").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "assert_ne('a', 'a', msg='same')").is_err());
+        starlark_eval_in_env(&mut env, "assert_true([1])").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "assert_true(None)").is_err());
+        starlark_eval_in_env(&mut env, "assert_false('')").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "assert_false(True)").is_err());
+    }
+
+    #[test]
+    fn test_assert_fails() {
+        let mut env = testing_env();
+
+        starlark_eval_in_env(&mut env, "def bad(): fail('broken')").unwrap();
+        starlark_eval_in_env(&mut env, "def good(): pass").unwrap();
+
+        starlark_eval_in_env(&mut env, "assert_fails(bad)").unwrap();
+        starlark_eval_in_env(&mut env, "assert_fails(bad, contains='broken')").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "assert_fails(bad, contains='other')").is_err());
+        assert!(starlark_eval_in_env(&mut env, "assert_fails(good)").is_err());
+        assert!(starlark_eval_in_env(&mut env, "assert_fails(1)").is_err());
+    }
+
+    #[test]
+    fn test_find_test_functions() {
+        assert_eq!(
+            find_test_functions(
+                "def helper():\n    pass\n\
+                 def test_a():\n    def test_nested(): pass\n\
+                 def test_b (x=1):\n    pass\n\
+                 # def test_comment(): pass\n"
+            ),
+            vec!["test_a".to_string(), "test_b".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_run_test_file() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+
+        std::fs::write(
+            root.join("helpers.bzl"),
+            "def double(x):\n    return x * 2\n\
+             def make():\n    return FileManifest()\n\
+             register_target('files', make)\n\
+             resolve_targets()\n",
+        )?;
+        std::fs::write(
+            root.join("helpers_test.bzl"),
+            "def test_double():\n    assert_eq(double(2), 4)\n\
+             def test_resolve():\n    assert_eq(type(resolve_target('files')), 'FileManifest')\n\
+             def test_broken():\n    assert_eq(double(2), 5, msg='double')\n",
+        )?;
+        std::fs::write(root.join("invalid_test.bzl"), "def test_a(:\n")?;
+        std::fs::create_dir(root.join(".hidden"))?;
+        std::fs::write(root.join(".hidden").join("other_test.bzl"), "")?;
+
+        let files = find_test_files(root)?;
+        assert_eq!(
+            files,
+            vec![root.join("helpers_test.bzl"), root.join("invalid_test.bzl")]
+        );
+
+        let logger = crate::logging::logger_from_env(slog::Level::Error).logger;
+
+        let res = run_test_file(&logger, &files[0], crate::project_building::HOST, false)?;
+        assert!(res.error.is_none());
+        assert!(!res.success());
+        assert_eq!(
+            res.cases
+                .iter()
+                .map(|case| (case.name.as_str(), case.error.is_none()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("test_double", true),
+                ("test_resolve", true),
+                ("test_broken", false)
+            ]
+        );
+        assert!(res.cases[2]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("double: 4 != 5"));
+
+        let res = run_test_file(&logger, &files[1], crate::project_building::HOST, false)?;
+        assert!(res.error.is_some());
+        assert!(res.cases.is_empty());
+
+        Ok(())
+    }
+}
@@ -37,6 +37,9 @@ pub fn starlark_env() -> Environment {
         "0",
         None,
         false,
+        false,
+        false,
+        false,
     )
     .expect("unable to create EnvironmentContext");
 
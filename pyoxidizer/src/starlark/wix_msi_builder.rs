@@ -0,0 +1,278 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::file_resource::FileManifest,
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    super::util::{optional_str_arg, required_str_arg, required_type_arg},
+    crate::app_packaging::resource::{FileContent, FileManifest as RawFileManifest},
+    crate::app_packaging::wix::{build_msi, WixMsiOptions, WixShortcut},
+    crate::project_building::build_python_executable,
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    anyhow::Result,
+    slog::warn,
+    starlark::environment::Environment,
+    starlark::values::{default_compare, RuntimeError, TypedValue, Value, ValueError, ValueResult},
+    starlark::{
+        any, immutable, not_supported, starlark_fun, starlark_module, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::any::Any,
+    std::cmp::Ordering,
+    std::path::{Path, PathBuf},
+};
+
+/// Represents a Windows MSI installer of a Python executable.
+///
+/// The installer installs the executable and its extra files, plus any files
+/// added with `add_program_files_manifest()`, into `Program Files`.
+pub struct WiXMSIBuilder {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub options: WixMsiOptions,
+    pub program_files: RawFileManifest,
+}
+
+impl WiXMSIBuilder {
+    /// WiXMSIBuilder.add_program_files_manifest(manifest)
+    pub fn starlark_add_program_files_manifest(&mut self, manifest: &Value) -> ValueResult {
+        required_type_arg("manifest", "FileManifest", manifest)?;
+
+        let manifest = manifest.downcast_apply(|m: &FileManifest| m.manifest.clone());
+
+        self.program_files.add_manifest(&manifest).or_else(|e| {
+            Err(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: e.to_string(),
+                label: "add_program_files_manifest()".to_string(),
+            }
+            .into())
+        })?;
+
+        Ok(Value::new(None))
+    }
+
+    /// WiXMSIBuilder.add_shortcut(name, target=None)
+    pub fn starlark_add_shortcut(&mut self, name: &Value, target: &Value) -> ValueResult {
+        let name = required_str_arg("name", name)?;
+        let target = optional_str_arg("target", target)?;
+
+        let target = match target {
+            Some(target) => PathBuf::from(target),
+            None => PathBuf::from(self.exe_filename()),
+        };
+
+        self.options.shortcuts.push(WixShortcut { name, target });
+
+        Ok(Value::new(None))
+    }
+
+    /// File name of the executable, which is installed at the root.
+    fn exe_filename(&self) -> String {
+        // Built executables of Windows targets have an .exe extension.
+        format!("{}.exe", self.exe.name())
+    }
+}
+
+impl TypedValue for WiXMSIBuilder {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("WiXMSIBuilder<{}>", self.options.id_prefix)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "WiXMSIBuilder"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "id_prefix" => Value::new(self.options.id_prefix.clone()),
+            "product_name" => Value::new(self.options.product_name.clone()),
+            "product_version" => Value::new(self.options.product_version.clone()),
+            "product_manufacturer" => Value::new(self.options.product_manufacturer.clone()),
+            "upgrade_code" => Value::new(self.options.resolved_upgrade_code()),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "WiXMSIBuilder".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "id_prefix" => true,
+            "product_name" => true,
+            "product_version" => true,
+            "product_manufacturer" => true,
+            "upgrade_code" => true,
+            _ => false,
+        })
+    }
+}
+
+impl BuildTarget for WiXMSIBuilder {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let build = build_python_executable(
+            &context.logger,
+            &self.exe.name(),
+            self.exe.as_ref(),
+            &context.target_triple,
+            &context.opt_level,
+            context.release,
+            context.bytecode_compiler_jobs,
+            context.bytecode_compiler_in_process,
+            Some(&context.bytecode_cache_path),
+        )?;
+
+        let mut manifest = RawFileManifest::default();
+        manifest.add_file(
+            Path::new(&build.exe_name),
+            &FileContent {
+                data: build.exe_data.clone(),
+                executable: true,
+            },
+        )?;
+        manifest.add_manifest(&build.binary_data.extra_files)?;
+        manifest.add_manifest(&self.program_files)?;
+
+        let build_path = context.output_path.join("wix");
+        std::fs::create_dir_all(&build_path)?;
+
+        let msi_path = build_msi(
+            &context.logger,
+            &self.options,
+            &manifest,
+            &context.target_triple,
+            &build_path,
+            &context.tools_path,
+            context.offline,
+            &context.output_path,
+        )?;
+
+        warn!(
+            &context.logger,
+            "MSI installer written to {}",
+            msi_path.display()
+        );
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: context.output_path.clone(),
+        })
+    }
+}
+
+starlark_module! { wix_msi_builder_env =>
+    #[allow(clippy::ptr_arg)]
+    WiXMSIBuilder.add_program_files_manifest(this, manifest) {
+        this.downcast_apply_mut(|builder: &mut WiXMSIBuilder| {
+            builder.starlark_add_program_files_manifest(&manifest)
+        })
+    }
+
+    #[allow(clippy::ptr_arg)]
+    WiXMSIBuilder.add_shortcut(this, name, target=None) {
+        this.downcast_apply_mut(|builder: &mut WiXMSIBuilder| {
+            builder.starlark_add_shortcut(&name, &target)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*};
+
+    #[test]
+    fn test_to_wix_msi_builder() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+
+        let builder = starlark_eval_in_env(
+            &mut env,
+            "exe.to_wix_msi_builder('myapp', 'My App', '1.0', 'Me')",
+        )
+        .unwrap();
+        assert_eq!(builder.get_type(), "WiXMSIBuilder");
+
+        let v = starlark_eval_in_env(
+            &mut env,
+            "exe.to_wix_msi_builder('myapp', 'My App', '1.0', 'Me').product_name",
+        )
+        .unwrap();
+        assert_eq!(v.to_str(), "My App");
+
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_wix_msi_builder('my app', 'My App', '1.0', 'Me')"
+        )
+        .is_err());
+        assert!(starlark_eval_in_env(
+            &mut env,
+            "exe.to_wix_msi_builder('myapp', 'My App', '1.0-beta', 'Me')"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_add_shortcut_and_files() {
+        let mut env = starlark_env();
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(&mut env, "exe = dist.to_python_executable('myapp')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "msi = exe.to_wix_msi_builder('myapp', 'My App', '1.0', 'Me', upgrade_code='41e3ec6f-3bd0-4c1b-a3ca-0fcd85aaff92')",
+        )
+        .unwrap();
+
+        starlark_eval_in_env(&mut env, "msi.add_shortcut('My App')").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "msi.add_shortcut('Docs', target='docs/index.html')",
+        )
+        .unwrap();
+        starlark_eval_in_env(&mut env, "msi.add_program_files_manifest(FileManifest())").unwrap();
+        assert!(starlark_eval_in_env(&mut env, "msi.add_program_files_manifest(None)").is_err());
+
+        let msi = starlark_eval_in_env(&mut env, "msi").unwrap();
+        msi.downcast_apply(|builder: &WiXMSIBuilder| {
+            assert_eq!(
+                builder.options.resolved_upgrade_code(),
+                "41e3ec6f-3bd0-4c1b-a3ca-0fcd85aaff92"
+            );
+            assert_eq!(
+                builder.options.shortcuts,
+                vec![
+                    WixShortcut {
+                        name: "My App".to_string(),
+                        target: PathBuf::from("myapp.exe"),
+                    },
+                    WixShortcut {
+                        name: "Docs".to_string(),
+                        target: PathBuf::from("docs/index.html"),
+                    },
+                ]
+            );
+        });
+    }
+}
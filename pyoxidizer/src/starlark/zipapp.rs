@@ -0,0 +1,191 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::target::{BuildContext, BuildTarget, ResolvedTarget, RunMode},
+    crate::app_packaging::resource::{FileContent, FileManifest},
+    crate::py_packaging::binary::PythonBinaryBuilder,
+    crate::py_packaging::zipapp::write_zipapp,
+    anyhow::Result,
+    slog::warn,
+    starlark::values::{default_compare, TypedValue, ValueError},
+    starlark::{any, immutable, not_supported},
+    std::any::Any,
+    std::cmp::Ordering,
+    std::path::Path,
+};
+
+/// Represents a zip application built from the resources of a Python executable.
+///
+/// The archive is written to `<name>.pyz` in the output directory. If
+/// `interpreter` is defined, the archive starts with a shebang line running
+/// it and is executable.
+pub struct ZipApp {
+    pub exe: Box<dyn PythonBinaryBuilder>,
+    pub name: String,
+    pub interpreter: Option<String>,
+}
+
+impl ZipApp {
+    /// Obtain the file name of the archive.
+    pub fn file_name(&self) -> String {
+        if self.name.ends_with(".pyz") {
+            self.name.clone()
+        } else {
+            format!("{}.pyz", self.name)
+        }
+    }
+}
+
+impl TypedValue for ZipApp {
+    immutable!();
+    any!();
+    not_supported!(binop, container, function, get_hash, to_int);
+
+    fn to_str(&self) -> String {
+        format!("ZipApp<name={}>", self.name)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "ZipApp"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
+impl BuildTarget for ZipApp {
+    fn build(&mut self, context: &BuildContext) -> Result<ResolvedTarget> {
+        let (files, skipped) = self.exe.zipapp_files()?;
+
+        for name in &skipped {
+            warn!(
+                &context.logger,
+                "{} can't be imported from a zip application; it is not included", name
+            );
+        }
+
+        let file_name = self.file_name();
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file(
+            Path::new(&file_name),
+            &FileContent {
+                data: write_zipapp(&files, self.interpreter.as_deref())?,
+                executable: self.interpreter.is_some(),
+            },
+        )?;
+
+        warn!(
+            &context.logger,
+            "writing zip application to {}",
+            context.output_path.join(&file_name).display()
+        );
+        manifest.replace_path(&context.output_path)?;
+
+        Ok(ResolvedTarget {
+            run_mode: if self.interpreter.is_some() {
+                RunMode::Path {
+                    path: context.output_path.join(&file_name),
+                }
+            } else {
+                RunMode::None
+            },
+            output_path: context.output_path.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::super::python_executable::PythonExecutable, super::super::testutil::*, super::*,
+        std::io::Read,
+    };
+
+    #[test]
+    fn test_build() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path().join("src");
+        std::fs::create_dir_all(root.join("app"))?;
+        std::fs::write(root.join("app").join("__init__.py"), "")?;
+        std::fs::write(root.join("app").join("main.py"), "print('hello')")?;
+
+        let mut env = starlark_env();
+
+        starlark_eval_in_env(&mut env, "dist = default_python_distribution()").unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "config = PythonInterpreterConfig(run_module='app.main')",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            "exe = dist.to_python_executable('testapp', config=config)",
+        )
+        .unwrap();
+        starlark_eval_in_env(
+            &mut env,
+            &format!(
+                "exe.add_in_memory_python_resources(dist.read_package_root({:?}, ['app']))",
+                root.display().to_string()
+            ),
+        )
+        .unwrap();
+
+        let exe = env.get("exe").unwrap();
+        let mut app = exe.downcast_apply(|exe: &PythonExecutable| ZipApp {
+            exe: exe.exe.clone_box(),
+            name: "app".to_string(),
+            interpreter: Some("/usr/bin/env python3".to_string()),
+        });
+
+        let logger = crate::logging::logger_from_env(slog::Level::Error).logger;
+
+        let context = BuildContext {
+            logger,
+            host_triple: crate::project_building::HOST.to_string(),
+            target_triple: crate::project_building::HOST.to_string(),
+            release: false,
+            opt_level: "0".to_string(),
+            output_path: temp_dir.path().join("out"),
+            bytecode_compiler_jobs: 1,
+            bytecode_compiler_in_process: false,
+            bytecode_cache_path: temp_dir.path().join("bytecode_cache"),
+            offline: true,
+            tools_path: temp_dir.path().join("tools"),
+        };
+
+        let resolved = app.build(&context)?;
+        let path = temp_dir.path().join("out").join("app.pyz");
+        match resolved.run_mode {
+            RunMode::Path { path: run_path } => assert_eq!(run_path, path),
+            RunMode::None => panic!("zip application should be runnable"),
+        }
+
+        let data = std::fs::read(&path)?;
+        let offset = data.iter().position(|b| *b == b'\n').unwrap() + 1;
+        let mut zf = zip::ZipArchive::new(std::io::Cursor::new(&data[offset..]))?;
+
+        let mut main = String::new();
+        zf.by_name("__main__.py")?.read_to_string(&mut main)?;
+        assert!(main.contains("runpy.run_module(\"app.main\""));
+        assert!(zf.by_name("app/__init__.py").is_ok());
+        assert!(zf.by_name("app/main.py").is_ok());
+
+        // The standard library is provided by the interpreter running the archive.
+        assert!(zf.by_name("os.py").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+//! Extension module bundling the resources of an application.
+//!
+//! Importing this module from a Python interpreter makes modules and
+//! resources of the application importable.
+
+use std::os::raw::c_void;
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn PyInit_{{{name}}}() -> *mut c_void {
+    pyembed::init_bundle_module(
+        b"{{{name}}}\0",
+        include_bytes!(env!("PYOXIDIZER_PACKED_RESOURCES")),
+        "{{{python_version}}}",
+    ) as *mut c_void
+}
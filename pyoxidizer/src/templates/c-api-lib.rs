@@ -0,0 +1,62 @@
+//! C API of a library embedding a Python interpreter.
+//!
+//! The API is declared by the generated C header distributed with the
+//! library. Functions must be called from the same thread.
+
+use {pyembed::MainPythonInterpreter, std::os::raw::c_int};
+
+// Include an auto-generated file containing the default
+// `pyembed::PythonConfig` derived by the PyOxidizer configuration file.
+include!(env!("PYOXIDIZER_DEFAULT_PYTHON_CONFIG_RS"));
+
+/// The interpreter, once initialized.
+static mut INTERPRETER: Option<MainPythonInterpreter<'static, 'static, 'static>> = None;
+
+/// Initialize the embedded Python interpreter.
+///
+/// Returns 0 on success. Does nothing if the interpreter is initialized.
+#[no_mangle]
+pub extern "C" fn pyoxidizer_init() -> c_int {
+    unsafe {
+        if INTERPRETER.is_some() {
+            return 0;
+        }
+
+        match MainPythonInterpreter::new(default_python_config().into()) {
+            Ok(interp) => {
+                INTERPRETER = Some(interp);
+                0
+            }
+            Err(msg) => {
+                eprintln!("{}", msg);
+                1
+            }
+        }
+    }
+}
+
+/// Run the embedded Python interpreter like the `main()` of an executable.
+///
+/// Returns the exit code of the Python code that ran.
+#[no_mangle]
+pub extern "C" fn pyoxidizer_run_main() -> c_int {
+    unsafe {
+        match &mut INTERPRETER {
+            Some(interp) => interp.run_as_main(),
+            None => {
+                eprintln!("Python interpreter is not initialized");
+                1
+            }
+        }
+    }
+}
+
+/// Shut down the embedded Python interpreter.
+///
+/// Does nothing if the interpreter isn't initialized.
+#[no_mangle]
+pub extern "C" fn pyoxidizer_shutdown() {
+    unsafe {
+        INTERPRETER = None;
+    }
+}
@@ -0,0 +1,43 @@
+/* This file was generated by PyOxidizer.
+ *
+ * C API of {{library_name}}, a library embedding a Python interpreter.
+ *
+ * Functions must be called from the same thread. Errors are printed to
+ * stderr.
+ */
+
+#ifndef {{include_guard}}
+#define {{include_guard}}
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Version of this API. Incremented when functions change incompatibly. */
+#define PYOXIDIZER_C_API_VERSION 1
+
+/* Initialize the embedded Python interpreter.
+ *
+ * Returns 0 on success. Does nothing if the interpreter is initialized.
+ */
+int pyoxidizer_init(void);
+
+/* Run the embedded Python interpreter like the main() of an executable.
+ *
+ * What runs is defined by the PyOxidizer configuration file, e.g. a
+ * module or an entry point. Returns the exit code of the Python code that
+ * ran. The interpreter can't run Python code afterwards.
+ */
+int pyoxidizer_run_main(void);
+
+/* Shut down the embedded Python interpreter.
+ *
+ * Does nothing if the interpreter isn't initialized.
+ */
+void pyoxidizer_shutdown(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* {{include_guard}} */
@@ -16,6 +16,7 @@ def make_exe(dist):
     # embedded Python interpreter.
     python_config = PythonInterpreterConfig(
     #     bytes_warning=0,
+    #     ctypes_find_library_redirect=False,
     #     dont_write_bytecode=True,
     #     ignore_environment=True,
     #     inspect=False,
@@ -27,17 +28,25 @@ def make_exe(dist):
     #     no_user_site_directory=True,
     #     optimize_level=0,
     #     parser_debug=False,
+    #     python_executable_env=None,
     #     stdio_encoding=None,
     #     unbuffered_stdio=False,
     #     filesystem_importer=False,
+    #     startup_error_catalog=None,
+    #     startup_error_message=None,
+    #     startup_progress_callback=None,
     #     sys_frozen=False,
     #     sys_meipass=False,
     #     sys_paths=None,
     #     raw_allocator=None,
+    #     raw_allocator_stats=False,
+    #     remote_chunk_fetcher=None,
+    #     remote_resources_url=None,
     #     terminfo_resolution="dynamic",
     #     terminfo_dirs=None,
     #     use_hash_seed=False,
     #     verbose=0,
+    #     write_memory_stats_env=None,
     #     write_modules_directory_env=None,
     #     run_eval={{#if code}}(r"""{{{code}}}"""{{else}}None{{/if}},
     #     run_module=None,
@@ -92,6 +101,13 @@ def make_exe(dist):
         include_test=False,
     )
 
+    # Transform resources matching a pattern as they are added. Transforms
+    # are built-in (`strip_pycache`, `minify` or `gzip`) or defined by a
+    # Python function receiving the resource name and data.
+    #exe.add_resource_transform("*", "strip_pycache")
+    #exe.add_resource_transform("mypackage:static/*", "gzip")
+    #exe.add_resource_transform("mypackage.*", python_hook="hooks.py", python_hook_function="transform")
+
     # Invoke `pip install` with our Python distribution to install a single package.
     # `pip_install()` returns objects representing installed files.
     # `add_in_memory_python_resources()` adds these objects to the binary,
@@ -102,10 +118,40 @@ def make_exe(dist):
     # to our binary.
     #exe.add_in_memory_python_resources(dist.pip_install(["-r", "requirements.txt"]))
 
+    # Like above, but validate the requirements file first and require every
+    # requirement to be pinned to a version and hashes.
+    #exe.add_in_memory_python_resources(dist.pip_install_requirements("requirements.txt"))
+
+    # Invoke `pip download` to fetch wheels of a package and its dependencies
+    # and add the resources they contain to our binary. No package code runs
+    # on the build machine.
+    #exe.add_in_memory_python_resources(dist.pip_download(["appdirs"]))
+
+    # Read the resources of wheels in the `wheels` directory and add them to
+    # our binary, without running `pip`.
+    #exe.add_in_memory_python_resources(dist.read_wheels("wheels/*.whl"))
+
     {{#each pip_install_simple}}
     exe.add_in_memory_python_resources(dist.pip_install("{{{ this }}}"))
     {{/each}}
 
+    # Compile Jinja2 templates at build time and add them to our binary as the
+    # `mytemplates` package. `mytemplates.loader()` returns a Jinja2 loader
+    # of the compiled templates.
+    #exe.add_in_memory_python_resources(
+    #    dist.compile_jinja2_templates("templates", "mytemplates"),
+    #    add_source_module=False,
+    #)
+
+    # Generate Python modules and gRPC stubs from `.proto` files in the
+    # `protos` directory and add them to our binary. The `protobuf` and
+    # `grpcio` packages they import must be added as well.
+    #exe.add_in_memory_python_resources(dist.compile_protobuf(
+    #    ["protos/service.proto"],
+    #    include_dirs=["protos"],
+    #    grpc=True,
+    #))
+
     # Read Python files from a local directory and add them to our embedded
     # context, taking just the resources belonging to the `foo` and `bar`
     # Python packages.
@@ -122,6 +168,47 @@ def make_exe(dist):
     # in a file.
     #exe.filter_from_files(files=["/path/to/filter-file"]))
 
+    # Allow the application to create virtual environments seeded with pip
+    # at run-time via the `oxidized_venv` module. Requires `python_executable_env`
+    # to be set in the interpreter config.
+    #exe.add_venv_support()
+
+    # Store bytecode of a rarely used package in a `remote-resources` file
+    # next to the binary instead of in the binary. The file must be hosted at
+    # the `remote_resources_url` set in the interpreter config. Modules are
+    # fetched and verified when first imported.
+    #exe.add_remote_resources_package("my_package.plugins")
+
+    # Sign resource files installed next to the binary with an Ed25519 key,
+    # so the binary refuses to load them if they are modified after
+    # installation. The key file holds 32 random bytes, e.g. generated by
+    # `openssl rand -hex 32 > signing.key`.
+    #exe.sign_external_resources("signing.key")
+
+    # Define settings of the application manifest embedded in Windows
+    # binaries.
+    #exe.set_windows_manifest(
+    #    dpi_awareness="per-monitor-v2",
+    #    long_path_aware=True,
+    #    uac_execution_level="asInvoker",
+    #)
+
+    # Sign macOS binaries with the hardened runtime and the given
+    # entitlements.
+    #exe.set_macos_signing(
+    #    "Developer ID Application: Example Corp (ABCDE12345)",
+    #    hardened_runtime=True,
+    #    entitlements=["com.apple.security.cs.allow-unsigned-executable-memory"],
+    #)
+
+    # Install a systemd unit and a seccomp profile next to Linux binaries
+    # confining the application when deployed as a service.
+    #exe.set_linux_service(
+    #    network=True,
+    #    capabilities=["CAP_NET_BIND_SERVICE"],
+    #    writable_paths=["/var/lib/myapp"],
+    #)
+
     # Return our `PythonExecutable` instance so it can be built and
     # referenced by other consumers of this target.
     return exe
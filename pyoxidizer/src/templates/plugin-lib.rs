@@ -0,0 +1,80 @@
+//! Functions of a plugin library implemented in Python.
+//!
+//! Exported functions are declared by the generated C header distributed
+//! with the library. Calls are forwarded to the Python callables
+//! implementing them.
+
+#![allow(unused_imports)]
+
+use {
+    pyembed::{library_directory, PluginType, PluginValue, PythonPlugin},
+    std::ffi::CStr,
+    std::os::raw::{
+        c_char, c_int, c_long, c_longlong, c_uint, c_ulong, c_ulonglong, c_void,
+    },
+    std::sync::Once,
+};
+
+// Include an auto-generated file containing the default
+// `pyembed::PythonConfig` derived by the PyOxidizer configuration file.
+include!(env!("PYOXIDIZER_DEFAULT_PYTHON_CONFIG_RS"));
+
+/// Exported functions and the Python callables implementing them.
+const EXPORTS: &[(&str, &str)] = &[
+{{#each exports}}
+    ("{{{name}}}", "{{{callable}}}"),
+{{/each}}
+];
+
+/// The plugin, once initialized.
+///
+/// Only written by the closure passed to `INIT`.
+static mut PLUGIN: Option<PythonPlugin<'static, 'static, 'static>> = None;
+
+/// Initializes the plugin on the first call.
+static INIT: Once = Once::new();
+
+/// Convert a string argument, which may be NULL.
+unsafe fn string_arg(value: *const c_char) -> PluginValue {
+    PluginValue::String(if value.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+    })
+}
+
+/// Call the Python callable implementing an exported function.
+///
+/// The interpreter is initialized by the first call, while calls from other
+/// threads wait for it. Files installed next to the library are resolved
+/// relative to its directory, not to the directory of the executable of the
+/// host.
+unsafe fn call(symbol: &str, args: &[PluginValue], returns: PluginType) -> PluginValue {
+    INIT.call_once(|| {
+        let mut config: pyembed::OxidizedPythonInterpreterConfig = default_python_config().into();
+        if let Some(directory) = library_directory(call as *const c_void) {
+            config.origin = Some(match &config.origin {
+                Some(origin) => directory.join(origin),
+                None => directory,
+            });
+        }
+
+        match PythonPlugin::new(config, EXPORTS) {
+            Ok(plugin) => PLUGIN = Some(plugin),
+            Err(msg) => eprintln!("{}", msg),
+        }
+    });
+
+    match &PLUGIN {
+        Some(plugin) => plugin.call(symbol, args, returns),
+        None => PluginValue::zero(returns),
+    }
+}
+{{#each exports}}
+
+/// Implemented by `{{{callable}}}`.
+#[export_name = "{{{name}}}"]
+pub unsafe extern "C" fn {{{rust_name}}}({{{rust_parameters}}}){{{rust_returns}}} {
+    {{{rust_body}}}
+}
+{{/each}}
@@ -0,0 +1,31 @@
+/* This file was generated by PyOxidizer.
+ *
+ * Functions exported by {{library_name}}, a plugin library implemented in
+ * Python.
+ *
+ * Functions may be called from any thread. The Python interpreter is
+ * initialized by the first call. Exceptions raised by Python are printed to
+ * stderr and make functions return 0.
+ */
+
+#ifndef {{include_guard}}
+#define {{include_guard}}
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+{{#each exports}}
+/* Implemented by {{{callable}}}. */
+{{{declaration}}};
+
+{{/each}}
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* {{include_guard}} */
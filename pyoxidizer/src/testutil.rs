@@ -6,10 +6,13 @@ use {
     crate::logging::PrintlnDrain,
     crate::py_packaging::distribution::DistributionFlavor,
     crate::py_packaging::standalone_distribution::StandaloneDistribution,
+    crate::py_packaging::wheel::record_hash,
     crate::python_distributions::PYTHON_DISTRIBUTIONS,
     anyhow::Result,
     lazy_static::lazy_static,
     slog::{Drain, Logger},
+    std::io::Write,
+    std::path::Path,
     std::sync::Arc,
 };
 
@@ -30,12 +33,12 @@ lazy_static! {
         let path = DEFAULT_DISTRIBUTION_TEMP_DIR.path();
 
         let dist = PYTHON_DISTRIBUTIONS
-            .find_distribution(env!("HOST"), &DistributionFlavor::Standalone)
+            .find_distribution(env!("HOST"), &DistributionFlavor::Standalone, None)
             .expect("target triple not supported");
 
         let logger = get_logger().expect("unable to construct logger");
 
-        let dist = StandaloneDistribution::from_location(&logger, &dist.location, path)
+        let dist = StandaloneDistribution::from_location(&logger, &dist.location, path, false)
             .expect("unable to obtain distribution");
 
         Arc::new(Box::new(dist))
@@ -44,12 +47,12 @@ lazy_static! {
         let path = DEFAULT_DISTRIBUTION_TEMP_DIR.path();
 
         let dist = PYTHON_DISTRIBUTIONS
-            .find_distribution(env!("HOST"), &DistributionFlavor::StandaloneDynamic)
+            .find_distribution(env!("HOST"), &DistributionFlavor::StandaloneDynamic, None)
             .expect("target triple not supported");
 
         let logger = get_logger().expect("unable to construct logger");
 
-        let dist = StandaloneDistribution::from_location(&logger, &dist.location, path)
+        let dist = StandaloneDistribution::from_location(&logger, &dist.location, path, false)
             .expect("unable to obtain distribution");
 
         Arc::new(Box::new(dist))
@@ -64,3 +67,42 @@ pub fn get_default_distribution() -> Result<Arc<Box<StandaloneDistribution>>> {
 pub fn get_default_dynamic_distribution() -> Result<Arc<Box<StandaloneDistribution>>> {
     Ok(DEFAULT_DYNAMIC_DISTRIBUTION.clone())
 }
+
+/// Write a wheel of the `foo` 1.0 package.
+///
+/// `files` are listed in the RECORD file of the wheel and `unlisted_files`
+/// aren't.
+pub fn write_wheel(
+    path: &Path,
+    files: &[(&str, &[u8])],
+    unlisted_files: &[(&str, &[u8])],
+) -> Result<()> {
+    let mut zf = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let mut record = String::new();
+
+    for (name, data) in files {
+        zf.start_file(*name, zip::write::FileOptions::default())?;
+        zf.write_all(data)?;
+        record.push_str(&format!(
+            "{},{},{}\n",
+            name,
+            record_hash("sha256", data)?,
+            data.len()
+        ));
+    }
+
+    for (name, data) in unlisted_files {
+        zf.start_file(*name, zip::write::FileOptions::default())?;
+        zf.write_all(data)?;
+    }
+
+    record.push_str("foo-1.0.dist-info/RECORD,,\n");
+    zf.start_file(
+        "foo-1.0.dist-info/RECORD",
+        zip::write::FileOptions::default(),
+    )?;
+    zf.write_all(record.as_bytes())?;
+    zf.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Detection of changes to files targets are built from, for rebuild loops.
+
+`pyoxidizer watch` builds targets, then polls the configuration file, the
+`inputs` of targets and source directories read by resolved targets, such
+as package roots and `setup.py` projects. When files change, targets
+affected by the changes are built again, as determined by
+`change_detection`.
+
+Files are polled rather than watched with notification APIs of operating
+systems, which behave differently on each platform and on network
+filesystems. Snapshots are taken after builds, so files written by builds
+themselves, e.g. by `setup.py`, don't trigger builds.
+*/
+
+use {
+    crate::change_detection::normalize_path,
+    crate::starlark::env::EnvironmentContext,
+    anyhow::Result,
+    std::collections::BTreeMap,
+    std::path::{Path, PathBuf},
+    std::time::SystemTime,
+};
+
+/// Modification times and sizes of files in watched paths.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileSnapshot {
+    files: BTreeMap<PathBuf, (Option<SystemTime>, u64)>,
+}
+
+impl FileSnapshot {
+    /// Record files in `paths`, which may be files or directories.
+    ///
+    /// Files under `exclude`, e.g. the build directory, are ignored. Paths
+    /// which don't exist are ignored as well, so creating them is a change.
+    pub fn capture(paths: &[PathBuf], exclude: &Path) -> FileSnapshot {
+        let mut files = BTreeMap::new();
+
+        for path in paths {
+            for entry in walkdir::WalkDir::new(path)
+                .into_iter()
+                .filter_entry(|entry| !entry.path().starts_with(exclude))
+                .filter_map(|entry| entry.ok())
+            {
+                if let Ok(metadata) = entry.metadata() {
+                    if metadata.is_file() {
+                        files.insert(
+                            entry.path().to_path_buf(),
+                            (metadata.modified().ok(), metadata.len()),
+                        );
+                    }
+                }
+            }
+        }
+
+        FileSnapshot { files }
+    }
+
+    /// Obtain paths of files added, removed or modified in `other`.
+    pub fn changed_paths(&self, other: &FileSnapshot) -> Vec<PathBuf> {
+        let mut paths = other
+            .files
+            .iter()
+            .filter(|(path, state)| self.files.get(*path) != Some(state))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.files
+                    .keys()
+                    .filter(|path| !other.files.contains_key(*path))
+                    .cloned(),
+            )
+            .collect::<Vec<_>>();
+
+        paths.sort();
+
+        paths
+    }
+}
+
+/// Obtain paths to watch for changes affecting targets.
+///
+/// These are the configuration file, inputs of all targets and source
+/// directories read by resolved targets, normalized like changed paths
+/// compared to them.
+pub fn watched_paths(context: &EnvironmentContext) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![normalize_path(&context.config_path)?];
+
+    for target in context.targets.values() {
+        for path in target.inputs.iter().chain(&target.source_paths) {
+            let path = normalize_path(path)?;
+
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::*, starlark::values::Value, std::collections::HashMap};
+
+    #[test]
+    fn test_file_snapshot() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path().canonicalize()?;
+
+        std::fs::create_dir_all(root.join("src/app"))?;
+        std::fs::create_dir_all(root.join("src/build"))?;
+        std::fs::write(root.join("src/app/__init__.py"), "")?;
+        std::fs::write(root.join("src/app/main.py"), "print('hello')")?;
+        std::fs::write(root.join("src/build/main.py"), "")?;
+        std::fs::write(root.join("pyoxidizer.bzl"), "")?;
+
+        let paths = vec![
+            root.join("pyoxidizer.bzl"),
+            root.join("src"),
+            root.join("requirements.txt"),
+        ];
+        let exclude = root.join("src/build");
+
+        let snapshot = FileSnapshot::capture(&paths, &exclude);
+        assert!(snapshot
+            .changed_paths(&FileSnapshot::capture(&paths, &exclude))
+            .is_empty());
+
+        std::fs::write(root.join("src/app/main.py"), "print('hello world')")?;
+        std::fs::write(root.join("src/build/main.py"), "# built")?;
+        std::fs::remove_file(root.join("src/app/__init__.py"))?;
+        std::fs::write(root.join("requirements.txt"), "")?;
+
+        assert_eq!(
+            snapshot.changed_paths(&FileSnapshot::capture(&paths, &exclude)),
+            vec![
+                root.join("requirements.txt"),
+                root.join("src/app/__init__.py"),
+                root.join("src/app/main.py"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watched_paths() -> Result<()> {
+        let logger = get_logger()?;
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path().canonicalize()?;
+        let config_path = root.join("pyoxidizer.bzl");
+        std::fs::write(&config_path, "")?;
+        std::fs::create_dir(root.join("src"))?;
+
+        let mut context = EnvironmentContext::new(
+            &logger,
+            false,
+            &config_path,
+            crate::project_building::HOST,
+            crate::project_building::HOST,
+            false,
+            "0",
+            None,
+            false,
+            false,
+            false,
+            false,
+        )?;
+
+        for (target, inputs) in &[("app", vec![root.join("src")]), ("lib", vec![])] {
+            context.register_target(
+                target.to_string(),
+                Value::new(None),
+                Vec::new(),
+                HashMap::new(),
+                inputs.clone(),
+                Vec::new(),
+                false,
+                false,
+            );
+        }
+        context.targets.get_mut("lib").unwrap().source_paths =
+            vec![root.join("lib"), root.join("src")];
+
+        assert_eq!(
+            watched_paths(&context)?,
+            vec![config_path, root.join("src"), root.join("lib")]
+        );
+
+        Ok(())
+    }
+}
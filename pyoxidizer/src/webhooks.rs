@@ -0,0 +1,278 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Notification of build events to HTTP webhooks.
+
+When a webhook is configured, `pyoxidizer build` POSTs a JSON document to
+it when the build starts, when each target is built, when the build fails
+and when it completes. Documents of a build share a `build_id`.
+
+The webhook is defined by the `PYOXIDIZER_WEBHOOK_URL` environment variable
+or by `set_build_webhook()` in the configuration file, the environment
+taking precedence. `PYOXIDIZER_WEBHOOK_HEADERS` defines extra request
+headers, e.g. for authentication. As the configuration file must be
+evaluated to know its webhook, only webhooks defined by the environment
+are notified of failures to evaluate it.
+
+Notification failures are logged and don't fail builds. Webhooks aren't
+notified in offline mode.
+*/
+
+use {
+    crate::otlp::parse_headers,
+    crate::starlark::hooks::TargetResult,
+    anyhow::{anyhow, Context, Result},
+    serde_json::{json, Value},
+    sha2::{Digest, Sha256},
+    slog::warn,
+    std::path::Path,
+    std::time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Environment variable defining the URL of the webhook.
+pub const WEBHOOK_URL_ENV: &str = "PYOXIDIZER_WEBHOOK_URL";
+
+/// Environment variable defining `key=value` headers, separated by commas.
+pub const WEBHOOK_HEADERS_ENV: &str = "PYOXIDIZER_WEBHOOK_HEADERS";
+
+/// Error code of failures to evaluate the configuration file.
+pub const EVALUATION_ERROR_CODE: &str = "PYOXIDIZER_EVAL";
+
+/// Error code of failures to build a target.
+pub const BUILD_ERROR_CODE: &str = "PYOXIDIZER_BUILD";
+
+/// Error code of failures of hooks registered by the configuration file.
+pub const HOOK_ERROR_CODE: &str = "PYOXIDIZER_HOOK";
+
+/// An HTTP endpoint notified of build events.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildWebhook {
+    pub url: String,
+
+    /// Extra headers of requests.
+    pub headers: Vec<(String, String)>,
+}
+
+impl BuildWebhook {
+    /// Resolve the webhook defined by the environment, if any.
+    pub fn from_env() -> Result<Option<Self>> {
+        let url = match std::env::var(WEBHOOK_URL_ENV) {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+
+        let headers = match std::env::var(WEBHOOK_HEADERS_ENV) {
+            Ok(headers) => parse_headers(&headers, WEBHOOK_HEADERS_ENV)?,
+            Err(_) => Vec::new(),
+        };
+
+        Ok(Some(Self { url, headers }))
+    }
+
+    fn post(&self, data: &Value) -> Result<()> {
+        let mut request = reqwest::blocking::Client::new()
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(data)?);
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request
+            .send()
+            .with_context(|| format!("sending {}", self.url))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} responded {}", self.url, response.status()))
+        }
+    }
+}
+
+/// Describe a file produced by a build, with its size and SHA-256 digest.
+fn artifact_json(path: &Path) -> Result<Value> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    let mut hasher = Sha256::new();
+    hasher.input(&data);
+
+    Ok(json!({
+        "path": path.display().to_string(),
+        "size": data.len(),
+        "sha256": hex::encode(hasher.result()),
+    }))
+}
+
+/// Sends the events of a build to a webhook.
+#[derive(Clone, Debug)]
+pub struct BuildNotifier {
+    pub webhook: BuildWebhook,
+
+    /// Identifier shared by events of the build.
+    pub build_id: String,
+
+    pub config_path: String,
+    pub target_triple: String,
+    pub release: bool,
+}
+
+impl BuildNotifier {
+    pub fn new(
+        webhook: BuildWebhook,
+        config_path: &Path,
+        target_triple: &str,
+        release: bool,
+    ) -> Self {
+        Self {
+            webhook,
+            build_id: uuid::Uuid::new_v4().to_string(),
+            config_path: config_path.display().to_string(),
+            target_triple: target_triple.to_string(),
+            release,
+        }
+    }
+
+    /// Obtain the JSON document describing an event.
+    ///
+    /// `data` holds fields specific to the event.
+    pub fn event_json(&self, event: &str, data: Value) -> Value {
+        let mut value = json!({
+            "event": event,
+            "build_id": self.build_id,
+            "timestamp": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            "pyoxidizer_version": crate::environment::PYOXIDIZER_VERSION,
+            "config_path": self.config_path,
+            "target_triple": self.target_triple,
+            "release": self.release,
+        });
+
+        if let (Some(value), Value::Object(data)) = (value.as_object_mut(), data) {
+            value.extend(data);
+        }
+
+        value
+    }
+
+    fn send(&self, logger: &slog::Logger, event: &str, data: Value) {
+        warn!(logger, "notifying {} of {}", self.webhook.url, event);
+
+        if let Err(e) = self.webhook.post(&self.event_json(event, data)) {
+            warn!(logger, "error notifying webhook: {:#}", e);
+        }
+    }
+
+    /// Notify that targets are about to be built.
+    pub fn started(&self, logger: &slog::Logger, targets: &[String]) {
+        self.send(logger, "build_started", json!({ "targets": targets }));
+    }
+
+    /// Obtain the data of a `target_built` event.
+    pub fn target_built_json(result: &TargetResult) -> Result<Value> {
+        Ok(json!({
+            "target": result.target,
+            "duration_ms": result.duration.as_millis() as u64,
+            "artifacts": result
+                .artifacts
+                .iter()
+                .map(|path| artifact_json(Path::new(path)))
+                .collect::<Result<Vec<_>>>()?,
+        }))
+    }
+
+    /// Notify that a target was built.
+    pub fn target_built(&self, logger: &slog::Logger, result: &TargetResult) {
+        match Self::target_built_json(result) {
+            Ok(data) => self.send(logger, "target_built", data),
+            Err(e) => warn!(
+                logger,
+                "error describing artifacts of {}: {:#}", result.target, e
+            ),
+        }
+    }
+
+    /// Notify that the build failed.
+    ///
+    /// `target` is the target being built when the failure occurred, if any.
+    pub fn failed(
+        &self,
+        logger: &slog::Logger,
+        target: Option<&str>,
+        error_code: &str,
+        error: &anyhow::Error,
+    ) {
+        self.send(
+            logger,
+            "build_failed",
+            json!({
+                "target": target,
+                "error_code": error_code,
+                "error": format!("{:#}", error),
+            }),
+        );
+    }
+
+    /// Notify that all targets were built.
+    pub fn completed(&self, logger: &slog::Logger, targets: &[String]) {
+        self.send(logger, "build_completed", json!({ "targets": targets }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[test]
+    fn test_event_json() {
+        let notifier = BuildNotifier::new(
+            BuildWebhook {
+                url: "http://localhost/hook".to_string(),
+                headers: vec![],
+            },
+            Path::new("/project/pyoxidizer.bzl"),
+            "x86_64-unknown-linux-gnu",
+            true,
+        );
+
+        let value = notifier.event_json(
+            "build_failed",
+            json!({"target": "exe", "error_code": BUILD_ERROR_CODE}),
+        );
+
+        assert_eq!(value["event"], "build_failed");
+        assert_eq!(value["build_id"], notifier.build_id.as_str());
+        assert_eq!(value["config_path"], "/project/pyoxidizer.bzl");
+        assert_eq!(value["target_triple"], "x86_64-unknown-linux-gnu");
+        assert_eq!(value["release"], true);
+        assert_eq!(value["target"], "exe");
+        assert_eq!(value["error_code"], "PYOXIDIZER_BUILD");
+        assert!(value["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_target_built_json() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        std::fs::write(temp_dir.path().join("myapp"), b"foo")?;
+
+        let result = TargetResult::built("exe", Duration::from_millis(42), Some(temp_dir.path()));
+        let value = BuildNotifier::target_built_json(&result)?;
+
+        assert_eq!(value["target"], "exe");
+        assert_eq!(value["duration_ms"], 42);
+        assert_eq!(
+            value["artifacts"],
+            json!([{
+                "path": temp_dir.path().join("myapp").display().to_string(),
+                "size": 3,
+                "sha256": "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae",
+            }])
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Building several configuration files at once.
+
+A workspace is a directory containing `pyoxidizer.bzl` files in any of its
+subdirectories. Each configuration file is evaluated in its own environment,
+so targets of different configuration files don't conflict, and builds into
+its own build directory. Caches are shared: Python distributions are cached
+per user and compiled bytecode is cached in the workspace's build directory.
+
+The outcome of building every configuration file is aggregated into a
+report, which can be serialized to JSON.
+*/
+
+use {
+    crate::starlark::hooks::TargetResult,
+    anyhow::{anyhow, Context, Result},
+    serde::Serialize,
+    std::path::{Path, PathBuf},
+};
+
+/// Name of configuration files discovered in workspaces.
+pub const CONFIG_FILE_NAME: &str = "pyoxidizer.bzl";
+
+/// Version of the workspace report format.
+pub const REPORT_FORMAT_VERSION: u32 = 1;
+
+/// Find configuration files in a workspace directory.
+///
+/// Hidden directories (whose names start with `.`) and build directories next
+/// to configuration files aren't searched. Paths are sorted.
+pub fn find_config_files(root: &Path) -> Result<Vec<PathBuf>> {
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a directory", root.display()));
+    }
+
+    let mut res = Vec::new();
+
+    let walker = walkdir::WalkDir::new(root)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            if entry.depth() == 0 || !entry.file_type().is_dir() {
+                return true;
+            }
+
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let build_dir = entry.file_name() == "build"
+                && entry
+                    .path()
+                    .parent()
+                    .map(|parent| parent.join(CONFIG_FILE_NAME).is_file())
+                    .unwrap_or(false);
+
+            !hidden && !build_dir
+        });
+
+    for entry in walker {
+        let entry = entry.with_context(|| format!("walking {}", root.display()))?;
+
+        if entry.file_type().is_file() && entry.file_name() == CONFIG_FILE_NAME {
+            res.push(entry.path().to_path_buf());
+        }
+    }
+
+    Ok(res)
+}
+
+/// Obtain the path where configuration files of a workspace cache compiled bytecode.
+pub fn workspace_bytecode_cache_path(workspace_path: &Path) -> PathBuf {
+    workspace_path.join("build").join("bytecode_cache")
+}
+
+/// Describes the outcome of building a target of a configuration file.
+#[derive(Clone, Debug, Serialize)]
+pub struct TargetReport {
+    pub target: String,
+    pub success: bool,
+    /// Wall time spent building the target, in milliseconds.
+    pub duration_ms: u64,
+    /// Paths of files produced by building the target.
+    pub artifacts: Vec<String>,
+}
+
+impl From<&TargetResult> for TargetReport {
+    fn from(result: &TargetResult) -> Self {
+        Self {
+            target: result.target.clone(),
+            success: result.success,
+            duration_ms: result.duration.as_millis() as u64,
+            artifacts: result.artifacts.clone(),
+        }
+    }
+}
+
+/// Describes the outcome of building a configuration file.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigReport {
+    pub config_path: String,
+    pub success: bool,
+    /// Error building the configuration file, if it failed.
+    pub error: Option<String>,
+    pub targets: Vec<TargetReport>,
+}
+
+/// Describes the outcome of building a workspace.
+#[derive(Clone, Debug, Serialize)]
+pub struct WorkspaceReport {
+    pub format_version: u32,
+    pub workspace_path: String,
+    pub target_triple: String,
+    pub release: bool,
+    pub configs: Vec<ConfigReport>,
+}
+
+impl WorkspaceReport {
+    pub fn new(workspace_path: &Path, target_triple: &str, release: bool) -> Self {
+        Self {
+            format_version: REPORT_FORMAT_VERSION,
+            workspace_path: workspace_path.display().to_string(),
+            target_triple: target_triple.to_string(),
+            release,
+            configs: Vec::new(),
+        }
+    }
+
+    /// Record the outcome of building a configuration file.
+    pub fn add_config(&mut self, config_path: &Path, results: &[TargetResult], res: &Result<()>) {
+        self.configs.push(ConfigReport {
+            config_path: config_path.display().to_string(),
+            success: res.is_ok(),
+            error: res.as_ref().err().map(|e| format!("{:?}", e)),
+            targets: results.iter().map(TargetReport::from).collect(),
+        });
+    }
+
+    /// Paths of configuration files which failed to build.
+    pub fn failed_configs(&self) -> Vec<&str> {
+        self.configs
+            .iter()
+            .filter(|config| !config.success)
+            .map(|config| config.config_path.as_str())
+            .collect()
+    }
+
+    /// Render a human readable summary.
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+
+        for config in &self.configs {
+            let built = config.targets.iter().filter(|t| t.success).count();
+
+            lines.push(format!(
+                "{} {}: {} target{} built",
+                if config.success { "ok    " } else { "FAILED" },
+                config.config_path,
+                built,
+                if built == 1 { "" } else { "s" }
+            ));
+
+            if let Some(error) = &config.error {
+                if let Some(line) = error.lines().next() {
+                    lines.push(format!("       {}", line));
+                }
+            }
+        }
+
+        lines.push(format!(
+            "{} of {} configuration files built successfully",
+            self.configs.len() - self.failed_configs().len(),
+            self.configs.len()
+        ));
+
+        lines.join("\n")
+    }
+
+    /// Serialize the report to a JSON string.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[test]
+    fn test_find_config_files() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-test")?;
+        let root = temp_dir.path();
+
+        for path in &[
+            "pyoxidizer.bzl",
+            "apps/a/pyoxidizer.bzl",
+            "apps/b/pyoxidizer.bzl",
+            "apps/b/build/copy/pyoxidizer.bzl",
+            "apps/c/other.bzl",
+            "lib/build/pyoxidizer.bzl",
+            ".git/pyoxidizer.bzl",
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, b"")?;
+        }
+
+        assert_eq!(
+            find_config_files(root)?
+                .iter()
+                .map(|p| p.strip_prefix(root).unwrap().to_path_buf())
+                .collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("apps/a/pyoxidizer.bzl"),
+                PathBuf::from("apps/b/pyoxidizer.bzl"),
+                PathBuf::from("lib/build/pyoxidizer.bzl"),
+                PathBuf::from("pyoxidizer.bzl"),
+            ]
+        );
+
+        assert!(find_config_files(&root.join("missing")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() -> Result<()> {
+        let mut report = WorkspaceReport::new(Path::new("apps"), "x86_64", false);
+
+        report.add_config(
+            Path::new("apps/a/pyoxidizer.bzl"),
+            &[TargetResult {
+                target: "exe".to_string(),
+                success: true,
+                duration: Duration::from_millis(42),
+                artifacts: vec!["build/exe/a".to_string()],
+            }],
+            &Ok(()),
+        );
+        report.add_config(
+            Path::new("apps/b/pyoxidizer.bzl"),
+            &[],
+            &Err(anyhow!("error evaluating")),
+        );
+
+        assert_eq!(report.failed_configs(), vec!["apps/b/pyoxidizer.bzl"]);
+        assert_eq!(
+            report.summary(),
+            "ok     apps/a/pyoxidizer.bzl: 1 target built\n\
+             FAILED apps/b/pyoxidizer.bzl: 0 targets built\n       \
+             error evaluating\n\
+             1 of 2 configuration files built successfully"
+        );
+
+        let value: serde_json::Value = serde_json::from_str(&report.to_json()?)?;
+        assert_eq!(value["configs"][0]["targets"][0]["duration_ms"], 42);
+        assert_eq!(value["configs"][1]["error"], "error evaluating");
+
+        Ok(())
+    }
+}
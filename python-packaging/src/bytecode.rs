@@ -5,13 +5,18 @@
 /*! Work with Python bytecode. */
 
 use {
+    super::libpython_compiler::LibpythonCompiler,
     super::resource::BytecodeOptimizationLevel,
     anyhow::{anyhow, Result},
     byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt},
+    sha2::{Digest, Sha256},
+    std::collections::VecDeque,
     std::fs::File,
     std::io::{BufRead, BufReader, Read, Write},
     std::path::{Path, PathBuf},
     std::process,
+    std::sync::atomic::{AtomicUsize, Ordering},
+    std::sync::{Arc, Mutex},
 };
 
 pub const BYTECODE_COMPILER: &[u8] = include_bytes!("bytecodecompiler.py");
@@ -27,6 +32,7 @@ pub struct BytecodeCompiler {
 }
 
 /// Output mode for BytecodeCompiler.
+#[derive(Clone, Copy, Debug)]
 pub enum CompileMode {
     /// Emit just Python bytecode.
     Bytecode,
@@ -115,7 +121,9 @@ impl BytecodeCompiler {
         reader.read_line(&mut len_s)?;
 
         let len_s = len_s.trim_end();
-        let bytecode_len = len_s.parse::<u64>().unwrap();
+        let bytecode_len = len_s
+            .parse::<u64>()
+            .or_else(|_| Err(anyhow!("error compiling {}", filename)))?;
 
         let mut bytecode: Vec<u8> = Vec::new();
         reader.take(bytecode_len).read_to_end(&mut bytecode)?;
@@ -124,11 +132,328 @@ impl BytecodeCompiler {
     }
 }
 
+/// A request to compile Python source into bytecode.
+#[derive(Clone, Debug)]
+pub struct BytecodeCompileRequest {
+    pub source: Vec<u8>,
+    pub filename: String,
+    pub optimize: BytecodeOptimizationLevel,
+    pub output_mode: CompileMode,
+}
+
+static BYTECODE_CACHE_HITS: AtomicUsize = AtomicUsize::new(0);
+static BYTECODE_CACHE_MISSES: AtomicUsize = AtomicUsize::new(0);
+
+/// Obtain the numbers of hits and misses of bytecode cache lookups.
+///
+/// Lookups of all caches of the current process are counted.
+pub fn bytecode_cache_lookups() -> (usize, usize) {
+    (
+        BYTECODE_CACHE_HITS.load(Ordering::SeqCst),
+        BYTECODE_CACHE_MISSES.load(Ordering::SeqCst),
+    )
+}
+
+/// An on-disk cache of compiled bytecode.
+///
+/// Entries are keyed by the hash of the source, the compilation settings
+/// and the Python distribution compiling it, so bytecode of unchanged
+/// modules is reused across builds.
+#[derive(Clone, Debug)]
+pub struct BytecodeCache {
+    path: PathBuf,
+}
+
+impl BytecodeCache {
+    /// Create a cache storing entries in a directory.
+    pub fn new(path: &Path) -> BytecodeCache {
+        BytecodeCache {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Compute the key of the bytecode compiled for a request.
+    ///
+    /// `distribution_id` identifies the Python distribution compiling bytecode.
+    pub fn key(distribution_id: &str, request: &BytecodeCompileRequest) -> String {
+        let mut hasher = Sha256::new();
+
+        hasher.input(Sha256::digest(&request.source));
+        // The filename is part of the bytecode, so it is part of the key.
+        hasher.input(format!(
+            "\0{}\0{}\0{:?}\0{}",
+            request.filename,
+            i32::from(request.optimize),
+            request.output_mode,
+            distribution_id
+        ));
+
+        format!("{:x}", hasher.result())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.path.join(&key[0..2]).join(key)
+    }
+
+    /// Obtain cached bytecode, if present.
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(self.entry_path(key)) {
+            Ok(data) => {
+                BYTECODE_CACHE_HITS.fetch_add(1, Ordering::SeqCst);
+                Ok(Some(data))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                BYTECODE_CACHE_MISSES.fetch_add(1, Ordering::SeqCst);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Store bytecode in the cache.
+    pub fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.entry_path(key);
+        let parent = path
+            .parent()
+            .ok_or_else(|| anyhow!("unable to resolve parent directory"))?;
+        std::fs::create_dir_all(parent)?;
+
+        // Entries are written to a temporary file then renamed, so concurrent
+        // builds never read a partial entry.
+        let temp_path = parent.join(format!("{}.{}.tmp", key, std::process::id()));
+        std::fs::write(&temp_path, data)?;
+        std::fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+}
+
+/// How bytecode is compiled.
+#[derive(Clone, Debug)]
+pub enum BytecodeCompilerBackend {
+    /// Run up to `jobs` processes of a Python executable in parallel.
+    Process { python_exe: PathBuf, jobs: usize },
+
+    /// Load a shared libpython into this process.
+    ///
+    /// `stdlib_path` is the standard library of the distribution providing
+    /// `libpython`.
+    InProcess {
+        libpython: PathBuf,
+        stdlib_path: PathBuf,
+    },
+}
+
+/// A pool of `BytecodeCompiler` compiling Python source in parallel.
+///
+/// Each compiler is a separate Python process. Processes are started as
+/// needed, up to the size of the pool, and are reused across calls.
+///
+/// A pool created with `new_in_process()` instead compiles sequentially
+/// with a `LibpythonCompiler`.
+#[derive(Debug)]
+pub struct BytecodeCompilerPool {
+    python: PathBuf,
+    size: usize,
+    compilers: Vec<BytecodeCompiler>,
+    in_process: Option<LibpythonCompiler>,
+    cache: Option<BytecodeCache>,
+
+    /// Magic number for bytecode header.
+    pub magic_number: u32,
+}
+
+impl BytecodeCompilerPool {
+    /// Create a pool of up to `size` bytecode compilers using a Python executable.
+    pub fn new(python: &Path, size: usize) -> Result<BytecodeCompilerPool> {
+        if size == 0 {
+            return Err(anyhow!("bytecode compiler pool size must be at least 1"));
+        }
+
+        let compiler = BytecodeCompiler::new(python)?;
+
+        Ok(BytecodeCompilerPool {
+            python: python.to_path_buf(),
+            size,
+            magic_number: compiler.magic_number,
+            compilers: vec![compiler],
+            in_process: None,
+            cache: None,
+        })
+    }
+
+    /// Create a pool compiling with a shared libpython loaded in this process.
+    pub fn new_in_process(libpython: &Path, stdlib_path: &Path) -> Result<BytecodeCompilerPool> {
+        let compiler = LibpythonCompiler::new(libpython, stdlib_path)?;
+
+        Ok(BytecodeCompilerPool {
+            python: libpython.to_path_buf(),
+            size: 1,
+            magic_number: compiler.magic_number,
+            compilers: Vec::new(),
+            in_process: Some(compiler),
+            cache: None,
+        })
+    }
+
+    /// Create a pool using a compilation backend.
+    pub fn from_backend(backend: &BytecodeCompilerBackend) -> Result<BytecodeCompilerPool> {
+        match backend {
+            BytecodeCompilerBackend::Process { python_exe, jobs } => Self::new(python_exe, *jobs),
+            BytecodeCompilerBackend::InProcess {
+                libpython,
+                stdlib_path,
+            } => Self::new_in_process(libpython, stdlib_path),
+        }
+    }
+
+    /// Look up and store compiled bytecode in a cache.
+    pub fn set_cache(&mut self, cache: BytecodeCache) {
+        self.cache = Some(cache);
+    }
+
+    /// Compile requests, returning bytecode in the order of requests.
+    ///
+    /// Requests are distributed across compilers as they become idle.
+    /// Bytecode found in the cache isn't compiled again.
+    pub fn compile_all(&mut self, requests: Vec<BytecodeCompileRequest>) -> Result<Vec<Vec<u8>>> {
+        let cache = match &self.cache {
+            Some(cache) => cache.clone(),
+            None => return self.compile_uncached(requests),
+        };
+
+        // The interpreter or libpython path identifies the distribution and the magic
+        // number the bytecode format.
+        let distribution_id = format!("{}:{}", self.python.display(), self.magic_number);
+
+        let mut bytecodes = Vec::with_capacity(requests.len());
+        let mut misses = Vec::new();
+        let mut miss_requests = Vec::new();
+
+        for (index, request) in requests.into_iter().enumerate() {
+            let key = BytecodeCache::key(&distribution_id, &request);
+
+            match cache.get(&key)? {
+                Some(bytecode) => bytecodes.push(bytecode),
+                None => {
+                    bytecodes.push(Vec::new());
+                    misses.push((index, key));
+                    miss_requests.push(request);
+                }
+            }
+        }
+
+        for ((index, key), bytecode) in misses
+            .into_iter()
+            .zip(self.compile_uncached(miss_requests)?)
+        {
+            cache.put(&key, &bytecode)?;
+            bytecodes[index] = bytecode;
+        }
+
+        Ok(bytecodes)
+    }
+
+    fn compile_uncached(&mut self, requests: Vec<BytecodeCompileRequest>) -> Result<Vec<Vec<u8>>> {
+        if let Some(compiler) = &self.in_process {
+            return requests
+                .into_iter()
+                .map(|r| compiler.compile(&r.source, &r.filename, r.optimize, r.output_mode))
+                .collect();
+        }
+
+        let count = requests.len();
+        let jobs = self.size.min(count).max(1);
+
+        while self.compilers.len() < jobs {
+            self.compilers.push(BytecodeCompiler::new(&self.python)?);
+        }
+
+        if jobs == 1 {
+            let compiler = &mut self.compilers[0];
+
+            return requests
+                .into_iter()
+                .map(|r| compiler.compile(&r.source, &r.filename, r.optimize, r.output_mode))
+                .collect();
+        }
+
+        let queue = Arc::new(Mutex::new(
+            requests.into_iter().enumerate().collect::<VecDeque<_>>(),
+        ));
+
+        let handles = self
+            .compilers
+            .drain(..)
+            .map(|mut compiler| {
+                let queue = queue.clone();
+
+                std::thread::spawn(move || {
+                    // The lock is only held while taking a request.
+                    let next = || queue.lock().unwrap().pop_front();
+                    let mut res = Vec::new();
+
+                    while let Some((index, r)) = next() {
+                        match compiler.compile(&r.source, &r.filename, r.optimize, r.output_mode) {
+                            Ok(bytecode) => res.push((index, bytecode)),
+                            Err(e) => {
+                                // Other compilers stop once the queue is empty.
+                                queue.lock().unwrap().clear();
+                                return (compiler, Err(e));
+                            }
+                        }
+                    }
+
+                    (compiler, Ok(res))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut bytecodes = vec![None; count];
+        let mut error = None;
+
+        for handle in handles {
+            let (compiler, res) = handle
+                .join()
+                .or_else(|_| Err(anyhow!("bytecode compiler thread panicked")))?;
+
+            match res {
+                Ok(res) => {
+                    for (index, bytecode) in res {
+                        bytecodes[index] = Some(bytecode);
+                    }
+
+                    self.compilers.push(compiler);
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        Ok(bytecodes
+            .into_iter()
+            .map(|bytecode| bytecode.expect("all requests should be compiled"))
+            .collect())
+    }
+}
+
 impl Drop for BytecodeCompiler {
     fn drop(&mut self) {
         let stdin = self.command.stdin.as_mut().expect("failed to get stdin");
-        stdin.write_all(b"exit\n").expect("write failed");
-        stdin.flush().expect("flush failed");
+
+        // The process has already exited if compilation failed.
+        if stdin
+            .write_all(b"exit\n")
+            .and_then(|_| stdin.flush())
+            .is_err()
+        {
+            let _ = self.command.kill();
+        }
 
         self.command.wait().expect("compiler process did not exit");
     }
@@ -197,4 +522,53 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_cache() -> Result<()> {
+        let temp_dir = tempdir::TempDir::new("bytecode-cache-test")?;
+        let cache = BytecodeCache::new(temp_dir.path());
+
+        let request = BytecodeCompileRequest {
+            source: b"x = 1\n".to_vec(),
+            filename: "foo".to_string(),
+            optimize: BytecodeOptimizationLevel::Zero,
+            output_mode: CompileMode::Bytecode,
+        };
+        let key = BytecodeCache::key("python", &request);
+
+        for other in &[
+            BytecodeCompileRequest {
+                source: b"x = 2\n".to_vec(),
+                ..request.clone()
+            },
+            BytecodeCompileRequest {
+                filename: "bar".to_string(),
+                ..request.clone()
+            },
+            BytecodeCompileRequest {
+                optimize: BytecodeOptimizationLevel::One,
+                ..request.clone()
+            },
+            BytecodeCompileRequest {
+                output_mode: CompileMode::PycUncheckedHash,
+                ..request.clone()
+            },
+        ] {
+            assert_ne!(BytecodeCache::key("python", other), key);
+        }
+        assert_ne!(BytecodeCache::key("other-python", &request), key);
+
+        let (hits, misses) = bytecode_cache_lookups();
+
+        assert_eq!(cache.get(&key)?, None);
+        cache.put(&key, b"bytecode")?;
+        assert_eq!(cache.get(&key)?, Some(b"bytecode".to_vec()));
+
+        // Other tests may look up caches concurrently.
+        let (new_hits, new_misses) = bytecode_cache_lookups();
+        assert!(new_hits > hits);
+        assert!(new_misses > misses);
+
+        Ok(())
+    }
 }
@@ -21,74 +21,86 @@ RE_CODING = re.compile(b"^[ \t\f]*#.*?coding[:=][ \t]*([-_.a-zA-Z0-9]+)")
 if marshal.version != 4:
     raise Exception("unexpected marshal version: %d" % marshal.version)
 
-stdin = sys.__stdin__.buffer
-stdout = sys.__stdout__.buffer
+def compile_source(name, source, optimize_level, output_mode):
+    """Compile source bytes to bytecode, possibly with a .pyc header."""
+    # Default source encoding is UTF-8. But per PEP 263, the first or second
+    # line of source can match a regular expression to define a custom
+    # encoding. We need to detect custom encodings and use it to decode
+    # the passed bytes to str.
+    encoding = "utf-8"
+
+    for line in source.splitlines()[0:2]:
+        m = RE_CODING.match(line)
+        if m:
+            encoding = m.group(1).decode("ascii")
+            break
+
+    # Someone has set us up the BOM! According to PEP 263 the file should
+    # be interpreted as UTF-8.
+    if source.startswith(b"\xef\xbb\xbf"):
+        encoding = "utf-8"
+        source = source[3:]
+
+    source_bytes = source
+    source = source.decode(encoding)
+
+    code = compile(source, name, "exec", optimize=optimize_level)
+    bytecode = marshal.dumps(code)
+
+    if output_mode == b"bytecode":
+        return bytecode
+    elif output_mode == b"pyc-checked-hash":
+        source_hash = importlib.util.source_hash(source_bytes)
+        return importlib._bootstrap_external._code_to_hash_pyc(
+            code, source_hash, checked=True,
+        )
+    elif output_mode == b"pyc-unchecked-hash":
+        source_hash = importlib.util.source_hash(source_bytes)
+        return importlib._bootstrap_external._code_to_hash_pyc(
+            code, source_hash, checked=False,
+        )
+    else:
+        raise Exception("unknown output mode: %s" % output_mode)
 
 
-while True:
-    command = stdin.readline().rstrip()
+def serve():
+    """Serve commands received on stdin."""
+    stdin = sys.__stdin__.buffer
+    stdout = sys.__stdout__.buffer
 
-    if command == b"exit":
-        sys.exit(0)
-    elif command == b"magic_number":
-        stdout.write(importlib._bootstrap_external.MAGIC_NUMBER)
-        stdout.flush()
-    elif command == b"compile":
-        name_len = stdin.readline().rstrip()
-        source_len = stdin.readline().rstrip()
-        optimize_level = stdin.readline().rstrip()
-        output_mode = stdin.readline().rstrip()
+    while True:
+        command = stdin.readline().rstrip()
 
-        name_len = int(name_len)
-        source_len = int(source_len)
-        optimize_level = int(optimize_level)
+        if command == b"exit":
+            sys.exit(0)
+        elif command == b"magic_number":
+            stdout.write(importlib._bootstrap_external.MAGIC_NUMBER)
+            stdout.flush()
+        elif command == b"compile":
+            name_len = stdin.readline().rstrip()
+            source_len = stdin.readline().rstrip()
+            optimize_level = stdin.readline().rstrip()
+            output_mode = stdin.readline().rstrip()
 
-        name = stdin.read(name_len)
-        source = stdin.read(source_len)
+            name_len = int(name_len)
+            source_len = int(source_len)
+            optimize_level = int(optimize_level)
 
-        name = os.fsdecode(name)
+            name = stdin.read(name_len)
+            source = stdin.read(source_len)
 
-        # Default source encoding is UTF-8. But per PEP 263, the first or second
-        # line of source can match a regular expression to define a custom
-        # encoding. We need to detect custom encodings and use it to decode
-        # the passed bytes to str.
-        encoding = "utf-8"
+            name = os.fsdecode(name)
 
-        for line in source.splitlines()[0:2]:
-            m = RE_CODING.match(line)
-            if m:
-                encoding = m.group(1).decode("ascii")
-                break
-
-        # Someone has set us up the BOM! According to PEP 263 the file should
-        # be interpreted as UTF-8.
-        if source.startswith(b"\xef\xbb\xbf"):
-            encoding = "utf-8"
-            source = source[3:]
-
-        source_bytes = source
-        source = source.decode(encoding)
-
-        code = compile(source, name, "exec", optimize=optimize_level)
-        bytecode = marshal.dumps(code)
-
-        if output_mode == b"bytecode":
-            out = bytecode
-        elif output_mode == b"pyc-checked-hash":
-            source_hash = importlib.util.source_hash(source_bytes)
-            out = importlib._bootstrap_external._code_to_hash_pyc(
-                code, source_hash, checked=True,
-            )
-        elif output_mode == b"pyc-unchecked-hash":
-            source_hash = importlib.util.source_hash(source_bytes)
-            out = importlib._bootstrap_external._code_to_hash_pyc(
-                code, source_hash, checked=False,
-            )
+            out = compile_source(name, source, optimize_level, output_mode)
+
+            stdout.write(b"%d\n" % len(out))
+            stdout.write(out)
+            stdout.flush()
         else:
-            raise Exception("unknown output mode: %s" % output_mode)
+            raise Exception("invalid command: %s" % command)
 
-        stdout.write(b"%d\n" % len(out))
-        stdout.write(out)
-        stdout.flush()
-    else:
-        raise Exception("invalid command: %s" % command)
+
+# The script is also loaded as a module by the in-process compiler, which
+# only calls compile_source().
+if __name__ == "__main__":
+    serve()
@@ -0,0 +1,112 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Detection of paths differing only by case.
+
+Some packages contain files whose names differ only by case, e.g.
+`Foo.py` and `foo.py`. Filesystems are case-insensitive by default on
+Windows and macOS, so such files overwrite each other when written there,
+silently breaking whichever file was written first. Paths of files
+installed relative to binaries are checked, so builds fail on all
+platforms instead.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    std::collections::BTreeMap,
+    std::path::{Path, PathBuf},
+};
+
+/// Records paths and detects those colliding on case-insensitive filesystems.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CaseCollisionDetector {
+    /// Recorded paths and their parent directories, keyed by lowercase form.
+    ///
+    /// Values are the path and the first recorded file path it belongs to.
+    paths: BTreeMap<String, (PathBuf, PathBuf)>,
+}
+
+impl CaseCollisionDetector {
+    /// Record a path, failing if it collides with a recorded path.
+    ///
+    /// Parent directories collide as well: `Foo/a.py` and `foo/b.py` would
+    /// be written in the same directory.
+    pub fn add(&mut self, path: &Path) -> Result<()> {
+        let mut prefixes = path
+            .ancestors()
+            .filter(|prefix| !prefix.as_os_str().is_empty())
+            .collect::<Vec<_>>();
+        prefixes.reverse();
+
+        for current in prefixes {
+            let key = current
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            match self.paths.get(&key) {
+                Some((existing, _)) if existing == current => {}
+                Some((existing, existing_file)) => {
+                    return Err(if existing_file == existing && current == path {
+                        anyhow!(
+                            "{} and {} differ only by case and would overwrite each other on case-insensitive filesystems",
+                            existing.display(),
+                            current.display()
+                        )
+                    } else {
+                        anyhow!(
+                            "{} and {} would collide on case-insensitive filesystems, as {} and {} differ only by case",
+                            existing_file.display(),
+                            path.display(),
+                            existing.display(),
+                            current.display()
+                        )
+                    });
+                }
+                None => {
+                    self.paths
+                        .insert(key, (current.to_path_buf(), path.to_path_buf()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_collisions() -> Result<()> {
+        let mut detector = CaseCollisionDetector::default();
+
+        detector.add(Path::new("lib/foo/__init__.py"))?;
+        detector.add(Path::new("lib/foo/bar.py"))?;
+        detector.add(Path::new("lib/foo/bar.py"))?;
+        detector.add(Path::new("lib/foo/bar2.py"))?;
+
+        let err = detector.add(Path::new("lib/foo/Bar.py")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "lib/foo/bar.py and lib/foo/Bar.py differ only by case and would overwrite each other on case-insensitive filesystems"
+        );
+
+        let err = detector.add(Path::new("lib/Foo/baz.py")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "lib/foo/__init__.py and lib/Foo/baz.py would collide on case-insensitive filesystems, as lib/foo and lib/Foo differ only by case"
+        );
+
+        let err = detector.add(Path::new("lib/foo/BAR2.py/x")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "lib/foo/bar2.py and lib/foo/BAR2.py/x would collide on case-insensitive filesystems, as lib/foo/bar2.py and lib/foo/BAR2.py differ only by case"
+        );
+
+        Ok(())
+    }
+}
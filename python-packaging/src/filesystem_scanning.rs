@@ -7,6 +7,7 @@ Scanning the filesystem for Python resources.
 */
 
 use {
+    crate::long_path::extended_length_path,
     crate::module_util::{is_package_from_path, PythonModuleSuffixes},
     crate::package_metadata::PythonPackageMetadata,
     crate::resource::{
@@ -16,7 +17,7 @@ use {
         PythonResource,
     },
     anyhow::Result,
-    std::collections::HashSet,
+    std::collections::{BTreeMap, HashSet},
     std::ffi::OsStr,
     std::path::{Path, PathBuf},
 };
@@ -56,6 +57,8 @@ enum DirEntryItem {
 
 pub struct PythonResourceIterator {
     root_path: PathBuf,
+    /// Extended-length form of `root_path` directories are walked from.
+    walk_root: PathBuf,
     cache_tag: String,
     suffixes: PythonModuleSuffixes,
     walkdir_result: Box<dyn Iterator<Item = walkdir::DirEntry>>,
@@ -69,7 +72,11 @@ impl PythonResourceIterator {
         cache_tag: &str,
         suffixes: &PythonModuleSuffixes,
     ) -> PythonResourceIterator {
-        let res = walkdir::WalkDir::new(path).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        // Package data can be nested deeper than Windows supports with
+        // regular paths.
+        let walk_root = extended_length_path(path);
+        let res =
+            walkdir::WalkDir::new(&walk_root).sort_by(|a, b| a.file_name().cmp(b.file_name()));
 
         let filtered = res.into_iter().filter_map(|entry| {
             let entry = entry.expect("unable to get directory entry");
@@ -85,6 +92,7 @@ impl PythonResourceIterator {
 
         PythonResourceIterator {
             root_path: path.to_path_buf(),
+            walk_root,
             cache_tag: cache_tag.to_string(),
             suffixes: suffixes.clone(),
             walkdir_result: Box::new(filtered),
@@ -94,7 +102,15 @@ impl PythonResourceIterator {
     }
 
     fn resolve_dir_entry(&mut self, entry: walkdir::DirEntry) -> Option<DirEntryItem> {
-        let path = entry.path();
+        // Resources refer to paths in their usual form. They are converted
+        // to extended-length paths when read.
+        let full_path = self.root_path.join(
+            entry
+                .path()
+                .strip_prefix(&self.walk_root)
+                .expect("unable to strip path prefix"),
+        );
+        let path = full_path.as_path();
 
         let mut rel_path = path
             .strip_prefix(&self.root_path)
@@ -127,7 +143,7 @@ impl PythonResourceIterator {
         };
 
         if let Some((metadata_path, location)) = distribution_info {
-            let metadata = if let Ok(data) = std::fs::read(&metadata_path) {
+            let metadata = if let Ok(data) = std::fs::read(extended_length_path(&metadata_path)) {
                 if let Ok(metadata) = PythonPackageMetadata::from_metadata(&data) {
                     metadata
                 } else {
@@ -245,6 +261,7 @@ impl PythonResourceIterator {
                         is_package: is_package_from_path(path),
                         libraries: vec![],
                         library_dirs: vec![],
+                        shared_libraries: BTreeMap::new(),
                     }),
                 ));
             }
@@ -968,6 +985,7 @@ mod tests {
                 is_package: false,
                 libraries: vec![],
                 library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
             })
         );
         assert_eq!(
@@ -981,6 +999,7 @@ mod tests {
                 is_package: false,
                 libraries: vec![],
                 library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
             }),
         );
         assert_eq!(
@@ -994,6 +1013,7 @@ mod tests {
                 is_package: false,
                 libraries: vec![],
                 library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
             }),
         );
         assert_eq!(
@@ -1007,6 +1027,7 @@ mod tests {
                 is_package: false,
                 libraries: vec![],
                 library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
             }),
         );
         assert_eq!(
@@ -1020,6 +1041,7 @@ mod tests {
                 is_package: false,
                 libraries: vec![],
                 library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
             }),
         );
 
@@ -9,7 +9,10 @@ and packaging facilities.
 */
 
 pub mod bytecode;
+pub mod case_collision;
 pub mod filesystem_scanning;
+pub mod libpython_compiler;
+pub mod long_path;
 pub mod module_util;
 pub mod package_metadata;
 pub mod python_source;
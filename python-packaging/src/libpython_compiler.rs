@@ -0,0 +1,408 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Compile Python bytecode with a libpython loaded in this process.
+
+`BytecodeCompiler` runs a Python process, which may be unavailable or
+restricted in sandboxed build environments. `LibpythonCompiler` instead
+loads a shared libpython into the current process and calls the
+interpreter through the Python C API. Source is compiled by the same
+Python code as `BytecodeCompiler`, so both produce identical output.
+
+A process can only hold a single Python interpreter. The first libpython
+loaded is kept for the lifetime of the process and attempts to load
+another one fail. Compilation is serialized by the interpreter lock.
+*/
+
+use {
+    super::bytecode::{CompileMode, BYTECODE_COMPILER},
+    super::resource::BytecodeOptimizationLevel,
+    anyhow::{anyhow, Result},
+    lazy_static::lazy_static,
+    libloading::Library,
+    std::ffi::{CStr, CString},
+    std::os::raw::{c_char, c_int, c_long, c_void},
+    std::path::{Path, PathBuf},
+    std::sync::Mutex,
+};
+
+type PyObject = c_void;
+
+/// `Py_file_input` start symbol of the Python grammar.
+const PY_FILE_INPUT: c_int = 257;
+
+/// Functions of the Python C API used to compile bytecode.
+struct PythonApi {
+    gil_state_ensure: unsafe extern "C" fn() -> c_int,
+    gil_state_release: unsafe extern "C" fn(c_int),
+    bytes_from_string_and_size: unsafe extern "C" fn(*const c_char, isize) -> *mut PyObject,
+    bytes_as_string_and_size:
+        unsafe extern "C" fn(*mut PyObject, *mut *mut c_char, *mut isize) -> c_int,
+    unicode_from_string: unsafe extern "C" fn(*const c_char) -> *mut PyObject,
+    unicode_as_utf8: unsafe extern "C" fn(*mut PyObject) -> *const c_char,
+    long_from_long: unsafe extern "C" fn(c_long) -> *mut PyObject,
+    tuple_new: unsafe extern "C" fn(isize) -> *mut PyObject,
+    tuple_set_item: unsafe extern "C" fn(*mut PyObject, isize, *mut PyObject) -> c_int,
+    object_call_object: unsafe extern "C" fn(*mut PyObject, *mut PyObject) -> *mut PyObject,
+    object_str: unsafe extern "C" fn(*mut PyObject) -> *mut PyObject,
+    err_fetch: unsafe extern "C" fn(*mut *mut PyObject, *mut *mut PyObject, *mut *mut PyObject),
+    err_normalize_exception:
+        unsafe extern "C" fn(*mut *mut PyObject, *mut *mut PyObject, *mut *mut PyObject),
+    dec_ref: unsafe extern "C" fn(*mut PyObject),
+}
+
+/// Resolve a symbol of a library.
+///
+/// `T` is a function pointer type for functions and a pointer type for data.
+unsafe fn symbol<T: Copy>(library: &Library, name: &str) -> Result<T> {
+    let c_name = CString::new(name)?;
+
+    let symbol = library
+        .get::<T>(c_name.as_bytes_with_nul())
+        .or_else(|e| Err(anyhow!("unable to resolve {}: {}", name, e)))?;
+
+    Ok(*symbol)
+}
+
+impl PythonApi {
+    unsafe fn new(library: &Library) -> Result<Self> {
+        Ok(PythonApi {
+            gil_state_ensure: symbol(library, "PyGILState_Ensure")?,
+            gil_state_release: symbol(library, "PyGILState_Release")?,
+            bytes_from_string_and_size: symbol(library, "PyBytes_FromStringAndSize")?,
+            bytes_as_string_and_size: symbol(library, "PyBytes_AsStringAndSize")?,
+            unicode_from_string: symbol(library, "PyUnicode_FromString")?,
+            unicode_as_utf8: symbol(library, "PyUnicode_AsUTF8")?,
+            long_from_long: symbol(library, "PyLong_FromLong")?,
+            tuple_new: symbol(library, "PyTuple_New")?,
+            tuple_set_item: symbol(library, "PyTuple_SetItem")?,
+            object_call_object: symbol(library, "PyObject_CallObject")?,
+            object_str: symbol(library, "PyObject_Str")?,
+            err_fetch: symbol(library, "PyErr_Fetch")?,
+            err_normalize_exception: symbol(library, "PyErr_NormalizeException")?,
+            dec_ref: symbol(library, "Py_DecRef")?,
+        })
+    }
+
+    /// Obtain the message of the raised Python exception and clear it.
+    ///
+    /// The GIL must be held.
+    unsafe fn take_error(&self) -> anyhow::Error {
+        let mut ptype = std::ptr::null_mut();
+        let mut pvalue = std::ptr::null_mut();
+        let mut ptraceback = std::ptr::null_mut();
+
+        (self.err_fetch)(&mut ptype, &mut pvalue, &mut ptraceback);
+        (self.err_normalize_exception)(&mut ptype, &mut pvalue, &mut ptraceback);
+
+        let mut message = "unknown Python error".to_string();
+
+        if !pvalue.is_null() {
+            let s = (self.object_str)(pvalue);
+            if !s.is_null() {
+                let data = (self.unicode_as_utf8)(s);
+                if !data.is_null() {
+                    message = CStr::from_ptr(data).to_string_lossy().to_string();
+                }
+                (self.dec_ref)(s);
+            }
+        }
+
+        (self.dec_ref)(ptype);
+        (self.dec_ref)(pvalue);
+        (self.dec_ref)(ptraceback);
+
+        anyhow!("{}", message)
+    }
+}
+
+/// The Python interpreter of the libpython loaded in this process.
+struct LoadedInterpreter {
+    libpython: PathBuf,
+    // Never unloaded: the interpreter can't be finalized and initialized again.
+    _library: Library,
+    api: PythonApi,
+    /// Address of the `compile_source()` function of the compiler script.
+    compile_source: usize,
+    magic_number: u32,
+}
+
+impl LoadedInterpreter {
+    /// Load a libpython and initialize its interpreter.
+    ///
+    /// The interpreter imports modules from `stdlib_path` only.
+    fn load(libpython: &Path, stdlib_path: &Path) -> Result<Self> {
+        let library = Library::new(libpython)
+            .or_else(|e| Err(anyhow!("unable to load {}: {}", libpython.display(), e)))?;
+
+        unsafe {
+            let api = PythonApi::new(&library)?;
+
+            let is_initialized: unsafe extern "C" fn() -> c_int =
+                symbol(&library, "Py_IsInitialized")?;
+            if is_initialized() != 0 {
+                return Err(anyhow!(
+                    "Python interpreter of {} is already initialized",
+                    libpython.display()
+                ));
+            }
+
+            // Ignore site-packages, PYTHON* environment variables and don't
+            // write .pyc files into the distribution.
+            for flag in &[
+                "Py_NoSiteFlag",
+                "Py_IgnoreEnvironmentFlag",
+                "Py_DontWriteBytecodeFlag",
+            ] {
+                *symbol::<*mut c_int>(&library, flag)? = 1;
+            }
+
+            let decode_locale: unsafe extern "C" fn(*const c_char, *mut usize) -> *mut c_void =
+                symbol(&library, "Py_DecodeLocale")?;
+            let raw_free: unsafe extern "C" fn(*mut c_void) = symbol(&library, "PyMem_RawFree")?;
+            let set_path: unsafe extern "C" fn(*const c_void) = symbol(&library, "Py_SetPath")?;
+
+            let path = CString::new(stdlib_path.to_string_lossy().as_bytes())?;
+            let wide_path = decode_locale(path.as_ptr(), std::ptr::null_mut());
+            if wide_path.is_null() {
+                return Err(anyhow!("unable to decode {}", stdlib_path.display()));
+            }
+            set_path(wide_path);
+            raw_free(wide_path);
+
+            let initialize: unsafe extern "C" fn(c_int) = symbol(&library, "Py_InitializeEx")?;
+            initialize(0);
+
+            // The interpreter lock is held from here, until released below.
+            let res = Self::load_compiler(&library, &api);
+
+            let save_thread: unsafe extern "C" fn() -> *mut c_void =
+                symbol(&library, "PyEval_SaveThread")?;
+            save_thread();
+
+            let (compile_source, magic_number) = res?;
+
+            Ok(LoadedInterpreter {
+                libpython: libpython.to_path_buf(),
+                _library: library,
+                api,
+                compile_source: compile_source as usize,
+                magic_number,
+            })
+        }
+    }
+
+    /// Run the compiler script and obtain its `compile_source()` function.
+    ///
+    /// The GIL must be held.
+    unsafe fn load_compiler(library: &Library, api: &PythonApi) -> Result<(*mut PyObject, u32)> {
+        let dict_new: unsafe extern "C" fn() -> *mut PyObject = symbol(library, "PyDict_New")?;
+        let dict_set_item_string: unsafe extern "C" fn(
+            *mut PyObject,
+            *const c_char,
+            *mut PyObject,
+        ) -> c_int = symbol(library, "PyDict_SetItemString")?;
+        let dict_get_item_string: unsafe extern "C" fn(
+            *mut PyObject,
+            *const c_char,
+        ) -> *mut PyObject = symbol(library, "PyDict_GetItemString")?;
+        let import_module: unsafe extern "C" fn(*const c_char) -> *mut PyObject =
+            symbol(library, "PyImport_ImportModule")?;
+        let run_string: unsafe extern "C" fn(
+            *const c_char,
+            c_int,
+            *mut PyObject,
+            *mut PyObject,
+            *mut c_void,
+        ) -> *mut PyObject = symbol(library, "PyRun_StringFlags")?;
+        let get_magic_number: unsafe extern "C" fn() -> c_long =
+            symbol(library, "PyImport_GetMagicNumber")?;
+
+        // The globals of the script are never released, which keeps
+        // compile_source() alive.
+        let globals = dict_new();
+        if globals.is_null() {
+            return Err(api.take_error());
+        }
+
+        let builtins = import_module(b"builtins\0".as_ptr() as *const c_char);
+        if builtins.is_null() {
+            return Err(api.take_error());
+        }
+        dict_set_item_string(
+            globals,
+            b"__builtins__\0".as_ptr() as *const c_char,
+            builtins,
+        );
+        (api.dec_ref)(builtins);
+
+        // Any name but __main__, so the script doesn't serve stdin.
+        let name = (api.unicode_from_string)(b"bytecodecompiler\0".as_ptr() as *const c_char);
+        dict_set_item_string(globals, b"__name__\0".as_ptr() as *const c_char, name);
+        (api.dec_ref)(name);
+
+        let script = CString::new(BYTECODE_COMPILER)?;
+        let res = run_string(
+            script.as_ptr(),
+            PY_FILE_INPUT,
+            globals,
+            globals,
+            std::ptr::null_mut(),
+        );
+        if res.is_null() {
+            return Err(api.take_error());
+        }
+        (api.dec_ref)(res);
+
+        let compile_source =
+            dict_get_item_string(globals, b"compile_source\0".as_ptr() as *const c_char);
+        if compile_source.is_null() {
+            return Err(anyhow!("compiler script does not define compile_source()"));
+        }
+
+        Ok((compile_source, get_magic_number() as u32))
+    }
+
+    /// Call `compile_source()` of the compiler script.
+    ///
+    /// The GIL must be held.
+    unsafe fn compile(
+        &self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let api = &self.api;
+
+        let filename = CString::new(filename)?;
+        let output_mode: &[u8] = match output_mode {
+            CompileMode::Bytecode => b"bytecode",
+            CompileMode::PycCheckedHash => b"pyc-checked-hash",
+            CompileMode::PycUncheckedHash => b"pyc-unchecked-hash",
+        };
+
+        let args = (api.tuple_new)(4);
+        if args.is_null() {
+            return Err(api.take_error());
+        }
+
+        // PyTuple_SetItem() steals references, including on failure.
+        let items = [
+            (api.unicode_from_string)(filename.as_ptr()),
+            (api.bytes_from_string_and_size)(
+                source.as_ptr() as *const c_char,
+                source.len() as isize,
+            ),
+            (api.long_from_long)(i32::from(optimize) as c_long),
+            (api.bytes_from_string_and_size)(
+                output_mode.as_ptr() as *const c_char,
+                output_mode.len() as isize,
+            ),
+        ];
+        let mut failed = false;
+        for (i, item) in items.iter().enumerate() {
+            if item.is_null() {
+                failed = true;
+            } else {
+                (api.tuple_set_item)(args, i as isize, *item);
+            }
+        }
+        if failed {
+            (api.dec_ref)(args);
+            return Err(api.take_error());
+        }
+
+        let res = (api.object_call_object)(self.compile_source as *mut PyObject, args);
+        (api.dec_ref)(args);
+        if res.is_null() {
+            return Err(anyhow!(
+                "error compiling {}: {}",
+                filename.to_string_lossy(),
+                api.take_error()
+            ));
+        }
+
+        let mut data = std::ptr::null_mut();
+        let mut size = 0;
+        if (api.bytes_as_string_and_size)(res, &mut data, &mut size) != 0 {
+            (api.dec_ref)(res);
+            return Err(api.take_error());
+        }
+
+        let bytecode = std::slice::from_raw_parts(data as *const u8, size as usize).to_vec();
+        (api.dec_ref)(res);
+
+        Ok(bytecode)
+    }
+}
+
+lazy_static! {
+    static ref INTERPRETER: Mutex<Option<LoadedInterpreter>> = Mutex::new(None);
+}
+
+/// Compiles Python bytecode with a libpython loaded in this process.
+#[derive(Clone, Debug)]
+pub struct LibpythonCompiler {
+    libpython: PathBuf,
+
+    /// Magic number for bytecode header.
+    pub magic_number: u32,
+}
+
+impl LibpythonCompiler {
+    /// Create a compiler using a shared libpython.
+    ///
+    /// `stdlib_path` is the directory of the standard library of the Python
+    /// distribution providing `libpython`. The library is loaded and its
+    /// interpreter initialized by the first call. Later calls must refer to
+    /// the same library.
+    pub fn new(libpython: &Path, stdlib_path: &Path) -> Result<LibpythonCompiler> {
+        let mut interpreter = INTERPRETER
+            .lock()
+            .or_else(|_| Err(anyhow!("in-process Python interpreter lock poisoned")))?;
+
+        if interpreter.is_none() {
+            *interpreter = Some(LoadedInterpreter::load(libpython, stdlib_path)?);
+        }
+
+        let loaded = interpreter.as_ref().unwrap();
+        if loaded.libpython != libpython {
+            return Err(anyhow!(
+                "cannot compile bytecode in process with {}: {} is already loaded",
+                libpython.display(),
+                loaded.libpython.display()
+            ));
+        }
+
+        Ok(LibpythonCompiler {
+            libpython: libpython.to_path_buf(),
+            magic_number: loaded.magic_number,
+        })
+    }
+
+    /// Compile Python source into bytecode with an optimization level.
+    pub fn compile(
+        &self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let interpreter = INTERPRETER
+            .lock()
+            .or_else(|_| Err(anyhow!("in-process Python interpreter lock poisoned")))?;
+        let loaded = interpreter
+            .as_ref()
+            .ok_or_else(|| anyhow!("in-process Python interpreter not loaded"))?;
+
+        unsafe {
+            let gil = (loaded.api.gil_state_ensure)();
+            let res = loaded.compile(source, filename, optimize, output_mode);
+            (loaded.api.gil_state_release)(gil);
+
+            res
+        }
+    }
+}
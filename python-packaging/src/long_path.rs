@@ -0,0 +1,116 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Accessing paths longer than `MAX_PATH` on Windows.
+
+Windows APIs reject paths longer than 260 characters (`MAX_PATH`) unless
+they are extended-length paths, prefixed with `\\?\`. Deeply nested package
+data easily exceeds this limit.
+
+Extended-length paths aren't normalized by Windows: they must be absolute
+and only use `\` as separator. Paths are converted right before filesystem
+operations, so paths displayed to users keep their usual form.
+*/
+
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// Obtain the extended-length form of a path on Windows.
+///
+/// Relative paths are resolved against the current directory and `.` and
+/// `..` components are normalized. Paths are returned as is on other
+/// platforms, or if they already are extended-length paths.
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else if let Ok(cwd) = std::env::current_dir() {
+        cwd.join(path)
+    } else {
+        return path.to_path_buf();
+    };
+
+    let mut prefix = None;
+    let mut parts: Vec<&std::ffi::OsStr> = Vec::new();
+
+    for component in absolute.components() {
+        match component {
+            Component::Prefix(p) => match p.kind() {
+                Prefix::Disk(_) => prefix = Some(p.as_os_str().to_os_string()),
+                Prefix::UNC(server, share) => {
+                    let mut value = std::ffi::OsString::from("UNC\\");
+                    value.push(server);
+                    value.push("\\");
+                    value.push(share);
+                    prefix = Some(value);
+                }
+                // Verbatim and device paths are used as is.
+                _ => return absolute,
+            },
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part),
+        }
+    }
+
+    let prefix = match prefix {
+        Some(prefix) => prefix,
+        // e.g. `\foo`, relative to the current drive.
+        None => return absolute,
+    };
+
+    let mut res = std::ffi::OsString::from(r"\\?\");
+    res.push(prefix);
+    for part in parts {
+        res.push("\\");
+        res.push(part);
+    }
+    if res.len() == r"\\?\C:".len() {
+        res.push("\\");
+    }
+
+    PathBuf::from(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_extended_length_path() {
+        assert_eq!(
+            extended_length_path(Path::new("/usr/lib/../lib/python3.8")),
+            PathBuf::from("/usr/lib/../lib/python3.8")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_extended_length_path() {
+        assert_eq!(
+            extended_length_path(Path::new(r"C:\Python\Lib\..\Lib/./site-packages")),
+            PathBuf::from(r"\\?\C:\Python\Lib\site-packages")
+        );
+        assert_eq!(
+            extended_length_path(Path::new(r"\\server\share\site-packages")),
+            PathBuf::from(r"\\?\UNC\server\share\site-packages")
+        );
+        assert_eq!(
+            extended_length_path(Path::new(r"\\?\C:\Python")),
+            PathBuf::from(r"\\?\C:\Python")
+        );
+        assert_eq!(
+            extended_length_path(Path::new(r"C:\")),
+            PathBuf::from(r"\\?\C:\")
+        );
+        assert!(extended_length_path(Path::new("site-packages"))
+            .to_string_lossy()
+            .starts_with(r"\\?\"));
+    }
+}
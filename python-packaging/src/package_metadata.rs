@@ -5,7 +5,7 @@
 /*! Working with Python package metadata (i.e. .pkg-info directories) */
 
 use {
-    anyhow::{Context, Result},
+    anyhow::{anyhow, Context, Result},
     mailparse::parse_mail,
 };
 
@@ -62,6 +62,221 @@ impl PythonPackageMetadata {
     }
 }
 
+/// An entry in a `RECORD` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PythonPackageRecordEntry {
+    /// Path of the installed file, relative to the installation root.
+    pub path: String,
+
+    /// Hash of the file content, in `<algorithm>=<urlsafe-base64 digest>` form.
+    ///
+    /// Not present for the `RECORD` file itself and for files like `.pyc`
+    /// files that are generated at install time.
+    pub hash: Option<String>,
+
+    /// Size of the file in bytes.
+    pub size: Option<u64>,
+}
+
+/// Represents a Python RECORD file.
+///
+/// A `RECORD` file is a CSV file in a `.dist-info` directory listing every
+/// file installed by a package along with its hash and size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PythonPackageRecord {
+    pub entries: Vec<PythonPackageRecordEntry>,
+}
+
+impl PythonPackageRecord {
+    /// Create an instance from data in a RECORD file.
+    pub fn from_record(data: &[u8]) -> Result<PythonPackageRecord> {
+        let data = std::str::from_utf8(data).context("decoding RECORD file")?;
+
+        let mut entries = Vec::new();
+
+        for (i, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields =
+                split_csv_line(line).with_context(|| format!("parsing RECORD line {}", i + 1))?;
+
+            if fields.len() != 3 {
+                return Err(anyhow!(
+                    "RECORD line {} has {} fields; expected 3",
+                    i + 1,
+                    fields.len()
+                ));
+            }
+
+            entries.push(PythonPackageRecordEntry {
+                path: fields[0].clone(),
+                hash: if fields[1].is_empty() {
+                    None
+                } else {
+                    Some(fields[1].clone())
+                },
+                size: if fields[2].is_empty() {
+                    None
+                } else {
+                    Some(
+                        fields[2]
+                            .parse::<u64>()
+                            .with_context(|| format!("parsing size on RECORD line {}", i + 1))?,
+                    )
+                },
+            });
+        }
+
+        Ok(PythonPackageRecord { entries })
+    }
+
+    /// Serialize to the content of a RECORD file.
+    pub fn to_record(&self) -> Vec<u8> {
+        let mut data = String::new();
+
+        for entry in &self.entries {
+            data.push_str(&quote_csv_field(&entry.path));
+            data.push(',');
+            if let Some(hash) = &entry.hash {
+                data.push_str(&quote_csv_field(hash));
+            }
+            data.push(',');
+            if let Some(size) = entry.size {
+                data.push_str(&size.to_string());
+            }
+            data.push('\n');
+        }
+
+        data.into_bytes()
+    }
+}
+
+/// An entry point declared in an `entry_points.txt` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PythonPackageEntryPoint {
+    /// Group of the entry point, e.g. `console_scripts`.
+    pub group: String,
+
+    /// Name of the entry point within its group.
+    pub name: String,
+
+    /// Module defining the entry point.
+    pub module: String,
+
+    /// Attribute path of the object in the module, e.g. `cli.main`.
+    ///
+    /// If not present, the entry point refers to the module.
+    pub attr: Option<String>,
+}
+
+impl PythonPackageEntryPoint {
+    /// Parse entry points from data in an `entry_points.txt` file.
+    ///
+    /// The file is an INI file whose sections are entry point groups and
+    /// whose values are object references like `module:attr [extras]`.
+    pub fn from_entry_points(data: &[u8]) -> Result<Vec<PythonPackageEntryPoint>> {
+        let data = std::str::from_utf8(data).context("decoding entry_points.txt file")?;
+
+        let mut res = Vec::new();
+        let mut group = None;
+
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                group = Some(line[1..line.len() - 1].trim().to_string());
+                continue;
+            }
+
+            let group = group
+                .as_ref()
+                .ok_or_else(|| anyhow!("entry_points.txt line {} is outside a section", i + 1))?;
+
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) if !name.trim().is_empty() => (name.trim(), value),
+                _ => return Err(anyhow!("entry_points.txt line {} is invalid", i + 1)),
+            };
+
+            // Extras don't matter once packages are installed.
+            let value = value.split('[').next().unwrap_or_default().trim();
+
+            let mut parts = value.splitn(2, ':');
+            let module = parts.next().unwrap_or_default().trim();
+            let attr = parts.next().map(|attr| attr.trim().to_string());
+
+            if module.is_empty() || attr.as_ref().map(|attr| attr.is_empty()) == Some(true) {
+                return Err(anyhow!(
+                    "entry_points.txt line {} has an invalid object reference: {}",
+                    i + 1,
+                    value
+                ));
+            }
+
+            res.push(PythonPackageEntryPoint {
+                group: group.clone(),
+                name: name.to_string(),
+                module: module.to_string(),
+                attr,
+            });
+        }
+
+        Ok(res)
+    }
+}
+
+/// Split a line of CSV into fields, honoring double quoted fields.
+fn split_csv_line(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut in_quotes = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => {
+                in_quotes = true;
+            }
+            ',' if !in_quotes => {
+                fields.push(field.clone());
+                field.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(anyhow!("unterminated quoted field"));
+    }
+
+    fields.push(field);
+
+    Ok(fields)
+}
+
+/// Quote a CSV field if necessary.
+fn quote_csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,4 +314,94 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_record() -> Result<()> {
+        let data = concat!(
+            "black.py,sha256=xIFQlY0y6pIA7UKIUkfxasw4UKfymfPIbH2PNmYjMLQ,141243\n",
+            "\"dir,with,commas/foo.py\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\n",
+            "black-19.10b0.dist-info/RECORD,,\n",
+            "__pycache__/black.cpython-37.pyc,,\n",
+        )
+        .as_bytes();
+
+        let record = PythonPackageRecord::from_record(data)?;
+
+        assert_eq!(
+            record.entries,
+            vec![
+                PythonPackageRecordEntry {
+                    path: "black.py".to_string(),
+                    hash: Some("sha256=xIFQlY0y6pIA7UKIUkfxasw4UKfymfPIbH2PNmYjMLQ".to_string()),
+                    size: Some(141243),
+                },
+                PythonPackageRecordEntry {
+                    path: "dir,with,commas/foo.py".to_string(),
+                    hash: Some("sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU".to_string()),
+                    size: Some(0),
+                },
+                PythonPackageRecordEntry {
+                    path: "black-19.10b0.dist-info/RECORD".to_string(),
+                    hash: None,
+                    size: None,
+                },
+                PythonPackageRecordEntry {
+                    path: "__pycache__/black.cpython-37.pyc".to_string(),
+                    hash: None,
+                    size: None,
+                },
+            ]
+        );
+
+        assert_eq!(record.to_record(), data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_record_invalid() {
+        assert!(PythonPackageRecord::from_record(b"foo.py,sha256=abc\n").is_err());
+        assert!(PythonPackageRecord::from_record(b"foo.py,sha256=abc,big\n").is_err());
+        assert!(PythonPackageRecord::from_record(b"\"foo.py,,\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_entry_points() -> Result<()> {
+        let entry_points = PythonPackageEntryPoint::from_entry_points(
+            b"[console_scripts]\nmycli = mypkg.cli:main\n\n# comment\n[gui_scripts]\nmygui=mypkg.gui:App.run [qt]\nmod = mypkg.tool\n",
+        )?;
+
+        assert_eq!(
+            entry_points,
+            vec![
+                PythonPackageEntryPoint {
+                    group: "console_scripts".to_string(),
+                    name: "mycli".to_string(),
+                    module: "mypkg.cli".to_string(),
+                    attr: Some("main".to_string()),
+                },
+                PythonPackageEntryPoint {
+                    group: "gui_scripts".to_string(),
+                    name: "mygui".to_string(),
+                    module: "mypkg.gui".to_string(),
+                    attr: Some("App.run".to_string()),
+                },
+                PythonPackageEntryPoint {
+                    group: "gui_scripts".to_string(),
+                    name: "mod".to_string(),
+                    module: "mypkg.tool".to_string(),
+                    attr: None,
+                },
+            ]
+        );
+
+        assert!(PythonPackageEntryPoint::from_entry_points(b"mycli = mypkg:main\n").is_err());
+        assert!(PythonPackageEntryPoint::from_entry_points(b"[console_scripts]\nmycli\n").is_err());
+        assert!(
+            PythonPackageEntryPoint::from_entry_points(b"[console_scripts]\nmycli = mypkg:\n")
+                .is_err()
+        );
+
+        Ok(())
+    }
 }
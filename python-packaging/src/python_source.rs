@@ -4,7 +4,7 @@
 
 /*! Utility functions related to Python source code. */
 
-use {anyhow::Result, lazy_static::lazy_static};
+use {anyhow::Result, lazy_static::lazy_static, std::collections::BTreeSet};
 
 lazy_static! {
     static ref RE_CODING: regex::bytes::Regex =
@@ -31,11 +31,8 @@ pub fn python_source_encoding(source: &[u8]) -> Vec<u8> {
     b"utf-8".to_vec()
 }
 
-/// Whether __file__ occurs in Python source code.
-pub fn has_dunder_file(source: &[u8]) -> Result<bool> {
-    // We can't just look for b"__file__ because the source file may be in
-    // encodings like UTF-16. So we need to decode to Unicode first then look for
-    // the code points.
+/// Decode Python source code to Unicode using its declared encoding.
+fn decode_source(source: &[u8]) -> String {
     let encoding = python_source_encoding(source);
 
     let encoder = match encoding_rs::Encoding::for_label(&encoding) {
@@ -45,5 +42,75 @@ pub fn has_dunder_file(source: &[u8]) -> Result<bool> {
 
     let (source, ..) = encoder.decode(source);
 
-    Ok(source.contains("__file__"))
+    source.into_owned()
+}
+
+/// Whether __file__ occurs in Python source code.
+pub fn has_dunder_file(source: &[u8]) -> Result<bool> {
+    // We can't just look for b"__file__ because the source file may be in
+    // encodings like UTF-16. So we need to decode to Unicode first then look for
+    // the code points.
+    Ok(decode_source(source).contains("__file__"))
+}
+
+/// A pattern in Python code suggesting it expects to exist on the filesystem.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum FilesystemReference {
+    /// `__file__` is referenced.
+    DunderFile,
+    /// `__path__` is referenced.
+    DunderPath,
+    /// `pkg_resources` is used.
+    PkgResources,
+    /// Shared libraries are loaded with `ctypes`.
+    CtypesLibrary,
+}
+
+impl FilesystemReference {
+    /// Human readable description of the pattern.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::DunderFile => "references __file__",
+            Self::DunderPath => "references __path__",
+            Self::PkgResources => "uses pkg_resources",
+            Self::CtypesLibrary => "loads shared libraries with ctypes",
+        }
+    }
+}
+
+/// Names of `ctypes` attributes loading shared libraries from paths.
+const CTYPES_LOADERS: &[&str] = &["CDLL", "PyDLL", "WinDLL", "LoadLibrary", "cdll", "windll"];
+
+fn find_references_in_text(text: &str) -> BTreeSet<FilesystemReference> {
+    let mut res = BTreeSet::new();
+
+    if text.contains("__file__") {
+        res.insert(FilesystemReference::DunderFile);
+    }
+    if text.contains("__path__") {
+        res.insert(FilesystemReference::DunderPath);
+    }
+    if text.contains("pkg_resources") {
+        res.insert(FilesystemReference::PkgResources);
+    }
+    if text.contains("ctypes") && CTYPES_LOADERS.iter().any(|name| text.contains(name)) {
+        res.insert(FilesystemReference::CtypesLibrary);
+    }
+
+    res
+}
+
+/// Find patterns in Python source code suggesting it expects to exist on the filesystem.
+///
+/// Detection is textual, so occurrences in comments and strings are reported.
+pub fn find_filesystem_references(source: &[u8]) -> BTreeSet<FilesystemReference> {
+    find_references_in_text(&decode_source(source))
+}
+
+/// Find patterns in Python bytecode suggesting it expects to exist on the filesystem.
+///
+/// Names referenced by code objects are stored as plain strings in marshalled
+/// bytecode, so they are searched for in its raw bytes.
+pub fn find_bytecode_filesystem_references(bytecode: &[u8]) -> BTreeSet<FilesystemReference> {
+    find_references_in_text(&String::from_utf8_lossy(bytecode))
 }
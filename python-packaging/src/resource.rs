@@ -6,11 +6,13 @@
 
 use {
     crate::bytecode::{BytecodeCompiler, CompileMode},
+    crate::long_path::extended_length_path,
     crate::module_util::{
         is_package_from_path, packages_from_module_name, resolve_path_for_module,
     },
     crate::python_source::has_dunder_file,
     anyhow::{anyhow, Context, Result},
+    std::collections::BTreeMap,
     std::convert::TryFrom,
     std::path::{Path, PathBuf},
 };
@@ -28,11 +30,23 @@ impl DataLocation {
     /// Resolve the raw content of this instance.
     pub fn resolve(&self) -> Result<Vec<u8>> {
         match self {
-            DataLocation::Path(p) => std::fs::read(p).context(format!("reading {}", p.display())),
+            DataLocation::Path(p) => {
+                std::fs::read(extended_length_path(p)).context(format!("reading {}", p.display()))
+            }
             DataLocation::Memory(data) => Ok(data.clone()),
         }
     }
 
+    /// Obtain the size of the content of this instance, in bytes.
+    pub fn size(&self) -> Result<u64> {
+        match self {
+            DataLocation::Path(p) => Ok(std::fs::metadata(extended_length_path(p))
+                .context(format!("reading {}", p.display()))?
+                .len()),
+            DataLocation::Memory(data) => Ok(data.len() as u64),
+        }
+    }
+
     /// Resolve the instance to a Memory variant.
     pub fn to_memory(&self) -> Result<DataLocation> {
         Ok(DataLocation::Memory(self.resolve()?))
@@ -258,7 +272,7 @@ impl PythonModuleBytecode {
         match &self.bytecode {
             DataLocation::Memory(data) => Ok(data.clone()),
             DataLocation::Path(path) => {
-                let data = std::fs::read(path)?;
+                let data = std::fs::read(extended_length_path(path))?;
 
                 if data.len() >= 16 {
                     Ok(data[16..data.len()].to_vec())
@@ -310,6 +324,20 @@ impl PythonPackageResource {
         format!("{}:{}", self.leaf_package, self.relative_name)
     }
 
+    /// Whether this resource holds type information for type checkers.
+    ///
+    /// These are `.pyi` stub files and `py.typed` markers (PEP 561). They
+    /// aren't used at run-time.
+    pub fn is_type_information(&self) -> bool {
+        match Path::new(&self.relative_name)
+            .file_name()
+            .and_then(|name| name.to_str())
+        {
+            Some(name) => name.ends_with(".pyi") || name == "py.typed",
+            None => false,
+        }
+    }
+
     /// Resolve filesystem path to this bytecode.
     pub fn resolve_path(&self, prefix: &str) -> PathBuf {
         let mut path = PathBuf::from(prefix);
@@ -407,6 +435,10 @@ pub struct PythonExtensionModule {
     pub libraries: Vec<String>,
     /// Paths to directories holding libraries needed for extension module.
     pub library_dirs: Vec<PathBuf>,
+    /// Shared libraries loaded by the extension module, keyed by file name.
+    ///
+    /// These are distributed along with the extension module.
+    pub shared_libraries: BTreeMap<String, DataLocation>,
 }
 
 impl PythonExtensionModule {
@@ -424,6 +456,11 @@ impl PythonExtensionModule {
             is_package: self.is_package,
             libraries: self.libraries.clone(),
             library_dirs: self.library_dirs.clone(),
+            shared_libraries: self
+                .shared_libraries
+                .iter()
+                .map(|(name, data)| Ok((name.clone(), data.to_memory()?)))
+                .collect::<Result<_>>()?,
         })
     }
 
@@ -612,4 +649,19 @@ mod tests {
         assert!(!bytecode.is_in_packages(&[]));
         assert!(!bytecode.is_in_packages(&["bar".to_string()]));
     }
+
+    #[test]
+    fn test_is_type_information() {
+        let resource = |relative_name: &str| PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: relative_name.to_string(),
+            data: DataLocation::Memory(vec![]),
+        };
+
+        assert!(resource("__init__.pyi").is_type_information());
+        assert!(resource("sub/bar.pyi").is_type_information());
+        assert!(resource("py.typed").is_type_information());
+        assert!(!resource("data.txt").is_type_information());
+        assert!(!resource("py.typed.txt").is_type_information());
+    }
 }
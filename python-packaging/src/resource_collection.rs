@@ -5,16 +5,26 @@
 /*! Functionality for collecting Python resources. */
 
 use {
-    crate::bytecode::{compute_bytecode_header, BytecodeCompiler, BytecodeHeaderMode, CompileMode},
+    crate::bytecode::{
+        compute_bytecode_header, BytecodeCache, BytecodeCompileRequest, BytecodeCompilerBackend,
+        BytecodeCompilerPool, BytecodeHeaderMode, CompileMode,
+    },
+    crate::case_collision::CaseCollisionDetector,
     crate::module_util::{packages_from_module_name, resolve_path_for_module},
-    crate::python_source::has_dunder_file,
+    crate::package_metadata::{PythonPackageEntryPoint, PythonPackageRecord},
+    crate::python_source::{
+        find_bytecode_filesystem_references, find_filesystem_references, has_dunder_file,
+        FilesystemReference,
+    },
     crate::resource::{
         BytecodeOptimizationLevel, DataLocation, PythonExtensionModule, PythonModuleBytecode,
         PythonModuleBytecodeFromSource, PythonModuleSource, PythonPackageDistributionResource,
-        PythonPackageResource,
+        PythonPackageDistributionResourceFlavor, PythonPackageResource,
     },
-    anyhow::{anyhow, Error, Result},
-    python_packed_resources::data::{Resource, ResourceFlavor},
+    anyhow::{anyhow, Context, Error, Result},
+    python_packed_resources::chunking::{find_chunks, ChunkingParameters},
+    python_packed_resources::data::{RemoteChunk, Resource, ResourceFlavor},
+    sha2::{Digest, Sha256},
     std::borrow::Cow,
     std::collections::{BTreeMap, BTreeSet, HashMap},
     std::convert::TryFrom,
@@ -42,6 +52,14 @@ pub enum PythonResourcesPolicy {
     /// in-memory loading works, it is used. Otherwise loading from a filesystem path
     /// relative to the produced binary is used.
     PreferInMemoryFallbackFilesystemRelative(String),
+
+    /// Prefer loading resources from memory, except for packages known to not work from memory.
+    ///
+    /// This behaves like `PreferInMemoryFallbackFilesystemRelative`, but when
+    /// packaging resources, top-level packages detected as incompatible with
+    /// in-memory importing, e.g. because their modules reference `__file__`,
+    /// are installed in the `String` path prefix relative to the binary.
+    PreferInMemoryFallbackFilesystem(String),
 }
 
 impl TryFrom<&str> for PythonResourcesPolicy {
@@ -60,6 +78,12 @@ impl TryFrom<&str> for PythonResourcesPolicy {
             let prefix = &value["prefer-in-memory-fallback-filesystem-relative:".len()..];
 
             Ok(PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(prefix.to_string()))
+        } else if value.starts_with("prefer-in-memory-fallback-filesystem:") {
+            let prefix = &value["prefer-in-memory-fallback-filesystem:".len()..];
+
+            Ok(PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(
+                prefix.to_string(),
+            ))
         } else {
             Err(anyhow!(
                 "invalid value for Python Resources Policy: {}",
@@ -79,10 +103,101 @@ impl Into<String> for &PythonResourcesPolicy {
             PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(ref prefix) => {
                 format!("prefer-in-memory-fallback-filesystem-relative:{}", prefix)
             }
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(ref prefix) => {
+                format!("prefer-in-memory-fallback-filesystem:{}", prefix)
+            }
+        }
+    }
+}
+
+impl PythonResourcesPolicy {
+    /// Resolve the policy to apply to dynamically linked extension modules.
+    ///
+    /// Loading extension modules from memory is only possible on some
+    /// platforms. Rather than failing when a policy requests in-memory loading
+    /// on a platform that can't support it, this will route extension modules
+    /// to the filesystem, next to the produced binary, provided the
+    /// filesystem is a viable alternative. `InMemoryOnly` policies use
+    /// `DEFAULT_EXTENSION_MODULE_PREFIX` as the install prefix.
+    pub fn resolve_extension_module_policy(
+        &self,
+        in_memory_supported: bool,
+        filesystem_supported: bool,
+    ) -> Self {
+        if in_memory_supported || !filesystem_supported {
+            return self.clone();
+        }
+
+        match self {
+            PythonResourcesPolicy::InMemoryOnly => PythonResourcesPolicy::FilesystemRelativeOnly(
+                DEFAULT_EXTENSION_MODULE_PREFIX.to_string(),
+            ),
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(prefix)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(prefix) => {
+                PythonResourcesPolicy::FilesystemRelativeOnly(prefix.clone())
+            }
+            PythonResourcesPolicy::FilesystemRelativeOnly(_) => self.clone(),
+        }
+    }
+}
+
+/// Describes the layout of Python modules installed on the filesystem.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilesystemModuleLayout {
+    /// Files are only meaningful to the resources index.
+    ///
+    /// Bytecode is compiled with the module name as its file name and its
+    /// `.pyc` header doesn't tie it to a source file.
+    Indexed,
+
+    /// Sources and PEP 3147 `__pycache__` files, as written by `compileall`.
+    ///
+    /// Bytecode derived from source is installed along with its source and is
+    /// compiled with the path of its source as its file name. Its `.pyc`
+    /// header records the hash of the source, so tools reading files directly
+    /// (e.g. coverage.py and debuggers) can map bytecode to sources and
+    /// detect stale bytecode.
+    Pycache,
+}
+
+impl Default for FilesystemModuleLayout {
+    fn default() -> Self {
+        FilesystemModuleLayout::Indexed
+    }
+}
+
+impl TryFrom<&str> for FilesystemModuleLayout {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "indexed" => Ok(FilesystemModuleLayout::Indexed),
+            "pycache" => Ok(FilesystemModuleLayout::Pycache),
+            _ => Err(anyhow!(
+                "invalid value for filesystem module layout: {}",
+                value
+            )),
+        }
+    }
+}
+
+impl Into<String> for &FilesystemModuleLayout {
+    fn into(self) -> String {
+        match self {
+            FilesystemModuleLayout::Indexed => "indexed".to_string(),
+            FilesystemModuleLayout::Pycache => "pycache".to_string(),
         }
     }
 }
 
+/// Default path prefix for extension modules that cannot be loaded from memory.
+pub const DEFAULT_EXTENSION_MODULE_PREFIX: &str = "lib";
+
+/// Whether a target triple supports loading extension module shared libraries from memory.
+pub fn target_supports_in_memory_shared_library_loading(target_triple: &str) -> bool {
+    target_triple.contains("pc-windows")
+}
+
 /// Describes how Python module bytecode will be obtained.
 #[derive(Clone, Debug, PartialEq)]
 pub enum PythonModuleBytecodeProvider {
@@ -254,6 +369,231 @@ impl<'a> TryFrom<&PrePackagedResource> for Resource<'a, u8> {
 }
 
 impl PrePackagedResource {
+    /// Whether any data of this resource is loaded from memory.
+    pub fn is_in_memory(&self) -> bool {
+        self.in_memory_source.is_some()
+            || self.in_memory_bytecode.is_some()
+            || self.in_memory_bytecode_opt1.is_some()
+            || self.in_memory_bytecode_opt2.is_some()
+            || self.in_memory_extension_module_shared_library.is_some()
+            || self.in_memory_resources.is_some()
+            || self.in_memory_distribution_resources.is_some()
+            || self.in_memory_shared_library.is_some()
+    }
+
+    /// Whether any data of this resource is installed relative to the binary.
+    pub fn is_relative_path(&self) -> bool {
+        self.relative_path_module_source.is_some()
+            || self.relative_path_bytecode.is_some()
+            || self.relative_path_bytecode_opt1.is_some()
+            || self.relative_path_bytecode_opt2.is_some()
+            || self.relative_path_extension_module_shared_library.is_some()
+            || self.relative_path_package_resources.is_some()
+            || self.relative_path_distribution_resources.is_some()
+            || self.relative_path_shared_library.is_some()
+    }
+
+    /// Move all data of this resource to files relative to the produced binary.
+    ///
+    /// See `PythonResourceCollector::relocate_to_relative_path()`.
+    pub fn relocate_to_relative_path(
+        &mut self,
+        prefix: &str,
+        resources_prefix: &str,
+        cache_tag: &str,
+        package_distributions: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let move_path = |old_prefix: &str, path: &Path, new_prefix: &str| -> Result<PathBuf> {
+            Ok(PathBuf::from(new_prefix).join(path.strip_prefix(old_prefix)?))
+        };
+
+        if let Some(location) = self.in_memory_source.take() {
+            self.relative_path_module_source = Some((prefix.to_string(), location));
+        }
+        if let Some((old_prefix, _)) = self.relative_path_module_source.as_mut() {
+            *old_prefix = prefix.to_string();
+        }
+
+        // The optimization level tag is added when resolving bytecode paths.
+        for (in_memory, relative_path) in vec![
+            (
+                &mut self.in_memory_bytecode,
+                &mut self.relative_path_bytecode,
+            ),
+            (
+                &mut self.in_memory_bytecode_opt1,
+                &mut self.relative_path_bytecode_opt1,
+            ),
+            (
+                &mut self.in_memory_bytecode_opt2,
+                &mut self.relative_path_bytecode_opt2,
+            ),
+        ] {
+            if let Some(provider) = in_memory.take() {
+                *relative_path = Some((prefix.to_string(), cache_tag.to_string(), provider));
+            }
+            if let Some((old_prefix, _, _)) = relative_path.as_mut() {
+                *old_prefix = prefix.to_string();
+            }
+        }
+
+        if let Some(location) = self.in_memory_extension_module_shared_library.take() {
+            // Extension modules are only loaded from memory on Windows,
+            // where they have a .pyd suffix.
+            let module = PythonExtensionModule {
+                name: self.name.clone(),
+                init_fn: None,
+                extension_file_suffix: ".pyd".to_string(),
+                extension_data: None,
+                object_file_data: vec![],
+                is_package: self.is_package,
+                libraries: vec![],
+                library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
+            };
+
+            self.relative_path_extension_module_shared_library =
+                Some((prefix.to_string(), module.resolve_path(prefix), location));
+        }
+        if let Some((old_prefix, path, _)) =
+            self.relative_path_extension_module_shared_library.as_mut()
+        {
+            *path = move_path(old_prefix, path, prefix)?;
+            *old_prefix = prefix.to_string();
+        }
+
+        let mut package_resources = self
+            .relative_path_package_resources
+            .take()
+            .unwrap_or_default();
+        for (old_prefix, path, _) in package_resources.values_mut() {
+            *path = move_path(old_prefix, path, resources_prefix)?;
+            *old_prefix = resources_prefix.to_string();
+        }
+        for (relative_name, location) in self.in_memory_resources.take().unwrap_or_default() {
+            let path = PythonPackageResource {
+                leaf_package: self.name.clone(),
+                relative_name: relative_name.clone(),
+                data: location.clone(),
+            }
+            .resolve_path(resources_prefix);
+
+            package_resources.insert(
+                relative_name,
+                (resources_prefix.to_string(), path, location),
+            );
+        }
+        if !package_resources.is_empty() {
+            self.relative_path_package_resources = Some(package_resources);
+        }
+
+        let mut distribution_resources = self
+            .relative_path_distribution_resources
+            .take()
+            .unwrap_or_default();
+        for (old_prefix, path, _) in distribution_resources.values_mut() {
+            *path = move_path(old_prefix, path, prefix)?;
+            *old_prefix = prefix.to_string();
+        }
+        for (name, location) in self
+            .in_memory_distribution_resources
+            .take()
+            .unwrap_or_default()
+        {
+            let version = package_distributions.get(&self.name).ok_or_else(|| {
+                anyhow!("version of package distribution {} is unknown", self.name)
+            })?;
+
+            let path = PythonPackageDistributionResource {
+                location: PythonPackageDistributionResourceFlavor::DistInfo,
+                package: self.name.clone(),
+                version: version.clone(),
+                name: name.clone(),
+                data: location.clone(),
+            }
+            .resolve_path(prefix);
+
+            distribution_resources.insert(name, (prefix.to_string(), path, location));
+        }
+        if !distribution_resources.is_empty() {
+            self.relative_path_distribution_resources = Some(distribution_resources);
+        }
+
+        if let Some(location) = self.in_memory_shared_library.take() {
+            self.relative_path_shared_library = Some((prefix.to_string(), location));
+        }
+        if let Some((old_prefix, _)) = self.relative_path_shared_library.as_mut() {
+            *old_prefix = prefix.to_string();
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the source of a module installed relative to the binary.
+    ///
+    /// This is the module source if defined, or else the source its bytecode
+    /// is compiled from. Returns the path prefix and the source.
+    pub fn relative_path_source(&self) -> Option<(&String, &DataLocation)> {
+        if let Some((prefix, location)) = &self.relative_path_module_source {
+            Some((prefix, location))
+        } else {
+            [
+                &self.relative_path_bytecode,
+                &self.relative_path_bytecode_opt1,
+                &self.relative_path_bytecode_opt2,
+            ]
+            .iter()
+            .find_map(|bytecode| match bytecode {
+                Some((prefix, _, PythonModuleBytecodeProvider::FromSource(location))) => {
+                    Some((prefix, location))
+                }
+                _ => None,
+            })
+        }
+    }
+
+    /// Obtain bytecode installed relative to the binary.
+    ///
+    /// Returns the optimization level, the path of the bytecode file and how
+    /// the bytecode is obtained. The file name is derived from the cache tag
+    /// and the optimization level, as `importlib` expects.
+    pub fn relative_path_bytecode_files(
+        &self,
+    ) -> Vec<(
+        BytecodeOptimizationLevel,
+        PathBuf,
+        &PythonModuleBytecodeProvider,
+    )> {
+        [
+            (
+                &self.relative_path_bytecode,
+                BytecodeOptimizationLevel::Zero,
+            ),
+            (
+                &self.relative_path_bytecode_opt1,
+                BytecodeOptimizationLevel::One,
+            ),
+            (
+                &self.relative_path_bytecode_opt2,
+                BytecodeOptimizationLevel::Two,
+            ),
+        ]
+        .iter()
+        .filter_map(|(bytecode, optimize)| {
+            bytecode.as_ref().map(|(prefix, cache_tag, provider)| {
+                let path = resolve_path_for_module(
+                    prefix,
+                    &self.name,
+                    self.is_package,
+                    Some(&format!("{}{}", cache_tag, optimize.to_extra_tag())),
+                );
+
+                (*optimize, path, provider)
+            })
+        })
+        .collect()
+    }
+
     /// Derive additional file installs to perform for filesystem-based resources.
     ///
     /// Returns 3-tuples denoting the relative resource path, data to materialize there,
@@ -452,6 +792,15 @@ pub enum ResourceLocation {
     RelativePath,
 }
 
+/// Where bytecode compiled by `to_prepared_python_resources()` is stored.
+enum BytecodeDestination {
+    /// In-memory bytecode of a module at an optimization level.
+    InMemory(String, BytecodeOptimizationLevel),
+
+    /// Data of the extra file at an index.
+    ExtraFile(usize),
+}
+
 /// Represents a finalized collection of Python resources.
 ///
 /// Instances are produced from a `PythonResourceCollector` and a
@@ -460,6 +809,8 @@ pub enum ResourceLocation {
 pub struct PreparedPythonResources<'a> {
     pub resources: BTreeMap<String, Resource<'a, u8>>,
     pub extra_files: Vec<(PathBuf, DataLocation, bool)>,
+    /// Magic number of the bytecode of the resources.
+    pub bytecode_magic_number: u32,
 }
 
 impl<'a> PreparedPythonResources<'a> {
@@ -475,6 +826,155 @@ impl<'a> PreparedPythonResources<'a> {
             None,
         )
     }
+
+    /// Move bytecode of modules in the given packages to a remote archive.
+    ///
+    /// In-memory bytecode of matching modules is split into content-defined
+    /// chunks, which are appended to the returned archive data. Identical
+    /// chunks are stored once. Resources reference their chunks by offset
+    /// into the archive and SHA-256 digest, so readers can fetch and verify
+    /// them on demand.
+    pub fn move_bytecode_to_remote_archive(
+        &mut self,
+        packages: &BTreeSet<String>,
+        params: &ChunkingParameters,
+    ) -> Result<Vec<u8>> {
+        let mut archive = Vec::new();
+        let mut seen: HashMap<Vec<u8>, (u64, u32)> = HashMap::new();
+
+        for (name, resource) in self.resources.iter_mut() {
+            if !packages
+                .iter()
+                .any(|package| name == package || name.starts_with(&format!("{}.", package)))
+            {
+                continue;
+            }
+
+            let bytecode = match resource.in_memory_bytecode.take() {
+                Some(bytecode) => bytecode,
+                None => continue,
+            };
+
+            let mut chunks = Vec::new();
+
+            for range in find_chunks(&bytecode, params) {
+                let data = &bytecode[range];
+                let digest = Sha256::digest(data).to_vec();
+
+                let (offset, length) = match seen.get(&digest) {
+                    Some(location) => *location,
+                    None => {
+                        let location = (
+                            archive.len() as u64,
+                            u32::try_from(data.len()).context("converting chunk length to u32")?,
+                        );
+                        archive.extend_from_slice(data);
+                        seen.insert(digest.clone(), location);
+                        location
+                    }
+                };
+
+                chunks.push(RemoteChunk {
+                    offset,
+                    length,
+                    digest: Cow::Owned(digest),
+                });
+            }
+
+            resource.remote_module_bytecode_chunks = Some(chunks);
+        }
+
+        Ok(archive)
+    }
+
+    /// Move modules in the given packages to files of a zip archive.
+    ///
+    /// In-memory sources, bytecode and package resources of matching modules
+    /// are removed and returned as files laid out the way `zipimport` expects
+    /// them: `.py` and `.pyc` files next to each other. Bytecode files have a
+    /// header with an unchecked hash, so they are used without the source.
+    /// Extension modules and package distribution resources are left in memory.
+    pub fn move_packages_to_zip_files(
+        &mut self,
+        packages: &BTreeSet<String>,
+    ) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        let mut files = BTreeMap::new();
+
+        for (name, resource) in self.resources.iter_mut() {
+            if !packages
+                .iter()
+                .any(|package| name == package || name.starts_with(&format!("{}.", package)))
+            {
+                continue;
+            }
+
+            let module_path = resolve_path_for_module("", name, resource.is_package, None);
+
+            if let Some(source) = resource.in_memory_source.take() {
+                files.insert(module_path.clone(), source.into_owned());
+            }
+
+            let bytecode = resource.in_memory_bytecode.take();
+            let bytecode_opt1 = resource.in_memory_bytecode_opt1.take();
+            let bytecode_opt2 = resource.in_memory_bytecode_opt2.take();
+
+            if let Some(bytecode) = bytecode.or(bytecode_opt1).or(bytecode_opt2) {
+                let mut data = compute_bytecode_header(
+                    self.bytecode_magic_number,
+                    BytecodeHeaderMode::UncheckedHash(0),
+                )?;
+                data.extend_from_slice(&bytecode);
+
+                files.insert(module_path.with_extension("pyc"), data);
+            }
+
+            if let Some(package_resources) = resource.in_memory_package_resources.take() {
+                let package_path = name.split('.').collect::<PathBuf>();
+
+                for (resource_name, data) in package_resources {
+                    files.insert(package_path.join(resource_name.as_ref()), data.into_owned());
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Compress in-memory module sources and bytecode with zstd.
+    ///
+    /// Compressed resources are marked as such so the importer knows to
+    /// decompress their data when the module is imported. This should be
+    /// called after other operations consuming in-memory module data, as
+    /// they expect uncompressed data.
+    pub fn compress_module_data(&mut self, level: i32) -> Result<()> {
+        for (name, resource) in self.resources.iter_mut() {
+            if resource.in_memory_module_data_compressed {
+                continue;
+            }
+
+            for data in vec![
+                &mut resource.in_memory_source,
+                &mut resource.in_memory_bytecode,
+                &mut resource.in_memory_bytecode_opt1,
+                &mut resource.in_memory_bytecode_opt2,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *data = Cow::Owned(
+                    zstd::encode_all(data.as_ref(), level)
+                        .with_context(|| format!("compressing data of {}", name))?,
+                );
+            }
+
+            resource.in_memory_module_data_compressed = resource.in_memory_source.is_some()
+                || resource.in_memory_bytecode.is_some()
+                || resource.in_memory_bytecode_opt1.is_some()
+                || resource.in_memory_bytecode_opt2.is_some();
+        }
+
+        Ok(())
+    }
 }
 
 /// Type used to collect Python resources to they can be serialized.
@@ -488,6 +988,10 @@ pub struct PythonResourceCollector {
     policy: PythonResourcesPolicy,
     resources: BTreeMap<String, PrePackagedResource>,
     cache_tag: String,
+    /// Versions of package distributions having resources in this instance.
+    package_distributions: BTreeMap<String, String>,
+    /// Layout of modules installed on the filesystem.
+    filesystem_module_layout: FilesystemModuleLayout,
 }
 
 impl PythonResourceCollector {
@@ -503,6 +1007,8 @@ impl PythonResourceCollector {
             policy: policy.clone(),
             resources: BTreeMap::new(),
             cache_tag: cache_tag.to_string(),
+            package_distributions: BTreeMap::new(),
+            filesystem_module_layout: FilesystemModuleLayout::default(),
         }
     }
 
@@ -511,6 +1017,16 @@ impl PythonResourceCollector {
         &self.policy
     }
 
+    /// Obtain the layout of modules installed on the filesystem.
+    pub fn get_filesystem_module_layout(&self) -> FilesystemModuleLayout {
+        self.filesystem_module_layout
+    }
+
+    /// Define the layout of modules installed on the filesystem.
+    pub fn set_filesystem_module_layout(&mut self, layout: FilesystemModuleLayout) {
+        self.filesystem_module_layout = layout;
+    }
+
     /// Validate that a resource add in the specified location is allowed.
     pub fn check_policy(&self, location: ResourceLocation) -> Result<()> {
         match self.policy {
@@ -526,7 +1042,8 @@ impl PythonResourceCollector {
                 )),
                 ResourceLocation::RelativePath => Ok(()),
             },
-            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_) => Ok(()),
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative(_)
+            | PythonResourcesPolicy::PreferInMemoryFallbackFilesystem(_) => Ok(()),
         }
     }
 
@@ -587,6 +1104,204 @@ impl PythonResourceCollector {
         }))
     }
 
+    /// Obtain a named resource in this instance.
+    pub fn get_resource(&self, name: &str) -> Option<&PrePackagedResource> {
+        self.resources.get(name)
+    }
+
+    /// Obtain the flavor of each named resource in this instance.
+    pub fn get_resource_flavors(&self) -> BTreeMap<String, ResourceFlavor> {
+        BTreeMap::from_iter(
+            self.resources
+                .iter()
+                .map(|(name, resource)| (name.clone(), resource.flavor)),
+        )
+    }
+
+    /// Obtain names of Python modules having data loaded from memory.
+    pub fn get_in_memory_module_names(&self) -> BTreeSet<String> {
+        BTreeSet::from_iter(
+            self.resources
+                .values()
+                .filter(|resource| {
+                    resource.flavor == ResourceFlavor::Module && resource.is_in_memory()
+                })
+                .map(|resource| resource.name.clone()),
+        )
+    }
+
+    /// Count resources in this instance by location.
+    ///
+    /// Returns the number of resources loaded from memory and the number of
+    /// resources installed relative to the binary. Resources having data in
+    /// both locations are counted twice.
+    pub fn count_resources_by_location(&self) -> (usize, usize) {
+        (
+            self.resources
+                .values()
+                .filter(|resource| resource.is_in_memory())
+                .count(),
+            self.resources
+                .values()
+                .filter(|resource| resource.is_relative_path())
+                .count(),
+        )
+    }
+
+    /// Obtain the total size of the data of resources in this instance, in bytes.
+    ///
+    /// Bytecode compiled from source is counted as the size of its source.
+    pub fn resources_size(&self) -> Result<u64> {
+        let mut size = 0;
+
+        for resource in self.resources.values() {
+            let mut locations = Vec::new();
+
+            locations.extend(&resource.in_memory_source);
+            for provider in &[
+                &resource.in_memory_bytecode,
+                &resource.in_memory_bytecode_opt1,
+                &resource.in_memory_bytecode_opt2,
+            ] {
+                if let Some(PythonModuleBytecodeProvider::Provided(location))
+                | Some(PythonModuleBytecodeProvider::FromSource(location)) = provider
+                {
+                    locations.push(location);
+                }
+            }
+            locations.extend(&resource.in_memory_extension_module_shared_library);
+            for resources in &[
+                &resource.in_memory_resources,
+                &resource.in_memory_distribution_resources,
+            ] {
+                if let Some(resources) = resources {
+                    locations.extend(resources.values());
+                }
+            }
+            locations.extend(&resource.in_memory_shared_library);
+
+            for bytecode in &[
+                &resource.relative_path_bytecode,
+                &resource.relative_path_bytecode_opt1,
+                &resource.relative_path_bytecode_opt2,
+            ] {
+                if let Some((_, _, PythonModuleBytecodeProvider::Provided(location)))
+                | Some((_, _, PythonModuleBytecodeProvider::FromSource(location))) = bytecode
+                {
+                    locations.push(location);
+                }
+            }
+            for (_, location, _) in resource.derive_file_installs()? {
+                locations.push(location);
+            }
+
+            for location in locations {
+                size += location.size()?;
+            }
+        }
+
+        Ok(size)
+    }
+
+    /// Obtain package distributions having resources in this instance.
+    ///
+    /// Keys are package names and values are package versions.
+    pub fn get_package_distributions(&self) -> &BTreeMap<String, String> {
+        &self.package_distributions
+    }
+
+    /// Obtain parsed `RECORD` files of package distributions in this instance.
+    ///
+    /// Keys are package names. Package distributions without a `RECORD`
+    /// distribution resource are not present.
+    pub fn get_package_records(&self) -> Result<BTreeMap<String, PythonPackageRecord>> {
+        let mut res = BTreeMap::new();
+
+        for package in self.package_distributions.keys() {
+            if let Some(location) = self.get_package_distribution_resource(package, "RECORD") {
+                res.insert(
+                    package.clone(),
+                    PythonPackageRecord::from_record(&location.resolve()?)
+                        .with_context(|| format!("parsing RECORD of {}", package))?,
+                );
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Obtain entry points declared by package distributions in this instance.
+    ///
+    /// Keys are package names. Package distributions without an
+    /// `entry_points.txt` distribution resource are not present.
+    pub fn get_package_entry_points(
+        &self,
+    ) -> Result<BTreeMap<String, Vec<PythonPackageEntryPoint>>> {
+        let mut res = BTreeMap::new();
+
+        for package in self.package_distributions.keys() {
+            if let Some(location) =
+                self.get_package_distribution_resource(package, "entry_points.txt")
+            {
+                res.insert(
+                    package.clone(),
+                    PythonPackageEntryPoint::from_entry_points(&location.resolve()?)
+                        .with_context(|| format!("parsing entry_points.txt of {}", package))?,
+                );
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Obtain the data of a distribution resource of a package.
+    fn get_package_distribution_resource(
+        &self,
+        package: &str,
+        name: &str,
+    ) -> Option<&DataLocation> {
+        let entry = self.resources.get(package)?;
+
+        if let Some(location) = entry
+            .in_memory_distribution_resources
+            .as_ref()
+            .and_then(|resources| resources.get(name))
+        {
+            Some(location)
+        } else {
+            entry
+                .relative_path_distribution_resources
+                .as_ref()
+                .and_then(|resources| resources.get(name))
+                .map(|(_, _, location)| location)
+        }
+    }
+
+    /// Obtain the original paths of module sources installed on the filesystem.
+    ///
+    /// Keys are paths of sources relative to the binary and values are the
+    /// files they are read from. Sources are only installed with the pycache
+    /// layout and sources not backed by a file are not present.
+    pub fn get_filesystem_module_source_paths(&self) -> BTreeMap<PathBuf, PathBuf> {
+        if self.filesystem_module_layout != FilesystemModuleLayout::Pycache {
+            return BTreeMap::new();
+        }
+
+        BTreeMap::from_iter(self.resources.values().filter_map(|resource| {
+            if resource.flavor != ResourceFlavor::Module {
+                return None;
+            }
+
+            match resource.relative_path_source() {
+                Some((prefix, DataLocation::Path(path))) => Some((
+                    resolve_path_for_module(prefix, &resource.name, resource.is_package, None),
+                    path.clone(),
+                )),
+                _ => None,
+            }
+        }))
+    }
+
     /// Add Python module source to be loaded from memory.
     pub fn add_in_memory_python_module_source(
         &mut self,
@@ -908,6 +1623,9 @@ impl PythonResourceCollector {
             .unwrap()
             .insert(resource.name.clone(), resource.data.clone());
 
+        self.package_distributions
+            .insert(resource.package.clone(), resource.version.clone());
+
         Ok(())
     }
 
@@ -946,6 +1664,9 @@ impl PythonResourceCollector {
                 ),
             );
 
+        self.package_distributions
+            .insert(resource.package.clone(), resource.version.clone());
+
         Ok(())
     }
 
@@ -1081,14 +1802,69 @@ impl PythonResourceCollector {
         Ok(())
     }
 
-    /// Searches for Python sources for references to __file__.
+    /// Move all resources to files relative to the produced binary.
     ///
-    /// __file__ usage can be problematic for in-memory modules. This method searches
-    /// for its occurrences and returns module names having it present.
-    pub fn find_dunder_file(&self) -> Result<BTreeSet<String>> {
-        let mut res = BTreeSet::new();
+    /// Modules, extension modules, shared libraries and package distribution
+    /// resources are installed in the `prefix` directory. Package resources
+    /// are installed in the `resources_prefix` directory, which can be the
+    /// same. Resources already loaded from the filesystem are moved to these
+    /// directories. The policy of this instance becomes
+    /// `filesystem-relative-only:<prefix>`.
+    ///
+    /// Built-in extension modules remain linked into the binary.
+    pub fn relocate_to_relative_path(
+        &mut self,
+        prefix: &str,
+        resources_prefix: &str,
+    ) -> Result<()> {
+        for resource in self.resources.values_mut() {
+            resource.relocate_to_relative_path(
+                prefix,
+                resources_prefix,
+                &self.cache_tag,
+                &self.package_distributions,
+            )?;
+        }
 
-        for (name, module) in &self.resources {
+        self.policy = PythonResourcesPolicy::FilesystemRelativeOnly(prefix.to_string());
+
+        Ok(())
+    }
+
+    /// Move resources of top-level packages to files relative to the produced binary.
+    ///
+    /// Resources whose top-level package is in `packages` are moved to the
+    /// `prefix` directory, as by `relocate_to_relative_path()`. Other
+    /// resources and the policy of this instance are left unchanged.
+    pub fn relocate_packages_to_relative_path(
+        &mut self,
+        packages: &BTreeSet<String>,
+        prefix: &str,
+    ) -> Result<()> {
+        for resource in self.resources.values_mut() {
+            let package = resource.name.split('.').next().unwrap_or_default();
+
+            if packages.contains(package) {
+                resource.relocate_to_relative_path(
+                    prefix,
+                    prefix,
+                    &self.cache_tag,
+                    &self.package_distributions,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Searches for Python sources for references to __file__.
+    ///
+    /// __file__ usage can be problematic for in-memory modules. This method searches
+    /// for its occurrences and returns module names having it present.
+    pub fn find_dunder_file(&self) -> Result<BTreeSet<String>> {
+        let mut res = BTreeSet::new();
+
+        for (name, module) in &self.resources {
             if let Some(location) = &module.in_memory_source {
                 if has_dunder_file(&location.resolve()?)? {
                     res.insert(name.clone());
@@ -1123,6 +1899,114 @@ impl PythonResourceCollector {
         Ok(res)
     }
 
+    /// Find modules loaded from memory which expect to exist on the filesystem.
+    ///
+    /// The in-memory source and bytecode of each module are searched for
+    /// patterns such as `__file__` references. Keys are names of modules
+    /// having at least one pattern.
+    pub fn find_filesystem_references(
+        &self,
+    ) -> Result<BTreeMap<String, BTreeSet<FilesystemReference>>> {
+        let mut res = BTreeMap::new();
+
+        for (name, module) in &self.resources {
+            let mut references = BTreeSet::new();
+
+            if let Some(location) = &module.in_memory_source {
+                references.extend(find_filesystem_references(&location.resolve()?));
+            }
+
+            for provider in &[
+                &module.in_memory_bytecode,
+                &module.in_memory_bytecode_opt1,
+                &module.in_memory_bytecode_opt2,
+            ] {
+                match provider {
+                    Some(PythonModuleBytecodeProvider::FromSource(location)) => {
+                        references.extend(find_filesystem_references(&location.resolve()?));
+                    }
+                    Some(PythonModuleBytecodeProvider::Provided(location)) => {
+                        references
+                            .extend(find_bytecode_filesystem_references(&location.resolve()?));
+                    }
+                    None => {}
+                }
+            }
+
+            if !references.is_empty() {
+                res.insert(name.clone(), references);
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Derive the files of a zip application from resources in this instance.
+    ///
+    /// Zip applications (PEP 441) are run by an existing Python interpreter,
+    /// which imports modules from the archive with `zipimport`. Module sources,
+    /// including sources bytecode would be compiled from, package resources
+    /// and package distribution resources are returned, keyed by their path
+    /// in the archive. Parent packages without a source get an empty
+    /// `__init__.py`.
+    ///
+    /// Resources whose top-level package is in `exclude_packages` are
+    /// ignored. The names of resources which can't be imported from a zip
+    /// file (extension modules, shared libraries and modules only having
+    /// bytecode) are returned as well.
+    pub fn derive_zipapp_files(
+        &self,
+        exclude_packages: &BTreeSet<String>,
+    ) -> Result<(BTreeMap<PathBuf, DataLocation>, BTreeSet<String>)> {
+        let mut resources = self.resources.clone();
+        populate_parent_packages(&mut resources)?;
+
+        let mut files = BTreeMap::new();
+        let mut skipped = BTreeSet::new();
+
+        for (name, mut resource) in resources {
+            let package = name.split('.').next().unwrap_or_default();
+            if exclude_packages.contains(package) {
+                continue;
+            }
+
+            resource.relocate_to_relative_path(
+                "",
+                "",
+                &self.cache_tag,
+                &self.package_distributions,
+            )?;
+
+            for (path, location, executable) in resource.derive_file_installs()? {
+                if executable {
+                    skipped.insert(name.clone());
+                } else {
+                    files.insert(path, location.clone());
+                }
+            }
+
+            if resource.flavor != ResourceFlavor::Module
+                || resource.relative_path_module_source.is_some()
+            {
+                continue;
+            }
+
+            if let Some((_, location)) = resource.relative_path_source() {
+                files.insert(
+                    resolve_path_for_module("", &name, resource.is_package, None),
+                    location.clone(),
+                );
+            } else if resource.relative_path_bytecode.is_some()
+                || resource.relative_path_bytecode_opt1.is_some()
+                || resource.relative_path_bytecode_opt2.is_some()
+            {
+                skipped.insert(name);
+            }
+        }
+
+        Ok((files, skipped))
+    }
+
     /// Derive a list of extra file installs that need to be performed for referenced resources.
     pub fn derive_file_installs(&self) -> Result<Vec<(PathBuf, &DataLocation, bool)>> {
         let mut res = Vec::new();
@@ -1134,180 +2018,171 @@ impl PythonResourceCollector {
         Ok(res)
     }
 
+    /// Verify that files installed relative to the binary don't differ only by case.
+    ///
+    /// Such files would overwrite each other on case-insensitive filesystems.
+    /// Resources loaded from memory are looked up by exact name and can't
+    /// collide.
+    pub fn check_case_collisions(&self) -> Result<()> {
+        let mut detector = CaseCollisionDetector::default();
+
+        for resource in self.resources.values() {
+            for (path, _, _) in resource.derive_file_installs()? {
+                detector.add(&path)?;
+            }
+
+            for (_, path, _) in resource.relative_path_bytecode_files() {
+                detector.add(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Converts this collection of resources into a `PreparedPythonResources`.
+    ///
+    /// Bytecode is compiled by `compiler_backend`. If `bytecode_cache_path`
+    /// is defined, compiled bytecode is cached in that directory and reused
+    /// by later calls. Fails if files installed relative to the binary differ
+    /// only by case.
     pub fn to_prepared_python_resources(
         &self,
-        python_exe: &Path,
+        compiler_backend: &BytecodeCompilerBackend,
+        bytecode_cache_path: Option<&Path>,
     ) -> Result<PreparedPythonResources> {
+        self.check_case_collisions()?;
+
         let mut input_resources = self.resources.clone();
         populate_parent_packages(&mut input_resources)?;
 
         let mut resources = BTreeMap::new();
         let mut extra_files = Vec::new();
 
-        let mut compiler = BytecodeCompiler::new(python_exe)?;
-        {
-            for (name, resource) in &input_resources {
-                if resource.flavor != ResourceFlavor::Module {
-                    continue;
-                }
-
-                let mut entry = Resource::try_from(resource)?;
+        // Bytecode is compiled once all modules are processed, so compilation
+        // can be spread across compilers. Each request has a destination.
+        let mut requests = Vec::new();
+        let mut destinations = Vec::new();
 
-                if let Some(PythonModuleBytecodeProvider::FromSource(location)) =
-                    &resource.in_memory_bytecode
-                {
-                    entry.in_memory_bytecode = Some(Cow::Owned(compiler.compile(
-                        &location.resolve()?,
-                        &name,
-                        BytecodeOptimizationLevel::Zero,
-                        CompileMode::Bytecode,
-                    )?));
-                }
+        let mut compiler = BytecodeCompilerPool::from_backend(compiler_backend)?;
+        if let Some(path) = bytecode_cache_path {
+            compiler.set_cache(BytecodeCache::new(path));
+        }
 
-                if let Some(PythonModuleBytecodeProvider::FromSource(location)) =
-                    &resource.in_memory_bytecode_opt1
-                {
-                    entry.in_memory_bytecode_opt1 = Some(Cow::Owned(compiler.compile(
-                        &location.resolve()?,
-                        &name,
-                        BytecodeOptimizationLevel::One,
-                        CompileMode::Bytecode,
-                    )?));
-                }
+        for (name, resource) in &input_resources {
+            if resource.flavor != ResourceFlavor::Module {
+                continue;
+            }
 
-                if let Some(PythonModuleBytecodeProvider::FromSource(location)) =
-                    &resource.in_memory_bytecode_opt2
-                {
-                    entry.in_memory_bytecode_opt2 = Some(Cow::Owned(compiler.compile(
-                        &location.resolve()?,
-                        &name,
-                        BytecodeOptimizationLevel::Two,
-                        CompileMode::Bytecode,
-                    )?));
-                }
+            let mut entry = Resource::try_from(resource)?;
 
-                if let Some((prefix, cache_tag, provider)) = &resource.relative_path_bytecode {
-                    let path = resolve_path_for_module(
-                        prefix,
-                        &resource.name,
-                        resource.is_package,
-                        Some(&format!(
-                            "{}{}",
-                            cache_tag,
-                            BytecodeOptimizationLevel::Zero.to_extra_tag()
-                        )),
-                    );
-
-                    extra_files.push((
-                        path.clone(),
-                        DataLocation::Memory(match provider {
-                            PythonModuleBytecodeProvider::FromSource(location) => compiler
-                                .compile(
-                                    &location.resolve()?,
-                                    &name,
-                                    BytecodeOptimizationLevel::Zero,
-                                    CompileMode::PycUncheckedHash,
-                                )?,
-                            PythonModuleBytecodeProvider::Provided(location) => {
-                                let mut data = compute_bytecode_header(
-                                    compiler.magic_number,
-                                    BytecodeHeaderMode::UncheckedHash(0),
-                                )?;
-                                data.extend(location.resolve()?);
-
-                                data
-                            }
-                        }),
-                        false,
-                    ));
+            // The pycache layout installs the source of modules on the
+            // filesystem, which their bytecode is compiled against.
+            let mut source_path = None;
+            if self.filesystem_module_layout == FilesystemModuleLayout::Pycache {
+                if let Some((prefix, location)) = resource.relative_path_source() {
+                    let path =
+                        resolve_path_for_module(prefix, &resource.name, resource.is_package, None);
 
-                    entry.relative_path_module_bytecode = Some(Cow::Owned(path));
+                    extra_files.push((path.clone(), location.clone(), false));
+                    entry.relative_path_module_source = Some(Cow::Owned(path.clone()));
+                    source_path = Some(path);
                 }
+            }
 
-                if let Some((prefix, cache_tag, provider)) = &resource.relative_path_bytecode_opt1 {
-                    let path = resolve_path_for_module(
-                        prefix,
-                        &resource.name,
-                        resource.is_package,
-                        Some(&format!(
-                            "{}{}",
-                            cache_tag,
-                            BytecodeOptimizationLevel::One.to_extra_tag()
-                        )),
-                    );
-
-                    extra_files.push((
-                        path.clone(),
-                        DataLocation::Memory(match provider {
-                            PythonModuleBytecodeProvider::FromSource(location) => compiler
-                                .compile(
-                                    &location.resolve()?,
-                                    &name,
-                                    BytecodeOptimizationLevel::One,
-                                    CompileMode::PycUncheckedHash,
-                                )?,
-                            PythonModuleBytecodeProvider::Provided(location) => {
-                                let mut data = compute_bytecode_header(
-                                    compiler.magic_number,
-                                    BytecodeHeaderMode::UncheckedHash(0),
-                                )?;
-                                data.extend(location.resolve()?);
-
-                                data
-                            }
-                        }),
-                        false,
-                    ));
-
-                    entry.relative_path_module_bytecode_opt1 = Some(Cow::Owned(path));
+            for (provider, optimize) in &[
+                (
+                    &resource.in_memory_bytecode,
+                    BytecodeOptimizationLevel::Zero,
+                ),
+                (
+                    &resource.in_memory_bytecode_opt1,
+                    BytecodeOptimizationLevel::One,
+                ),
+                (
+                    &resource.in_memory_bytecode_opt2,
+                    BytecodeOptimizationLevel::Two,
+                ),
+            ] {
+                if let Some(PythonModuleBytecodeProvider::FromSource(location)) = provider {
+                    requests.push(BytecodeCompileRequest {
+                        source: location.resolve()?,
+                        filename: name.clone(),
+                        optimize: *optimize,
+                        output_mode: CompileMode::Bytecode,
+                    });
+                    destinations.push(BytecodeDestination::InMemory(name.clone(), *optimize));
                 }
+            }
 
-                if let Some((prefix, cache_tag, provider)) = &resource.relative_path_bytecode_opt2 {
-                    let path = resolve_path_for_module(
-                        prefix,
-                        &resource.name,
-                        resource.is_package,
-                        Some(&format!(
-                            "{}{}",
-                            cache_tag,
-                            BytecodeOptimizationLevel::Two.to_extra_tag()
-                        )),
-                    );
-
-                    extra_files.push((
-                        path.clone(),
-                        DataLocation::Memory(match provider {
-                            PythonModuleBytecodeProvider::FromSource(location) => compiler
-                                .compile(
-                                    &location.resolve()?,
-                                    &name,
-                                    BytecodeOptimizationLevel::Two,
-                                    CompileMode::PycUncheckedHash,
-                                )?,
-                            PythonModuleBytecodeProvider::Provided(location) => {
-                                let mut data = compute_bytecode_header(
-                                    compiler.magic_number,
-                                    BytecodeHeaderMode::UncheckedHash(0),
-                                )?;
-                                data.extend(location.resolve()?);
-
-                                data
-                            }
-                        }),
-                        false,
-                    ));
+            for (optimize, path, provider) in resource.relative_path_bytecode_files() {
+                let data = match provider {
+                    PythonModuleBytecodeProvider::FromSource(location) => {
+                        let (filename, output_mode) = match &source_path {
+                            Some(path) => (path.display().to_string(), CompileMode::PycCheckedHash),
+                            None => (name.clone(), CompileMode::PycUncheckedHash),
+                        };
+
+                        requests.push(BytecodeCompileRequest {
+                            source: location.resolve()?,
+                            filename,
+                            optimize,
+                            output_mode,
+                        });
+                        destinations.push(BytecodeDestination::ExtraFile(extra_files.len()));
+
+                        Vec::new()
+                    }
+                    PythonModuleBytecodeProvider::Provided(location) => {
+                        let mut data = compute_bytecode_header(
+                            compiler.magic_number,
+                            BytecodeHeaderMode::UncheckedHash(0),
+                        )?;
+                        data.extend(location.resolve()?);
+
+                        data
+                    }
+                };
+
+                extra_files.push((path.clone(), DataLocation::Memory(data), false));
+
+                let field = match optimize {
+                    BytecodeOptimizationLevel::Zero => &mut entry.relative_path_module_bytecode,
+                    BytecodeOptimizationLevel::One => &mut entry.relative_path_module_bytecode_opt1,
+                    BytecodeOptimizationLevel::Two => &mut entry.relative_path_module_bytecode_opt2,
+                };
+                *field = Some(Cow::Owned(path));
+            }
 
-                    entry.relative_path_module_bytecode_opt2 = Some(Cow::Owned(path));
-                }
+            resources.insert(name.clone(), entry);
+        }
 
-                resources.insert(name.clone(), entry);
+        for (destination, bytecode) in destinations
+            .into_iter()
+            .zip(compiler.compile_all(requests)?)
+        {
+            match destination {
+                BytecodeDestination::InMemory(name, optimize) => {
+                    let entry = resources
+                        .get_mut(&name)
+                        .ok_or_else(|| anyhow!("resource {} should exist", name))?;
+
+                    let field = match optimize {
+                        BytecodeOptimizationLevel::Zero => &mut entry.in_memory_bytecode,
+                        BytecodeOptimizationLevel::One => &mut entry.in_memory_bytecode_opt1,
+                        BytecodeOptimizationLevel::Two => &mut entry.in_memory_bytecode_opt2,
+                    };
+                    *field = Some(Cow::Owned(bytecode));
+                }
+                BytecodeDestination::ExtraFile(index) => {
+                    extra_files[index].1 = DataLocation::Memory(bytecode);
+                }
             }
         }
 
         Ok(PreparedPythonResources {
             resources,
             extra_files,
+            bytecode_magic_number: compiler.magic_number,
         })
     }
 }
@@ -1332,6 +2207,13 @@ mod tests {
             PythonResourcesPolicy::try_from("prefer-in-memory-fallback-filesystem-relative:lib")?,
             PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("lib".to_string())
         );
+        assert_eq!(
+            PythonResourcesPolicy::try_from("prefer-in-memory-fallback-filesystem:lib")?,
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystem("lib".to_string())
+        );
+        let s: String =
+            (&PythonResourcesPolicy::PreferInMemoryFallbackFilesystem("lib".to_string())).into();
+        assert_eq!(s, "prefer-in-memory-fallback-filesystem:lib");
         assert_eq!(
             PythonResourcesPolicy::try_from("foo")
                 .unwrap_err()
@@ -1342,6 +2224,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_filesystem_module_layout_from_str() -> Result<()> {
+        assert_eq!(
+            FilesystemModuleLayout::try_from("indexed")?,
+            FilesystemModuleLayout::Indexed
+        );
+        assert_eq!(
+            FilesystemModuleLayout::try_from("pycache")?,
+            FilesystemModuleLayout::Pycache
+        );
+        assert_eq!(
+            FilesystemModuleLayout::try_from("flat")
+                .unwrap_err()
+                .to_string(),
+            "invalid value for filesystem module layout: flat"
+        );
+
+        let s: String = (&FilesystemModuleLayout::Pycache).into();
+        assert_eq!(s, "pycache");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_extension_module_policy() {
+        let in_memory = PythonResourcesPolicy::InMemoryOnly;
+        let prefer =
+            PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("prefix".to_string());
+        let filesystem = PythonResourcesPolicy::FilesystemRelativeOnly("prefix".to_string());
+
+        assert_eq!(
+            in_memory.resolve_extension_module_policy(true, true),
+            in_memory
+        );
+        assert_eq!(
+            in_memory.resolve_extension_module_policy(false, true),
+            PythonResourcesPolicy::FilesystemRelativeOnly("lib".to_string())
+        );
+        assert_eq!(
+            in_memory.resolve_extension_module_policy(false, false),
+            in_memory
+        );
+        assert_eq!(prefer.resolve_extension_module_policy(true, true), prefer);
+        assert_eq!(
+            prefer.resolve_extension_module_policy(false, true),
+            filesystem
+        );
+        assert_eq!(
+            filesystem.resolve_extension_module_policy(true, true),
+            filesystem
+        );
+
+        assert!(target_supports_in_memory_shared_library_loading(
+            "x86_64-pc-windows-msvc"
+        ));
+        assert!(!target_supports_in_memory_shared_library_loading(
+            "x86_64-unknown-linux-gnu"
+        ));
+    }
+
     #[test]
     fn test_populate_parent_packages_in_memory_source() -> Result<()> {
         let mut h = BTreeMap::new();
@@ -1634,6 +2576,81 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_case_collisions() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            &PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("lib".to_string()),
+            DEFAULT_CACHE_TAG,
+        );
+
+        for name in &["foo", "Foo"] {
+            r.add_in_memory_python_module_source(&PythonModuleSource {
+                name: name.to_string(),
+                source: DataLocation::Memory(vec![42]),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            })?;
+        }
+        r.check_case_collisions()?;
+
+        for name in &["bar", "Bar"] {
+            r.add_relative_path_python_module_source(
+                &PythonModuleSource {
+                    name: name.to_string(),
+                    source: DataLocation::Memory(vec![42]),
+                    is_package: false,
+                    cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                },
+                "lib",
+            )?;
+        }
+
+        let err = r.check_case_collisions().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{} and {} differ only by case and would overwrite each other on case-insensitive filesystems",
+                PathBuf::from("lib").join("Bar.py").display(),
+                PathBuf::from("lib").join("bar.py").display()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_resources_by_location() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            &PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("lib".to_string()),
+            DEFAULT_CACHE_TAG,
+        );
+        assert_eq!(r.count_resources_by_location(), (0, 0));
+
+        for name in &["foo", "bar"] {
+            r.add_in_memory_python_module_source(&PythonModuleSource {
+                name: name.to_string(),
+                source: DataLocation::Memory(vec![42]),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            })?;
+        }
+        for name in &["bar", "baz"] {
+            r.add_relative_path_python_module_source(
+                &PythonModuleSource {
+                    name: name.to_string(),
+                    source: DataLocation::Memory(vec![42]),
+                    is_package: false,
+                    cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                },
+                "lib",
+            )?;
+        }
+
+        assert_eq!(r.count_resources_by_location(), (2, 2));
+
+        Ok(())
+    }
+
     #[test]
     fn test_add_in_memory_bytecode_module() -> Result<()> {
         let mut r =
@@ -1766,6 +2783,7 @@ mod tests {
             is_package: false,
             libraries: vec![],
             library_dirs: vec![],
+            shared_libraries: BTreeMap::new(),
         };
 
         c.add_relative_path_python_extension_module(&em, "prefix")?;
@@ -1835,4 +2853,593 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_find_filesystem_references() -> Result<()> {
+        let mut r =
+            PythonResourceCollector::new(&PythonResourcesPolicy::InMemoryOnly, DEFAULT_CACHE_TAG);
+
+        r.add_in_memory_python_module_source(&PythonModuleSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(Vec::from("import os")),
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        r.add_in_memory_python_module_source(&PythonModuleSource {
+            name: "bar".to_string(),
+            source: DataLocation::Memory(Vec::from(
+                "import ctypes, os\nlib = ctypes.CDLL(os.path.join(__path__[0], 'lib.so'))",
+            )),
+            is_package: true,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        r.add_in_memory_python_module_bytecode(&PythonModuleBytecode::new(
+            "baz",
+            BytecodeOptimizationLevel::Zero,
+            false,
+            DEFAULT_CACHE_TAG,
+            b"\xe3\x00\xdapkg_resources\xda\x08__file__",
+        ))?;
+
+        let references = r.find_filesystem_references()?;
+        assert_eq!(references.len(), 2);
+        assert_eq!(
+            references.get("bar").unwrap().iter().collect::<Vec<_>>(),
+            vec![
+                &FilesystemReference::DunderPath,
+                &FilesystemReference::CtypesLibrary
+            ]
+        );
+        assert_eq!(
+            references.get("baz").unwrap().iter().collect::<Vec<_>>(),
+            vec![
+                &FilesystemReference::DunderFile,
+                &FilesystemReference::PkgResources
+            ]
+        );
+        assert_eq!(r.get_in_memory_module_names().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_derive_zipapp_files() -> Result<()> {
+        let mut r =
+            PythonResourceCollector::new(&PythonResourcesPolicy::InMemoryOnly, DEFAULT_CACHE_TAG);
+
+        r.add_in_memory_python_module_source(&PythonModuleSource {
+            name: "foo.bar".to_string(),
+            source: DataLocation::Memory(b"bar".to_vec()),
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        r.add_in_memory_python_module_bytecode_from_source(&PythonModuleBytecodeFromSource {
+            name: "baz".to_string(),
+            source: DataLocation::Memory(b"baz".to_vec()),
+            optimize_level: BytecodeOptimizationLevel::Zero,
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        r.add_in_memory_python_module_bytecode(&PythonModuleBytecode::new(
+            "compiled",
+            BytecodeOptimizationLevel::Zero,
+            false,
+            DEFAULT_CACHE_TAG,
+            b"compiled",
+        ))?;
+        r.add_in_memory_python_package_resource(&PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: "data.txt".to_string(),
+            data: DataLocation::Memory(b"data".to_vec()),
+        })?;
+        r.add_in_memory_python_module_source(&PythonModuleSource {
+            name: "os".to_string(),
+            source: DataLocation::Memory(b"os".to_vec()),
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+
+        let (files, skipped) =
+            r.derive_zipapp_files(&vec!["os".to_string()].into_iter().collect())?;
+
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec![
+                &PathBuf::from("baz.py"),
+                &PathBuf::from("foo/__init__.py"),
+                &PathBuf::from("foo/bar.py"),
+                &PathBuf::from("foo/data.txt"),
+            ]
+        );
+        assert_eq!(
+            files.get(&PathBuf::from("foo/bar.py")).unwrap().resolve()?,
+            b"bar".to_vec()
+        );
+        assert!(files
+            .get(&PathBuf::from("foo/__init__.py"))
+            .unwrap()
+            .resolve()?
+            .is_empty());
+        assert_eq!(skipped.into_iter().collect::<Vec<_>>(), vec!["compiled"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_distributions() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            &PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("prefix".to_string()),
+            DEFAULT_CACHE_TAG,
+        );
+        assert!(r.get_package_distributions().is_empty());
+
+        r.add_in_memory_package_distribution_resource(&PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "foo".to_string(),
+            version: "1.0".to_string(),
+            name: "METADATA".to_string(),
+            data: DataLocation::Memory(vec![42]),
+        })?;
+        r.add_relative_path_package_distribution_resource(
+            "prefix",
+            &PythonPackageDistributionResource {
+                location: PythonPackageDistributionResourceFlavor::DistInfo,
+                package: "bar".to_string(),
+                version: "2.0".to_string(),
+                name: "METADATA".to_string(),
+                data: DataLocation::Memory(vec![42]),
+            },
+        )?;
+
+        let distributions = r.get_package_distributions();
+        assert_eq!(distributions.len(), 2);
+        assert_eq!(distributions.get("foo"), Some(&"1.0".to_string()));
+        assert_eq!(distributions.get("bar"), Some(&"2.0".to_string()));
+
+        assert!(r.get_package_records()?.is_empty());
+
+        r.add_in_memory_package_distribution_resource(&PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "foo".to_string(),
+            version: "1.0".to_string(),
+            name: "RECORD".to_string(),
+            data: DataLocation::Memory(b"foo/__init__.py,sha256=abc,3\n".to_vec()),
+        })?;
+
+        let records = r.get_package_records()?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records.get("foo").unwrap().entries.len(), 1);
+        assert_eq!(
+            records.get("foo").unwrap().entries[0].path,
+            "foo/__init__.py"
+        );
+
+        assert!(r.get_package_entry_points()?.is_empty());
+
+        r.add_relative_path_package_distribution_resource(
+            "prefix",
+            &PythonPackageDistributionResource {
+                location: PythonPackageDistributionResourceFlavor::DistInfo,
+                package: "bar".to_string(),
+                version: "2.0".to_string(),
+                name: "entry_points.txt".to_string(),
+                data: DataLocation::Memory(b"[console_scripts]\nbar = bar.cli:main\n".to_vec()),
+            },
+        )?;
+
+        let entry_points = r.get_package_entry_points()?;
+        assert_eq!(entry_points.len(), 1);
+        assert_eq!(entry_points.get("bar").unwrap()[0].name, "bar");
+        assert_eq!(entry_points.get("bar").unwrap()[0].module, "bar.cli");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress_module_data() -> Result<()> {
+        let source = b"import io\n".repeat(100);
+
+        let mut prepared = PreparedPythonResources::default();
+        prepared.resources.insert(
+            "foo".to_string(),
+            Resource {
+                flavor: ResourceFlavor::Module,
+                name: Cow::Owned("foo".to_string()),
+                in_memory_source: Some(Cow::Owned(source.clone())),
+                in_memory_bytecode: Some(Cow::Owned(source.clone())),
+                ..Resource::default()
+            },
+        );
+        prepared.resources.insert(
+            "bar".to_string(),
+            Resource {
+                flavor: ResourceFlavor::Module,
+                name: Cow::Owned("bar".to_string()),
+                relative_path_module_source: Some(Cow::Owned(PathBuf::from("bar.py"))),
+                ..Resource::default()
+            },
+        );
+
+        prepared.compress_module_data(3)?;
+
+        let resource = prepared.resources.get("foo").unwrap();
+        assert!(resource.in_memory_module_data_compressed);
+        for data in &[&resource.in_memory_source, &resource.in_memory_bytecode] {
+            let data = data.as_ref().unwrap();
+            assert!(data.len() < source.len());
+            assert_eq!(zstd::decode_all(data.as_ref())?, source);
+        }
+        assert!(resource.in_memory_bytecode_opt1.is_none());
+
+        assert!(
+            !prepared
+                .resources
+                .get("bar")
+                .unwrap()
+                .in_memory_module_data_compressed
+        );
+
+        // Compressing again leaves data alone.
+        let compressed = prepared.resources.get("foo").unwrap().clone();
+        prepared.compress_module_data(3)?;
+        assert_eq!(prepared.resources.get("foo").unwrap(), &compressed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_bytecode_to_remote_archive() -> Result<()> {
+        let mut state = 1u32;
+        let bytecode = (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+
+        let mut prepared = PreparedPythonResources::default();
+        for name in &["foo", "foo.bar", "foobar", "baz"] {
+            prepared.resources.insert(
+                name.to_string(),
+                Resource {
+                    flavor: ResourceFlavor::Module,
+                    name: Cow::Owned(name.to_string()),
+                    in_memory_bytecode: Some(Cow::Owned(bytecode.clone())),
+                    ..Resource::default()
+                },
+            );
+        }
+
+        let packages = BTreeSet::from_iter(vec!["foo".to_string()]);
+        let archive =
+            prepared.move_bytecode_to_remote_archive(&packages, &ChunkingParameters::default())?;
+
+        // Identical bytecode is only stored once.
+        assert_eq!(archive.len(), bytecode.len());
+
+        for name in &["foo", "foo.bar"] {
+            let resource = prepared.resources.get(*name).unwrap();
+            assert!(resource.in_memory_bytecode.is_none());
+
+            let chunks = resource.remote_module_bytecode_chunks.as_ref().unwrap();
+            assert!(chunks.len() > 1);
+
+            let mut data = Vec::new();
+            for chunk in chunks {
+                let start = chunk.offset as usize;
+                let chunk_data = &archive[start..start + chunk.length as usize];
+                assert_eq!(Sha256::digest(chunk_data).as_slice(), chunk.digest.as_ref());
+                data.extend_from_slice(chunk_data);
+            }
+            assert_eq!(data, bytecode);
+        }
+
+        for name in &["foobar", "baz"] {
+            let resource = prepared.resources.get(*name).unwrap();
+            assert!(resource.in_memory_bytecode.is_some());
+            assert!(resource.remote_module_bytecode_chunks.is_none());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_packages_to_zip_files() -> Result<()> {
+        let mut prepared = PreparedPythonResources {
+            bytecode_magic_number: 42,
+            ..PreparedPythonResources::default()
+        };
+        for (name, is_package) in &[("foo", true), ("foo.bar", false), ("foobar", false)] {
+            prepared.resources.insert(
+                name.to_string(),
+                Resource {
+                    flavor: ResourceFlavor::Module,
+                    name: Cow::Owned(name.to_string()),
+                    is_package: *is_package,
+                    in_memory_source: Some(Cow::Owned(b"source".to_vec())),
+                    in_memory_bytecode: Some(Cow::Owned(b"bytecode".to_vec())),
+                    ..Resource::default()
+                },
+            );
+        }
+
+        let mut resources = HashMap::new();
+        resources.insert(
+            Cow::Owned("data.txt".to_string()),
+            Cow::Owned(b"data".to_vec()),
+        );
+        prepared
+            .resources
+            .get_mut("foo")
+            .unwrap()
+            .in_memory_package_resources = Some(resources);
+
+        let packages = BTreeSet::from_iter(vec!["foo".to_string()]);
+        let files = prepared.move_packages_to_zip_files(&packages)?;
+
+        let mut bytecode = compute_bytecode_header(42, BytecodeHeaderMode::UncheckedHash(0))?;
+        bytecode.extend_from_slice(b"bytecode");
+
+        assert_eq!(
+            files.keys().cloned().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("foo/__init__.py"),
+                PathBuf::from("foo/__init__.pyc"),
+                PathBuf::from("foo/bar.py"),
+                PathBuf::from("foo/bar.pyc"),
+                PathBuf::from("foo/data.txt"),
+            ]
+        );
+        assert_eq!(
+            files.get(Path::new("foo/bar.py")),
+            Some(&b"source".to_vec())
+        );
+        assert_eq!(files.get(Path::new("foo/bar.pyc")), Some(&bytecode));
+        assert_eq!(
+            files.get(Path::new("foo/data.txt")),
+            Some(&b"data".to_vec())
+        );
+
+        for name in &["foo", "foo.bar"] {
+            let resource = prepared.resources.get(*name).unwrap();
+            assert!(resource.in_memory_source.is_none());
+            assert!(resource.in_memory_bytecode.is_none());
+            assert!(resource.in_memory_package_resources.is_none());
+        }
+
+        let resource = prepared.resources.get("foobar").unwrap();
+        assert!(resource.in_memory_source.is_some());
+        assert!(resource.in_memory_bytecode.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_to_relative_path() -> Result<()> {
+        let mut c = PythonResourceCollector::new(
+            &PythonResourcesPolicy::PreferInMemoryFallbackFilesystemRelative("old".to_string()),
+            DEFAULT_CACHE_TAG,
+        );
+
+        c.add_in_memory_python_module_source(&PythonModuleSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(vec![42]),
+            is_package: true,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        c.add_in_memory_python_module_bytecode_from_source(&PythonModuleBytecodeFromSource {
+            name: "foo".to_string(),
+            source: DataLocation::Memory(vec![42]),
+            optimize_level: BytecodeOptimizationLevel::One,
+            is_package: true,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        c.add_in_memory_python_package_resource(&PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: "resource.txt".to_string(),
+            data: DataLocation::Memory(vec![42]),
+        })?;
+        c.add_in_memory_package_distribution_resource(&PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "foo".to_string(),
+            version: "1.0".to_string(),
+            name: "METADATA".to_string(),
+            data: DataLocation::Memory(vec![42]),
+        })?;
+        c.add_relative_path_python_extension_module(
+            &PythonExtensionModule {
+                name: "foo.bar".to_string(),
+                init_fn: None,
+                extension_file_suffix: ".so".to_string(),
+                extension_data: Some(DataLocation::Memory(vec![42])),
+                object_file_data: vec![],
+                is_package: false,
+                libraries: vec![],
+                library_dirs: vec![],
+                shared_libraries: BTreeMap::new(),
+            },
+            "old",
+        )?;
+        c.add_in_memory_shared_library("libbaz.so", &DataLocation::Memory(vec![42]))?;
+
+        c.relocate_to_relative_path("lib", "data")?;
+
+        assert_eq!(
+            c.get_policy(),
+            &PythonResourcesPolicy::FilesystemRelativeOnly("lib".to_string())
+        );
+        assert!(c.get_in_memory_module_sources().is_empty());
+        assert!(c.get_in_memory_package_resources().is_empty());
+
+        let foo = c.resources.get("foo").unwrap();
+        assert!(foo.in_memory_bytecode_opt1.is_none());
+        assert_eq!(
+            foo.relative_path_bytecode_opt1,
+            Some((
+                "lib".to_string(),
+                "cpython-37".to_string(),
+                PythonModuleBytecodeProvider::FromSource(DataLocation::Memory(vec![42]))
+            ))
+        );
+        assert_eq!(
+            foo.relative_path_bytecode_files()
+                .into_iter()
+                .map(|(optimize, path, _)| (optimize, path))
+                .collect::<Vec<_>>(),
+            vec![(
+                BytecodeOptimizationLevel::One,
+                PathBuf::from("lib/foo/__pycache__/__init__.cpython-37.opt-1.pyc")
+            )]
+        );
+
+        let mut files = c
+            .derive_file_installs()?
+            .into_iter()
+            .map(|(path, _, executable)| (path, executable))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                (PathBuf::from("data/foo/resource.txt"), false),
+                (PathBuf::from("lib/foo/__init__.py"), false),
+                (PathBuf::from("lib/foo/bar.so"), true),
+                (PathBuf::from("lib/foo-1.0.dist-info/METADATA"), false),
+                (PathBuf::from("lib/libbaz.so"), true),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_packages_to_relative_path() -> Result<()> {
+        let mut c = PythonResourceCollector::new(
+            &PythonResourcesPolicy::PreferInMemoryFallbackFilesystem("lib".to_string()),
+            DEFAULT_CACHE_TAG,
+        );
+
+        for name in &["foo", "foo.bar", "foobar"] {
+            c.add_in_memory_python_module_source(&PythonModuleSource {
+                name: name.to_string(),
+                source: DataLocation::Memory(vec![42]),
+                is_package: *name == "foo",
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            })?;
+        }
+        c.add_in_memory_python_module_bytecode_from_source(&PythonModuleBytecodeFromSource {
+            name: "foo.bar".to_string(),
+            source: DataLocation::Memory(vec![42]),
+            optimize_level: BytecodeOptimizationLevel::Two,
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+        })?;
+        c.add_in_memory_python_package_resource(&PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: "resource.txt".to_string(),
+            data: DataLocation::Memory(vec![42]),
+        })?;
+
+        c.relocate_packages_to_relative_path(
+            &vec!["foo".to_string()].into_iter().collect(),
+            "lib",
+        )?;
+
+        assert_eq!(
+            c.get_policy(),
+            &PythonResourcesPolicy::PreferInMemoryFallbackFilesystem("lib".to_string())
+        );
+        assert_eq!(
+            c.get_in_memory_module_sources()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec!["foobar".to_string()]
+        );
+
+        let mut files = c
+            .derive_file_installs()?
+            .into_iter()
+            .map(|(path, _, _)| path)
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("lib/foo/__init__.py"),
+                PathBuf::from("lib/foo/bar.py"),
+                PathBuf::from("lib/foo/resource.txt"),
+            ]
+        );
+
+        let bar = c.resources.get("foo.bar").unwrap();
+        assert_eq!(
+            bar.relative_path_bytecode_files()
+                .into_iter()
+                .map(|(optimize, path, _)| (optimize, path))
+                .collect::<Vec<_>>(),
+            vec![(
+                BytecodeOptimizationLevel::Two,
+                PathBuf::from("lib/foo/__pycache__/bar.cpython-37.opt-2.pyc")
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_filesystem_module_source_paths() -> Result<()> {
+        let mut c = PythonResourceCollector::new(
+            &PythonResourcesPolicy::FilesystemRelativeOnly("lib".to_string()),
+            DEFAULT_CACHE_TAG,
+        );
+
+        c.add_relative_path_python_module_bytecode_from_source(
+            &PythonModuleBytecodeFromSource {
+                name: "foo".to_string(),
+                source: DataLocation::Path(PathBuf::from("/src/foo/__init__.py")),
+                optimize_level: BytecodeOptimizationLevel::Zero,
+                is_package: true,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            },
+            "lib",
+        )?;
+        c.add_relative_path_python_module_source(
+            &PythonModuleSource {
+                name: "foo.bar".to_string(),
+                source: DataLocation::Path(PathBuf::from("/src/foo/bar.py")),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            },
+            "lib",
+        )?;
+        c.add_relative_path_python_module_source(
+            &PythonModuleSource {
+                name: "baz".to_string(),
+                source: DataLocation::Memory(vec![42]),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            },
+            "lib",
+        )?;
+
+        assert!(c.get_filesystem_module_source_paths().is_empty());
+
+        c.set_filesystem_module_layout(FilesystemModuleLayout::Pycache);
+
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            PathBuf::from("lib/foo/__init__.py"),
+            PathBuf::from("/src/foo/__init__.py"),
+        );
+        expected.insert(
+            PathBuf::from("lib/foo/bar.py"),
+            PathBuf::from("/src/foo/bar.py"),
+        );
+        assert_eq!(c.get_filesystem_module_source_paths(), expected);
+
+        Ok(())
+    }
 }
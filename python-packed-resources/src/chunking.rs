@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Content-defined chunking of data.
+
+Data stored in remote archives is split into chunks so that readers only
+need to fetch the parts they use. Chunk boundaries are derived from the
+content using a *gear* rolling hash: a boundary is placed wherever the hash
+of the preceding bytes matches a pattern. Inserting or removing bytes only
+moves the boundaries near the edit, so most chunks of a modified archive
+are identical to chunks of the original and can be deduplicated and cached
+across versions.
+*/
+
+use std::ops::Range;
+
+/// Controls the sizes of chunks produced by `find_chunks()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkingParameters {
+    /// Chunks are at least this many bytes, unless at the end of the data.
+    pub min_size: usize,
+
+    /// The size chunks should average.
+    ///
+    /// Rounded up to a power of 2.
+    pub avg_size: usize,
+
+    /// Chunks are at most this many bytes.
+    pub max_size: usize,
+}
+
+impl Default for ChunkingParameters {
+    fn default() -> Self {
+        Self {
+            min_size: 2048,
+            avg_size: 8192,
+            max_size: 65536,
+        }
+    }
+}
+
+/// Derive the table of random values used by the gear hash.
+///
+/// Values are derived with splitmix64 from a fixed seed, so chunk boundaries
+/// are stable across builds.
+fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x5079_4f78_6964_697a;
+
+    for value in table.iter_mut() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        *value = z ^ (z >> 31);
+    }
+
+    table
+}
+
+/// Split data into content-defined chunks.
+///
+/// Returns the ranges of `data` constituting each chunk, in order.
+pub fn find_chunks(data: &[u8], params: &ChunkingParameters) -> Vec<Range<usize>> {
+    let table = gear_table();
+
+    let min_size = params.min_size.max(1);
+    let max_size = params.max_size.max(min_size);
+    let bits = params.avg_size.max(2).next_power_of_two().trailing_zeros();
+    // A boundary is placed when the high bits of the hash are all 0. High bits
+    // depend on more of the preceding bytes than low bits.
+    let mask = !(u64::max_value() >> bits);
+
+    let mut res = Vec::new();
+    let mut start = 0;
+
+    while start < data.len() {
+        let end = (start + max_size).min(data.len());
+        let mut boundary = end;
+        let mut hash: u64 = 0;
+
+        for (i, byte) in data[start..end].iter().enumerate() {
+            hash = (hash << 1).wrapping_add(table[*byte as usize]);
+
+            if i + 1 >= min_size && hash & mask == 0 {
+                boundary = start + i + 1;
+                break;
+            }
+        }
+
+        res.push(start..boundary);
+        start = boundary;
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random data.
+    fn data(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(find_chunks(&[], &ChunkingParameters::default()).is_empty());
+    }
+
+    #[test]
+    fn test_sizes() {
+        let params = ChunkingParameters::default();
+        let data = data(1_000_000, 1);
+        let chunks = find_chunks(&data, &params);
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].start, 0);
+        assert_eq!(chunks[chunks.len() - 1].end, data.len());
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            if i > 0 {
+                assert_eq!(chunk.start, chunks[i - 1].end);
+            }
+
+            assert!(chunk.len() <= params.max_size);
+            if i + 1 < chunks.len() {
+                assert!(chunk.len() >= params.min_size);
+            }
+        }
+
+        // Identical input yields identical chunks.
+        assert_eq!(find_chunks(&data, &params), chunks);
+    }
+
+    #[test]
+    fn test_insertion_preserves_chunks() {
+        let params = ChunkingParameters::default();
+        let original = data(500_000, 2);
+
+        let mut modified = original[0..100_000].to_vec();
+        modified.extend_from_slice(b"inserted bytes");
+        modified.extend_from_slice(&original[100_000..]);
+
+        let original_chunks = find_chunks(&original, &params)
+            .into_iter()
+            .map(|r| original[r].to_vec())
+            .collect::<Vec<_>>();
+        let modified_chunks = find_chunks(&modified, &params)
+            .into_iter()
+            .map(|r| modified[r].to_vec())
+            .collect::<Vec<_>>();
+
+        let shared = modified_chunks
+            .iter()
+            .filter(|c| original_chunks.contains(c))
+            .count();
+
+        // Only chunks around the insertion should differ.
+        assert!(shared + 3 >= modified_chunks.len());
+    }
+}
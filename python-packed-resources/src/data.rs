@@ -12,6 +12,11 @@ use {
 /// Header value for version 1 of resources payload.
 pub const HEADER_V1: &[u8] = b"pyembed\x01";
 
+/// Length in bytes of digests of remote chunks.
+///
+/// Digests are SHA-256.
+pub const REMOTE_CHUNK_DIGEST_LENGTH: usize = 32;
+
 /// Defines the type of a resource.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ResourceFlavor {
@@ -147,6 +152,8 @@ pub enum ResourceField {
     RelativeFilesystemExtensionModuleSharedLibrary = 0x13,
     RelativeFilesystemPackageResources = 0x14,
     RelativeFilesystemDistributionResource = 0x15,
+    RemoteModuleBytecodeChunks = 0x16,
+    InMemoryModuleDataCompressed = 0x17,
 }
 
 impl Into<u8> for ResourceField {
@@ -174,6 +181,8 @@ impl Into<u8> for ResourceField {
             ResourceField::RelativeFilesystemExtensionModuleSharedLibrary => 0x13,
             ResourceField::RelativeFilesystemPackageResources => 0x14,
             ResourceField::RelativeFilesystemDistributionResource => 0x15,
+            ResourceField::RemoteModuleBytecodeChunks => 0x16,
+            ResourceField::InMemoryModuleDataCompressed => 0x17,
             ResourceField::EndOfEntry => 0xff,
         }
     }
@@ -206,12 +215,37 @@ impl TryFrom<u8> for ResourceField {
             0x13 => Ok(ResourceField::RelativeFilesystemExtensionModuleSharedLibrary),
             0x14 => Ok(ResourceField::RelativeFilesystemPackageResources),
             0x15 => Ok(ResourceField::RelativeFilesystemDistributionResource),
+            0x16 => Ok(ResourceField::RemoteModuleBytecodeChunks),
+            0x17 => Ok(ResourceField::InMemoryModuleDataCompressed),
             0xff => Ok(ResourceField::EndOfEntry),
             _ => Err("invalid field type"),
         }
     }
 }
 
+/// Describes a chunk of data stored in a remote archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteChunk<'a> {
+    /// Offset of the chunk in the remote archive.
+    pub offset: u64,
+
+    /// Length in bytes of the chunk.
+    pub length: u32,
+
+    /// SHA-256 digest of the chunk's content.
+    pub digest: Cow<'a, [u8]>,
+}
+
+impl<'a> RemoteChunk<'a> {
+    pub fn to_owned(&self) -> RemoteChunk<'static> {
+        RemoteChunk {
+            offset: self.offset,
+            length: self.length,
+            digest: Cow::Owned(self.digest.clone().into_owned()),
+        }
+    }
+}
+
 /// Represents an embedded resource and all its metadata.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Resource<'a, X: 'a>
@@ -282,6 +316,14 @@ where
 
     /// Mapping of Python package distribution files to relative filesystem paths for those resources.
     pub relative_path_distribution_resources: Option<HashMap<Cow<'a, str>, Cow<'a, Path>>>,
+
+    /// Chunks of a remote archive holding Python module bytecode.
+    ///
+    /// The bytecode is the concatenation of the chunks, in order.
+    pub remote_module_bytecode_chunks: Option<Vec<RemoteChunk<'a>>>,
+
+    /// Whether in-memory Python module source and bytecode are zstd compressed.
+    pub in_memory_module_data_compressed: bool,
 }
 
 impl<'a, X> Default for Resource<'a, X>
@@ -310,6 +352,8 @@ where
             relative_path_extension_module_shared_library: None,
             relative_path_package_resources: None,
             relative_path_distribution_resources: None,
+            remote_module_bytecode_chunks: None,
+            in_memory_module_data_compressed: false,
         }
     }
 }
@@ -419,6 +463,11 @@ where
                         )
                     }))
                 }),
+            remote_module_bytecode_chunks: self
+                .remote_module_bytecode_chunks
+                .as_ref()
+                .map(|value| value.iter().map(|chunk| chunk.to_owned()).collect()),
+            in_memory_module_data_compressed: self.in_memory_module_data_compressed,
         }
     }
 }
@@ -24,8 +24,10 @@ This crate is developed primarily for
 be used outside the PyOxidizer project.
 */
 
+pub mod chunking;
 pub mod data;
 #[allow(unused)]
 pub mod parser;
+pub mod signing;
 pub mod specifications;
 pub mod writer;
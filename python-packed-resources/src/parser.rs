@@ -6,7 +6,8 @@
 
 use {
     super::data::{
-        BlobInteriorPadding, BlobSectionField, Resource, ResourceField, ResourceFlavor, HEADER_V1,
+        BlobInteriorPadding, BlobSectionField, RemoteChunk, Resource, ResourceField,
+        ResourceFlavor, HEADER_V1, REMOTE_CHUNK_DIGEST_LENGTH,
     },
     byteorder::{LittleEndian, ReadBytesExt},
     std::borrow::Cow,
@@ -439,6 +440,41 @@ impl<'a> ResourceParserIterator<'a> {
 
                     current_resource.relative_path_distribution_resources = Some(resources);
                 }
+
+                ResourceField::RemoteModuleBytecodeChunks => {
+                    let chunk_count = self
+                        .reader
+                        .read_u32::<LittleEndian>()
+                        .or_else(|_| Err("failed reading remote chunk count"))?
+                        as usize;
+
+                    let mut chunks = Vec::new();
+
+                    for _ in 0..chunk_count {
+                        let offset = self
+                            .reader
+                            .read_u64::<LittleEndian>()
+                            .or_else(|_| Err("failed reading remote chunk offset"))?;
+                        let length = self
+                            .reader
+                            .read_u32::<LittleEndian>()
+                            .or_else(|_| Err("failed reading remote chunk length"))?;
+
+                        let digest = self.resolve_blob_data(field_type, REMOTE_CHUNK_DIGEST_LENGTH);
+
+                        chunks.push(RemoteChunk {
+                            offset,
+                            length,
+                            digest: Cow::Borrowed(digest),
+                        });
+                    }
+
+                    current_resource.remote_module_bytecode_chunks = Some(chunks);
+                }
+
+                ResourceField::InMemoryModuleDataCompressed => {
+                    current_resource.in_memory_module_data_compressed = true;
+                }
             }
         }
     }
@@ -1293,6 +1329,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_remote_module_bytecode_chunks() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            remote_module_bytecode_chunks: Some(vec![RemoteChunk {
+                offset: 1024,
+                length: 512,
+                digest: Cow::from(vec![42; REMOTE_CHUNK_DIGEST_LENGTH]),
+            }]),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v1(&[resource], &mut data, None).unwrap();
+        let resources = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources.len(), 1);
+
+        let entry = &resources[0];
+
+        assert_eq!(
+            entry.remote_module_bytecode_chunks,
+            Some(vec![RemoteChunk {
+                offset: 1024,
+                length: 512,
+                digest: Cow::Borrowed(&[42; REMOTE_CHUNK_DIGEST_LENGTH][..]),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_in_memory_module_data_compressed() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_bytecode: Some(Cow::from(b"compressed".to_vec())),
+            in_memory_module_data_compressed: true,
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v1(&[resource], &mut data, None).unwrap();
+        let resources = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources.len(), 1);
+
+        let entry = &resources[0];
+
+        assert!(entry.in_memory_module_data_compressed);
+        assert_eq!(
+            entry.in_memory_bytecode.as_ref().unwrap().as_ref(),
+            b"compressed"
+        );
+    }
+
+    #[test]
+    fn test_remote_module_bytecode_chunks_bad_digest() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            remote_module_bytecode_chunks: Some(vec![RemoteChunk {
+                offset: 0,
+                length: 1,
+                digest: Cow::from(vec![0; 4]),
+            }]),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        assert!(write_packed_resources_v1(&[resource], &mut data, None).is_err());
+    }
+
     #[allow(clippy::cognitive_complexity)]
     #[test]
     fn test_all_fields() {
@@ -1345,6 +1457,19 @@ mod tests {
             relative_path_extension_module_shared_library: Some(Cow::from(Path::new("em_path"))),
             relative_path_package_resources: Some(relative_path_resources),
             relative_path_distribution_resources: Some(relative_path_distribution),
+            remote_module_bytecode_chunks: Some(vec![
+                RemoteChunk {
+                    offset: 0,
+                    length: 42,
+                    digest: Cow::from(vec![1; REMOTE_CHUNK_DIGEST_LENGTH]),
+                },
+                RemoteChunk {
+                    offset: 42,
+                    length: 10,
+                    digest: Cow::from(vec![2; REMOTE_CHUNK_DIGEST_LENGTH]),
+                },
+            ]),
+            in_memory_module_data_compressed: true,
         };
 
         let mut data = Vec::new();
@@ -1445,6 +1570,23 @@ mod tests {
             distribution.get("resource.txt"),
             Some(&Cow::Borrowed(Path::new("package/resource.txt")))
         );
+
+        let chunks = entry.remote_module_bytecode_chunks.as_ref().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].offset, 0);
+        assert_eq!(chunks[0].length, 42);
+        assert_eq!(
+            chunks[0].digest.as_ref(),
+            &[1; REMOTE_CHUNK_DIGEST_LENGTH][..]
+        );
+        assert_eq!(chunks[1].offset, 42);
+        assert_eq!(chunks[1].length, 10);
+        assert_eq!(
+            chunks[1].digest.as_ref(),
+            &[2; REMOTE_CHUNK_DIGEST_LENGTH][..]
+        );
+
+        assert!(entry.in_memory_module_data_compressed);
     }
 
     #[test]
@@ -0,0 +1,53 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Signatures of resource files.
+
+Resource files installed next to a binary can be signed at build time. Each
+file has a companion file holding the signature of its path relative to the
+resources origin and its content. This module defines what is signed, so
+producers and consumers of signatures agree on it.
+*/
+
+use std::path::Path;
+
+/// Suffix appended to the path of a file to obtain the path of its signature.
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Obtain the message signed for a file.
+///
+/// This is the file's path relative to the resources origin, with `/`
+/// separators, a NUL byte and the file content. Including the path means a
+/// signed file can't be swapped for another signed file.
+pub fn signed_message(relative_path: &Path, data: &[u8]) -> Vec<u8> {
+    let path = relative_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let mut res = Vec::with_capacity(path.len() + 1 + data.len());
+    res.extend_from_slice(path.as_bytes());
+    res.push(0);
+    res.extend_from_slice(data);
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_message() {
+        assert_eq!(
+            signed_message(Path::new("lib/foo.py"), b"data"),
+            b"lib/foo.py\0data".to_vec()
+        );
+        assert_eq!(
+            signed_message(&Path::new("lib").join("foo.py"), b"data"),
+            b"lib/foo.py\0data".to_vec()
+        );
+    }
+}
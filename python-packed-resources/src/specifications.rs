@@ -226,6 +226,19 @@ The number of files being described is contained in a `u32` that immediately
 follows this byte. Following this `u32` is an array of `(u16, u32)` denoting
 the distribution file name and filesystem path to that distribution file.
 
+`0x16` - Remote Python module bytecode chunks. The module's bytecode is not
+present in the data. Instead, it is the concatenation of chunks of an archive
+stored elsewhere (e.g. on an HTTP server). The number of chunks is contained in
+a `u32` that immediately follows this byte. Following this `u32` is an array of
+`(u64, u32)` denoting the offset and length in bytes of each chunk in the
+archive. The blob section for this field holds the 32 byte SHA-256 digest of
+each chunk. Readers must verify fetched chunks against their digest.
+
+`0x17` - In-memory Python module data is compressed. If present, the data of
+the in-memory module source (`0x06`) and bytecode (`0x07`, `0x08`, `0x09`)
+fields is a zstd frame holding the original data. Readers should only
+decompress data when it is needed.
+
 ## Resource Flavors
 
 The data format allows defining different types/flavors of resources.
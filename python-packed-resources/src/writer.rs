@@ -5,7 +5,10 @@
 /*! Serializing of structures into packed resources blobs. */
 
 use {
-    super::data::{BlobInteriorPadding, BlobSectionField, Resource, ResourceField, HEADER_V1},
+    super::data::{
+        BlobInteriorPadding, BlobSectionField, Resource, ResourceField, HEADER_V1,
+        REMOTE_CHUNK_DIGEST_LENGTH,
+    },
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, WriteBytesExt},
     std::collections::BTreeMap,
@@ -130,6 +133,7 @@ where
             || self.relative_path_extension_module_shared_library.is_some()
             || self.relative_path_package_resources.is_some()
             || self.relative_path_distribution_resources.is_some()
+            || self.remote_module_bytecode_chunks.is_some()
     }
 
     /// Compute length of index entry for version 1 payload format.
@@ -151,6 +155,10 @@ where
             index += 1;
         }
 
+        if self.in_memory_module_data_compressed {
+            index += 1;
+        }
+
         if self.in_memory_source.is_some() {
             index += 5;
         }
@@ -225,6 +233,13 @@ where
             index += 6 * metadata.len();
         }
 
+        if let Some(chunks) = &self.remote_module_bytecode_chunks {
+            index += 5;
+
+            // u64 + u32 for chunk offset and length.
+            index += 12 * chunks.len();
+        }
+
         // End of index entry.
         index += 1;
 
@@ -243,6 +258,7 @@ where
             ResourceField::ModuleName => self.name.as_bytes().len(),
             ResourceField::IsPackage => 0,
             ResourceField::IsNamespacePackage => 0,
+            ResourceField::InMemoryModuleDataCompressed => 0,
             ResourceField::InMemorySource => {
                 if let Some(source) = &self.in_memory_source {
                     source.len()
@@ -367,6 +383,13 @@ where
                     0
                 }
             }
+            ResourceField::RemoteModuleBytecodeChunks => {
+                if let Some(chunks) = &self.remote_module_bytecode_chunks {
+                    chunks.len() * REMOTE_CHUNK_DIGEST_LENGTH
+                } else {
+                    0
+                }
+            }
         }
     }
 
@@ -384,6 +407,7 @@ where
             ResourceField::ModuleName => 1,
             ResourceField::IsPackage => 0,
             ResourceField::IsNamespacePackage => 0,
+            ResourceField::InMemoryModuleDataCompressed => 0,
             ResourceField::InMemorySource => {
                 if self.in_memory_source.is_some() {
                     1
@@ -496,6 +520,13 @@ where
                     0
                 }
             }
+            ResourceField::RemoteModuleBytecodeChunks => {
+                if let Some(chunks) = &self.remote_module_bytecode_chunks {
+                    chunks.len()
+                } else {
+                    0
+                }
+            }
         };
 
         let overhead = match padding {
@@ -535,6 +566,11 @@ where
                 .context("writing is_namespace field")?;
         }
 
+        if self.in_memory_module_data_compressed {
+            dest.write_u8(ResourceField::InMemoryModuleDataCompressed.into())
+                .context("writing in_memory_module_data_compressed field")?;
+        }
+
         if let Some(source) = &self.in_memory_source {
             let l =
                 u32::try_from(source.len()).context("converting in-memory source length to u32")?;
@@ -726,6 +762,29 @@ where
             }
         }
 
+        if let Some(chunks) = &self.remote_module_bytecode_chunks {
+            let l = u32::try_from(chunks.len())
+                .context("converting remote module bytecode chunks length to u32")?;
+            dest.write_u8(ResourceField::RemoteModuleBytecodeChunks.into())
+                .context("writing remote module bytecode chunks field")?;
+            dest.write_u32::<LittleEndian>(l)
+                .context("writing remote module bytecode chunks length")?;
+
+            for chunk in chunks {
+                if chunk.digest.len() != REMOTE_CHUNK_DIGEST_LENGTH {
+                    return Err(anyhow!(
+                        "remote chunk digest must be {} bytes",
+                        REMOTE_CHUNK_DIGEST_LENGTH
+                    ));
+                }
+
+                dest.write_u64::<LittleEndian>(chunk.offset)
+                    .context("writing remote chunk offset")?;
+                dest.write_u32::<LittleEndian>(chunk.length)
+                    .context("writing remote chunk length")?;
+            }
+        }
+
         dest.write_u8(ResourceField::EndOfEntry.into())
             .or_else(|_| Err(anyhow!("error writing end of index entry")))?;
 
@@ -858,6 +917,11 @@ pub fn write_packed_resources_v1<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
             module,
             ResourceField::RelativeFilesystemDistributionResource,
         );
+        process_field(
+            &mut blob_sections,
+            module,
+            ResourceField::RemoteModuleBytecodeChunks,
+        );
     }
 
     for section in blob_sections.values() {
@@ -1023,6 +1087,15 @@ pub fn write_packed_resources_v1<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         }
     }
 
+    for module in modules {
+        if let Some(chunks) = &module.as_ref().remote_module_bytecode_chunks {
+            for chunk in chunks {
+                dest.write_all(&chunk.digest)?;
+                add_interior_padding(dest)?;
+            }
+        }
+    }
+
     Ok(())
 }
 